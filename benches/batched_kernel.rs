@@ -0,0 +1,86 @@
+use clap::Parser;
+use gmrs::core::FactorGraph;
+use gmrs::ising::ensembles::sherrington_kirkpatrick;
+use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+use gmrs::ising::{random_message_initializer, IsingFactor, IsingVariable, SumProduct};
+use rand::{rngs::StdRng, SeedableRng};
+use std::time::{Duration, Instant};
+
+type Graph = FactorGraph<IsingFactor<SumProduct>, IsingVariable<SumProduct>>;
+
+// Compares wall time of the lane-batched kernel with the one of
+// the node-wise message passing on a Sherrington-Kirkpatrick graph,
+// run by `cargo bench --bench batched_kernel -- --spins-number 1000`
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    /// Number of spins in the SK system
+    #[arg(short, long, default_value = "500")]
+    spins_number: usize,
+
+    /// Number of message passing iterations per run
+    #[arg(short, long, default_value = "20")]
+    iterations: usize,
+
+    /// Number of timed runs of each kernel
+    #[arg(short, long, default_value = "5")]
+    runs: usize,
+}
+
+// Returns the minimal time of a number of runs, each run starts from a copy of a factor graph
+fn min_time(runs: usize, fg: &Graph, mut run: impl FnMut(&mut Graph)) -> Duration {
+    (0..runs)
+        .map(|_| {
+            let mut fg = fg.clone();
+            let start = Instant::now();
+            run(&mut fg);
+            start.elapsed()
+        })
+        .min()
+        .expect("At least one run")
+}
+
+fn main() {
+    // `cargo bench` passes `--bench` to a benchmark without a harness
+    let cli = Cli::parse_from(std::env::args().filter(|arg| arg != "--bench"));
+    let mut rng = StdRng::seed_from_u64(42);
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(7), -0.5, 0.5);
+    let (fgb, _) =
+        sherrington_kirkpatrick::<SumProduct>(cli.spins_number, &mut rng, &mut initializer)
+            .unwrap();
+    let fg = fgb.build();
+    let factor_scheduler = get_standard_factor_scheduler(0.5);
+    let variable_scheduler = get_standard_variable_scheduler(0.5);
+    // a zero threshold is never reached, thus all runs make the same number of iterations
+    let batched = min_time(cli.runs, &fg, |fg| {
+        let _ = fg.run_message_passing_batched(
+            cli.iterations,
+            0,
+            0f64,
+            &factor_scheduler,
+            &variable_scheduler,
+        );
+    });
+    let parallel = min_time(cli.runs, &fg, |fg| {
+        let _ = fg.run_message_passing_parallel(
+            cli.iterations,
+            0,
+            0f64,
+            &factor_scheduler,
+            &variable_scheduler,
+        );
+    });
+    let couplings_number = cli.spins_number * (cli.spins_number - 1) / 2;
+    println!(
+        "{} spins, {couplings_number} couplings, {} iterations, {} threads available",
+        cli.spins_number,
+        cli.iterations,
+        std::thread::available_parallelism().map_or(1, |threads| threads.get()),
+    );
+    println!("run_message_passing_batched:  {batched:?}");
+    println!("run_message_passing_parallel: {parallel:?}");
+    println!(
+        "speedup: {:.2}",
+        parallel.as_secs_f64() / batched.as_secs_f64()
+    );
+}
@@ -1,12 +1,10 @@
 use clap::Parser;
 use gmrs::{
-    core::FGError,
     ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler},
     ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct},
 };
 use rand::thread_rng;
 use rand_distr::{Distribution, Normal};
-use serde::{Deserialize, Serialize};
 
 // This part is for parsing the parameters from command line,
 // can be safely skipped, does not affect the understanding
@@ -35,18 +33,6 @@ struct Cli {
     decay: f64,
 }
 
-// This part if for serialization of the output result into
-// a yaml config. Can be safely skipped. Does not affect the
-// understanding of the example
-#[derive(Serialize, Deserialize)]
-struct ExampleResult {
-    is_converged: bool,
-    iterations_number: usize,
-    discrepancy: f64,
-    bethe_free_entropy: f64,
-    replica_symmetric_free_entropy: f64,
-}
-
 /// Returns a replica symmetric free entropy
 /// (valid for high temperature, beta < 1)
 fn rs_sk_free_entropy(beta: f64) -> f64 {
@@ -85,19 +71,18 @@ fn main() {
 
     let mut fgb =
         new_ising_builder::<SumProduct>(spins_number, (spins_number - 1) * spins_number / 2);
-    for i in 0..spins_number {
-        for j in (i + 1)..spins_number {
-            fgb.add_factor(
-                IsingFactor::new(distr.sample(&mut rng_couplings), 0f64, 0f64),
-                &[i, j],
-                &mut initializer,
-            )
-            .unwrap();
-        }
-    }
+    let pairs = (0..spins_number).flat_map(|i| ((i + 1)..spins_number).map(move |j| [i, j]));
+    fgb.add_factors(
+        pairs.map(|pair| {
+            let coupling = distr.sample(&mut rng_couplings);
+            (IsingFactor::new(coupling, 0f64, 0f64), pair)
+        }),
+        &mut initializer,
+    )
+    .unwrap();
     let mut fg = fgb.build();
-    let info =
-        fg.run_message_passing_parallel(max_iter, 0, error, &factor_scheduler, &variable_scheduler);
+    let report =
+        fg.run_message_passing_report(max_iter, 0, error, &factor_scheduler, &variable_scheduler);
     let variable_marginals = fg.variable_marginals();
     let factors = fg.factors();
     let factor_marginals = fg.factor_marginals();
@@ -110,27 +95,9 @@ fn main() {
     }
     bethe_free_entropy /= spins_number as f64;
     let replica_symmetric_free_entropy = rs_sk_free_entropy(beta);
-    let (is_converged, iterations_number, discrepancy) = match info {
-        Ok(info) => (true, info.iterations_number, info.last_discrepancy),
-        Err(err) => {
-            if let FGError::MessagePassingError {
-                iterations_number,
-                last_discrepancy,
-                ..
-            } = err
-            {
-                (false, iterations_number, last_discrepancy)
-            } else {
-                unreachable!()
-            }
-        }
-    };
-    let example_result = ExampleResult {
-        is_converged,
-        iterations_number,
-        discrepancy,
-        bethe_free_entropy,
-        replica_symmetric_free_entropy,
-    };
-    println!("{}", serde_yaml::to_string(&example_result).unwrap());
+    // the report is serialized into a yaml config
+    let report = report
+        .with_free_entropy("bethe", bethe_free_entropy)
+        .with_free_entropy("replica_symmetric", replica_symmetric_free_entropy);
+    println!("{}", serde_yaml::to_string(&report).unwrap());
 }
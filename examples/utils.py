@@ -37,7 +37,7 @@ def run_sk(
         result_dict["is_converged"],
         result_dict["iterations_number"],
         result_dict["discrepancy"],
-        result_dict["bethe_free_entropy"],
-        result_dict["replica_symmetric_free_entropy"],
+        result_dict["free_entropies"]["bethe"],
+        result_dict["free_entropies"]["replica_symmetric"],
     )
     return result
\ No newline at end of file
@@ -0,0 +1,68 @@
+"""Smoke tests of the Python bindings, run by `pytest` after `maturin develop`."""
+
+import itertools
+import math
+
+import gmrs
+import numpy as np
+
+
+def exact_marginals(spins_number, couplings, fields):
+    weights = [0.0] * spins_number
+    partition_function = 0.0
+    for spins in itertools.product([1, -1], repeat=spins_number):
+        energy = sum(j * spins[lhs] * spins[rhs] for lhs, rhs, j in couplings)
+        energy += sum(b * s for b, s in zip(fields, spins))
+        weight = math.exp(energy)
+        partition_function += weight
+        for i, spin in enumerate(spins):
+            if spin == 1:
+                weights[i] += weight
+    return [weight / partition_function for weight in weights]
+
+
+def test_chain_marginals():
+    couplings = [(0, 1, 0.5), (1, 2, -0.5)]
+    fields = [0.2, 0.0, -0.1]
+    model = gmrs.IsingModel(3, seed=42)
+    for lhs, rhs, coupling in couplings:
+        model.add_coupling(lhs, rhs, coupling)
+    for spin, field in enumerate(fields):
+        model.set_field(spin, field)
+    model.run_message_passing(max_iterations_number=1000, threshold=1e-10)
+    marginals = model.marginals()
+    assert isinstance(marginals, np.ndarray)
+    assert marginals.shape == (3, 2)
+    for (p_up, p_down), exact in zip(marginals, exact_marginals(3, couplings, fields)):
+        assert abs(p_up + p_down - 1.0) < 1e-10
+        assert abs(p_up - exact) < 1e-8
+    magnetizations = model.magnetizations()
+    assert magnetizations.shape == (3,)
+    assert abs(magnetizations[0] - (2.0 * marginals[0, 0] - 1.0)) < 1e-10
+
+
+def test_sampling_and_errors():
+    model = gmrs.IsingModel(4, seed=7)
+    model.add_coupling(0, 1, 1.0)
+    model.add_coupling(2, 3, -1.0)
+    samples = model.sample(10)
+    assert isinstance(samples, np.ndarray)
+    assert samples.shape == (10, 4)
+    assert set(samples.flatten().tolist()) <= {1, -1}
+    try:
+        model.add_coupling(0, 2, 1.0)
+    except RuntimeError:
+        pass
+    else:
+        raise AssertionError("a built model must not be modified")
+    try:
+        gmrs.IsingModel(2).set_field(2, 1.0)
+    except IndexError:
+        pass
+    else:
+        raise AssertionError("a spin out of range must be rejected")
+
+
+if __name__ == "__main__":
+    test_chain_marginals()
+    test_sampling_and_errors()
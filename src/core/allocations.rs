@@ -0,0 +1,95 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+thread_local! {
+    static ALLOCATIONS_NUMBER: Cell<usize> = const { Cell::new(0) };
+    static AUDITED: Cell<bool> = const { Cell::new(false) };
+}
+
+#[inline(always)]
+fn count_allocation() {
+    // the counter is not available while a thread is being destroyed
+    let _ = ALLOCATIONS_NUMBER.try_with(|number| number.set(number.get() + 1));
+}
+
+/// A global allocator on top of the system one that counts heap allocations
+/// made by each thread. When it is installed, debug builds verify that
+/// serial message passing run within `AllocationCounter::audit` does not
+/// allocate after the first iteration
+///
+/// # Example
+///
+/// ```
+/// use gmrs::core::AllocationCounter;
+///
+/// #[global_allocator]
+/// static GLOBAL: AllocationCounter = AllocationCounter;
+///
+/// let allocations_number = AllocationCounter::allocations_number();
+/// let buffer = vec![0u8; 16];
+/// assert_eq!(AllocationCounter::allocations_number(), allocations_number + 1);
+/// # drop(buffer);
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AllocationCounter;
+
+impl AllocationCounter {
+    /// Returns the number of heap allocations made by the current thread,
+    /// it is always zero if the counter is not installed as a global allocator
+    #[inline]
+    pub fn allocations_number() -> usize {
+        ALLOCATIONS_NUMBER.try_with(Cell::get).unwrap_or(0)
+    }
+
+    /// Runs `f` verifying in debug builds that message passing sweeps run by
+    /// the current thread do not allocate after the first iteration, it requires
+    /// factors and variables that do not allocate while sending messages
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - A function running message passing
+    ///
+    /// # Notes
+    ///
+    /// Message passing panics on an allocation in a sweep. Only serial
+    /// sweeps are verified, parallel ones rely on rayon that allocates internally
+    #[inline]
+    pub fn audit<R>(f: impl FnOnce() -> R) -> R {
+        let audited = AUDITED.with(|audited| audited.replace(true));
+        let result = f();
+        AUDITED.with(|flag| flag.set(audited));
+        result
+    }
+
+    // Returns true if allocations of the current thread are being audited
+    #[cfg(all(debug_assertions, not(feature = "parallel")))]
+    #[inline(always)]
+    pub(crate) fn is_audited() -> bool {
+        AUDITED.with(Cell::get)
+    }
+}
+
+unsafe impl GlobalAlloc for AllocationCounter {
+    #[inline]
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count_allocation();
+        System.alloc(layout)
+    }
+
+    #[inline]
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        count_allocation();
+        System.alloc_zeroed(layout)
+    }
+
+    #[inline]
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count_allocation();
+        System.realloc(ptr, layout, new_size)
+    }
+
+    #[inline]
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
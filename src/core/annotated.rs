@@ -0,0 +1,288 @@
+use rand::Rng;
+
+use crate::core::{
+    conditional::ConditionalFactor,
+    factor::Factor,
+    factor_graph::{FGError, FGResult, FactorGraph},
+    variable::{DecodableVariable, Variable},
+};
+
+// ------------------------------------------------------------------------------------------
+
+/// A factor or a variable carrying arbitrary user data, e.g. lattice coordinates,
+/// clause IDs or gene names. It implements `Factor` or `Variable` by delegating
+/// all methods to the wrapped node, thus data is cloned together with a factor
+/// graph and is accessible in callbacks via `get_factor` and `get_variable`
+///
+/// # Notes
+///
+/// Factors created from messages, e.g. by freezing a variable, carry default data
+///
+/// # Example
+///
+/// ```
+/// use gmrs::core::{Annotated, FactorGraphBuilder};
+/// use gmrs::ising::{IsingFactor, IsingMessage, IsingVariable, SumProduct};
+///
+/// let side = 3;
+/// let mut fgb = FactorGraphBuilder::new_with_variables(
+///     (0..side * side).map(|i| Annotated::new(IsingVariable::<SumProduct>::new(), (i / side, i % side))),
+///     2 * side * (side - 1),
+/// );
+/// for i in 0..side {
+///     for j in 0..(side - 1) {
+///         let bond = Annotated::new(IsingFactor::<SumProduct>::new(0.5, 0., 0.), "horizontal");
+///         fgb.add_factor(bond, &[i * side + j, i * side + j + 1], &mut || IsingMessage(0.)).unwrap();
+///         let bond = Annotated::new(IsingFactor::<SumProduct>::new(0.5, 0., 0.), "vertical");
+///         fgb.add_factor(bond, &[j * side + i, (j + 1) * side + i], &mut || IsingMessage(0.)).unwrap();
+///     }
+/// }
+/// let fg = fgb.build();
+/// assert_eq!(fg.get_variable(5).data, (1, 2));
+/// assert_eq!(fg.get_factor(1).data, "vertical");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotated<X, T> {
+    /// A wrapped factor or variable
+    pub node: X,
+    /// User data
+    pub data: T,
+}
+
+impl<X, T> Annotated<X, T> {
+    /// Creates a new factor or variable carrying user data
+    ///
+    /// # Arguments
+    ///
+    /// * `node` - A factor or a variable
+    /// * `data` - User data
+    #[inline]
+    pub fn new(node: X, data: T) -> Self {
+        Annotated { node, data }
+    }
+}
+
+impl<X, T> Factor for Annotated<X, T>
+where
+    X: Factor,
+    T: Clone + std::fmt::Debug + Send + Default,
+{
+    type Message = X::Message;
+    type Parameters = X::Parameters;
+    type Marginal = X::Marginal;
+
+    #[inline(always)]
+    fn from_message(message: &Self::Message) -> Self {
+        Annotated::new(X::from_message(message), T::default())
+    }
+
+    #[inline(always)]
+    fn degree(&self) -> usize {
+        self.node.degree()
+    }
+
+    #[inline(always)]
+    fn send_messages(
+        &self,
+        src: &[Self::Message],
+        dst: &mut [Self::Message],
+        parameters: &Self::Parameters,
+    ) {
+        self.node.send_messages(src, dst, parameters)
+    }
+
+    #[inline(always)]
+    fn send_messages_fixed<const N: usize>(
+        &self,
+        src: &[Self::Message; N],
+        dst: &mut [Self::Message; N],
+        parameters: &Self::Parameters,
+    ) {
+        self.node.send_messages_fixed(src, dst, parameters)
+    }
+
+    #[inline(always)]
+    fn marginal(&self, messages: &[Self::Message]) -> Self::Marginal {
+        self.node.marginal(messages)
+    }
+
+    #[inline(always)]
+    fn factor(&self) -> Self::Marginal {
+        self.node.factor()
+    }
+
+    #[inline(always)]
+    fn rescaled(&self, target: &Self::Marginal, current: &Self::Marginal) -> Option<Self> {
+        let node = self.node.rescaled(target, current)?;
+        Some(Annotated::new(node, self.data.clone()))
+    }
+
+    #[inline(always)]
+    fn is_contradictory(&self, messages: &[Self::Message]) -> bool {
+        self.node.is_contradictory(messages)
+    }
+}
+
+impl<X, T, S> ConditionalFactor<S> for Annotated<X, T>
+where
+    X: ConditionalFactor<S>,
+    T: Clone + std::fmt::Debug + Send + Default,
+{
+    #[inline(always)]
+    fn child_position(&self) -> usize {
+        self.node.child_position()
+    }
+
+    #[inline(always)]
+    fn normalization_error(&self) -> f64 {
+        self.node.normalization_error()
+    }
+
+    #[inline(always)]
+    fn sample_child(&self, parents: &[S], rng: &mut impl Rng) -> S {
+        self.node.sample_child(parents, rng)
+    }
+}
+
+impl<X, T> Variable for Annotated<X, T>
+where
+    X: Variable,
+    T: Clone + std::fmt::Debug + Send,
+{
+    type Message = X::Message;
+    type Parameters = X::Parameters;
+    type Marginal = X::Marginal;
+    type Sample = X::Sample;
+
+    #[inline(always)]
+    fn send_messages(
+        &self,
+        src: &[Self::Message],
+        dst: &mut [Self::Message],
+        parameters: &Self::Parameters,
+    ) {
+        self.node.send_messages(src, dst, parameters)
+    }
+
+    #[inline(always)]
+    fn marginal(&self, messages: &[Self::Message]) -> Self::Marginal {
+        self.node.marginal(messages)
+    }
+
+    #[inline(always)]
+    fn sample(&self, messages: &[Self::Message], rng: &mut impl Rng) -> Self::Sample {
+        self.node.sample(messages, rng)
+    }
+
+    #[inline(always)]
+    fn pinned(&self, messages: &[Self::Message], tolerance: f64) -> Option<Self::Sample> {
+        self.node.pinned(messages, tolerance)
+    }
+
+    #[inline(always)]
+    fn marginal_gap(&self, messages: &[Self::Message]) -> Option<f64> {
+        self.node.marginal_gap(messages)
+    }
+
+    #[inline(always)]
+    fn sample_to_message(sample: &Self::Sample) -> Self::Message {
+        X::sample_to_message(sample)
+    }
+
+    #[inline(always)]
+    fn sample_to_soft_message(
+        &self,
+        sample: &Self::Sample,
+        probability: f64,
+    ) -> Option<Self::Message> {
+        self.node.sample_to_soft_message(sample, probability)
+    }
+
+    #[inline(always)]
+    fn marginal_to_message(
+        &self,
+        marginal: &Self::Marginal,
+        messages: &[Self::Message],
+    ) -> Option<Self::Message> {
+        self.node.marginal_to_message(marginal, messages)
+    }
+
+    #[inline(always)]
+    fn is_valid_message(&self, message: &Self::Message) -> bool {
+        self.node.is_valid_message(message)
+    }
+
+    #[inline(always)]
+    fn sample_index(&self, sample: &Self::Sample) -> Option<usize> {
+        self.node.sample_index(sample)
+    }
+
+    #[inline(always)]
+    fn marginal_discrepancy(
+        &self,
+        marginal: &Self::Marginal,
+        other: &Self::Marginal,
+    ) -> Option<f64> {
+        self.node.marginal_discrepancy(marginal, other)
+    }
+
+    #[inline(always)]
+    fn marginal_values(&self, marginal: &Self::Marginal) -> Option<Vec<f64>> {
+        self.node.marginal_values(marginal)
+    }
+}
+
+impl<X, T> DecodableVariable for Annotated<X, T>
+where
+    X: DecodableVariable,
+    T: Clone + std::fmt::Debug + Send,
+{
+    #[inline(always)]
+    fn argmax(&self, messages: &[Self::Message]) -> Self::Sample {
+        self.node.argmax(messages)
+    }
+}
+
+// ------------------------------------------------------------------------------------------
+
+impl<X, T, V> FactorGraph<Annotated<X, T>, V>
+where
+    X: Factor,
+    T: Clone + std::fmt::Debug + Send + Default,
+    V: Variable<Message = X::Message>,
+{
+    /// Returns a mutable reference to user data of a factor,
+    /// changing data does not affect message passing
+    ///
+    /// # Arguments
+    ///
+    /// * `fac_index` - An index of a factor
+    pub fn factor_data_mut(&mut self, fac_index: usize) -> FGResult<&mut T> {
+        let factors_number = self.factors.len();
+        self.factors
+            .get_mut(fac_index)
+            .map(|factor| &mut factor.factor.data)
+            .ok_or(FGError::OutOfRangeFactor(factors_number, fac_index))
+    }
+}
+
+impl<F, X, T> FactorGraph<F, Annotated<X, T>>
+where
+    F: Factor<Message = X::Message>,
+    X: Variable,
+    T: Clone + std::fmt::Debug + Send,
+{
+    /// Returns a mutable reference to user data of a variable,
+    /// changing data does not affect message passing
+    ///
+    /// # Arguments
+    ///
+    /// * `var_index` - An index of a variable
+    pub fn variable_data_mut(&mut self, var_index: usize) -> FGResult<&mut T> {
+        let variables_number = self.variables.len();
+        self.variables
+            .get_mut(var_index)
+            .map(|variable| &mut variable.variable.data)
+            .ok_or(FGError::OutOfRangeVariable(variables_number, var_index))
+    }
+}
@@ -0,0 +1,208 @@
+use std::{
+    cmp::Reverse,
+    ops::ControlFlow,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::core::{
+    factor::Factor,
+    factor_graph::{FGResult, FactorGraph, MessagePassingInfo},
+    factor_node::FactorNode,
+    message::BoundedMessage,
+    variable::Variable,
+    variable_node::VariableNode,
+};
+
+// ------------------------------------------------------------------------------------------
+
+/// Numbers of messages that have left configurable bounds per node during message
+/// passing, it helps to find factors driving messages towards the regime where
+/// the arithmetic of messages loses precision, e.g. Ising messages of order `1e30`.
+/// An audit is filled by [`FactorGraph::run_message_passing_parallel_audited`]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MessageAudit {
+    /// A maximal absolute value of a parameter of a message
+    pub bound: f64,
+
+    /// Whether out of bounds parameters are clamped to the bound
+    pub clamp: bool,
+
+    /// Numbers of out of bounds messages sent by each factor
+    pub factor_saturations: Vec<usize>,
+
+    /// Numbers of out of bounds messages sent by each variable
+    pub variable_saturations: Vec<usize>,
+}
+
+impl MessageAudit {
+    /// Creates an empty audit
+    ///
+    /// # Arguments
+    ///
+    /// * `bound` - A maximal absolute value of a parameter of a message
+    /// * `clamp` - Whether to clamp out of bounds parameters to the bound,
+    ///   otherwise messages are only checked
+    #[inline]
+    pub fn new(bound: f64, clamp: bool) -> Self {
+        MessageAudit {
+            bound,
+            clamp,
+            factor_saturations: Vec::new(),
+            variable_saturations: Vec::new(),
+        }
+    }
+
+    /// Returns the total number of out of bounds messages
+    #[inline]
+    pub fn saturations_number(&self) -> usize {
+        self.factor_saturations.iter().sum::<usize>()
+            + self.variable_saturations.iter().sum::<usize>()
+    }
+
+    /// Returns pairs (a factor index, a number of out of bounds messages) of factors
+    /// that have sent at least one out of bounds message, from the most to the least
+    /// frequently saturating factor
+    #[inline]
+    pub fn saturating_factors(&self) -> Vec<(usize, usize)> {
+        let mut factors: Vec<_> = self
+            .factor_saturations
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|(_, saturations)| *saturations > 0)
+            .collect();
+        factors.sort_by_key(|(_, saturations)| Reverse(*saturations));
+        factors
+    }
+}
+
+impl<F, V> FactorGraph<F, V>
+where
+    F: Factor,
+    V: Variable<Message = F::Message>,
+    F::Message: BoundedMessage,
+{
+    /// Runs a message passing algorithm in parallel similarly to
+    /// [`FactorGraph::run_message_passing_parallel`], additionally checking every message
+    /// against the bound of an audit right after it is sent and counting out of bounds
+    /// messages per sending node. Counts are added to an audit whether message passing
+    /// converges or not
+    ///
+    /// # Arguments
+    ///
+    /// * `max_iterations_number` - A maximal number of iterations, if a process
+    ///   does not converge before reaching this number of iterations, it fails
+    /// * `min_iterations_number` - A minimal number of iterations that is performed
+    ///   disregards reaching the convergence criterion
+    /// * `threshold` - A threshold specifying the convergence criterion
+    /// * `factor_scheduler` - A scheduler of a factor's messages update rule hyper-parameters
+    /// * `variable_scheduler` - A scheduler of a variable's messages update rule hyper-parameters
+    /// * `audit` - An audit specifying the bound, counts of out of bounds messages are added to it
+    ///
+    /// # Notes
+    ///
+    /// Checks take a pass over messages of each node, thus an audited run is slower than
+    /// a plain one and is meant for debugging. Clamping changes the fixed point of message
+    /// passing, it should be used to find the culprits rather than to fix them
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::MessageAudit;
+    /// use gmrs::ising::{new_ising_builder, IsingFactor, IsingMessage, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    ///
+    /// let mut fgb = new_ising_builder::<SumProduct>(3, 2);
+    /// fgb.add_factor(IsingFactor::new(0.5, 0., 0.), &[0, 1], &mut || IsingMessage(0.)).unwrap();
+    /// // A nearly hard field
+    /// fgb.add_factor(IsingFactor::new(0., 0., 100.), &[1, 2], &mut || IsingMessage(0.)).unwrap();
+    /// let mut fg = fgb.build();
+    /// let mut audit = MessageAudit::new(50., false);
+    /// fg.run_message_passing_parallel_audited(
+    ///     100,
+    ///     0,
+    ///     1e-10,
+    ///     &get_standard_factor_scheduler(0.),
+    ///     &get_standard_variable_scheduler(0.),
+    ///     &mut audit,
+    /// ).unwrap();
+    /// assert_eq!(audit.saturating_factors()[0].0, 1);
+    /// assert_eq!(audit.factor_saturations[0], 0);
+    /// ```
+    pub fn run_message_passing_parallel_audited(
+        &mut self,
+        max_iterations_number: usize,
+        min_iterations_number: usize,
+        threshold: f64,
+        factor_scheduler: &impl Fn(usize) -> F::Parameters,
+        variable_scheduler: &impl Fn(usize) -> V::Parameters,
+        audit: &mut MessageAudit,
+    ) -> FGResult<MessagePassingInfo> {
+        // nodes do not know their indices, thus they are found by their first edges
+        let factor_indices: Vec<Vec<usize>> = self
+            .variables
+            .iter()
+            .map(|node| node.fac_node_indices.clone())
+            .collect();
+        let variable_indices: Vec<Vec<usize>> = self
+            .factors
+            .iter()
+            .map(|node| node.var_node_indices.clone())
+            .collect();
+        let factor_saturations: Vec<_> = (0..self.factors.len())
+            .map(|_| AtomicUsize::new(0))
+            .collect();
+        let variable_saturations: Vec<_> = (0..self.variables.len())
+            .map(|_| AtomicUsize::new(0))
+            .collect();
+        let (bound, clamp) = (audit.bound, audit.clamp);
+        let count = |messages: &mut [F::Message], counter: Option<&AtomicUsize>| {
+            let saturations_number = messages
+                .iter_mut()
+                .map(|message| message.saturate(bound, clamp))
+                .filter(|is_saturated| *is_saturated)
+                .count();
+            if let (Some(counter), true) = (counter, saturations_number > 0) {
+                counter.fetch_add(saturations_number, Ordering::Relaxed);
+            }
+        };
+        let result = self.run_message_passing_with_hook(
+            max_iterations_number,
+            min_iterations_number,
+            threshold,
+            1,
+            factor_scheduler,
+            variable_scheduler,
+            &|node: &mut FactorNode<F, V>, parameters: &F::Parameters| {
+                node.eval_messages(parameters);
+                let counter = node.var_node_indices.first().map(|var_index| {
+                    &factor_saturations
+                        [factor_indices[*var_index][node.var_node_receiver_indices[0]]]
+                });
+                count(&mut node.messages, counter);
+            },
+            &|node: &mut VariableNode<V, F>, parameters: &V::Parameters| {
+                node.eval_messages(parameters);
+                let counter = node.fac_node_indices.first().map(|fac_index| {
+                    &variable_saturations
+                        [variable_indices[*fac_index][node.fac_node_receiver_indices[0]]]
+                });
+                count(&mut node.messages, counter);
+            },
+            |_, _, _| ControlFlow::Continue(()),
+        );
+        let add = |counts: &mut Vec<usize>, saturations: Vec<AtomicUsize>| {
+            counts.resize(saturations.len(), 0);
+            for (count, saturations) in counts.iter_mut().zip(saturations) {
+                *count += saturations.into_inner();
+            }
+        };
+        add(&mut audit.factor_saturations, factor_saturations);
+        add(&mut audit.variable_saturations, variable_saturations);
+        result
+    }
+}
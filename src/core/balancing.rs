@@ -0,0 +1,47 @@
+use std::mem::take;
+
+// ------------------------------------------------------------------------------------------
+
+// Number of chunks per thread, extra chunks let idle threads steal work
+const CHUNKS_PER_THREAD: usize = 4;
+
+// Nodes with at least this number of edges pull messages in parallel
+pub(crate) const MIN_HUB_DEGREE: usize = 1024;
+
+// Minimal number of edges processed by a single task when a hub's edges are split
+pub(crate) const MIN_EDGES_PER_TASK: usize = 256;
+
+// Graphs with fewer edges are swept serially by default, since for them
+// the overhead of rayon dominates the cost of message updates
+pub(crate) const DEFAULT_PARALLEL_THRESHOLD: usize = 2048;
+
+// Splits nodes into contiguous chunks of approximately equal total cost for
+// the current rayon thread pool. A node whose cost exceeds the target cost of
+// a chunk forms a chunk on its own, thus hubs of heavy-tailed graphs do not
+// serialize work of other nodes
+pub(crate) fn balanced_chunks<T>(nodes: &mut [T], cost: impl Fn(&T) -> usize) -> Vec<&mut [T]> {
+    let total_cost: usize = nodes.iter().map(&cost).sum();
+    let chunks_number = rayon::current_num_threads() * CHUNKS_PER_THREAD;
+    let target_cost = (total_cost / chunks_number).max(1);
+    let mut chunks = Vec::with_capacity(chunks_number + 1);
+    let mut rest = nodes;
+    while !rest.is_empty() {
+        let mut chunk_cost = 0;
+        let mut chunk_len = 0;
+        for node in rest.iter() {
+            let node_cost = cost(node);
+            if chunk_len > 0 && chunk_cost + node_cost > target_cost {
+                break;
+            }
+            chunk_cost += node_cost;
+            chunk_len += 1;
+            if chunk_cost >= target_cost {
+                break;
+            }
+        }
+        let (chunk, tail) = take(&mut rest).split_at_mut(chunk_len);
+        chunks.push(chunk);
+        rest = tail;
+    }
+    chunks
+}
@@ -0,0 +1,164 @@
+use std::sync::OnceLock;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::core::{
+    factor::Factor, factor_graph::FactorGraph, topology::UnionFind, variable::Variable,
+};
+
+// ------------------------------------------------------------------------------------------
+
+/// A connected component of a factor graph
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ConnectedComponent {
+    /// Indices of variables of a component in ascending order
+    pub variables: Vec<usize>,
+
+    /// Indices of factors of a component in ascending order
+    pub factors: Vec<usize>,
+}
+
+impl<F, V> FactorGraph<F, V>
+where
+    F: Factor,
+    V: Variable<Message = F::Message>,
+{
+    /// Returns the partition of a factor graph into connected components
+    /// ordered by their smallest variable index. A variable without adjoint
+    /// factors forms a component on its own
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let mut fgb = new_ising_builder::<SumProduct>(5, 3);
+    /// fgb.add_factor(IsingFactor::new(0.5, 0., 0.), &[3, 0], &mut initializer).unwrap();
+    /// fgb.add_factor(IsingFactor::new(0.5, 0., 0.), &[1, 2], &mut initializer).unwrap();
+    /// fgb.add_factor(IsingFactor::UnitFactor(0.5), &[0], &mut initializer).unwrap();
+    /// let fg = fgb.build();
+    /// let components = fg.connected_components();
+    /// assert_eq!(components.len(), 3);
+    /// assert_eq!(components[0].variables, vec![0, 3]);
+    /// assert_eq!(components[0].factors, vec![0, 2]);
+    /// assert_eq!(components[1].variables, vec![1, 2]);
+    /// assert_eq!(components[2].variables, vec![4]);
+    /// assert!(components[2].factors.is_empty());
+    /// ```
+    pub fn connected_components(&self) -> Vec<ConnectedComponent> {
+        let variables_number = self.variables.len();
+        let mut union_find = UnionFind::new(variables_number);
+        for factor in &self.factors {
+            for var_index in &factor.var_node_indices[1..] {
+                union_find.union(factor.var_node_indices[0], *var_index);
+            }
+        }
+        let mut component_indices = vec![usize::MAX; variables_number];
+        let mut components = Vec::new();
+        for var_index in 0..variables_number {
+            let root = union_find.find(var_index);
+            if component_indices[root] == usize::MAX {
+                component_indices[root] = components.len();
+                components.push(ConnectedComponent {
+                    variables: Vec::new(),
+                    factors: Vec::new(),
+                });
+            }
+            components[component_indices[root]]
+                .variables
+                .push(var_index);
+        }
+        for (fac_index, factor) in self.factors.iter().enumerate() {
+            let root = union_find.find(factor.var_node_indices[0]);
+            components[component_indices[root]].factors.push(fac_index);
+        }
+        components
+    }
+
+    /// Splits a factor graph into independent factor graphs, one per
+    /// connected component (see [`FactorGraph::connected_components`]).
+    /// Messages are kept, thus message passing on each of the returned factor
+    /// graphs is warm started and can be run with its own convergence
+    /// criterion, so that a single non-converging component does not fail the rest
+    ///
+    /// # Notes
+    ///
+    /// Variables and factors of a returned factor graph are indexed in the order
+    /// of the corresponding component's `variables` and `factors`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let mut fgb = new_ising_builder::<SumProduct>(4, 2);
+    /// fgb.add_factor(IsingFactor::new(0.5, 0.2, 0.), &[0, 2], &mut initializer).unwrap();
+    /// fgb.add_factor(IsingFactor::new(-0.5, 0., 0.3), &[1, 3], &mut initializer).unwrap();
+    /// let fg = fgb.build();
+    /// let factor_scheduler = get_standard_factor_scheduler(0.);
+    /// let variable_scheduler = get_standard_variable_scheduler(0.);
+    /// for (component, mut subgraph) in fg.split_components() {
+    ///     assert_eq!(subgraph.get_variable_degrees(), vec![1; 2]);
+    ///     subgraph.run_message_passing_parallel(100, 0, 1e-10, &factor_scheduler, &variable_scheduler).unwrap();
+    ///     assert_eq!(component.variables.len(), subgraph.variable_marginals().len());
+    /// }
+    /// ```
+    pub fn split_components(&self) -> Vec<(ConnectedComponent, FactorGraph<F, V>)> {
+        let mut local_var_indices = vec![0; self.variables.len()];
+        let mut local_fac_indices = vec![0; self.factors.len()];
+        let components = self.connected_components();
+        for component in &components {
+            for (local_index, var_index) in component.variables.iter().enumerate() {
+                local_var_indices[*var_index] = local_index;
+            }
+            for (local_index, fac_index) in component.factors.iter().enumerate() {
+                local_fac_indices[*fac_index] = local_index;
+            }
+        }
+        components
+            .into_iter()
+            .map(|component| {
+                // positions of messages within nodes are kept, only indices of nodes change
+                let factors = component
+                    .factors
+                    .iter()
+                    .map(|fac_index| {
+                        let mut factor = self.factors[*fac_index].clone();
+                        for var_index in &mut factor.var_node_indices {
+                            *var_index = local_var_indices[*var_index];
+                        }
+                        factor
+                    })
+                    .collect();
+                let variables = component
+                    .variables
+                    .iter()
+                    .map(|var_index| {
+                        let mut variable = self.variables[*var_index].clone();
+                        for fac_index in &mut variable.fac_node_indices {
+                            *fac_index = local_fac_indices[*fac_index];
+                        }
+                        variable
+                    })
+                    .collect();
+                let subgraph = FactorGraph {
+                    factors,
+                    variables,
+                    certificate: OnceLock::new(),
+                    #[cfg(feature = "parallel")]
+                    thread_pool: self.thread_pool.clone(),
+                    #[cfg(feature = "parallel")]
+                    parallel_threshold: self.parallel_threshold,
+                };
+                (component, subgraph)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,243 @@
+use std::collections::VecDeque;
+
+use rand::Rng;
+
+use crate::core::{
+    factor::Factor,
+    factor_graph::{BatchSamplingInfo, FGError, FGResult, FactorGraph},
+    variable::Variable,
+};
+
+// ------------------------------------------------------------------------------------------
+
+/// A trait of factors representing a conditional distribution of one adjacent
+/// variable (a child) given the other adjacent variables (parents), i.e. factors
+/// normalized over a child for any configuration of parents. A factor graph
+/// whose variables are children of exactly one factor each and whose
+/// parent-child relations form a directed acyclic graph is a Bayesian network
+pub trait ConditionalFactor<S>: Factor {
+    /// Returns the position of a child among adjacent variables
+    fn child_position(&self) -> usize;
+
+    /// Returns the maximal deviation of the sum over child values from one
+    /// across all configurations of parents
+    fn normalization_error(&self) -> f64;
+
+    /// Samples a child given values of parents
+    ///
+    /// # Arguments
+    ///
+    /// * `parents` - Values of parents in the order of adjacent variables (the child is skipped)
+    /// * `rng` - A random numbers generator
+    fn sample_child(&self, parents: &[S], rng: &mut impl Rng) -> S;
+}
+
+impl<F, V, S> FactorGraph<F, V>
+where
+    F: ConditionalFactor<S>,
+    V: Variable<Message = F::Message, Sample = S>,
+    S: Copy,
+{
+    /// Checks that all factors are normalized over their children up to a tolerance
+    /// and returns the index of the first factor violating it in the error otherwise
+    ///
+    /// # Arguments
+    ///
+    /// * `tolerance` - A maximal allowed deviation of the sum over child values from one
+    pub fn verify_normalization(&self, tolerance: f64) -> FGResult<()> {
+        for (fac_index, factor) in self.factors.iter().enumerate() {
+            let error = factor.factor.normalization_error();
+            if error > tolerance || error.is_nan() {
+                return Err(FGError::NormalizationError(fac_index, error));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns indices of factors in a topological order of their children,
+    /// i.e. parents of each factor are children of preceding factors, or None
+    /// if some variable is not a child of exactly one factor or parent-child
+    /// relations contain a directed cycle
+    ///
+    /// # Notes
+    ///
+    /// Fixing a variable (e.g. by sampling) adds a second factor whose child
+    /// is this variable, thus such graphs are not recognized as directed acyclic
+    pub fn ancestral_order(&self) -> Option<Vec<usize>> {
+        let mut child_factors = vec![None; self.variables.len()];
+        for (fac_index, factor) in self.factors.iter().enumerate() {
+            let child = *factor
+                .var_node_indices
+                .get(factor.factor.child_position())?;
+            if child_factors[child].replace(fac_index).is_some() {
+                return None;
+            }
+        }
+        let mut unsampled_parents: Vec<usize> = self
+            .factors
+            .iter()
+            .map(|factor| factor.var_node_indices.len() - 1)
+            .collect();
+        let mut queue: VecDeque<usize> = unsampled_parents
+            .iter()
+            .enumerate()
+            .filter(|(_, parents_number)| **parents_number == 0)
+            .map(|(fac_index, _)| fac_index)
+            .collect();
+        let mut order = Vec::with_capacity(self.factors.len());
+        while let Some(fac_index) = queue.pop_front() {
+            order.push(fac_index);
+            let factor = &self.factors[fac_index];
+            let child = factor.var_node_indices[factor.factor.child_position()];
+            for next_index in &self.variables[child].fac_node_indices {
+                if *next_index != fac_index {
+                    unsampled_parents[*next_index] -= 1;
+                    if unsampled_parents[*next_index] == 0 {
+                        queue.push_back(*next_index);
+                    }
+                }
+            }
+        }
+        if order.len() != self.factors.len() || child_factors.iter().any(Option::is_none) {
+            return None;
+        }
+        Some(order)
+    }
+
+    /// Draws an exact sample of a directed acyclic graph of conditional factors
+    /// by ancestral sampling, i.e. by sampling children given already sampled
+    /// parents in a topological order, without any message passing
+    ///
+    /// # Arguments
+    ///
+    /// * `rng` - A random numbers generator
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::FactorGraphBuilder;
+    /// use gmrs::tabular::{uninformative_message_initializer, ConditionalTabularFactor, TabularVariable};
+    /// use ndarray::array;
+    /// use rand::thread_rng;
+    ///
+    /// // rain -> wet grass
+    /// let mut initializer = uninformative_message_initializer();
+    /// let mut fgb = FactorGraphBuilder::new_with_variables(vec![TabularVariable::new(2); 2], 2);
+    /// let rain = ConditionalTabularFactor::new(array![0.8, 0.2].into_dyn(), 0);
+    /// let wet_given_rain = ConditionalTabularFactor::new(array![[0.9, 0.1], [0., 1.]].into_dyn(), 1);
+    /// fgb.add_factor(wet_given_rain, &[0, 1], &mut initializer).unwrap();
+    /// fgb.add_factor(rain, &[0], &mut initializer).unwrap();
+    /// let fg = fgb.build();
+    /// fg.verify_normalization(1e-12).unwrap();
+    /// assert_eq!(fg.ancestral_order(), Some(vec![1, 0]));
+    /// let sample = fg.ancestral_sample(&mut thread_rng()).unwrap();
+    /// // it always rains on wet grass
+    /// assert!(sample[0] == 0 || sample[1] == 1);
+    /// ```
+    pub fn ancestral_sample(&self, rng: &mut impl Rng) -> FGResult<Vec<S>> {
+        let order = self.ancestral_order().ok_or(FGError::NotDirectedAcyclic)?;
+        Ok(self.ancestral_sample_ordered(&order, rng))
+    }
+
+    /// Draws several exact samples of a directed acyclic graph of conditional
+    /// factors by ancestral sampling. The topological order is computed once,
+    /// thus each sample costs a single pass over factors
+    ///
+    /// # Arguments
+    ///
+    /// * `samples_number` - A number of samples to draw
+    /// * `rng` - A random numbers generator
+    pub fn sample_n_ancestral(
+        &self,
+        samples_number: usize,
+        rng: &mut impl Rng,
+    ) -> FGResult<BatchSamplingInfo<S>> {
+        let order = self.ancestral_order().ok_or(FGError::NotDirectedAcyclic)?;
+        let samples = (0..samples_number)
+            .map(|_| self.ancestral_sample_ordered(&order, rng))
+            .collect();
+        Ok(BatchSamplingInfo::from_samples(samples))
+    }
+
+    /// Draws several samples by ancestral sampling if a factor graph is recognized
+    /// as a directed acyclic graph of conditional factors, which requires no message
+    /// passing at all, and falls back to [`FactorGraph::sample_n`] otherwise
+    ///
+    /// # Arguments
+    ///
+    /// * `samples_number` - A number of samples to draw
+    /// * `max_iterations_number` - A maximal number of iterations in a message passing algorithm
+    /// * `min_iterations_number` - A minimal number of iterations in a message passing algorithm
+    /// * `threshold` - A threshold specifying the convergence criterion
+    /// * `rng` - A random numbers generator
+    /// * `factor_scheduler` - A scheduler of a factor's messages update rule hyper-parameters
+    /// * `variable_scheduler` - A scheduler of a variable's messages update rule hyper-parameters
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::FactorGraphBuilder;
+    /// use gmrs::tabular::{uninformative_message_initializer, ConditionalTabularFactor, TabularVariable};
+    /// use ndarray::array;
+    /// use rand::thread_rng;
+    ///
+    /// // A Markov chain of 100 binary variables
+    /// let mut initializer = uninformative_message_initializer();
+    /// let mut fgb = FactorGraphBuilder::new_with_variables(vec![TabularVariable::new(2); 100], 100);
+    /// fgb.add_factor(ConditionalTabularFactor::new(array![0.5, 0.5].into_dyn(), 0), &[0], &mut initializer).unwrap();
+    /// for i in 0..99 {
+    ///     let transition = array![[0.9, 0.1], [0.2, 0.8]].into_dyn();
+    ///     fgb.add_factor(ConditionalTabularFactor::new(transition, 1), &[i, i + 1], &mut initializer).unwrap();
+    /// }
+    /// let fg = fgb.build();
+    /// let factor_scheduler = |_| 0.;
+    /// let variable_scheduler = |_| 0.;
+    /// let info = fg.sample_n_auto(1000, 1000, 0, 1e-10, &mut thread_rng(), &factor_scheduler, &variable_scheduler).unwrap();
+    /// assert_eq!(info.samples.len(), 1000);
+    /// assert_eq!(info.total_iterations_number, 0);
+    /// ```
+    #[allow(clippy::too_many_arguments)]
+    pub fn sample_n_auto(
+        &self,
+        samples_number: usize,
+        max_iterations_number: usize,
+        min_iterations_number: usize,
+        threshold: f64,
+        rng: &mut impl Rng,
+        factor_scheduler: &impl Fn(usize) -> F::Parameters,
+        variable_scheduler: &impl Fn(usize) -> V::Parameters,
+    ) -> FGResult<BatchSamplingInfo<S>> {
+        if self.ancestral_order().is_some() {
+            self.sample_n_ancestral(samples_number, rng)
+        } else {
+            self.sample_n(
+                samples_number,
+                max_iterations_number,
+                min_iterations_number,
+                threshold,
+                rng,
+                factor_scheduler,
+                variable_scheduler,
+            )
+        }
+    }
+
+    // Samples variables in a given topological order of factors
+    pub(crate) fn ancestral_sample_ordered(&self, order: &[usize], rng: &mut impl Rng) -> Vec<S> {
+        let mut samples = vec![None; self.variables.len()];
+        let mut parents = Vec::new();
+        for fac_index in order {
+            let factor = &self.factors[*fac_index];
+            let child_position = factor.factor.child_position();
+            parents.clear();
+            for (position, var_index) in factor.var_node_indices.iter().enumerate() {
+                if position != child_position {
+                    parents.push(samples[*var_index].expect("Parents are sampled before children"));
+                }
+            }
+            samples[factor.var_node_indices[child_position]] =
+                Some(factor.factor.sample_child(&parents, rng));
+        }
+        samples.into_iter().map(Option::unwrap).collect()
+    }
+}
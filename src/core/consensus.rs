@@ -0,0 +1,243 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::core::{
+    factor::Factor,
+    factor_graph::{FGResult, FactorGraph},
+    variable::{DecodableVariable, Variable},
+};
+
+// ------------------------------------------------------------------------------------------
+
+/// Information returned after a multi-seed consensus search of the MAP assignment
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ConsensusInfo<S> {
+    /// The consensus assignment, i.e. the most frequent value of each variable
+    /// across runs, values of not frozen variables are replaced by the re-solved ones
+    /// if freezing is enabled
+    pub assignment: Vec<S>,
+
+    /// Fractions of successful runs agreeing with the most frequent value of each variable
+    pub agreement: Vec<f64>,
+
+    /// Assignments found by successful runs
+    pub runs: Vec<Vec<S>>,
+
+    /// Number of runs where message passing has not converged
+    pub failed_runs_number: usize,
+
+    /// Indices of variables frozen at their consensus values before re-solving the rest
+    pub frozen_variables: Vec<usize>,
+
+    /// Total number of message passing iterations across all runs
+    pub total_iterations_number: usize,
+}
+
+impl<F, V> FactorGraph<F, V>
+where
+    F: Factor,
+    V: Variable<Message = F::Message>,
+    V::Sample: PartialEq,
+{
+    /// Searches for the MAP assignment by running message passing followed by
+    /// decimation (see [`FactorGraph::decimate_most_confident`]) from several random
+    /// initializations of messages and taking a majority vote per variable.
+    /// On hard instances different seeds converge to different fixed points,
+    /// the fraction of runs agreeing on a variable indicates how reliable its value is.
+    /// Optionally, variables with high agreement are frozen at their consensus values
+    /// and the rest of variables are re-solved
+    ///
+    /// # Arguments
+    ///
+    /// * `runs_number` - A number of runs from different initializations of messages
+    /// * `message_initializer` - An object that initializes messages of each run
+    /// * `freezing_threshold` - A minimal agreement for a variable to be frozen before
+    ///   re-solving the rest of variables, None disables re-solving
+    /// * `max_iterations_number` - A maximal number of iterations in a message passing algorithm
+    /// * `min_iterations_number` - A minimal number of iterations in a message passing algorithm
+    /// * `threshold` - A threshold specifying the convergence criterion
+    /// * `factor_scheduler` - A scheduler of a factor's messages update rule hyper-parameters
+    /// * `variable_scheduler` - A scheduler of a variable's messages update rule hyper-parameters
+    ///
+    /// # Notes
+    ///
+    /// Runs are performed on clones of a factor graph, thus a factor graph stays untouched.
+    /// Runs where message passing has not converged are skipped, if all runs fail the
+    /// method returns the error of the last one. Re-solving starts from the current messages
+    /// of a factor graph. Ties in a majority vote are broken in favor of a value found first
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, MaxProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    ///
+    /// // A frustrated ring of 4 spins with a field on the first spin
+    /// let mut fgb = new_ising_builder::<MaxProduct>(4, 5);
+    /// for (i, coupling) in [1., 1., 1., -1.].into_iter().enumerate() {
+    ///     fgb.add_factor(IsingFactor::new(coupling, 0., 0.), &[i, (i + 1) % 4], &mut initializer).unwrap();
+    /// }
+    /// fgb.add_factor(IsingFactor::UnitFactor(1.), &[0], &mut initializer).unwrap();
+    /// let fg = fgb.build();
+    ///
+    /// let info = fg.consensus_map(
+    ///     8,
+    ///     &mut initializer,
+    ///     Some(0.9),
+    ///     1000,
+    ///     0,
+    ///     1e-10,
+    ///     &get_standard_factor_scheduler(0.5),
+    ///     &get_standard_variable_scheduler(0.5),
+    /// ).unwrap();
+    /// assert_eq!(info.runs.len() + info.failed_runs_number, 8);
+    /// assert_eq!(info.assignment.len(), 4);
+    /// assert!(info.agreement.iter().all(|a| (0. ..=1.).contains(a)));
+    /// ```
+    #[allow(clippy::too_many_arguments)]
+    pub fn consensus_map(
+        &self,
+        runs_number: usize,
+        message_initializer: &mut impl FnMut() -> F::Message,
+        freezing_threshold: Option<f64>,
+        max_iterations_number: usize,
+        min_iterations_number: usize,
+        threshold: f64,
+        factor_scheduler: &impl Fn(usize) -> F::Parameters,
+        variable_scheduler: &impl Fn(usize) -> V::Parameters,
+    ) -> FGResult<ConsensusInfo<V::Sample>>
+    where
+        V: DecodableVariable,
+    {
+        let mut runs = Vec::with_capacity(runs_number);
+        let mut failed_runs_number = 0;
+        let mut total_iterations_number = 0;
+        let mut last_error = None;
+        for _ in 0..runs_number {
+            let mut fg = self.clone();
+            fg.reinitialize_messages(message_initializer);
+            let result = fg
+                .run_message_passing_parallel(
+                    max_iterations_number,
+                    min_iterations_number,
+                    threshold,
+                    factor_scheduler,
+                    variable_scheduler,
+                )
+                .and_then(|info| {
+                    total_iterations_number += info.iterations_number;
+                    fg.decimate_most_confident(
+                        max_iterations_number,
+                        min_iterations_number,
+                        threshold,
+                        factor_scheduler,
+                        variable_scheduler,
+                    )
+                });
+            match result {
+                Ok(info) => {
+                    total_iterations_number += info.total_iterations_number;
+                    runs.push(info.samples);
+                }
+                Err(err) => {
+                    failed_runs_number += 1;
+                    last_error = Some(err);
+                }
+            }
+        }
+        if runs.is_empty() {
+            if let Some(err) = last_error {
+                return Err(err);
+            }
+        }
+        let variables_number = self.variables.len();
+        let mut assignment = Vec::with_capacity(variables_number);
+        let mut agreement = Vec::with_capacity(variables_number);
+        for var_index in 0..variables_number {
+            // (value, votes) pairs in the order of appearance
+            let mut votes: Vec<(V::Sample, usize)> = Vec::new();
+            for run in &runs {
+                match votes.iter_mut().find(|(value, _)| *value == run[var_index]) {
+                    Some((_, count)) => *count += 1,
+                    None => votes.push((run[var_index], 1)),
+                }
+            }
+            // the first of the most frequent values, the current argmax if there are no runs
+            let (value, count) = votes
+                .iter()
+                .rev()
+                .max_by_key(|(_, count)| *count)
+                .copied()
+                .unwrap_or_else(|| (self.variables[var_index].argmax(), 0));
+            assignment.push(value);
+            agreement.push(count as f64 / runs.len().max(1) as f64);
+        }
+        let mut frozen_variables = Vec::new();
+        if let Some(freezing_threshold) = freezing_threshold {
+            frozen_variables.extend(
+                agreement
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, agreement)| **agreement >= freezing_threshold)
+                    .map(|(var_index, _)| var_index),
+            );
+            if frozen_variables.len() < variables_number {
+                let mut fg = self.clone();
+                for var_index in &frozen_variables {
+                    fg.freeze_variable(&assignment[*var_index], *var_index)?;
+                }
+                total_iterations_number += fg
+                    .run_message_passing_parallel(
+                        max_iterations_number,
+                        min_iterations_number,
+                        threshold,
+                        factor_scheduler,
+                        variable_scheduler,
+                    )?
+                    .iterations_number;
+                let info = fg.decimate_most_confident(
+                    max_iterations_number,
+                    min_iterations_number,
+                    threshold,
+                    factor_scheduler,
+                    variable_scheduler,
+                )?;
+                total_iterations_number += info.total_iterations_number;
+                assignment = info.samples;
+            }
+        }
+        Ok(ConsensusInfo {
+            assignment,
+            agreement,
+            runs,
+            failed_runs_number,
+            frozen_variables,
+            total_iterations_number,
+        })
+    }
+
+    // Replaces all messages by new ones the same way as a builder initializes them
+    fn reinitialize_messages(&mut self, message_initializer: &mut impl FnMut() -> F::Message) {
+        for factor in &mut self.factors {
+            let neighbours = factor
+                .var_node_indices
+                .iter()
+                .zip(&factor.var_node_receiver_indices)
+                .enumerate();
+            for (position, (var_index, receiver_index)) in neighbours {
+                let variable = &mut self.variables[*var_index];
+                let factor_message = message_initializer();
+                let variable_message = message_initializer();
+                factor.receivers[position] = factor_message.clone();
+                factor.messages[position] = variable_message.clone();
+                variable.messages[*receiver_index] = factor_message;
+                variable.receivers[*receiver_index] = variable_message;
+                variable.invalidate_marginal();
+            }
+        }
+    }
+}
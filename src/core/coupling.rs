@@ -0,0 +1,77 @@
+use crate::core::{
+    factor::Factor,
+    factor_graph::{FGResult, FactorGraph},
+    variable::Variable,
+};
+
+// ------------------------------------------------------------------------------------------
+
+impl<F, V> FactorGraph<F, V>
+where
+    F: Factor,
+    V: Variable<Message = F::Message>,
+{
+    /// Creates a factor graph of two replicas of a factor graph where each pair of copies
+    /// of a variable is joined by a coupling factor, e.g. to study the Franz–Parisi
+    /// potential or replica symmetry breaking. A variable `i` of the first replica keeps
+    /// its index, its copy in the second replica gets the index `i + N`, where `N` is
+    /// the number of variables. Factors of the first replica keep their indices and
+    /// messages, they are followed by factors of the second replica in the same order
+    /// and by coupling factors of variables `0, 1, ..., N - 1`
+    ///
+    /// # Arguments
+    ///
+    /// * `coupling_factor` - A function returning a coupling factor of degree 2 for a variable
+    ///   index, it is attached to variables `i` and `i + N`
+    /// * `message_initializer` - A function generating initial messages of the second
+    ///   replica and coupling factors
+    ///
+    /// # Notes
+    ///
+    /// The strength of coupling could be scheduled by means of factor hyper-parameters,
+    /// e.g. see [`FactorGraph::coupled_ising_replicas`] for Ising models
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let mut fgb = new_ising_builder::<SumProduct>(3, 2);
+    /// fgb.add_factor(IsingFactor::new(0.5, 0., 0.), &[0, 1], &mut initializer).unwrap();
+    /// fgb.add_factor(IsingFactor::new(-0.5, 0., 0.), &[1, 2], &mut initializer).unwrap();
+    /// let fg = fgb.build();
+    /// let doubled = fg
+    ///     .coupled_replicas(|_| IsingFactor::new(0.2, 0., 0.), &mut initializer)
+    ///     .unwrap();
+    /// assert_eq!(doubled.get_variable_degrees(), vec![2, 3, 2, 2, 3, 2]);
+    /// assert_eq!(doubled.factor_neighbors(2), &[3, 4]);
+    /// assert_eq!(doubled.factor_neighbors(5), &[1, 4]);
+    /// ```
+    pub fn coupled_replicas(
+        &self,
+        mut coupling_factor: impl FnMut(usize) -> F,
+        message_initializer: &mut impl FnMut() -> F::Message,
+    ) -> FGResult<Self> {
+        let variables_number = self.variables.len();
+        let mut doubled = self.clone();
+        doubled.add_variables(self.variables.iter().map(|node| node.variable.clone()));
+        for node in &self.factors {
+            let var_indices: Vec<_> = node
+                .var_node_indices
+                .iter()
+                .map(|var_index| var_index + variables_number)
+                .collect();
+            doubled.add_factor(node.factor.clone(), &var_indices, message_initializer)?;
+        }
+        for var_index in 0..variables_number {
+            doubled.add_factor(
+                coupling_factor(var_index),
+                &[var_index, var_index + variables_number],
+                message_initializer,
+            )?;
+        }
+        Ok(doubled)
+    }
+}
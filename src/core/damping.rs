@@ -0,0 +1,172 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::core::message::DampableMessage;
+
+// ------------------------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// Hyper-parameters of adaptive per-edge damping. Each edge keeps its own
+/// damping coefficient `gamma`, a new message is replaced by
+/// `(1 - gamma) * new + gamma * old`. When the message of an edge oscillates,
+/// `gamma` moves towards `max_gamma` by the fraction `increase_rate` of the distance
+/// to it, otherwise it moves towards `min_gamma` by the fraction `decrease_rate`.
+pub struct AdaptiveDamping {
+    /// Damping coefficient of an edge at the first iteration
+    pub initial_gamma: f64,
+
+    /// Minimal damping coefficient
+    pub min_gamma: f64,
+
+    /// Maximal damping coefficient, must be less than 1
+    pub max_gamma: f64,
+
+    /// Rate of damping growth for oscillating edges
+    pub increase_rate: f64,
+
+    /// Rate of damping decay for monotone edges
+    pub decrease_rate: f64,
+}
+
+impl Default for AdaptiveDamping {
+    fn default() -> Self {
+        AdaptiveDamping {
+            initial_gamma: 0.,
+            min_gamma: 0.,
+            max_gamma: 0.95,
+            increase_rate: 0.5,
+            decrease_rate: 0.1,
+        }
+    }
+}
+
+/// Parameters of stochastic message passing, each iteration updates only a random
+/// subset of factors, other factors keep their messages. It is useful for extremely
+/// large dense graphs, where full sweeps are too expensive and a stochastic fixed point
+/// iteration suffices. Factors are selected independently with probability
+/// `batch_fraction`, thus a factor is updated once per `1 / batch_fraction` iterations
+/// on average. Messages of a selected factor are damped with the coefficient
+/// `max(0, 1 - (1 - damping) / batch_fraction)`, so that messages move on average as in
+/// full sweeps damped with the coefficient `damping`. Since the discrepancy of a single
+/// iteration involves only selected factors, it must stay below the threshold for at least
+/// `ceil(1 / batch_fraction)` consecutive iterations.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct StochasticUpdates {
+    /// A fraction of factors updated per iteration, it is clipped to `[1 / factors_number, 1]`
+    pub batch_fraction: f64,
+
+    /// A damping coefficient of full sweeps that is emulated, it is applied on top of
+    /// the update rule, thus one typically sets the scheduled damping to zero
+    pub damping: f64,
+
+    /// A seed of random selections of factors, each run configured by the same
+    /// parameters selects the same factors
+    pub seed: u64,
+}
+
+/// A way messages are damped during message passing
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum DampingMode {
+    /// Messages are damped only by update rules with scheduled hyper-parameters
+    #[default]
+    Scheduled,
+
+    /// Damping is adapted individually for each edge: edges whose messages oscillate
+    /// get stronger damping, edges whose messages change monotonically get weaker damping.
+    /// It is applied on top of update rules, thus one typically sets the scheduled damping
+    /// to zero. Damping coefficients persist between runs
+    Adaptive(AdaptiveDamping),
+
+    /// Each iteration updates only a random subset of factors
+    Stochastic(StochasticUpdates),
+}
+
+// Damping state of a single edge
+#[derive(Debug, Clone)]
+pub(crate) struct EdgeDamping<M> {
+    gamma: f64,
+    prev: M,
+    prev_prev: Option<M>,
+}
+
+impl<M: DampableMessage> EdgeDamping<M> {
+    #[inline(always)]
+    fn new(damping: &AdaptiveDamping, prev: &M) -> Self {
+        EdgeDamping {
+            gamma: damping.initial_gamma,
+            prev: prev.clone(),
+            prev_prev: None,
+        }
+    }
+
+    // Adapts gamma and damps a freshly computed message
+    #[inline(always)]
+    fn damp(&mut self, message: &mut M, damping: &AdaptiveDamping) {
+        if let Some(prev_prev) = &self.prev_prev {
+            if message.oscillates(&self.prev, prev_prev) {
+                self.gamma += damping.increase_rate * (damping.max_gamma - self.gamma);
+            } else {
+                self.gamma -= damping.decrease_rate * (self.gamma - damping.min_gamma);
+            }
+        }
+        message.damp(&self.prev, self.gamma);
+        match &mut self.prev_prev {
+            Some(prev_prev) => self.prev.memcpy(prev_prev),
+            None => self.prev_prev = Some(self.prev.clone()),
+        }
+    }
+}
+
+// Updates messages of a node by `eval` and damps them edge by edge,
+// the damping state of edges added since the last update is initialized lazily
+#[inline(always)]
+pub(super) fn eval_damped<M: DampableMessage>(
+    edges: &mut Vec<EdgeDamping<M>>,
+    messages: &mut [M],
+    damping: &AdaptiveDamping,
+    eval: impl FnOnce(&mut [M]),
+) {
+    edges.truncate(messages.len());
+    for (edge, message) in edges.iter_mut().zip(messages.iter()) {
+        message.memcpy(&mut edge.prev);
+    }
+    for message in &messages[edges.len()..] {
+        edges.push(EdgeDamping::new(damping, message));
+    }
+    eval(messages);
+    for (edge, message) in edges.iter_mut().zip(messages) {
+        edge.damp(message, damping);
+    }
+}
+
+// Updates messages of a node by `eval` and damps them with a fixed coefficient,
+// previous messages are kept in the damping state of edges
+#[inline(always)]
+pub(super) fn eval_damped_fixed<M: DampableMessage>(
+    edges: &mut Vec<EdgeDamping<M>>,
+    messages: &mut [M],
+    gamma: f64,
+    eval: impl FnOnce(&mut [M]),
+) {
+    init_damping(edges, messages);
+    for (edge, message) in edges.iter_mut().zip(messages.iter()) {
+        message.memcpy(&mut edge.prev);
+    }
+    eval(messages);
+    for (edge, message) in edges.iter_mut().zip(messages) {
+        message.damp(&edge.prev, gamma);
+    }
+}
+
+// Initializes the damping state of edges added since the last update,
+// so that damping with a fixed coefficient does not allocate later
+#[inline(always)]
+pub(super) fn init_damping<M: DampableMessage>(edges: &mut Vec<EdgeDamping<M>>, messages: &[M]) {
+    edges.truncate(messages.len());
+    for message in &messages[edges.len()..] {
+        edges.push(EdgeDamping::new(&AdaptiveDamping::default(), message));
+    }
+}
@@ -0,0 +1,341 @@
+use std::collections::VecDeque;
+
+use rand::Rng;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::core::{
+    factor::Factor,
+    factor_graph::{FGError, FGResult, FactorGraph, SamplingInfo},
+    variable::{DecodableVariable, Variable},
+};
+
+// ------------------------------------------------------------------------------------------
+
+/// A confidence of a model about a variable, i.e. the difference between
+/// probabilities of the most probable value of a variable and the second
+/// most probable one (see [`Variable::marginal_gap`])
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MarginalGap {
+    /// An index of a variable
+    pub var_index: usize,
+
+    /// A gap between the two largest probabilities of a variable's marginal
+    pub gap: f64,
+}
+
+impl<F, V> FactorGraph<F, V>
+where
+    F: Factor,
+    V: Variable<Message = F::Message>,
+{
+    /// Returns variables ranked by their marginal gaps in the decreasing order,
+    /// i.e. variables a model is most confident about go first. Ties are
+    /// ranked by indices of variables. For max-product message passing
+    /// gaps are computed from max-marginals
+    ///
+    /// # Notes
+    ///
+    /// Variables that do not report their marginal gaps are omitted
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    ///
+    /// // Three independent spins in fields of different strength
+    /// let mut fgb = new_ising_builder::<SumProduct>(3, 3);
+    /// for (i, field) in [0.1, -2., 0.5].into_iter().enumerate() {
+    ///     fgb.add_factor(IsingFactor::UnitFactor(field), &[i], &mut initializer).unwrap();
+    /// }
+    /// let mut fg = fgb.build();
+    /// fg.run_message_passing_parallel(
+    ///     10,
+    ///     0,
+    ///     1e-10,
+    ///     &get_standard_factor_scheduler(0.),
+    ///     &get_standard_variable_scheduler(0.),
+    /// ).unwrap();
+    /// let ranking = fg.marginal_gap_ranking();
+    /// let order: Vec<_> = ranking.iter().map(|gap| gap.var_index).collect();
+    /// assert_eq!(order, vec![1, 2, 0]);
+    /// assert!((ranking[0].gap - f64::tanh(1.)).abs() < 1e-10);
+    /// ```
+    pub fn marginal_gap_ranking(&self) -> Vec<MarginalGap> {
+        let mut ranking: Vec<_> = self
+            .variables
+            .iter()
+            .enumerate()
+            .filter_map(|(var_index, var)| {
+                var.marginal_gap().map(|gap| MarginalGap { var_index, gap })
+            })
+            .collect();
+        ranking.sort_by(|lhs, rhs| {
+            rhs.gap
+                .total_cmp(&lhs.gap)
+                .then(lhs.var_index.cmp(&rhs.var_index))
+        });
+        ranking
+    }
+
+    /// Decimates a factor graph fixing variables one by one at their most
+    /// probable values, always choosing a not yet fixed variable with the
+    /// largest marginal gap and rerunning message passing after each fixing.
+    /// Fixing confident variables first reduces the chance to introduce
+    /// a contradiction compared to decimation in the order of indices
+    ///
+    /// # Arguments
+    ///
+    /// * `max_iterations_number` - A maximal number of iterations in a message passing algorithm
+    /// * `min_iterations_number` - A minimal number of iterations in a message passing algorithm
+    /// * `threshold` - A threshold specifying the convergence criterion
+    /// * `factor_scheduler` - A scheduler of a factor's messages update rule hyper-parameters
+    /// * `variable_scheduler` - A scheduler of a variable's messages update rule hyper-parameters
+    ///
+    /// # Notes
+    ///
+    /// Messages of a factor graph are expected to be converged before decimation.
+    /// Variables that do not report their marginal gaps are fixed last in the order
+    /// of indices. Similarly to the `sample` method, decimated variables are fixed in
+    /// a factor graph, to keep the initial graph clone it before decimation
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, MaxProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let factor_scheduler = get_standard_factor_scheduler(0.);
+    /// let variable_scheduler = get_standard_variable_scheduler(0.);
+    ///
+    /// // A ferromagnetic chain with a field at its end
+    /// let mut fgb = new_ising_builder::<MaxProduct>(5, 5);
+    /// for i in 0..4 {
+    ///     fgb.add_factor(IsingFactor::new(1., 0., 0.), &[i, i + 1], &mut initializer).unwrap();
+    /// }
+    /// fgb.add_factor(IsingFactor::UnitFactor(-1.), &[4], &mut initializer).unwrap();
+    /// let mut fg = fgb.build();
+    /// fg.run_message_passing_parallel(100, 0, 1e-10, &factor_scheduler, &variable_scheduler).unwrap();
+    ///
+    /// let info = fg.decimate_most_confident(100, 0, 1e-10, &factor_scheduler, &variable_scheduler).unwrap();
+    /// assert_eq!(info.samples, vec![-1; 5]);
+    /// assert_eq!(info.decimation_order.len(), 5);
+    /// ```
+    pub fn decimate_most_confident(
+        &mut self,
+        max_iterations_number: usize,
+        min_iterations_number: usize,
+        threshold: f64,
+        factor_scheduler: &impl Fn(usize) -> F::Parameters,
+        variable_scheduler: &impl Fn(usize) -> V::Parameters,
+    ) -> FGResult<SamplingInfo<V::Sample>>
+    where
+        V: DecodableVariable,
+    {
+        let variables_number = self.variables.len();
+        let mut samples: Vec<Option<V::Sample>> = vec![None; variables_number];
+        let mut total_iterations_number = 0;
+        let mut iterations_per_variable = vec![0; variables_number];
+        let mut decimation_order = Vec::with_capacity(variables_number);
+        for decimated_number in 0..variables_number {
+            let var_index = self
+                .marginal_gap_ranking()
+                .into_iter()
+                .map(|gap| gap.var_index)
+                .find(|var_index| samples[*var_index].is_none())
+                .or_else(|| samples.iter().position(|sample| sample.is_none()))
+                .unwrap();
+            let sample = self.variables[var_index].argmax();
+            samples[var_index] = Some(sample);
+            decimation_order.push(var_index);
+            self.freeze_variable(&sample, var_index)?;
+            match self.run_message_passing_parallel(
+                max_iterations_number,
+                min_iterations_number,
+                threshold,
+                factor_scheduler,
+                variable_scheduler,
+            ) {
+                Ok(info) => {
+                    total_iterations_number += info.iterations_number;
+                    iterations_per_variable[var_index] = info.iterations_number;
+                }
+                Err(FGError::MessagePassingError {
+                    iterations_number,
+                    last_discrepancy,
+                    discrepancy_dynamics,
+                }) => {
+                    return Err(FGError::SamplingError {
+                        variables_number: decimated_number,
+                        total_iterations_number: total_iterations_number + iterations_number,
+                        last_discrepancy,
+                        discrepancy_dynamics,
+                    })
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(SamplingInfo {
+            samples: samples.into_iter().map(|s| s.unwrap()).collect(),
+            iterations_per_variable,
+            total_iterations_number,
+            pinned_variables_number: 0,
+            decimation_order,
+            clamped_variables: Vec::new(),
+            backtracks_number: 0,
+            log_weight: None,
+            energy: None,
+        })
+    }
+
+    /// Samples from a factor graph similarly to [`FactorGraph::sample`], but recovers
+    /// from message passing failures by backtracking: fixing factors of the last
+    /// `backtracking_depth` sampled variables are removed, messages are restored
+    /// to their state before these variables have been fixed and the variables are
+    /// sampled again with new random numbers. Zero depth restarts sampling from scratch
+    ///
+    /// # Arguments
+    ///
+    /// * `max_iterations_number` - A maximal number of iterations in a message passing algorithm
+    /// * `min_iterations_number` - A minimal number of iterations in a message passing algorithm
+    /// * `threshold` - A threshold specifying the convergence criterion
+    /// * `backtracking_depth` - A number of last sampled variables that are sampled again
+    ///   after a failure, zero means all variables
+    /// * `max_backtracks_number` - A maximal number of backtracks
+    /// * `rng` - A random numbers generator
+    /// * `factor_scheduler` - A scheduler of a factor's messages update rule hyper-parameters
+    /// * `variable_scheduler` - A scheduler of a variable's messages update rule hyper-parameters
+    ///
+    /// # Notes
+    ///
+    /// Messages are saved before fixing each of the last `backtracking_depth` variables,
+    /// thus memory consumption grows linearly with the depth. If message passing fails
+    /// after `max_backtracks_number` backtracks or any other error appears, a factor graph
+    /// is restored to its state before sampling and the error is returned. Iterations
+    /// of failed message passing runs are counted in `total_iterations_number`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use rand::thread_rng;
+    ///
+    /// let mut rng = thread_rng();
+    /// let mut initializer = random_message_initializer(rng.clone(), -0.5, 0.5);
+    /// let factor_scheduler = get_standard_factor_scheduler(0.);
+    /// let variable_scheduler = get_standard_variable_scheduler(0.);
+    /// let mut fgb = new_ising_builder::<SumProduct>(3, 3);
+    /// for i in 0..3 {
+    ///     fgb.add_factor(IsingFactor::new(0.5, 0.1, 0.), &[i, (i + 1) % 3], &mut initializer).unwrap();
+    /// }
+    /// let mut fg = fgb.build();
+    /// fg.run_message_passing_parallel(100, 0, 1e-10, &factor_scheduler, &variable_scheduler).unwrap();
+    /// let info = fg.sample_with_backtracking(
+    ///     100,
+    ///     0,
+    ///     1e-10,
+    ///     2,
+    ///     10,
+    ///     &mut rng,
+    ///     &factor_scheduler,
+    ///     &variable_scheduler,
+    /// ).unwrap();
+    /// assert_eq!(info.samples.len(), 3);
+    /// assert_eq!(info.backtracks_number, 0);
+    /// ```
+    #[allow(clippy::too_many_arguments)]
+    pub fn sample_with_backtracking(
+        &mut self,
+        max_iterations_number: usize,
+        min_iterations_number: usize,
+        threshold: f64,
+        backtracking_depth: usize,
+        max_backtracks_number: usize,
+        rng: &mut impl Rng,
+        factor_scheduler: &impl Fn(usize) -> F::Parameters,
+        variable_scheduler: &impl Fn(usize) -> V::Parameters,
+    ) -> FGResult<SamplingInfo<V::Sample>> {
+        let variables_number = self.variables.len();
+        let initial_snapshot = self.snapshot();
+        // snapshots of messages taken before fixing the last sampled variables
+        let mut snapshots = VecDeque::with_capacity(backtracking_depth + 1);
+        let mut samples = Vec::with_capacity(variables_number);
+        let mut total_iterations_number = 0;
+        let mut iterations_per_variable = vec![0; variables_number];
+        let mut backtracks_number = 0;
+        while samples.len() < variables_number {
+            let var_index = samples.len();
+            if backtracking_depth > 0 {
+                if snapshots.len() == backtracking_depth {
+                    snapshots.pop_front();
+                }
+                snapshots.push_back((var_index, self.snapshot()));
+            }
+            let sample = self.variables[var_index].sample(rng);
+            samples.push(sample);
+            self.freeze_variable(&sample, var_index).unwrap();
+            match self.run_message_passing_parallel(
+                max_iterations_number,
+                min_iterations_number,
+                threshold,
+                factor_scheduler,
+                variable_scheduler,
+            ) {
+                Ok(info) => {
+                    total_iterations_number += info.iterations_number;
+                    iterations_per_variable[var_index] = info.iterations_number;
+                }
+                Err(FGError::MessagePassingError {
+                    iterations_number,
+                    last_discrepancy,
+                    discrepancy_dynamics,
+                }) => {
+                    total_iterations_number += iterations_number;
+                    if backtracks_number == max_backtracks_number {
+                        self.rollback(&initial_snapshot).unwrap();
+                        return Err(FGError::SamplingError {
+                            variables_number: var_index,
+                            total_iterations_number,
+                            last_discrepancy,
+                            discrepancy_dynamics,
+                        });
+                    }
+                    backtracks_number += 1;
+                    match snapshots.drain(..).next() {
+                        Some((sampled_number, snapshot)) => {
+                            self.rollback(&snapshot).unwrap();
+                            samples.truncate(sampled_number);
+                        }
+                        None => {
+                            self.rollback(&initial_snapshot).unwrap();
+                            samples.clear();
+                        }
+                    }
+                }
+                Err(err) => {
+                    self.rollback(&initial_snapshot).unwrap();
+                    return Err(err);
+                }
+            }
+        }
+        Ok(SamplingInfo {
+            samples,
+            iterations_per_variable,
+            total_iterations_number,
+            pinned_variables_number: 0,
+            decimation_order: (0..variables_number).collect(),
+            clamped_variables: Vec::new(),
+            backtracks_number,
+            log_weight: None,
+            energy: None,
+        })
+    }
+}
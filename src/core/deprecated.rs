@@ -0,0 +1,583 @@
+use std::{
+    ops::ControlFlow,
+    time::{Duration, Instant},
+};
+
+use rand::Rng;
+
+use crate::core::{
+    damping::{AdaptiveDamping, DampingMode, StochasticUpdates},
+    factor::Factor,
+    factor_graph::{
+        FGError, FGResult, FactorGraph, MessagePassingInfo, SamplingInfo, SamplingProgress,
+    },
+    message::DampableMessage,
+    observable::Observable,
+    options::MessagePassingOptions,
+    publisher::{MarginalsPublisher, PublishedMarginals},
+    variable::Variable,
+};
+
+// ------------------------------------------------------------------------------------------
+
+// Variants of message passing and sampling that predate `MessagePassingOptions`,
+// each of them is a shim configuring options
+impl<F, V> FactorGraph<F, V>
+where
+    F: Factor,
+    V: Variable<Message = F::Message>,
+{
+    /// Runs a message passing algorithm in parallel similarly to
+    /// [`FactorGraph::run_message_passing_parallel`], but a process is considered
+    /// as successful only if the discrepancy stays below the threshold for a number
+    /// of consecutive iterations. It prevents premature stops of oscillatory dynamics
+    /// whose discrepancy momentarily dips below the threshold.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_iterations_number` - A maximal number of iterations, if a process
+    ///   does not converge before reaching this number of iterations, it fails
+    /// * `min_iterations_number` - A minimal number of iterations that is performed
+    ///   disregards reaching the convergence criterion
+    /// * `threshold` - A threshold specifying the convergence criterion
+    /// * `convergence_window` - A number of consecutive iterations the discrepancy must
+    ///   stay below the threshold, zero is treated as one
+    /// * `factor_scheduler` - A scheduler of a factor's messages update rule hyper-parameters
+    /// * `variable_scheduler` - A scheduler of a variable's messages update rule hyper-parameters
+    #[deprecated(
+        note = "use `run_message_passing_with_options` with `MessagePassingOptions::with_convergence_window` instead"
+    )]
+    #[inline]
+    pub fn run_message_passing_parallel_windowed(
+        &mut self,
+        max_iterations_number: usize,
+        min_iterations_number: usize,
+        threshold: f64,
+        convergence_window: usize,
+        factor_scheduler: &impl Fn(usize) -> F::Parameters,
+        variable_scheduler: &impl Fn(usize) -> V::Parameters,
+    ) -> FGResult<MessagePassingInfo> {
+        self.run_scheduled_since(
+            Instant::now(),
+            &mut MessagePassingOptions::new(factor_scheduler, variable_scheduler)
+                .with_max_iterations_number(max_iterations_number)
+                .with_min_iterations_number(min_iterations_number)
+                .with_threshold(threshold)
+                .with_convergence_window(convergence_window),
+        )
+    }
+
+    /// Runs a message passing algorithm in parallel similarly to
+    /// [`FactorGraph::run_message_passing_parallel`], but each iteration updates only
+    /// a random subset of factors, other factors keep their messages. It is useful for
+    /// extremely large dense graphs, where full sweeps are too expensive and a stochastic
+    /// fixed point iteration suffices. Factors are selected independently with a given
+    /// probability, thus a factor is updated once per `1 / batch_fraction` iterations on
+    /// average. Damping is scaled accordingly: messages of a selected factor are damped
+    /// with the coefficient `max(0, 1 - (1 - damping) / batch_fraction)`, so that messages
+    /// move on average as in full sweeps damped with the coefficient `damping`. Since
+    /// the discrepancy of a single iteration involves only selected factors, it must stay
+    /// below the threshold for `ceil(1 / batch_fraction)` consecutive iterations.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_iterations_number` - A maximal number of iterations, if a process
+    ///   does not converge before reaching this number of iterations, it fails
+    /// * `min_iterations_number` - A minimal number of iterations that is performed
+    ///   disregards reaching the convergence criterion
+    /// * `threshold` - A threshold specifying the convergence criterion
+    /// * `batch_fraction` - A fraction of factors updated per iteration, it is clipped
+    ///   to `[1 / factors_number, 1]`
+    /// * `damping` - A damping coefficient of full sweeps that is emulated, it is applied on top
+    ///   of the update rule, thus one typically sets the scheduled damping to zero
+    /// * `rng` - A random numbers generator
+    /// * `factor_scheduler` - A scheduler of a factor's messages update rule hyper-parameters
+    /// * `variable_scheduler` - A scheduler of a variable's messages update rule hyper-parameters
+    #[deprecated(
+        note = "use `run_message_passing_with_options` with `MessagePassingOptions::with_damping` and `DampingMode::Stochastic` instead"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    #[inline]
+    pub fn run_message_passing_parallel_stochastic(
+        &mut self,
+        max_iterations_number: usize,
+        min_iterations_number: usize,
+        threshold: f64,
+        batch_fraction: f64,
+        damping: f64,
+        rng: &mut impl Rng,
+        factor_scheduler: &impl Fn(usize) -> F::Parameters,
+        variable_scheduler: &impl Fn(usize) -> V::Parameters,
+    ) -> FGResult<MessagePassingInfo>
+    where
+        F::Message: DampableMessage,
+    {
+        self.run_message_passing_with_options(
+            &mut MessagePassingOptions::new(factor_scheduler, variable_scheduler)
+                .with_max_iterations_number(max_iterations_number)
+                .with_min_iterations_number(min_iterations_number)
+                .with_threshold(threshold)
+                .with_damping(DampingMode::Stochastic(StochasticUpdates {
+                    batch_fraction,
+                    damping,
+                    seed: rng.gen(),
+                })),
+        )
+    }
+
+    /// Runs a message passing algorithm in parallel similarly to
+    /// [`FactorGraph::run_message_passing_parallel`], calling an observer after
+    /// each iteration. The observer gets read-only access to the factor graph,
+    /// thus it can inspect messages and marginals, and it can stop message
+    /// passing by returning `ControlFlow::Break`. In this case the method
+    /// returns `FGError::Interrupted`.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_iterations_number` - A maximal number of iterations, if a process
+    ///   does not converge before reaching this number of iterations, it fails
+    /// * `min_iterations_number` - A minimal number of iterations that is performed
+    ///   disregards reaching the convergence criterion
+    /// * `threshold` - A threshold specifying the convergence criterion
+    /// * `factor_scheduler` - A scheduler of a factor's messages update rule hyper-parameters
+    /// * `variable_scheduler` - A scheduler of a variable's messages update rule hyper-parameters
+    /// * `observer` - A function taking a factor graph, an iteration number (starts from 0)
+    ///   and the iteration's discrepancy
+    #[deprecated(
+        note = "use `run_message_passing_with_options` with `MessagePassingOptions::with_observer` instead"
+    )]
+    #[inline]
+    pub fn run_message_passing_parallel_observed(
+        &mut self,
+        max_iterations_number: usize,
+        min_iterations_number: usize,
+        threshold: f64,
+        factor_scheduler: &impl Fn(usize) -> F::Parameters,
+        variable_scheduler: &impl Fn(usize) -> V::Parameters,
+        observer: impl FnMut(&Self, usize, f64) -> ControlFlow<()>,
+    ) -> FGResult<MessagePassingInfo> {
+        self.run_scheduled_since(
+            Instant::now(),
+            &mut MessagePassingOptions::new(factor_scheduler, variable_scheduler)
+                .with_max_iterations_number(max_iterations_number)
+                .with_min_iterations_number(min_iterations_number)
+                .with_threshold(threshold)
+                .with_observer(observer),
+        )
+    }
+
+    /// Runs a message passing algorithm in parallel similarly to
+    /// [`FactorGraph::run_message_passing_parallel`], additionally publishing
+    /// variable marginals every `publishing_period` iterations to a publisher.
+    /// Other threads can read the latest published marginals through a clone
+    /// of the publisher while message passing is in progress. Marginals of
+    /// the final iteration are always published.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_iterations_number` - A maximal number of iterations, if a process
+    ///   does not converge before reaching this number of iterations, it fails
+    /// * `min_iterations_number` - A minimal number of iterations that is performed
+    ///   disregards reaching the convergence criterion
+    /// * `threshold` - A threshold specifying the convergence criterion
+    /// * `factor_scheduler` - A scheduler of a factor's messages update rule hyper-parameters
+    /// * `variable_scheduler` - A scheduler of a variable's messages update rule hyper-parameters
+    /// * `publisher` - A publisher marginals are sent to
+    /// * `publishing_period` - A number of iterations between two subsequent publications,
+    ///   zero is treated as one
+    #[deprecated(
+        note = "use `run_message_passing_with_options` with an observer publishing marginals instead"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    #[inline]
+    pub fn run_message_passing_parallel_publishing(
+        &mut self,
+        max_iterations_number: usize,
+        min_iterations_number: usize,
+        threshold: f64,
+        factor_scheduler: &impl Fn(usize) -> F::Parameters,
+        variable_scheduler: &impl Fn(usize) -> V::Parameters,
+        publisher: &MarginalsPublisher<V::Marginal>,
+        publishing_period: usize,
+    ) -> FGResult<MessagePassingInfo> {
+        let publishing_period = publishing_period.max(1);
+        let publish = |fg: &Self, iteration: usize, discrepancy: f64| {
+            publisher.publish(PublishedMarginals {
+                iteration,
+                discrepancy,
+                marginals: fg.variable_marginals(),
+            })
+        };
+        let result = self.run_scheduled_since(
+            Instant::now(),
+            &mut MessagePassingOptions::new(factor_scheduler, variable_scheduler)
+                .with_max_iterations_number(max_iterations_number)
+                .with_min_iterations_number(min_iterations_number)
+                .with_threshold(threshold)
+                .with_observer(|fg: &Self, iteration, discrepancy| {
+                    if (iteration + 1) % publishing_period == 0 {
+                        publish(fg, iteration, discrepancy);
+                    }
+                    ControlFlow::Continue(())
+                }),
+        );
+        let (iteration, discrepancy) = match &result {
+            Ok(info) => (info.iterations_number, info.last_discrepancy),
+            Err(FGError::MessagePassingError {
+                iterations_number,
+                last_discrepancy,
+                ..
+            }) => (iterations_number.saturating_sub(1), *last_discrepancy),
+            Err(_) => return result,
+        };
+        if (iteration + 1) % publishing_period != 0 {
+            publish(self, iteration, discrepancy);
+        }
+        result
+    }
+
+    /// Runs a message passing algorithm in parallel similarly to
+    /// [`FactorGraph::run_message_passing_parallel`], additionally recording variable
+    /// marginals every `recording_period` iterations
+    ///
+    /// # Arguments
+    ///
+    /// * `max_iterations_number` - A maximal number of iterations, if a process
+    ///   does not converge before reaching this number of iterations, it fails
+    /// * `min_iterations_number` - A minimal number of iterations that is performed
+    ///   disregards reaching the convergence criterion
+    /// * `threshold` - A threshold specifying the convergence criterion
+    /// * `factor_scheduler` - A scheduler of a factor's messages update rule hyper-parameters
+    /// * `variable_scheduler` - A scheduler of a variable's messages update rule hyper-parameters
+    /// * `recording_period` - A number of iterations between two subsequent records,
+    ///   zero is treated as one
+    #[deprecated(
+        note = "use `run_message_passing_with_options` with `MessagePassingOptions::with_marginals_recording` instead"
+    )]
+    #[inline]
+    pub fn run_message_passing_parallel_recording(
+        &mut self,
+        max_iterations_number: usize,
+        min_iterations_number: usize,
+        threshold: f64,
+        factor_scheduler: &impl Fn(usize) -> F::Parameters,
+        variable_scheduler: &impl Fn(usize) -> V::Parameters,
+        recording_period: usize,
+    ) -> FGResult<MessagePassingInfo> {
+        self.run_scheduled_since(
+            Instant::now(),
+            &mut MessagePassingOptions::new(factor_scheduler, variable_scheduler)
+                .with_max_iterations_number(max_iterations_number)
+                .with_min_iterations_number(min_iterations_number)
+                .with_threshold(threshold)
+                .with_marginals_recording(recording_period),
+        )
+    }
+
+    /// Runs a message passing algorithm in parallel similarly to
+    /// [`FactorGraph::run_message_passing_parallel`], additionally evaluating observables
+    /// every `stride` iterations. Time series of observables are returned in
+    /// [`MessagePassingInfo::observables`] in the order of observables, observables
+    /// are always evaluated at the final iteration.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_iterations_number` - A maximal number of iterations, if a process
+    ///   does not converge before reaching this number of iterations, it fails
+    /// * `min_iterations_number` - A minimal number of iterations that is performed
+    ///   disregards reaching the convergence criterion
+    /// * `threshold` - A threshold specifying the convergence criterion
+    /// * `factor_scheduler` - A scheduler of a factor's messages update rule hyper-parameters
+    /// * `variable_scheduler` - A scheduler of a variable's messages update rule hyper-parameters
+    /// * `observables` - Observables to evaluate
+    /// * `stride` - A number of iterations between two subsequent evaluations,
+    ///   zero is treated as one
+    #[deprecated(
+        note = "use `run_message_passing_with_options` with `MessagePassingOptions::with_observables` instead"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    #[inline]
+    pub fn run_message_passing_parallel_measuring(
+        &mut self,
+        max_iterations_number: usize,
+        min_iterations_number: usize,
+        threshold: f64,
+        factor_scheduler: &impl Fn(usize) -> F::Parameters,
+        variable_scheduler: &impl Fn(usize) -> V::Parameters,
+        observables: &[&dyn Observable<F, V>],
+        stride: usize,
+    ) -> FGResult<MessagePassingInfo> {
+        self.run_scheduled_since(
+            Instant::now(),
+            &mut MessagePassingOptions::new(factor_scheduler, variable_scheduler)
+                .with_max_iterations_number(max_iterations_number)
+                .with_min_iterations_number(min_iterations_number)
+                .with_threshold(threshold)
+                .with_observables(observables, stride),
+        )
+    }
+
+    /// Runs a message passing algorithm in parallel similarly to
+    /// [`FactorGraph::run_message_passing_parallel`], but with damping
+    /// adapted individually for each edge: edges whose messages oscillate
+    /// get stronger damping, edges whose messages change monotonically get
+    /// weaker damping. Damping coefficients persist between runs.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_iterations_number` - A maximal number of iterations, if a process
+    ///   does not converge before reaching this number of iterations, it fails
+    /// * `min_iterations_number` - A minimal number of iterations that is performed
+    ///   disregards reaching the convergence criterion
+    /// * `threshold` - A threshold specifying the convergence criterion
+    /// * `factor_scheduler` - A scheduler of a factor's messages update rule hyper-parameters.
+    ///   Adaptive damping is applied on top of the update rule, thus one typically
+    ///   sets the scheduled damping to zero
+    /// * `variable_scheduler` - A scheduler of a variable's messages update rule hyper-parameters.
+    ///   Adaptive damping is applied on top of the update rule, thus one typically
+    ///   sets the scheduled damping to zero
+    /// * `damping` - Hyper-parameters of adaptive damping
+    #[deprecated(
+        note = "use `run_message_passing_with_options` with `MessagePassingOptions::with_damping` and `DampingMode::Adaptive` instead"
+    )]
+    #[inline]
+    pub fn run_message_passing_parallel_adaptive(
+        &mut self,
+        max_iterations_number: usize,
+        min_iterations_number: usize,
+        threshold: f64,
+        factor_scheduler: &impl Fn(usize) -> F::Parameters,
+        variable_scheduler: &impl Fn(usize) -> V::Parameters,
+        damping: &AdaptiveDamping,
+    ) -> FGResult<MessagePassingInfo>
+    where
+        F::Message: DampableMessage,
+    {
+        self.run_message_passing_with_options(
+            &mut MessagePassingOptions::new(factor_scheduler, variable_scheduler)
+                .with_max_iterations_number(max_iterations_number)
+                .with_min_iterations_number(min_iterations_number)
+                .with_threshold(threshold)
+                .with_damping(DampingMode::Adaptive(*damping)),
+        )
+    }
+
+    /// Runs a message passing algorithm in parallel similarly to
+    /// [`FactorGraph::run_message_passing_parallel`], but a process is also considered
+    /// as successful once variable marginals stop changing, i.e. the maximal discrepancy
+    /// between marginals of two subsequent iterations is less than a tolerance. On
+    /// degenerate instances marginals often stabilize long before individual messages do.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_iterations_number` - A maximal number of iterations, if a process
+    ///   does not converge before reaching this number of iterations, it fails
+    /// * `min_iterations_number` - A minimal number of iterations that is performed
+    ///   disregards reaching any of the convergence criteria
+    /// * `threshold` - A threshold specifying the convergence criterion of messages
+    /// * `marginal_tolerance` - A tolerance specifying the stability criterion of marginals
+    /// * `factor_scheduler` - A scheduler of a factor's messages update rule hyper-parameters
+    /// * `variable_scheduler` - A scheduler of a variable's messages update rule hyper-parameters
+    ///
+    /// # Notes
+    ///
+    /// Marginals are compared by [`Variable::marginal_discrepancy`], variables whose marginals
+    /// can not be compared are never stable. A marginal of the previous iteration is kept
+    /// per variable, thus each iteration computes all marginals
+    #[deprecated(
+        note = "use `run_message_passing_with_options` with `MessagePassingOptions::with_marginal_tolerance` instead"
+    )]
+    #[inline]
+    pub fn run_message_passing_parallel_until_stable(
+        &mut self,
+        max_iterations_number: usize,
+        min_iterations_number: usize,
+        threshold: f64,
+        marginal_tolerance: f64,
+        factor_scheduler: &impl Fn(usize) -> F::Parameters,
+        variable_scheduler: &impl Fn(usize) -> V::Parameters,
+    ) -> FGResult<MessagePassingInfo> {
+        self.run_scheduled_since(
+            Instant::now(),
+            &mut MessagePassingOptions::new(factor_scheduler, variable_scheduler)
+                .with_max_iterations_number(max_iterations_number)
+                .with_min_iterations_number(min_iterations_number)
+                .with_threshold(threshold)
+                .with_marginal_tolerance(marginal_tolerance),
+        )
+    }
+
+    /// Runs a message passing algorithm in parallel similarly to
+    /// [`FactorGraph::run_message_passing_parallel`], but additionally stops once
+    /// a wall-clock time budget is exceeded. Iteration limits are poor proxies for
+    /// runtime on heterogeneous graphs, a budget bounds the runtime directly
+    ///
+    /// # Arguments
+    ///
+    /// * `max_iterations_number` - A maximal number of iterations, if a process
+    ///   does not converge before reaching this number of iterations, it fails
+    /// * `min_iterations_number` - A minimal number of iterations that is performed
+    ///   disregards reaching the convergence criterion
+    /// * `threshold` - A threshold specifying the convergence criterion
+    /// * `budget` - A maximal time of a run
+    /// * `factor_scheduler` - A scheduler of a factor's messages update rule hyper-parameters
+    /// * `variable_scheduler` - A scheduler of a variable's messages update rule hyper-parameters
+    ///
+    /// # Notes
+    ///
+    /// The budget is checked after each iteration, thus a run takes at most one
+    /// iteration longer than the budget. If the budget is exceeded, `FGError::TimedOut`
+    /// is returned and messages of the last iteration are kept, e.g. for a warm restart
+    #[deprecated(
+        note = "use `run_message_passing_with_options` with `MessagePassingOptions::with_budget` instead"
+    )]
+    #[inline]
+    pub fn run_message_passing_parallel_with_budget(
+        &mut self,
+        max_iterations_number: usize,
+        min_iterations_number: usize,
+        threshold: f64,
+        budget: Duration,
+        factor_scheduler: &impl Fn(usize) -> F::Parameters,
+        variable_scheduler: &impl Fn(usize) -> V::Parameters,
+    ) -> FGResult<MessagePassingInfo> {
+        self.run_scheduled_since(
+            Instant::now(),
+            &mut MessagePassingOptions::new(factor_scheduler, variable_scheduler)
+                .with_max_iterations_number(max_iterations_number)
+                .with_min_iterations_number(min_iterations_number)
+                .with_threshold(threshold)
+                .with_budget(budget),
+        )
+    }
+
+    /// Samples from a factor graph similarly to [`FactorGraph::sample`], but after
+    /// each message passing detects connected components of not yet sampled variables
+    /// whose marginals are all pinned, i.e. concentrated on a single value up
+    /// to a tolerance, and fixes all their variables at once without further
+    /// message passing runs. For models with hard or strong constraints it collapses
+    /// the tail of the sampling loop to a handful of message passing runs.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_iterations_number` - A maximal number of iterations in a message passing algorithm
+    /// * `min_iterations_number` - A minimal number of iterations in a message passing algorithm
+    /// * `threshold` - A threshold specifying the convergence criterion
+    /// * `pinning_tolerance` - A maximal probability of all values of a variable except the
+    ///   most probable one for a variable to be considered as pinned. Zero disables pinning
+    /// * `rng` - A random numbers generator
+    /// * `factor_scheduler` - A scheduler of a factor's messages update rule hyper-parameters
+    /// * `variable_scheduler` - A scheduler of a variable's messages update rule hyper-parameters
+    #[deprecated(
+        note = "use `sample_with_options` with `MessagePassingOptions::with_pinning_tolerance` instead"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    #[inline]
+    pub fn sample_with_pinning(
+        &mut self,
+        max_iterations_number: usize,
+        min_iterations_number: usize,
+        threshold: f64,
+        pinning_tolerance: f64,
+        rng: &mut impl Rng,
+        factor_scheduler: &impl Fn(usize) -> F::Parameters,
+        variable_scheduler: &impl Fn(usize) -> V::Parameters,
+    ) -> FGResult<SamplingInfo<V::Sample>> {
+        self.sample_clamped(
+            &[],
+            rng,
+            &mut MessagePassingOptions::new(factor_scheduler, variable_scheduler)
+                .with_max_iterations_number(max_iterations_number)
+                .with_min_iterations_number(min_iterations_number)
+                .with_threshold(threshold)
+                .with_pinning_tolerance(pinning_tolerance),
+            Self::run_scheduled_since,
+        )
+    }
+
+    /// Samples from a factor graph similarly to [`FactorGraph::sample`], additionally
+    /// reporting progress after each decimation step, e.g. to drive a progress bar
+    /// during hours long sampling of a large factor graph
+    ///
+    /// # Arguments
+    ///
+    /// * `max_iterations_number` - A maximal number of iterations in a message passing algorithm
+    /// * `min_iterations_number` - A minimal number of iterations in a message passing algorithm
+    /// * `threshold` - A threshold specifying the convergence criterion
+    /// * `rng` - A random numbers generator
+    /// * `factor_scheduler` - A scheduler of a factor's messages update rule hyper-parameters
+    /// * `variable_scheduler` - A scheduler of a variable's messages update rule hyper-parameters
+    /// * `progress` - A function called with the progress of sampling after each
+    ///   decimation step
+    #[deprecated(
+        note = "use `sample_with_options` with `MessagePassingOptions::with_progress` instead"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    #[inline]
+    pub fn sample_with_progress(
+        &mut self,
+        max_iterations_number: usize,
+        min_iterations_number: usize,
+        threshold: f64,
+        rng: &mut impl Rng,
+        factor_scheduler: &impl Fn(usize) -> F::Parameters,
+        variable_scheduler: &impl Fn(usize) -> V::Parameters,
+        progress: impl FnMut(&SamplingProgress),
+    ) -> FGResult<SamplingInfo<V::Sample>> {
+        self.sample_clamped(
+            &[],
+            rng,
+            &mut MessagePassingOptions::new(factor_scheduler, variable_scheduler)
+                .with_max_iterations_number(max_iterations_number)
+                .with_min_iterations_number(min_iterations_number)
+                .with_threshold(threshold)
+                .with_progress(progress),
+            Self::run_scheduled_since,
+        )
+    }
+
+    /// Samples from a factor graph similarly to [`FactorGraph::sample`], but stops
+    /// once a wall-clock time budget of the whole sampling is exceeded
+    ///
+    /// # Arguments
+    ///
+    /// * `max_iterations_number` - A maximal number of iterations in a message passing algorithm
+    /// * `min_iterations_number` - A minimal number of iterations in a message passing algorithm
+    /// * `threshold` - A threshold specifying the convergence criterion
+    /// * `budget` - A maximal time of sampling
+    /// * `rng` - A random numbers generator
+    /// * `factor_scheduler` - A scheduler of a factor's messages update rule hyper-parameters
+    /// * `variable_scheduler` - A scheduler of a variable's messages update rule hyper-parameters
+    ///
+    /// # Notes
+    ///
+    /// The budget is checked after each iteration of message passing. If it is exceeded,
+    /// `FGError::TimedOut` reports the number of variables sampled before the interrupted
+    /// message passing run, sampled variables stay frozen in a factor graph
+    #[deprecated(
+        note = "use `sample_with_options` with `MessagePassingOptions::with_budget` instead"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    #[inline]
+    pub fn sample_with_budget(
+        &mut self,
+        max_iterations_number: usize,
+        min_iterations_number: usize,
+        threshold: f64,
+        budget: Duration,
+        rng: &mut impl Rng,
+        factor_scheduler: &impl Fn(usize) -> F::Parameters,
+        variable_scheduler: &impl Fn(usize) -> V::Parameters,
+    ) -> FGResult<SamplingInfo<V::Sample>> {
+        self.sample_clamped(
+            &[],
+            rng,
+            &mut MessagePassingOptions::new(factor_scheduler, variable_scheduler)
+                .with_max_iterations_number(max_iterations_number)
+                .with_min_iterations_number(min_iterations_number)
+                .with_threshold(threshold)
+                .with_budget(budget),
+            Self::run_scheduled_since,
+        )
+    }
+}
@@ -0,0 +1,170 @@
+use ndarray::{Array1, ArrayD, Dimension};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::core::{
+    factor::Factor,
+    factor_graph::{FGError, FGResult, FactorGraph},
+    variable::Variable,
+};
+
+// ------------------------------------------------------------------------------------------
+
+/// Local inconsistency of a factor's beliefs
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FactorInconsistency {
+    /// Index of a factor
+    pub factor_index: usize,
+
+    /// Indices of variables adjacent to a factor
+    pub var_indices: Vec<usize>,
+
+    /// Kullback-Leibler divergence between a factor marginal and
+    /// the product of marginals of adjacent variables
+    pub divergence: f64,
+}
+
+impl<F, V> FactorGraph<F, V>
+where
+    F: Factor<Marginal = ArrayD<f64>>,
+    V: Variable<Message = F::Message, Marginal = Array1<f64>>,
+{
+    /// Ranks factors by local inconsistency of beliefs, i.e. by the
+    /// Kullback-Leibler divergence between a factor marginal and the product
+    /// of marginals of its variables. Factors with large divergence indicate
+    /// strong local constraints that disagree with the rest of a model, which
+    /// is useful for pinpointing modeling errors and near-contradictory evidence.
+    /// Factors are sorted in descending order of divergence.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::FactorGraphBuilder;
+    /// use gmrs::ising::{IsingFactor, IsingVariable, SumProduct, random_message_initializer};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use rand::thread_rng;
+    ///
+    /// // Aliases to shorten types
+    /// type Factor = IsingFactor<SumProduct>;
+    /// type Variable = IsingVariable<SumProduct>;
+    ///
+    /// // Messages initializer
+    /// let rng = thread_rng();
+    /// let mut initializer = random_message_initializer(rng, -0.5, 0.5);
+    ///
+    /// // Message passing schedulers
+    /// let factor_scheduler = get_standard_factor_scheduler(0.5);
+    /// let variable_scheduler = get_standard_variable_scheduler(0.5);
+    ///
+    /// // A chain with one strong coupling
+    /// let mut fgb = FactorGraphBuilder::<Factor, Variable>::new_with_variables(vec![IsingVariable::new(); 4], 3);
+    /// fgb.add_factor(IsingFactor::new(0.1, 0., 0.), &[0, 1], &mut initializer).unwrap();
+    /// fgb.add_factor(IsingFactor::new(3., 0., 0.), &[1, 2], &mut initializer).unwrap();
+    /// fgb.add_factor(IsingFactor::new(-0.2, 0., 0.), &[2, 3], &mut initializer).unwrap();
+    /// let mut fg = fgb.build();
+    /// let _ = fg.run_message_passing_parallel(
+    ///     100,
+    ///     0,
+    ///     1e-10,
+    ///     &factor_scheduler,
+    ///     &variable_scheduler,
+    /// ).unwrap();
+    ///
+    /// let ranking = fg.factor_inconsistency_ranking();
+    /// assert_eq!(ranking[0].factor_index, 1);
+    /// assert_eq!(ranking[0].var_indices, vec![1, 2]);
+    /// ```
+    pub fn factor_inconsistency_ranking(&self) -> Vec<FactorInconsistency> {
+        let variable_marginals = self.variable_marginals();
+        let mut ranking: Vec<_> = self
+            .factors
+            .iter()
+            .enumerate()
+            .map(|(factor_index, factor)| {
+                let factor_marginal = factor.marginal();
+                let var_indices = factor.var_node_indices.clone();
+                let mut divergence = 0f64;
+                for (index, p) in factor_marginal.indexed_iter() {
+                    if *p <= 0f64 {
+                        continue;
+                    }
+                    let q: f64 = index
+                        .as_array_view()
+                        .iter()
+                        .zip(&var_indices)
+                        .map(|(state, var_index)| variable_marginals[*var_index][*state])
+                        .product();
+                    divergence += p * (p / q).ln();
+                }
+                FactorInconsistency {
+                    factor_index,
+                    var_indices,
+                    divergence,
+                }
+            })
+            .collect();
+        ranking.sort_by(|lhs, rhs| rhs.divergence.total_cmp(&lhs.divergence));
+        ranking
+    }
+}
+
+impl<F, V> FactorGraph<F, V>
+where
+    F: Factor,
+    V: Variable<Message = F::Message>,
+{
+    /// Returns indices of factors whose product with received messages vanishes,
+    /// i.e. factors contradicting evidence (e.g. frozen variables) propagated to
+    /// them. A non-empty set means that the model has zero probability and its
+    /// marginals are not defined.
+    ///
+    /// # Notes
+    ///
+    /// Message passing checks contradictions lazily, i.e. only if messages diverge
+    /// or do not converge, and returns `FGError::ContradictionError`. Contradictions
+    /// keeping messages finite, e.g. variables frozen to incompatible values,
+    /// are found by calling this method explicitly
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, IsingFactor, IsingMessage, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    ///
+    /// // A ferromagnetic chain of infinitely strong couplings
+    /// let mut fgb = new_ising_builder::<SumProduct>(3, 2);
+    /// fgb.add_factor(IsingFactor::new(1e30, 0., 0.), &[0, 1], &mut || IsingMessage(0.)).unwrap();
+    /// fgb.add_factor(IsingFactor::new(1e30, 0., 0.), &[1, 2], &mut || IsingMessage(0.)).unwrap();
+    /// let mut fg = fgb.build();
+    /// // ends of the chain are frozen to opposite values
+    /// fg.freeze_variable(&1, 0).unwrap();
+    /// fg.freeze_variable(&-1, 2).unwrap();
+    /// let factor_scheduler = get_standard_factor_scheduler(0.);
+    /// let variable_scheduler = get_standard_variable_scheduler(0.);
+    /// fg.run_message_passing_parallel(100, 0, 1e-10, &factor_scheduler, &variable_scheduler).unwrap();
+    /// assert!(!fg.contradictory_factors().is_empty());
+    /// ```
+    pub fn contradictory_factors(&self) -> Vec<usize> {
+        self.factors
+            .iter()
+            .enumerate()
+            .filter(|(_, factor)| factor.factor.is_contradictory(&factor.receivers))
+            .map(|(fac_index, _)| fac_index)
+            .collect()
+    }
+
+    // Returns an error listing contradictory factors if there are any, factors
+    // are scanned without allocations unless a contradiction is found
+    pub(crate) fn check_contradictions(&self) -> FGResult<()> {
+        if self
+            .factors
+            .iter()
+            .any(|factor| factor.factor.is_contradictory(&factor.receivers))
+        {
+            Err(FGError::ContradictionError(self.contradictory_factors()))
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use std::fmt::Write;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::core::{factor::Factor, factor_graph::FactorGraph, variable::Variable};
+
+// ------------------------------------------------------------------------------------------
+
+/// Options of a GraphViz export of a factor graph
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DotOptions {
+    /// Labels of variable nodes, variable indices are used if None
+    pub variable_labels: Option<Vec<String>>,
+
+    /// Labels of factor nodes, factor indices are used if None
+    pub factor_labels: Option<Vec<String>>,
+
+    /// Whether to add parameters (debug representation) of factors to factor labels
+    pub factor_parameters: bool,
+
+    /// Whether to label edges by current messages in both directions
+    pub messages: bool,
+}
+
+// escapes a string to be used inside a quoted DOT identifier
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn label(labels: &Option<Vec<String>>, index: usize, prefix: &str) -> String {
+    labels
+        .as_ref()
+        .and_then(|labels| labels.get(index).cloned())
+        .unwrap_or_else(|| format!("{}{}", prefix, index))
+}
+
+impl<F, V> FactorGraph<F, V>
+where
+    F: Factor,
+    V: Variable<Message = F::Message>,
+{
+    /// Returns a GraphViz (DOT) description of a factor graph, where
+    /// variables are circles and factors are boxes
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::FactorGraphBuilder;
+    /// use gmrs::ising::{IsingFactor, IsingVariable, SumProduct, random_message_initializer};
+    /// use rand::thread_rng;
+    ///
+    /// // Aliases to shorten types
+    /// type Factor = IsingFactor<SumProduct>;
+    /// type Variable = IsingVariable<SumProduct>;
+    ///
+    /// // Messages initializer
+    /// let rng = thread_rng();
+    /// let mut initializer = random_message_initializer(rng, -0.5, 0.5);
+    ///
+    /// let mut fgb = FactorGraphBuilder::<Factor, Variable>::new_with_variables(vec![IsingVariable::new(); 2], 1);
+    /// fgb.add_factor(IsingFactor::new(0.5, -0.5, 0.5), &[0, 1], &mut initializer).unwrap();
+    /// let fg = fgb.build();
+    /// let dot = fg.to_dot();
+    /// assert!(dot.starts_with("graph factor_graph {"));
+    /// assert!(dot.contains("f0 -- v1"));
+    /// ```
+    #[inline]
+    pub fn to_dot(&self) -> String {
+        self.to_dot_with(&DotOptions::default())
+    }
+
+    /// Returns a GraphViz (DOT) description of a factor graph with
+    /// optional node labels, factor parameters and current message values
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - Options specifying what is shown
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::{DotOptions, FactorGraphBuilder};
+    /// use gmrs::ising::{IsingFactor, IsingVariable, SumProduct, random_message_initializer};
+    /// use rand::thread_rng;
+    ///
+    /// // Aliases to shorten types
+    /// type Factor = IsingFactor<SumProduct>;
+    /// type Variable = IsingVariable<SumProduct>;
+    ///
+    /// // Messages initializer
+    /// let rng = thread_rng();
+    /// let mut initializer = random_message_initializer(rng, -0.5, 0.5);
+    ///
+    /// let mut fgb = FactorGraphBuilder::<Factor, Variable>::new_with_variables(vec![IsingVariable::new(); 2], 1);
+    /// fgb.add_factor(IsingFactor::new(0.5, -0.5, 0.5), &[0, 1], &mut initializer).unwrap();
+    /// let fg = fgb.build();
+    /// let options = DotOptions {
+    ///     variable_labels: Some(vec!["a".into(), "b".into()]),
+    ///     messages: true,
+    ///     ..Default::default()
+    /// };
+    /// let dot = fg.to_dot_with(&options);
+    /// assert!(dot.contains("v1 [shape=circle, label=\"b\"]"));
+    /// assert!(dot.contains("IsingMessage"));
+    /// ```
+    pub fn to_dot_with(&self, options: &DotOptions) -> String {
+        let mut dot = String::from("graph factor_graph {\n");
+        for var_index in 0..self.variables.len() {
+            let var_label = label(&options.variable_labels, var_index, "");
+            writeln!(
+                dot,
+                "    v{} [shape=circle, label=\"{}\"];",
+                var_index,
+                escape(&var_label)
+            )
+            .unwrap();
+        }
+        for (fac_index, factor) in self.factors.iter().enumerate() {
+            let mut fac_label = label(&options.factor_labels, fac_index, "f");
+            if options.factor_parameters {
+                write!(fac_label, "\n{:?}", factor.factor).unwrap();
+            }
+            writeln!(
+                dot,
+                "    f{} [shape=box, label=\"{}\"];",
+                fac_index,
+                escape(&fac_label)
+            )
+            .unwrap();
+        }
+        for (fac_index, factor) in self.factors.iter().enumerate() {
+            let edges = factor
+                .var_node_indices
+                .iter()
+                .zip(&factor.var_node_receiver_indices)
+                .zip(&factor.messages);
+            for ((var_index, receiver_index), fac_to_var) in edges {
+                if options.messages {
+                    let var_to_fac = &self.variables[*var_index].messages[*receiver_index];
+                    let edge_label = format!("f->v: {:?}\nv->f: {:?}", fac_to_var, var_to_fac);
+                    writeln!(
+                        dot,
+                        "    f{} -- v{} [label=\"{}\"];",
+                        fac_index,
+                        var_index,
+                        escape(&edge_label)
+                    )
+                    .unwrap();
+                } else {
+                    writeln!(dot, "    f{} -- v{};", fac_index, var_index).unwrap();
+                }
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
@@ -0,0 +1,223 @@
+use ndarray::{Array1, Array2};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::core::{
+    factor::Factor,
+    factor_graph::{FGError, FGResult, FactorGraph},
+    variable::Variable,
+};
+
+// ------------------------------------------------------------------------------------------
+
+/// A discrepancy between the marginal of a variable found by message passing
+/// and the empirical marginal of the variable estimated from samples
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MarginalError {
+    /// An index of a variable
+    pub var_index: usize,
+
+    /// The maximal absolute difference between probabilities of values
+    pub max_error: f64,
+
+    /// The total variation distance between marginals
+    pub total_variation: f64,
+}
+
+impl<F, V> FactorGraph<F, V>
+where
+    F: Factor,
+    V: Variable<Message = F::Message, Marginal = Array1<f64>>,
+{
+    // Positions of values of samples within marginals of variables
+    fn sample_indices(&self, samples: &[Vec<V::Sample>]) -> FGResult<Vec<Vec<usize>>> {
+        let variables_number = self.variables.len();
+        let cardinalities: Vec<usize> = self
+            .variables
+            .iter()
+            .map(|var| var.marginal().len())
+            .collect();
+        samples
+            .iter()
+            .map(|sample| {
+                if sample.len() != variables_number {
+                    return Err(FGError::ConfigurationSizeError(
+                        sample.len(),
+                        variables_number,
+                    ));
+                }
+                sample
+                    .iter()
+                    .zip(&self.variables)
+                    .enumerate()
+                    .map(|(var_index, (value, var))| {
+                        var.variable
+                            .sample_index(value)
+                            .filter(|index| *index < cardinalities[var_index])
+                            .ok_or(FGError::InvalidSample(var_index))
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Estimates marginals of all variables from samples, e.g. drawn by any of samplers,
+    /// as frequencies of values of variables. Values are ordered as in marginals
+    /// returned by [`FactorGraph::variable_marginals`]
+    ///
+    /// # Arguments
+    ///
+    /// * `samples` - Samples, each one is a configuration of all variables
+    ///
+    /// # Notes
+    ///
+    /// It fails if a sample has a wrong size or a value of a variable
+    /// can not be matched with its marginal (see [`Variable::sample_index`]).
+    /// Marginals estimated from an empty set of samples are NaN
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use ndarray::array;
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let mut fgb = new_ising_builder::<SumProduct>(2, 1);
+    /// fgb.add_factor(IsingFactor::new(0.5, 0., 0.), &[0, 1], &mut initializer).unwrap();
+    /// let fg = fgb.build();
+    /// let samples = vec![vec![1, 1], vec![1, -1], vec![-1, -1], vec![1, -1]];
+    /// let marginals = fg.empirical_marginals(&samples).unwrap();
+    /// assert_eq!(marginals, vec![array![0.75, 0.25], array![0.25, 0.75]]);
+    /// ```
+    pub fn empirical_marginals(&self, samples: &[Vec<V::Sample>]) -> FGResult<Vec<Array1<f64>>> {
+        let indices = self.sample_indices(samples)?;
+        let mut marginals: Vec<Array1<f64>> = self
+            .variables
+            .iter()
+            .map(|var| Array1::zeros(var.marginal().len()))
+            .collect();
+        for sample in &indices {
+            for (marginal, index) in marginals.iter_mut().zip(sample) {
+                marginal[*index] += 1f64;
+            }
+        }
+        for marginal in &mut marginals {
+            *marginal /= indices.len() as f64;
+        }
+        Ok(marginals)
+    }
+
+    /// Estimates pairwise marginals of given pairs of variables from samples.
+    /// An element `[a, b]` of a pairwise marginal of a pair `(i, j)` is the frequency
+    /// of the `a`-th value of the `i`-th variable together with the `b`-th value
+    /// of the `j`-th variable
+    ///
+    /// # Arguments
+    ///
+    /// * `samples` - Samples, each one is a configuration of all variables
+    /// * `pairs` - Pairs of indices of variables
+    ///
+    /// # Notes
+    ///
+    /// It fails similarly to [`FactorGraph::empirical_marginals`] or if an
+    /// index of a variable is out of range
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use ndarray::array;
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let mut fgb = new_ising_builder::<SumProduct>(2, 1);
+    /// fgb.add_factor(IsingFactor::new(0.5, 0., 0.), &[0, 1], &mut initializer).unwrap();
+    /// let fg = fgb.build();
+    /// let samples = vec![vec![1, 1], vec![1, -1], vec![-1, -1], vec![1, -1]];
+    /// let marginals = fg.empirical_pair_marginals(&samples, &[(0, 1)]).unwrap();
+    /// assert_eq!(marginals[0], array![[0.25, 0.5], [0., 0.25]]);
+    /// ```
+    pub fn empirical_pair_marginals(
+        &self,
+        samples: &[Vec<V::Sample>],
+        pairs: &[(usize, usize)],
+    ) -> FGResult<Vec<Array2<f64>>> {
+        let variables_number = self.variables.len();
+        if let Some(var_index) = pairs
+            .iter()
+            .flat_map(|(i, j)| [*i, *j])
+            .find(|var_index| *var_index >= variables_number)
+        {
+            return Err(FGError::OutOfRangeVariable(variables_number, var_index));
+        }
+        let indices = self.sample_indices(samples)?;
+        Ok(pairs
+            .iter()
+            .map(|(i, j)| {
+                let shape = (
+                    self.variables[*i].marginal().len(),
+                    self.variables[*j].marginal().len(),
+                );
+                let mut marginal = Array2::zeros(shape);
+                for sample in &indices {
+                    marginal[[sample[*i], sample[*j]]] += 1f64;
+                }
+                marginal / indices.len() as f64
+            })
+            .collect())
+    }
+
+    /// Compares marginals of variables found by message passing with empirical
+    /// marginals estimated from samples (see [`FactorGraph::empirical_marginals`]),
+    /// e.g. to validate message passing against exact samplers. It should be called
+    /// after message passing has converged
+    ///
+    /// # Arguments
+    ///
+    /// * `samples` - Samples, each one is a configuration of all variables
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let mut fgb = new_ising_builder::<SumProduct>(1, 1);
+    /// // p_up = 0.75
+    /// fgb.add_factor(IsingFactor::UnitFactor(f64::ln(3.)), &[0], &mut initializer).unwrap();
+    /// let mut fg = fgb.build();
+    /// fg.run_message_passing_parallel(
+    ///     10,
+    ///     0,
+    ///     1e-10,
+    ///     &get_standard_factor_scheduler(0.),
+    ///     &get_standard_variable_scheduler(0.),
+    /// ).unwrap();
+    /// let samples = vec![vec![1], vec![1], vec![-1], vec![-1]];
+    /// let errors = fg.marginal_errors(&samples).unwrap();
+    /// assert_eq!(errors[0].var_index, 0);
+    /// assert!((errors[0].max_error - 0.25).abs() < 1e-10);
+    /// assert!((errors[0].total_variation - 0.25).abs() < 1e-10);
+    /// ```
+    pub fn marginal_errors(&self, samples: &[Vec<V::Sample>]) -> FGResult<Vec<MarginalError>> {
+        let empirical = self.empirical_marginals(samples)?;
+        Ok(self
+            .variables
+            .iter()
+            .zip(empirical)
+            .enumerate()
+            .map(|(var_index, (var, empirical))| {
+                let differences = (var.marginal() - empirical).mapv(f64::abs);
+                MarginalError {
+                    var_index,
+                    max_error: differences.fold(0f64, |acc, x| acc.max(*x)),
+                    total_variation: differences.sum() / 2f64,
+                }
+            })
+            .collect())
+    }
+}
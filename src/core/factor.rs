@@ -50,6 +50,26 @@ pub trait Factor: Clone + Debug + Send {
         parameters: &Self::Parameters,
     );
 
+    /// Sends messages to adjoint variables of a factor whose degree `N`
+    /// is known at compile time, thus messages are fixed-size arrays.
+    /// Message passing calls it instead of `send_messages` for factors of degree
+    /// 1 and 2, the default implementation falls back to `send_messages`
+    ///
+    /// # Arguments
+    ///
+    /// * `src` - Messages received from adjoint variables previously
+    /// * `dst` - Destinations where to send messages
+    /// * `parameters` - Hyper parameters of message passing rules
+    #[inline(always)]
+    fn send_messages_fixed<const N: usize>(
+        &self,
+        src: &[Self::Message; N],
+        dst: &mut [Self::Message; N],
+        parameters: &Self::Parameters,
+    ) {
+        self.send_messages(src, dst, parameters)
+    }
+
     /// Computes a joint marginal of adjoint factor variables
     ///
     /// # Arguments
@@ -65,4 +85,42 @@ pub trait Factor: Clone + Debug + Send {
     /// the most natural data structure representing a standalone factor
     /// is the same used to represent a marginal
     fn factor(&self) -> Self::Marginal;
+
+    /// Returns a factor multiplied by the ratio of a target marginal and
+    /// a current one, which is a step of iterative proportional fitting
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - A target joint marginal of adjoint factor variables
+    /// * `current` - A current joint marginal of adjoint factor variables
+    ///
+    /// # Notes
+    ///
+    /// The returned factor must have the same degree. It returns None if
+    /// the ratio can not be applied, e.g. the target marginal is positive where
+    /// the current one vanishes. The default implementation always returns None
+    /// meaning that rescaling is not supported by a factor
+    fn rescaled(&self, target: &Self::Marginal, current: &Self::Marginal) -> Option<Self> {
+        let _ = (target, current);
+        None
+    }
+
+    /// Returns true if the product of a factor and received messages vanishes
+    /// for all configurations of adjoint variables or is not a number, i.e.
+    /// evidence received by a factor contradicts it
+    ///
+    /// # Arguments
+    ///
+    /// * `messages` - Messages received from adjoint variables previously
+    ///
+    /// # Notes
+    ///
+    /// Marginals of a contradictory model are not defined, message passing
+    /// reports such factors by an error if messages diverge or do not converge
+    /// (see `FactorGraph::contradictory_factors`). The default implementation always
+    /// returns false meaning that contradictions are not detected by a factor
+    fn is_contradictory(&self, messages: &[Self::Message]) -> bool {
+        let _ = messages;
+        false
+    }
 }
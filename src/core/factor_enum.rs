@@ -0,0 +1,187 @@
+/// Creates an enum wrapping several factor types that share the message,
+/// parameters and marginal types and implements the `Factor` trait for it
+/// with static dispatch per variant. This allows one to mix different kinds of
+/// factors in a single factor graph. `From` is implemented for each variant.
+/// Unit factors created by `Factor::from_message`, e.g. while freezing a variable,
+/// are of the first variant.
+///
+/// # Example
+///
+/// ```
+/// use gmrs::core::{Factor, FactorGraphBuilder};
+/// use gmrs::factor_enum;
+/// use gmrs::tabular::{
+///     uninformative_message_initializer, TabularFactor, TabularMessage, TabularVariable,
+/// };
+/// use ndarray::{array, ArrayD};
+///
+/// // A factor that forces two binary variables to be equal
+/// #[derive(Debug, Clone)]
+/// struct Equality;
+///
+/// impl Factor for Equality {
+///     type Message = TabularMessage;
+///     type Marginal = ArrayD<f64>;
+///     type Parameters = f64;
+///
+///     fn from_message(_: &TabularMessage) -> Self {
+///         unimplemented!()
+///     }
+///
+///     fn degree(&self) -> usize {
+///         2
+///     }
+///
+///     fn send_messages(&self, src: &[TabularMessage], dst: &mut [TabularMessage], _: &f64) {
+///         let normalized = |m: &TabularMessage| {
+///             let (p0, p1) = (m.value(0), m.value(1));
+///             TabularMessage(array![p0 / (p0 + p1), p1 / (p0 + p1)])
+///         };
+///         dst[0] = normalized(&src[1]);
+///         dst[1] = normalized(&src[0]);
+///     }
+///
+///     fn marginal(&self, src: &[TabularMessage]) -> ArrayD<f64> {
+///         let (p0, p1) = (src[0].value(0) * src[1].value(0), src[0].value(1) * src[1].value(1));
+///         array![[p0, 0.], [0., p1]].into_dyn() / (p0 + p1)
+///     }
+///
+///     fn factor(&self) -> ArrayD<f64> {
+///         array![[1., 0.], [0., 1.]].into_dyn()
+///     }
+/// }
+///
+/// factor_enum! {
+///     /// Tabular factors mixed with equality constraints
+///     pub enum MixedFactor {
+///         Tabular(TabularFactor),
+///         Equality(Equality),
+///     }
+/// }
+///
+/// let mut initializer = uninformative_message_initializer();
+/// let mut fgb = FactorGraphBuilder::<MixedFactor, _>::new_with_variables(vec![TabularVariable::new(2); 2], 2);
+/// fgb.add_factor(TabularFactor::new(array![1., 3.].into_dyn()).into(), &[0], &mut initializer).unwrap();
+/// fgb.add_factor(Equality.into(), &[0, 1], &mut initializer).unwrap();
+/// let mut fg = fgb.build();
+/// let _ = fg.run_message_passing_parallel(10, 0, 1e-10, &|_| 0., &|_| 0.).unwrap();
+/// assert!((fg.variable_marginals()[1][1] - 0.75).abs() < 1e-10);
+/// ```
+#[macro_export]
+macro_rules! factor_enum {
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident {
+            $first_variant:ident($first_type:ty)
+            $(, $variant:ident($type:ty))* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone)]
+        $vis enum $name {
+            $first_variant($first_type),
+            $($variant($type),)*
+        }
+
+        impl $crate::core::Factor for $name {
+            type Message = <$first_type as $crate::core::Factor>::Message;
+            type Parameters = <$first_type as $crate::core::Factor>::Parameters;
+            type Marginal = <$first_type as $crate::core::Factor>::Marginal;
+
+            #[inline(always)]
+            fn from_message(message: &Self::Message) -> Self {
+                $name::$first_variant(
+                    <$first_type as $crate::core::Factor>::from_message(message),
+                )
+            }
+
+            #[inline(always)]
+            fn degree(&self) -> usize {
+                match self {
+                    $name::$first_variant(factor) => factor.degree(),
+                    $($name::$variant(factor) => factor.degree(),)*
+                }
+            }
+
+            #[inline(always)]
+            fn send_messages(
+                &self,
+                src: &[Self::Message],
+                dst: &mut [Self::Message],
+                parameters: &Self::Parameters,
+            ) {
+                match self {
+                    $name::$first_variant(factor) => factor.send_messages(src, dst, parameters),
+                    $($name::$variant(factor) => factor.send_messages(src, dst, parameters),)*
+                }
+            }
+
+            #[inline(always)]
+            fn send_messages_fixed<const N: usize>(
+                &self,
+                src: &[Self::Message; N],
+                dst: &mut [Self::Message; N],
+                parameters: &Self::Parameters,
+            ) {
+                match self {
+                    $name::$first_variant(factor) => {
+                        factor.send_messages_fixed(src, dst, parameters)
+                    }
+                    $($name::$variant(factor) => factor.send_messages_fixed(src, dst, parameters),)*
+                }
+            }
+
+            #[inline(always)]
+            fn marginal(&self, messages: &[Self::Message]) -> Self::Marginal {
+                match self {
+                    $name::$first_variant(factor) => factor.marginal(messages),
+                    $($name::$variant(factor) => factor.marginal(messages),)*
+                }
+            }
+
+            #[inline(always)]
+            fn factor(&self) -> Self::Marginal {
+                match self {
+                    $name::$first_variant(factor) => factor.factor(),
+                    $($name::$variant(factor) => factor.factor(),)*
+                }
+            }
+
+            #[inline(always)]
+            fn rescaled(&self, target: &Self::Marginal, current: &Self::Marginal) -> Option<Self> {
+                match self {
+                    $name::$first_variant(factor) => {
+                        factor.rescaled(target, current).map($name::$first_variant)
+                    }
+                    $($name::$variant(factor) => {
+                        factor.rescaled(target, current).map($name::$variant)
+                    })*
+                }
+            }
+
+            #[inline(always)]
+            fn is_contradictory(&self, messages: &[Self::Message]) -> bool {
+                match self {
+                    $name::$first_variant(factor) => factor.is_contradictory(messages),
+                    $($name::$variant(factor) => factor.is_contradictory(messages),)*
+                }
+            }
+        }
+
+        impl From<$first_type> for $name {
+            #[inline]
+            fn from(factor: $first_type) -> Self {
+                $name::$first_variant(factor)
+            }
+        }
+
+        $(
+            impl From<$type> for $name {
+                #[inline]
+                fn from(factor: $type) -> Self {
+                    $name::$variant(factor)
+                }
+            }
+        )*
+    };
+}
@@ -1,19 +1,45 @@
-use std::{error::Error, fmt::Display};
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fmt::Display,
+    ops::{ControlFlow, Range},
+    sync::OnceLock,
+    time::Duration,
+};
+
+#[cfg(feature = "parallel")]
+use std::sync::Arc;
+
+#[cfg(feature = "parallel")]
+use rayon::{
+    prelude::{IntoParallelIterator, ParallelIterator},
+    ThreadPool,
+};
 
-use rayon::prelude::{IntoParallelRefMutIterator, ParallelIterator};
+#[cfg(feature = "parallel")]
+use crate::core::balancing::{balanced_chunks, MIN_HUB_DEGREE};
+
+#[cfg(all(debug_assertions, not(feature = "parallel")))]
+use crate::core::allocations::AllocationCounter;
 
 use crate::{
-    core::factor::Factor, core::factor_node::FactorNode, core::variable::Variable,
+    core::factor::Factor,
+    core::factor_node::FactorNode,
+    core::message::{nan_max, Message},
+    core::observable::ObservableSeries,
+    core::publisher::PublishedMarginals,
+    core::topology::{max_cycles_per_component, ExactnessCertificate},
+    core::variable::{DecodableVariable, Variable},
     core::variable_node::VariableNode,
 };
 
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
-use rand::Rng;
-
 // ------------------------------------------------------------------------------------------
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 /// Errors that could appear in factor graph's methods
 pub enum FGError {
     /// Message passing error appearing when a message passing does not converge
@@ -44,6 +70,116 @@ pub enum FGError {
 
     /// Index of a variable is out of range
     OutOfRangeVariable(usize, usize),
+
+    /// Size of a configuration does not match the number of variables
+    ConfigurationSizeError(usize, usize),
+
+    /// A snapshot of messages does not match the structure of a factor graph
+    SnapshotMismatch,
+
+    /// Index of a factor is out of range
+    OutOfRangeFactor(usize, usize),
+
+    /// Degree of a new factor does not match the degree of a replaced one
+    FactorDegreeError(usize, usize),
+
+    /// Degree of a factor does not match a number of adjacent variables
+    DegreeError(usize, Vec<usize>),
+
+    /// A conditional factor is not normalized over its child,
+    /// contains the index of a factor and its normalization error
+    NormalizationError(usize, f64),
+
+    /// A factor graph is not a directed acyclic graph of conditional factors
+    NotDirectedAcyclic,
+
+    /// Message passing has been stopped by an observer before convergence
+    Interrupted {
+        /// Number of iterations past before interruption
+        iterations_number: usize,
+
+        /// Discrepancy of the last iteration before interruption
+        last_discrepancy: f64,
+
+        /// Dynamics of discrepancy before interruption
+        discrepancy_dynamics: Vec<f64>,
+    },
+
+    /// A variable can not be softly pinned with a given probability,
+    /// contains the index of a variable and the probability
+    SoftPinningError(usize, f64),
+
+    /// A target marginal of a variable is invalid, can not be reached or
+    /// moment matching is not supported by the variable, contains the index of a variable
+    MarginalEvidenceError(usize),
+
+    /// A factor can not be rescaled to match a target marginal,
+    /// contains the index of a factor
+    ScalingError(usize),
+
+    /// Iterative proportional fitting has not converged
+    FittingError {
+        /// Number of scaling steps passed before failure
+        scaling_steps_number: usize,
+
+        /// Maximal discrepancy between factor marginals and target ones
+        /// at the last scaling step
+        last_discrepancy: f64,
+    },
+
+    /// Evidence is contradictory, i.e. the model has zero probability,
+    /// contains indices of factors whose product with received messages vanishes
+    ContradictionError(Vec<usize>),
+
+    /// Number of edge appearance probabilities does not match the number of factors,
+    /// contains the number of factors and the number of probabilities
+    AppearanceProbabilitiesSizeError(usize, usize),
+
+    /// An edge appearance probability of a factor does not lie in (0, 1],
+    /// contains the index of a factor and the probability
+    AppearanceProbabilityError(usize, f64),
+
+    /// A variable can not be contracted with itself, contains the index of a variable
+    ContractionError(usize),
+
+    /// A message initializer produced a message that is not valid for a variable,
+    /// contains the index of the variable
+    InvalidMessage(usize),
+
+    /// A variable is assigned several times by a partial assignment,
+    /// contains the index of the variable
+    DuplicateAssignment(usize),
+
+    /// A sample of a variable can not be matched with its marginal,
+    /// contains the index of the variable
+    InvalidSample(usize),
+
+    /// A factor is not adjacent to a variable, contains the index of the factor
+    /// and the index of the variable
+    UnknownEdge(usize, usize),
+
+    /// A factor is adjacent to a variable several times, which is not supported
+    /// by a method, contains the index of the factor and the index of the variable
+    RepeatedEdge(usize, usize),
+
+    /// A time budget has been exceeded before message passing has converged
+    /// or sampling has finished
+    TimedOut {
+        /// Time elapsed before stopping
+        elapsed: Duration,
+
+        /// Number of variables sampled before stopping, zero for message passing
+        sampled_variables_number: usize,
+
+        /// Total number of message passing iterations passed before stopping
+        iterations_number: usize,
+
+        /// Discrepancy of the last iteration before stopping
+        last_discrepancy: f64,
+
+        /// Dynamics of discrepancy of the last message passing run
+        discrepancy_dynamics: Vec<f64>,
+    },
 }
 
 impl Display for FGError {
@@ -72,6 +208,132 @@ impl Display for FGError {
                     total_iterations_number,
                 )
             }
+            FGError::ConfigurationSizeError(size, config_size) => write!(
+                f,
+                "Configuration of size {} does not match the number of variables {}",
+                config_size, size,
+            ),
+            FGError::SnapshotMismatch => write!(
+                f,
+                "Snapshot of messages does not match the structure of a factor graph",
+            ),
+            FGError::OutOfRangeFactor(size, pos) => write!(
+                f,
+                "Index of a factor {} is out of range of [0..{}] factors",
+                pos, size,
+            ),
+            FGError::FactorDegreeError(degree, new_degree) => write!(
+                f,
+                "Degree of a new factor {} does not match the degree of a replaced factor {}",
+                new_degree, degree,
+            ),
+            FGError::DegreeError(deg, vars) => write!(
+                f,
+                "Degree of a factor does not match the number of variables. The factor's degree: {}, the variables list {:?}",
+                deg,
+                vars,
+            ),
+            FGError::NormalizationError(fac_index, error) => write!(
+                f,
+                "Conditional factor {} is not normalized over its child, normalization error: {}",
+                fac_index, error,
+            ),
+            FGError::NotDirectedAcyclic => write!(
+                f,
+                "Factor graph is not a directed acyclic graph of conditional factors",
+            ),
+            FGError::Interrupted {
+                iterations_number,
+                last_discrepancy,
+                ..
+            } => write!(
+                f,
+                "Message passing has been interrupted by an observer after {} iterations, last iteration discrepancy: {}",
+                iterations_number,
+                last_discrepancy,
+            ),
+            FGError::SoftPinningError(var_index, probability) => write!(
+                f,
+                "Variable {} can not be pinned with probability {}, it must lie in (0, 1) and be supported by the variable",
+                var_index, probability,
+            ),
+            FGError::MarginalEvidenceError(var_index) => write!(
+                f,
+                "Marginal of variable {} can not be matched to a target one",
+                var_index,
+            ),
+            FGError::ScalingError(fac_index) => write!(
+                f,
+                "Factor {} can not be rescaled to match a target marginal",
+                fac_index,
+            ),
+            FGError::FittingError {
+                scaling_steps_number,
+                last_discrepancy,
+            } => write!(
+                f,
+                "Iterative proportional fitting has not converged after {} scaling steps, last discrepancy: {}",
+                scaling_steps_number,
+                last_discrepancy,
+            ),
+            FGError::ContradictionError(fac_indices) => write!(
+                f,
+                "Evidence is contradictory, products of factors {:?} and received messages vanish",
+                fac_indices,
+            ),
+            FGError::AppearanceProbabilitiesSizeError(factors_number, probabilities_number) => {
+                write!(
+                    f,
+                    "Number of edge appearance probabilities {} does not match the number of factors {}",
+                    probabilities_number, factors_number,
+                )
+            }
+            FGError::AppearanceProbabilityError(fac_index, probability) => write!(
+                f,
+                "Edge appearance probability {} of factor {} does not lie in (0, 1]",
+                probability, fac_index,
+            ),
+            FGError::ContractionError(var_index) => write!(
+                f,
+                "Variable {} can not be contracted with itself",
+                var_index,
+            ),
+            FGError::InvalidMessage(var_index) => write!(
+                f,
+                "A message initializer produced an invalid message for the variable {}",
+                var_index,
+            ),
+            FGError::DuplicateAssignment(var_index) => write!(
+                f,
+                "Variable {} is assigned several times by a partial assignment",
+                var_index,
+            ),
+            FGError::InvalidSample(var_index) => write!(
+                f,
+                "A sample of variable {} can not be matched with its marginal",
+                var_index,
+            ),
+            FGError::UnknownEdge(fac_index, var_index) => write!(
+                f,
+                "Factor {} is not adjacent to variable {}",
+                fac_index, var_index,
+            ),
+            FGError::RepeatedEdge(fac_index, var_index) => write!(
+                f,
+                "Factor {} is adjacent to variable {} several times",
+                fac_index, var_index,
+            ),
+            FGError::TimedOut {
+                elapsed,
+                sampled_variables_number,
+                iterations_number,
+                last_discrepancy,
+                ..
+            } => write!(
+                f,
+                "Time budget has been exceeded after {:?}, {} variables sampled, {} iterations passed, last iteration discrepancy: {}",
+                elapsed, sampled_variables_number, iterations_number, last_discrepancy,
+            ),
         }
     }
 }
@@ -79,7 +341,8 @@ impl Display for FGError {
 impl Error for FGError {}
 
 /// Information returned after successful convergence of the a message passing procedure
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct MessagePassingInfo {
     /// Number of iterations past before convergence
     pub iterations_number: usize,
@@ -89,6 +352,17 @@ pub struct MessagePassingInfo {
 
     /// Dynamics of discrepancy before failure
     pub discrepancy_dynamics: Vec<f64>,
+
+    /// Certificate of exactness if the topology of a factor graph guarantees it
+    pub certificate: Option<ExactnessCertificate>,
+
+    /// Values of variable marginals (see [`Variable::marginal_values`]) recorded during
+    /// a run, it is None unless recording is requested by options
+    pub marginal_history: Option<Vec<PublishedMarginals<Vec<f64>>>>,
+
+    /// Time series of observables evaluated during a run, it is empty unless
+    /// observables are requested
+    pub observables: Vec<ObservableSeries>,
 }
 
 impl Display for MessagePassingInfo {
@@ -105,22 +379,205 @@ impl Display for MessagePassingInfo {
 pub type FGResult<T> = Result<T, FGError>;
 
 /// Information returned after successful convergence of the sampling procedure
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct SamplingInfo<S> {
     /// Generated samples
     pub samples: Vec<S>,
 
     /// Number of message passing iterations per variable
+    /// (zero for variables fixed as parts of pinned components)
     pub iterations_per_variable: Vec<usize>,
 
     /// Total number of message passing iterations
     pub total_iterations_number: usize,
+
+    /// Number of variables fixed as parts of pinned components
+    /// without running message passing
+    pub pinned_variables_number: usize,
+
+    /// Indices of variables in the order they have been fixed
+    /// (variables clamped by a partial assignment are not included)
+    pub decimation_order: Vec<usize>,
+
+    /// Indices of variables clamped by a partial assignment before sampling
+    pub clamped_variables: Vec<usize>,
+
+    /// Number of times sampling has been rolled back after message passing failures
+    pub backtracks_number: usize,
+
+    /// Logarithm of the importance weight of a sample if a sampler provides it
+    pub log_weight: Option<f64>,
+
+    /// Energy of a sample if a sampler provides it
+    pub energy: Option<f64>,
+}
+
+/// Progress of sampling reported after each decimation step
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SamplingProgress {
+    /// Number of variables fixed so far, including variables of pinned components
+    pub sampled_variables_number: usize,
+
+    /// Total number of variables to sample
+    pub variables_number: usize,
+
+    /// Total number of message passing iterations so far
+    pub total_iterations_number: usize,
+
+    /// Discrepancy of the last iteration of the last message passing run
+    pub last_discrepancy: f64,
+}
+
+/// Information returned after successful generation of a batch of samples
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BatchSamplingInfo<S> {
+    /// Generated samples, one configuration of all variables per sample
+    pub samples: Vec<Vec<S>>,
+
+    /// Total number of message passing iterations per sample
+    pub iterations_per_sample: Vec<usize>,
+
+    /// Total number of message passing iterations across all samples
+    pub total_iterations_number: usize,
+
+    /// Logarithms of importance weights of samples if a sampler provides them
+    pub log_weights: Option<Vec<f64>>,
+
+    /// Energies of samples if a sampler provides them
+    pub energies: Option<Vec<f64>>,
+
+    /// Orders in which variables have been fixed per sample if a sampler provides them
+    pub decimation_orders: Option<Vec<Vec<usize>>>,
+}
+
+impl<S> BatchSamplingInfo<S> {
+    /// Creates a batch of samples generated without message passing
+    /// (e.g. by a Markov chain) that carries no weights, energies and orders
+    ///
+    /// # Arguments
+    ///
+    /// * `samples` - Samples, one configuration of all variables per sample
+    #[inline]
+    pub fn from_samples(samples: Vec<Vec<S>>) -> Self {
+        let samples_number = samples.len();
+        BatchSamplingInfo {
+            samples,
+            iterations_per_sample: vec![0; samples_number],
+            total_iterations_number: 0,
+            log_weights: None,
+            energies: None,
+            decimation_orders: None,
+        }
+    }
+
+    /// Evaluates energies of all samples
+    ///
+    /// # Arguments
+    ///
+    /// * `energy` - A function computing the energy of a configuration
+    #[inline]
+    pub fn with_energies(mut self, energy: impl Fn(&[S]) -> f64) -> Self {
+        self.energies = Some(self.samples.iter().map(|s| energy(s)).collect());
+        self
+    }
+
+    /// Evaluates logarithms of importance weights of all samples
+    ///
+    /// # Arguments
+    ///
+    /// * `log_weight` - A function computing the logarithm of the weight of a configuration
+    #[inline]
+    pub fn with_log_weights(mut self, log_weight: impl Fn(&[S]) -> f64) -> Self {
+        self.log_weights = Some(self.samples.iter().map(|s| log_weight(s)).collect());
+        self
+    }
+
+    /// Returns normalized importance weights of samples,
+    /// all weights are equal if samples are not weighted
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::BatchSamplingInfo;
+    ///
+    /// let info = BatchSamplingInfo::from_samples(vec![vec![1i8], vec![-1], vec![-1]])
+    ///     .with_log_weights(|s| if s[0] == 1 { 2f64.ln() } else { 0. });
+    /// assert_eq!(info.normalized_weights(), vec![0.5, 0.25, 0.25]);
+    /// assert!((info.effective_sample_size() - 8. / 3.).abs() < 1e-12);
+    /// ```
+    #[inline]
+    pub fn normalized_weights(&self) -> Vec<f64> {
+        match &self.log_weights {
+            Some(log_weights) => {
+                let max = log_weights
+                    .iter()
+                    .copied()
+                    .fold(f64::NEG_INFINITY, f64::max);
+                let weights: Vec<f64> = log_weights.iter().map(|w| (w - max).exp()).collect();
+                let sum: f64 = weights.iter().sum();
+                weights.into_iter().map(|w| w / sum).collect()
+            }
+            None => vec![1f64 / self.samples.len() as f64; self.samples.len()],
+        }
+    }
+
+    /// Returns the effective sample size (sum of weights)^2 / (sum of squared weights),
+    /// which is the number of samples if samples are not weighted
+    #[inline]
+    pub fn effective_sample_size(&self) -> f64 {
+        if self.log_weights.is_none() {
+            return self.samples.len() as f64;
+        }
+        1f64 / self.normalized_weights().iter().map(|w| w * w).sum::<f64>()
+    }
+}
+
+/// Information returned after streaming samples to a sink
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct StreamingSamplingInfo {
+    /// Number of samples consumed by a sink
+    pub samples_number: usize,
+
+    /// Total number of message passing iterations
+    pub total_iterations_number: usize,
+
+    /// Whether a sink has stopped sampling before generating all samples
+    pub is_stopped_by_sink: bool,
+}
+
+/// A saved configuration of all messages of a factor graph
+#[derive(Debug, Clone)]
+pub struct MessagesSnapshot<M> {
+    factors: Vec<NodeMessages<M>>,
+    variables: Vec<NodeMessages<M>>,
+}
+
+#[derive(Debug, Clone)]
+struct NodeMessages<M> {
+    receivers: Vec<M>,
+    messages: Vec<M>,
+}
+
+impl<M: Message> NodeMessages<M> {
+    #[inline(always)]
+    fn restore(&self, receivers: &mut [M], messages: &mut [M]) {
+        for (src, dst) in self.receivers.iter().zip(receivers) {
+            src.memcpy(dst);
+        }
+        for (src, dst) in self.messages.iter().zip(messages) {
+            src.memcpy(dst);
+        }
+    }
 }
 
 // ------------------------------------------------------------------------------------------
 
 /// A factor graph
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct FactorGraph<F, V>
 where
     F: Factor,
@@ -128,24 +585,13 @@ where
 {
     pub(crate) factors: Vec<FactorNode<F, V>>,
     pub(crate) variables: Vec<VariableNode<V, F>>,
-}
-
-impl<F, V> Clone for FactorGraph<F, V>
-where
-    F: Factor,
-    V: Variable<Message = F::Message>,
-{
-    fn clone(&self) -> Self {
-        let mut factors = self.factors.clone();
-        let mut variables = self.variables.clone();
-        for factor in &mut factors {
-            factor.init_senders(&mut variables);
-        }
-        for variable in &mut variables {
-            variable.init_senders(&mut factors);
-        }
-        FactorGraph { factors, variables }
-    }
+    // topology does not change during message passing, thus the certificate
+    // is computed once and invalidated by methods adding or removing nodes
+    pub(crate) certificate: OnceLock<Option<ExactnessCertificate>>,
+    #[cfg(feature = "parallel")]
+    pub(crate) thread_pool: Option<Arc<ThreadPool>>,
+    #[cfg(feature = "parallel")]
+    pub(crate) parallel_threshold: usize,
 }
 
 impl<F, V> FactorGraph<F, V>
@@ -170,8 +616,7 @@ where
     /// let rng = thread_rng();
     /// let mut initializer = random_message_initializer(rng, -0.5, 0.5);
     ///
-    /// let mut fgb = FactorGraphBuilder::<Factor, Variable>::new_with_capacity(2, 1);
-    /// fgb.fill(IsingVariable::new());
+    /// let mut fgb = FactorGraphBuilder::<Factor, Variable>::new_with_variables(vec![IsingVariable::new(); 2], 1);
     /// fgb.add_factor(
     ///     IsingFactor::new(0.5f64, -0.5f64, 0.5f64),
     ///    &[0, 1],
@@ -203,8 +648,7 @@ where
     /// let rng = thread_rng();
     /// let mut initializer = random_message_initializer(rng, -0.5, 0.5);
     ///
-    /// let mut fgb = FactorGraphBuilder::<Factor, Variable>::new_with_capacity(2, 1);
-    /// fgb.fill(IsingVariable::new());
+    /// let mut fgb = FactorGraphBuilder::<Factor, Variable>::new_with_variables(vec![IsingVariable::new(); 2], 1);
     /// fgb.add_factor(
     ///     IsingFactor::new(0.5f64, -0.5f64, 0.5f64),
     ///    &[0, 1],
@@ -218,132 +662,258 @@ where
         self.factors.iter().map(|x| x.degree()).collect()
     }
 
-    /// Runs a message passing algorithm in parallel. Typically, it is
-    /// a fixed point iteration method targeted on achieving an equilibrium
-    /// configuration of messages. This method mutates a factor graph
-    /// since it updates messages that are stored internally.
+    /// Returns indices of variables adjoint to a factor in the order
+    /// of the factor's arguments
     ///
     /// # Arguments
     ///
-    /// * `max_iterations_number` - A maximal number of iterations, if a process
-    ///     does not converge before reaching this number of iterations, it fails
-    /// * `min_iterations_number` - A minimal number of iterations that is performed
-    ///     disregards reaching the convergence criterion. It could be useful
-    ///     for example when one performs belief propagation with temperature annealing
-    /// * `threshold` - A threshold specifying the convergence criterion. A process
-    ///     is considered as successful if the discrepancy between two subsequent
-    ///     messages configurations is less than the threshold
-    /// * `factor_scheduler` - A scheduler of a factor's messages update rule hyper-parameters.
-    ///     It takes an iteration number (starts from 0) and return hyper-parameters.
-    /// * `variable_scheduler` - A scheduler of a variable's messages update rule hyper-parameters.
-    ///     It takes an iteration number (starts from 0) and return hyper-parameters.
+    /// * `fac_index` - An index of a factor
+    ///
+    /// # Notes
+    ///
+    /// Panics if `fac_index` is out of range
     ///
     /// # Example
     ///
     /// ```
-    /// use gmrs::core::FactorGraphBuilder;
-    /// use gmrs::ising::{IsingFactor, IsingVariable, SumProduct, random_message_initializer};
-    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
     /// use rand::thread_rng;
     ///
-    /// // Aliases to shorten types
-    /// type Factor = IsingFactor<SumProduct>;
-    /// type Variable = IsingVariable<SumProduct>;
-    ///
-    /// // Messages initializer
-    /// let rng = thread_rng();
-    /// let mut initializer = random_message_initializer(rng, -0.5, 0.5);
-    ///
-    /// // Message passing schedulers
-    /// let factor_scheduler = get_standard_factor_scheduler(0.5);
-    /// let variable_scheduler = get_standard_variable_scheduler(0.5);
-    ///
-    /// // Message passing
-    /// let mut fgb = FactorGraphBuilder::<Factor, Variable>::new_with_capacity(4, 3);
-    /// fgb.fill(IsingVariable::new());
-    /// for i in 0..3 {
-    ///     fgb.add_factor(
-    ///         IsingFactor::new(0.5f64, 0.5f64, -0.5f64),
-    ///         &[i, 3],
-    ///         &mut initializer,
-    ///     );
-    /// }
-    /// let mut fg = fgb.build();
-    /// let _ = fg.run_message_passing_parallel(
-    ///     100,
-    ///     0,
-    ///     1e-10,
-    ///     &factor_scheduler,
-    ///     &variable_scheduler,
-    /// ).unwrap();
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let mut fgb = new_ising_builder::<SumProduct>(3, 2);
+    /// fgb.add_factor(IsingFactor::new(0.5, 0., 0.), &[2, 0], &mut initializer).unwrap();
+    /// fgb.add_factor(IsingFactor::new(0.5, 0., 0.), &[0, 1], &mut initializer).unwrap();
+    /// let fg = fgb.build();
+    /// assert_eq!(fg.factor_neighbors(0), &[2, 0]);
+    /// assert_eq!(fg.variable_neighbors(0), &[0, 1]);
+    /// assert_eq!(fg.edges().collect::<Vec<_>>(), vec![(0, 2), (0, 0), (1, 0), (1, 1)]);
     /// ```
     #[inline]
-    pub fn run_message_passing_parallel(
-        &mut self,
-        max_iterations_number: usize,
-        min_iterations_number: usize,
-        threshold: f64,
-        factor_scheduler: &impl Fn(usize) -> F::Parameters,
-        variable_scheduler: &impl Fn(usize) -> V::Parameters,
-    ) -> FGResult<MessagePassingInfo> {
-        let mut last_discrepancy = f64::MAX;
-        let mut discrepancy_dynamics = Vec::with_capacity(max_iterations_number);
-        for i in 0..max_iterations_number {
-            let factor_parameters = factor_scheduler(i);
-            let variable_parameters = variable_scheduler(i);
-            let factors_discrepancy = self
-                .factors
-                .par_iter_mut()
-                .map(|factor| {
-                    factor.eval_messages(&factor_parameters);
-                    let max_discrepancy = factor.eval_discrepancy();
-                    factor.send_messages();
-                    max_discrepancy
-                })
-                .reduce(|| 0f64, |x, y| x.max(y));
-            let variables_discrepancy = self
-                .variables
-                .par_iter_mut()
-                .map(|variable| {
-                    variable.eval_messages(&variable_parameters);
-                    let max_discrepancy = variable.eval_discrepancy();
-                    variable.send_messages();
-                    max_discrepancy
-                })
-                .reduce(|| 0f64, |x, y| x.max(y));
-            let max_discrepancy = factors_discrepancy.max(variables_discrepancy);
-            discrepancy_dynamics.push(max_discrepancy);
-            last_discrepancy = max_discrepancy;
-            if (max_discrepancy < threshold) && (i + 1 >= min_iterations_number) {
-                return Ok(MessagePassingInfo {
-                    iterations_number: i,
-                    discrepancy_dynamics,
-                    last_discrepancy,
-                });
-            }
-        }
-        Err(FGError::MessagePassingError {
-            iterations_number: max_iterations_number,
-            discrepancy_dynamics,
-            last_discrepancy,
-        })
+    pub fn factor_neighbors(&self, fac_index: usize) -> &[usize] {
+        &self.factors[fac_index].var_node_indices
     }
 
-    /// Computes marginals for all variables
+    /// Returns indices of factors adjoint to a variable in the order
+    /// they have been attached to the variable
     ///
-    /// # Example
+    /// # Arguments
     ///
-    /// ```
-    /// use gmrs::core::FactorGraphBuilder;
-    /// use gmrs::ising::{IsingFactor, IsingVariable, SumProduct, random_message_initializer};
-    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
-    /// use rand::thread_rng;
+    /// * `var_index` - An index of a variable
     ///
-    /// // Aliases to shorten types
-    /// type Factor = IsingFactor<SumProduct>;
-    /// type Variable = IsingVariable<SumProduct>;
+    /// # Notes
     ///
-    /// // Messages initializer
+    /// Panics if `var_index` is out of range
+    #[inline]
+    pub fn variable_neighbors(&self, var_index: usize) -> &[usize] {
+        &self.variables[var_index].fac_node_indices
+    }
+
+    /// Returns an iterator over all edges of a factor graph as pairs
+    /// (a factor index, a variable index) ordered by factors
+    #[inline]
+    pub fn edges(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.factors
+            .iter()
+            .enumerate()
+            .flat_map(|(fac_index, factor)| {
+                factor
+                    .var_node_indices
+                    .iter()
+                    .map(move |var_index| (fac_index, *var_index))
+            })
+    }
+
+    /// Returns a reference to a factor of a factor graph, unlike
+    /// `factors` it does not materialize a dense representation of the factor
+    ///
+    /// # Arguments
+    ///
+    /// * `fac_index` - An index of a factor
+    ///
+    /// # Notes
+    ///
+    /// Panics if `fac_index` is out of range
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{
+    ///     new_ising_builder, random_message_initializer, IsingFactor, IsingVariable, SumProduct,
+    /// };
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let mut fgb = new_ising_builder::<SumProduct>(2, 2);
+    /// fgb.add_variable(IsingVariable::new().with_field(0.3));
+    /// fgb.add_factor(IsingFactor::new(0.5, 0., 0.), &[0, 1], &mut initializer).unwrap();
+    /// fgb.add_factor(IsingFactor::UnitFactor(1.5), &[2], &mut initializer).unwrap();
+    /// let fg = fgb.build();
+    /// assert!(matches!(fg.get_factor(1), IsingFactor::UnitFactor(b) if *b == 1.5));
+    /// assert_eq!(fg.get_variable(2).field(), 0.3);
+    /// ```
+    #[inline]
+    pub fn get_factor(&self, fac_index: usize) -> &F {
+        &self.factors[fac_index].factor
+    }
+
+    /// Returns a reference to a variable of a factor graph
+    ///
+    /// # Arguments
+    ///
+    /// * `var_index` - An index of a variable
+    ///
+    /// # Notes
+    ///
+    /// Panics if `var_index` is out of range
+    #[inline]
+    pub fn get_variable(&self, var_index: usize) -> &V {
+        &self.variables[var_index].variable
+    }
+
+    /// Returns a certificate of exactness of message passing following from
+    /// the topology of a factor graph, or None if the factor graph has
+    /// more than one cycle in some connected component. The certificate is
+    /// computed once and cached until factors or variables are added or removed
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::{ExactnessCertificate, FactorGraphBuilder};
+    /// use gmrs::ising::{IsingFactor, IsingVariable, MaxProduct, random_message_initializer};
+    /// use rand::thread_rng;
+    ///
+    /// // Aliases to shorten types
+    /// type Factor = IsingFactor<MaxProduct>;
+    /// type Variable = IsingVariable<MaxProduct>;
+    ///
+    /// // Messages initializer
+    /// let rng = thread_rng();
+    /// let mut initializer = random_message_initializer(rng, -0.5, 0.5);
+    ///
+    /// // A ring of 4 spins
+    /// let mut fgb = FactorGraphBuilder::<Factor, Variable>::new_with_variables(vec![IsingVariable::new(); 4], 4);
+    /// for i in 0..4 {
+    ///     fgb.add_factor(IsingFactor::new(1., 0., 0.), &[i, (i + 1) % 4], &mut initializer).unwrap();
+    /// }
+    /// assert_eq!(fgb.build().exactness_certificate(), Some(ExactnessCertificate::SingleCycle));
+    /// ```
+    #[inline]
+    pub fn exactness_certificate(&self) -> Option<ExactnessCertificate> {
+        *self.certificate.get_or_init(|| {
+            match max_cycles_per_component(self.factors.len(), self.variables.len(), self.edges()) {
+                0 => Some(ExactnessCertificate::Tree),
+                1 => Some(ExactnessCertificate::SingleCycle),
+                _ => None,
+            }
+        })
+    }
+
+    /// Sets a rayon thread pool used by message passing (and thus sampling),
+    /// None means the global thread pool. Clones of a factor graph share its pool
+    ///
+    /// # Arguments
+    ///
+    /// * `thread_pool` - A thread pool
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let mut fgb = new_ising_builder::<SumProduct>(2, 1);
+    /// fgb.add_factor(IsingFactor::new(0.5, 0., 0.), &[0, 1], &mut initializer).unwrap();
+    /// let mut fg = fgb.build();
+    ///
+    /// // Bounding parallelism of message passing by two threads
+    /// let pool = rayon::ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+    /// fg.set_thread_pool(Some(Arc::new(pool)));
+    /// assert_eq!(fg.thread_pool().unwrap().current_num_threads(), 2);
+    /// let factor_scheduler = get_standard_factor_scheduler(0.);
+    /// let variable_scheduler = get_standard_variable_scheduler(0.);
+    /// fg.run_message_passing_parallel(100, 0, 1e-10, &factor_scheduler, &variable_scheduler).unwrap();
+    /// ```
+    #[cfg(feature = "parallel")]
+    #[inline]
+    pub fn set_thread_pool(&mut self, thread_pool: Option<Arc<ThreadPool>>) {
+        self.thread_pool = thread_pool;
+    }
+
+    /// Returns a rayon thread pool used by message passing
+    /// or None if the global thread pool is used
+    #[cfg(feature = "parallel")]
+    #[inline]
+    pub fn thread_pool(&self) -> Option<&Arc<ThreadPool>> {
+        self.thread_pool.as_ref()
+    }
+
+    /// Sets the minimal number of edges of a factor graph for which message passing
+    /// sweeps are parallel, smaller factor graphs are swept serially in the current
+    /// thread (of a thread pool if it is set), since for them the overhead of rayon
+    /// dominates the cost of message updates
+    ///
+    /// # Arguments
+    ///
+    /// * `parallel_threshold` - A number of edges, zero makes all sweeps parallel
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let mut fgb = new_ising_builder::<SumProduct>(2, 1);
+    /// fgb.add_factor(IsingFactor::new(0.5, 0., 0.), &[0, 1], &mut initializer).unwrap();
+    /// let mut fg = fgb.build();
+    ///
+    /// // Even a tiny factor graph is swept in parallel
+    /// fg.set_parallel_threshold(0);
+    /// assert_eq!(fg.parallel_threshold(), 0);
+    /// let factor_scheduler = get_standard_factor_scheduler(0.);
+    /// let variable_scheduler = get_standard_variable_scheduler(0.);
+    /// fg.run_message_passing_parallel(100, 0, 1e-10, &factor_scheduler, &variable_scheduler).unwrap();
+    /// ```
+    #[cfg(feature = "parallel")]
+    #[inline]
+    pub fn set_parallel_threshold(&mut self, parallel_threshold: usize) {
+        self.parallel_threshold = parallel_threshold;
+    }
+
+    /// Returns the minimal number of edges of a factor graph
+    /// for which message passing sweeps are parallel
+    #[cfg(feature = "parallel")]
+    #[inline]
+    pub fn parallel_threshold(&self) -> usize {
+        self.parallel_threshold
+    }
+
+    /// Computes marginals for all variables
+    ///
+    /// # Notes
+    ///
+    /// Marginals are cached per variable, a cached marginal is reused until messages
+    /// received by a variable change, e.g. by message passing, or factors adjoint to it
+    /// are added or removed. Thus repeated calls between edits of a large graph only
+    /// recompute marginals of affected variables
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::FactorGraphBuilder;
+    /// use gmrs::ising::{IsingFactor, IsingVariable, SumProduct, random_message_initializer};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use rand::thread_rng;
+    ///
+    /// // Aliases to shorten types
+    /// type Factor = IsingFactor<SumProduct>;
+    /// type Variable = IsingVariable<SumProduct>;
+    ///
+    /// // Messages initializer
     /// let rng = thread_rng();
     /// let mut initializer = random_message_initializer(rng, -0.5, 0.5);
     ///
@@ -352,8 +922,7 @@ where
     /// let variable_scheduler = get_standard_variable_scheduler(0.5);
     ///
     /// // Message passing
-    /// let mut fgb = FactorGraphBuilder::<Factor, Variable>::new_with_capacity(2, 1);
-    /// fgb.fill(IsingVariable::new());
+    /// let mut fgb = FactorGraphBuilder::<Factor, Variable>::new_with_variables(vec![IsingVariable::new(); 2], 1);
     /// fgb.add_factor(
     ///     IsingFactor::new(0.5f64, 0.5f64, -0.5f64),
     ///    &[0, 1],
@@ -407,8 +976,7 @@ where
     /// let variable_scheduler = get_standard_variable_scheduler(0.5);
     ///
     /// // Message passing
-    /// let mut fgb = FactorGraphBuilder::<Factor, Variable>::new_with_capacity(2, 1);
-    /// fgb.fill(IsingVariable::new());
+    /// let mut fgb = FactorGraphBuilder::<Factor, Variable>::new_with_variables(vec![IsingVariable::new(); 2], 1);
     /// fgb.add_factor(
     ///     IsingFactor::new(0.5f64, 0.5f64, -0.5f64),
     ///    &[0, 1],
@@ -475,8 +1043,7 @@ where
     /// let variable_scheduler = get_standard_variable_scheduler(0.5);
     ///
     /// // Message passing
-    /// let mut fgb = FactorGraphBuilder::<Factor, Variable>::new_with_capacity(2, 1);
-    /// fgb.fill(IsingVariable::new());
+    /// let mut fgb = FactorGraphBuilder::<Factor, Variable>::new_with_variables(vec![IsingVariable::new(); 2], 1);
     /// fgb.add_factor(
     ///     IsingFactor::new(0.5f64, 0.5f64, -0.5f64),
     ///    &[0, 1],
@@ -512,6 +1079,418 @@ where
         self.factors.iter().map(|x| x.factor()).collect()
     }
 
+    /// Extracts a maximum a posteriori configuration after a max-product message passing.
+    /// Starting from the most probable value of a variable according to its beliefs,
+    /// the method traverses the factor graph in the breadth-first order and
+    /// assigns every next variable the most probable value conditioned on already
+    /// assigned neighbours. On trees this backtracking resolves ties between
+    /// equally probable configurations consistently and returns an exact maximizer;
+    /// on graphs with loops the result is a heuristic. The factor graph is not mutated.
+    ///
+    /// # Arguments
+    ///
+    /// * `parameters` - Hyper parameters of factors' message update rules
+    ///   used to compute conditioned messages. They must correspond to an
+    ///   undamped max-product update rule
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::FactorGraphBuilder;
+    /// use gmrs::ising::{IsingFactor, IsingVariable, MaxProduct, random_message_initializer};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use rand::thread_rng;
+    ///
+    /// // Aliases to shorten types
+    /// type Factor = IsingFactor<MaxProduct>;
+    /// type Variable = IsingVariable<MaxProduct>;
+    ///
+    /// // Messages initializer
+    /// let rng = thread_rng();
+    /// let mut initializer = random_message_initializer(rng, -0.5, 0.5);
+    ///
+    /// // Message passing schedulers
+    /// let factor_scheduler = get_standard_factor_scheduler(0.);
+    /// let variable_scheduler = get_standard_variable_scheduler(0.);
+    ///
+    /// // A chain without fields, all beliefs are ties
+    /// let mut fgb = FactorGraphBuilder::<Factor, Variable>::new_with_variables(vec![IsingVariable::new(); 3], 2);
+    /// fgb.add_factor(IsingFactor::new(1., 0., 0.), &[0, 1], &mut initializer).unwrap();
+    /// fgb.add_factor(IsingFactor::new(-1., 0., 0.), &[1, 2], &mut initializer).unwrap();
+    /// let mut fg = fgb.build();
+    /// let _ = fg.run_message_passing_parallel(
+    ///     100,
+    ///     0,
+    ///     1e-10,
+    ///     &factor_scheduler,
+    ///     &variable_scheduler,
+    /// ).unwrap();
+    ///
+    /// // MAP decoding
+    /// let assignment = fg.map_assignment(&factor_scheduler(0));
+    /// assert_eq!(assignment[0] * assignment[1], 1);
+    /// assert_eq!(assignment[1] * assignment[2], -1);
+    /// ```
+    pub fn map_assignment(&self, parameters: &F::Parameters) -> Vec<V::Sample>
+    where
+        V: DecodableVariable,
+    {
+        let mut assignment: Vec<Option<V::Sample>> = vec![None; self.variables.len()];
+        let mut visited_factors = vec![false; self.factors.len()];
+        let mut queue = VecDeque::new();
+        for root in 0..self.variables.len() {
+            if assignment[root].is_some() {
+                continue;
+            }
+            let root_node = &self.variables[root];
+            assignment[root] = Some(root_node.variable.argmax(&root_node.receivers));
+            queue.push_back(root);
+            while let Some(var_index) = queue.pop_front() {
+                for fac_index in &self.variables[var_index].fac_node_indices {
+                    if visited_factors[*fac_index] {
+                        continue;
+                    }
+                    visited_factors[*fac_index] = true;
+                    let factor = &self.factors[*fac_index];
+                    let neighbours = factor
+                        .var_node_indices
+                        .iter()
+                        .zip(&factor.var_node_receiver_indices)
+                        .enumerate();
+                    for (position, (neighbour, receiver_index)) in neighbours {
+                        if assignment[*neighbour].is_some() {
+                            continue;
+                        }
+                        let substitute = |index: usize| {
+                            assignment[index].map(|sample| V::sample_to_message(&sample))
+                        };
+                        let message = factor.substituted_message(position, substitute, parameters);
+                        let node = &self.variables[*neighbour];
+                        let mut receivers = node.receivers.clone();
+                        receivers[*receiver_index] = message;
+                        assignment[*neighbour] = Some(node.variable.argmax(&receivers));
+                        queue.push_back(*neighbour);
+                    }
+                }
+            }
+        }
+        assignment
+            .into_iter()
+            .map(|sample| sample.unwrap())
+            .collect()
+    }
+
+    /// Replaces a factor keeping its adjacent variables and messages.
+    /// It is useful for warm starts of message passing on a slightly modified problem.
+    ///
+    /// # Arguments
+    ///
+    /// * `factor_index` - An index of a factor to replace
+    /// * `factor` - A new factor, its degree must match the degree of a replaced factor
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::FactorGraphBuilder;
+    /// use gmrs::ising::{IsingFactor, IsingVariable, SumProduct, random_message_initializer};
+    /// use rand::thread_rng;
+    ///
+    /// // Aliases to shorten types
+    /// type Factor = IsingFactor<SumProduct>;
+    /// type Variable = IsingVariable<SumProduct>;
+    ///
+    /// // Messages initializer
+    /// let rng = thread_rng();
+    /// let mut initializer = random_message_initializer(rng, -0.5, 0.5);
+    ///
+    /// let mut fgb = FactorGraphBuilder::<Factor, Variable>::new_with_variables(vec![IsingVariable::new(); 2], 1);
+    /// fgb.add_factor(
+    ///     IsingFactor::new(0.5f64, 0.5f64, -0.5f64),
+    ///     &[0, 1],
+    ///     &mut initializer,
+    /// );
+    /// let mut fg = fgb.build();
+    /// fg.replace_factor(0, IsingFactor::new(-0.5f64, 0.5f64, -0.5f64)).unwrap();
+    /// assert!(fg.replace_factor(1, IsingFactor::new(-0.5f64, 0.5f64, -0.5f64)).is_err());
+    /// ```
+    #[inline]
+    pub fn replace_factor(&mut self, factor_index: usize, factor: F) -> FGResult<()> {
+        let factors_number = self.factors.len();
+        let factor_node = if let Some(factor_node) = self.factors.get_mut(factor_index) {
+            factor_node
+        } else {
+            return Err(FGError::OutOfRangeFactor(factors_number, factor_index));
+        };
+        if factor_node.degree() != factor.degree() {
+            return Err(FGError::FactorDegreeError(
+                factor_node.degree(),
+                factor.degree(),
+            ));
+        }
+        factor_node.factor = factor;
+        Ok(())
+    }
+
+    /// Modifies parameters of a factor in place keeping its adjacent variables
+    /// and messages, e.g. for ramping couplings between warm started runs
+    /// of message passing. A factor graph is not modified if the update fails.
+    ///
+    /// # Arguments
+    ///
+    /// * `factor_index` - An index of a factor to update
+    /// * `update` - A function modifying a factor, it must not change the factor's degree
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let mut fgb = new_ising_builder::<SumProduct>(2, 1);
+    /// fgb.add_factor(IsingFactor::new(0.1, 0.2, 0.), &[0, 1], &mut initializer).unwrap();
+    /// let mut fg = fgb.build();
+    /// let factor_scheduler = get_standard_factor_scheduler(0.);
+    /// let variable_scheduler = get_standard_variable_scheduler(0.);
+    ///
+    /// // Lowering the temperature step by step
+    /// for _ in 0..5 {
+    ///     fg.update_factor(0, |factor| {
+    ///         if let IsingFactor::Coupling { log_puu, log_pud, log_pdu, log_pdd, .. } = factor {
+    ///             for log_p in [log_puu, log_pud, log_pdu, log_pdd] {
+    ///                 *log_p *= 1.5;
+    ///             }
+    ///         }
+    ///     }).unwrap();
+    ///     fg.run_message_passing_parallel(100, 0, 1e-10, &factor_scheduler, &variable_scheduler).unwrap();
+    /// }
+    /// assert!(fg.update_factor(0, |factor| *factor = IsingFactor::UnitFactor(0.5)).is_err());
+    /// assert!(matches!(fg.get_factor(0), IsingFactor::Coupling { .. }));
+    /// ```
+    #[inline]
+    pub fn update_factor(
+        &mut self,
+        factor_index: usize,
+        update: impl FnOnce(&mut F),
+    ) -> FGResult<()> {
+        let factors_number = self.factors.len();
+        let mut factor = if let Some(factor_node) = self.factors.get(factor_index) {
+            factor_node.factor.clone()
+        } else {
+            return Err(FGError::OutOfRangeFactor(factors_number, factor_index));
+        };
+        update(&mut factor);
+        self.replace_factor(factor_index, factor)
+    }
+
+    /// Adds a new disconnected variable to an already built factor graph
+    /// and returns its index. Together with [`FactorGraph::add_factor`] it allows
+    /// growing a factor graph without rebuilding it
+    ///
+    /// # Arguments
+    ///
+    /// * `variable` - A variable to add
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, IsingVariable, SumProduct};
+    ///
+    /// let mut fg = new_ising_builder::<SumProduct>(2, 0).build();
+    /// assert_eq!(fg.add_variable(IsingVariable::new()), 2);
+    /// assert_eq!(fg.get_variable_degrees(), vec![0, 0, 0]);
+    /// ```
+    #[inline]
+    pub fn add_variable(&mut self, variable: V) -> usize {
+        self.variables
+            .push(VariableNode::new_disconnected(variable));
+        self.variables.len() - 1
+    }
+
+    /// Adds new disconnected variables to an already built factor graph
+    /// and returns the range of their indices
+    ///
+    /// # Arguments
+    ///
+    /// * `variables` - Variables to add
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, IsingVariable, SumProduct};
+    ///
+    /// let mut fg = new_ising_builder::<SumProduct>(2, 0).build();
+    /// let indices = fg.add_variables((0..3).map(|_| IsingVariable::new()));
+    /// assert_eq!(indices, 2..5);
+    /// ```
+    #[inline]
+    pub fn add_variables(&mut self, variables: impl IntoIterator<Item = V>) -> Range<usize> {
+        let start = self.variables.len();
+        self.variables
+            .extend(variables.into_iter().map(VariableNode::new_disconnected));
+        start..self.variables.len()
+    }
+
+    /// Adds a new factor to an already built factor graph. Messages of the rest
+    /// of a factor graph are kept, thus message passing after adding a factor
+    /// is warm started from them
+    ///
+    /// # Arguments
+    ///
+    /// * `factor` - A factor
+    /// * `var_indices` - Indices of variables adjoint to a factor
+    /// * `message_initializer` - A function generating initial messages
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let mut fgb = new_ising_builder::<SumProduct>(3, 2);
+    /// fgb.add_factor(IsingFactor::new(0.5, 0., 0.), &[0, 1], &mut initializer).unwrap();
+    /// let mut fg = fgb.build();
+    /// let factor_scheduler = get_standard_factor_scheduler(0.);
+    /// let variable_scheduler = get_standard_variable_scheduler(0.);
+    /// fg.run_message_passing_parallel(100, 0, 1e-10, &factor_scheduler, &variable_scheduler).unwrap();
+    ///
+    /// // Attaching the third spin
+    /// fg.add_factor(IsingFactor::new(0.5, 0., 0.), &[1, 2], &mut initializer).unwrap();
+    /// assert_eq!(fg.get_variable_degrees(), vec![1, 2, 1]);
+    /// assert!(fg.add_factor(IsingFactor::new(0.5, 0., 0.), &[1, 3], &mut initializer).is_err());
+    /// fg.run_message_passing_parallel(100, 0, 1e-10, &factor_scheduler, &variable_scheduler).unwrap();
+    /// assert_eq!(fg.factor_marginals().len(), 2);
+    /// ```
+    #[inline]
+    pub fn add_factor(
+        &mut self,
+        factor: F,
+        var_indices: &[usize],
+        message_initializer: &mut impl FnMut() -> F::Message,
+    ) -> FGResult<()> {
+        self.insert_factor(factor, var_indices, message_initializer, true)
+    }
+
+    // Adds a new factor verifying initial messages by variables if `check_messages`
+    // is set, messages created internally, e.g. ones fixing variables, are not verified
+    pub(crate) fn insert_factor(
+        &mut self,
+        factor: F,
+        var_indices: &[usize],
+        message_initializer: &mut impl FnMut() -> F::Message,
+        check_messages: bool,
+    ) -> FGResult<()> {
+        if factor.degree() != var_indices.len() {
+            return Err(FGError::DegreeError(factor.degree(), var_indices.to_vec()));
+        }
+        let variables_number = self.variables.len();
+        if let Some(index) = var_indices.iter().find(|index| **index >= variables_number) {
+            return Err(FGError::OutOfRangeVariable(variables_number, *index));
+        }
+        let mut messages = Vec::with_capacity(var_indices.len());
+        for index in var_indices {
+            let variable = &self.variables[*index].variable;
+            let (factor_message, variable_message) = (message_initializer(), message_initializer());
+            if check_messages
+                && !(variable.is_valid_message(&factor_message)
+                    && variable.is_valid_message(&variable_message))
+            {
+                return Err(FGError::InvalidMessage(*index));
+            }
+            messages.push((factor_message, variable_message));
+        }
+        let fac_index = self.factors.len();
+        let mut factor_node = FactorNode::<F, V>::new_disconnected(factor);
+        for (position, (index, (factor_message, variable_message))) in
+            var_indices.iter().zip(messages).enumerate()
+        {
+            let variable = &mut self.variables[*index];
+            factor_node.receivers.push(factor_message.clone());
+            factor_node.messages.push(variable_message.clone());
+            factor_node.var_node_indices.push(*index);
+            factor_node
+                .var_node_receiver_indices
+                .push(variable.receivers.len());
+            variable.messages.push(factor_message);
+            variable.receivers.push(variable_message);
+            variable.fac_node_indices.push(fac_index);
+            variable.fac_node_receiver_indices.push(position);
+            variable.invalidate_marginal();
+        }
+        self.factors.push(factor_node);
+        // a factor of a unit degree is a leaf and does not add cycles
+        if var_indices.len() > 1 {
+            self.certificate.take();
+        }
+        Ok(())
+    }
+
+    // Removes factors marked in `is_removed_factor` and a variable whose all adjoint
+    // factors are removed, indices of nodes following removed ones are shifted down.
+    // Messages of the remaining edges are kept
+    pub(crate) fn remove_nodes(
+        &mut self,
+        is_removed_factor: &[bool],
+        removed_variable: Option<usize>,
+    ) {
+        for (var_index, node) in self.variables.iter_mut().enumerate() {
+            if Some(var_index) == removed_variable
+                || !node
+                    .fac_node_indices
+                    .iter()
+                    .any(|fac_index| is_removed_factor[*fac_index])
+            {
+                continue;
+            }
+            let mut degree = 0;
+            for edge in 0..node.degree() {
+                let fac_index = node.fac_node_indices[edge];
+                if is_removed_factor[fac_index] {
+                    continue;
+                }
+                let position = node.fac_node_receiver_indices[edge];
+                node.fac_node_indices.swap(degree, edge);
+                node.fac_node_receiver_indices.swap(degree, edge);
+                node.messages.swap(degree, edge);
+                node.receivers.swap(degree, edge);
+                self.factors[fac_index].var_node_receiver_indices[position] = degree;
+                degree += 1;
+            }
+            node.truncate(degree);
+            node.damping.clear();
+        }
+        let mut new_fac_indices = Vec::with_capacity(is_removed_factor.len());
+        let mut fac_index = 0;
+        for is_removed in is_removed_factor {
+            new_fac_indices.push(fac_index);
+            if !is_removed {
+                fac_index += 1;
+            }
+        }
+        let mut is_removed = is_removed_factor.iter();
+        self.factors.retain(|_| !is_removed.next().unwrap());
+        self.certificate.take();
+        if let Some(removed_variable) = removed_variable {
+            self.variables.remove(removed_variable);
+            for node in &mut self.factors {
+                for var_index in &mut node.var_node_indices {
+                    debug_assert_ne!(*var_index, removed_variable);
+                    if *var_index > removed_variable {
+                        *var_index -= 1;
+                    }
+                }
+            }
+        }
+        for node in &mut self.variables {
+            for fac_index in &mut node.fac_node_indices {
+                *fac_index = new_fac_indices[*fac_index];
+            }
+        }
+    }
+
     /// Adds a unit degree factor fixing a variable value
     ///
     /// # Arguments
@@ -545,8 +1524,7 @@ where
     /// let variable_scheduler = get_standard_variable_scheduler(0.);
     ///
     /// // Message passing
-    /// let mut fgb = FactorGraphBuilder::<Factor, Variable>::new_with_capacity(2, 2);
-    /// fgb.fill(IsingVariable::new());
+    /// let mut fgb = FactorGraphBuilder::<Factor, Variable>::new_with_variables(vec![IsingVariable::new(); 2], 2);
     /// fgb.add_factor(
     ///     IsingFactor::new(0.5f64, 0.5f64, -0.5f64),
     ///    &[0, 1],
@@ -585,74 +1563,268 @@ where
                 "Factor degree is {degree} but it must be 1. This is a bug, please make an issue."
             )
         }
-        let factor_node = FactorNode::<F, V>::new_disconnected(factor);
-        self.factors.push(factor_node);
-        let factor_node = self.factors.last_mut().unwrap();
-        let variable_node = if let Some(var) = self.variables.get_mut(var_index) {
-            var
+        self.insert_factor(factor, &[var_index], &mut || message.clone(), false)
+    }
+
+    /// Softly freezes a variable by attaching a unit degree factor that assigns
+    /// a given probability to a value. Unlike `freeze_variable` the created factor
+    /// is finite, thus the variable's value is not fixed but only biased.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - A value that gets the probability `probability`
+    /// * `var_index` - An index of a variable
+    /// * `probability` - A probability of the value under the attached factor in (0, 1)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let mut fg = new_ising_builder::<SumProduct>(1, 1).build();
+    /// fg.soft_freeze_variable(&-1, 0, 0.8).unwrap();
+    /// assert!(fg.soft_freeze_variable(&-1, 0, 1.).is_err());
+    /// let factor_scheduler = get_standard_factor_scheduler(0.);
+    /// let variable_scheduler = get_standard_variable_scheduler(0.);
+    /// fg.run_message_passing_parallel(100, 0, 1e-10, &factor_scheduler, &variable_scheduler).unwrap();
+    /// assert!((fg.variable_marginals()[0][1] - 0.8).abs() < 1e-10);
+    /// ```
+    #[inline]
+    pub fn soft_freeze_variable(
+        &mut self,
+        value: &V::Sample,
+        var_index: usize,
+        probability: f64,
+    ) -> FGResult<()> {
+        let variables_number = self.variables.len();
+        let variable = if let Some(variable_node) = self.variables.get(var_index) {
+            &variable_node.variable
         } else {
-            return Err(FGError::OutOfRangeVariable(self.variables.len(), var_index));
+            return Err(FGError::OutOfRangeVariable(variables_number, var_index));
         };
-        let receivers_len = variable_node.receivers.len();
-        let receivers_cap = variable_node.receivers.capacity();
-        factor_node.receivers.push(message.clone());
-        factor_node.messages.push(message.clone());
-        factor_node.var_node_indices.push(var_index);
-        factor_node.var_node_receiver_indices.push(receivers_len);
-        variable_node.receivers.push(message.clone());
-        factor_node
-            .senders
-            .push(variable_node.receivers.last_mut().unwrap());
-        variable_node
-            .senders
-            .push(factor_node.receivers.last_mut().unwrap());
-        variable_node.messages.push(message);
-        variable_node.fac_node_indices.push(self.factors.len() - 1);
-        variable_node.fac_node_receiver_indices.push(0);
-        // if reallocation happens, one needs to reinitialize pointers
-        if receivers_cap == receivers_len {
-            let receivers_iter = variable_node.receivers.iter_mut();
-            let fac_node_index_iter = variable_node.fac_node_indices.iter();
-            let fac_node_receiver_index_iter = variable_node.fac_node_receiver_indices.iter();
-            let iter = receivers_iter.zip(fac_node_index_iter.zip(fac_node_receiver_index_iter));
-            for (r, (i, ri)) in iter {
-                unsafe {
-                    *self
-                        .factors
-                        .get_unchecked_mut(*i)
-                        .senders
-                        .get_unchecked_mut(*ri) = r;
-                }
+        let message = if probability > 0f64 && probability < 1f64 {
+            variable.sample_to_soft_message(value, probability)
+        } else {
+            None
+        }
+        .ok_or(FGError::SoftPinningError(var_index, probability))?;
+        let factor = F::from_message(&message);
+        let degree = factor.degree();
+        if degree != 1 {
+            panic!(
+                "Factor degree is {degree} but it must be 1. This is a bug, please make an issue."
+            )
+        }
+        self.insert_factor(factor, &[var_index], &mut || message.clone(), false)
+    }
+
+    /// Attaches unit degree factors that match marginals of variables to target
+    /// ones, injecting evidence expressed as probabilities. Each factor is computed
+    /// from the current messages received by a variable, so that the variable's
+    /// marginal equals the target one right after attaching it. A factor graph
+    /// is not modified if any of the targets can not be matched.
+    ///
+    /// # Arguments
+    ///
+    /// * `targets` - Pairs of an index of a variable and its target marginal
+    ///
+    /// # Notes
+    ///
+    /// Messages of a factor graph are expected to be converged. After message
+    /// passing is run again, marginals still match the targets if evidence on one
+    /// variable does not affect messages received by the others, e.g. on a tree
+    /// with a single target per component. Otherwise marginals match the targets
+    /// only approximately
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use ndarray::array;
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let mut fgb = new_ising_builder::<SumProduct>(2, 2);
+    /// fgb.add_factor(IsingFactor::new(0.5, 0.3, 0.), &[0, 1], &mut initializer).unwrap();
+    /// let mut fg = fgb.build();
+    /// let factor_scheduler = get_standard_factor_scheduler(0.);
+    /// let variable_scheduler = get_standard_variable_scheduler(0.);
+    /// fg.run_message_passing_parallel(100, 0, 1e-10, &factor_scheduler, &variable_scheduler).unwrap();
+    /// fg.add_marginal_evidence(&[(1, array![0.1, 0.9])]).unwrap();
+    /// assert!(fg.add_marginal_evidence(&[(0, array![1., 0.])]).is_err());
+    /// fg.run_message_passing_parallel(100, 0, 1e-10, &factor_scheduler, &variable_scheduler).unwrap();
+    /// assert!((fg.variable_marginals()[1][0] - 0.1).abs() < 1e-10);
+    /// ```
+    pub fn add_marginal_evidence(&mut self, targets: &[(usize, V::Marginal)]) -> FGResult<()> {
+        let variables_number = self.variables.len();
+        let mut messages = Vec::with_capacity(targets.len());
+        for (var_index, marginal) in targets {
+            let variable_node = self
+                .variables
+                .get(*var_index)
+                .ok_or(FGError::OutOfRangeVariable(variables_number, *var_index))?;
+            let message = variable_node
+                .variable
+                .marginal_to_message(marginal, &variable_node.receivers)
+                .ok_or(FGError::MarginalEvidenceError(*var_index))?;
+            messages.push(message);
+        }
+        for ((var_index, _), message) in targets.iter().zip(messages) {
+            let factor = F::from_message(&message);
+            let degree = factor.degree();
+            if degree != 1 {
+                panic!(
+                    "Factor degree is {degree} but it must be 1. This is a bug, please make an issue."
+                )
             }
+            self.insert_factor(factor, &[*var_index], &mut || message.clone(), false)?;
         }
         Ok(())
     }
 
-    /// Samples variables from a factor graph
+    /// Saves the current configuration of messages
+    ///
+    /// # Notes
+    ///
+    /// A snapshot contains only messages, not a factor graph's structure.
+    /// It is useful for cheap speculative operations that can be
+    /// undone by the `rollback` method
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let mut fgb = new_ising_builder::<SumProduct>(2, 1);
+    /// fgb.add_factor(IsingFactor::new(0.5, 0., 0.), &[0, 1], &mut initializer).unwrap();
+    /// let fg = fgb.build();
+    /// let snapshot = fg.snapshot();
+    /// ```
+    #[inline]
+    pub fn snapshot(&self) -> MessagesSnapshot<F::Message> {
+        let factors = self
+            .factors
+            .iter()
+            .map(|x| NodeMessages {
+                receivers: x.receivers.clone(),
+                messages: x.messages.clone(),
+            })
+            .collect();
+        let variables = self
+            .variables
+            .iter()
+            .map(|x| NodeMessages {
+                receivers: x.receivers.clone(),
+                messages: x.messages.clone(),
+            })
+            .collect();
+        MessagesSnapshot { factors, variables }
+    }
+
+    /// Restores a configuration of messages saved by the `snapshot` method
     ///
     /// # Arguments
     ///
-    /// * `max_iterations_number` - A maximal number of iterations in a message passing algorithm
-    /// * `min_iterations_number` - A minimal number of iterations that is performed
-    ///     disregards reaching the convergence criterion. It could be useful
-    ///     for example when one performs belief propagation with temperature annealing
-    /// * `threshold` - A threshold specifying the convergence criterion. A process
-    ///     is considered as successful if the discrepancy between two subsequent
-    ///     messages configurations is less than the threshold
-    /// * `rng` - A random numbers generator
-    /// * `factor_scheduler` - A scheduler of a factor's messages update rule hyper-parameters.
-    ///     It takes an iteration number (starts from 0) and return hyper-parameters.
-    /// * `variable_scheduler` - A scheduler of a variable's messages update rule hyper-parameters.
-    ///     It takes an iteration number (starts from 0) and return hyper-parameters.
+    /// * `snapshot` - A snapshot of messages
     ///
     /// # Notes
     ///
-    /// Sampling requires to run message passing after sampling and fixing of each
-    /// variable to get stationary messages for the updated factor graph.
-    /// This is why one has some arguments similar to those of 'run_message_passing_parallel'
-    /// method. Note also, that this method fixes all variables of a factor graph making
-    /// them further unusable. To keep the initial graph simply clone it before running
-    /// sampling
+    /// Factors and variables added after taking a snapshot (e.g. factors
+    /// fixing variables' values) are removed by this method. If a factor graph
+    /// structure is not an extension of the one that a snapshot was taken from,
+    /// the method returns an error and leaves a factor graph untouched
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use rand::thread_rng;
+    ///
+    /// let factor_scheduler = get_standard_factor_scheduler(0.);
+    /// let variable_scheduler = get_standard_variable_scheduler(0.);
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let mut fgb = new_ising_builder::<SumProduct>(2, 1);
+    /// fgb.add_factor(IsingFactor::new(0.5, 0.3, 0.), &[0, 1], &mut initializer).unwrap();
+    /// let mut fg = fgb.build();
+    /// fg.run_message_passing_parallel(100, 0, 1e-10, &factor_scheduler, &variable_scheduler)
+    ///     .unwrap();
+    /// let marginals = fg.variable_marginals();
+    ///
+    /// // A clamping experiment
+    /// let snapshot = fg.snapshot();
+    /// fg.freeze_variable(&-1, 0).unwrap();
+    /// fg.run_message_passing_parallel(100, 0, 1e-10, &factor_scheduler, &variable_scheduler)
+    ///     .unwrap();
+    /// assert_eq!(fg.get_factor_degrees(), vec![2, 1]);
+    ///
+    /// // Undoing the experiment
+    /// fg.rollback(&snapshot).unwrap();
+    /// assert_eq!(fg.get_factor_degrees(), vec![2]);
+    /// assert_eq!(fg.variable_marginals(), marginals);
+    /// ```
+    pub fn rollback(&mut self, snapshot: &MessagesSnapshot<F::Message>) -> FGResult<()> {
+        if self.factors.len() < snapshot.factors.len()
+            || self.variables.len() < snapshot.variables.len()
+        {
+            return Err(FGError::SnapshotMismatch);
+        }
+        let is_factor_mismatch = self
+            .factors
+            .iter()
+            .zip(&snapshot.factors)
+            .any(|(x, y)| x.receivers.len() != y.receivers.len());
+        let is_variable_mismatch = self
+            .variables
+            .iter()
+            .zip(&snapshot.variables)
+            .any(|(x, y)| x.receivers.len() < y.receivers.len());
+        if is_factor_mismatch || is_variable_mismatch {
+            return Err(FGError::SnapshotMismatch);
+        }
+        self.factors.truncate(snapshot.factors.len());
+        self.variables.truncate(snapshot.variables.len());
+        self.certificate.take();
+        for (variable, saved) in self.variables.iter_mut().zip(&snapshot.variables) {
+            variable.truncate(saved.receivers.len());
+            saved.restore(&mut variable.receivers, &mut variable.messages);
+        }
+        for (factor, saved) in self.factors.iter_mut().zip(&snapshot.factors) {
+            saved.restore(&mut factor.receivers, &mut factor.messages);
+        }
+        Ok(())
+    }
+
+    /// Scans a sequence of problem instances that differ slightly from each other,
+    /// e.g. by one resampled coupling. Each instance is obtained from the previous one
+    /// by replacing some factors, message passing on it is warm started from
+    /// the messages of the previous instance. After message passing on each instance
+    /// a measurement is performed. It is a cheap way to study sample-to-sample
+    /// fluctuations of an observable.
+    ///
+    /// # Arguments
+    ///
+    /// * `realizations` - A sequence of instances, each instance is given by
+    ///   pairs of an index of a factor and a new factor replacing it
+    /// * `max_iterations_number` - A maximal number of iterations per instance
+    /// * `min_iterations_number` - A minimal number of iterations per instance
+    /// * `threshold` - A threshold specifying the convergence criterion
+    /// * `factor_scheduler` - A scheduler of a factor's messages update rule hyper-parameters
+    /// * `variable_scheduler` - A scheduler of a variable's messages update rule hyper-parameters
+    /// * `measure` - A function taking a factor graph after message passing and
+    ///   the result of message passing and returning a measurement
+    ///
+    /// # Notes
+    ///
+    /// Non-converged message passing does not stop a scan, its result is passed to `measure`.
+    /// The method fails only if a factor replacement is invalid, in this case the factor graph
+    /// is left in the state of the last valid replacement
     ///
     /// # Example
     ///
@@ -671,101 +1843,314 @@ where
     /// let mut initializer = random_message_initializer(rng, -0.5, 0.5);
     ///
     /// // Message passing schedulers
-    /// let factor_scheduler = get_standard_factor_scheduler(0.);
-    /// let variable_scheduler = get_standard_variable_scheduler(0.);
+    /// let factor_scheduler = get_standard_factor_scheduler(0.5);
+    /// let variable_scheduler = get_standard_variable_scheduler(0.5);
     ///
-    /// // Message passing
-    /// let mut fgb = FactorGraphBuilder::<Factor, Variable>::new_with_capacity(2, 2);
-    /// fgb.fill(IsingVariable::new());
-    /// fgb.add_factor(
-    ///     IsingFactor::new(0.5f64, 0.5f64, -0.5f64),
-    ///    &[0, 1],
-    ///    &mut initializer,
-    /// );
+    /// // A chain of 3 spins
+    /// let mut fgb = FactorGraphBuilder::<Factor, Variable>::new_with_variables(vec![IsingVariable::new(); 3], 2);
+    /// fgb.add_factor(IsingFactor::new(0.5, 0.1, 0.), &[0, 1], &mut initializer).unwrap();
+    /// fgb.add_factor(IsingFactor::new(0.5, 0., 0.), &[1, 2], &mut initializer).unwrap();
     /// let mut fg = fgb.build();
-    /// let _ = fg.run_message_passing_parallel(
-    ///     100,
-    ///     0,
-    ///     1e-10,
-    ///     &factor_scheduler,
-    ///     &variable_scheduler,
-    /// ).unwrap();
     ///
-    /// // Sampling
-    /// let mut rng = thread_rng();
-    /// let sampling_info = fg.sample(
+    /// // Changing the second coupling
+    /// let realizations = [0.2, 0.4, -0.3].map(|coupling| vec![(1, IsingFactor::new(coupling, 0., 0.))]);
+    /// let magnetizations = fg.scan_realizations(
+    ///     realizations,
     ///     100,
     ///     0,
     ///     1e-10,
-    ///     &mut rng,
     ///     &factor_scheduler,
     ///     &variable_scheduler,
+    ///     |fg, result| {
+    ///         assert!(result.is_ok());
+    ///         fg.variable_marginals()[2][0]
+    ///     },
     /// ).unwrap();
-    ///
-    /// // Validation
-    ///
-    /// // Sampling adds fixing factor to each variable
-    /// let variable_degree = fg.get_variable_degrees();
-    /// let factor_degree = fg.get_factor_degrees();
-    /// assert_eq!(variable_degree, vec![2, 2]);
-    /// assert_eq!(factor_degree, vec![2, 1, 1]);
-    ///
-    /// let marginals = fg.variable_marginals();
-    /// let samples = sampling_info.samples;
-    /// assert!((marginals[0][((1 - samples[0]) / 2) as usize] - 1f64).abs() < 1e-8);
-    /// assert!((marginals[1][((1 - samples[1]) / 2) as usize] - 1f64).abs() < 1e-8);
+    /// assert_eq!(magnetizations.len(), 3);
     /// ```
-    pub fn sample(
+    #[allow(clippy::too_many_arguments)]
+    pub fn scan_realizations<R>(
         &mut self,
+        realizations: impl IntoIterator<Item = impl IntoIterator<Item = (usize, F)>>,
         max_iterations_number: usize,
         min_iterations_number: usize,
         threshold: f64,
-        rng: &mut impl Rng,
         factor_scheduler: &impl Fn(usize) -> F::Parameters,
         variable_scheduler: &impl Fn(usize) -> V::Parameters,
-    ) -> FGResult<SamplingInfo<V::Sample>> {
-        let variables_number = self.variables.len();
-        let mut samples = Vec::with_capacity(variables_number);
-        let mut total_iterations_number = 0;
-        let mut iterations_per_variable = Vec::with_capacity(self.variables.len());
-        for i in 0..variables_number {
-            let sample = self.variables.get_mut(i).unwrap().sample(rng);
-            samples.push(sample);
-            self.freeze_variable(&sample, i).unwrap();
-            match self.run_message_passing_parallel(
+        mut measure: impl FnMut(&Self, FGResult<MessagePassingInfo>) -> R,
+    ) -> FGResult<Vec<R>> {
+        let mut measurements = Vec::new();
+        for realization in realizations {
+            for (factor_index, factor) in realization {
+                self.replace_factor(factor_index, factor)?;
+            }
+            let result = self.run_message_passing_parallel(
                 max_iterations_number,
                 min_iterations_number,
                 threshold,
                 factor_scheduler,
                 variable_scheduler,
-            ) {
-                Ok(info) => {
-                    total_iterations_number += info.iterations_number;
-                    iterations_per_variable.push(info.iterations_number);
-                }
-                Err(info) => {
-                    if let FGError::MessagePassingError {
-                        iterations_number,
-                        last_discrepancy,
-                        discrepancy_dynamics,
-                    } = info
-                    {
-                        return Err(FGError::SamplingError {
-                            variables_number: i,
-                            total_iterations_number: total_iterations_number + iterations_number,
-                            last_discrepancy,
-                            discrepancy_dynamics,
-                        });
-                    } else {
-                        unreachable!()
-                    }
+            );
+            measurements.push(measure(self, result));
+        }
+        Ok(measurements)
+    }
+}
+
+// private methods --------------------------------------------------------------------------
+
+impl<F, V> FactorGraph<F, V>
+where
+    F: Factor,
+    V: Variable<Message = F::Message>,
+{
+    // Updates all messages once and returns the maximal discrepancy,
+    // `factor_update` and `variable_update` evaluate new messages of a node.
+    // Nodes pull messages sent to them from neighbours by indices,
+    // so that each node is modified only by its own task
+    #[inline(always)]
+    fn sweep(
+        &mut self,
+        factor_parameters: &F::Parameters,
+        variable_parameters: &V::Parameters,
+        factor_update: &(impl Fn(&mut FactorNode<F, V>, &F::Parameters) + Sync),
+        variable_update: &(impl Fn(&mut VariableNode<V, F>, &V::Parameters) + Sync),
+    ) -> f64 {
+        #[cfg(feature = "parallel")]
+        if let Some(thread_pool) = self.thread_pool.clone() {
+            return thread_pool.install(|| {
+                self.sweep_nodes(
+                    factor_parameters,
+                    variable_parameters,
+                    factor_update,
+                    variable_update,
+                )
+            });
+        }
+        self.sweep_nodes(
+            factor_parameters,
+            variable_parameters,
+            factor_update,
+            variable_update,
+        )
+    }
+
+    #[cfg(feature = "parallel")]
+    fn sweep_nodes(
+        &mut self,
+        factor_parameters: &F::Parameters,
+        variable_parameters: &V::Parameters,
+        factor_update: &(impl Fn(&mut FactorNode<F, V>, &F::Parameters) + Sync),
+        variable_update: &(impl Fn(&mut VariableNode<V, F>, &V::Parameters) + Sync),
+    ) -> f64 {
+        let edges_number: usize = self.factors.iter().map(|f| f.messages.len()).sum();
+        if edges_number < self.parallel_threshold {
+            return self.sweep_nodes_serial(
+                factor_parameters,
+                variable_parameters,
+                factor_update,
+                variable_update,
+            );
+        }
+        // chunks of nodes have approximately equal total degree
+        let factor_cost = |factor: &FactorNode<F, V>| factor.messages.len() + 1;
+        let variable_cost = |variable: &VariableNode<V, F>| variable.messages.len() + 1;
+        balanced_chunks(&mut self.factors, factor_cost)
+            .into_par_iter()
+            .for_each(|chunk| {
+                for factor in chunk {
+                    factor_update(factor, factor_parameters);
                 }
+            });
+        let sent: Vec<_> = self.factors.iter().map(|f| f.messages.as_slice()).collect();
+        let factors_discrepancy = balanced_chunks(&mut self.variables, variable_cost)
+            .into_par_iter()
+            .map(|chunk| {
+                chunk
+                    .iter_mut()
+                    .map(|variable| {
+                        let max_discrepancy = if variable.receivers.len() >= MIN_HUB_DEGREE {
+                            variable.receive_messages_split(&sent)
+                        } else {
+                            variable.receive_messages(|i| sent[i])
+                        };
+                        variable_update(variable, variable_parameters);
+                        max_discrepancy
+                    })
+                    .fold(0f64, nan_max)
+            })
+            .reduce(|| 0f64, nan_max);
+        let sent: Vec<_> = self
+            .variables
+            .iter()
+            .map(|v| v.messages.as_slice())
+            .collect();
+        let variables_discrepancy = balanced_chunks(&mut self.factors, factor_cost)
+            .into_par_iter()
+            .map(|chunk| {
+                chunk
+                    .iter_mut()
+                    .map(|factor| {
+                        if factor.receivers.len() >= MIN_HUB_DEGREE {
+                            factor.receive_messages_split(&sent)
+                        } else {
+                            factor.receive_messages(|i| sent[i])
+                        }
+                    })
+                    .fold(0f64, nan_max)
+            })
+            .reduce(|| 0f64, nan_max);
+        nan_max(factors_discrepancy, variables_discrepancy)
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    #[inline(always)]
+    fn sweep_nodes(
+        &mut self,
+        factor_parameters: &F::Parameters,
+        variable_parameters: &V::Parameters,
+        factor_update: &(impl Fn(&mut FactorNode<F, V>, &F::Parameters) + Sync),
+        variable_update: &(impl Fn(&mut VariableNode<V, F>, &V::Parameters) + Sync),
+    ) -> f64 {
+        self.sweep_nodes_serial(
+            factor_parameters,
+            variable_parameters,
+            factor_update,
+            variable_update,
+        )
+    }
+
+    // Messages are pulled directly from neighbouring nodes,
+    // thus a serial sweep does not allocate
+    fn sweep_nodes_serial(
+        &mut self,
+        factor_parameters: &F::Parameters,
+        variable_parameters: &V::Parameters,
+        factor_update: &(impl Fn(&mut FactorNode<F, V>, &F::Parameters) + Sync),
+        variable_update: &(impl Fn(&mut VariableNode<V, F>, &V::Parameters) + Sync),
+    ) -> f64 {
+        self.factors
+            .iter_mut()
+            .for_each(|factor| factor_update(factor, factor_parameters));
+        let factors = &self.factors;
+        let factors_discrepancy = self
+            .variables
+            .iter_mut()
+            .map(|variable| {
+                let max_discrepancy = variable.receive_messages(|i| &factors[i].messages);
+                variable_update(variable, variable_parameters);
+                max_discrepancy
+            })
+            .fold(0f64, nan_max);
+        let variables = &self.variables;
+        let variables_discrepancy = self
+            .factors
+            .iter_mut()
+            .map(|factor| factor.receive_messages(|i| &variables[i].messages))
+            .fold(0f64, nan_max);
+        nan_max(factors_discrepancy, variables_discrepancy)
+    }
+
+    // Runs message passing calling `hook` after each iteration with
+    // the iteration number and the iteration's discrepancy,
+    // the hook can interrupt message passing by returning `ControlFlow::Break`.
+    // Message passing converges once the discrepancy stays below the threshold
+    // for `convergence_window` consecutive iterations
+    #[allow(clippy::too_many_arguments)]
+    #[inline]
+    pub(crate) fn run_message_passing_with_hook(
+        &mut self,
+        max_iterations_number: usize,
+        min_iterations_number: usize,
+        threshold: f64,
+        convergence_window: usize,
+        factor_scheduler: &impl Fn(usize) -> F::Parameters,
+        variable_scheduler: &impl Fn(usize) -> V::Parameters,
+        factor_update: &(impl Fn(&mut FactorNode<F, V>, &F::Parameters) + Sync),
+        variable_update: &(impl Fn(&mut VariableNode<V, F>, &V::Parameters) + Sync),
+        mut hook: impl FnMut(&Self, usize, f64) -> ControlFlow<()>,
+    ) -> FGResult<MessagePassingInfo> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!(
+            "message_passing",
+            factors_number = self.factors.len(),
+            variables_number = self.variables.len(),
+            max_iterations_number,
+            threshold,
+        )
+        .entered();
+        let mut last_discrepancy = f64::MAX;
+        let mut discrepancy_dynamics = Vec::with_capacity(max_iterations_number);
+        let mut converged_iterations_number = 0;
+        for i in 0..max_iterations_number {
+            let factor_parameters = factor_scheduler(i);
+            let variable_parameters = variable_scheduler(i);
+            #[cfg(all(debug_assertions, not(feature = "parallel")))]
+            let allocations_number = AllocationCounter::allocations_number();
+            let max_discrepancy = self.sweep(
+                &factor_parameters,
+                &variable_parameters,
+                factor_update,
+                variable_update,
+            );
+            // sweeps after the first one must not allocate, it is verified within
+            // `AllocationCounter::audit` if the counter is a global allocator
+            #[cfg(all(debug_assertions, not(feature = "parallel")))]
+            debug_assert!(
+                i == 0
+                    || !AllocationCounter::is_audited()
+                    || allocations_number == AllocationCounter::allocations_number(),
+                "message passing sweep allocated at iteration {i}"
+            );
+            // contradictions make products of factors and messages vanish, thus
+            // factors are checked lazily once messages diverge
+            if !max_discrepancy.is_finite() && last_discrepancy.is_finite() {
+                self.check_contradictions()?;
+            }
+            discrepancy_dynamics.push(max_discrepancy);
+            last_discrepancy = max_discrepancy;
+            #[cfg(feature = "tracing")]
+            tracing::trace!(iteration = i, discrepancy = max_discrepancy, "iteration");
+            if hook(self, i, max_discrepancy).is_break() {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(iterations_number = i + 1, last_discrepancy, "interrupted");
+                return Err(FGError::Interrupted {
+                    iterations_number: i + 1,
+                    discrepancy_dynamics,
+                    last_discrepancy,
+                });
+            }
+            if max_discrepancy < threshold {
+                converged_iterations_number += 1;
+            } else {
+                converged_iterations_number = 0;
+            }
+            if (converged_iterations_number >= convergence_window.max(1))
+                && (i + 1 >= min_iterations_number)
+            {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(iterations_number = i, last_discrepancy, "converged");
+                return Ok(MessagePassingInfo {
+                    iterations_number: i,
+                    discrepancy_dynamics,
+                    last_discrepancy,
+                    certificate: self.exactness_certificate(),
+                    marginal_history: None,
+                    observables: Vec::new(),
+                });
             }
         }
-        Ok(SamplingInfo {
-            samples,
-            iterations_per_variable,
-            total_iterations_number,
+        #[cfg(feature = "tracing")]
+        tracing::warn!(
+            iterations_number = max_iterations_number,
+            last_discrepancy,
+            "not converged"
+        );
+        self.check_contradictions()?;
+        Err(FGError::MessagePassingError {
+            iterations_number: max_iterations_number,
+            discrepancy_dynamics,
+            last_discrepancy,
         })
     }
 }
@@ -1,4 +1,10 @@
-use std::{error::Error, fmt::Display, ptr::null_mut};
+use std::{collections::HashMap, error::Error, fmt::Display, ops::Range, sync::OnceLock};
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+
+#[cfg(feature = "parallel")]
+use crate::core::balancing::DEFAULT_PARALLEL_THRESHOLD;
 
 use crate::{
     core::factor::Factor, core::factor_graph::FactorGraph, core::factor_node::FactorNode,
@@ -15,6 +21,63 @@ pub enum FGBuilderError {
 
     /// Index of a variable is out of range
     OutOfRangeVariable(usize, usize),
+
+    /// A label is already used by a variable, contains the index of the variable
+    DuplicateLabel(usize),
+
+    /// A label of a factor's variable is unknown, contains the position of the label
+    UnknownLabel(usize),
+
+    /// A message initializer produced a message that is not valid for a variable,
+    /// contains the index of the variable
+    InvalidMessage(usize),
+
+    /// A variable is added to a group of variables twice, contains the index of the variable
+    DuplicateGroupMember(usize),
+
+    /// A variable appears several times among variables of a factor, i.e. a factor
+    /// forms a self-loop, contains the index of the variable
+    DuplicateVariable(usize),
+
+    /// An identical factor is already attached to the same variables in the same order,
+    /// contains the index of the existing factor
+    DuplicateFactor(usize),
+
+    /// A factor can not be added to a builder, a builder is left unchanged
+    FactorError {
+        /// The ordinal position of a factor, i.e. the index it would get in a factor graph
+        factor_index: usize,
+
+        /// The debug representation of a factor with its parameters
+        factor: String,
+
+        /// The reason a factor is rejected
+        cause: Box<FGBuilderError>,
+    },
+}
+
+impl FGBuilderError {
+    /// Returns the reason of an error, i.e. the cause of an error
+    /// of adding a factor and the error itself otherwise
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::FGBuilderError;
+    /// use gmrs::ising::{new_ising_builder, IsingFactor, IsingMessage, SumProduct};
+    ///
+    /// let mut fgb = new_ising_builder::<SumProduct>(2, 1);
+    /// let error = fgb
+    ///     .add_factor(IsingFactor::new(0.5, 0., 0.), &[0, 2], &mut || IsingMessage(0.))
+    ///     .unwrap_err();
+    /// assert_eq!(error.reason(), &FGBuilderError::OutOfRangeVariable(2, 2));
+    /// ```
+    pub fn reason(&self) -> &FGBuilderError {
+        match self {
+            FGBuilderError::FactorError { cause, .. } => cause.reason(),
+            _ => self,
+        }
+    }
 }
 
 impl Display for FGBuilderError {
@@ -32,11 +95,57 @@ impl Display for FGBuilderError {
                 pos,
                 size,
             ),
+            FGBuilderError::DuplicateLabel(var_index) => write!(
+                f,
+                "A label is already used by the variable {}",
+                var_index,
+            ),
+            FGBuilderError::UnknownLabel(pos) => write!(
+                f,
+                "Label {} of a factor's variables does not belong to any variable",
+                pos,
+            ),
+            FGBuilderError::InvalidMessage(var_index) => write!(
+                f,
+                "A message initializer produced an invalid message for the variable {}",
+                var_index,
+            ),
+            FGBuilderError::DuplicateGroupMember(var_index) => write!(
+                f,
+                "The variable {} already belongs to a group",
+                var_index,
+            ),
+            FGBuilderError::DuplicateVariable(var_index) => write!(
+                f,
+                "The variable {} appears several times among variables of a factor",
+                var_index,
+            ),
+            FGBuilderError::DuplicateFactor(fac_index) => write!(
+                f,
+                "An identical factor {} is already attached to the same variables",
+                fac_index,
+            ),
+            FGBuilderError::FactorError {
+                factor_index,
+                factor,
+                cause,
+            } => write!(
+                f,
+                "Factor {} can not be added: {}. The factor: {}",
+                factor_index, cause, factor,
+            ),
         }
     }
 }
 
-impl Error for FGBuilderError {}
+impl Error for FGBuilderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FGBuilderError::FactorError { cause, .. } => Some(cause.as_ref()),
+            _ => None,
+        }
+    }
+}
 
 /// Factor graph builder's methods result type
 pub type FGBuilderResult<T> = Result<T, FGBuilderError>;
@@ -52,6 +161,9 @@ where
 {
     factors: Vec<FactorNode<F, V>>,
     variables: Vec<VariableNode<V, F>>,
+    // indices of variables of factors mapped to indices of factors attached to them
+    factor_indices: HashMap<Vec<usize>, Vec<usize>>,
+    multi_edges_allowed: bool,
 }
 
 impl<F, V> Default for FactorGraphBuilder<F, V>
@@ -88,6 +200,8 @@ where
         FactorGraphBuilder {
             factors: Vec::new(),
             variables: Vec::new(),
+            factor_indices: HashMap::new(),
+            multi_edges_allowed: false,
         }
     }
 
@@ -112,9 +226,87 @@ where
     /// ```
     #[inline]
     pub fn new_with_capacity(variables_capacity: usize, factors_capacity: usize) -> Self {
-        let variables = Vec::with_capacity(variables_capacity);
-        let factors = Vec::with_capacity(factors_capacity);
-        FactorGraphBuilder { factors, variables }
+        FactorGraphBuilder {
+            factors: Vec::with_capacity(factors_capacity),
+            variables: Vec::with_capacity(variables_capacity),
+            factor_indices: HashMap::with_capacity(factors_capacity),
+            multi_edges_allowed: false,
+        }
+    }
+
+    /// Creates a factor graph with given variables and preallocated memory for factors.
+    /// Variables may carry their own state, e.g. individual parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `variables` - Variables of a factor graph, the position of a variable
+    ///   in the sequence is its index
+    /// * `factors_capacity` - A number of factors
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::FactorGraphBuilder;
+    /// use gmrs::ising::{IsingFactor, IsingVariable, SumProduct};
+    ///
+    /// // Aliases to shorten types
+    /// type Factor = IsingFactor<SumProduct>;
+    /// type Variable = IsingVariable<SumProduct>;
+    ///
+    /// let fgb = FactorGraphBuilder::<Factor, Variable>::new_with_variables(vec![IsingVariable::new(); 10], 9);
+    /// ```
+    #[inline]
+    pub fn new_with_variables(
+        variables: impl IntoIterator<Item = V>,
+        factors_capacity: usize,
+    ) -> Self {
+        let mut fgb = Self::new_with_capacity(0, factors_capacity);
+        fgb.add_variables(variables);
+        fgb
+    }
+
+    /// Sets whether factors could form multi-edges, i.e. a variable could appear
+    /// several times among variables of a factor (a self-loop) and an identical factor
+    /// could be attached to the same variables several times. Such multi-edges are typically
+    /// mistakes of model construction, thus they are rejected by
+    /// [`FactorGraphBuilder::add_factor`] by default
+    ///
+    /// # Arguments
+    ///
+    /// * `multi_edges_allowed` - Whether multi-edges are allowed
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::FGBuilderError;
+    /// use gmrs::ising::{new_ising_builder, IsingFactor, IsingMessage, SumProduct};
+    ///
+    /// let mut fgb = new_ising_builder::<SumProduct>(2, 3);
+    /// fgb.add_factor(IsingFactor::new(0.5, 0., 0.), &[0, 1], &mut || IsingMessage(0.)).unwrap();
+    /// let error = fgb
+    ///     .add_factor(IsingFactor::new(0.5, 0., 0.), &[0, 1], &mut || IsingMessage(0.))
+    ///     .unwrap_err();
+    /// assert_eq!(error.reason(), &FGBuilderError::DuplicateFactor(0));
+    /// let error = fgb
+    ///     .add_factor(IsingFactor::new(0.5, 0., 0.), &[1, 1], &mut || IsingMessage(0.))
+    ///     .unwrap_err();
+    /// assert_eq!(error.reason(), &FGBuilderError::DuplicateVariable(1));
+    /// // A different factor over the same variables is accepted
+    /// fgb.add_factor(IsingFactor::new(0., 0., 0.2), &[0, 1], &mut || IsingMessage(0.)).unwrap();
+    ///
+    /// fgb.set_multi_edges_allowed(true);
+    /// fgb.add_factor(IsingFactor::new(0.5, 0., 0.), &[0, 1], &mut || IsingMessage(0.)).unwrap();
+    /// assert_eq!(fgb.build().get_variable_degrees(), vec![3, 3]);
+    /// ```
+    #[inline]
+    pub fn set_multi_edges_allowed(&mut self, multi_edges_allowed: bool) {
+        self.multi_edges_allowed = multi_edges_allowed;
+    }
+
+    /// Returns whether factors could form multi-edges
+    #[inline]
+    pub fn multi_edges_allowed(&self) -> bool {
+        self.multi_edges_allowed
     }
 
     /// Fills a vector of variables of a factor graph by a given variable
@@ -127,6 +319,7 @@ where
     /// # Example
     ///
     /// ```
+    /// #![allow(deprecated)]
     /// use gmrs::core::FactorGraphBuilder;
     /// use gmrs::ising::{IsingFactor, IsingVariable, SumProduct};
     ///
@@ -135,9 +328,11 @@ where
     /// type Variable = IsingVariable<SumProduct>;
     ///
     /// // Builder creation
-    /// let mut fgb = FactorGraphBuilder::<Factor, Variable>::new_with_capacity(10, 1);
-    /// fgb.fill(IsingVariable::new());
+    /// let mut fgb = FactorGraphBuilder::<Factor, Variable>::new_with_variables(vec![IsingVariable::new(); 10], 1);
     /// ```
+    #[deprecated(
+        note = "depends on the allocator's capacity, use `new_with_variables` or `add_variables` instead"
+    )]
     #[inline]
     pub fn fill(&mut self, variable: V) {
         let capacity = self.variables.capacity();
@@ -148,7 +343,7 @@ where
         }
     }
 
-    /// Adds a variable to a factor graph
+    /// Adds a variable to a factor graph and returns its index
     ///
     /// # Arguments
     ///
@@ -165,12 +360,43 @@ where
     /// type Variable = IsingVariable<SumProduct>;
     ///
     /// let mut fgb = FactorGraphBuilder::<Factor, Variable>::new_with_capacity(0, 1);
+    /// assert_eq!(fgb.add_variable(IsingVariable::new()), 0);
+    /// assert_eq!(fgb.add_variable(IsingVariable::new()), 1);
+    /// ```
+    #[inline]
+    pub fn add_variable(&mut self, variable: V) -> usize {
+        self.variables
+            .push(VariableNode::new_disconnected(variable));
+        self.variables.len() - 1
+    }
+
+    /// Adds variables to a factor graph and returns the range of their indices
+    ///
+    /// # Arguments
+    ///
+    /// * `variables` -  Variables to add
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::FactorGraphBuilder;
+    /// use gmrs::ising::{IsingFactor, IsingVariable, SumProduct};
+    ///
+    /// // Aliases to shorten types
+    /// type Factor = IsingFactor<SumProduct>;
+    /// type Variable = IsingVariable<SumProduct>;
+    ///
+    /// let mut fgb = FactorGraphBuilder::<Factor, Variable>::new();
     /// fgb.add_variable(IsingVariable::new());
+    /// let indices = fgb.add_variables((0..5).map(|_| IsingVariable::new()));
+    /// assert_eq!(indices, 1..6);
     /// ```
     #[inline]
-    pub fn add_variable(&mut self, variable: V) {
+    pub fn add_variables(&mut self, variables: impl IntoIterator<Item = V>) -> Range<usize> {
+        let start = self.variables.len();
         self.variables
-            .push(VariableNode::new_disconnected(variable))
+            .extend(variables.into_iter().map(VariableNode::new_disconnected));
+        start..self.variables.len()
     }
 
     /// Adds a factor to a factor graph
@@ -185,7 +411,15 @@ where
     ///
     /// If number of `var_indices` does not match a factor degree, the method
     /// returns an error. If an index from `var_indices` is out of range of
-    /// the variables list, the method returns an error
+    /// the variables list, the method returns an error. Unless multi-edges are allowed
+    /// (see [`FactorGraphBuilder::set_multi_edges_allowed`]), the method returns an error
+    /// if a variable appears in `var_indices` several times or if an identical factor,
+    /// i.e. a factor with the same debug representation, has been added over the same
+    /// `var_indices` before. If a message initializer produces
+    /// a message that is not valid for a variable (see [`Variable::is_valid_message`]),
+    /// the method returns an error. Errors are reported as
+    /// [`FGBuilderError::FactorError`] with the ordinal position of a factor, and
+    /// a builder is left unchanged, thus it can be used further
     ///
     /// # Example
     ///
@@ -199,8 +433,7 @@ where
     /// type Variable = IsingVariable<SumProduct>;
     ///
     /// // Builder creation
-    /// let mut fgb = FactorGraphBuilder::<Factor, Variable>::new_with_capacity(10, 1);
-    /// fgb.fill(IsingVariable::new());
+    /// let mut fgb = FactorGraphBuilder::<Factor, Variable>::new_with_variables(vec![IsingVariable::new(); 10], 1);
     ///
     /// // Messages initializer
     /// let rng = thread_rng();
@@ -219,44 +452,141 @@ where
         var_indices: &[usize],
         message_initializer: &mut impl FnMut() -> F::Message,
     ) -> FGBuilderResult<()> {
-        let factor_deg = var_indices.len();
-        if factor.degree() != factor_deg {
-            return Err(FGBuilderError::DegreeError(
-                factor.degree(),
-                var_indices.to_vec(),
-            ));
+        let messages = match self
+            .validate_factor(&factor, var_indices)
+            .or_else(|| self.find_duplicate_factor(&factor, var_indices))
+        {
+            Some(cause) => Err(cause),
+            None => self.init_messages(var_indices, message_initializer),
+        };
+        match messages {
+            Ok(messages) => {
+                self.connect_factor(factor, var_indices, messages);
+                Ok(())
+            }
+            Err(cause) => Err(self.factor_error(&factor, cause)),
         }
-        let factor_node = FactorNode::new_disconnected(factor);
-        self.factors.push(factor_node);
-        let factors_number = self.factors.len();
-        let MutFactorsAndVariables { factors, variables } = self.factors_and_variables();
-        let last_factor = factors.last_mut().unwrap();
-        for index in var_indices {
-            let variable = if let Some(v) = variables.get_mut(*index) {
-                v
-            } else {
-                return Err(FGBuilderError::OutOfRangeVariable(variables.len(), *index));
-            };
-            let factor_message = message_initializer();
-            let variable_message = message_initializer();
-            last_factor.senders.push(null_mut());
-            last_factor.receivers.push(factor_message.clone());
-            last_factor.messages.push(variable_message.clone());
-            last_factor.var_node_indices.push(*index);
-            variable.senders.push(null_mut());
-            variable.messages.push(factor_message);
-            variable.receivers.push(variable_message);
-            variable.fac_node_indices.push(factors_number - 1);
-            let variable_recivers_number = variable.receivers.len();
-            let factor_recivers_number = last_factor.receivers.len();
-            last_factor
-                .var_node_receiver_indices
-                .push(variable_recivers_number - 1);
-            variable
-                .fac_node_receiver_indices
-                .push(factor_recivers_number - 1);
+    }
+
+    /// Adds factors to a factor graph and returns the range of their indices.
+    /// Memory for factors and their edges is reserved at once, thus it is
+    /// faster than adding factors one by one
+    ///
+    /// # Arguments
+    ///
+    /// * `factors` - Pairs of new factors and indices of their adjoint variables
+    /// * `message_initializer` - An object that initializes messages
+    ///
+    /// # Notes
+    ///
+    /// Factors are validated as in [`FactorGraphBuilder::add_factor`] and messages are
+    /// initialized in the same order, thus the result is the same as of adding factors
+    /// one by one. The method stops at the first rejected factor and returns its error,
+    /// factors preceding it are left in a builder
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, IsingFactor, IsingMessage, SumProduct};
+    ///
+    /// let mut fgb = new_ising_builder::<SumProduct>(100, 4950);
+    /// let pairs = (0..100).flat_map(|i| ((i + 1)..100).map(move |j| [i, j]));
+    /// let indices = fgb
+    ///     .add_factors(
+    ///         pairs.map(|pair| (IsingFactor::new(0.01, 0., 0.), pair)),
+    ///         &mut || IsingMessage(0.),
+    ///     )
+    ///     .unwrap();
+    /// assert_eq!(indices, 0..4950);
+    /// assert_eq!(fgb.build().get_variable_degrees(), vec![99; 100]);
+    /// ```
+    pub fn add_factors<I>(
+        &mut self,
+        factors: impl IntoIterator<Item = (F, I)>,
+        message_initializer: &mut impl FnMut() -> F::Message,
+    ) -> FGBuilderResult<Range<usize>>
+    where
+        I: AsRef<[usize]>,
+    {
+        let factors: Vec<_> = factors.into_iter().collect();
+        self.reserve(factors.iter().map(|(_, var_indices)| var_indices.as_ref()));
+        let start = self.factors.len();
+        for (factor, var_indices) in factors {
+            self.add_factor(factor, var_indices.as_ref(), message_initializer)?;
         }
-        Ok(())
+        Ok(start..self.factors.len())
+    }
+
+    /// Adds factors to a factor graph similarly to [`FactorGraphBuilder::add_factors`],
+    /// but factors are validated and their messages are initialized in parallel
+    ///
+    /// # Arguments
+    ///
+    /// * `factors` - A parallel iterator over pairs of new factors and indices
+    ///   of their adjoint variables
+    /// * `message_initializer` - An object that initializes messages, it is called
+    ///   from several threads
+    ///
+    /// # Notes
+    ///
+    /// Factors get indices in the order of the parallel iterator, if a factor is
+    /// rejected, factors preceding it are left in a builder. Since messages are initialized
+    /// in parallel, a random message initializer should not rely on the order of calls
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, IsingFactor, IsingMessage, SumProduct};
+    /// use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+    ///
+    /// let mut fgb = new_ising_builder::<SumProduct>(100, 4950);
+    /// let pairs = (0..100usize)
+    ///     .into_par_iter()
+    ///     .flat_map_iter(|i| ((i + 1)..100).map(move |j| vec![i, j]));
+    /// let indices = fgb
+    ///     .add_factors_parallel(
+    ///         pairs.map(|pair| (IsingFactor::new(0.01, 0., 0.), pair)),
+    ///         &|| IsingMessage(0.),
+    ///     )
+    ///     .unwrap();
+    /// assert_eq!(indices, 0..4950);
+    /// assert_eq!(fgb.build().get_variable_degrees(), vec![99; 100]);
+    /// ```
+    #[cfg(feature = "parallel")]
+    pub fn add_factors_parallel(
+        &mut self,
+        factors: impl IntoParallelIterator<Item = (F, Vec<usize>)>,
+        message_initializer: &(impl Fn() -> F::Message + Sync),
+    ) -> FGBuilderResult<Range<usize>>
+    where
+        F: Sync,
+        V: Sync,
+    {
+        let builder = &*self;
+        let factors: Vec<_> = factors
+            .into_par_iter()
+            .map(|(factor, var_indices)| {
+                let messages = match builder.validate_factor(&factor, &var_indices) {
+                    Some(cause) => Err(cause),
+                    None => Ok(builder.init_messages(&var_indices, &mut || message_initializer())),
+                };
+                (factor, var_indices, messages)
+            })
+            .collect();
+        self.reserve(factors.iter().map(|(_, var_indices, _)| &var_indices[..]));
+        let start = self.factors.len();
+        for (factor, var_indices, messages) in factors {
+            // duplicates are searched sequentially, since they could be among new factors
+            let messages = messages.and_then(|messages| {
+                self.find_duplicate_factor(&factor, &var_indices)
+                    .map_or(messages, Err)
+            });
+            match messages {
+                Ok(messages) => self.connect_factor(factor, &var_indices, messages),
+                Err(cause) => return Err(self.factor_error(&factor, cause)),
+            }
+        }
+        Ok(start..self.factors.len())
     }
 
     /// Returns a factor graph
@@ -273,8 +603,7 @@ where
     /// type Variable = IsingVariable<SumProduct>;
     ///
     /// // Builder creation
-    /// let mut fgb = FactorGraphBuilder::<Factor, Variable>::new_with_capacity(10, 9);
-    /// fgb.fill(IsingVariable::new());
+    /// let mut fgb = FactorGraphBuilder::<Factor, Variable>::new_with_variables(vec![IsingVariable::new(); 10], 9);
     ///
     /// // Messages initializer
     /// let rng = thread_rng();
@@ -292,44 +621,142 @@ where
     /// let fg = fgb.build();
     /// ```
     #[inline]
-    pub fn build(mut self) -> FactorGraph<F, V> {
-        for factor in &mut self.factors {
-            factor.init_senders(&mut self.variables);
-        }
-        for variable in &mut self.variables {
-            variable.init_senders(&mut self.factors);
-        }
+    pub fn build(self) -> FactorGraph<F, V> {
         FactorGraph {
             factors: self.factors,
             variables: self.variables,
+            certificate: OnceLock::new(),
+            #[cfg(feature = "parallel")]
+            thread_pool: None,
+            #[cfg(feature = "parallel")]
+            parallel_threshold: DEFAULT_PARALLEL_THRESHOLD,
         }
     }
 }
 
 // private methods --------------------------------------------------------------------------
 
-struct MutFactorsAndVariables<'a, F, V>
-where
-    F: Factor,
-    V: Variable<Message = F::Message>,
-{
-    factors: &'a mut Vec<FactorNode<F, V>>,
-    variables: &'a mut Vec<VariableNode<V, F>>,
-}
-
 impl<F, V> FactorGraphBuilder<F, V>
 where
     F: Factor,
     V: Variable<Message = F::Message>,
 {
-    #[inline(always)]
-    fn factors_and_variables(&mut self) -> MutFactorsAndVariables<F, V> {
-        let mut_ptr: *mut Self = self;
-        unsafe {
-            MutFactorsAndVariables {
-                factors: &mut (*mut_ptr).factors,
-                variables: &mut (*mut_ptr).variables,
+    // Returns the reason a factor can not be attached to given variables, except
+    // duplicate factors and invalid messages
+    fn validate_factor(&self, factor: &F, var_indices: &[usize]) -> Option<FGBuilderError> {
+        let variables_number = self.variables.len();
+        if factor.degree() != var_indices.len() {
+            return Some(FGBuilderError::DegreeError(
+                factor.degree(),
+                var_indices.to_vec(),
+            ));
+        }
+        if let Some(index) = var_indices.iter().find(|index| **index >= variables_number) {
+            return Some(FGBuilderError::OutOfRangeVariable(variables_number, *index));
+        }
+        if self.multi_edges_allowed {
+            return None;
+        }
+        var_indices
+            .iter()
+            .enumerate()
+            .find(|(position, index)| var_indices[..*position].contains(index))
+            .map(|(_, index)| FGBuilderError::DuplicateVariable(*index))
+    }
+
+    // Returns an error if an identical factor is already attached to given variables
+    fn find_duplicate_factor(&self, factor: &F, var_indices: &[usize]) -> Option<FGBuilderError> {
+        if self.multi_edges_allowed {
+            return None;
+        }
+        // factors are compared by debug representations only on collisions
+        let fac_indices = self.factor_indices.get(var_indices)?;
+        let factor = format!("{:?}", factor);
+        fac_indices
+            .iter()
+            .find(|fac_index| format!("{:?}", self.factors[**fac_index].factor) == factor)
+            .map(|fac_index| FGBuilderError::DuplicateFactor(*fac_index))
+    }
+
+    // Initializes pairs of messages (to a variable, to a factor) of edges of a factor
+    fn init_messages(
+        &self,
+        var_indices: &[usize],
+        message_initializer: &mut impl FnMut() -> F::Message,
+    ) -> FGBuilderResult<Vec<(F::Message, F::Message)>> {
+        let mut messages = Vec::with_capacity(var_indices.len());
+        for index in var_indices {
+            let variable = &self.variables[*index].variable;
+            let (factor_message, variable_message) = (message_initializer(), message_initializer());
+            if !(variable.is_valid_message(&factor_message)
+                && variable.is_valid_message(&variable_message))
+            {
+                return Err(FGBuilderError::InvalidMessage(*index));
             }
+            messages.push((factor_message, variable_message));
+        }
+        Ok(messages)
+    }
+
+    fn factor_error(&self, factor: &F, cause: FGBuilderError) -> FGBuilderError {
+        FGBuilderError::FactorError {
+            factor_index: self.factors.len(),
+            factor: format!("{:?}", factor),
+            cause: Box::new(cause),
+        }
+    }
+
+    // Attaches a validated factor to variables
+    fn connect_factor(
+        &mut self,
+        factor: F,
+        var_indices: &[usize],
+        messages: Vec<(F::Message, F::Message)>,
+    ) {
+        let fac_index = self.factors.len();
+        let mut factor_node = FactorNode::new_disconnected(factor);
+        for (position, (index, (factor_message, variable_message))) in
+            var_indices.iter().zip(messages).enumerate()
+        {
+            let variable = &mut self.variables[*index];
+            factor_node.receivers.push(factor_message.clone());
+            factor_node.messages.push(variable_message.clone());
+            factor_node.var_node_indices.push(*index);
+            factor_node
+                .var_node_receiver_indices
+                .push(variable.receivers.len());
+            variable.messages.push(factor_message);
+            variable.receivers.push(variable_message);
+            variable.fac_node_indices.push(fac_index);
+            variable.fac_node_receiver_indices.push(position);
+        }
+        self.factors.push(factor_node);
+        self.factor_indices
+            .entry(var_indices.to_vec())
+            .or_default()
+            .push(fac_index);
+    }
+
+    // Reserves memory for factors attached to given variables, out of range
+    // indices are ignored
+    fn reserve<'a>(&mut self, factors_var_indices: impl Iterator<Item = &'a [usize]>) {
+        let mut degrees = vec![0; self.variables.len()];
+        let mut factors_number = 0;
+        for var_indices in factors_var_indices {
+            factors_number += 1;
+            for index in var_indices {
+                if let Some(degree) = degrees.get_mut(*index) {
+                    *degree += 1;
+                }
+            }
+        }
+        self.factors.reserve(factors_number);
+        self.factor_indices.reserve(factors_number);
+        for (variable, degree) in self.variables.iter_mut().zip(degrees) {
+            variable.messages.reserve(degree);
+            variable.receivers.reserve(degree);
+            variable.fac_node_indices.reserve(degree);
+            variable.fac_node_receiver_indices.reserve(degree);
         }
     }
 }
@@ -1,6 +1,16 @@
+#[cfg(feature = "parallel")]
+use rayon::prelude::{
+    IndexedParallelIterator, IntoParallelRefIterator, IntoParallelRefMutIterator, ParallelIterator,
+};
+
+#[cfg(feature = "parallel")]
+use crate::core::balancing::MIN_EDGES_PER_TASK;
+
 use crate::{
-    core::factor::Factor, core::message::Message, core::variable::Variable,
-    core::variable_node::VariableNode,
+    core::damping::{eval_damped, eval_damped_fixed, init_damping, AdaptiveDamping, EdgeDamping},
+    core::factor::Factor,
+    core::message::{nan_max, DampableMessage, Message},
+    core::variable::Variable,
 };
 
 #[derive(Debug, Clone)]
@@ -9,19 +19,12 @@ where
     F: Factor,
     V: Variable<Message = F::Message>,
 {
-    factor: F,
+    pub(crate) factor: F,
     pub(crate) var_node_indices: Vec<usize>,
     pub(crate) var_node_receiver_indices: Vec<usize>,
     pub(crate) messages: Vec<V::Message>,
-    pub(crate) senders: Vec<*mut V::Message>,
     pub(crate) receivers: Vec<F::Message>,
-}
-
-unsafe impl<F, V> Send for FactorNode<F, V>
-where
-    F: Factor,
-    V: Variable<Message = F::Message>,
-{
+    pub(crate) damping: Vec<EdgeDamping<F::Message>>,
 }
 
 impl<F, V> FactorNode<F, V>
@@ -36,8 +39,8 @@ where
             var_node_indices: Vec::new(),
             var_node_receiver_indices: Vec::new(),
             messages: Vec::new(),
-            senders: Vec::new(),
             receivers: Vec::new(),
+            damping: Vec::new(),
         }
     }
 
@@ -47,44 +50,120 @@ where
     }
 
     #[inline(always)]
-    pub(super) fn init_senders(&mut self, variables: &mut [VariableNode<V, F>]) {
-        let indices_iter = self
-            .var_node_indices
-            .iter()
-            .zip(&self.var_node_receiver_indices);
-        let senders: Vec<_> = indices_iter
-            .map(|(var_index, var_receiver_index)| unsafe {
-                let var = variables.get_unchecked_mut(*var_index);
-                let mut_ptr: *mut _ = var.receivers.get_unchecked_mut(*var_receiver_index);
-                mut_ptr
-            })
-            .collect();
-        self.senders = senders;
+    pub(super) fn eval_messages(&mut self, parameters: &F::Parameters) {
+        match self.messages.len() {
+            1 => self.eval_messages_fixed::<1>(parameters),
+            2 => self.eval_messages_fixed::<2>(parameters),
+            _ => self
+                .factor
+                .send_messages(&self.receivers, &mut self.messages, parameters),
+        }
     }
 
     #[inline(always)]
-    pub(super) fn eval_messages(&mut self, parameters: &F::Parameters) {
-        self.factor
-            .send_messages(&self.receivers, &mut self.messages, parameters)
+    fn eval_messages_fixed<const N: usize>(&mut self, parameters: &F::Parameters) {
+        // receivers and messages always have the same length
+        let src: &[F::Message; N] = self.receivers.as_slice().try_into().unwrap();
+        let dst: &mut [F::Message; N] = self.messages.as_mut_slice().try_into().unwrap();
+        self.factor.send_messages_fixed(src, dst, parameters)
+    }
+
+    #[inline(always)]
+    pub(super) fn eval_damped_messages(
+        &mut self,
+        parameters: &F::Parameters,
+        damping: &AdaptiveDamping,
+    ) where
+        F::Message: DampableMessage,
+    {
+        let (factor, receivers) = (&self.factor, &self.receivers);
+        eval_damped(&mut self.damping, &mut self.messages, damping, |messages| {
+            factor.send_messages(receivers, messages, parameters)
+        });
+    }
+
+    #[inline(always)]
+    pub(super) fn init_damping(&mut self)
+    where
+        F::Message: DampableMessage,
+    {
+        init_damping(&mut self.damping, &self.messages);
+    }
+
+    #[inline(always)]
+    pub(super) fn eval_fixed_damped_messages(&mut self, parameters: &F::Parameters, gamma: f64)
+    where
+        F::Message: DampableMessage,
+    {
+        let (factor, receivers) = (&self.factor, &self.receivers);
+        eval_damped_fixed(&mut self.damping, &mut self.messages, gamma, |messages| {
+            factor.send_messages(receivers, messages, parameters)
+        });
     }
 
+    // Decides whether a factor is updated at an iteration of stochastic message passing.
+    // A factor is identified by its first edge, thus the decision depends only on
+    // the seed and does not depend on the order nodes are processed in
     #[inline(always)]
-    pub(super) fn eval_discrepancy(&self) -> f64 {
+    pub(super) fn is_selected(&self, seed: u64, fraction: f64) -> bool {
+        let (Some(var_index), Some(position)) = (
+            self.var_node_indices.first(),
+            self.var_node_receiver_indices.first(),
+        ) else {
+            return true;
+        };
+        let hash = mix_bits(mix_bits(seed ^ *var_index as u64) ^ *position as u64);
+        ((hash >> 11) as f64) / ((1u64 << 53) as f64) < fraction
+    }
+
+    // Replaces the message received from the `position`-th adjoint variable
+    // and returns the discrepancy between the new and the old messages
+    #[inline(always)]
+    pub(super) fn receive_message(&mut self, position: usize, message: &F::Message) -> f64 {
+        let receiver = &mut self.receivers[position];
+        let discrepancy = message.discrepancy(receiver);
+        message.memcpy(receiver);
+        discrepancy
+    }
+
+    // Pulls messages sent by adjoint variables, where `sent(i)` are messages
+    // sent by the i-th variable, and returns the maximal discrepancy
+    #[inline(always)]
+    pub(super) fn receive_messages<'a>(&mut self, sent: impl Fn(usize) -> &'a [F::Message]) -> f64 {
         let mut max_discrepancy = 0f64;
-        for (new_msg, old_msg_ptr) in self.messages.iter().zip(&self.senders) {
-            let discrepancy = new_msg.discrepancy(unsafe { &**old_msg_ptr });
-            if max_discrepancy < discrepancy {
-                max_discrepancy = discrepancy;
-            }
+        let indices_iter = self
+            .var_node_indices
+            .iter()
+            .zip(&self.var_node_receiver_indices);
+        for (receiver, (var_index, var_receiver_index)) in
+            self.receivers.iter_mut().zip(indices_iter)
+        {
+            let message = &sent(*var_index)[*var_receiver_index];
+            let discrepancy = message.discrepancy(receiver);
+            max_discrepancy = nan_max(max_discrepancy, discrepancy);
+            message.memcpy(receiver);
         }
         max_discrepancy
     }
 
-    #[inline(always)]
-    pub(super) fn send_messages(&mut self) {
-        for (msg, dst_ptr) in self.messages.iter().zip(&mut self.senders) {
-            unsafe { (*msg).memcpy(&mut **dst_ptr) }
-        }
+    // Pulls messages similarly to `receive_messages`, but splits edges across workers
+    #[cfg(feature = "parallel")]
+    pub(super) fn receive_messages_split(&mut self, sent: &[&[F::Message]]) -> f64 {
+        let indices_iter = self
+            .var_node_indices
+            .par_iter()
+            .zip(self.var_node_receiver_indices.par_iter());
+        self.receivers
+            .par_iter_mut()
+            .zip(indices_iter)
+            .with_min_len(MIN_EDGES_PER_TASK)
+            .map(|(receiver, (index, receiver_index))| {
+                let message = &sent[*index][*receiver_index];
+                let discrepancy = message.discrepancy(receiver);
+                message.memcpy(receiver);
+                discrepancy
+            })
+            .reduce(|| 0f64, nan_max)
     }
 
     #[inline(always)]
@@ -96,4 +175,35 @@ where
     pub(super) fn factor(&self) -> F::Marginal {
         self.factor.factor()
     }
+
+    /// Computes a message that a factor sends to the `position`-th adjoint
+    /// variable if messages received from some variables are substituted.
+    /// `substitute` takes an index of a variable and returns a message
+    /// replacing the one received from this variable
+    #[inline(always)]
+    pub(crate) fn substituted_message(
+        &self,
+        position: usize,
+        substitute: impl Fn(usize) -> Option<F::Message>,
+        parameters: &F::Parameters,
+    ) -> F::Message {
+        let src: Vec<_> = self
+            .var_node_indices
+            .iter()
+            .zip(&self.receivers)
+            .map(|(var_index, msg)| substitute(*var_index).unwrap_or_else(|| msg.clone()))
+            .collect();
+        let mut dst = self.messages.clone();
+        self.factor.send_messages(&src, &mut dst, parameters);
+        dst.swap_remove(position)
+    }
+}
+
+// Mixes bits of a number by a step of the SplitMix64 generator
+#[inline(always)]
+fn mix_bits(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9E3779B97F4A7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D049BB133111EB);
+    x ^ (x >> 31)
 }
@@ -0,0 +1,136 @@
+use ndarray::ArrayD;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::core::{
+    factor::Factor,
+    factor_graph::{FGError, FGResult, FactorGraph},
+    variable::Variable,
+};
+
+// ------------------------------------------------------------------------------------------
+
+/// Information returned after successful iterative proportional fitting
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FittingInfo {
+    /// Number of scaling steps passed before convergence
+    pub scaling_steps_number: usize,
+
+    /// Total number of message passing iterations
+    pub total_iterations_number: usize,
+
+    /// Maximal discrepancy between factor marginals and target ones
+    pub last_discrepancy: f64,
+}
+
+impl<F, V> FactorGraph<F, V>
+where
+    F: Factor<Marginal = ArrayD<f64>>,
+    V: Variable<Message = F::Message>,
+{
+    /// Runs iterative proportional fitting that adjusts factors so that their
+    /// marginals match target ones. Each scaling step visits targets one by one,
+    /// converges message passing and multiplies a factor by the ratio of its
+    /// target marginal and the current one (see [`Factor::rescaled`]).
+    /// Unit degree factors (see [`FactorGraph::add_factor`]) allow fitting marginals
+    /// of single variables
+    ///
+    /// # Arguments
+    ///
+    /// * `targets` - Pairs of an index of a factor and a target joint marginal of its variables
+    /// * `max_scaling_steps_number` - A maximal number of scaling steps
+    /// * `tolerance` - A maximal absolute difference between marginals and target ones
+    /// * `max_iterations_number` - A maximal number of iterations in a message passing algorithm
+    /// * `min_iterations_number` - A minimal number of iterations in a message passing algorithm
+    /// * `threshold` - A threshold specifying the convergence criterion of message passing
+    /// * `factor_scheduler` - A scheduler of a factor's messages update rule hyper-parameters
+    /// * `variable_scheduler` - A scheduler of a variable's messages update rule hyper-parameters
+    ///
+    /// # Notes
+    ///
+    /// Marginals are message passing ones, thus on graphs with cycles factors are
+    /// fitted to match approximate marginals. Factors are modified in place
+    /// and stay modified if fitting fails
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use ndarray::array;
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let mut fgb = new_ising_builder::<SumProduct>(2, 1);
+    /// fgb.add_factor(IsingFactor::new(0., 0., 0.), &[0, 1], &mut initializer).unwrap();
+    /// let mut fg = fgb.build();
+    /// let factor_scheduler = get_standard_factor_scheduler(0.);
+    /// let variable_scheduler = get_standard_variable_scheduler(0.);
+    /// let target = array![[0.4, 0.1], [0.2, 0.3]].into_dyn();
+    /// fg.fit_marginals(&[(0, target.clone())], 10, 1e-8, 100, 0, 1e-10, &factor_scheduler, &variable_scheduler)
+    ///     .unwrap();
+    /// let marginal = &fg.factor_marginals()[0];
+    /// assert!((marginal - &target).iter().all(|x| x.abs() < 1e-8));
+    /// ```
+    #[allow(clippy::too_many_arguments)]
+    pub fn fit_marginals(
+        &mut self,
+        targets: &[(usize, ArrayD<f64>)],
+        max_scaling_steps_number: usize,
+        tolerance: f64,
+        max_iterations_number: usize,
+        min_iterations_number: usize,
+        threshold: f64,
+        factor_scheduler: &impl Fn(usize) -> F::Parameters,
+        variable_scheduler: &impl Fn(usize) -> V::Parameters,
+    ) -> FGResult<FittingInfo> {
+        let factors_number = self.factors.len();
+        if let Some((fac_index, _)) = targets.iter().find(|(i, _)| *i >= factors_number) {
+            return Err(FGError::OutOfRangeFactor(factors_number, *fac_index));
+        }
+        let mut total_iterations_number = 0;
+        let mut last_discrepancy = f64::MAX;
+        for scaling_step in 0..max_scaling_steps_number {
+            last_discrepancy = 0f64;
+            for (fac_index, target) in targets {
+                let info = self.run_message_passing_parallel(
+                    max_iterations_number,
+                    min_iterations_number,
+                    threshold,
+                    factor_scheduler,
+                    variable_scheduler,
+                )?;
+                total_iterations_number += info.iterations_number;
+                let factor_node = &self.factors[*fac_index];
+                let current = factor_node.marginal();
+                let discrepancy = if current.shape() == target.shape() {
+                    (&current - target).fold(0f64, |acc, x| acc.max(x.abs()))
+                } else {
+                    return Err(FGError::ScalingError(*fac_index));
+                };
+                last_discrepancy = last_discrepancy.max(discrepancy);
+                if discrepancy < tolerance {
+                    continue;
+                }
+                let factor = factor_node
+                    .factor
+                    .rescaled(target, &current)
+                    .ok_or(FGError::ScalingError(*fac_index))?;
+                self.replace_factor(*fac_index, factor)?;
+            }
+            // no factor has been rescaled at this step
+            if last_discrepancy < tolerance {
+                return Ok(FittingInfo {
+                    scaling_steps_number: scaling_step,
+                    total_iterations_number,
+                    last_discrepancy,
+                });
+            }
+        }
+        Err(FGError::FittingError {
+            scaling_steps_number: max_scaling_steps_number,
+            last_discrepancy,
+        })
+    }
+}
@@ -0,0 +1,186 @@
+use std::{collections::HashMap, hash::Hash};
+
+use crate::core::{
+    factor::Factor,
+    factor_graph::FactorGraph,
+    factor_graph_builder::{FGBuilderError, FGBuilderResult, FactorGraphBuilder},
+    variable::Variable,
+};
+
+// ------------------------------------------------------------------------------------------
+
+/// A bidirectional map between user labels of variables and their indices
+#[derive(Debug, Clone)]
+pub struct Labels<K> {
+    labels: Vec<K>,
+    indices: HashMap<K, usize>,
+}
+
+impl<K> Labels<K>
+where
+    K: Eq + Hash + Clone,
+{
+    /// Returns the index of a variable with a given label
+    ///
+    /// # Arguments
+    ///
+    /// * `label` - A label of a variable
+    #[inline]
+    pub fn index(&self, label: &K) -> Option<usize> {
+        self.indices.get(label).copied()
+    }
+
+    /// Returns the label of a variable with a given index
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - An index of a variable
+    #[inline]
+    pub fn label(&self, index: usize) -> Option<&K> {
+        self.labels.get(index)
+    }
+
+    /// Returns the number of labeled variables
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    /// Returns true if there are no labeled variables
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    /// Maps per variable values, e.g. marginals or samples, to their labels
+    ///
+    /// # Arguments
+    ///
+    /// * `values` - Values ordered by indices of variables
+    ///
+    /// # Notes
+    ///
+    /// Values of variables added to a factor graph after building it do not have
+    /// labels and are skipped
+    #[inline]
+    pub fn labeled<T>(&self, values: impl IntoIterator<Item = T>) -> HashMap<K, T> {
+        self.labels.iter().cloned().zip(values).collect()
+    }
+}
+
+/// A factor graph builder addressing variables by user labels,
+/// e.g. strings or arbitrary hashable IDs
+///
+/// # Example
+///
+/// ```
+/// use gmrs::core::LabeledBuilder;
+/// use gmrs::ising::{random_message_initializer, IsingFactor, IsingVariable, SumProduct};
+/// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+/// use rand::thread_rng;
+///
+/// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+/// let mut fgb = LabeledBuilder::<&str, IsingFactor<SumProduct>, IsingVariable<SumProduct>>::new();
+/// fgb.add_variable("rain", IsingVariable::new().with_field(-0.5)).unwrap();
+/// fgb.add_variable("wet grass", IsingVariable::new()).unwrap();
+/// assert!(fgb.add_variable("rain", IsingVariable::new()).is_err());
+/// fgb.add_factor(IsingFactor::new(1., 0., 0.), &["rain", "wet grass"], &mut initializer).unwrap();
+/// assert!(fgb.add_factor(IsingFactor::new(1., 0., 0.), &["rain", "sun"], &mut initializer).is_err());
+/// let (mut fg, labels) = fgb.build();
+///
+/// let factor_scheduler = get_standard_factor_scheduler(0.);
+/// let variable_scheduler = get_standard_variable_scheduler(0.);
+/// fg.run_message_passing_parallel(100, 0, 1e-10, &factor_scheduler, &variable_scheduler).unwrap();
+/// let marginals = labels.labeled(fg.variable_marginals());
+/// assert!(marginals["wet grass"][0] < 0.5);
+/// assert_eq!(labels.index(&"wet grass"), Some(1));
+/// ```
+#[derive(Debug)]
+pub struct LabeledBuilder<K, F, V>
+where
+    F: Factor,
+    V: Variable<Message = F::Message>,
+{
+    builder: FactorGraphBuilder<F, V>,
+    labels: Labels<K>,
+}
+
+impl<K, F, V> Default for LabeledBuilder<K, F, V>
+where
+    K: Eq + Hash + Clone,
+    F: Factor,
+    V: Variable<Message = F::Message>,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, F, V> LabeledBuilder<K, F, V>
+where
+    K: Eq + Hash + Clone,
+    F: Factor,
+    V: Variable<Message = F::Message>,
+{
+    /// Creates an empty labeled factor graph builder
+    #[inline]
+    pub fn new() -> Self {
+        LabeledBuilder {
+            builder: FactorGraphBuilder::new(),
+            labels: Labels {
+                labels: Vec::new(),
+                indices: HashMap::new(),
+            },
+        }
+    }
+
+    /// Adds a labeled variable and returns its index
+    ///
+    /// # Arguments
+    ///
+    /// * `label` - A unique label of a variable
+    /// * `variable` - A variable to add
+    #[inline]
+    pub fn add_variable(&mut self, label: K, variable: V) -> FGBuilderResult<usize> {
+        if let Some(index) = self.labels.index(&label) {
+            return Err(FGBuilderError::DuplicateLabel(index));
+        }
+        let index = self.builder.add_variable(variable);
+        self.labels.indices.insert(label.clone(), index);
+        self.labels.labels.push(label);
+        Ok(index)
+    }
+
+    /// Adds a factor to a factor graph
+    ///
+    /// # Arguments
+    ///
+    /// * `factor` - A new factor
+    /// * `labels` - Labels of adjoint variables
+    /// * `message_initializer` - An object that initializes messages
+    #[inline]
+    pub fn add_factor(
+        &mut self,
+        factor: F,
+        labels: &[K],
+        message_initializer: &mut impl FnMut() -> F::Message,
+    ) -> FGBuilderResult<()> {
+        let var_indices = labels
+            .iter()
+            .enumerate()
+            .map(|(pos, label)| {
+                self.labels
+                    .index(label)
+                    .ok_or(FGBuilderError::UnknownLabel(pos))
+            })
+            .collect::<FGBuilderResult<Vec<_>>>()?;
+        self.builder
+            .add_factor(factor, &var_indices, message_initializer)
+    }
+
+    /// Returns a factor graph and labels of its variables
+    #[inline]
+    pub fn build(self) -> (FactorGraph<F, V>, Labels<K>) {
+        (self.builder.build(), self.labels)
+    }
+}
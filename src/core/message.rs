@@ -1,7 +1,8 @@
 use std::fmt::Debug;
 
-/// A trait providing message's methods
-pub trait Message: Debug + Clone + 'static {
+/// A trait providing message's methods. Messages are read by several
+/// threads during message passing, thus they must be `Send` and `Sync`
+pub trait Message: Debug + Clone + Send + Sync + 'static {
     /// Evaluates a distance between messages
     ///
     /// # Arguments
@@ -30,3 +31,60 @@ pub trait Message: Debug + Clone + 'static {
         *dst = self.clone();
     }
 }
+
+/// A trait providing methods necessary for adaptive per-edge damping of messages
+pub trait DampableMessage: Message {
+    /// Replaces a message by a convex combination `(1 - gamma) * self + gamma * prev`
+    ///
+    /// # Arguments
+    ///
+    /// * `prev` - A message from the previous iteration
+    /// * `gamma` - Damping coefficient from [0, 1)
+    fn damp(&mut self, prev: &Self, gamma: f64);
+
+    /// Checks whether the update `prev -> self` turns back the
+    /// previous update `prev_prev -> prev`
+    ///
+    /// # Arguments
+    ///
+    /// * `prev` - A message from the previous iteration
+    /// * `prev_prev` - A message from the iteration before the previous one
+    fn oscillates(&self, prev: &Self, prev_prev: &Self) -> bool;
+}
+
+/// A trait providing bounds checks of messages, it is used to audit numerical
+/// stability of message passing (see [`crate::core::MessageAudit`])
+pub trait BoundedMessage: Message {
+    /// Checks whether all parameters of a message are finite and do not exceed a bound
+    /// in absolute value, returns `true` if a message is out of bounds
+    ///
+    /// # Arguments
+    ///
+    /// * `bound` - A maximal absolute value of a parameter
+    /// * `clamp` - Whether to clamp out of bounds parameters to `[-bound, bound]`,
+    ///   `NaN` parameters are left intact
+    fn saturate(&mut self, bound: f64, clamp: bool) -> bool;
+}
+
+// Checks a single parameter of a message, see `BoundedMessage::saturate`
+#[inline(always)]
+pub(crate) fn saturate_value(value: &mut f64, bound: f64, clamp: bool) -> bool {
+    if value.abs() <= bound {
+        return false;
+    }
+    if clamp && !value.is_nan() {
+        *value = bound.copysign(*value);
+    }
+    true
+}
+
+// Returns the maximum of discrepancies propagating NaN, thus message passing
+// with diverged messages never converges
+#[inline(always)]
+pub(crate) fn nan_max(lhs: f64, rhs: f64) -> f64 {
+    if lhs.is_nan() || lhs > rhs {
+        lhs
+    } else {
+        rhs
+    }
+}
@@ -1,13 +1,80 @@
+mod allocations;
+mod annotated;
+mod audit;
+#[cfg(feature = "parallel")]
+pub(crate) mod balancing;
+mod components;
+mod conditional;
+mod consensus;
+mod coupling;
+mod damping;
+mod decimation;
+mod deprecated;
+mod diagnostics;
+mod dot;
+mod empirical;
 mod factor;
+mod factor_enum;
 mod factor_graph;
 mod factor_graph_builder;
 mod factor_node;
+mod fitting;
+mod labeled;
 mod message;
+mod observable;
+mod options;
+mod publisher;
+mod queries;
+mod rao_blackwell;
+mod repair;
+#[cfg(feature = "parallel")]
+mod replicas;
+mod report;
+mod sampling;
+mod sink;
+mod sparse;
+mod streaming;
+mod topology;
+mod trace;
+mod tree_decomposition;
 mod variable;
 mod variable_node;
 
+pub use allocations::AllocationCounter;
+pub use annotated::Annotated;
+pub use audit::MessageAudit;
+pub use components::ConnectedComponent;
+pub use conditional::ConditionalFactor;
+pub use consensus::ConsensusInfo;
+pub use damping::{AdaptiveDamping, DampingMode, StochasticUpdates};
+pub use decimation::MarginalGap;
+pub use diagnostics::FactorInconsistency;
+pub use dot::DotOptions;
+pub use empirical::MarginalError;
 pub use factor::Factor;
-pub use factor_graph::{FGError, FGResult, FactorGraph, MessagePassingInfo, SamplingInfo};
+pub use factor_graph::{
+    BatchSamplingInfo, FGError, FGResult, FactorGraph, MessagePassingInfo, MessagesSnapshot,
+    SamplingInfo, SamplingProgress, StreamingSamplingInfo,
+};
 pub use factor_graph_builder::{FGBuilderError, FGBuilderResult, FactorGraphBuilder};
-pub use message::Message;
-pub use variable::Variable;
+pub use fitting::FittingInfo;
+pub use labeled::{LabeledBuilder, Labels};
+pub(crate) use message::{nan_max, saturate_value};
+pub use message::{BoundedMessage, DampableMessage, Message};
+pub use observable::{FnObservable, Observable, ObservableSeries};
+pub use options::{
+    MessagePassingOptions, NoObservables, NoObserver, NoProgress, Observables, Observer,
+};
+pub use publisher::{MarginalsPublisher, PublishedMarginals};
+pub use queries::QueryInfo;
+pub use rao_blackwell::RaoBlackwellizedSamplingInfo;
+pub use repair::RepairInfo;
+pub use report::RunReport;
+pub use sink::{SampleSink, SampleWriter};
+pub use sparse::CooMatrix;
+pub use streaming::StreamedMarginal;
+pub(crate) use topology::UnionFind;
+pub use topology::{CycleCounts, ExactnessCertificate};
+pub use trace::MessageTrace;
+pub use tree_decomposition::{EliminationHeuristic, TreeDecomposition};
+pub use variable::{DecodableVariable, Variable};
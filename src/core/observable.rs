@@ -0,0 +1,108 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::core::{factor::Factor, factor_graph::FactorGraph, variable::Variable};
+
+// ------------------------------------------------------------------------------------------
+
+/// A scalar quantity computed from the current state of a factor graph, e.g.
+/// a magnetization, an energy or a free energy. Observables are evaluated
+/// during message passing configured by
+/// [`MessagePassingOptions::with_observables`](crate::core::MessagePassingOptions::with_observables)
+pub trait Observable<F, V>
+where
+    F: Factor,
+    V: Variable<Message = F::Message>,
+{
+    /// Returns the name of an observable, it identifies a time series of its values
+    fn name(&self) -> &str;
+
+    /// Evaluates an observable
+    ///
+    /// # Arguments
+    ///
+    /// * `factor_graph` - A factor graph in its current state
+    fn evaluate(&self, factor_graph: &FactorGraph<F, V>) -> f64;
+}
+
+/// An observable given by a name and a function of a factor graph
+#[derive(Debug, Clone)]
+pub struct FnObservable<Fun> {
+    name: String,
+    function: Fun,
+}
+
+impl<Fun> FnObservable<Fun> {
+    /// Creates an observable from a function
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - A name of an observable
+    /// * `function` - A function evaluating an observable from a factor graph
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::{FactorGraph, FnObservable, Observable};
+    /// use gmrs::ising::{new_ising_builder, IsingFactor, IsingVariable, SumProduct};
+    ///
+    /// type Graph = FactorGraph<IsingFactor<SumProduct>, IsingVariable<SumProduct>>;
+    ///
+    /// let first_spin = FnObservable::new("first_spin", |fg: &Graph| {
+    ///     let marginal = &fg.variable_marginals()[0];
+    ///     marginal[0] - marginal[1]
+    /// });
+    /// let fg = new_ising_builder::<SumProduct>(2, 0).build();
+    /// assert_eq!(first_spin.name(), "first_spin");
+    /// assert_eq!(first_spin.evaluate(&fg), 0.);
+    /// ```
+    #[inline]
+    pub fn new(name: &str, function: Fun) -> Self {
+        FnObservable {
+            name: name.to_string(),
+            function,
+        }
+    }
+}
+
+impl<F, V, Fun> Observable<F, V> for FnObservable<Fun>
+where
+    F: Factor,
+    V: Variable<Message = F::Message>,
+    Fun: Fn(&FactorGraph<F, V>) -> f64,
+{
+    #[inline(always)]
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[inline(always)]
+    fn evaluate(&self, factor_graph: &FactorGraph<F, V>) -> f64 {
+        (self.function)(factor_graph)
+    }
+}
+
+/// Values of an observable evaluated during message passing
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ObservableSeries {
+    /// The name of an observable
+    pub name: String,
+
+    /// Iteration numbers (start from 0) at which an observable has been evaluated
+    pub iterations: Vec<usize>,
+
+    /// Values of an observable at the corresponding iterations
+    pub values: Vec<f64>,
+}
+
+impl ObservableSeries {
+    #[inline]
+    pub(crate) fn new(name: &str) -> Self {
+        ObservableSeries {
+            name: name.to_string(),
+            iterations: Vec::new(),
+            values: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,1030 @@
+use std::{
+    ops::ControlFlow,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::core::{
+    damping::DampingMode,
+    factor::Factor,
+    factor_graph::{
+        FGError, FGResult, FactorGraph, MessagePassingInfo, SamplingInfo, SamplingProgress,
+    },
+    factor_node::FactorNode,
+    message::DampableMessage,
+    observable::{Observable, ObservableSeries},
+    publisher::PublishedMarginals,
+    variable::Variable,
+    variable_node::VariableNode,
+};
+
+// ------------------------------------------------------------------------------------------
+
+/// A function called after each iteration of message passing, it is implemented
+/// by closures taking a factor graph, an iteration number (starts from 0) and
+/// the iteration's discrepancy
+pub trait Observer<F, V>
+where
+    F: Factor,
+    V: Variable<Message = F::Message>,
+{
+    /// Observes a factor graph after an iteration, message passing is interrupted
+    /// if `ControlFlow::Break` is returned
+    ///
+    /// # Arguments
+    ///
+    /// * `factor_graph` - A factor graph after an iteration
+    /// * `iteration` - An iteration number (starts from 0)
+    /// * `discrepancy` - A discrepancy of the iteration
+    fn observe(
+        &mut self,
+        factor_graph: &FactorGraph<F, V>,
+        iteration: usize,
+        discrepancy: f64,
+    ) -> ControlFlow<()>;
+}
+
+impl<F, V, T> Observer<F, V> for T
+where
+    F: Factor,
+    V: Variable<Message = F::Message>,
+    T: FnMut(&FactorGraph<F, V>, usize, f64) -> ControlFlow<()>,
+{
+    #[inline(always)]
+    fn observe(
+        &mut self,
+        factor_graph: &FactorGraph<F, V>,
+        iteration: usize,
+        discrepancy: f64,
+    ) -> ControlFlow<()> {
+        self(factor_graph, iteration, discrepancy)
+    }
+}
+
+/// An observer that never interrupts message passing
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoObserver;
+
+impl<F, V> Observer<F, V> for NoObserver
+where
+    F: Factor,
+    V: Variable<Message = F::Message>,
+{
+    #[inline(always)]
+    fn observe(&mut self, _: &FactorGraph<F, V>, _: usize, _: f64) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+}
+
+/// A set of observables evaluated during message passing, it is implemented
+/// by slices of observables
+pub trait Observables<F, V>
+where
+    F: Factor,
+    V: Variable<Message = F::Message>,
+{
+    /// Returns observables in the order of their time series
+    fn observables(&self) -> &[&dyn Observable<F, V>];
+}
+
+impl<F, V> Observables<F, V> for &[&dyn Observable<F, V>]
+where
+    F: Factor,
+    V: Variable<Message = F::Message>,
+{
+    #[inline(always)]
+    fn observables(&self) -> &[&dyn Observable<F, V>] {
+        self
+    }
+}
+
+/// An empty set of observables
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoObservables;
+
+impl<F, V> Observables<F, V> for NoObservables
+where
+    F: Factor,
+    V: Variable<Message = F::Message>,
+{
+    #[inline(always)]
+    fn observables(&self) -> &[&dyn Observable<F, V>] {
+        &[]
+    }
+}
+
+/// A sampling progress callback that ignores progress
+pub type NoProgress = fn(&SamplingProgress);
+
+/// Options of message passing and sampling, they are accepted by
+/// [`FactorGraph::run_message_passing_with_options`] and [`FactorGraph::sample_with_options`].
+/// Options are created from schedulers by [`MessagePassingOptions::new`] and adjusted
+/// by `with_*` methods, all other parameters have defaults
+pub struct MessagePassingOptions<FS, VS, O = NoObserver, P = NoProgress, B = NoObservables> {
+    /// A maximal number of iterations, if a process does not converge before
+    /// reaching this number of iterations, it fails. 1000 by default
+    pub max_iterations_number: usize,
+
+    /// A minimal number of iterations that is performed disregards reaching
+    /// the convergence criterion. 0 by default
+    pub min_iterations_number: usize,
+
+    /// A threshold on the discrepancy between two subsequent messages configurations
+    /// specifying the convergence criterion. 1e-10 by default
+    pub threshold: f64,
+
+    /// A number of consecutive iterations the discrepancy must stay below the threshold,
+    /// zero is treated as one. 1 by default
+    pub convergence_window: usize,
+
+    /// A maximal time of a message passing run or of the whole sampling,
+    /// if it is exceeded `FGError::TimedOut` is returned. No budget by default
+    pub budget: Option<Duration>,
+
+    /// A way messages are damped, [`DampingMode::Scheduled`] by default
+    pub damping: DampingMode,
+
+    /// A tolerance of the stability criterion of variable marginals, a process is also
+    /// considered as successful once the maximal discrepancy between marginals of two
+    /// subsequent iterations is less than the tolerance. None by default
+    pub marginal_tolerance: Option<f64>,
+
+    /// A maximal probability of all values of a variable except the most probable one
+    /// for a variable to be considered as pinned during sampling, zero disables pinning.
+    /// 0 by default
+    pub pinning_tolerance: f64,
+
+    /// A number of iterations between two subsequent records of variable marginals
+    /// returned in [`MessagePassingInfo::marginal_history`], zero is treated as one.
+    /// Marginals of the final iteration are always recorded. None disables recording,
+    /// by default
+    pub record_marginals: Option<usize>,
+
+    /// Observables whose time series are returned in [`MessagePassingInfo::observables`],
+    /// no observables by default
+    pub observables: B,
+
+    /// A number of iterations between two subsequent evaluations of observables,
+    /// zero is treated as one. 1 by default
+    pub observables_stride: usize,
+
+    /// A scheduler of a factor's messages update rule hyper-parameters
+    pub factor_scheduler: FS,
+
+    /// A scheduler of a variable's messages update rule hyper-parameters
+    pub variable_scheduler: VS,
+
+    /// An observer called after each iteration, it interrupts message passing
+    /// by returning `ControlFlow::Break`
+    pub observer: O,
+
+    /// A function called with the progress of sampling after each decimation step
+    pub progress: P,
+}
+
+impl<FS, VS> MessagePassingOptions<FS, VS> {
+    /// Creates options with given schedulers and default parameters
+    ///
+    /// # Arguments
+    ///
+    /// * `factor_scheduler` - A scheduler of a factor's messages update rule hyper-parameters.
+    ///   It takes an iteration number (starts from 0) and return hyper-parameters.
+    /// * `variable_scheduler` - A scheduler of a variable's messages update rule hyper-parameters.
+    ///   It takes an iteration number (starts from 0) and return hyper-parameters.
+    #[inline]
+    pub fn new(factor_scheduler: FS, variable_scheduler: VS) -> Self {
+        MessagePassingOptions {
+            max_iterations_number: 1000,
+            min_iterations_number: 0,
+            threshold: 1e-10,
+            convergence_window: 1,
+            budget: None,
+            damping: DampingMode::Scheduled,
+            marginal_tolerance: None,
+            pinning_tolerance: 0f64,
+            record_marginals: None,
+            observables: NoObservables,
+            observables_stride: 1,
+            factor_scheduler,
+            variable_scheduler,
+            observer: NoObserver,
+            progress: |_| {},
+        }
+    }
+}
+
+impl<FS, VS, O, P, B> MessagePassingOptions<FS, VS, O, P, B> {
+    /// Sets the maximal number of iterations
+    #[inline]
+    pub fn with_max_iterations_number(mut self, max_iterations_number: usize) -> Self {
+        self.max_iterations_number = max_iterations_number;
+        self
+    }
+
+    /// Sets the minimal number of iterations
+    #[inline]
+    pub fn with_min_iterations_number(mut self, min_iterations_number: usize) -> Self {
+        self.min_iterations_number = min_iterations_number;
+        self
+    }
+
+    /// Sets the threshold of the convergence criterion
+    #[inline]
+    pub fn with_threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Sets the number of consecutive iterations the discrepancy must stay below
+    /// the threshold. It prevents premature stops of oscillatory dynamics
+    /// whose discrepancy momentarily dips below the threshold
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::MessagePassingOptions;
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let mut fgb = new_ising_builder::<SumProduct>(3, 3);
+    /// for (i, j) in [(0, 1), (1, 2), (2, 0)] {
+    ///     fgb.add_factor(IsingFactor::new(0.5, 0.1, 0.1), &[i, j], &mut initializer).unwrap();
+    /// }
+    /// let mut fg = fgb.build();
+    /// let mut options = MessagePassingOptions::new(
+    ///     get_standard_factor_scheduler(0.5),
+    ///     get_standard_variable_scheduler(0.5),
+    /// )
+    /// .with_convergence_window(5);
+    /// let info = fg.run_message_passing_with_options(&mut options).unwrap();
+    /// assert!(info.discrepancy_dynamics.iter().rev().take(5).all(|d| *d < 1e-10));
+    /// ```
+    #[inline]
+    pub fn with_convergence_window(mut self, convergence_window: usize) -> Self {
+        self.convergence_window = convergence_window;
+        self
+    }
+
+    /// Sets the time budget. Iteration limits are poor proxies for runtime on
+    /// heterogeneous graphs, a budget bounds the runtime directly
+    ///
+    /// # Notes
+    ///
+    /// The budget is checked after each iteration, thus a run takes at most one
+    /// iteration longer than the budget. If the budget is exceeded, `FGError::TimedOut`
+    /// is returned and messages of the last iteration are kept, e.g. for a warm restart.
+    /// During sampling the budget limits the time of the whole sampling, sampled variables
+    /// stay frozen in a factor graph
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use gmrs::core::{FGError, MessagePassingOptions};
+    /// use gmrs::ising::{new_ising_builder, IsingFactor, IsingMessage, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    ///
+    /// // A frustrated triangle without damping does not converge
+    /// let mut fgb = new_ising_builder::<SumProduct>(3, 3);
+    /// for (i, j) in [(0, 1), (1, 2), (2, 0)] {
+    ///     fgb.add_factor(IsingFactor::new(-3., 0., 0.), &[i, j], &mut || IsingMessage(0.5)).unwrap();
+    /// }
+    /// let mut fg = fgb.build();
+    /// let mut options = MessagePassingOptions::new(
+    ///     get_standard_factor_scheduler(0.),
+    ///     get_standard_variable_scheduler(0.),
+    /// )
+    /// .with_budget(Duration::ZERO);
+    /// let result = fg.run_message_passing_with_options(&mut options);
+    /// assert!(matches!(result, Err(FGError::TimedOut { iterations_number: 1, .. })));
+    /// ```
+    #[inline]
+    pub fn with_budget(mut self, budget: Duration) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Sets the way messages are damped
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::{AdaptiveDamping, DampingMode, MessagePassingOptions, StochasticUpdates};
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// // A fully connected model
+    /// let spins_number = 20;
+    /// let mut fgb = new_ising_builder::<SumProduct>(spins_number, spins_number * spins_number);
+    /// for i in 0..spins_number {
+    ///     for j in (i + 1)..spins_number {
+    ///         fgb.add_factor(IsingFactor::new(0.02, 0.01, 0.), &[i, j], &mut initializer).unwrap();
+    ///     }
+    /// }
+    /// let mut fg = fgb.build();
+    /// let mut options = MessagePassingOptions::new(
+    ///     get_standard_factor_scheduler(0.),
+    ///     get_standard_variable_scheduler(0.),
+    /// )
+    /// .with_max_iterations_number(10000)
+    /// .with_threshold(1e-8)
+    /// .with_damping(DampingMode::Stochastic(StochasticUpdates {
+    ///     batch_fraction: 0.25,
+    ///     damping: 0.5,
+    ///     seed: 42,
+    /// }));
+    /// let info = fg.run_message_passing_with_options(&mut options).unwrap();
+    /// assert!(info.discrepancy_dynamics.iter().rev().take(4).all(|d| *d < 1e-8));
+    ///
+    /// // Damping adapted individually for each edge
+    /// options.damping = DampingMode::Adaptive(AdaptiveDamping::default());
+    /// fg.run_message_passing_with_options(&mut options).unwrap();
+    /// ```
+    #[inline]
+    pub fn with_damping(mut self, damping: DampingMode) -> Self {
+        self.damping = damping;
+        self
+    }
+
+    /// Sets the tolerance of the stability criterion of variable marginals.
+    /// On degenerate instances marginals often stabilize long before individual
+    /// messages do
+    ///
+    /// # Notes
+    ///
+    /// Marginals are compared by [`Variable::marginal_discrepancy`], variables whose marginals
+    /// can not be compared are never stable. A marginal of the previous iteration is kept
+    /// per variable, thus each iteration computes all marginals
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::MessagePassingOptions;
+    /// use gmrs::ising::{new_ising_builder, IsingFactor, IsingMessage, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    ///
+    /// // A slowly converging ferromagnetic ring
+    /// let mut fgb = new_ising_builder::<SumProduct>(10, 10);
+    /// for i in 0..10 {
+    ///     fgb.add_factor(IsingFactor::new(1., 0., 0.), &[i, (i + 1) % 10], &mut || IsingMessage(0.1))
+    ///         .unwrap();
+    /// }
+    /// let mut fg = fgb.build();
+    /// let mut options = MessagePassingOptions::new(
+    ///     get_standard_factor_scheduler(0.),
+    ///     get_standard_variable_scheduler(0.),
+    /// )
+    /// .with_max_iterations_number(10000)
+    /// .with_threshold(1e-12);
+    /// let messages_info = fg.clone().run_message_passing_with_options(&mut options).unwrap();
+    /// let mut options = options.with_marginal_tolerance(1e-6);
+    /// let info = fg.run_message_passing_with_options(&mut options).unwrap();
+    /// assert!(info.iterations_number < messages_info.iterations_number);
+    /// ```
+    #[inline]
+    pub fn with_marginal_tolerance(mut self, marginal_tolerance: f64) -> Self {
+        self.marginal_tolerance = Some(marginal_tolerance);
+        self
+    }
+
+    /// Sets the pinning tolerance of sampling. After each message passing sampling
+    /// detects connected components of not yet sampled variables whose marginals are all
+    /// pinned, i.e. concentrated on a single value up to the tolerance, and fixes all
+    /// their variables at once without further message passing runs. For models with
+    /// hard or strong constraints it collapses the tail of the sampling loop to a handful
+    /// of message passing runs
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::MessagePassingOptions;
+    /// use gmrs::ising::{new_ising_chain_builder, IsingMessage, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use rand::thread_rng;
+    ///
+    /// // A chain of spins locked together by strong couplings
+    /// let mut fg = new_ising_chain_builder::<SumProduct>(10, 30., 0., [0., 0.], &mut || IsingMessage(0.))
+    ///     .unwrap()
+    ///     .build();
+    /// let mut options = MessagePassingOptions::new(
+    ///     get_standard_factor_scheduler(0.),
+    ///     get_standard_variable_scheduler(0.),
+    /// )
+    /// .with_max_iterations_number(100)
+    /// .with_pinning_tolerance(1e-10);
+    /// fg.run_message_passing_with_options(&mut options).unwrap();
+    ///
+    /// // After sampling the first spin all other spins are pinned
+    /// let info = fg.sample_with_options(&mut thread_rng(), &mut options).unwrap();
+    /// assert_eq!(info.pinned_variables_number, 9);
+    /// assert!(info.samples.iter().all(|s| *s == info.samples[0]));
+    /// ```
+    #[inline]
+    pub fn with_pinning_tolerance(mut self, pinning_tolerance: f64) -> Self {
+        self.pinning_tolerance = pinning_tolerance;
+        self
+    }
+
+    /// Enables recording of variable marginals every `recording_period` iterations
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::MessagePassingOptions;
+    /// use gmrs::ising::{new_ising_chain_builder, IsingMessage, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    ///
+    /// let mut fg = new_ising_chain_builder::<SumProduct>(10, 0.5, 0.1, [0., 0.], &mut || IsingMessage(0.))
+    ///     .unwrap()
+    ///     .build();
+    /// let mut options = MessagePassingOptions::new(
+    ///     get_standard_factor_scheduler(0.),
+    ///     get_standard_variable_scheduler(0.),
+    /// )
+    /// .with_min_iterations_number(20)
+    /// .with_marginals_recording(5);
+    /// let info = fg.run_message_passing_with_options(&mut options).unwrap();
+    /// let history = info.marginal_history.unwrap();
+    /// assert_eq!(history[0].iteration, 4);
+    /// assert_eq!(history[1].iteration, 9);
+    /// let last = history.last().unwrap();
+    /// assert_eq!(last.iteration, info.iterations_number);
+    /// assert_eq!(last.marginals[0], fg.variable_marginals()[0].to_vec());
+    /// ```
+    #[inline]
+    pub fn with_marginals_recording(mut self, recording_period: usize) -> Self {
+        self.record_marginals = Some(recording_period);
+        self
+    }
+
+    /// Sets observables evaluated every `stride` iterations, observables are always
+    /// evaluated at the final iteration
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::{FactorGraph, FnObservable, MessagePassingOptions, Observable};
+    /// use gmrs::ising::{
+    ///     new_ising_builder, random_message_initializer, IsingFactor, IsingVariable, Magnetization, SumProduct,
+    /// };
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use rand::thread_rng;
+    ///
+    /// type Graph = FactorGraph<IsingFactor<SumProduct>, IsingVariable<SumProduct>>;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let mut fgb = new_ising_builder::<SumProduct>(3, 3);
+    /// for (i, j) in [(0, 1), (1, 2), (2, 0)] {
+    ///     fgb.add_factor(IsingFactor::new(0.5, 0.1, 0.), &[i, j], &mut initializer).unwrap();
+    /// }
+    /// let mut fg = fgb.build();
+    /// let degrees_sum = FnObservable::new("degrees_sum", |fg: &Graph| {
+    ///     fg.get_variable_degrees().iter().sum::<usize>() as f64
+    /// });
+    /// let observables: [&dyn Observable<_, _>; 2] = [&Magnetization, &degrees_sum];
+    /// let mut options = MessagePassingOptions::new(
+    ///     get_standard_factor_scheduler(0.),
+    ///     get_standard_variable_scheduler(0.),
+    /// )
+    /// .with_observables(&observables, 2);
+    /// let info = fg.run_message_passing_with_options(&mut options).unwrap();
+    /// assert_eq!(info.observables[0].name, "magnetization");
+    /// assert_eq!(info.observables[0].iterations[..2], [1, 3]);
+    /// assert!(info.observables[1].values.iter().all(|value| *value == 6.));
+    /// ```
+    #[inline]
+    pub fn with_observables<'a, F, V>(
+        self,
+        observables: &'a [&'a dyn Observable<F, V>],
+        stride: usize,
+    ) -> MessagePassingOptions<FS, VS, O, P, &'a [&'a dyn Observable<F, V>]>
+    where
+        F: Factor,
+        V: Variable<Message = F::Message>,
+    {
+        let mut options =
+            self.map_callbacks(|observer, progress, _| (observer, progress, observables));
+        options.observables_stride = stride;
+        options
+    }
+
+    /// Sets the observer called after each iteration of message passing. The observer
+    /// gets read-only access to a factor graph, thus it can inspect messages and marginals
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::ops::ControlFlow;
+    /// use gmrs::core::{FGError, FactorGraph, MessagePassingOptions};
+    /// use gmrs::ising::{new_ising_chain_builder, IsingFactor, IsingMessage, IsingVariable, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    ///
+    /// type Graph = FactorGraph<IsingFactor<SumProduct>, IsingVariable<SumProduct>>;
+    ///
+    /// let mut fg = new_ising_chain_builder::<SumProduct>(2, 0.5, 0.5, [0., 0.], &mut || IsingMessage(0.1))
+    ///     .unwrap()
+    ///     .build();
+    ///
+    /// // Monitoring magnetization and stopping after 3 iterations
+    /// let mut magnetizations = Vec::new();
+    /// let mut options = MessagePassingOptions::new(
+    ///     get_standard_factor_scheduler(0.5),
+    ///     get_standard_variable_scheduler(0.5),
+    /// )
+    /// .with_observer(|fg: &Graph, iteration, _| {
+    ///     magnetizations.push(fg.variable_marginals()[0][0]);
+    ///     if iteration == 2 {
+    ///         ControlFlow::Break(())
+    ///     } else {
+    ///         ControlFlow::Continue(())
+    ///     }
+    /// });
+    /// let result = fg.run_message_passing_with_options(&mut options);
+    /// assert!(matches!(result, Err(FGError::Interrupted { iterations_number: 3, .. })));
+    /// assert_eq!(magnetizations.len(), 3);
+    /// ```
+    #[inline]
+    pub fn with_observer<F, V, O2>(self, observer: O2) -> MessagePassingOptions<FS, VS, O2, P, B>
+    where
+        F: Factor,
+        V: Variable<Message = F::Message>,
+        O2: FnMut(&FactorGraph<F, V>, usize, f64) -> ControlFlow<()>,
+    {
+        self.map_callbacks(|_, progress, observables| (observer, progress, observables))
+    }
+
+    /// Sets the function called after each decimation step of sampling, e.g. to drive
+    /// a progress bar during hours long sampling of a large factor graph
+    #[inline]
+    pub fn with_progress<P2>(self, progress: P2) -> MessagePassingOptions<FS, VS, O, P2, B>
+    where
+        P2: FnMut(&SamplingProgress),
+    {
+        self.map_callbacks(|observer, _, observables| (observer, progress, observables))
+    }
+
+    // Replaces the observer, the progress callback and observables keeping other options
+    fn map_callbacks<O2, P2, B2>(
+        self,
+        map: impl FnOnce(O, P, B) -> (O2, P2, B2),
+    ) -> MessagePassingOptions<FS, VS, O2, P2, B2> {
+        let (observer, progress, observables) = map(self.observer, self.progress, self.observables);
+        MessagePassingOptions {
+            max_iterations_number: self.max_iterations_number,
+            min_iterations_number: self.min_iterations_number,
+            threshold: self.threshold,
+            convergence_window: self.convergence_window,
+            budget: self.budget,
+            damping: self.damping,
+            marginal_tolerance: self.marginal_tolerance,
+            pinning_tolerance: self.pinning_tolerance,
+            record_marginals: self.record_marginals,
+            observables,
+            observables_stride: self.observables_stride,
+            factor_scheduler: self.factor_scheduler,
+            variable_scheduler: self.variable_scheduler,
+            observer,
+            progress,
+        }
+    }
+}
+
+impl<F, V> FactorGraph<F, V>
+where
+    F: Factor,
+    V: Variable<Message = F::Message>,
+{
+    /// Runs a message passing algorithm in parallel. Typically, it is
+    /// a fixed point iteration method targeted on achieving an equilibrium
+    /// configuration of messages. This method mutates a factor graph
+    /// since it updates messages that are stored internally.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_iterations_number` - A maximal number of iterations, if a process
+    ///   does not converge before reaching this number of iterations, it fails
+    /// * `min_iterations_number` - A minimal number of iterations that is performed
+    ///   disregards reaching the convergence criterion. It could be useful
+    ///   for example when one performs belief propagation with temperature annealing
+    /// * `threshold` - A threshold specifying the convergence criterion. A process
+    ///   is considered as successful if the discrepancy between two subsequent
+    ///   messages configurations is less than the threshold
+    /// * `factor_scheduler` - A scheduler of a factor's messages update rule hyper-parameters.
+    ///   It takes an iteration number (starts from 0) and return hyper-parameters.
+    /// * `variable_scheduler` - A scheduler of a variable's messages update rule hyper-parameters.
+    ///   It takes an iteration number (starts from 0) and return hyper-parameters.
+    ///
+    /// # Notes
+    ///
+    /// Without the `parallel` feature (enabled by default) all nodes
+    /// are updated by a serial loop in the current thread, the same holds for
+    /// factor graphs smaller than [`FactorGraph::parallel_threshold`].
+    /// Other parameters of message passing are set by [`MessagePassingOptions`]
+    /// accepted by [`FactorGraph::run_message_passing_with_options`]
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::FactorGraphBuilder;
+    /// use gmrs::ising::{IsingFactor, IsingVariable, SumProduct, random_message_initializer};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use rand::thread_rng;
+    ///
+    /// // Aliases to shorten types
+    /// type Factor = IsingFactor<SumProduct>;
+    /// type Variable = IsingVariable<SumProduct>;
+    ///
+    /// // Messages initializer
+    /// let rng = thread_rng();
+    /// let mut initializer = random_message_initializer(rng, -0.5, 0.5);
+    ///
+    /// // Message passing schedulers
+    /// let factor_scheduler = get_standard_factor_scheduler(0.5);
+    /// let variable_scheduler = get_standard_variable_scheduler(0.5);
+    ///
+    /// // Message passing
+    /// let mut fgb = FactorGraphBuilder::<Factor, Variable>::new_with_variables(vec![IsingVariable::new(); 4], 3);
+    /// for i in 0..3 {
+    ///     fgb.add_factor(
+    ///         IsingFactor::new(0.5f64, 0.5f64, -0.5f64),
+    ///         &[i, 3],
+    ///         &mut initializer,
+    ///     );
+    /// }
+    /// let mut fg = fgb.build();
+    /// let _ = fg.run_message_passing_parallel(
+    ///     100,
+    ///     0,
+    ///     1e-10,
+    ///     &factor_scheduler,
+    ///     &variable_scheduler,
+    /// ).unwrap();
+    /// ```
+    #[inline]
+    pub fn run_message_passing_parallel(
+        &mut self,
+        max_iterations_number: usize,
+        min_iterations_number: usize,
+        threshold: f64,
+        factor_scheduler: &impl Fn(usize) -> F::Parameters,
+        variable_scheduler: &impl Fn(usize) -> V::Parameters,
+    ) -> FGResult<MessagePassingInfo> {
+        self.run_scheduled_since(
+            Instant::now(),
+            &mut MessagePassingOptions::new(factor_scheduler, variable_scheduler)
+                .with_max_iterations_number(max_iterations_number)
+                .with_min_iterations_number(min_iterations_number)
+                .with_threshold(threshold),
+        )
+    }
+
+    /// Runs a message passing algorithm in parallel as
+    /// [`FactorGraph::run_message_passing_parallel`] configured by options
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - Options of message passing, sampling specific options are ignored
+    ///
+    /// # Notes
+    ///
+    /// If the observer interrupts message passing, `FGError::Interrupted` is returned,
+    /// if the budget is exceeded, `FGError::TimedOut` is returned
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::ops::ControlFlow;
+    /// use gmrs::core::MessagePassingOptions;
+    /// use gmrs::ising::{new_ising_chain_builder, IsingMessage, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    ///
+    /// let mut fg = new_ising_chain_builder::<SumProduct>(10, 0.5, 0.1, [0., 0.], &mut || IsingMessage(0.))
+    ///     .unwrap()
+    ///     .build();
+    /// let mut discrepancies = Vec::new();
+    /// let mut options = MessagePassingOptions::new(
+    ///     get_standard_factor_scheduler(0.),
+    ///     get_standard_variable_scheduler(0.),
+    /// )
+    /// .with_threshold(1e-12)
+    /// .with_convergence_window(3)
+    /// .with_observer(|_, _, discrepancy| {
+    ///     discrepancies.push(discrepancy);
+    ///     ControlFlow::Continue(())
+    /// });
+    /// let info = fg.run_message_passing_with_options(&mut options).unwrap();
+    /// assert_eq!(discrepancies, info.discrepancy_dynamics);
+    /// ```
+    pub fn run_message_passing_with_options<FS, VS, O, P, B>(
+        &mut self,
+        options: &mut MessagePassingOptions<FS, VS, O, P, B>,
+    ) -> FGResult<MessagePassingInfo>
+    where
+        F::Message: DampableMessage,
+        FS: Fn(usize) -> F::Parameters,
+        VS: Fn(usize) -> V::Parameters,
+        O: Observer<F, V>,
+        B: Observables<F, V>,
+    {
+        self.run_message_passing_since(Instant::now(), options)
+    }
+
+    /// Samples from a factor graph as [`FactorGraph::sample`] configured by options
+    ///
+    /// # Arguments
+    ///
+    /// * `rng` - A random numbers generator
+    /// * `options` - Options of message passing runs and sampling
+    ///
+    /// # Notes
+    ///
+    /// The observer is called after each iteration of each message passing run,
+    /// the budget limits the time of the whole sampling. If the budget is exceeded,
+    /// `FGError::TimedOut` reports the number of variables sampled before the interrupted
+    /// message passing run
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::MessagePassingOptions;
+    /// use gmrs::ising::{new_ising_chain_builder, IsingMessage, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use rand::thread_rng;
+    ///
+    /// let mut fg = new_ising_chain_builder::<SumProduct>(10, 0.5, 0., [0., 0.], &mut || IsingMessage(0.))
+    ///     .unwrap()
+    ///     .build();
+    /// let mut reports = Vec::new();
+    /// let mut options = MessagePassingOptions::new(
+    ///     get_standard_factor_scheduler(0.),
+    ///     get_standard_variable_scheduler(0.),
+    /// )
+    /// .with_max_iterations_number(100)
+    /// .with_progress(|progress| reports.push(*progress));
+    /// let info = fg.sample_with_options(&mut thread_rng(), &mut options).unwrap();
+    /// assert_eq!(info.samples.len(), 10);
+    /// assert_eq!(reports.len(), 10);
+    /// assert_eq!(reports[9].sampled_variables_number, reports[9].variables_number);
+    /// assert_eq!(reports[9].total_iterations_number, info.total_iterations_number);
+    /// ```
+    pub fn sample_with_options<FS, VS, O, P, B>(
+        &mut self,
+        rng: &mut impl Rng,
+        options: &mut MessagePassingOptions<FS, VS, O, P, B>,
+    ) -> FGResult<SamplingInfo<V::Sample>>
+    where
+        F::Message: DampableMessage,
+        FS: Fn(usize) -> F::Parameters,
+        VS: Fn(usize) -> V::Parameters,
+        O: Observer<F, V>,
+        P: FnMut(&SamplingProgress),
+        B: Observables<F, V>,
+    {
+        self.sample_clamped(&[], rng, options, Self::run_message_passing_since)
+    }
+
+    // Runs message passing configured by options, the budget is counted from a start time
+    pub(super) fn run_message_passing_since<FS, VS, O, P, B>(
+        &mut self,
+        start: Instant,
+        options: &mut MessagePassingOptions<FS, VS, O, P, B>,
+    ) -> FGResult<MessagePassingInfo>
+    where
+        F::Message: DampableMessage,
+        FS: Fn(usize) -> F::Parameters,
+        VS: Fn(usize) -> V::Parameters,
+        O: Observer<F, V>,
+        B: Observables<F, V>,
+    {
+        match options.damping {
+            DampingMode::Scheduled => self.run_scheduled_since(start, options),
+            DampingMode::Adaptive(damping) => {
+                let convergence_window = options.convergence_window;
+                self.run_configured(
+                    start,
+                    options,
+                    convergence_window,
+                    &|factor: &mut FactorNode<F, V>, parameters: &F::Parameters| {
+                        factor.eval_damped_messages(parameters, &damping);
+                    },
+                    &|variable: &mut VariableNode<V, F>, parameters: &V::Parameters| {
+                        variable.eval_damped_messages(parameters, &damping);
+                    },
+                    || {},
+                )
+            }
+            DampingMode::Stochastic(updates) => {
+                let min_fraction = 1f64 / self.factors.len().max(1) as f64;
+                let batch_fraction = if updates.batch_fraction > 0f64 {
+                    updates.batch_fraction.min(1f64)
+                } else {
+                    0f64
+                }
+                .max(min_fraction);
+                let convergence_window = options
+                    .convergence_window
+                    .max((1f64 / batch_fraction).ceil() as usize);
+                let gamma = (1f64 - (1f64 - updates.damping) / batch_fraction).max(0f64);
+                for factor in &mut self.factors {
+                    factor.init_damping();
+                }
+                // a seed of the current iteration, it is updated after each iteration
+                let mut rng = StdRng::seed_from_u64(updates.seed);
+                let seed = AtomicU64::new(rng.gen());
+                let factor_update = |factor: &mut FactorNode<F, V>, parameters: &F::Parameters| {
+                    if factor.is_selected(seed.load(Ordering::Relaxed), batch_fraction) {
+                        factor.eval_fixed_damped_messages(parameters, gamma);
+                    }
+                };
+                self.run_configured(
+                    start,
+                    options,
+                    convergence_window,
+                    &factor_update,
+                    &VariableNode::eval_messages,
+                    || seed.store(rng.gen(), Ordering::Relaxed),
+                )
+            }
+        }
+    }
+
+    // Runs message passing configured by options ignoring the damping mode,
+    // messages are damped only by update rules
+    pub(super) fn run_scheduled_since<FS, VS, O, P, B>(
+        &mut self,
+        start: Instant,
+        options: &mut MessagePassingOptions<FS, VS, O, P, B>,
+    ) -> FGResult<MessagePassingInfo>
+    where
+        FS: Fn(usize) -> F::Parameters,
+        VS: Fn(usize) -> V::Parameters,
+        O: Observer<F, V>,
+        B: Observables<F, V>,
+    {
+        let convergence_window = options.convergence_window;
+        self.run_configured(
+            start,
+            options,
+            convergence_window,
+            &FactorNode::eval_messages,
+            &VariableNode::eval_messages,
+            || {},
+        )
+    }
+
+    // Runs message passing with given update rules, the budget, the observer,
+    // recording, observables and the stability criterion are taken from options
+    fn run_configured<FS, VS, O, P, B>(
+        &mut self,
+        start: Instant,
+        options: &mut MessagePassingOptions<FS, VS, O, P, B>,
+        convergence_window: usize,
+        factor_update: &(impl Fn(&mut FactorNode<F, V>, &F::Parameters) + Sync),
+        variable_update: &(impl Fn(&mut VariableNode<V, F>, &V::Parameters) + Sync),
+        mut on_iteration: impl FnMut(),
+    ) -> FGResult<MessagePassingInfo>
+    where
+        FS: Fn(usize) -> F::Parameters,
+        VS: Fn(usize) -> V::Parameters,
+        O: Observer<F, V>,
+        B: Observables<F, V>,
+    {
+        let (threshold, min_iterations_number) = (options.threshold, options.min_iterations_number);
+        let budget = options.budget;
+        let marginal_tolerance = options.marginal_tolerance;
+        let recording_period = options.record_marginals.map(|period| period.max(1));
+        let stride = options.observables_stride.max(1);
+        let observables = options.observables.observables();
+        let observer = &mut options.observer;
+        let mut series: Vec<_> = observables
+            .iter()
+            .map(|observable| ObservableSeries::new(observable.name()))
+            .collect();
+        let measure = |fg: &Self, iteration: usize, series: &mut [ObservableSeries]| {
+            for (observable, series) in observables.iter().zip(series) {
+                series.iterations.push(iteration);
+                series.values.push(observable.evaluate(fg));
+            }
+        };
+        if marginal_tolerance.is_some() {
+            for variable in &mut self.variables {
+                variable.previous_marginal = None;
+            }
+        }
+        // bits of the maximal marginal change of the current iteration,
+        // the order of bits of non-negative floats matches the order of floats
+        let max_change = AtomicU64::new(0);
+        let variable_update = |variable: &mut VariableNode<V, F>, parameters: &V::Parameters| {
+            variable_update(variable, parameters);
+            if marginal_tolerance.is_some() {
+                max_change.fetch_max(variable.marginal_change().to_bits(), Ordering::Relaxed);
+            }
+        };
+        let mut is_timed_out = false;
+        let mut is_stable = false;
+        let mut marginal_history = Vec::new();
+        let result = self.run_message_passing_with_hook(
+            options.max_iterations_number,
+            min_iterations_number,
+            threshold,
+            convergence_window,
+            &options.factor_scheduler,
+            &options.variable_scheduler,
+            factor_update,
+            &variable_update,
+            |fg, iteration, discrepancy| {
+                on_iteration();
+                if recording_period.is_some_and(|period| (iteration + 1) % period == 0) {
+                    marginal_history.push(fg.recorded_marginals(iteration, discrepancy));
+                }
+                if !series.is_empty() && (iteration + 1) % stride == 0 {
+                    measure(fg, iteration, &mut series);
+                }
+                observer.observe(fg, iteration, discrepancy)?;
+                let is_min_reached = iteration + 1 >= min_iterations_number;
+                if let Some(tolerance) = marginal_tolerance {
+                    let change = f64::from_bits(max_change.swap(0, Ordering::Relaxed));
+                    if change < tolerance && is_min_reached {
+                        is_stable = true;
+                        return ControlFlow::Break(());
+                    }
+                }
+                let is_converged = discrepancy < threshold && is_min_reached;
+                match budget {
+                    Some(budget) if !is_converged && start.elapsed() > budget => {
+                        is_timed_out = true;
+                        ControlFlow::Break(())
+                    }
+                    _ => ControlFlow::Continue(()),
+                }
+            },
+        );
+        let mut info = match result {
+            Ok(info) => info,
+            Err(FGError::Interrupted {
+                iterations_number,
+                discrepancy_dynamics,
+                last_discrepancy,
+            }) if is_stable => MessagePassingInfo {
+                iterations_number: iterations_number - 1,
+                discrepancy_dynamics,
+                last_discrepancy,
+                certificate: self.exactness_certificate(),
+                marginal_history: None,
+                observables: Vec::new(),
+            },
+            Err(FGError::Interrupted {
+                iterations_number,
+                last_discrepancy,
+                discrepancy_dynamics,
+            }) if is_timed_out => {
+                return Err(FGError::TimedOut {
+                    elapsed: start.elapsed(),
+                    sampled_variables_number: 0,
+                    iterations_number,
+                    last_discrepancy,
+                    discrepancy_dynamics,
+                })
+            }
+            Err(err) => return Err(err),
+        };
+        if let Some(period) = recording_period {
+            if (info.iterations_number + 1) % period != 0 {
+                marginal_history
+                    .push(self.recorded_marginals(info.iterations_number, info.last_discrepancy));
+            }
+            info.marginal_history = Some(marginal_history);
+        }
+        if !series.is_empty() && (info.iterations_number + 1) % stride != 0 {
+            measure(self, info.iterations_number, &mut series);
+        }
+        info.observables = series;
+        Ok(info)
+    }
+
+    // Returns values of marginals of all variables, variables that do not provide
+    // values of marginals get empty vectors
+    fn recorded_marginals(
+        &self,
+        iteration: usize,
+        discrepancy: f64,
+    ) -> PublishedMarginals<Vec<f64>> {
+        let marginals = self
+            .variable_marginals()
+            .iter()
+            .zip(&self.variables)
+            .map(|(marginal, node)| node.variable.marginal_values(marginal).unwrap_or_default())
+            .collect();
+        PublishedMarginals {
+            iteration,
+            discrepancy,
+            marginals,
+        }
+    }
+}
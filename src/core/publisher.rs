@@ -0,0 +1,71 @@
+use std::sync::{Arc, RwLock};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// ------------------------------------------------------------------------------------------
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// Variable marginals published or recorded by a running message passing
+pub struct PublishedMarginals<M> {
+    /// Iteration number (starts from 0) at which marginals were computed
+    pub iteration: usize,
+
+    /// Discrepancy of the iteration at which marginals were computed
+    pub discrepancy: f64,
+
+    /// Marginals of all variables
+    pub marginals: Vec<M>,
+}
+
+#[derive(Debug)]
+/// A shared read buffer that a running message passing publishes variable marginals to.
+/// It can be cloned and moved to other threads that want to observe evolving beliefs
+/// without stopping the solver. Marginals are computed outside of the lock,
+/// so readers are only blocked for the time of a pointer swap.
+pub struct MarginalsPublisher<M> {
+    latest: Arc<RwLock<Option<Arc<PublishedMarginals<M>>>>>,
+}
+
+impl<M> Clone for MarginalsPublisher<M> {
+    fn clone(&self) -> Self {
+        MarginalsPublisher {
+            latest: self.latest.clone(),
+        }
+    }
+}
+
+impl<M> Default for MarginalsPublisher<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M> MarginalsPublisher<M> {
+    /// Creates a publisher with no published marginals
+    #[inline]
+    pub fn new() -> Self {
+        MarginalsPublisher {
+            latest: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// Returns the latest published marginals or None if nothing has been published yet
+    #[inline]
+    pub fn latest(&self) -> Option<Arc<PublishedMarginals<M>>> {
+        self.latest
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    #[inline]
+    pub(crate) fn publish(&self, published: PublishedMarginals<M>) {
+        let published = Arc::new(published);
+        *self
+            .latest
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(published);
+    }
+}
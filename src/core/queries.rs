@@ -0,0 +1,300 @@
+use std::collections::VecDeque;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::core::{
+    factor::Factor,
+    factor_graph::{FGError, FGResult, FactorGraph},
+    variable::Variable,
+};
+
+// ------------------------------------------------------------------------------------------
+
+/// Result of a marginal query with evidence
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct QueryInfo<M> {
+    /// Marginals of all variables given evidence
+    pub marginals: Vec<M>,
+
+    /// Number of node updates performed by the localized re-propagation
+    pub updates_number: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Node {
+    Factor(usize),
+    Variable(usize),
+}
+
+impl<F, V> FactorGraph<F, V>
+where
+    F: Factor,
+    V: Variable<Message = F::Message>,
+{
+    /// Runs many marginal queries that differ only in evidence on a few variables
+    /// while sharing converged messages of a factor graph. For each query, evidence is
+    /// applied to a copy of the factor graph and changes of messages are propagated
+    /// from observed variables, updating only nodes whose incoming messages have
+    /// changed by more than a threshold, instead of full message passing runs.
+    /// A factor graph itself is not modified
+    ///
+    /// # Arguments
+    ///
+    /// * `queries` - Sets of pairs (a variable index, an observed value)
+    /// * `threshold` - Changes of messages below this threshold are not propagated
+    /// * `max_updates_number` - A maximal number of node updates per query
+    /// * `factor_parameters` - Hyper-parameters of factors' message update rules
+    /// * `variable_parameters` - Hyper-parameters of variables' message update rules
+    ///
+    /// # Notes
+    ///
+    /// Messages of a factor graph are expected to be converged, otherwise
+    /// answers depend on the state of unconverged messages. If re-propagation
+    /// of a query does not settle within `max_updates_number` updates, the method
+    /// fails with `FGError::MessagePassingError` whose iterations number is the
+    /// number of updates and whose discrepancy is the maximal change of
+    /// messages at the last update
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, IsingFactorHyperParameters, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use rand::thread_rng;
+    ///
+    /// // A chain of 1000 spins
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let mut fgb = new_ising_builder::<SumProduct>(1000, 999);
+    /// for i in 0..999 {
+    ///     fgb.add_factor(IsingFactor::new(0.3, 0., 0.), &[i, i + 1], &mut initializer).unwrap();
+    /// }
+    /// let mut fg = fgb.build();
+    /// let factor_scheduler = get_standard_factor_scheduler(0.);
+    /// let variable_scheduler = get_standard_variable_scheduler(0.);
+    /// fg.run_message_passing_parallel(10000, 0, 1e-10, &factor_scheduler, &variable_scheduler).unwrap();
+    ///
+    /// // Observing the first spin in each query
+    /// let queries = vec![vec![(0, 1)], vec![(0, -1)]];
+    /// let infos = fg.query_marginals(queries, 1e-10, 100000, &factor_scheduler(0), &variable_scheduler(0)).unwrap();
+    /// let magnetization = infos[0].marginals[1][0] - infos[0].marginals[1][1];
+    /// assert!((magnetization - f64::tanh(0.3)).abs() < 1e-8);
+    /// assert!((infos[1].marginals[1][0] - infos[0].marginals[1][1]).abs() < 1e-8);
+    /// // only a neighbourhood of the observed spin has been updated
+    /// assert!(infos[0].updates_number < 100);
+    /// ```
+    pub fn query_marginals<Q>(
+        &self,
+        queries: impl IntoIterator<Item = Q>,
+        threshold: f64,
+        max_updates_number: usize,
+        factor_parameters: &F::Parameters,
+        variable_parameters: &V::Parameters,
+    ) -> FGResult<Vec<QueryInfo<V::Marginal>>>
+    where
+        Q: IntoIterator<Item = (usize, V::Sample)>,
+    {
+        queries
+            .into_iter()
+            .map(|evidence| {
+                let mut fg = self.clone();
+                let mut queue = VecDeque::new();
+                for (var_index, value) in evidence {
+                    fg.freeze_variable(&value, var_index)?;
+                    queue.push_back(Node::Variable(var_index));
+                }
+                let updates_number = fg.propagate_locally(
+                    queue,
+                    threshold,
+                    max_updates_number,
+                    factor_parameters,
+                    variable_parameters,
+                )?;
+                Ok(QueryInfo {
+                    marginals: fg.variable_marginals(),
+                    updates_number,
+                })
+            })
+            .collect()
+    }
+
+    /// Computes marginals of all variables conditioned on evidence. Evidence
+    /// is clamped temporarily and changes of messages are propagated locally
+    /// similarly to `query_marginals`, afterwards the structure and messages of
+    /// a factor graph are restored, even if the method fails. Unlike
+    /// `query_marginals` it does not copy factors and variables of a factor graph
+    ///
+    /// # Arguments
+    ///
+    /// * `evidence` - Pairs (a variable index, an observed value)
+    /// * `threshold` - Changes of messages below this threshold are not propagated
+    /// * `max_updates_number` - A maximal number of node updates
+    /// * `factor_parameters` - Hyper-parameters of factors' message update rules
+    /// * `variable_parameters` - Hyper-parameters of variables' message update rules
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let mut fgb = new_ising_builder::<SumProduct>(3, 2);
+    /// fgb.add_factor(IsingFactor::new(0.3, 0., 0.), &[0, 1], &mut initializer).unwrap();
+    /// fgb.add_factor(IsingFactor::new(0.3, 0., 0.), &[1, 2], &mut initializer).unwrap();
+    /// let mut fg = fgb.build();
+    /// let factor_scheduler = get_standard_factor_scheduler(0.);
+    /// let variable_scheduler = get_standard_variable_scheduler(0.);
+    /// fg.run_message_passing_parallel(100, 0, 1e-10, &factor_scheduler, &variable_scheduler).unwrap();
+    /// let marginals = fg.variable_marginals();
+    ///
+    /// let info = fg
+    ///     .conditional_variable_marginals(&[(0, 1)], 1e-10, 1000, &factor_scheduler(0), &variable_scheduler(0))
+    ///     .unwrap();
+    /// let magnetization = info.marginals[2][0] - info.marginals[2][1];
+    /// assert!((magnetization - f64::tanh(0.3).powi(2)).abs() < 1e-8);
+    /// assert_eq!(fg.get_factor_degrees(), vec![2, 2]);
+    /// assert_eq!(fg.variable_marginals(), marginals);
+    /// ```
+    pub fn conditional_variable_marginals(
+        &mut self,
+        evidence: &[(usize, V::Sample)],
+        threshold: f64,
+        max_updates_number: usize,
+        factor_parameters: &F::Parameters,
+        variable_parameters: &V::Parameters,
+    ) -> FGResult<QueryInfo<V::Marginal>> {
+        self.perturbed_variable_marginals(
+            |fg| {
+                for (var_index, value) in evidence {
+                    fg.freeze_variable(value, *var_index)?;
+                }
+                Ok(evidence.iter().map(|(var_index, _)| *var_index).collect())
+            },
+            threshold,
+            max_updates_number,
+            factor_parameters,
+            variable_parameters,
+        )
+    }
+
+    // Modifies a factor graph by `perturb` that returns indices of perturbed variables,
+    // propagates changes of messages locally from them and returns marginals,
+    // afterwards the structure and messages of a factor graph are restored
+    pub(crate) fn perturbed_variable_marginals(
+        &mut self,
+        perturb: impl FnOnce(&mut Self) -> FGResult<Vec<usize>>,
+        threshold: f64,
+        max_updates_number: usize,
+        factor_parameters: &F::Parameters,
+        variable_parameters: &V::Parameters,
+    ) -> FGResult<QueryInfo<V::Marginal>> {
+        let snapshot = self.snapshot();
+        let run = || {
+            let queue = perturb(self)?.into_iter().map(Node::Variable).collect();
+            let updates_number = self.propagate_locally(
+                queue,
+                threshold,
+                max_updates_number,
+                factor_parameters,
+                variable_parameters,
+            )?;
+            Ok(QueryInfo {
+                marginals: self.variable_marginals(),
+                updates_number,
+            })
+        };
+        let result = run();
+        self.rollback(&snapshot)
+            .expect("A factor graph extends itself. This is a bug, please make an issue.");
+        result
+    }
+
+    // Updates nodes from a queue, enqueuing neighbours that have received
+    // messages changed by more than a threshold, returns the number of updates
+    fn propagate_locally(
+        &mut self,
+        mut queue: VecDeque<Node>,
+        threshold: f64,
+        max_updates_number: usize,
+        factor_parameters: &F::Parameters,
+        variable_parameters: &V::Parameters,
+    ) -> FGResult<usize> {
+        let mut queued_factors = vec![false; self.factors.len()];
+        let mut queued_variables = vec![false; self.variables.len()];
+        for node in &queue {
+            if let Node::Variable(var_index) = node {
+                queued_variables[*var_index] = true;
+            }
+        }
+        let mut updates_number = 0;
+        let mut last_discrepancy = 0f64;
+        let mut changed = Vec::new();
+        while let Some(node) = queue.pop_front() {
+            if updates_number == max_updates_number {
+                return Err(FGError::MessagePassingError {
+                    iterations_number: updates_number,
+                    last_discrepancy,
+                    discrepancy_dynamics: Vec::new(),
+                });
+            }
+            updates_number += 1;
+            last_discrepancy = 0f64;
+            changed.clear();
+            match node {
+                Node::Factor(fac_index) => {
+                    queued_factors[fac_index] = false;
+                    let factor = &mut self.factors[fac_index];
+                    factor.eval_messages(factor_parameters);
+                    let edges = factor
+                        .var_node_indices
+                        .iter()
+                        .zip(&factor.var_node_receiver_indices);
+                    for (message, (var_index, receiver_index)) in factor.messages.iter().zip(edges)
+                    {
+                        let discrepancy =
+                            self.variables[*var_index].receive_message(*receiver_index, message);
+                        last_discrepancy = last_discrepancy.max(discrepancy);
+                        if discrepancy > threshold {
+                            changed.push(*var_index);
+                        }
+                    }
+                    for var_index in &changed {
+                        if !queued_variables[*var_index] {
+                            queued_variables[*var_index] = true;
+                            queue.push_back(Node::Variable(*var_index));
+                        }
+                    }
+                }
+                Node::Variable(var_index) => {
+                    queued_variables[var_index] = false;
+                    let variable = &mut self.variables[var_index];
+                    variable.eval_messages(variable_parameters);
+                    let edges = variable
+                        .fac_node_indices
+                        .iter()
+                        .zip(&variable.fac_node_receiver_indices);
+                    for (message, (fac_index, receiver_index)) in
+                        variable.messages.iter().zip(edges)
+                    {
+                        let discrepancy =
+                            self.factors[*fac_index].receive_message(*receiver_index, message);
+                        last_discrepancy = last_discrepancy.max(discrepancy);
+                        if discrepancy > threshold {
+                            changed.push(*fac_index);
+                        }
+                    }
+                    for fac_index in &changed {
+                        if !queued_factors[*fac_index] {
+                            queued_factors[*fac_index] = true;
+                            queue.push_back(Node::Factor(*fac_index));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(updates_number)
+    }
+}
@@ -0,0 +1,217 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use rand::Rng;
+
+use crate::core::{
+    factor::Factor,
+    factor_graph::{FGError, FGResult, FactorGraph},
+    topology::{max_cycles_per_component, ExactnessCertificate, UnionFind},
+    variable::Variable,
+};
+
+// ------------------------------------------------------------------------------------------
+
+/// Information returned after Rao-Blackwellized sampling
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RaoBlackwellizedSamplingInfo<S, M> {
+    /// Samples of variables, None for variables that have not been sampled
+    pub samples: Vec<Option<S>>,
+
+    /// Marginals of all variables conditioned on the sampled ones
+    pub marginals: Vec<M>,
+
+    /// Total number of message passing iterations
+    pub total_iterations_number: usize,
+
+    /// Certificate of exactness of the conditional marginals that follows from
+    /// the topology of a factor graph with sampled variables removed
+    pub certificate: Option<ExactnessCertificate>,
+}
+
+impl<F, V> FactorGraph<F, V>
+where
+    F: Factor,
+    V: Variable<Message = F::Message>,
+{
+    /// Samples only a chosen subset of variables one by one similarly to
+    /// [`FactorGraph::sample`] and returns marginals of all the other variables
+    /// conditioned on the sampled ones instead of their samples. If a factor graph
+    /// with sampled variables removed is a forest, the conditional marginals of
+    /// a sum-product message passing are exact, and averaging them over samples gives
+    /// estimates of marginals with lower variance than averaging fully sampled
+    /// configurations. A loop cutset (see [`FactorGraph::loop_cutset`]) is a natural
+    /// choice of variables to sample
+    ///
+    /// # Arguments
+    ///
+    /// * `sampled_variables` - Indices of variables to sample in the order of sampling
+    /// * `max_iterations_number` - A maximal number of iterations in a message passing algorithm
+    /// * `min_iterations_number` - A minimal number of iterations in a message passing algorithm
+    /// * `threshold` - A threshold specifying the convergence criterion
+    /// * `rng` - A random numbers generator
+    /// * `factor_scheduler` - A scheduler of a factor's messages update rule hyper-parameters
+    /// * `variable_scheduler` - A scheduler of a variable's messages update rule hyper-parameters
+    ///
+    /// # Notes
+    ///
+    /// Messages of a factor graph are expected to be converged before sampling.
+    /// Note that sampled variables are drawn from message passing marginals,
+    /// which are approximate while a factor graph still has cycles.
+    /// Similarly to the `sample` method, sampled variables are fixed in a factor graph,
+    /// to keep the initial graph clone it before sampling
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::ExactnessCertificate;
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    ///
+    /// // A ring of 6 spins
+    /// let mut fgb = new_ising_builder::<SumProduct>(6, 6);
+    /// for i in 0..6 {
+    ///     fgb.add_factor(IsingFactor::new(0.5, 0.1, 0.), &[i, (i + 1) % 6], &mut initializer).unwrap();
+    /// }
+    /// let mut fg = fgb.build();
+    /// let factor_scheduler = get_standard_factor_scheduler(0.);
+    /// let variable_scheduler = get_standard_variable_scheduler(0.);
+    /// fg.run_message_passing_parallel(1000, 0, 1e-10, &factor_scheduler, &variable_scheduler).unwrap();
+    ///
+    /// // Sampling a single spin breaks the only cycle
+    /// let cutset = fg.loop_cutset();
+    /// assert_eq!(cutset.len(), 1);
+    /// let info = fg.sample_rao_blackwellized(
+    ///     &cutset,
+    ///     1000,
+    ///     0,
+    ///     1e-10,
+    ///     &mut thread_rng(),
+    ///     &factor_scheduler,
+    ///     &variable_scheduler,
+    /// ).unwrap();
+    /// assert_eq!(info.certificate, Some(ExactnessCertificate::Tree));
+    /// assert_eq!(info.samples.iter().filter(|s| s.is_some()).count(), 1);
+    /// assert_eq!(info.marginals.len(), 6);
+    /// ```
+    #[allow(clippy::too_many_arguments)]
+    pub fn sample_rao_blackwellized(
+        &mut self,
+        sampled_variables: &[usize],
+        max_iterations_number: usize,
+        min_iterations_number: usize,
+        threshold: f64,
+        rng: &mut impl Rng,
+        factor_scheduler: &impl Fn(usize) -> F::Parameters,
+        variable_scheduler: &impl Fn(usize) -> V::Parameters,
+    ) -> FGResult<RaoBlackwellizedSamplingInfo<V::Sample, V::Marginal>> {
+        let variables_number = self.variables.len();
+        if let Some(var_index) = sampled_variables
+            .iter()
+            .find(|var_index| **var_index >= variables_number)
+        {
+            return Err(FGError::OutOfRangeVariable(variables_number, *var_index));
+        }
+        let mut samples: Vec<Option<V::Sample>> = vec![None; variables_number];
+        let mut total_iterations_number = 0;
+        for (sampled_number, var_index) in sampled_variables.iter().enumerate() {
+            let sample = self.variables[*var_index].sample(rng);
+            samples[*var_index] = Some(sample);
+            self.freeze_variable(&sample, *var_index)?;
+            match self.run_message_passing_parallel(
+                max_iterations_number,
+                min_iterations_number,
+                threshold,
+                factor_scheduler,
+                variable_scheduler,
+            ) {
+                Ok(info) => total_iterations_number += info.iterations_number,
+                Err(FGError::MessagePassingError {
+                    iterations_number,
+                    last_discrepancy,
+                    discrepancy_dynamics,
+                }) => {
+                    return Err(FGError::SamplingError {
+                        variables_number: sampled_number,
+                        total_iterations_number: total_iterations_number + iterations_number,
+                        last_discrepancy,
+                        discrepancy_dynamics,
+                    })
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        let edges = self
+            .edges()
+            .filter(|(_, var_index)| samples[*var_index].is_none());
+        let certificate =
+            match max_cycles_per_component(self.factors.len(), variables_number, edges) {
+                0 => Some(ExactnessCertificate::Tree),
+                1 => Some(ExactnessCertificate::SingleCycle),
+                _ => None,
+            };
+        Ok(RaoBlackwellizedSamplingInfo {
+            samples,
+            marginals: self.variable_marginals(),
+            total_iterations_number,
+            certificate,
+        })
+    }
+
+    /// Greedily finds a loop cutset, i.e. a set of variables whose removal
+    /// turns a factor graph into a forest. Variables are considered in the order of
+    /// increasing degree and a variable goes to a cutset if it closes a cycle,
+    /// so that high degree variables tend to be in a cutset. The result is
+    /// not necessarily a minimal cutset
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    ///
+    /// // A star of triangles sharing the spin 0
+    /// let mut fgb = new_ising_builder::<SumProduct>(7, 9);
+    /// for i in 0..3 {
+    ///     let (lhs, rhs) = (2 * i + 1, 2 * i + 2);
+    ///     fgb.add_factor(IsingFactor::new(1., 0., 0.), &[0, lhs], &mut initializer).unwrap();
+    ///     fgb.add_factor(IsingFactor::new(1., 0., 0.), &[0, rhs], &mut initializer).unwrap();
+    ///     fgb.add_factor(IsingFactor::new(1., 0., 0.), &[lhs, rhs], &mut initializer).unwrap();
+    /// }
+    /// assert_eq!(fgb.build().loop_cutset(), vec![0]);
+    /// ```
+    pub fn loop_cutset(&self) -> Vec<usize> {
+        let factors_number = self.factors.len();
+        let mut order: Vec<usize> = (0..self.variables.len()).collect();
+        order.sort_by_key(|var_index| self.variables[*var_index].fac_node_indices.len());
+        let mut union_find = UnionFind::new(factors_number + self.variables.len());
+        let mut cutset = Vec::new();
+        let mut roots = Vec::new();
+        for var_index in order {
+            let fac_node_indices = &self.variables[var_index].fac_node_indices;
+            roots.clear();
+            roots.extend(
+                fac_node_indices
+                    .iter()
+                    .map(|fac_index| union_find.find(*fac_index)),
+            );
+            roots.sort_unstable();
+            roots.dedup();
+            if roots.len() < fac_node_indices.len() {
+                cutset.push(var_index);
+            } else {
+                for fac_index in fac_node_indices {
+                    union_find.union(*fac_index, factors_number + var_index);
+                }
+            }
+        }
+        cutset.sort_unstable();
+        cutset
+    }
+}
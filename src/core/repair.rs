@@ -0,0 +1,177 @@
+use std::collections::VecDeque;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::core::{
+    factor::Factor,
+    factor_graph::{FGError, FGResult, FactorGraph},
+    message::nan_max,
+    variable::Variable,
+};
+
+// ------------------------------------------------------------------------------------------
+
+/// Information returned after a localized repair of messages
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RepairInfo {
+    /// Number of factor updates performed before convergence
+    pub updates_number: usize,
+
+    /// Number of distinct factors updated at least once
+    pub touched_factors_number: usize,
+
+    /// Number of distinct variables whose messages have been updated at least once
+    pub touched_variables_number: usize,
+}
+
+impl<F, V> FactorGraph<F, V>
+where
+    F: Factor,
+    V: Variable<Message = F::Message>,
+{
+    /// Replaces a few factors of a converged factor graph and re-converges messages
+    /// by localized repair. Starting from replaced factors, a factor sends new messages
+    /// to its variables, and a variable whose received message has changed by more than
+    /// a threshold updates its messages and schedules its neighboring factors. Thus only
+    /// the region where messages actually change is updated, which is much faster than
+    /// a full re-solve if a few couplings change at a time, e.g. at each tick of an online
+    /// model. A factor graph is not modified if a replacement fails
+    ///
+    /// # Arguments
+    ///
+    /// * `updates` - Pairs (a factor index, a new factor), degrees of new factors must match
+    ///   degrees of replaced ones
+    /// * `max_updates_number` - A maximal number of factor updates, if messages
+    ///   do not converge before reaching this number of updates, it fails
+    /// * `threshold` - A threshold on the discrepancy of messages below which a change
+    ///   is not propagated further
+    /// * `factor_parameters` - Hyper-parameters of a factor's messages update rule
+    /// * `variable_parameters` - Hyper-parameters of a variable's messages update rule
+    ///
+    /// # Notes
+    ///
+    /// Messages are updated asynchronously and without damping. If a factor graph
+    /// has not converged before the repair, the result approximates a fixed point
+    /// only in the repaired region, thus run [`FactorGraph::run_message_passing_parallel`]
+    /// first. On failure `FGError::MessagePassingError` reports the number of factor updates
+    /// and discrepancies of messages sent by each updated factor
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_chain_builder, IsingFactor, IsingFactorHyperParameters, IsingMessage, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    ///
+    /// let mut fg = new_ising_chain_builder::<SumProduct>(100, 0.5, 0.1, [0., 0.], &mut || IsingMessage(0.))
+    ///     .unwrap()
+    ///     .build();
+    /// fg.run_message_passing_parallel(
+    ///     1000,
+    ///     0,
+    ///     1e-12,
+    ///     &get_standard_factor_scheduler(0.),
+    ///     &get_standard_variable_scheduler(0.),
+    /// ).unwrap();
+    ///
+    /// // A coupling in the middle of the chain changes
+    /// let info = fg.update_factors_locally(
+    ///     [(50, IsingFactor::new(-0.5, 0., 0.))],
+    ///     10000,
+    ///     1e-12,
+    ///     &IsingFactorHyperParameters::new(1., 0.),
+    ///     &0.,
+    /// ).unwrap();
+    /// assert!(info.touched_factors_number < 99);
+    /// ```
+    pub fn update_factors_locally(
+        &mut self,
+        updates: impl IntoIterator<Item = (usize, F)>,
+        max_updates_number: usize,
+        threshold: f64,
+        factor_parameters: &F::Parameters,
+        variable_parameters: &V::Parameters,
+    ) -> FGResult<RepairInfo> {
+        let updates: Vec<_> = updates.into_iter().collect();
+        let factors_number = self.factors.len();
+        for (fac_index, factor) in &updates {
+            let Some(factor_node) = self.factors.get(*fac_index) else {
+                return Err(FGError::OutOfRangeFactor(factors_number, *fac_index));
+            };
+            if factor_node.degree() != factor.degree() {
+                return Err(FGError::FactorDegreeError(
+                    factor_node.degree(),
+                    factor.degree(),
+                ));
+            }
+        }
+        let mut is_queued = vec![false; factors_number];
+        let mut is_touched_factor = vec![false; factors_number];
+        let mut is_touched_variable = vec![false; self.variables.len()];
+        let mut queue = VecDeque::with_capacity(updates.len());
+        for (fac_index, factor) in updates {
+            self.factors[fac_index].factor = factor;
+            if !is_queued[fac_index] {
+                is_queued[fac_index] = true;
+                queue.push_back(fac_index);
+            }
+        }
+        let mut discrepancy_dynamics = Vec::new();
+        while let Some(fac_index) = queue.pop_front() {
+            if discrepancy_dynamics.len() == max_updates_number {
+                return Err(FGError::MessagePassingError {
+                    iterations_number: max_updates_number,
+                    last_discrepancy: discrepancy_dynamics.last().copied().unwrap_or(f64::NAN),
+                    discrepancy_dynamics,
+                });
+            }
+            is_queued[fac_index] = false;
+            is_touched_factor[fac_index] = true;
+            self.factors[fac_index].eval_messages(factor_parameters);
+            let mut max_discrepancy = 0f64;
+            for position in 0..self.factors[fac_index].messages.len() {
+                let factor = &self.factors[fac_index];
+                let var_index = factor.var_node_indices[position];
+                let variable = &mut self.variables[var_index];
+                let discrepancy = variable.receive_message(
+                    factor.var_node_receiver_indices[position],
+                    &factor.messages[position],
+                );
+                max_discrepancy = nan_max(max_discrepancy, discrepancy);
+                if discrepancy <= threshold {
+                    continue;
+                }
+                is_touched_variable[var_index] = true;
+                variable.eval_messages(variable_parameters);
+                let variable = &self.variables[var_index];
+                let neighbors = variable
+                    .fac_node_indices
+                    .iter()
+                    .zip(&variable.fac_node_receiver_indices)
+                    .zip(&variable.messages);
+                for ((neighbor, receiver_index), message) in neighbors {
+                    let neighbor_discrepancy =
+                        self.factors[*neighbor].receive_message(*receiver_index, message);
+                    if neighbor_discrepancy > threshold && !is_queued[*neighbor] {
+                        is_queued[*neighbor] = true;
+                        queue.push_back(*neighbor);
+                    }
+                }
+            }
+            discrepancy_dynamics.push(max_discrepancy);
+        }
+        // contradictions are checked lazily, only if messages have diverged
+        if discrepancy_dynamics
+            .iter()
+            .any(|discrepancy| !discrepancy.is_finite())
+        {
+            self.check_contradictions()?;
+        }
+        Ok(RepairInfo {
+            updates_number: discrepancy_dynamics.len(),
+            touched_factors_number: is_touched_factor.iter().filter(|x| **x).count(),
+            touched_variables_number: is_touched_variable.iter().filter(|x| **x).count(),
+        })
+    }
+}
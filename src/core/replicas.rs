@@ -0,0 +1,132 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+
+use crate::core::{
+    factor::Factor,
+    factor_graph::{BatchSamplingInfo, FGResult, FactorGraph, SamplingInfo},
+    variable::Variable,
+};
+
+// ------------------------------------------------------------------------------------------
+
+impl<F, V> FactorGraph<F, V>
+where
+    F: Factor + Sync,
+    V: Variable<Message = F::Message> + Sync,
+    V::Sample: Send,
+{
+    /// Draws independent samples by running decimation chains on replicas of
+    /// a factor graph concurrently, one replica per sample, without modifying
+    /// a factor graph. It is a parallel counterpart of [`FactorGraph::sample_n`]
+    ///
+    /// # Arguments
+    ///
+    /// * `replicas_number` - A number of replicas, i.e. samples to draw
+    /// * `max_iterations_number` - A maximal number of iterations in a message passing algorithm
+    /// * `min_iterations_number` - A minimal number of iterations in a message passing algorithm
+    /// * `threshold` - A threshold specifying the convergence criterion
+    /// * `rng` - A random numbers generator seeding generators of replicas
+    /// * `factor_scheduler` - A scheduler of a factor's messages update rule hyper-parameters
+    /// * `variable_scheduler` - A scheduler of a variable's messages update rule hyper-parameters
+    ///
+    /// # Notes
+    ///
+    /// Replicas are distributed over threads of a thread pool of a factor graph if it
+    /// is set or the global rayon thread pool otherwise, message passing within a replica
+    /// is serial. Each replica gets its own random numbers generator seeded by `rng`,
+    /// thus samples do not depend on the number of threads. Samples and per-replica
+    /// numbers of iterations and decimation orders are returned in the order of replicas.
+    /// If any of the replicas fails, the error of the first failed one is returned
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use rand::{rngs::StdRng, SeedableRng};
+    ///
+    /// let mut initializer = random_message_initializer(StdRng::seed_from_u64(42), -0.5, 0.5);
+    /// let mut fgb = new_ising_builder::<SumProduct>(3, 2);
+    /// fgb.add_factor(IsingFactor::new(0.5, 0.2, 0.), &[0, 1], &mut initializer).unwrap();
+    /// fgb.add_factor(IsingFactor::new(-0.5, 0., 0.1), &[1, 2], &mut initializer).unwrap();
+    /// let mut fg = fgb.build();
+    /// let factor_scheduler = get_standard_factor_scheduler(0.);
+    /// let variable_scheduler = get_standard_variable_scheduler(0.);
+    /// fg.run_message_passing_parallel(100, 0, 1e-10, &factor_scheduler, &variable_scheduler).unwrap();
+    /// let info = fg.sample_parallel(
+    ///     100,
+    ///     100,
+    ///     0,
+    ///     1e-10,
+    ///     &mut StdRng::seed_from_u64(7),
+    ///     &factor_scheduler,
+    ///     &variable_scheduler,
+    /// ).unwrap();
+    /// assert_eq!(info.samples.len(), 100);
+    /// assert_eq!(info.iterations_per_sample.len(), 100);
+    ///
+    /// // The same seed gives the same samples
+    /// let other = fg.sample_parallel(
+    ///     100,
+    ///     100,
+    ///     0,
+    ///     1e-10,
+    ///     &mut StdRng::seed_from_u64(7),
+    ///     &factor_scheduler,
+    ///     &variable_scheduler,
+    /// ).unwrap();
+    /// assert_eq!(info.samples, other.samples);
+    /// ```
+    #[allow(clippy::too_many_arguments)]
+    pub fn sample_parallel(
+        &self,
+        replicas_number: usize,
+        max_iterations_number: usize,
+        min_iterations_number: usize,
+        threshold: f64,
+        rng: &mut impl Rng,
+        factor_scheduler: &(impl Fn(usize) -> F::Parameters + Sync),
+        variable_scheduler: &(impl Fn(usize) -> V::Parameters + Sync),
+    ) -> FGResult<BatchSamplingInfo<V::Sample>> {
+        let seeds: Vec<u64> = (0..replicas_number).map(|_| rng.gen()).collect();
+        let sample_replicas = || -> Vec<FGResult<SamplingInfo<V::Sample>>> {
+            seeds
+                .into_par_iter()
+                .map(|seed| {
+                    let mut replica = self.clone();
+                    replica.thread_pool = None;
+                    replica.parallel_threshold = usize::MAX;
+                    replica.sample(
+                        max_iterations_number,
+                        min_iterations_number,
+                        threshold,
+                        &mut StdRng::seed_from_u64(seed),
+                        factor_scheduler,
+                        variable_scheduler,
+                    )
+                })
+                .collect()
+        };
+        let replicas = match &self.thread_pool {
+            Some(thread_pool) => thread_pool.install(sample_replicas),
+            None => sample_replicas(),
+        };
+        let mut samples = Vec::with_capacity(replicas_number);
+        let mut iterations_per_sample = Vec::with_capacity(replicas_number);
+        let mut decimation_orders = Vec::with_capacity(replicas_number);
+        for info in replicas {
+            let info = info?;
+            samples.push(info.samples);
+            iterations_per_sample.push(info.total_iterations_number);
+            decimation_orders.push(info.decimation_order);
+        }
+        Ok(BatchSamplingInfo {
+            samples,
+            total_iterations_number: iterations_per_sample.iter().sum(),
+            iterations_per_sample,
+            log_weights: None,
+            energies: None,
+            decimation_orders: Some(decimation_orders),
+        })
+    }
+}
@@ -0,0 +1,182 @@
+use std::{collections::BTreeMap, time::Instant};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::core::{
+    factor::Factor,
+    factor_graph::{FGError, FGResult, FactorGraph, MessagePassingInfo},
+    variable::Variable,
+};
+
+// ------------------------------------------------------------------------------------------
+
+/// A summary of a message passing run with a stable schema, e.g. to pass results of
+/// experiments to downstream pipelines. Quantities computed after a run
+/// (free entropies, observables, timings) are added by name
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RunReport {
+    /// Whether message passing has converged
+    pub is_converged: bool,
+
+    /// Number of iterations past before convergence or failure
+    pub iterations_number: usize,
+
+    /// Discrepancy of the last iteration, NaN if message passing has not been run
+    pub discrepancy: f64,
+
+    /// A description of an error if message passing has failed
+    pub error: Option<String>,
+
+    /// Estimates of free entropies, e.g. the Bethe one, by names
+    pub free_entropies: BTreeMap<String, f64>,
+
+    /// Values of observables by names
+    pub observables: BTreeMap<String, f64>,
+
+    /// Durations of stages of a run in seconds by names
+    pub timings: BTreeMap<String, f64>,
+}
+
+impl RunReport {
+    /// Creates a report from a result of message passing
+    ///
+    /// # Arguments
+    ///
+    /// * `result` - A result of message passing
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::{FGError, RunReport};
+    ///
+    /// let report = RunReport::new(&Err(FGError::MessagePassingError {
+    ///     iterations_number: 100,
+    ///     last_discrepancy: 0.5,
+    ///     discrepancy_dynamics: vec![],
+    /// }));
+    /// assert!(!report.is_converged);
+    /// assert_eq!(report.iterations_number, 100);
+    /// assert_eq!(report.discrepancy, 0.5);
+    /// assert!(report.error.is_some());
+    /// ```
+    pub fn new(result: &FGResult<MessagePassingInfo>) -> Self {
+        let (is_converged, iterations_number, discrepancy) = match result {
+            Ok(info) => (true, info.iterations_number, info.last_discrepancy),
+            Err(FGError::MessagePassingError {
+                iterations_number,
+                last_discrepancy,
+                ..
+            })
+            | Err(FGError::Interrupted {
+                iterations_number,
+                last_discrepancy,
+                ..
+            }) => (false, *iterations_number, *last_discrepancy),
+            Err(_) => (false, 0, f64::NAN),
+        };
+        RunReport {
+            is_converged,
+            iterations_number,
+            discrepancy,
+            error: result.as_ref().err().map(|err| err.to_string()),
+            free_entropies: BTreeMap::new(),
+            observables: BTreeMap::new(),
+            timings: BTreeMap::new(),
+        }
+    }
+
+    /// Adds an estimate of a free entropy
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - A name of an estimate
+    /// * `value` - A value of an estimate
+    #[inline]
+    pub fn with_free_entropy(mut self, name: &str, value: f64) -> Self {
+        self.free_entropies.insert(name.to_string(), value);
+        self
+    }
+
+    /// Adds a value of an observable
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - A name of an observable
+    /// * `value` - A value of an observable
+    #[inline]
+    pub fn with_observable(mut self, name: &str, value: f64) -> Self {
+        self.observables.insert(name.to_string(), value);
+        self
+    }
+
+    /// Adds a duration of a stage of a run
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - A name of a stage
+    /// * `seconds` - A duration in seconds
+    #[inline]
+    pub fn with_timing(mut self, name: &str, seconds: f64) -> Self {
+        self.timings.insert(name.to_string(), seconds);
+        self
+    }
+}
+
+impl<F, V> FactorGraph<F, V>
+where
+    F: Factor,
+    V: Variable<Message = F::Message>,
+{
+    /// Runs message passing as [`FactorGraph::run_message_passing_parallel`] and
+    /// summarizes the run in a report, the duration of message passing is
+    /// reported as the `message_passing` timing
+    ///
+    /// # Arguments
+    ///
+    /// * `max_iterations_number` - A maximal number of iterations
+    /// * `min_iterations_number` - A minimal number of iterations
+    /// * `threshold` - A threshold specifying the convergence criterion
+    /// * `factor_scheduler` - A scheduler of a factor's messages update rule hyper-parameters
+    /// * `variable_scheduler` - A scheduler of a variable's messages update rule hyper-parameters
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let mut fgb = new_ising_builder::<SumProduct>(2, 1);
+    /// fgb.add_factor(IsingFactor::new(0.5, 0.1, 0.), &[0, 1], &mut initializer).unwrap();
+    /// let mut fg = fgb.build();
+    /// let factor_scheduler = get_standard_factor_scheduler(0.);
+    /// let report = fg
+    ///     .run_message_passing_report(100, 0, 1e-10, &factor_scheduler, &get_standard_variable_scheduler(0.))
+    ///     .with_free_entropy("bethe", fg.bethe_entropy(&factor_scheduler(0)) - fg.bethe_energy(&factor_scheduler(0)))
+    ///     .with_observable("magnetization", fg.variable_marginals()[0][0] * 2. - 1.);
+    /// assert!(report.is_converged);
+    /// assert!(report.timings.contains_key("message_passing"));
+    /// assert!(report.free_entropies.contains_key("bethe"));
+    /// ```
+    pub fn run_message_passing_report(
+        &mut self,
+        max_iterations_number: usize,
+        min_iterations_number: usize,
+        threshold: f64,
+        factor_scheduler: &impl Fn(usize) -> F::Parameters,
+        variable_scheduler: &impl Fn(usize) -> V::Parameters,
+    ) -> RunReport {
+        let start = Instant::now();
+        let result = self.run_message_passing_parallel(
+            max_iterations_number,
+            min_iterations_number,
+            threshold,
+            factor_scheduler,
+            variable_scheduler,
+        );
+        RunReport::new(&result).with_timing("message_passing", start.elapsed().as_secs_f64())
+    }
+}
@@ -0,0 +1,654 @@
+use std::{ops::ControlFlow, time::Instant};
+
+use rand::Rng;
+
+use crate::core::{
+    factor::Factor,
+    factor_graph::{
+        BatchSamplingInfo, FGError, FGResult, FactorGraph, MessagePassingInfo, SamplingInfo,
+        SamplingProgress, StreamingSamplingInfo,
+    },
+    options::{MessagePassingOptions, Observables, Observer},
+    sink::SampleSink,
+    topology::UnionFind,
+    variable::Variable,
+};
+
+// ------------------------------------------------------------------------------------------
+
+impl<F, V> FactorGraph<F, V>
+where
+    F: Factor,
+    V: Variable<Message = F::Message>,
+{
+    /// Samples variables from a factor graph
+    ///
+    /// # Arguments
+    ///
+    /// * `max_iterations_number` - A maximal number of iterations in a message passing algorithm
+    /// * `min_iterations_number` - A minimal number of iterations that is performed
+    ///   disregards reaching the convergence criterion. It could be useful
+    ///   for example when one performs belief propagation with temperature annealing
+    /// * `threshold` - A threshold specifying the convergence criterion. A process
+    ///   is considered as successful if the discrepancy between two subsequent
+    ///   messages configurations is less than the threshold
+    /// * `rng` - A random numbers generator
+    /// * `factor_scheduler` - A scheduler of a factor's messages update rule hyper-parameters.
+    ///   It takes an iteration number (starts from 0) and return hyper-parameters.
+    /// * `variable_scheduler` - A scheduler of a variable's messages update rule hyper-parameters.
+    ///   It takes an iteration number (starts from 0) and return hyper-parameters.
+    ///
+    /// # Notes
+    ///
+    /// Sampling requires to run message passing after sampling and fixing of each
+    /// variable to get stationary messages for the updated factor graph.
+    /// This is why one has some arguments similar to those of 'run_message_passing_parallel'
+    /// method. Note also, that this method fixes all variables of a factor graph making
+    /// them further unusable. To keep the initial graph simply clone it before running
+    /// sampling. Other parameters of sampling, e.g. pinning or a time budget, are set by
+    /// [`MessagePassingOptions`] accepted by [`FactorGraph::sample_with_options`]
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::FactorGraphBuilder;
+    /// use gmrs::ising::{IsingFactor, IsingVariable, SumProduct, random_message_initializer};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use rand::thread_rng;
+    ///
+    /// // Aliases to shorten types
+    /// type Factor = IsingFactor<SumProduct>;
+    /// type Variable = IsingVariable<SumProduct>;
+    ///
+    /// // Messages initializer
+    /// let rng = thread_rng();
+    /// let mut initializer = random_message_initializer(rng, -0.5, 0.5);
+    ///
+    /// // Message passing schedulers
+    /// let factor_scheduler = get_standard_factor_scheduler(0.);
+    /// let variable_scheduler = get_standard_variable_scheduler(0.);
+    ///
+    /// // Message passing
+    /// let mut fgb = FactorGraphBuilder::<Factor, Variable>::new_with_variables(vec![IsingVariable::new(); 2], 2);
+    /// fgb.add_factor(
+    ///     IsingFactor::new(0.5f64, 0.5f64, -0.5f64),
+    ///    &[0, 1],
+    ///    &mut initializer,
+    /// );
+    /// let mut fg = fgb.build();
+    /// let _ = fg.run_message_passing_parallel(
+    ///     100,
+    ///     0,
+    ///     1e-10,
+    ///     &factor_scheduler,
+    ///     &variable_scheduler,
+    /// ).unwrap();
+    ///
+    /// // Sampling
+    /// let mut rng = thread_rng();
+    /// let sampling_info = fg.sample(
+    ///     100,
+    ///     0,
+    ///     1e-10,
+    ///     &mut rng,
+    ///     &factor_scheduler,
+    ///     &variable_scheduler,
+    /// ).unwrap();
+    ///
+    /// // Validation
+    ///
+    /// // Sampling adds fixing factor to each variable
+    /// let variable_degree = fg.get_variable_degrees();
+    /// let factor_degree = fg.get_factor_degrees();
+    /// assert_eq!(variable_degree, vec![2, 2]);
+    /// assert_eq!(factor_degree, vec![2, 1, 1]);
+    ///
+    /// let marginals = fg.variable_marginals();
+    /// let samples = sampling_info.samples;
+    /// assert!((marginals[0][((1 - samples[0]) / 2) as usize] - 1f64).abs() < 1e-8);
+    /// assert!((marginals[1][((1 - samples[1]) / 2) as usize] - 1f64).abs() < 1e-8);
+    /// ```
+    pub fn sample(
+        &mut self,
+        max_iterations_number: usize,
+        min_iterations_number: usize,
+        threshold: f64,
+        rng: &mut impl Rng,
+        factor_scheduler: &impl Fn(usize) -> F::Parameters,
+        variable_scheduler: &impl Fn(usize) -> V::Parameters,
+    ) -> FGResult<SamplingInfo<V::Sample>> {
+        self.sample_clamped(
+            &[],
+            rng,
+            &mut MessagePassingOptions::new(factor_scheduler, variable_scheduler)
+                .with_max_iterations_number(max_iterations_number)
+                .with_min_iterations_number(min_iterations_number)
+                .with_threshold(threshold),
+            Self::run_scheduled_since,
+        )
+    }
+
+    /// Draws a conditional sample of not assigned variables given a partial
+    /// assignment, i.e. a sample from `P(x_rest | x_fixed)`. Assigned variables are
+    /// clamped to their values before decimation starts, message passing is run
+    /// once to propagate the assignment and the rest variables are sampled
+    /// one by one as in [`FactorGraph::sample`]
+    ///
+    /// # Arguments
+    ///
+    /// * `assignment` - Pairs of indices of variables and their values
+    /// * `max_iterations_number` - A maximal number of iterations in a message passing algorithm
+    /// * `min_iterations_number` - A minimal number of iterations in a message passing algorithm
+    /// * `threshold` - A threshold specifying the convergence criterion
+    /// * `rng` - A random numbers generator
+    /// * `factor_scheduler` - A scheduler of a factor's messages update rule hyper-parameters
+    /// * `variable_scheduler` - A scheduler of a variable's messages update rule hyper-parameters
+    ///
+    /// # Notes
+    ///
+    /// Returned samples contain values of clamped variables at their positions, the clamped
+    /// variables are listed in `clamped_variables` and are not included into `decimation_order`.
+    /// Iterations of the message passing run propagating the assignment are counted in
+    /// `total_iterations_number` only. If an assignment has zero probability and the variable
+    /// checks contradictions, the corresponding error is returned. The method
+    /// fails if an index is out of range or a variable is assigned several times
+    /// before a factor graph is modified
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::FactorGraphBuilder;
+    /// use gmrs::ising::{IsingFactor, IsingVariable, SumProduct, random_message_initializer};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use rand::thread_rng;
+    ///
+    /// // Aliases to shorten types
+    /// type Factor = IsingFactor<SumProduct>;
+    /// type Variable = IsingVariable<SumProduct>;
+    ///
+    /// let mut rng = thread_rng();
+    /// let mut initializer = random_message_initializer(rng.clone(), -0.5, 0.5);
+    /// let factor_scheduler = get_standard_factor_scheduler(0.);
+    /// let variable_scheduler = get_standard_variable_scheduler(0.);
+    ///
+    /// // A chain of spins locked together by strong couplings
+    /// let mut fgb = FactorGraphBuilder::<Factor, Variable>::new_with_variables(vec![IsingVariable::new(); 5], 4);
+    /// for i in 0..4 {
+    ///     fgb.add_factor(IsingFactor::new(30., 0., 0.), &[i, i + 1], &mut initializer).unwrap();
+    /// }
+    /// let mut fg = fgb.build();
+    ///
+    /// // The middle spin is clamped down, thus all other spins follow it
+    /// let info = fg.sample_conditional(&[(2, -1)], 100, 0, 1e-10, &mut rng, &factor_scheduler, &variable_scheduler).unwrap();
+    /// assert_eq!(info.samples, vec![-1; 5]);
+    /// assert_eq!(info.clamped_variables, vec![2]);
+    /// assert_eq!(info.decimation_order, vec![0, 1, 3, 4]);
+    /// ```
+    #[allow(clippy::too_many_arguments)]
+    pub fn sample_conditional(
+        &mut self,
+        assignment: &[(usize, V::Sample)],
+        max_iterations_number: usize,
+        min_iterations_number: usize,
+        threshold: f64,
+        rng: &mut impl Rng,
+        factor_scheduler: &impl Fn(usize) -> F::Parameters,
+        variable_scheduler: &impl Fn(usize) -> V::Parameters,
+    ) -> FGResult<SamplingInfo<V::Sample>> {
+        self.sample_clamped(
+            assignment,
+            rng,
+            &mut MessagePassingOptions::new(factor_scheduler, variable_scheduler)
+                .with_max_iterations_number(max_iterations_number)
+                .with_min_iterations_number(min_iterations_number)
+                .with_threshold(threshold),
+            Self::run_scheduled_since,
+        )
+    }
+
+    // Samples not assigned variables one by one after clamping assigned ones,
+    // message passing runs are performed by `run` and the budget of options limits
+    // the time of the whole sampling
+    pub(super) fn sample_clamped<FS, VS, O, P, B>(
+        &mut self,
+        assignment: &[(usize, V::Sample)],
+        rng: &mut impl Rng,
+        options: &mut MessagePassingOptions<FS, VS, O, P, B>,
+        mut run: impl FnMut(
+            &mut Self,
+            Instant,
+            &mut MessagePassingOptions<FS, VS, O, P, B>,
+        ) -> FGResult<MessagePassingInfo>,
+    ) -> FGResult<SamplingInfo<V::Sample>>
+    where
+        FS: Fn(usize) -> F::Parameters,
+        VS: Fn(usize) -> V::Parameters,
+        O: Observer<F, V>,
+        P: FnMut(&SamplingProgress),
+        B: Observables<F, V>,
+    {
+        let start = Instant::now();
+        let variables_number = self.variables.len();
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!(
+            "sampling",
+            variables_number,
+            clamped_variables_number = assignment.len()
+        )
+        .entered();
+        let mut samples: Vec<Option<V::Sample>> = vec![None; variables_number];
+        for (var_index, value) in assignment {
+            match samples.get_mut(*var_index) {
+                None => {
+                    return Err(FGError::OutOfRangeVariable(variables_number, *var_index));
+                }
+                Some(Some(_)) => return Err(FGError::DuplicateAssignment(*var_index)),
+                Some(sample) => *sample = Some(*value),
+            }
+        }
+        let mut total_iterations_number = 0;
+        let mut iterations_per_variable = vec![0; variables_number];
+        let mut pinned_variables_number = 0;
+        let mut decimation_order = Vec::with_capacity(variables_number);
+        let clamped_variables: Vec<usize> = assignment.iter().map(|(i, _)| *i).collect();
+        if !assignment.is_empty() {
+            for (var_index, value) in assignment {
+                self.freeze_variable(value, *var_index).unwrap();
+            }
+            match run(self, start, options) {
+                Ok(info) => total_iterations_number += info.iterations_number,
+                Err(FGError::MessagePassingError {
+                    iterations_number,
+                    last_discrepancy,
+                    discrepancy_dynamics,
+                }) => {
+                    return Err(FGError::SamplingError {
+                        variables_number: 0,
+                        total_iterations_number: iterations_number,
+                        last_discrepancy,
+                        discrepancy_dynamics,
+                    });
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        for i in 0..variables_number {
+            if samples[i].is_some() {
+                continue;
+            }
+            let sample = self.variables.get_mut(i).unwrap().sample(rng);
+            samples[i] = Some(sample);
+            decimation_order.push(i);
+            self.freeze_variable(&sample, i).unwrap();
+            let last_discrepancy = match run(self, start, options) {
+                Ok(info) => {
+                    total_iterations_number += info.iterations_number;
+                    iterations_per_variable[i] = info.iterations_number;
+                    info.last_discrepancy
+                }
+                Err(FGError::MessagePassingError {
+                    iterations_number,
+                    last_discrepancy,
+                    discrepancy_dynamics,
+                }) => {
+                    return Err(FGError::SamplingError {
+                        variables_number: decimation_order.len() - 1,
+                        total_iterations_number: total_iterations_number + iterations_number,
+                        last_discrepancy,
+                        discrepancy_dynamics,
+                    });
+                }
+                Err(FGError::TimedOut {
+                    elapsed,
+                    iterations_number,
+                    last_discrepancy,
+                    discrepancy_dynamics,
+                    ..
+                }) => {
+                    return Err(FGError::TimedOut {
+                        elapsed,
+                        sampled_variables_number: decimation_order.len() - 1,
+                        iterations_number: total_iterations_number + iterations_number,
+                        last_discrepancy,
+                        discrepancy_dynamics,
+                    });
+                }
+                Err(err) => return Err(err),
+            };
+            if options.pinning_tolerance > 0f64 {
+                let pinned = self.pinned_components(&samples, options.pinning_tolerance);
+                for (var_index, sample) in pinned {
+                    samples[var_index] = Some(sample);
+                    self.freeze_variable(&sample, var_index).unwrap();
+                    decimation_order.push(var_index);
+                    pinned_variables_number += 1;
+                }
+            }
+            #[cfg(feature = "tracing")]
+            tracing::trace!(
+                variable = i,
+                sampled_variables_number = decimation_order.len(),
+                "variable sampled"
+            );
+            (options.progress)(&SamplingProgress {
+                sampled_variables_number: decimation_order.len(),
+                variables_number: variables_number - clamped_variables.len(),
+                total_iterations_number,
+                last_discrepancy,
+            });
+        }
+        #[cfg(feature = "tracing")]
+        tracing::debug!(total_iterations_number, pinned_variables_number, "sampled");
+        Ok(SamplingInfo {
+            samples: samples.into_iter().map(|s| s.unwrap()).collect(),
+            iterations_per_variable,
+            total_iterations_number,
+            pinned_variables_number,
+            decimation_order,
+            clamped_variables,
+            backtracks_number: 0,
+            log_weight: None,
+            energy: None,
+        })
+    }
+
+    /// Draws several independent samples from a factor graph
+    /// without modifying it
+    ///
+    /// # Arguments
+    ///
+    /// * `samples_number` - A number of samples to draw
+    /// * `max_iterations_number` - A maximal number of iterations in a message passing algorithm
+    /// * `min_iterations_number` - A minimal number of iterations that is performed
+    ///   disregards reaching the convergence criterion
+    /// * `threshold` - A threshold specifying the convergence criterion
+    /// * `rng` - A random numbers generator
+    /// * `factor_scheduler` - A scheduler of a factor's messages update rule hyper-parameters.
+    ///   It takes an iteration number (starts from 0) and return hyper-parameters.
+    /// * `variable_scheduler` - A scheduler of a variable's messages update rule hyper-parameters.
+    ///   It takes an iteration number (starts from 0) and return hyper-parameters.
+    ///
+    /// # Notes
+    ///
+    /// Each sample is drawn by the `sample` method from a fresh clone of a factor graph,
+    /// i.e. all samples start from the current configuration of messages. A factor graph
+    /// itself stays untouched and can be used further. If sampling fails for any of
+    /// the samples, the method returns the corresponding error
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::FactorGraphBuilder;
+    /// use gmrs::ising::{IsingFactor, IsingVariable, SumProduct, random_message_initializer};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use rand::thread_rng;
+    ///
+    /// // Aliases to shorten types
+    /// type Factor = IsingFactor<SumProduct>;
+    /// type Variable = IsingVariable<SumProduct>;
+    ///
+    /// // Messages initializer
+    /// let rng = thread_rng();
+    /// let mut initializer = random_message_initializer(rng, -0.5, 0.5);
+    ///
+    /// // Message passing schedulers
+    /// let factor_scheduler = get_standard_factor_scheduler(0.);
+    /// let variable_scheduler = get_standard_variable_scheduler(0.);
+    ///
+    /// // Message passing
+    /// let mut fgb = FactorGraphBuilder::<Factor, Variable>::new_with_variables(vec![IsingVariable::new(); 2], 1);
+    /// fgb.add_factor(
+    ///     IsingFactor::new(0.5f64, 0.5f64, -0.5f64),
+    ///     &[0, 1],
+    ///     &mut initializer,
+    /// );
+    /// let mut fg = fgb.build();
+    /// let _ = fg.run_message_passing_parallel(
+    ///     100,
+    ///     0,
+    ///     1e-10,
+    ///     &factor_scheduler,
+    ///     &variable_scheduler,
+    /// ).unwrap();
+    ///
+    /// // Sampling
+    /// let mut rng = thread_rng();
+    /// let sampling_info = fg.sample_n(
+    ///     10,
+    ///     100,
+    ///     0,
+    ///     1e-10,
+    ///     &mut rng,
+    ///     &factor_scheduler,
+    ///     &variable_scheduler,
+    /// ).unwrap();
+    ///
+    /// // Validation
+    /// assert_eq!(sampling_info.samples.len(), 10);
+    /// assert!(sampling_info.samples.iter().all(|sample| sample.len() == 2));
+    ///
+    /// // The factor graph is untouched
+    /// assert_eq!(fg.get_factor_degrees(), vec![2]);
+    /// ```
+    #[allow(clippy::too_many_arguments)]
+    pub fn sample_n(
+        &self,
+        samples_number: usize,
+        max_iterations_number: usize,
+        min_iterations_number: usize,
+        threshold: f64,
+        rng: &mut impl Rng,
+        factor_scheduler: &impl Fn(usize) -> F::Parameters,
+        variable_scheduler: &impl Fn(usize) -> V::Parameters,
+    ) -> FGResult<BatchSamplingInfo<V::Sample>> {
+        let mut samples = Vec::with_capacity(samples_number);
+        let mut iterations_per_sample = Vec::with_capacity(samples_number);
+        let mut decimation_orders = Vec::with_capacity(samples_number);
+        let mut total_iterations_number = 0;
+        let _ = self.sample_n_with(
+            samples_number,
+            max_iterations_number,
+            min_iterations_number,
+            threshold,
+            rng,
+            factor_scheduler,
+            variable_scheduler,
+            |info| {
+                total_iterations_number += info.total_iterations_number;
+                iterations_per_sample.push(info.total_iterations_number);
+                samples.push(info.samples);
+                decimation_orders.push(info.decimation_order);
+                ControlFlow::Continue(())
+            },
+        )?;
+        Ok(BatchSamplingInfo {
+            samples,
+            iterations_per_sample,
+            total_iterations_number,
+            log_weights: None,
+            energies: None,
+            decimation_orders: Some(decimation_orders),
+        })
+    }
+
+    /// Generates samples similarly to [`FactorGraph::sample_n`], but instead
+    /// of accumulating samples in memory, passes each sample to a sink as soon as
+    /// it is generated. A sink may block to slow down sampling and may stop it.
+    ///
+    /// # Arguments
+    ///
+    /// * `samples_number` - A maximal number of samples
+    /// * `max_iterations_number` - A maximal number of iterations per message passing
+    /// * `min_iterations_number` - A minimal number of iterations per message passing
+    /// * `threshold` - A threshold specifying the convergence criterion
+    /// * `rng` - A random numbers generator
+    /// * `factor_scheduler` - A scheduler of a factor's messages update rule hyper-parameters
+    /// * `variable_scheduler` - A scheduler of a variable's messages update rule hyper-parameters
+    /// * `sink` - A destination of samples, e.g. a channel, a closure or a `SampleWriter`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::sync::mpsc::sync_channel;
+    /// use gmrs::core::{FactorGraphBuilder, SampleWriter};
+    /// use gmrs::ising::{IsingFactor, IsingVariable, SumProduct, random_message_initializer};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use rand::thread_rng;
+    ///
+    /// // Aliases to shorten types
+    /// type Factor = IsingFactor<SumProduct>;
+    /// type Variable = IsingVariable<SumProduct>;
+    ///
+    /// // Messages initializer
+    /// let mut rng = thread_rng();
+    /// let mut initializer = random_message_initializer(rng.clone(), -0.5, 0.5);
+    ///
+    /// // Message passing schedulers
+    /// let factor_scheduler = get_standard_factor_scheduler(0.);
+    /// let variable_scheduler = get_standard_variable_scheduler(0.);
+    ///
+    /// // Factor graph
+    /// let mut fgb = FactorGraphBuilder::<Factor, Variable>::new_with_variables(vec![IsingVariable::new(); 2], 1);
+    /// fgb.add_factor(IsingFactor::new(0.5, 0.5, -0.5), &[0, 1], &mut initializer).unwrap();
+    /// let mut fg = fgb.build();
+    /// fg.run_message_passing_parallel(100, 0, 1e-10, &factor_scheduler, &variable_scheduler).unwrap();
+    ///
+    /// // Writing samples as lines
+    /// let mut writer = SampleWriter::new(Vec::new());
+    /// let info = fg.sample_to_sink(10, 100, 0, 1e-10, &mut rng, &factor_scheduler, &variable_scheduler, &mut writer).unwrap();
+    /// assert_eq!(info.samples_number, 10);
+    /// assert_eq!(String::from_utf8(writer.into_inner().unwrap()).unwrap().lines().count(), 10);
+    ///
+    /// // Streaming samples through a bounded channel to another thread
+    /// let (mut sender, receiver) = sync_channel(4);
+    /// std::thread::scope(|s| {
+    ///     let consumer = s.spawn(move || receiver.iter().count());
+    ///     fg.sample_to_sink(100, 100, 0, 1e-10, &mut rng, &factor_scheduler, &variable_scheduler, &mut sender).unwrap();
+    ///     drop(sender);
+    ///     assert_eq!(consumer.join().unwrap(), 100);
+    /// });
+    /// ```
+    #[allow(clippy::too_many_arguments)]
+    pub fn sample_to_sink(
+        &self,
+        samples_number: usize,
+        max_iterations_number: usize,
+        min_iterations_number: usize,
+        threshold: f64,
+        rng: &mut impl Rng,
+        factor_scheduler: &impl Fn(usize) -> F::Parameters,
+        variable_scheduler: &impl Fn(usize) -> V::Parameters,
+        sink: &mut impl SampleSink<V::Sample>,
+    ) -> FGResult<StreamingSamplingInfo> {
+        let mut consumed_samples_number = 0;
+        let mut total_iterations_number = 0;
+        let flow = self.sample_n_with(
+            samples_number,
+            max_iterations_number,
+            min_iterations_number,
+            threshold,
+            rng,
+            factor_scheduler,
+            variable_scheduler,
+            |info| {
+                total_iterations_number += info.total_iterations_number;
+                consumed_samples_number += 1;
+                sink.consume(info.samples)
+            },
+        )?;
+        Ok(StreamingSamplingInfo {
+            samples_number: consumed_samples_number,
+            total_iterations_number,
+            is_stopped_by_sink: flow.is_break(),
+        })
+    }
+
+    // Generates samples one by one passing sampling information
+    // of each sample to `consume`
+    #[allow(clippy::too_many_arguments)]
+    fn sample_n_with(
+        &self,
+        samples_number: usize,
+        max_iterations_number: usize,
+        min_iterations_number: usize,
+        threshold: f64,
+        rng: &mut impl Rng,
+        factor_scheduler: &impl Fn(usize) -> F::Parameters,
+        variable_scheduler: &impl Fn(usize) -> V::Parameters,
+        mut consume: impl FnMut(SamplingInfo<V::Sample>) -> ControlFlow<()>,
+    ) -> FGResult<ControlFlow<()>> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("sampling_batch", samples_number).entered();
+        #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+        for sample_index in 0..samples_number {
+            let mut fg = self.clone();
+            let info = fg.sample(
+                max_iterations_number,
+                min_iterations_number,
+                threshold,
+                rng,
+                factor_scheduler,
+                variable_scheduler,
+            )?;
+            #[cfg(feature = "tracing")]
+            tracing::info!(
+                samples_number = sample_index + 1,
+                total_iterations_number = info.total_iterations_number,
+                "sample drawn"
+            );
+            if consume(info).is_break() {
+                return Ok(ControlFlow::Break(()));
+            }
+        }
+        Ok(ControlFlow::Continue(()))
+    }
+
+    // Returns values of not yet sampled variables that belong to connected
+    // components (with sampled variables removed) where all variables are pinned
+    fn pinned_components(
+        &self,
+        samples: &[Option<V::Sample>],
+        tolerance: f64,
+    ) -> Vec<(usize, V::Sample)> {
+        let variables_number = self.variables.len();
+        let mut components = UnionFind::new(variables_number);
+        for factor in &self.factors {
+            let mut free_vars = factor
+                .var_node_indices
+                .iter()
+                .filter(|var_index| samples[**var_index].is_none());
+            if let Some(first) = free_vars.next() {
+                for var_index in free_vars {
+                    components.union(*first, *var_index);
+                }
+            }
+        }
+        let pinned: Vec<_> = self
+            .variables
+            .iter()
+            .zip(samples)
+            .map(|(var, sample)| {
+                if sample.is_none() {
+                    var.pinned(tolerance)
+                } else {
+                    None
+                }
+            })
+            .collect();
+        let mut is_component_pinned = vec![true; variables_number];
+        for (var_index, (sample, pinned_sample)) in samples.iter().zip(&pinned).enumerate() {
+            if sample.is_none() && pinned_sample.is_none() {
+                is_component_pinned[components.find(var_index)] = false;
+            }
+        }
+        pinned
+            .into_iter()
+            .enumerate()
+            .filter_map(|(var_index, pinned_sample)| {
+                pinned_sample
+                    .filter(|_| is_component_pinned[components.find(var_index)])
+                    .map(|sample| (var_index, sample))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,112 @@
+use std::{
+    fmt::Display,
+    io::Write,
+    ops::ControlFlow,
+    sync::mpsc::{Sender, SyncSender},
+};
+
+// ------------------------------------------------------------------------------------------
+
+/// A trait for destinations of samples produced one by one. A sink can apply
+/// backpressure by blocking in `consume` (e.g. a bounded channel or a file writer)
+/// and can stop sampling by returning `ControlFlow::Break`.
+pub trait SampleSink<S> {
+    /// Consumes a sample
+    ///
+    /// # Arguments
+    ///
+    /// * `sample` - A configuration of all variables
+    fn consume(&mut self, sample: Vec<S>) -> ControlFlow<()>;
+}
+
+impl<S, F> SampleSink<S> for F
+where
+    F: FnMut(Vec<S>) -> ControlFlow<()>,
+{
+    #[inline]
+    fn consume(&mut self, sample: Vec<S>) -> ControlFlow<()> {
+        self(sample)
+    }
+}
+
+/// Blocks while the channel is full, stops sampling when the receiver is dropped
+impl<S> SampleSink<S> for SyncSender<Vec<S>> {
+    #[inline]
+    fn consume(&mut self, sample: Vec<S>) -> ControlFlow<()> {
+        match self.send(sample) {
+            Ok(()) => ControlFlow::Continue(()),
+            Err(_) => ControlFlow::Break(()),
+        }
+    }
+}
+
+/// Stops sampling when the receiver is dropped
+impl<S> SampleSink<S> for Sender<Vec<S>> {
+    #[inline]
+    fn consume(&mut self, sample: Vec<S>) -> ControlFlow<()> {
+        match self.send(sample) {
+            Ok(()) => ControlFlow::Continue(()),
+            Err(_) => ControlFlow::Break(()),
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------
+
+/// A sink writing each sample as a line of whitespace separated values.
+/// Sampling stops at the first writing error, which can be retrieved
+/// by `SampleWriter::into_inner`. Wrap a writer into `std::io::BufWriter`
+/// in order to write samples in chunks.
+#[derive(Debug)]
+pub struct SampleWriter<W: Write> {
+    writer: W,
+    error: Option<std::io::Error>,
+}
+
+impl<W: Write> SampleWriter<W> {
+    /// Creates a new sample writer
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - A destination of samples
+    #[inline]
+    pub fn new(writer: W) -> Self {
+        SampleWriter {
+            writer,
+            error: None,
+        }
+    }
+
+    /// Flushes and returns the underlying writer or the first writing error
+    #[inline]
+    pub fn into_inner(mut self) -> std::io::Result<W> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl<S: Display, W: Write> SampleSink<S> for SampleWriter<W> {
+    #[inline]
+    fn consume(&mut self, sample: Vec<S>) -> ControlFlow<()> {
+        let mut write = || -> std::io::Result<()> {
+            let mut values = sample.iter();
+            if let Some(value) = values.next() {
+                write!(self.writer, "{}", value)?;
+            }
+            for value in values {
+                write!(self.writer, " {}", value)?;
+            }
+            writeln!(self.writer)
+        };
+        match write() {
+            Ok(()) => ControlFlow::Continue(()),
+            Err(error) => {
+                self.error = Some(error);
+                ControlFlow::Break(())
+            }
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use ndarray::{Array1, Array2};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::core::{factor::Factor, factor_graph::FactorGraph, variable::Variable};
+
+// ------------------------------------------------------------------------------------------
+
+/// A sparse matrix in the coordinate (triplet) format. Duplicate entries are
+/// summed up, which is the convention of most sparse linear algebra crates,
+/// e.g. the matrix can be converted into a `sprs` matrix by
+/// `TriMat::from_triplets(m.shape, m.row_indices, m.col_indices, m.values)`
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CooMatrix {
+    /// Number of rows and columns
+    pub shape: (usize, usize),
+
+    /// Row indices of non-zero entries
+    pub row_indices: Vec<usize>,
+
+    /// Column indices of non-zero entries
+    pub col_indices: Vec<usize>,
+
+    /// Values of non-zero entries
+    pub values: Vec<f64>,
+}
+
+impl CooMatrix {
+    /// Creates an empty matrix of a given shape
+    ///
+    /// # Arguments
+    ///
+    /// * `shape` - Number of rows and columns
+    #[inline]
+    pub fn new(shape: (usize, usize)) -> Self {
+        CooMatrix {
+            shape,
+            row_indices: Vec::new(),
+            col_indices: Vec::new(),
+            values: Vec::new(),
+        }
+    }
+
+    /// Returns number of stored entries
+    #[inline]
+    pub fn nnz(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Converts a matrix to the dense form
+    pub fn to_dense(&self) -> Array2<f64> {
+        let mut dense = Array2::zeros(self.shape);
+        let entries = self
+            .row_indices
+            .iter()
+            .zip(&self.col_indices)
+            .zip(&self.values);
+        for ((row, col), value) in entries {
+            dense[[*row, *col]] += value;
+        }
+        dense
+    }
+
+    /// Computes a matrix-vector product
+    ///
+    /// # Arguments
+    ///
+    /// * `vector` - A vector whose size is equal to the number of columns
+    pub fn dot(&self, vector: &Array1<f64>) -> Array1<f64> {
+        assert_eq!(
+            vector.len(),
+            self.shape.1,
+            "Vector size must be equal to the number of columns"
+        );
+        let mut result = Array1::zeros(self.shape.0);
+        let entries = self
+            .row_indices
+            .iter()
+            .zip(&self.col_indices)
+            .zip(&self.values);
+        for ((row, col), value) in entries {
+            result[*row] += value * vector[*col];
+        }
+        result
+    }
+
+    /// Computes an eigenvalue with the largest magnitude and the corresponding
+    /// eigenvector of a square matrix by the power method. Returns None if the
+    /// method has not converged, e.g. when the eigenvalue with the largest magnitude
+    /// is not unique or complex
+    ///
+    /// # Arguments
+    ///
+    /// * `max_iterations_number` - A maximal number of power iterations
+    /// * `threshold` - A threshold for the residual norm `|Ax - lambda x|` of a normalized eigenvector
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::CooMatrix;
+    ///
+    /// let mut matrix = CooMatrix::new((2, 2));
+    /// matrix.row_indices = vec![0, 0, 1, 1];
+    /// matrix.col_indices = vec![0, 1, 0, 1];
+    /// matrix.values = vec![2., 1., 1., 2.];
+    /// let (eigenvalue, eigenvector) = matrix.leading_eigenpair(1000, 1e-10).unwrap();
+    /// assert!((eigenvalue - 3.).abs() < 1e-8);
+    /// assert!((eigenvector[0] - eigenvector[1]).abs() < 1e-8);
+    /// ```
+    pub fn leading_eigenpair(
+        &self,
+        max_iterations_number: usize,
+        threshold: f64,
+    ) -> Option<(f64, Array1<f64>)> {
+        assert_eq!(self.shape.0, self.shape.1, "Matrix must be square");
+        if self.shape.0 == 0 {
+            return None;
+        }
+        // a deterministic starting vector that is unlikely to be orthogonal to an eigenvector
+        let mut vector: Array1<f64> = (0..self.shape.0)
+            .map(|i| 1f64 + (i as f64 * 0.618034).fract())
+            .collect();
+        vector /= vector.dot(&vector).sqrt();
+        for _ in 0..max_iterations_number {
+            let product = self.dot(&vector);
+            let eigenvalue = vector.dot(&product);
+            let residual = &product - &(eigenvalue * &vector);
+            if residual.dot(&residual).sqrt() < threshold {
+                return Some((eigenvalue, vector));
+            }
+            let norm = product.dot(&product).sqrt();
+            if norm == 0f64 {
+                return Some((0f64, vector));
+            }
+            vector = product / norm;
+        }
+        None
+    }
+
+    #[inline]
+    pub(crate) fn push(&mut self, row: usize, col: usize, value: f64) {
+        self.row_indices.push(row);
+        self.col_indices.push(col);
+        self.values.push(value);
+    }
+}
+
+impl<F, V> FactorGraph<F, V>
+where
+    F: Factor,
+    V: Variable<Message = F::Message>,
+{
+    /// Returns the factor-variable incidence matrix of shape
+    /// (number of factors, number of variables) having unit entries
+    /// for each pair of a factor and an adjoint variable
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let mut fgb = new_ising_builder::<SumProduct>(3, 2);
+    /// fgb.add_factor(IsingFactor::new(0.5, 0., 0.), &[0, 1], &mut initializer).unwrap();
+    /// fgb.add_factor(IsingFactor::new(0.5, 0., 0.), &[1, 2], &mut initializer).unwrap();
+    /// let fg = fgb.build();
+    /// let incidence = fg.incidence_matrix();
+    /// assert_eq!(incidence.shape, (2, 3));
+    /// assert_eq!(incidence.nnz(), 4);
+    /// ```
+    pub fn incidence_matrix(&self) -> CooMatrix {
+        let mut incidence = CooMatrix::new((self.factors.len(), self.variables.len()));
+        for (fac_index, factor) in self.factors.iter().enumerate() {
+            for var_index in &factor.var_node_indices {
+                incidence.push(fac_index, *var_index, 1f64);
+            }
+        }
+        incidence
+    }
+}
@@ -0,0 +1,170 @@
+use std::ops::ControlFlow;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::core::{
+    factor::Factor,
+    factor_graph::{FGResult, FactorGraph, MessagePassingInfo},
+    factor_node::FactorNode,
+    variable::Variable,
+    variable_node::VariableNode,
+};
+
+// ------------------------------------------------------------------------------------------
+
+/// A marginal of a variable emitted by [`FactorGraph::run_message_passing_parallel_streaming`]
+/// once it has stabilized
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct StreamedMarginal<M> {
+    /// Index of a variable
+    pub variable: usize,
+
+    /// Iteration number (starts from 0) at which a marginal has been emitted
+    pub iteration: usize,
+
+    /// A marginal of a variable
+    pub marginal: M,
+}
+
+impl<F, V> FactorGraph<F, V>
+where
+    F: Factor,
+    V: Variable<Message = F::Message>,
+{
+    /// Runs a message passing algorithm in parallel similarly to
+    /// [`FactorGraph::run_message_passing_parallel`], additionally emitting the marginal
+    /// of a variable as soon as its local neighborhood converges, i.e. the marginal
+    /// changes by less than a tolerance for a number of consecutive iterations. Thus
+    /// consumers can start downstream processing of easy variables while a hard region
+    /// keeps iterating. Each variable is emitted once, marginals of variables not emitted
+    /// before convergence are emitted after it
+    ///
+    /// # Arguments
+    ///
+    /// * `max_iterations_number` - A maximal number of iterations, if a process
+    ///   does not converge before reaching this number of iterations, it fails
+    /// * `min_iterations_number` - A minimal number of iterations that is performed
+    ///   disregards reaching the convergence criterion, marginals are not emitted before it
+    /// * `threshold` - A threshold specifying the convergence criterion
+    /// * `marginal_tolerance` - A maximal change of a marginal between two subsequent
+    ///   iterations for a marginal to be considered as stable
+    /// * `stability_window` - A number of consecutive iterations a marginal must stay
+    ///   stable to be emitted, zero is treated as one
+    /// * `factor_scheduler` - A scheduler of a factor's messages update rule hyper-parameters
+    /// * `variable_scheduler` - A scheduler of a variable's messages update rule hyper-parameters
+    /// * `emit` - A function called with each emitted marginal
+    ///
+    /// # Notes
+    ///
+    /// An emitted marginal is an approximation of the final one, it could still change
+    /// by small amounts while a neighboring hard region converges. Changes of marginals
+    /// are computed by [`Variable::marginal_discrepancy`], variables that do not implement
+    /// it are emitted after convergence only. If message passing fails, not emitted
+    /// variables are not emitted at all
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, IsingFactor, IsingMessage, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    ///
+    /// // A strongly coupled chain next to a free spin with a field
+    /// let mut fgb = new_ising_builder::<SumProduct>(11, 10);
+    /// for i in 0..9 {
+    ///     fgb.add_factor(IsingFactor::new(1.5, 0.01, 0.), &[i, i + 1], &mut || IsingMessage(0.)).unwrap();
+    /// }
+    /// fgb.add_factor(IsingFactor::new(0., 0., 0.5), &[0, 10], &mut || IsingMessage(0.)).unwrap();
+    /// let mut fg = fgb.build();
+    /// let mut emitted = Vec::new();
+    /// let info = fg.run_message_passing_parallel_streaming(
+    ///     1000,
+    ///     0,
+    ///     1e-10,
+    ///     1e-10,
+    ///     2,
+    ///     &get_standard_factor_scheduler(0.),
+    ///     &get_standard_variable_scheduler(0.),
+    ///     |streamed| emitted.push(streamed),
+    /// ).unwrap();
+    /// assert_eq!(emitted.len(), 11);
+    /// // The free spin is emitted first, long before convergence
+    /// assert_eq!(emitted[0].variable, 10);
+    /// assert!(emitted[0].iteration < info.iterations_number);
+    /// ```
+    #[allow(clippy::too_many_arguments)]
+    pub fn run_message_passing_parallel_streaming(
+        &mut self,
+        max_iterations_number: usize,
+        min_iterations_number: usize,
+        threshold: f64,
+        marginal_tolerance: f64,
+        stability_window: usize,
+        factor_scheduler: &impl Fn(usize) -> F::Parameters,
+        variable_scheduler: &impl Fn(usize) -> V::Parameters,
+        mut emit: impl FnMut(StreamedMarginal<V::Marginal>),
+    ) -> FGResult<MessagePassingInfo> {
+        let variables_number = self.variables.len();
+        let stability_window = stability_window.max(1);
+        let mut previous_marginals: Vec<Option<V::Marginal>> = vec![None; variables_number];
+        let mut stable_iterations = vec![0; variables_number];
+        let mut is_emitted = vec![false; variables_number];
+        let result = self.run_message_passing_with_hook(
+            max_iterations_number,
+            min_iterations_number,
+            threshold,
+            1,
+            factor_scheduler,
+            variable_scheduler,
+            &FactorNode::eval_messages,
+            &VariableNode::eval_messages,
+            |fg, iteration, _| {
+                let nodes = fg.variables.iter().zip(&mut previous_marginals);
+                for (var_index, (node, previous)) in nodes.enumerate() {
+                    if is_emitted[var_index] {
+                        continue;
+                    }
+                    let marginal = node.marginal();
+                    let change = previous
+                        .as_ref()
+                        .and_then(|previous| {
+                            node.variable.marginal_discrepancy(&marginal, previous)
+                        })
+                        .unwrap_or(f64::INFINITY);
+                    if change < marginal_tolerance {
+                        stable_iterations[var_index] += 1;
+                    } else {
+                        stable_iterations[var_index] = 0;
+                    }
+                    if stable_iterations[var_index] >= stability_window
+                        && iteration + 1 >= min_iterations_number
+                    {
+                        is_emitted[var_index] = true;
+                        *previous = None;
+                        emit(StreamedMarginal {
+                            variable: var_index,
+                            iteration,
+                            marginal,
+                        });
+                    } else {
+                        *previous = Some(marginal);
+                    }
+                }
+                ControlFlow::Continue(())
+            },
+        );
+        if let Ok(info) = &result {
+            for (var_index, node) in self.variables.iter().enumerate() {
+                if !is_emitted[var_index] {
+                    emit(StreamedMarginal {
+                        variable: var_index,
+                        iteration: info.iterations_number,
+                        marginal: node.marginal(),
+                    });
+                }
+            }
+        }
+        result
+    }
+}
@@ -0,0 +1,275 @@
+use std::collections::VecDeque;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::core::{factor::Factor, factor_graph::FactorGraph, variable::Variable};
+
+// ------------------------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// A guarantee on a converged message passing that follows from the topology
+/// of a factor graph
+pub enum ExactnessCertificate {
+    /// Each connected component of a factor graph is a tree. Sum-product marginals
+    /// and max-product assignments are exact
+    Tree,
+
+    /// Each connected component of a factor graph has at most one cycle.
+    /// An assignment maximizing max-product beliefs is optimal provided that
+    /// the beliefs have unique maxima. Sum-product converges, but its marginals
+    /// are not exact in general
+    SingleCycle,
+}
+
+/// Numbers of short cycles of the bipartite graph of variables and factors
+/// passing through each node
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CycleCounts {
+    /// Numbers of cycles passing through each variable
+    pub variables: Vec<usize>,
+
+    /// Numbers of cycles passing through each factor
+    pub factors: Vec<usize>,
+}
+
+// ------------------------------------------------------------------------------------------
+
+// Disjoint sets of nodes with path compression and union by size
+#[derive(Debug, Clone)]
+pub(crate) struct UnionFind {
+    parents: Vec<usize>,
+    sizes: Vec<usize>,
+}
+
+impl UnionFind {
+    #[inline]
+    pub(crate) fn new(size: usize) -> Self {
+        UnionFind {
+            parents: (0..size).collect(),
+            sizes: vec![1; size],
+        }
+    }
+
+    #[inline]
+    pub(crate) fn find(&mut self, mut node: usize) -> usize {
+        let mut root = node;
+        while self.parents[root] != root {
+            root = self.parents[root];
+        }
+        while self.parents[node] != root {
+            let next = self.parents[node];
+            self.parents[node] = root;
+            node = next;
+        }
+        root
+    }
+
+    // Returns false if nodes are already in the same set
+    #[inline]
+    pub(crate) fn union(&mut self, lhs: usize, rhs: usize) -> bool {
+        let (mut lhs, mut rhs) = (self.find(lhs), self.find(rhs));
+        if lhs == rhs {
+            return false;
+        }
+        if self.sizes[lhs] < self.sizes[rhs] {
+            std::mem::swap(&mut lhs, &mut rhs);
+        }
+        self.parents[rhs] = lhs;
+        self.sizes[lhs] += self.sizes[rhs];
+        true
+    }
+}
+
+// Returns the maximal number of independent cycles across connected components
+// of a bipartite graph given by its edges between left and right nodes.
+// Repeated edges are counted once, as in `has_cycles`
+#[inline]
+pub(crate) fn max_cycles_per_component(
+    left_nodes_number: usize,
+    right_nodes_number: usize,
+    edges: impl Iterator<Item = (usize, usize)>,
+) -> usize {
+    let mut edges: Vec<_> = edges.collect();
+    edges.sort_unstable();
+    edges.dedup();
+    let mut union_find = UnionFind::new(left_nodes_number + right_nodes_number);
+    let mut redundant_edges = Vec::new();
+    for (left, right) in edges {
+        if !union_find.union(left, left_nodes_number + right) {
+            redundant_edges.push(left);
+        }
+    }
+    let mut cycles = vec![0usize; left_nodes_number + right_nodes_number];
+    for left in redundant_edges {
+        let root = union_find.find(left);
+        cycles[root] += 1;
+    }
+    cycles.into_iter().max().unwrap_or(0)
+}
+
+impl<F, V> FactorGraph<F, V>
+where
+    F: Factor,
+    V: Variable<Message = F::Message>,
+{
+    /// Returns true if the bipartite graph of variables and factors has a cycle,
+    /// i.e. message passing is not guaranteed to be exact
+    ///
+    /// # Notes
+    ///
+    /// A factor adjoint to the same variable several times does not form a cycle
+    #[inline]
+    pub fn has_cycles(&self) -> bool {
+        let adjacency = self.bipartite_adjacency();
+        let mut union_find = UnionFind::new(adjacency.len());
+        let variables_number = self.variables.len();
+        adjacency[..variables_number]
+            .iter()
+            .enumerate()
+            .flat_map(|(var_index, neighbors)| neighbors.iter().map(move |node| (var_index, *node)))
+            .any(|(var_node, fac_node)| !union_find.union(var_node, fac_node))
+    }
+
+    /// Returns the girth of a factor graph, i.e. the length of the shortest cycle
+    /// of the bipartite graph of variables and factors, or None if there are no cycles.
+    /// A cycle through `k` variables and `k` factors has length `2k`, e.g. two spins
+    /// coupled by two different factors form a cycle of length 4
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// // A ring of 5 spins with a tail
+    /// let mut fgb = new_ising_builder::<SumProduct>(6, 6);
+    /// for i in 0..5 {
+    ///     fgb.add_factor(IsingFactor::new(0.5, 0., 0.), &[i, (i + 1) % 5], &mut initializer).unwrap();
+    /// }
+    /// fgb.add_factor(IsingFactor::new(0.5, 0., 0.), &[0, 5], &mut initializer).unwrap();
+    /// let fg = fgb.build();
+    /// assert!(fg.has_cycles());
+    /// assert_eq!(fg.girth(), Some(10));
+    /// let counts = fg.short_cycles(10);
+    /// assert_eq!(counts.variables, vec![1, 1, 1, 1, 1, 0]);
+    /// assert_eq!(counts.factors, vec![1, 1, 1, 1, 1, 0]);
+    /// assert_eq!(fg.short_cycles(8).variables, vec![0; 6]);
+    /// ```
+    pub fn girth(&self) -> Option<usize> {
+        let adjacency = self.bipartite_adjacency();
+        let mut distances = vec![usize::MAX; adjacency.len()];
+        let mut parents = vec![usize::MAX; adjacency.len()];
+        let mut visited = Vec::new();
+        let mut girth = usize::MAX;
+        for source in 0..adjacency.len() {
+            for node in visited.drain(..) {
+                distances[node] = usize::MAX;
+            }
+            distances[source] = 0;
+            parents[source] = usize::MAX;
+            visited.push(source);
+            let mut queue = VecDeque::from([source]);
+            while let Some(node) = queue.pop_front() {
+                // cycles found from farther nodes can not improve the girth
+                if 2 * distances[node] >= girth {
+                    break;
+                }
+                for next in &adjacency[node] {
+                    if distances[*next] == usize::MAX {
+                        distances[*next] = distances[node] + 1;
+                        parents[*next] = node;
+                        visited.push(*next);
+                        queue.push_back(*next);
+                    } else if parents[node] != *next {
+                        girth = girth.min(distances[node] + distances[*next] + 1);
+                    }
+                }
+            }
+        }
+        (girth != usize::MAX).then_some(girth)
+    }
+
+    /// Counts cycles of the bipartite graph of variables and factors whose length
+    /// does not exceed a given one passing through each node. Short cycles are the
+    /// main source of inaccuracies of message passing
+    ///
+    /// # Arguments
+    ///
+    /// * `max_length` - A maximal length of a cycle, the number of cycles grows
+    ///   exponentially with it, thus it should be small (e.g. 4, 6 or 8)
+    pub fn short_cycles(&self, max_length: usize) -> CycleCounts {
+        let adjacency = self.bipartite_adjacency();
+        let mut counts = vec![0usize; adjacency.len()];
+        let mut path = Vec::with_capacity(max_length);
+        let mut on_path = vec![false; adjacency.len()];
+        // each cycle is enumerated from its smallest node
+        // in the direction given by the order of its end nodes
+        for source in 0..adjacency.len() {
+            path.push(source);
+            on_path[source] = true;
+            count_cycles(&adjacency, max_length, &mut path, &mut on_path, &mut counts);
+            on_path[source] = false;
+            path.pop();
+        }
+        let factors = counts.split_off(self.variables.len());
+        CycleCounts {
+            variables: counts,
+            factors,
+        }
+    }
+
+    // Returns sorted neighbors without repetitions of each node of the bipartite
+    // graph of variables and factors, variables precede factors
+    fn bipartite_adjacency(&self) -> Vec<Vec<usize>> {
+        let variables_number = self.variables.len();
+        let variables = self.variables.iter().map(|variable| {
+            variable
+                .fac_node_indices
+                .iter()
+                .map(|fac_index| variables_number + fac_index)
+                .collect::<Vec<_>>()
+        });
+        let factors = self
+            .factors
+            .iter()
+            .map(|factor| factor.var_node_indices.clone());
+        variables
+            .chain(factors)
+            .map(|mut neighbors| {
+                neighbors.sort_unstable();
+                neighbors.dedup();
+                neighbors
+            })
+            .collect()
+    }
+}
+
+// Extends a path starting from its smallest node by larger nodes and counts
+// cycles closing it for each node on it
+fn count_cycles(
+    adjacency: &[Vec<usize>],
+    max_length: usize,
+    path: &mut Vec<usize>,
+    on_path: &mut [bool],
+    counts: &mut [usize],
+) {
+    let source = path[0];
+    let last = *path.last().unwrap();
+    for next in &adjacency[last] {
+        if *next == source && path.len() > 2 && path[1] < last {
+            for node in path.iter() {
+                counts[*node] += 1;
+            }
+        } else if *next > source && !on_path[*next] && path.len() < max_length {
+            path.push(*next);
+            on_path[*next] = true;
+            count_cycles(adjacency, max_length, path, on_path, counts);
+            on_path[*next] = false;
+            path.pop();
+        }
+    }
+}
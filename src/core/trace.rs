@@ -0,0 +1,234 @@
+use std::{io::Write, ops::ControlFlow};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::core::{
+    factor::Factor,
+    factor_graph::{FGError, FGResult, FactorGraph, MessagePassingInfo},
+    factor_node::FactorNode,
+    message::{DampableMessage, Message},
+    variable::Variable,
+    variable_node::VariableNode,
+};
+
+// ------------------------------------------------------------------------------------------
+
+/// Messages of selected edges recorded after each iteration of message passing, it helps
+/// to find edges whose messages oscillate or diverge. A trace is filled by
+/// [`FactorGraph::run_message_passing_parallel_traced`] and could be dumped by
+/// [`MessageTrace::write`]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MessageTrace<M> {
+    /// Traced edges as pairs (a factor index, a variable index)
+    pub edges: Vec<(usize, usize)>,
+
+    /// Iteration numbers (start from 0 in each run) at which messages have been recorded
+    pub iterations: Vec<usize>,
+
+    /// Messages sent by factors to variables, one vector of messages of all traced edges
+    /// per recorded iteration
+    pub factor_messages: Vec<Vec<M>>,
+
+    /// Messages sent by variables to factors, one vector of messages of all traced edges
+    /// per recorded iteration
+    pub variable_messages: Vec<Vec<M>>,
+}
+
+impl<M: Message> MessageTrace<M> {
+    /// Creates an empty trace of given edges
+    ///
+    /// # Arguments
+    ///
+    /// * `edges` - Edges to trace as pairs (a factor index, a variable index)
+    #[inline]
+    pub fn new(edges: Vec<(usize, usize)>) -> Self {
+        MessageTrace {
+            edges,
+            iterations: Vec::new(),
+            factor_messages: Vec::new(),
+            variable_messages: Vec::new(),
+        }
+    }
+
+    /// Creates an empty trace of all edges of a factor graph
+    ///
+    /// # Arguments
+    ///
+    /// * `factor_graph` - A factor graph
+    #[inline]
+    pub fn all<F, V>(factor_graph: &FactorGraph<F, V>) -> Self
+    where
+        F: Factor<Message = M>,
+        V: Variable<Message = M>,
+    {
+        Self::new(factor_graph.edges().collect())
+    }
+
+    /// Writes a trace as tab separated values with the header
+    /// `iteration factor variable factor_message variable_message`,
+    /// one line per edge and iteration, messages are written in their debug representation
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - A destination of a trace, e.g. a file
+    pub fn write(&self, mut writer: impl Write) -> std::io::Result<()> {
+        writeln!(
+            writer,
+            "iteration\tfactor\tvariable\tfactor_message\tvariable_message"
+        )?;
+        let records = self
+            .iterations
+            .iter()
+            .zip(&self.factor_messages)
+            .zip(&self.variable_messages);
+        for ((iteration, factor_messages), variable_messages) in records {
+            let messages = self
+                .edges
+                .iter()
+                .zip(factor_messages)
+                .zip(variable_messages);
+            for (((fac_index, var_index), factor_message), variable_message) in messages {
+                writeln!(
+                    writer,
+                    "{}\t{}\t{}\t{:?}\t{:?}",
+                    iteration, fac_index, var_index, factor_message, variable_message,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<M: DampableMessage> MessageTrace<M> {
+    /// Returns traced edges whose factor to variable messages have oscillated, i.e.
+    /// changed the direction of their change, at least at a given number of iterations
+    ///
+    /// # Arguments
+    ///
+    /// * `min_oscillations_number` - A minimal number of oscillations of an edge
+    pub fn oscillating_edges(&self, min_oscillations_number: usize) -> Vec<(usize, usize)> {
+        (0..self.edges.len())
+            .filter(|edge| {
+                let oscillations_number = self
+                    .factor_messages
+                    .windows(3)
+                    .filter(|w| w[2][*edge].oscillates(&w[1][*edge], &w[0][*edge]))
+                    .count();
+                oscillations_number >= min_oscillations_number.max(1)
+            })
+            .map(|edge| self.edges[edge])
+            .collect()
+    }
+}
+
+impl<F, V> FactorGraph<F, V>
+where
+    F: Factor,
+    V: Variable<Message = F::Message>,
+{
+    /// Runs a message passing algorithm in parallel similarly to
+    /// [`FactorGraph::run_message_passing_parallel`], additionally appending messages
+    /// of traced edges to a trace after each iteration. A trace is filled whether message
+    /// passing converges or not, thus it shows dynamics of messages of a diverging run
+    ///
+    /// # Arguments
+    ///
+    /// * `max_iterations_number` - A maximal number of iterations, if a process
+    ///   does not converge before reaching this number of iterations, it fails
+    /// * `min_iterations_number` - A minimal number of iterations that is performed
+    ///   disregards reaching the convergence criterion
+    /// * `threshold` - A threshold specifying the convergence criterion
+    /// * `factor_scheduler` - A scheduler of a factor's messages update rule hyper-parameters
+    /// * `variable_scheduler` - A scheduler of a variable's messages update rule hyper-parameters
+    /// * `trace` - A trace messages are appended to
+    ///
+    /// # Notes
+    ///
+    /// It returns `FGError::UnknownEdge` before running message passing if a traced edge
+    /// does not exist. Tracing all edges of a large graph takes a lot of memory, since
+    /// all messages are copied at each iteration
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::MessageTrace;
+    /// use gmrs::ising::{new_ising_builder, IsingFactor, IsingMessage, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    ///
+    /// // A frustrated triangle without damping
+    /// let mut fgb = new_ising_builder::<SumProduct>(3, 3);
+    /// for (i, j) in [(0, 1), (1, 2), (2, 0)] {
+    ///     fgb.add_factor(IsingFactor::new(-3., 0., 0.), &[i, j], &mut || IsingMessage(0.5)).unwrap();
+    /// }
+    /// let mut fg = fgb.build();
+    /// let mut trace = MessageTrace::all(&fg);
+    /// let result = fg.run_message_passing_parallel_traced(
+    ///     10,
+    ///     0,
+    ///     1e-10,
+    ///     &get_standard_factor_scheduler(0.),
+    ///     &get_standard_variable_scheduler(0.),
+    ///     &mut trace,
+    /// );
+    /// assert!(result.is_err());
+    /// assert_eq!(trace.iterations.len(), 10);
+    /// assert_eq!(trace.oscillating_edges(5).len(), 6);
+    ///
+    /// let mut buffer = Vec::new();
+    /// trace.write(&mut buffer).unwrap();
+    /// assert_eq!(String::from_utf8(buffer).unwrap().lines().count(), 1 + 10 * 6);
+    /// ```
+    pub fn run_message_passing_parallel_traced(
+        &mut self,
+        max_iterations_number: usize,
+        min_iterations_number: usize,
+        threshold: f64,
+        factor_scheduler: &impl Fn(usize) -> F::Parameters,
+        variable_scheduler: &impl Fn(usize) -> V::Parameters,
+        trace: &mut MessageTrace<F::Message>,
+    ) -> FGResult<MessagePassingInfo> {
+        let positions = trace
+            .edges
+            .iter()
+            .map(|(fac_index, var_index)| {
+                self.factors
+                    .get(*fac_index)
+                    .and_then(|factor| {
+                        factor
+                            .var_node_indices
+                            .iter()
+                            .position(|index| index == var_index)
+                    })
+                    .ok_or(FGError::UnknownEdge(*fac_index, *var_index))
+            })
+            .collect::<FGResult<Vec<_>>>()?;
+        self.run_message_passing_with_hook(
+            max_iterations_number,
+            min_iterations_number,
+            threshold,
+            1,
+            factor_scheduler,
+            variable_scheduler,
+            &FactorNode::eval_messages,
+            &VariableNode::eval_messages,
+            |fg, iteration, _| {
+                let traced = trace.edges.iter().zip(&positions);
+                let (factor_messages, variable_messages) = traced
+                    .map(|((fac_index, _), position)| {
+                        let factor = &fg.factors[*fac_index];
+                        (
+                            factor.messages[*position].clone(),
+                            factor.receivers[*position].clone(),
+                        )
+                    })
+                    .unzip();
+                trace.iterations.push(iteration);
+                trace.factor_messages.push(factor_messages);
+                trace.variable_messages.push(variable_messages);
+                ControlFlow::Continue(())
+            },
+        )
+    }
+}
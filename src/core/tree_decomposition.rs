@@ -0,0 +1,161 @@
+use std::collections::BTreeSet;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::core::{factor::Factor, factor_graph::FactorGraph, variable::Variable};
+
+// ------------------------------------------------------------------------------------------
+
+/// A greedy heuristic choosing the next variable to eliminate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum EliminationHeuristic {
+    /// Eliminates a variable with the smallest number of neighbours
+    MinDegree,
+
+    /// Eliminates a variable whose elimination adds the smallest number
+    /// of edges between its neighbours, it is slower but usually gives
+    /// narrower decompositions
+    MinFill,
+}
+
+/// An approximate tree decomposition of the interaction graph of a factor graph,
+/// where two variables are adjacent if they share a factor. It is built from
+/// an elimination order, each bag contains an eliminated variable and its
+/// neighbours at the moment of elimination
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TreeDecomposition {
+    /// Variables in the order of elimination
+    pub elimination_order: Vec<usize>,
+
+    /// Sorted variables of each bag, the i-th bag corresponds
+    /// to the i-th eliminated variable
+    pub bags: Vec<Vec<usize>>,
+
+    /// A parent of each bag, bags of different connected components
+    /// form different trees whose roots have no parents
+    pub parents: Vec<Option<usize>>,
+
+    /// An estimated treewidth, i.e. the size of the largest bag minus one.
+    /// It is an upper bound of the exact treewidth
+    pub treewidth: usize,
+}
+
+impl<F, V> FactorGraph<F, V>
+where
+    F: Factor,
+    V: Variable<Message = F::Message>,
+{
+    /// Computes an approximate tree decomposition by greedy variable elimination.
+    /// The estimated treewidth tells whether exact inference (junction tree, bucket
+    /// elimination) is feasible, since its cost grows exponentially with the treewidth
+    ///
+    /// # Arguments
+    ///
+    /// * `heuristic` - A heuristic choosing the next variable to eliminate,
+    ///   ties are resolved in favour of the smallest variable index
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::EliminationHeuristic;
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// // A ring of 6 spins
+    /// let mut fgb = new_ising_builder::<SumProduct>(6, 6);
+    /// for i in 0..6 {
+    ///     fgb.add_factor(IsingFactor::new(0.5, 0., 0.), &[i, (i + 1) % 6], &mut initializer).unwrap();
+    /// }
+    /// let fg = fgb.build();
+    /// let decomposition = fg.tree_decomposition(EliminationHeuristic::MinFill);
+    /// assert_eq!(decomposition.treewidth, 2);
+    /// assert_eq!(decomposition.bags.len(), 6);
+    /// assert_eq!(decomposition.parents.iter().filter(|parent| parent.is_none()).count(), 1);
+    /// ```
+    pub fn tree_decomposition(&self, heuristic: EliminationHeuristic) -> TreeDecomposition {
+        let variables_number = self.variables.len();
+        let mut adjacency = vec![BTreeSet::new(); variables_number];
+        for factor in &self.factors {
+            for lhs in &factor.var_node_indices {
+                for rhs in &factor.var_node_indices {
+                    if lhs != rhs {
+                        adjacency[*lhs].insert(*rhs);
+                    }
+                }
+            }
+        }
+        let mut eliminated = vec![false; variables_number];
+        let mut elimination_order = Vec::with_capacity(variables_number);
+        let mut bags = Vec::with_capacity(variables_number);
+        for _ in 0..variables_number {
+            let var_index = (0..variables_number)
+                .filter(|var_index| !eliminated[*var_index])
+                .min_by_key(|var_index| match heuristic {
+                    EliminationHeuristic::MinDegree => adjacency[*var_index].len(),
+                    EliminationHeuristic::MinFill => fill_in(&adjacency, *var_index),
+                })
+                .expect("A variable is left to eliminate. This is a bug, please make an issue.");
+            let neighbors = std::mem::take(&mut adjacency[var_index]);
+            for lhs in &neighbors {
+                adjacency[*lhs].remove(&var_index);
+                for rhs in &neighbors {
+                    if lhs != rhs {
+                        adjacency[*lhs].insert(*rhs);
+                    }
+                }
+            }
+            eliminated[var_index] = true;
+            elimination_order.push(var_index);
+            let mut bag: Vec<_> = neighbors.into_iter().collect();
+            bag.push(var_index);
+            bag.sort_unstable();
+            bags.push(bag);
+        }
+        // a bag is attached to the bag of its neighbour eliminated first,
+        // which contains all other neighbours
+        let mut positions = vec![0; variables_number];
+        for (position, var_index) in elimination_order.iter().enumerate() {
+            positions[*var_index] = position;
+        }
+        let parents = bags
+            .iter()
+            .enumerate()
+            .map(|(position, bag)| {
+                bag.iter()
+                    .map(|var_index| positions[*var_index])
+                    .filter(|other| *other > position)
+                    .min()
+            })
+            .collect();
+        let treewidth = bags
+            .iter()
+            .map(Vec::len)
+            .max()
+            .unwrap_or(1)
+            .saturating_sub(1);
+        TreeDecomposition {
+            elimination_order,
+            bags,
+            parents,
+            treewidth,
+        }
+    }
+}
+
+// Number of edges missing between neighbours of a variable
+fn fill_in(adjacency: &[BTreeSet<usize>], var_index: usize) -> usize {
+    let neighbors = &adjacency[var_index];
+    neighbors
+        .iter()
+        .map(|lhs| {
+            neighbors
+                .iter()
+                .filter(|rhs| *rhs > lhs && !adjacency[*lhs].contains(*rhs))
+                .count()
+        })
+        .sum()
+}
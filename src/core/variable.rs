@@ -10,7 +10,7 @@ pub trait Variable: Clone + Debug + Send {
     /// Message passing hyper parameters
     type Parameters: Sync;
     /// Type representing a marginal distribution
-    type Marginal;
+    type Marginal: Clone + Debug + Send + Sync;
     /// Type representing a variable sample
     type Sample: Copy;
 
@@ -54,6 +54,40 @@ pub trait Variable: Clone + Debug + Send {
     /// * `rng` - A random numbers generator
     fn sample(&self, messages: &[Self::Message], rng: &mut impl Rng) -> Self::Sample;
 
+    /// Returns the value of a variable if its marginal distribution
+    /// is concentrated on this value up to a given tolerance
+    ///
+    /// # Arguments
+    ///
+    /// * `messages` - Messages received from adjoint factors previously
+    /// * `tolerance` - A maximal total probability of all other values
+    ///
+    /// # Notes
+    ///
+    /// The default implementation never reports a variable as pinned,
+    /// which disables shortcuts relying on this method during sampling
+    fn pinned(&self, messages: &[Self::Message], tolerance: f64) -> Option<Self::Sample> {
+        let _ = (messages, tolerance);
+        None
+    }
+
+    /// Returns the difference between probabilities of the most probable value of
+    /// a variable and the second most probable one, e.g. `|p_up - p_down|` for a spin.
+    /// It measures how confident a model is about a variable
+    ///
+    /// # Arguments
+    ///
+    /// * `messages` - Messages received from adjoint factors previously
+    ///
+    /// # Notes
+    ///
+    /// The default implementation returns None meaning that a variable does not
+    /// report its confidence, such variables are skipped by rankings
+    fn marginal_gap(&self, messages: &[Self::Message]) -> Option<f64> {
+        let _ = messages;
+        None
+    }
+
     /// Returns a message that sets a variable to the state corresponding to
     /// a given sample
     ///
@@ -70,4 +104,137 @@ pub trait Variable: Clone + Debug + Send {
     /// calling the given method, (2) one creates the factor that produces
     /// a created message by calling a `from_message` method
     fn sample_to_message(sample: &Self::Sample) -> Self::Message;
+
+    /// Returns a message that assigns a given probability to the state
+    /// corresponding to a given sample, the rest of the probability is
+    /// distributed uniformly among other states
+    ///
+    /// # Arguments
+    ///
+    /// * `sample` - A sample to convert to a message
+    /// * `probability` - A probability of the sample, it lies in (0, 1)
+    ///
+    /// # Notes
+    ///
+    /// Similarly to `sample_to_message` the returned message is used to create
+    /// a factor that softly pins a variable. The default implementation returns
+    /// None meaning that soft pinning is not supported by a variable
+    fn sample_to_soft_message(
+        &self,
+        sample: &Self::Sample,
+        probability: f64,
+    ) -> Option<Self::Message> {
+        let _ = (sample, probability);
+        None
+    }
+
+    /// Returns a message that makes the marginal of a variable equal to a target
+    /// one when it is received in addition to given messages
+    ///
+    /// # Arguments
+    ///
+    /// * `marginal` - A target marginal distribution
+    /// * `messages` - Messages received from adjoint factors previously
+    ///
+    /// # Notes
+    ///
+    /// The returned message is used to create a unit degree factor that injects
+    /// evidence expressed as probabilities. It returns None if the target marginal
+    /// is invalid or can not be reached, e.g. it is positive where the current
+    /// marginal vanishes. The default implementation always returns None meaning
+    /// that moment matching is not supported by a variable
+    fn marginal_to_message(
+        &self,
+        marginal: &Self::Marginal,
+        messages: &[Self::Message],
+    ) -> Option<Self::Message> {
+        let _ = (marginal, messages);
+        None
+    }
+
+    /// Checks whether a message is a valid initial message of an edge of a variable,
+    /// it is verified when factors are added in order to reject broken message
+    /// initializers before they produce NaNs during message passing
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - A message produced by a message initializer
+    ///
+    /// # Notes
+    ///
+    /// The default implementation accepts all messages
+    fn is_valid_message(&self, message: &Self::Message) -> bool {
+        let _ = message;
+        true
+    }
+
+    /// Returns the position of the value of a sample within the marginal
+    /// of a discrete variable, it is used to build empirical marginals from samples
+    ///
+    /// # Arguments
+    ///
+    /// * `sample` - A sample of a variable
+    ///
+    /// # Notes
+    ///
+    /// The default implementation returns None meaning that a variable is not
+    /// discrete or its samples can not be matched with its marginals
+    fn sample_index(&self, sample: &Self::Sample) -> Option<usize> {
+        let _ = sample;
+        None
+    }
+
+    /// Returns the distance between two marginals of a variable, e.g. the maximal
+    /// absolute difference of probabilities, it is used to detect that marginals
+    /// have stopped changing during message passing
+    ///
+    /// # Arguments
+    ///
+    /// * `marginal` - A marginal distribution
+    /// * `other` - Another marginal distribution
+    ///
+    /// # Notes
+    ///
+    /// The default implementation returns None meaning that marginals of a variable
+    /// can not be compared, such variables are never considered stable
+    fn marginal_discrepancy(
+        &self,
+        marginal: &Self::Marginal,
+        other: &Self::Marginal,
+    ) -> Option<f64> {
+        let _ = (marginal, other);
+        None
+    }
+
+    /// Returns parameters of a marginal as a flat vector, e.g. probabilities of values
+    /// of a discrete variable, it is used to record marginals during message passing
+    ///
+    /// # Arguments
+    ///
+    /// * `marginal` - A marginal distribution
+    ///
+    /// # Notes
+    ///
+    /// The default implementation returns None meaning that marginals of a variable
+    /// are not recorded
+    fn marginal_values(&self, marginal: &Self::Marginal) -> Option<Vec<f64>> {
+        let _ = marginal;
+        None
+    }
+}
+
+/// A trait providing the most probable value of a variable, it is required
+/// to decode maximum a posteriori configurations
+pub trait DecodableVariable: Variable {
+    /// Computes the most probable value of a variable
+    ///
+    /// # Arguments
+    ///
+    /// * `messages` - Messages received from adjoint factors previously
+    ///
+    /// # Notes
+    ///
+    /// Ties must be resolved deterministically, since this method is used
+    /// to decode a maximum a posteriori configuration
+    fn argmax(&self, messages: &[Self::Message]) -> Self::Sample;
 }
@@ -1,8 +1,20 @@
+use std::sync::OnceLock;
+
 use rand::Rng;
 
+#[cfg(feature = "parallel")]
+use rayon::prelude::{
+    IndexedParallelIterator, IntoParallelRefIterator, IntoParallelRefMutIterator, ParallelIterator,
+};
+
+#[cfg(feature = "parallel")]
+use crate::core::balancing::MIN_EDGES_PER_TASK;
+
 use crate::{
-    core::factor::Factor, core::factor_node::FactorNode, core::message::Message,
-    core::variable::Variable,
+    core::damping::{eval_damped, AdaptiveDamping, EdgeDamping},
+    core::factor::Factor,
+    core::message::{nan_max, DampableMessage, Message},
+    core::variable::{DecodableVariable, Variable},
 };
 
 #[derive(Debug, Clone)]
@@ -11,19 +23,17 @@ where
     V: Variable,
     F: Factor<Message = V::Message>,
 {
-    variable: V,
+    pub(crate) variable: V,
     pub(crate) fac_node_indices: Vec<usize>,
     pub(crate) fac_node_receiver_indices: Vec<usize>,
     pub(crate) messages: Vec<F::Message>,
-    pub(crate) senders: Vec<*mut F::Message>,
     pub(crate) receivers: Vec<V::Message>,
-}
-
-unsafe impl<V, F> Send for VariableNode<V, F>
-where
-    V: Variable,
-    F: Factor<Message = V::Message>,
-{
+    pub(crate) damping: Vec<EdgeDamping<V::Message>>,
+    // A marginal computed from the current receivers, it is reset whenever
+    // receivers or a variable change
+    pub(crate) marginal: OnceLock<V::Marginal>,
+    // A marginal of the previous iteration used to detect that marginals stopped changing
+    pub(crate) previous_marginal: Option<V::Marginal>,
 }
 
 impl<V, F> VariableNode<V, F>
@@ -38,30 +48,30 @@ where
             fac_node_indices: Vec::new(),
             messages: Vec::new(),
             fac_node_receiver_indices: Vec::new(),
-            senders: Vec::new(),
             receivers: Vec::new(),
+            damping: Vec::new(),
+            marginal: OnceLock::new(),
+            previous_marginal: None,
         }
     }
 
     #[inline(always)]
-    pub(super) fn degree(&self) -> usize {
+    pub(crate) fn degree(&self) -> usize {
         self.receivers.len()
     }
 
     #[inline(always)]
-    pub(super) fn init_senders(&mut self, factors: &mut [FactorNode<F, V>]) {
-        let indices_iter = self
-            .fac_node_indices
-            .iter()
-            .zip(&self.fac_node_receiver_indices);
-        let senders: Vec<_> = indices_iter
-            .map(|(fac_index, fac_receiver_index)| unsafe {
-                let fac = factors.get_unchecked_mut(*fac_index);
-                let mut_ptr: *mut _ = fac.receivers.get_unchecked_mut(*fac_receiver_index);
-                mut_ptr
-            })
-            .collect();
-        self.senders = senders;
+    pub(crate) fn invalidate_marginal(&mut self) {
+        self.marginal.take();
+    }
+
+    #[inline(always)]
+    pub(crate) fn truncate(&mut self, degree: usize) {
+        self.invalidate_marginal();
+        self.fac_node_indices.truncate(degree);
+        self.fac_node_receiver_indices.truncate(degree);
+        self.messages.truncate(degree);
+        self.receivers.truncate(degree);
     }
 
     #[inline(always)]
@@ -71,31 +81,116 @@ where
     }
 
     #[inline(always)]
-    pub(super) fn eval_discrepancy(&self) -> f64 {
+    pub(super) fn eval_damped_messages(
+        &mut self,
+        parameters: &V::Parameters,
+        damping: &AdaptiveDamping,
+    ) where
+        V::Message: DampableMessage,
+    {
+        let (variable, receivers) = (&self.variable, &self.receivers);
+        eval_damped(&mut self.damping, &mut self.messages, damping, |messages| {
+            variable.send_messages(receivers, messages, parameters)
+        });
+    }
+
+    // Replaces the message received from the `position`-th adjoint factor
+    // and returns the discrepancy between the new and the old messages
+    #[inline(always)]
+    pub(super) fn receive_message(&mut self, position: usize, message: &V::Message) -> f64 {
+        self.invalidate_marginal();
+        let receiver = &mut self.receivers[position];
+        let discrepancy = message.discrepancy(receiver);
+        message.memcpy(receiver);
+        discrepancy
+    }
+
+    // Pulls messages sent by adjoint factors, where `sent(i)` are messages
+    // sent by the i-th factor, and returns the maximal discrepancy
+    #[inline(always)]
+    pub(super) fn receive_messages<'a>(&mut self, sent: impl Fn(usize) -> &'a [V::Message]) -> f64 {
+        self.invalidate_marginal();
         let mut max_discrepancy = 0f64;
-        for (new_msg, old_msg_ptr) in self.messages.iter().zip(&self.senders) {
-            let discrepancy = new_msg.discrepancy(unsafe { &**old_msg_ptr });
-            if max_discrepancy < discrepancy {
-                max_discrepancy = discrepancy;
-            }
+        let indices_iter = self
+            .fac_node_indices
+            .iter()
+            .zip(&self.fac_node_receiver_indices);
+        for (receiver, (fac_index, fac_receiver_index)) in
+            self.receivers.iter_mut().zip(indices_iter)
+        {
+            let message = &sent(*fac_index)[*fac_receiver_index];
+            let discrepancy = message.discrepancy(receiver);
+            max_discrepancy = nan_max(max_discrepancy, discrepancy);
+            message.memcpy(receiver);
         }
         max_discrepancy
     }
 
-    #[inline(always)]
-    pub(super) fn send_messages(&mut self) {
-        for (msg, dst_ptr) in self.messages.iter().zip(&mut self.senders) {
-            unsafe { (*msg).memcpy(&mut **dst_ptr) }
-        }
+    // Pulls messages similarly to `receive_messages`, but splits edges across workers
+    #[cfg(feature = "parallel")]
+    pub(super) fn receive_messages_split(&mut self, sent: &[&[V::Message]]) -> f64 {
+        self.invalidate_marginal();
+        let indices_iter = self
+            .fac_node_indices
+            .par_iter()
+            .zip(self.fac_node_receiver_indices.par_iter());
+        self.receivers
+            .par_iter_mut()
+            .zip(indices_iter)
+            .with_min_len(MIN_EDGES_PER_TASK)
+            .map(|(receiver, (index, receiver_index))| {
+                let message = &sent[*index][*receiver_index];
+                let discrepancy = message.discrepancy(receiver);
+                message.memcpy(receiver);
+                discrepancy
+            })
+            .reduce(|| 0f64, nan_max)
     }
 
     #[inline(always)]
     pub(super) fn marginal(&self) -> V::Marginal {
-        self.variable.marginal(&self.receivers)
+        self.marginal
+            .get_or_init(|| self.variable.marginal(&self.receivers))
+            .clone()
+    }
+
+    // Replaces the previous marginal by the current one and returns the discrepancy
+    // between them, it is infinite if marginals can not be compared
+    #[inline(always)]
+    pub(super) fn marginal_change(&mut self) -> f64 {
+        let marginal = self.marginal();
+        let change = self
+            .previous_marginal
+            .as_ref()
+            .and_then(|previous| self.variable.marginal_discrepancy(&marginal, previous))
+            .unwrap_or(f64::INFINITY);
+        self.previous_marginal = Some(marginal);
+        change
     }
 
     #[inline(always)]
-    pub(super) fn sample(&self, rng: &mut impl Rng) -> V::Sample {
+    pub(crate) fn sample(&self, rng: &mut impl Rng) -> V::Sample {
         self.variable.sample(&self.receivers, rng)
     }
+
+    #[inline(always)]
+    pub(crate) fn pinned(&self, tolerance: f64) -> Option<V::Sample> {
+        self.variable.pinned(&self.receivers, tolerance)
+    }
+
+    #[inline(always)]
+    pub(crate) fn marginal_gap(&self) -> Option<f64> {
+        self.variable.marginal_gap(&self.receivers)
+    }
+}
+
+impl<V, F> VariableNode<V, F>
+where
+    V: DecodableVariable,
+    F: Factor<Message = V::Message>,
+{
+    #[inline(always)]
+    pub(crate) fn argmax(&self) -> V::Sample {
+        self.variable.argmax(&self.receivers)
+    }
 }
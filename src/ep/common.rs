@@ -0,0 +1,512 @@
+use std::fmt::{self, Debug};
+use std::marker::PhantomData;
+use std::ops::{Add, Sub};
+use std::sync::Arc;
+
+use rand::Rng;
+
+use crate::core::{
+    saturate_value, BoundedMessage, DampableMessage, DecodableVariable, Factor, FactorGraph,
+    Message, Variable,
+};
+use crate::ep::families::ExponentialFamily;
+
+// ------------------------------------------------------------------------------------------
+
+/// A message of expectation propagation, i.e. natural parameters of an
+/// unnormalized distribution `exp(linear * x - quadratic * x^2 / 2)`
+/// from an exponential family. A message sent by a factor is its
+/// site approximation, a message sent by a variable is a cavity distribution
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct EPMessage {
+    /// A natural parameter corresponding to the sufficient statistic `x`
+    pub linear: f64,
+
+    /// A natural parameter corresponding to the sufficient statistic `-x^2 / 2`
+    pub quadratic: f64,
+}
+
+impl EPMessage {
+    /// Creates a new message.
+    ///
+    /// # Arguments
+    ///
+    /// * `linear` - A natural parameter corresponding to `x`
+    /// * `quadratic` - A natural parameter corresponding to `-x^2 / 2`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ep::EPMessage;
+    ///
+    /// // the normal distribution with mean 2 and variance 0.5
+    /// let message = EPMessage::new(4., 2.);
+    /// ```
+    #[inline]
+    pub fn new(linear: f64, quadratic: f64) -> Self {
+        EPMessage { linear, quadratic }
+    }
+}
+
+impl Add for EPMessage {
+    type Output = EPMessage;
+
+    #[inline(always)]
+    fn add(self, rhs: Self) -> Self::Output {
+        EPMessage::new(self.linear + rhs.linear, self.quadratic + rhs.quadratic)
+    }
+}
+
+impl Sub for EPMessage {
+    type Output = EPMessage;
+
+    #[inline(always)]
+    fn sub(self, rhs: Self) -> Self::Output {
+        EPMessage::new(self.linear - rhs.linear, self.quadratic - rhs.quadratic)
+    }
+}
+
+impl Message for EPMessage {
+    #[inline(always)]
+    fn discrepancy(&self, other: &Self) -> f64 {
+        (self.linear - other.linear)
+            .abs()
+            .max((self.quadratic - other.quadratic).abs())
+    }
+
+    #[inline(always)]
+    fn memcpy(&self, dst: &mut Self) {
+        *dst = *self;
+    }
+}
+
+impl DampableMessage for EPMessage {
+    #[inline(always)]
+    fn damp(&mut self, prev: &Self, gamma: f64) {
+        self.linear = (1f64 - gamma) * self.linear + gamma * prev.linear;
+        self.quadratic = (1f64 - gamma) * self.quadratic + gamma * prev.quadratic;
+    }
+
+    #[inline(always)]
+    fn oscillates(&self, prev: &Self, prev_prev: &Self) -> bool {
+        let correlation = (self.linear - prev.linear) * (prev.linear - prev_prev.linear)
+            + (self.quadratic - prev.quadratic) * (prev.quadratic - prev_prev.quadratic);
+        correlation < 0f64
+    }
+}
+
+impl BoundedMessage for EPMessage {
+    #[inline(always)]
+    fn saturate(&mut self, bound: f64, clamp: bool) -> bool {
+        let linear = saturate_value(&mut self.linear, bound, clamp);
+        saturate_value(&mut self.quadratic, bound, clamp) || linear
+    }
+}
+
+/// Crates a new initializer producing uninformative messages, i.e. messages
+/// with zero natural parameters.
+///
+/// # Example
+///
+/// ```
+/// use gmrs::ep::uninformative_message_initializer;
+///
+/// let mut initializer = uninformative_message_initializer();
+/// let message = initializer();
+/// assert_eq!(message.linear, 0.);
+/// ```
+pub fn uninformative_message_initializer() -> impl FnMut() -> EPMessage {
+    EPMessage::default
+}
+
+// ------------------------------------------------------------------------------------------
+
+type LogPotential = Arc<dyn Fn(&[f64]) -> f64 + Send + Sync>;
+
+#[derive(Clone)]
+enum Site {
+    // A factor sending a fixed message, e.g. a prior or an observation
+    Fixed(EPMessage),
+    // An arbitrary factor whose tilted moments are computed by quadrature
+    Potential {
+        log_potential: LogPotential,
+        degree: usize,
+        standard_nodes: Arc<[(f64, f64)]>,
+    },
+}
+
+/// A factor of expectation propagation given by a log-potential `log psi(x_1, ..., x_n)`,
+/// possibly intractable for exact message passing. A factor keeps a site approximation
+/// per adjoint variable from an exponential family `Q`, a site is updated by
+/// projecting the tilted distribution, i.e. the product of cavities and the potential,
+/// onto the family and dividing the projection by the cavity.
+/// The damping coefficient of site updates is given by parameters
+///
+/// # Notes
+///
+/// Expectations over a tilted distribution are computed over the tensor product
+/// of per-variable quadratures, thus the cost of an update grows exponentially
+/// with the degree of a factor. Sites whose cavity is not normalizable
+/// are not updated
+pub struct EPFactor<Q> {
+    site: Site,
+    family: PhantomData<Q>,
+}
+
+impl<Q> Clone for EPFactor<Q> {
+    fn clone(&self) -> Self {
+        EPFactor {
+            site: self.site.clone(),
+            family: PhantomData,
+        }
+    }
+}
+
+impl<Q> Debug for EPFactor<Q> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.site {
+            Site::Fixed(message) => f.debug_tuple("EPFactor::Fixed").field(message).finish(),
+            Site::Potential {
+                degree,
+                standard_nodes,
+                ..
+            } => f
+                .debug_struct("EPFactor::Potential")
+                .field("degree", degree)
+                .field("points_number", &standard_nodes.len())
+                .finish(),
+        }
+    }
+}
+
+impl<Q: ExponentialFamily> EPFactor<Q> {
+    /// Creates a new factor.
+    ///
+    /// # Arguments
+    ///
+    /// * `degree` - A number of adjoint variables
+    /// * `points_number` - A number of quadrature nodes per variable,
+    ///   it is ignored by families with finite support
+    /// * `log_potential` - A logarithm of a factor, it takes values
+    ///   of adjoint variables in the order of their connection
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ep::{EPFactor, Gaussian};
+    ///
+    /// // a logistic likelihood of a binary label given a latent variable
+    /// let factor = EPFactor::<Gaussian>::new(1, 20, |x| -(-x[0]).exp().ln_1p());
+    /// ```
+    pub fn new(
+        degree: usize,
+        points_number: usize,
+        log_potential: impl Fn(&[f64]) -> f64 + Send + Sync + 'static,
+    ) -> Self {
+        EPFactor {
+            site: Site::Potential {
+                log_potential: Arc::new(log_potential),
+                degree,
+                standard_nodes: Q::standard_nodes(points_number).into(),
+            },
+            family: PhantomData,
+        }
+    }
+
+    // Returns unnormalized tilted moments per variable and the normalization
+    // constant, None if a cavity is not normalizable or the tilted distribution vanishes
+    fn tilted_moments(&self, cavities: &[EPMessage]) -> Option<(f64, Vec<(f64, f64)>)> {
+        let Site::Potential {
+            log_potential,
+            standard_nodes,
+            ..
+        } = &self.site
+        else {
+            return None;
+        };
+        if !cavities.iter().all(Q::is_proper) {
+            return None;
+        }
+        let nodes: Vec<_> = cavities
+            .iter()
+            .map(|cavity| Q::nodes(cavity, standard_nodes))
+            .collect();
+        if nodes.iter().any(Vec::is_empty) {
+            return None;
+        }
+        let points_number: usize = nodes.iter().map(Vec::len).product();
+        let mut index = vec![0; nodes.len()];
+        let mut point = vec![0f64; nodes.len()];
+        let mut log_weights = Vec::with_capacity(points_number);
+        for _ in 0..points_number {
+            let mut log_weight = 0f64;
+            for ((p, i), var_nodes) in point.iter_mut().zip(&index).zip(&nodes) {
+                *p = var_nodes[*i].0;
+                log_weight += var_nodes[*i].1.ln();
+            }
+            log_weights.push(log_weight + log_potential(&point));
+            increment(&mut index, &nodes);
+        }
+        let max_log_weight = log_weights
+            .iter()
+            .copied()
+            .fold(f64::NEG_INFINITY, f64::max);
+        if !max_log_weight.is_finite() {
+            return None;
+        }
+        let mut normalization = 0f64;
+        let mut moments = vec![(0f64, 0f64); nodes.len()];
+        for log_weight in log_weights {
+            let weight = (log_weight - max_log_weight).exp();
+            normalization += weight;
+            for ((first, second), (var_nodes, i)) in
+                moments.iter_mut().zip(nodes.iter().zip(&index))
+            {
+                let x = var_nodes[*i].0;
+                *first += weight * x;
+                *second += weight * x * x;
+            }
+            increment(&mut index, &nodes);
+        }
+        Some((normalization, moments))
+    }
+}
+
+// Increments a multi-index over the tensor product of quadratures
+#[inline(always)]
+fn increment(index: &mut [usize], nodes: &[Vec<(f64, f64)>]) {
+    for (i, var_nodes) in index.iter_mut().zip(nodes).rev() {
+        *i += 1;
+        if *i < var_nodes.len() {
+            return;
+        }
+        *i = 0;
+    }
+}
+
+impl<Q: ExponentialFamily> Factor for EPFactor<Q> {
+    type Message = EPMessage;
+    type Parameters = f64;
+    /// Means and variances of the tilted distribution per adjoint variable
+    type Marginal = Vec<(f64, f64)>;
+
+    #[inline(always)]
+    fn from_message(message: &Self::Message) -> Self {
+        EPFactor {
+            site: Site::Fixed(*message),
+            family: PhantomData,
+        }
+    }
+
+    #[inline(always)]
+    fn degree(&self) -> usize {
+        match &self.site {
+            Site::Fixed(_) => 1,
+            Site::Potential { degree, .. } => *degree,
+        }
+    }
+
+    fn send_messages(&self, src: &[Self::Message], dst: &mut [Self::Message], parameters: &f64) {
+        if let Site::Fixed(message) = &self.site {
+            dst[0] = *message;
+            return;
+        }
+        let Some((normalization, moments)) = self.tilted_moments(src) else {
+            return;
+        };
+        for ((d, cavity), (first, second)) in dst.iter_mut().zip(src).zip(moments) {
+            let Some(projection) = Q::project(first / normalization, second / normalization) else {
+                continue;
+            };
+            let mut site = projection - *cavity;
+            if site.linear.is_finite() && site.quadratic.is_finite() {
+                if *parameters != 0f64 {
+                    site.damp(d, *parameters);
+                }
+                *d = site;
+            }
+        }
+    }
+
+    fn marginal(&self, messages: &[Self::Message]) -> Self::Marginal {
+        if let Site::Fixed(message) = &self.site {
+            return vec![Q::moments(&(*message + messages[0]))];
+        }
+        match self.tilted_moments(messages) {
+            Some((normalization, moments)) => moments
+                .into_iter()
+                .map(|(first, second)| {
+                    let mean = first / normalization;
+                    (mean, second / normalization - mean * mean)
+                })
+                .collect(),
+            None => vec![(f64::NAN, f64::NAN); self.degree()],
+        }
+    }
+
+    /// Returns means and variances of the base distribution of a family
+    /// reweighted by a factor
+    fn factor(&self) -> Self::Marginal {
+        self.marginal(&vec![Q::base(); self.degree()])
+    }
+
+    fn is_contradictory(&self, messages: &[Self::Message]) -> bool {
+        matches!(self.site, Site::Potential { .. })
+            && messages.iter().all(Q::is_proper)
+            && self.tilted_moments(messages).is_none()
+    }
+}
+
+// ------------------------------------------------------------------------------------------
+
+/// A variable of expectation propagation with a prior from an exponential family `Q`,
+/// its marginal is the product of the prior and site approximations of adjoint factors.
+/// The damping coefficient of cavity updates is given by parameters
+#[derive(Debug, Clone, Copy)]
+pub struct EPVariable<Q> {
+    prior: EPMessage,
+    family: PhantomData<Q>,
+}
+
+impl<Q: ExponentialFamily> EPVariable<Q> {
+    /// Creates a new variable.
+    ///
+    /// # Arguments
+    ///
+    /// * `prior` - Natural parameters of a prior distribution
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ep::{EPMessage, EPVariable, Gaussian};
+    ///
+    /// // a standard normal prior
+    /// let var = EPVariable::<Gaussian>::new(EPMessage::new(0., 1.));
+    /// assert_eq!(var.prior().quadratic, 1.);
+    /// ```
+    #[inline]
+    pub fn new(prior: EPMessage) -> Self {
+        EPVariable {
+            prior,
+            family: PhantomData,
+        }
+    }
+
+    /// Returns natural parameters of a prior distribution
+    #[inline]
+    pub fn prior(&self) -> EPMessage {
+        self.prior
+    }
+
+    // Product of the prior and all received messages
+    #[inline(always)]
+    fn posterior(&self, messages: &[EPMessage]) -> EPMessage {
+        messages
+            .iter()
+            .fold(self.prior, |acc, message| acc + *message)
+    }
+}
+
+impl<Q: ExponentialFamily> Variable for EPVariable<Q> {
+    type Message = EPMessage;
+    type Parameters = f64;
+    /// A mean and a variance of a variable
+    type Marginal = (f64, f64);
+    type Sample = f64;
+
+    #[inline(always)]
+    fn send_messages(&self, src: &[Self::Message], dst: &mut [Self::Message], parameters: &f64) {
+        let posterior = self.posterior(src);
+        for (d, s) in dst.iter_mut().zip(src) {
+            let mut cavity = posterior - *s;
+            if *parameters != 0f64 {
+                cavity.damp(d, *parameters);
+            }
+            *d = cavity;
+        }
+    }
+
+    #[inline(always)]
+    fn marginal(&self, messages: &[Self::Message]) -> Self::Marginal {
+        Q::moments(&self.posterior(messages))
+    }
+
+    #[inline(always)]
+    fn sample(&self, messages: &[Self::Message], rng: &mut impl Rng) -> Self::Sample {
+        Q::sample(&self.posterior(messages), rng)
+    }
+
+    #[inline(always)]
+    fn sample_to_message(sample: &Self::Sample) -> Self::Message {
+        Q::point_message(*sample)
+    }
+
+    #[inline(always)]
+    fn marginal_to_message(
+        &self,
+        marginal: &Self::Marginal,
+        messages: &[Self::Message],
+    ) -> Option<Self::Message> {
+        let (mean, variance) = *marginal;
+        let target = Q::project(mean, variance + mean * mean)?;
+        Some(target - self.posterior(messages))
+    }
+
+    #[inline(always)]
+    fn marginal_discrepancy(
+        &self,
+        marginal: &Self::Marginal,
+        other: &Self::Marginal,
+    ) -> Option<f64> {
+        Some(f64::max(
+            (marginal.0 - other.0).abs(),
+            (marginal.1 - other.1).abs(),
+        ))
+    }
+
+    #[inline(always)]
+    fn marginal_values(&self, marginal: &Self::Marginal) -> Option<Vec<f64>> {
+        Some(vec![marginal.0, marginal.1])
+    }
+}
+
+impl<Q: ExponentialFamily> DecodableVariable for EPVariable<Q> {
+    #[inline(always)]
+    fn argmax(&self, messages: &[Self::Message]) -> Self::Sample {
+        Q::mode(&self.posterior(messages))
+    }
+}
+
+// ------------------------------------------------------------------------------------------
+
+impl<Q: ExponentialFamily> FactorGraph<EPFactor<Q>, EPVariable<Q>> {
+    /// Returns site approximations of all factors, i.e. messages
+    /// sent by factors to adjoint variables in the order of their connection
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::FactorGraphBuilder;
+    /// use gmrs::ep::{uninformative_message_initializer, EPFactor, EPMessage, EPVariable, Gaussian};
+    ///
+    /// let mut fgb = FactorGraphBuilder::new_with_variables(
+    ///     vec![EPVariable::<Gaussian>::new(EPMessage::new(0., 1.))],
+    ///     1,
+    /// );
+    /// let mut initializer = uninformative_message_initializer();
+    /// // a Gaussian likelihood of an observation 1 with unit variance
+    /// fgb.add_factor(EPFactor::<Gaussian>::new(1, 40, |x| -(x[0] - 1.).powi(2) / 2.), &[0], &mut initializer)
+    ///     .unwrap();
+    /// let mut fg = fgb.build();
+    /// fg.run_message_passing_parallel(100, 0, 1e-10, &|_| 0., &|_| 0.).unwrap();
+    /// let sites = fg.site_approximations();
+    /// assert!((sites[0][0].linear - 1.).abs() < 1e-8);
+    /// assert!((sites[0][0].quadratic - 1.).abs() < 1e-8);
+    /// ```
+    pub fn site_approximations(&self) -> Vec<&[EPMessage]> {
+        self.factors
+            .iter()
+            .map(|factor| factor.messages.as_slice())
+            .collect()
+    }
+}
@@ -0,0 +1,271 @@
+use std::f64::consts::PI;
+use std::fmt::Debug;
+
+use rand::Rng;
+use rand_distr::{Distribution, Normal};
+
+use crate::ep::common::EPMessage;
+
+// A precision of a message pinning a continuous variable
+const PINNING_PRECISION: f64 = 1e12;
+
+// A log-ratio of a message pinning a binary variable
+const PINNING_LOG_RATIO: f64 = 1e3;
+
+// The largest absolute value of a binary variable mean that can be projected
+const MAX_BINARY_MEAN: f64 = 1f64 - 1e-12;
+
+// ------------------------------------------------------------------------------------------
+
+/// A trait providing methods of an exponential family that messages of
+/// expectation propagation are projected onto. A family is chosen by the type
+/// of variables: [`Gaussian`] for continuous variables, [`Bernoulli`] for binary ones
+pub trait ExponentialFamily: Debug + Clone + Copy + Send + Sync + 'static {
+    /// Returns quadrature nodes and weights of the base distribution of a family
+    /// which are rescaled to a given distribution by [`ExponentialFamily::nodes`]
+    ///
+    /// # Arguments
+    ///
+    /// * `points_number` - A number of quadrature nodes
+    fn standard_nodes(points_number: usize) -> Vec<(f64, f64)>;
+
+    /// Returns quadrature nodes and normalized weights of a distribution
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - Natural parameters of a proper distribution
+    /// * `standard_nodes` - Nodes returned by [`ExponentialFamily::standard_nodes`]
+    fn nodes(message: &EPMessage, standard_nodes: &[(f64, f64)]) -> Vec<(f64, f64)>;
+
+    /// Checks whether natural parameters define a normalizable distribution
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - Natural parameters
+    fn is_proper(message: &EPMessage) -> bool;
+
+    /// Projects a distribution with given moments onto a family, i.e. returns
+    /// natural parameters of a family member with the same sufficient statistics.
+    /// Returns None if moments are not attainable within a family
+    ///
+    /// # Arguments
+    ///
+    /// * `mean` - A mean of a distribution
+    /// * `second_moment` - A second moment of a distribution
+    fn project(mean: f64, second_moment: f64) -> Option<EPMessage>;
+
+    /// Returns a mean and a variance of a distribution
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - Natural parameters of a proper distribution
+    fn moments(message: &EPMessage) -> (f64, f64);
+
+    /// Samples from a distribution
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - Natural parameters of a proper distribution
+    /// * `rng` - A random numbers generator
+    fn sample(message: &EPMessage, rng: &mut impl Rng) -> f64;
+
+    /// Returns the most probable value of a distribution
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - Natural parameters of a proper distribution
+    fn mode(message: &EPMessage) -> f64;
+
+    /// Returns natural parameters of a distribution concentrated at a given value
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - A value of a variable
+    fn point_message(value: f64) -> EPMessage;
+
+    /// Returns natural parameters of the base distribution of a family
+    fn base() -> EPMessage;
+}
+
+// ------------------------------------------------------------------------------------------
+
+/// A family of Gaussian distributions of a continuous variable,
+/// natural parameters are the precision `quadratic` and the
+/// precision adjusted mean `linear`. Expectations over a tilted distribution
+/// are computed by the Gauss-Hermite quadrature
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Gaussian;
+
+impl ExponentialFamily for Gaussian {
+    fn standard_nodes(points_number: usize) -> Vec<(f64, f64)> {
+        gauss_hermite(points_number)
+    }
+
+    #[inline(always)]
+    fn nodes(message: &EPMessage, standard_nodes: &[(f64, f64)]) -> Vec<(f64, f64)> {
+        let (mean, variance) = Self::moments(message);
+        let std = variance.sqrt();
+        standard_nodes
+            .iter()
+            .map(|(point, weight)| (mean + std * point, *weight))
+            .collect()
+    }
+
+    #[inline(always)]
+    fn is_proper(message: &EPMessage) -> bool {
+        message.quadratic > 0f64 && message.quadratic.is_finite() && message.linear.is_finite()
+    }
+
+    #[inline(always)]
+    fn project(mean: f64, second_moment: f64) -> Option<EPMessage> {
+        let variance = second_moment - mean * mean;
+        if variance > 0f64 && variance.is_finite() && mean.is_finite() {
+            Some(EPMessage::new(mean / variance, 1f64 / variance))
+        } else {
+            None
+        }
+    }
+
+    #[inline(always)]
+    fn moments(message: &EPMessage) -> (f64, f64) {
+        (message.linear / message.quadratic, 1f64 / message.quadratic)
+    }
+
+    #[inline(always)]
+    fn sample(message: &EPMessage, rng: &mut impl Rng) -> f64 {
+        let (mean, variance) = Self::moments(message);
+        Normal::new(mean, variance.sqrt()).unwrap().sample(rng)
+    }
+
+    #[inline(always)]
+    fn mode(message: &EPMessage) -> f64 {
+        message.linear / message.quadratic
+    }
+
+    #[inline(always)]
+    fn point_message(value: f64) -> EPMessage {
+        EPMessage::new(PINNING_PRECISION * value, PINNING_PRECISION)
+    }
+
+    #[inline(always)]
+    fn base() -> EPMessage {
+        EPMessage::new(0f64, 1f64)
+    }
+}
+
+// Nodes and weights of the Gauss-Hermite quadrature for the standard normal distribution,
+// they are found by the Newton method starting from asymptotic estimates of roots
+fn gauss_hermite(points_number: usize) -> Vec<(f64, f64)> {
+    let n = points_number;
+    let mut roots = vec![(0f64, 0f64); n];
+    let mut z = 0f64;
+    for i in 0..n.div_ceil(2) {
+        z = match i {
+            0 => {
+                let m = (2 * n + 1) as f64;
+                m.sqrt() - 1.85575 * m.powf(-1f64 / 6f64)
+            }
+            1 => z - 1.14 * (n as f64).powf(0.426) / z,
+            2 => 1.86 * z - 0.86 * roots[0].0,
+            3 => 1.91 * z - 0.91 * roots[1].0,
+            _ => 2f64 * z - roots[i - 2].0,
+        };
+        let mut derivative = 0f64;
+        for _ in 0..100 {
+            // orthonormal Hermite polynomials by the recurrence relation
+            let mut p1 = PI.powf(-0.25);
+            let mut p2 = 0f64;
+            for j in 0..n {
+                let p3 = p2;
+                p2 = p1;
+                p1 = z * (2f64 / (j + 1) as f64).sqrt() * p2
+                    - (j as f64 / (j + 1) as f64).sqrt() * p3;
+            }
+            derivative = (2f64 * n as f64).sqrt() * p2;
+            let step = p1 / derivative;
+            z -= step;
+            if step.abs() <= 1e-15 * z.abs().max(1f64) {
+                break;
+            }
+        }
+        let weight = 2f64 / (derivative * derivative);
+        roots[i] = (z, weight);
+        roots[n - 1 - i] = (-z, weight);
+    }
+    // rescaling from the weight exp(-x^2) to the standard normal density
+    roots
+        .into_iter()
+        .map(|(root, weight)| (2f64.sqrt() * root, weight / PI.sqrt()))
+        .collect()
+}
+
+// ------------------------------------------------------------------------------------------
+
+/// A family of distributions of a binary variable `s = ±1`
+/// of the form `p(s) ∝ exp(linear * s)`, the natural parameter `quadratic`
+/// is irrelevant since `s^2 = 1`. Expectations over a tilted distribution
+/// are computed exactly by summation over both values
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Bernoulli;
+
+impl ExponentialFamily for Bernoulli {
+    fn standard_nodes(_: usize) -> Vec<(f64, f64)> {
+        Vec::new()
+    }
+
+    #[inline(always)]
+    fn nodes(message: &EPMessage, _: &[(f64, f64)]) -> Vec<(f64, f64)> {
+        let p = 1f64 / (1f64 + (-2f64 * message.linear).exp());
+        vec![(1f64, p), (-1f64, 1f64 - p)]
+    }
+
+    #[inline(always)]
+    fn is_proper(message: &EPMessage) -> bool {
+        message.linear.is_finite()
+    }
+
+    #[inline(always)]
+    fn project(mean: f64, _: f64) -> Option<EPMessage> {
+        if mean.is_finite() {
+            let mean = mean.clamp(-MAX_BINARY_MEAN, MAX_BINARY_MEAN);
+            Some(EPMessage::new(mean.atanh(), 0f64))
+        } else {
+            None
+        }
+    }
+
+    #[inline(always)]
+    fn moments(message: &EPMessage) -> (f64, f64) {
+        let mean = message.linear.tanh();
+        (mean, 1f64 - mean * mean)
+    }
+
+    #[inline(always)]
+    fn sample(message: &EPMessage, rng: &mut impl Rng) -> f64 {
+        let p = 1f64 / (1f64 + (-2f64 * message.linear).exp());
+        if rng.gen::<f64>() < p {
+            1f64
+        } else {
+            -1f64
+        }
+    }
+
+    #[inline(always)]
+    fn mode(message: &EPMessage) -> f64 {
+        if message.linear >= 0f64 {
+            1f64
+        } else {
+            -1f64
+        }
+    }
+
+    #[inline(always)]
+    fn point_message(value: f64) -> EPMessage {
+        EPMessage::new(PINNING_LOG_RATIO * value.signum(), 0f64)
+    }
+
+    #[inline(always)]
+    fn base() -> EPMessage {
+        EPMessage::default()
+    }
+}
@@ -0,0 +1,5 @@
+mod common;
+mod families;
+
+pub use common::{uninformative_message_initializer, EPFactor, EPMessage, EPVariable};
+pub use families::{Bernoulli, ExponentialFamily, Gaussian};
@@ -0,0 +1,289 @@
+use std::{error::Error, fmt::Display, io::BufRead};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::core::{FGBuilderResult, Factor, FactorGraphBuilder, Variable};
+
+// ------------------------------------------------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// Errors that could appear while reading a DIMACS file
+pub enum DimacsError {
+    /// Reading from a source has failed
+    ReadError(String),
+
+    /// The problem line `p cnf <variables> <clauses>` is missing or malformed
+    HeaderError(String),
+
+    /// A line could not be parsed, contains the line number (starts from 1) and the line
+    ParseError(usize, String),
+
+    /// A literal refers to a variable out of range, contains the number of variables and the literal
+    OutOfRangeLiteral(usize, i64),
+
+    /// The number of clauses does not match the header, contains the declared and the actual numbers
+    ClausesNumberError(usize, usize),
+}
+
+impl Display for DimacsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DimacsError::ReadError(msg) => write!(f, "Failed to read a DIMACS file: {}", msg),
+            DimacsError::HeaderError(line) => {
+                write!(f, "Missing or malformed problem line: {:?}", line)
+            }
+            DimacsError::ParseError(number, line) => {
+                write!(f, "Failed to parse line {}: {:?}", number, line)
+            }
+            DimacsError::OutOfRangeLiteral(size, literal) => write!(
+                f,
+                "Literal {} is out of range of [1..{}] variables",
+                literal, size,
+            ),
+            DimacsError::ClausesNumberError(declared, actual) => write!(
+                f,
+                "The header declares {} clauses, but {} clauses are found",
+                declared, actual,
+            ),
+        }
+    }
+}
+
+impl Error for DimacsError {}
+
+/// DIMACS reader's result type
+pub type DimacsResult<T> = Result<T, DimacsError>;
+
+// ------------------------------------------------------------------------------------------
+
+/// A literal of a clause, i.e. a variable or its negation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Literal {
+    /// Index of a variable (starts from 0)
+    pub variable: usize,
+
+    /// Whether a variable is negated
+    pub negated: bool,
+}
+
+/// A disjunction of literals with a weight
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Clause {
+    /// Literals of a clause
+    pub literals: Vec<Literal>,
+
+    /// Weight of a clause, it is 1 for clauses read from a `cnf` file
+    /// and the given one for clauses read from a `wcnf` file
+    pub weight: f64,
+}
+
+/// A formula in conjunctive normal form
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CnfFormula {
+    /// Number of variables
+    pub variables_number: usize,
+
+    /// Clauses of a formula
+    pub clauses: Vec<Clause>,
+}
+
+impl CnfFormula {
+    /// Sets weights of all clauses, e.g. in order to solve a MAX-SAT problem
+    ///
+    /// # Arguments
+    ///
+    /// * `weight` - A function taking a clause index and a clause and returning its weight
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::io::read_dimacs;
+    ///
+    /// let mut formula = read_dimacs("p cnf 2 2\n1 2 0\n-1 0\n".as_bytes()).unwrap();
+    /// formula.set_weights(|_, clause| clause.literals.len() as f64);
+    /// assert_eq!(formula.clauses[0].weight, 2.);
+    /// assert_eq!(formula.clauses[1].weight, 1.);
+    /// ```
+    #[inline]
+    pub fn set_weights(&mut self, mut weight: impl FnMut(usize, &Clause) -> f64) {
+        for (index, clause) in self.clauses.iter_mut().enumerate() {
+            clause.weight = weight(index, clause);
+        }
+    }
+
+    /// Creates a factor graph builder where each variable of the formula
+    /// is a variable of a factor graph and each clause is a factor
+    /// adjacent to the variables of its literals in the order of literals
+    ///
+    /// # Arguments
+    ///
+    /// * `variable` - A variable that is used for all variables of a formula
+    /// * `clause_factor` - A function creating a factor from a clause
+    /// * `message_initializer` - An object that initializes messages
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::io::read_dimacs;
+    /// use gmrs::ising::{IsingFactor, IsingVariable, SumProduct, random_message_initializer};
+    /// use rand::thread_rng;
+    ///
+    /// // A 2-SAT formula
+    /// let formula = read_dimacs("p cnf 3 2\n1 -2 0\n2 3 0\n".as_bytes()).unwrap();
+    ///
+    /// // Each clause penalizes its only violating configuration
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let fgb = formula.to_builder(
+    ///     IsingVariable::<SumProduct>::new(),
+    ///     |clause| {
+    ///         let sign = |i: usize| if clause.literals[i].negated { -1. } else { 1. };
+    ///         let w = clause.weight / 4.;
+    ///         IsingFactor::<SumProduct>::new(-w * sign(0) * sign(1), w * sign(0), w * sign(1))
+    ///     },
+    ///     &mut initializer,
+    /// ).unwrap();
+    /// let fg = fgb.build();
+    /// assert_eq!(fg.get_factor_degrees(), vec![2, 2]);
+    /// ```
+    pub fn to_builder<F, V>(
+        &self,
+        variable: V,
+        mut clause_factor: impl FnMut(&Clause) -> F,
+        message_initializer: &mut impl FnMut() -> F::Message,
+    ) -> FGBuilderResult<FactorGraphBuilder<F, V>>
+    where
+        F: Factor,
+        V: Variable<Message = F::Message>,
+    {
+        let mut fgb = FactorGraphBuilder::new_with_variables(
+            vec![variable; self.variables_number],
+            self.clauses.len(),
+        );
+        // formulas may contain repeated clauses and clauses repeating a variable
+        fgb.set_multi_edges_allowed(true);
+        for clause in &self.clauses {
+            let var_indices: Vec<_> = clause.literals.iter().map(|x| x.variable).collect();
+            fgb.add_factor(clause_factor(clause), &var_indices, message_initializer)?;
+        }
+        fgb.set_multi_edges_allowed(false);
+        Ok(fgb)
+    }
+}
+
+// ------------------------------------------------------------------------------------------
+
+/// Reads a formula in the DIMACS CNF format. Both the plain `p cnf <variables> <clauses>`
+/// and the weighted MAX-SAT `p wcnf <variables> <clauses> [<top>]` problem lines are
+/// supported, in the latter case the first number of each clause is its weight.
+/// Comment lines start with `c`, a clause may span several lines and is terminated by `0`.
+///
+/// # Arguments
+///
+/// * `reader` - A source of a DIMACS file
+///
+/// # Example
+///
+/// ```
+/// use gmrs::io::{read_dimacs, Literal};
+///
+/// let dimacs = "c a simple formula\np cnf 3 2\n1 -3 0\n2 3 -1 0\n";
+/// let formula = read_dimacs(dimacs.as_bytes()).unwrap();
+/// assert_eq!(formula.variables_number, 3);
+/// assert_eq!(formula.clauses.len(), 2);
+/// assert_eq!(formula.clauses[0].literals[1], Literal { variable: 2, negated: true });
+/// ```
+pub fn read_dimacs(reader: impl BufRead) -> DimacsResult<CnfFormula> {
+    let mut header: Option<(usize, usize, bool)> = None;
+    let mut clauses = Vec::new();
+    let mut literals = Vec::new();
+    let mut weight: Option<f64> = None;
+    for (number, line) in reader.lines().enumerate() {
+        let line = line.map_err(|err| DimacsError::ReadError(err.to_string()))?;
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('c') {
+            continue;
+        }
+        // SATLIB benchmarks terminate with a `%` line followed by garbage
+        if trimmed.starts_with('%') {
+            break;
+        }
+        let (variables_number, _, weighted) = match header {
+            Some(header) => header,
+            None => {
+                header = Some(parse_header(trimmed)?);
+                continue;
+            }
+        };
+        let parse_error = || DimacsError::ParseError(number + 1, line.clone());
+        for token in trimmed.split_whitespace() {
+            if weighted && weight.is_none() {
+                weight = Some(token.parse().map_err(|_| parse_error())?);
+                continue;
+            }
+            let literal: i64 = token.parse().map_err(|_| parse_error())?;
+            if literal == 0 {
+                clauses.push(Clause {
+                    literals: std::mem::take(&mut literals),
+                    weight: weight.take().unwrap_or(1f64),
+                });
+                continue;
+            }
+            let variable = literal.unsigned_abs() as usize;
+            if variable > variables_number {
+                return Err(DimacsError::OutOfRangeLiteral(variables_number, literal));
+            }
+            literals.push(Literal {
+                variable: variable - 1,
+                negated: literal < 0,
+            });
+        }
+    }
+    let (variables_number, clauses_number, _) =
+        header.ok_or_else(|| DimacsError::HeaderError(String::new()))?;
+    // the last clause may miss the terminating zero
+    if !literals.is_empty() {
+        clauses.push(Clause {
+            literals,
+            weight: weight.unwrap_or(1f64),
+        });
+    }
+    if clauses.len() != clauses_number {
+        return Err(DimacsError::ClausesNumberError(
+            clauses_number,
+            clauses.len(),
+        ));
+    }
+    Ok(CnfFormula {
+        variables_number,
+        clauses,
+    })
+}
+
+// Parses a problem line, returns numbers of variables and clauses and whether clauses are weighted
+#[inline]
+fn parse_header(line: &str) -> DimacsResult<(usize, usize, bool)> {
+    let header_error = || DimacsError::HeaderError(line.to_string());
+    let mut tokens = line.split_whitespace();
+    if tokens.next() != Some("p") {
+        return Err(header_error());
+    }
+    let weighted = match tokens.next() {
+        Some("cnf") => false,
+        Some("wcnf") => true,
+        _ => return Err(header_error()),
+    };
+    let mut parse_next = || -> DimacsResult<usize> {
+        tokens
+            .next()
+            .and_then(|x| x.parse().ok())
+            .ok_or_else(header_error)
+    };
+    let variables_number = parse_next()?;
+    let clauses_number = parse_next()?;
+    Ok((variables_number, clauses_number, weighted))
+}
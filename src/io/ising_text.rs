@@ -0,0 +1,295 @@
+use std::{
+    error::Error,
+    fmt::{Debug, Display},
+    io::{BufRead, Write},
+};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    core::{FGBuilderResult, FactorGraph, FactorGraphBuilder},
+    ising::{new_ising_builder, IsingFactor, IsingMessage, IsingMessagePassingType, IsingVariable},
+};
+
+// ------------------------------------------------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// Errors that could appear while reading or writing an Ising instance in the text format
+pub enum IsingTextError {
+    /// Reading from a source has failed
+    ReadError(String),
+
+    /// Writing to a destination has failed
+    WriteError(String),
+
+    /// The line with the number of spins is missing or malformed
+    HeaderError(String),
+
+    /// A line could not be parsed, contains the line number (starts from 1) and the line
+    ParseError(usize, String),
+
+    /// A spin index is out of range, contains the number of spins and the index
+    OutOfRangeSpin(usize, usize),
+}
+
+impl Display for IsingTextError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IsingTextError::ReadError(msg) => {
+                write!(f, "Failed to read an Ising instance: {}", msg)
+            }
+            IsingTextError::WriteError(msg) => {
+                write!(f, "Failed to write an Ising instance: {}", msg)
+            }
+            IsingTextError::HeaderError(line) => {
+                write!(f, "Missing or malformed number of spins: {:?}", line)
+            }
+            IsingTextError::ParseError(number, line) => {
+                write!(f, "Failed to parse line {}: {:?}", number, line)
+            }
+            IsingTextError::OutOfRangeSpin(size, index) => write!(
+                f,
+                "Spin index {} is out of range of [0..{}) spins",
+                index, size,
+            ),
+        }
+    }
+}
+
+impl Error for IsingTextError {}
+
+/// Ising text format reader's and writer's result type
+pub type IsingTextResult<T> = Result<T, IsingTextError>;
+
+// ------------------------------------------------------------------------------------------
+
+/// An Ising model `exp ( sum_{(i, j)} J_ij s_i s_j + sum_i h_i s_i )` in the canonical
+/// form, i.e. as lists of couplings and fields
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct IsingInstance {
+    /// Number of spins
+    pub spins_number: usize,
+
+    /// Couplings as triples `(i, j, J_ij)`
+    pub couplings: Vec<(usize, usize, f64)>,
+
+    /// Magnetic fields as pairs `(i, h_i)`
+    pub fields: Vec<(usize, f64)>,
+}
+
+impl IsingInstance {
+    /// Creates an instance from an Ising factor graph. Each coupling factor gives
+    /// a coupling in the order of factors, fields of unit factors, coupling factors
+    /// and variables are summed up per spin and only non-zero ones are kept
+    ///
+    /// # Arguments
+    ///
+    /// * `factor_graph` - An Ising factor graph
+    ///
+    /// # Notes
+    ///
+    /// Annealing groups of factors are not kept
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::io::IsingInstance;
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let mut fgb = new_ising_builder::<SumProduct>(3, 2);
+    /// fgb.add_factor(IsingFactor::new(0.5, 0., 0.), &[0, 1], &mut initializer).unwrap();
+    /// fgb.add_factor(IsingFactor::UnitFactor(0.4), &[2], &mut initializer).unwrap();
+    /// let instance = IsingInstance::from_factor_graph(&fgb.build());
+    /// assert_eq!(instance.couplings, vec![(0, 1, 0.5)]);
+    /// assert_eq!(instance.fields, vec![(2, 0.2)]);
+    /// ```
+    pub fn from_factor_graph<T>(
+        factor_graph: &FactorGraph<IsingFactor<T>, IsingVariable<T>>,
+    ) -> Self
+    where
+        T: IsingMessagePassingType + Clone + Debug + Send,
+    {
+        let couplings = factor_graph
+            .factors
+            .iter()
+            .filter_map(|x| match x.factor {
+                IsingFactor::Coupling {
+                    log_puu,
+                    log_pud,
+                    log_pdu,
+                    log_pdd,
+                    ..
+                } => Some((
+                    x.var_node_indices[0],
+                    x.var_node_indices[1],
+                    (log_puu - log_pud - log_pdu + log_pdd) / 4f64,
+                )),
+                IsingFactor::UnitFactor(_) => None,
+            })
+            .collect();
+        let fields = factor_graph
+            .local_fields()
+            .iter()
+            .enumerate()
+            .filter(|(_, field)| **field != 0f64)
+            .map(|(index, field)| (index, *field))
+            .collect();
+        IsingInstance {
+            spins_number: factor_graph.variables.len(),
+            couplings,
+            fields,
+        }
+    }
+
+    /// Creates an Ising factor graph builder from an instance, each coupling
+    /// becomes a coupling factor and each field becomes a unit factor
+    ///
+    /// # Arguments
+    ///
+    /// * `message_initializer` - An object that initializes messages
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::io::read_ising;
+    /// use gmrs::ising::{random_message_initializer, SumProduct};
+    /// use rand::thread_rng;
+    ///
+    /// let instance = read_ising("3\n0 1 0.5\n1 2 -0.5\n2 0.1\n".as_bytes()).unwrap();
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let fg = instance.to_builder::<SumProduct>(&mut initializer).unwrap().build();
+    /// assert_eq!(fg.get_factor_degrees(), vec![2, 2, 1]);
+    /// ```
+    pub fn to_builder<T>(
+        &self,
+        message_initializer: &mut impl FnMut() -> IsingMessage,
+    ) -> FGBuilderResult<FactorGraphBuilder<IsingFactor<T>, IsingVariable<T>>>
+    where
+        T: IsingMessagePassingType + Clone + Debug + Send,
+    {
+        let mut fgb =
+            new_ising_builder(self.spins_number, self.couplings.len() + self.fields.len());
+        for (i, j, coupling) in &self.couplings {
+            fgb.add_factor(
+                IsingFactor::new(*coupling, 0f64, 0f64),
+                &[*i, *j],
+                message_initializer,
+            )?;
+        }
+        for (i, field) in &self.fields {
+            fgb.add_factor(
+                IsingFactor::UnitFactor(2f64 * field),
+                &[*i],
+                message_initializer,
+            )?;
+        }
+        Ok(fgb)
+    }
+}
+
+// ------------------------------------------------------------------------------------------
+
+/// Reads an Ising instance in the canonical text format. The first line contains
+/// the number of spins, each of the following lines is either a coupling `i j J_ij`
+/// or a field `i h_i`, spins are indexed from 0. Empty lines and lines starting
+/// with `#` are skipped, couplings of a spin with itself are rejected
+///
+/// # Arguments
+///
+/// * `reader` - A source of an instance
+///
+/// # Example
+///
+/// ```
+/// use gmrs::io::read_ising;
+///
+/// let text = "# a frustrated triangle\n3\n0 1 1.0\n1 2 1.0\n2 0 -1.0\n0 0.5\n";
+/// let instance = read_ising(text.as_bytes()).unwrap();
+/// assert_eq!(instance.spins_number, 3);
+/// assert_eq!(instance.couplings[2], (2, 0, -1.));
+/// assert_eq!(instance.fields, vec![(0, 0.5)]);
+/// ```
+pub fn read_ising(reader: impl BufRead) -> IsingTextResult<IsingInstance> {
+    let mut instance: Option<IsingInstance> = None;
+    for (number, line) in reader.lines().enumerate() {
+        let line = line.map_err(|err| IsingTextError::ReadError(err.to_string()))?;
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let Some(instance) = &mut instance else {
+            let spins_number = trimmed
+                .parse()
+                .map_err(|_| IsingTextError::HeaderError(line.clone()))?;
+            instance = Some(IsingInstance {
+                spins_number,
+                couplings: Vec::new(),
+                fields: Vec::new(),
+            });
+            continue;
+        };
+        let parse_error = || IsingTextError::ParseError(number + 1, line.clone());
+        let tokens: Vec<_> = trimmed.split_whitespace().collect();
+        let parse_spin = |token: &str| -> IsingTextResult<usize> {
+            let index = token.parse().map_err(|_| parse_error())?;
+            if index >= instance.spins_number {
+                return Err(IsingTextError::OutOfRangeSpin(instance.spins_number, index));
+            }
+            Ok(index)
+        };
+        let parse_value =
+            |token: &str| -> IsingTextResult<f64> { token.parse().map_err(|_| parse_error()) };
+        match tokens[..] {
+            [i, j, coupling] => {
+                let coupling = (parse_spin(i)?, parse_spin(j)?, parse_value(coupling)?);
+                if coupling.0 == coupling.1 {
+                    return Err(parse_error());
+                }
+                instance.couplings.push(coupling);
+            }
+            [i, field] => {
+                let field = (parse_spin(i)?, parse_value(field)?);
+                instance.fields.push(field);
+            }
+            _ => return Err(parse_error()),
+        }
+    }
+    instance.ok_or_else(|| IsingTextError::HeaderError(String::new()))
+}
+
+/// Writes an Ising instance in the canonical text format (see [`read_ising`]),
+/// couplings are written before fields
+///
+/// # Arguments
+///
+/// * `writer` - A destination of an instance
+/// * `instance` - An instance to write
+///
+/// # Example
+///
+/// ```
+/// use gmrs::io::{read_ising, write_ising};
+///
+/// let instance = read_ising("2\n0 1 0.25\n1 -1.5\n".as_bytes()).unwrap();
+/// let mut buffer = Vec::new();
+/// write_ising(&mut buffer, &instance).unwrap();
+/// assert_eq!(String::from_utf8(buffer).unwrap(), "2\n0 1 0.25\n1 -1.5\n");
+/// ```
+pub fn write_ising(mut writer: impl Write, instance: &IsingInstance) -> IsingTextResult<()> {
+    let mut write = || -> std::io::Result<()> {
+        writeln!(writer, "{}", instance.spins_number)?;
+        for (i, j, coupling) in &instance.couplings {
+            writeln!(writer, "{} {} {}", i, j, coupling)?;
+        }
+        for (i, field) in &instance.fields {
+            writeln!(writer, "{} {}", i, field)?;
+        }
+        Ok(())
+    };
+    write().map_err(|err| IsingTextError::WriteError(err.to_string()))
+}
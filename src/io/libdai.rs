@@ -0,0 +1,310 @@
+use std::{
+    error::Error,
+    fmt::Display,
+    io::{BufRead, Write},
+};
+
+use ndarray::{ArrayD, IxDyn, ShapeBuilder};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    core::{FGBuilderResult, FactorGraph, FactorGraphBuilder},
+    tabular::{TabularFactor, TabularMessage, TabularVariable},
+};
+
+// ------------------------------------------------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// Errors that could appear while reading or writing a libDAI file
+pub enum LibdaiError {
+    /// Reading from a source has failed
+    ReadError(String),
+
+    /// Writing to a destination has failed
+    WriteError(String),
+
+    /// A file is malformed, contains a description of the problem
+    ParseError(String),
+
+    /// A variable has different cardinalities in different factors,
+    /// contains the variable label and both cardinalities
+    CardinalityError(usize, usize, usize),
+
+    /// An index of a table entry is out of range, contains the table size and the index
+    OutOfRangeEntry(usize, usize),
+}
+
+impl Display for LibdaiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LibdaiError::ReadError(msg) => write!(f, "Failed to read a libDAI file: {}", msg),
+            LibdaiError::WriteError(msg) => write!(f, "Failed to write a libDAI file: {}", msg),
+            LibdaiError::ParseError(msg) => write!(f, "Malformed libDAI file: {}", msg),
+            LibdaiError::CardinalityError(label, lhs, rhs) => write!(
+                f,
+                "Variable {} has inconsistent cardinalities {} and {}",
+                label, lhs, rhs,
+            ),
+            LibdaiError::OutOfRangeEntry(size, index) => write!(
+                f,
+                "Index of a table entry {} is out of range of [0..{}] entries",
+                index, size,
+            ),
+        }
+    }
+}
+
+impl Error for LibdaiError {}
+
+/// libDAI reader's and writer's result type
+pub type LibdaiResult<T> = Result<T, LibdaiError>;
+
+// ------------------------------------------------------------------------------------------
+
+/// A factor of a libDAI model
+#[derive(Debug, Clone, PartialEq)]
+pub struct LibdaiFactor {
+    /// Indices of adjacent variables
+    pub var_indices: Vec<usize>,
+
+    /// A table of a factor, i-th axis corresponds to i-th adjacent variable
+    pub table: ArrayD<f64>,
+}
+
+/// A discrete model in the libDAI `.fg` format
+#[derive(Debug, Clone, PartialEq)]
+pub struct LibdaiModel {
+    /// Labels of variables as they appear in a file
+    pub labels: Vec<usize>,
+
+    /// Cardinalities of variables
+    pub cardinalities: Vec<usize>,
+
+    /// Factors of a model
+    pub factors: Vec<LibdaiFactor>,
+}
+
+impl LibdaiModel {
+    /// Creates a model from a tabular factor graph, variables are labeled by their indices
+    ///
+    /// # Arguments
+    ///
+    /// * `factor_graph` - A tabular factor graph
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::FactorGraphBuilder;
+    /// use gmrs::io::LibdaiModel;
+    /// use gmrs::tabular::{uninformative_message_initializer, TabularFactor, TabularVariable};
+    /// use ndarray::array;
+    ///
+    /// let mut initializer = uninformative_message_initializer();
+    /// let mut fgb = FactorGraphBuilder::new_with_variables(vec![TabularVariable::new(2); 2], 1);
+    /// fgb.add_factor(TabularFactor::new(array![[1., 2.], [3., 4.]].into_dyn()), &[1, 0], &mut initializer).unwrap();
+    /// let model = LibdaiModel::from_factor_graph(&fgb.build());
+    /// assert_eq!(model.factors[0].var_indices, vec![1, 0]);
+    /// ```
+    pub fn from_factor_graph(factor_graph: &FactorGraph<TabularFactor, TabularVariable>) -> Self {
+        let cardinalities: Vec<_> = factor_graph
+            .variables
+            .iter()
+            .map(|x| x.variable.cardinality())
+            .collect();
+        let factors = factor_graph
+            .factors
+            .iter()
+            .map(|x| LibdaiFactor {
+                var_indices: x.var_node_indices.clone(),
+                table: x.factor.table().clone(),
+            })
+            .collect();
+        LibdaiModel {
+            labels: (0..cardinalities.len()).collect(),
+            cardinalities,
+            factors,
+        }
+    }
+
+    /// Creates a tabular factor graph builder from a model
+    ///
+    /// # Arguments
+    ///
+    /// * `message_initializer` - An object that initializes messages
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::io::read_libdai;
+    /// use gmrs::tabular::uninformative_message_initializer;
+    ///
+    /// let fg = "1\n\n2\n0 1\n2 2\n4\n0 1\n1 2\n2 3\n3 4\n";
+    /// let model = read_libdai(fg.as_bytes()).unwrap();
+    /// let mut initializer = uninformative_message_initializer();
+    /// let fg = model.to_builder(&mut initializer).unwrap().build();
+    /// assert_eq!(fg.get_factor_degrees(), vec![2]);
+    /// ```
+    pub fn to_builder(
+        &self,
+        message_initializer: &mut impl FnMut() -> TabularMessage,
+    ) -> FGBuilderResult<FactorGraphBuilder<TabularFactor, TabularVariable>> {
+        let mut fgb = FactorGraphBuilder::new_with_variables(
+            self.cardinalities.iter().map(|x| TabularVariable::new(*x)),
+            self.factors.len(),
+        );
+        for factor in &self.factors {
+            fgb.add_factor(
+                TabularFactor::new(factor.table.clone()),
+                &factor.var_indices,
+                message_initializer,
+            )?;
+        }
+        Ok(fgb)
+    }
+}
+
+// ------------------------------------------------------------------------------------------
+
+/// Reads a model in the libDAI `.fg` format. Variables are indexed in the order
+/// of their first appearance in a file. Lines starting with `#` are comments.
+///
+/// # Arguments
+///
+/// * `reader` - A source of a libDAI file
+///
+/// # Example
+///
+/// ```
+/// use gmrs::io::read_libdai;
+///
+/// let fg = "# a single factor\n1\n\n2\n3 7\n2 3\n2\n1 0.5\n5 2\n";
+/// let model = read_libdai(fg.as_bytes()).unwrap();
+/// assert_eq!(model.labels, vec![3, 7]);
+/// assert_eq!(model.cardinalities, vec![2, 3]);
+/// // the first variable changes the fastest in linear indices
+/// assert_eq!(model.factors[0].table[[1, 0]], 0.5);
+/// assert_eq!(model.factors[0].table[[1, 2]], 2.);
+/// ```
+pub fn read_libdai(reader: impl BufRead) -> LibdaiResult<LibdaiModel> {
+    let mut tokens = Vec::new();
+    for line in reader.lines() {
+        let line = line.map_err(|err| LibdaiError::ReadError(err.to_string()))?;
+        if line.trim_start().starts_with('#') {
+            continue;
+        }
+        tokens.extend(line.split_whitespace().map(|x| x.to_string()));
+    }
+    let mut tokens = tokens.into_iter();
+    let mut next = |what: &str| -> LibdaiResult<String> {
+        tokens.next().ok_or_else(|| {
+            LibdaiError::ParseError(format!("unexpected end of file, {what} is expected"))
+        })
+    };
+    let parse_usize = |token: String, what: &str| -> LibdaiResult<usize> {
+        token
+            .parse()
+            .map_err(|_| LibdaiError::ParseError(format!("{what} is expected, got {token:?}")))
+    };
+    let mut model = LibdaiModel {
+        labels: Vec::new(),
+        cardinalities: Vec::new(),
+        factors: Vec::new(),
+    };
+    let factors_number = parse_usize(next("number of factors")?, "number of factors")?;
+    for _ in 0..factors_number {
+        let degree = parse_usize(next("number of variables")?, "number of variables")?;
+        let mut labels = Vec::with_capacity(degree);
+        for _ in 0..degree {
+            labels.push(parse_usize(next("variable label")?, "variable label")?);
+        }
+        let mut dims = Vec::with_capacity(degree);
+        for _ in 0..degree {
+            dims.push(parse_usize(next("cardinality")?, "cardinality")?);
+        }
+        let mut var_indices = Vec::with_capacity(degree);
+        for (label, dim) in labels.iter().zip(&dims) {
+            let index = match model.labels.iter().position(|x| x == label) {
+                Some(index) => {
+                    if model.cardinalities[index] != *dim {
+                        return Err(LibdaiError::CardinalityError(
+                            *label,
+                            model.cardinalities[index],
+                            *dim,
+                        ));
+                    }
+                    index
+                }
+                None => {
+                    model.labels.push(*label);
+                    model.cardinalities.push(*dim);
+                    model.labels.len() - 1
+                }
+            };
+            var_indices.push(index);
+        }
+        let size: usize = dims.iter().product();
+        let mut values = vec![0f64; size];
+        let entries_number = parse_usize(next("number of entries")?, "number of entries")?;
+        for _ in 0..entries_number {
+            let index = parse_usize(next("entry index")?, "entry index")?;
+            let token = next("entry value")?;
+            let value: f64 = token.parse().map_err(|_| {
+                LibdaiError::ParseError(format!("entry value is expected, got {token:?}"))
+            })?;
+            *values
+                .get_mut(index)
+                .ok_or(LibdaiError::OutOfRangeEntry(size, index))? = value;
+        }
+        let table = ArrayD::from_shape_vec(IxDyn(&dims).f(), values).unwrap();
+        model.factors.push(LibdaiFactor { var_indices, table });
+    }
+    Ok(model)
+}
+
+/// Writes a model in the libDAI `.fg` format, only non-zero entries of tables are written
+///
+/// # Arguments
+///
+/// * `writer` - A destination of a libDAI file
+/// * `model` - A model to write
+///
+/// # Example
+///
+/// ```
+/// use gmrs::io::{read_libdai, write_libdai};
+///
+/// let fg = "1\n\n2\n3 7\n2 3\n2\n1 0.5\n5 2\n";
+/// let model = read_libdai(fg.as_bytes()).unwrap();
+/// let mut buffer = Vec::new();
+/// write_libdai(&mut buffer, &model).unwrap();
+/// assert_eq!(read_libdai(buffer.as_slice()).unwrap(), model);
+/// ```
+pub fn write_libdai(mut writer: impl Write, model: &LibdaiModel) -> LibdaiResult<()> {
+    let mut write = || -> std::io::Result<()> {
+        writeln!(writer, "{}", model.factors.len())?;
+        for factor in &model.factors {
+            let labels: Vec<_> = factor
+                .var_indices
+                .iter()
+                .map(|x| model.labels[*x].to_string())
+                .collect();
+            // the first variable changes the fastest in linear indices
+            let values: Vec<_> = factor.table.t().iter().copied().collect();
+            writeln!(writer)?;
+            writeln!(writer, "{}", factor.var_indices.len())?;
+            writeln!(writer, "{}", labels.join(" "))?;
+            let dims: Vec<_> = factor.table.shape().iter().map(|x| x.to_string()).collect();
+            writeln!(writer, "{}", dims.join(" "))?;
+            writeln!(writer, "{}", values.iter().filter(|x| **x != 0f64).count())?;
+            for (index, value) in values.iter().enumerate() {
+                if *value != 0f64 {
+                    writeln!(writer, "{} {}", index, value)?;
+                }
+            }
+        }
+        Ok(())
+    };
+    write().map_err(|err| LibdaiError::WriteError(err.to_string()))
+}
@@ -0,0 +1,7 @@
+mod dimacs;
+mod ising_text;
+mod libdai;
+
+pub use dimacs::{read_dimacs, Clause, CnfFormula, DimacsError, DimacsResult, Literal};
+pub use ising_text::{read_ising, write_ising, IsingInstance, IsingTextError, IsingTextResult};
+pub use libdai::{read_libdai, write_libdai, LibdaiError, LibdaiFactor, LibdaiModel, LibdaiResult};
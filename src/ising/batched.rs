@@ -0,0 +1,318 @@
+use std::fmt::Debug;
+
+use crate::{
+    core::{nan_max, FGError, FGResult, FactorGraph, MessagePassingInfo},
+    ising::{
+        IsingFactor, IsingFactorHyperParameters, IsingMessage, IsingMessagePassingType,
+        IsingVariable, LANES,
+    },
+};
+
+// ------------------------------------------------------------------------------------------
+
+// A struct-of-arrays copy of an Ising factor graph. Edges are numbered
+// as follows: edges of coupling factors to their first variables [0, C),
+// edges of coupling factors to their second variables [C, 2C),
+// edges of unit factors [2C, 2C + U)
+pub(super) struct IsingBatch {
+    pub(super) couplings_number: usize,
+    pub(super) log_puu: Vec<f64>,
+    pub(super) log_pud: Vec<f64>,
+    pub(super) log_pdu: Vec<f64>,
+    pub(super) log_pdd: Vec<f64>,
+    // an annealing group of each coupling factor
+    pub(super) groups: Vec<usize>,
+    pub(super) unit_messages: Vec<f64>,
+    // factor index and position of a variable in a factor for each edge
+    pub(super) edge_factors: Vec<(usize, usize)>,
+    // edges adjacent to each variable in the CSR format
+    pub(super) var_offsets: Vec<usize>,
+    pub(super) var_edges: Vec<usize>,
+    // a variable of each edge
+    pub(super) edge_variables: Vec<usize>,
+    // a doubled field and a damping coefficient of each variable
+    pub(super) var_fields: Vec<f64>,
+    pub(super) var_gammas: Vec<Option<f64>>,
+    // factor to variable and variable to factor messages
+    pub(super) fv: Vec<f64>,
+    pub(super) vf: Vec<f64>,
+}
+
+impl IsingBatch {
+    pub(super) fn new<T>(fg: &FactorGraph<IsingFactor<T>, IsingVariable<T>>) -> Self
+    where
+        T: IsingMessagePassingType + Clone + Debug + Send,
+    {
+        let mut couplings = Vec::new();
+        let mut units = Vec::new();
+        for (fac_index, factor) in fg.factors.iter().enumerate() {
+            match factor.factor {
+                IsingFactor::Coupling { .. } => couplings.push(fac_index),
+                IsingFactor::UnitFactor(_) => units.push(fac_index),
+            }
+        }
+        let couplings_number = couplings.len();
+        let edges_number = 2 * couplings_number + units.len();
+        let mut factor_edges = vec![0; fg.factors.len()];
+        let mut edge_factors = vec![(0, 0); edges_number];
+        let mut batch = IsingBatch {
+            couplings_number,
+            log_puu: Vec::with_capacity(couplings_number),
+            log_pud: Vec::with_capacity(couplings_number),
+            log_pdu: Vec::with_capacity(couplings_number),
+            log_pdd: Vec::with_capacity(couplings_number),
+            groups: Vec::with_capacity(couplings_number),
+            unit_messages: Vec::with_capacity(units.len()),
+            edge_factors: Vec::new(),
+            var_offsets: Vec::with_capacity(fg.variables.len() + 1),
+            var_edges: Vec::with_capacity(edges_number),
+            edge_variables: vec![0; edges_number],
+            var_fields: Vec::with_capacity(fg.variables.len()),
+            var_gammas: Vec::with_capacity(fg.variables.len()),
+            fv: vec![0f64; edges_number],
+            vf: vec![0f64; edges_number],
+        };
+        for (c, fac_index) in couplings.iter().enumerate() {
+            let factor = &fg.factors[*fac_index];
+            if let IsingFactor::Coupling {
+                log_puu,
+                log_pud,
+                log_pdu,
+                log_pdd,
+                group,
+                ..
+            } = factor.factor
+            {
+                batch.log_puu.push(log_puu);
+                batch.log_pud.push(log_pud);
+                batch.log_pdu.push(log_pdu);
+                batch.log_pdd.push(log_pdd);
+                batch.groups.push(group);
+            }
+            factor_edges[*fac_index] = c;
+            for position in 0..2 {
+                let edge = c + position * couplings_number;
+                edge_factors[edge] = (*fac_index, position);
+                batch.fv[edge] = factor.messages[position].0;
+                batch.vf[edge] = factor.receivers[position].0;
+            }
+        }
+        for (u, fac_index) in units.iter().enumerate() {
+            let factor = &fg.factors[*fac_index];
+            if let IsingFactor::UnitFactor(message) = factor.factor {
+                batch.unit_messages.push(message);
+            }
+            let edge = 2 * couplings_number + u;
+            factor_edges[*fac_index] = edge;
+            edge_factors[edge] = (*fac_index, 0);
+            batch.fv[edge] = factor.messages[0].0;
+            batch.vf[edge] = factor.receivers[0].0;
+        }
+        batch.var_offsets.push(0);
+        for (var_index, var) in fg.variables.iter().enumerate() {
+            let edges = var
+                .fac_node_indices
+                .iter()
+                .zip(&var.fac_node_receiver_indices);
+            for (fac_index, position) in edges {
+                let edge = match fg.factors[*fac_index].factor {
+                    IsingFactor::Coupling { .. } => {
+                        factor_edges[*fac_index] + position * couplings_number
+                    }
+                    IsingFactor::UnitFactor(_) => factor_edges[*fac_index],
+                };
+                batch.var_edges.push(edge);
+                batch.edge_variables[edge] = var_index;
+            }
+            batch.var_offsets.push(batch.var_edges.len());
+            batch.var_fields.push(2f64 * var.variable.field());
+            batch.var_gammas.push(var.variable.damping());
+        }
+        batch.edge_factors = edge_factors;
+        batch
+    }
+
+    // Updates all factor to variable messages in batches of `LANES` coupling factors
+    // and returns the maximal discrepancy
+    fn factors_sweep<T: IsingMessagePassingType>(
+        &mut self,
+        parameters: &IsingFactorHyperParameters,
+    ) -> f64 {
+        let c_number = self.couplings_number;
+        let (fv_couplings, fv_units) = self.fv.split_at_mut(2 * c_number);
+        let (fv_first, fv_second) = fv_couplings.split_at_mut(c_number);
+        let (vf_first, vf_second) = self.vf[..(2 * c_number)].split_at(c_number);
+        let mut max_discrepancy = 0f64;
+        for start in (0..c_number).step_by(LANES) {
+            let size = LANES.min(c_number - start);
+            let range = start..(start + size);
+            // the last batch is padded by zeros, padding lanes are dropped
+            let load = |values: &[f64]| {
+                let mut lanes = [0f64; LANES];
+                lanes[..size].copy_from_slice(&values[range.clone()]);
+                lanes
+            };
+            let log_p = [
+                load(&self.log_puu),
+                load(&self.log_pud),
+                load(&self.log_pdu),
+                load(&self.log_pdd),
+            ];
+            let mut betas = [0f64; LANES];
+            for (beta, group) in betas.iter_mut().zip(&self.groups[range.clone()]) {
+                *beta = parameters.group_beta(*group);
+            }
+            // a message to the second variable is updated with roles of variables swapped
+            let new_second = T::factor_message_update_lanes(
+                &load(vf_first),
+                &load(fv_second),
+                &[log_p[0], log_p[2], log_p[1], log_p[3]],
+                &betas,
+                parameters.gamma,
+            );
+            let new_first = T::factor_message_update_lanes(
+                &load(vf_second),
+                &load(fv_first),
+                &log_p,
+                &betas,
+                parameters.gamma,
+            );
+            for k in 0..size {
+                max_discrepancy = nan_max(
+                    nan_max(max_discrepancy, (new_first[k] - fv_first[start + k]).abs()),
+                    (new_second[k] - fv_second[start + k]).abs(),
+                );
+            }
+            fv_first[range.clone()].copy_from_slice(&new_first[..size]);
+            fv_second[range].copy_from_slice(&new_second[..size]);
+        }
+        for (fv, message) in fv_units.iter_mut().zip(&self.unit_messages) {
+            max_discrepancy = nan_max(max_discrepancy, (message - *fv).abs());
+            *fv = *message;
+        }
+        max_discrepancy
+    }
+
+    // Updates all variable to factor messages and returns the maximal discrepancy
+    fn variables_sweep(&mut self, parameters: f64) -> f64 {
+        let mut max_discrepancy = 0f64;
+        for var_index in 0..self.var_fields.len() {
+            let edges =
+                &self.var_edges[self.var_offsets[var_index]..self.var_offsets[var_index + 1]];
+            let gamma = self.var_gammas[var_index].unwrap_or(parameters);
+            let sum_all =
+                self.var_fields[var_index] + edges.iter().map(|edge| self.fv[*edge]).sum::<f64>();
+            for edge in edges {
+                let prev_message = self.vf[*edge];
+                let new_message =
+                    (1f64 - gamma) * (sum_all - self.fv[*edge]) + gamma * prev_message;
+                max_discrepancy = nan_max(max_discrepancy, (new_message - prev_message).abs());
+                self.vf[*edge] = new_message;
+            }
+        }
+        max_discrepancy
+    }
+
+    // Writes messages back to a factor graph
+    pub(super) fn write_back<T>(&self, fg: &mut FactorGraph<IsingFactor<T>, IsingVariable<T>>)
+    where
+        T: IsingMessagePassingType + Clone + Debug + Send,
+    {
+        for (edge, (fac_index, position)) in self.edge_factors.iter().enumerate() {
+            let factor = &mut fg.factors[*fac_index];
+            factor.messages[*position] = IsingMessage(self.fv[edge]);
+            factor.receivers[*position] = IsingMessage(self.vf[edge]);
+        }
+        for (var_index, var) in fg.variables.iter_mut().enumerate() {
+            let edges =
+                &self.var_edges[self.var_offsets[var_index]..self.var_offsets[var_index + 1]];
+            var.invalidate_marginal();
+            for (k, edge) in edges.iter().enumerate() {
+                var.receivers[k] = IsingMessage(self.fv[*edge]);
+                var.messages[k] = IsingMessage(self.vf[*edge]);
+            }
+        }
+    }
+}
+
+impl<T> FactorGraph<IsingFactor<T>, IsingVariable<T>>
+where
+    T: IsingMessagePassingType + Clone + Debug + Send,
+{
+    /// Runs Ising message passing with the same update rules and convergence
+    /// criterion as [`FactorGraph::run_message_passing_parallel`], but on a
+    /// struct-of-arrays copy of a factor graph, where parameters and messages of
+    /// coupling factors are stored in contiguous arrays and updated in batches of
+    /// [`LANES`](crate::ising::LANES) factors by a vectorized kernel (see
+    /// `IsingMessagePassingType::factor_message_update_lanes`) instead of one
+    /// `send_messages` call per node. The kernel of sum-product evaluates exponents
+    /// and logarithms by polynomials, thus messages agree with the ones of
+    /// `run_message_passing_parallel` up to rounding. Messages are written back
+    /// to a factor graph when the method returns, also on failure
+    ///
+    /// # Arguments
+    ///
+    /// * `max_iterations_number` - A maximal number of iterations
+    /// * `min_iterations_number` - A minimal number of iterations
+    /// * `threshold` - A threshold specifying the convergence criterion
+    /// * `factor_scheduler` - A scheduler of a factor's messages update rule hyper-parameters
+    /// * `variable_scheduler` - A scheduler of a variable's messages update rule hyper-parameters
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let mut fgb = new_ising_builder::<SumProduct>(3, 3);
+    /// fgb.add_factor(IsingFactor::new(0.5, 0.1, 0.), &[0, 1], &mut initializer).unwrap();
+    /// fgb.add_factor(IsingFactor::new(-0.3, 0., 0.2), &[1, 2], &mut initializer).unwrap();
+    /// fgb.add_factor(IsingFactor::new(0.2, 0., 0.), &[2, 0], &mut initializer).unwrap();
+    /// let mut fg = fgb.build();
+    /// let mut reference_fg = fg.clone();
+    /// let factor_scheduler = get_standard_factor_scheduler(0.);
+    /// let variable_scheduler = get_standard_variable_scheduler(0.);
+    /// let info = fg.run_message_passing_batched(1000, 0, 1e-10, &factor_scheduler, &variable_scheduler).unwrap();
+    /// let reference_info = reference_fg.run_message_passing_parallel(1000, 0, 1e-10, &factor_scheduler, &variable_scheduler).unwrap();
+    /// assert_eq!(info.iterations_number, reference_info.iterations_number);
+    /// ```
+    pub fn run_message_passing_batched(
+        &mut self,
+        max_iterations_number: usize,
+        min_iterations_number: usize,
+        threshold: f64,
+        factor_scheduler: &impl Fn(usize) -> IsingFactorHyperParameters,
+        variable_scheduler: &impl Fn(usize) -> f64,
+    ) -> FGResult<MessagePassingInfo> {
+        let mut batch = IsingBatch::new(self);
+        let mut last_discrepancy = f64::MAX;
+        let mut discrepancy_dynamics = Vec::with_capacity(max_iterations_number);
+        for i in 0..max_iterations_number {
+            let factors_discrepancy = batch.factors_sweep::<T>(&factor_scheduler(i));
+            let variables_discrepancy = batch.variables_sweep(variable_scheduler(i));
+            let max_discrepancy = nan_max(factors_discrepancy, variables_discrepancy);
+            discrepancy_dynamics.push(max_discrepancy);
+            last_discrepancy = max_discrepancy;
+            if (max_discrepancy < threshold) && (i + 1 >= min_iterations_number) {
+                batch.write_back(self);
+                return Ok(MessagePassingInfo {
+                    iterations_number: i,
+                    discrepancy_dynamics,
+                    last_discrepancy,
+                    certificate: self.exactness_certificate(),
+                    marginal_history: None,
+                    observables: Vec::new(),
+                });
+            }
+        }
+        batch.write_back(self);
+        self.check_contradictions()?;
+        Err(FGError::MessagePassingError {
+            iterations_number: max_iterations_number,
+            discrepancy_dynamics,
+            last_discrepancy,
+        })
+    }
+}
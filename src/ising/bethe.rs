@@ -0,0 +1,151 @@
+use crate::{
+    core::FactorGraph,
+    ising::{
+        common::log_sigmoid, IsingFactor, IsingFactorHyperParameters, IsingMessage, IsingVariable,
+        SumProduct,
+    },
+};
+
+// ------------------------------------------------------------------------------------------
+
+// An entropy term of a probability
+#[inline(always)]
+fn entropy(p: f64) -> f64 {
+    if p > 0f64 {
+        -p * p.ln()
+    } else {
+        0f64
+    }
+}
+
+// Returns logarithms of a factor scaled by inverse temperatures and of its unnormalized
+// belief, entries of a coupling are ordered as (uu, ud, du, dd) and of a unit factor as (u, d)
+pub(super) fn log_factor_and_belief(
+    factor: &IsingFactor<SumProduct>,
+    receivers: &[IsingMessage],
+    factor_parameters: &IsingFactorHyperParameters,
+) -> (Vec<f64>, Vec<f64>) {
+    match *factor {
+        IsingFactor::Coupling {
+            log_puu,
+            log_pud,
+            log_pdu,
+            log_pdd,
+            group,
+            ..
+        } => {
+            let beta = factor_parameters.group_beta(group);
+            let log_factor = [log_puu, log_pud, log_pdu, log_pdd].map(|x| beta * x);
+            let (first, second) = (receivers[0].0, receivers[1].0);
+            let log_messages = [
+                log_sigmoid(first) + log_sigmoid(second),
+                log_sigmoid(first) + log_sigmoid(-second),
+                log_sigmoid(-first) + log_sigmoid(second),
+                log_sigmoid(-first) + log_sigmoid(-second),
+            ];
+            let mut log_belief = log_factor.to_vec();
+            for (x, y) in log_belief.iter_mut().zip(log_messages) {
+                *x += y;
+            }
+            (log_factor.to_vec(), log_belief)
+        }
+        IsingFactor::UnitFactor(message) => {
+            let log_factor = vec![log_sigmoid(message), log_sigmoid(-message)];
+            let received = receivers[0].0;
+            let log_belief = vec![
+                log_factor[0] + log_sigmoid(received),
+                log_factor[1] + log_sigmoid(-received),
+            ];
+            (log_factor, log_belief)
+        }
+    }
+}
+
+// Normalizes a distribution given by logarithms of unnormalized probabilities
+pub(super) fn normalized(log_belief: &[f64]) -> Vec<f64> {
+    let max = log_belief.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let belief: Vec<f64> = log_belief.iter().map(|x| (x - max).exp()).collect();
+    let normalization: f64 = belief.iter().sum();
+    belief.into_iter().map(|p| p / normalization).collect()
+}
+
+impl FactorGraph<IsingFactor<SumProduct>, IsingVariable<SumProduct>> {
+    /// Computes the Bethe average energy `U`, i.e. the average of the energy
+    /// `E(s) = -log w(s) / beta` over factor and variable beliefs, where `w(s)` is
+    /// the unnormalized weight of a configuration at given hyper-parameters.
+    /// Together with [`FactorGraph::bethe_entropy`] it gives the Bethe free energy
+    /// `F = U - S / beta`, and `-beta F` is the Bethe approximation of the logarithm
+    /// of the partition function. It should be called after message passing has converged
+    ///
+    /// # Arguments
+    ///
+    /// * `factor_parameters` - Parameters of factors used by message passing
+    ///
+    /// # Notes
+    ///
+    /// Coupling factors are scaled by inverse temperatures of their annealing groups,
+    /// while fields and unit factors are not, thus for a model with fields the energy
+    /// depends on `beta`. On trees the energy is exact
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, IsingFactor, IsingMessage, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    ///
+    /// // A single pair of spins, the partition function is 4 cosh(beta J)
+    /// let mut fgb = new_ising_builder::<SumProduct>(2, 1);
+    /// fgb.add_factor(IsingFactor::new(1., 0., 0.), &[0, 1], &mut || IsingMessage(0.)).unwrap();
+    /// let mut fg = fgb.build();
+    /// let factor_scheduler = get_standard_factor_scheduler(0.);
+    /// fg.run_message_passing_parallel(100, 0, 1e-12, &factor_scheduler, &get_standard_variable_scheduler(0.))
+    ///     .unwrap();
+    /// let parameters = factor_scheduler(0);
+    /// let energy = fg.bethe_energy(&parameters);
+    /// let entropy = fg.bethe_entropy(&parameters);
+    /// assert!((energy + f64::tanh(1.)).abs() < 1e-10);
+    /// assert!((entropy - energy - f64::ln(4. * f64::cosh(1.))).abs() < 1e-10);
+    /// ```
+    pub fn bethe_energy(&self, factor_parameters: &IsingFactorHyperParameters) -> f64 {
+        -self.bethe_terms(factor_parameters).0 / factor_parameters.beta
+    }
+
+    /// Computes the Bethe entropy `S = sum_a H(b_a) - sum_i (d_i - 1) H(b_i)`,
+    /// where `b_a` and `b_i` are beliefs of factors and variables, `H` is an entropy
+    /// and `d_i` is the number of factors adjacent to a variable `i`. It should be
+    /// called after message passing has converged, see [`FactorGraph::bethe_energy`]
+    ///
+    /// # Arguments
+    ///
+    /// * `factor_parameters` - Parameters of factors used by message passing
+    ///
+    /// # Notes
+    ///
+    /// On trees the entropy is exact, on graphs with cycles it may be negative
+    pub fn bethe_entropy(&self, factor_parameters: &IsingFactorHyperParameters) -> f64 {
+        self.bethe_terms(factor_parameters).1
+    }
+
+    // Returns the average logarithm of the weight of a configuration
+    // over beliefs and the Bethe entropy
+    pub(super) fn bethe_terms(&self, factor_parameters: &IsingFactorHyperParameters) -> (f64, f64) {
+        let mut average_log_weight = 0f64;
+        let mut bethe_entropy = 0f64;
+        for (node, marginal) in self.variables.iter().zip(self.variable_marginals()) {
+            average_log_weight += node.variable.field() * (marginal[0] - marginal[1]);
+            bethe_entropy -= (node.fac_node_indices.len() as f64 - 1f64)
+                * (entropy(marginal[0]) + entropy(marginal[1]));
+        }
+        for node in &self.factors {
+            let (log_factor, log_belief) =
+                log_factor_and_belief(&node.factor, &node.receivers, factor_parameters);
+            for (p, log_f) in normalized(&log_belief).into_iter().zip(log_factor) {
+                if p > 0f64 {
+                    average_log_weight += p * log_f;
+                }
+                bethe_entropy += entropy(p);
+            }
+        }
+        (average_log_weight, bethe_entropy)
+    }
+}
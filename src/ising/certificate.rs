@@ -0,0 +1,178 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    core::FactorGraph,
+    ising::{IsingFactor, IsingFactorHyperParameters, IsingVariable, SumProduct},
+};
+
+// ------------------------------------------------------------------------------------------
+
+/// A certificate of convergence of sum-product belief propagation on an Ising model
+/// in a weak coupling regime. The message passing update is a contraction
+/// in the maximum norm of messages, thus messages converge to the unique fixed point
+/// at a geometric rate and the number of iterations can be budgeted in advance
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ConvergenceCertificate {
+    /// A certified contraction rate of an iteration, i.e. a distance from the fixed point
+    /// decreases at least by this factor per iteration
+    pub contraction_rate: f64,
+
+    /// An upper bound on the distance between messages after the first iteration
+    /// and the fixed point
+    pub initial_distance: f64,
+}
+
+impl ConvergenceCertificate {
+    /// Returns an upper bound on the maximal distance between messages
+    /// and the fixed point after a given number of iterations
+    ///
+    /// # Arguments
+    ///
+    /// * `iterations_number` - A number of performed iterations
+    pub fn distance_bound(&self, iterations_number: usize) -> f64 {
+        match iterations_number {
+            0 => f64::INFINITY,
+            n => self.initial_distance * self.contraction_rate.powi(n as i32 - 1),
+        }
+    }
+
+    /// Returns an upper bound on the number of iterations after which
+    /// message passing with a given threshold stops, i.e. a sufficient
+    /// `max_iterations_number` when the convergence window is one
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - A positive threshold of the discrepancy between iterations
+    pub fn iterations_bound(&self, threshold: f64) -> usize {
+        if threshold <= 0f64 || threshold.is_nan() {
+            return usize::MAX;
+        }
+        // the discrepancy of the i-th iteration (counting from zero) is bounded by
+        // (1 + rate) * rate^(i - 1) * initial_distance for i > 0
+        let discrepancy_bound = |i: usize| (1f64 + self.contraction_rate) * self.distance_bound(i);
+        let mut i = if self.contraction_rate > 0f64 && discrepancy_bound(1) >= threshold {
+            let steps = (threshold / discrepancy_bound(1)).ln() / self.contraction_rate.ln();
+            1 + steps.floor() as usize
+        } else {
+            1
+        };
+        while discrepancy_bound(i) >= threshold {
+            i += 1;
+        }
+        i + 1
+    }
+}
+
+impl FactorGraph<IsingFactor<SumProduct>, IsingVariable<SumProduct>> {
+    /// Returns a certificate of convergence of sum-product message passing
+    /// with given hyper-parameters if the sufficient condition
+    /// `gamma + (1 - gamma) * (d_i - 1) * tanh(beta |J_ij|) < 1` holds for all pairs of coupled
+    /// spins, where `d_i` is the number of coupling factors of a spin `i`. The certificate
+    /// is computed from the current messages, thus it is valid for warm restarts as well
+    ///
+    /// # Arguments
+    ///
+    /// * `parameters` - Hyper-parameters of factors used at all iterations
+    ///
+    /// # Notes
+    ///
+    /// The method returns None if the condition is violated or a variable
+    /// has its own damping coefficient, since variable messages are assumed undamped
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// // a weakly coupled ring of 10 spins
+    /// let mut fgb = new_ising_builder::<SumProduct>(10, 10);
+    /// for i in 0..10 {
+    ///     fgb.add_factor(IsingFactor::new(0.3, 0.1, 0.), &[i, (i + 1) % 10], &mut initializer).unwrap();
+    /// }
+    /// let mut fg = fgb.build();
+    /// let factor_scheduler = get_standard_factor_scheduler(0.);
+    /// let certificate = fg.convergence_certificate(&factor_scheduler(0)).unwrap();
+    /// assert!((certificate.contraction_rate - f64::tanh(0.3)).abs() < 1e-12);
+    /// let max_iterations_number = certificate.iterations_bound(1e-10);
+    /// let info = fg
+    ///     .run_message_passing_parallel(
+    ///         max_iterations_number,
+    ///         0,
+    ///         1e-10,
+    ///         &factor_scheduler,
+    ///         &get_standard_variable_scheduler(0.),
+    ///     )
+    ///     .unwrap();
+    /// assert!(info.iterations_number < max_iterations_number);
+    /// ```
+    pub fn convergence_certificate(
+        &self,
+        parameters: &IsingFactorHyperParameters,
+    ) -> Option<ConvergenceCertificate> {
+        if self
+            .variables
+            .iter()
+            .any(|var| var.variable.damping().is_some_and(|gamma| gamma != 0f64))
+        {
+            return None;
+        }
+        let gamma = parameters.gamma;
+        let mut degrees = vec![0usize; self.variables.len()];
+        for factor in &self.factors {
+            if let IsingFactor::Coupling { .. } = factor.factor {
+                for var_index in &factor.var_node_indices {
+                    degrees[*var_index] += 1;
+                }
+            }
+        }
+        let mut contraction_rate = 0f64;
+        let mut initial_distance = 0f64;
+        for factor in &self.factors {
+            let IsingFactor::Coupling {
+                log_puu,
+                log_pud,
+                log_pdu,
+                log_pdd,
+                group,
+                ..
+            } = factor.factor
+            else {
+                continue;
+            };
+            let beta = parameters.group_beta(group);
+            let sensitivity = (beta * (log_puu - log_pud - log_pdu + log_pdd) / 4f64)
+                .abs()
+                .tanh();
+            // a message to a spin depends on messages received by the other spin,
+            // and it lies between its values for the other spin fixed up and down
+            let edges = [
+                (
+                    degrees[factor.var_node_indices[1]],
+                    beta * (log_puu - log_pdu),
+                    beta * (log_pud - log_pdd),
+                ),
+                (
+                    degrees[factor.var_node_indices[0]],
+                    beta * (log_puu - log_pud),
+                    beta * (log_pdu - log_pdd),
+                ),
+            ];
+            for ((other_degree, lhs, rhs), message) in edges.into_iter().zip(&factor.messages) {
+                let rate = gamma + (1f64 - gamma) * (other_degree - 1) as f64 * sensitivity;
+                contraction_rate = contraction_rate.max(rate);
+                let distance = (1f64 - gamma) * (lhs - rhs).abs()
+                    + gamma * (message.0 - lhs).abs().max((message.0 - rhs).abs());
+                initial_distance = initial_distance.max(distance);
+            }
+        }
+        (contraction_rate < 1f64).then_some(ConvergenceCertificate {
+            contraction_rate,
+            initial_distance,
+        })
+    }
+}
@@ -0,0 +1,143 @@
+use std::fmt::Debug;
+
+use crate::{
+    core::{FGBuilderResult, FactorGraphBuilder},
+    ising::{IsingFactor, IsingMessage, IsingMessagePassingType, IsingVariable},
+};
+
+// ------------------------------------------------------------------------------------------
+
+// Creates a builder of spins with given fields coupled by neighboring couplings,
+// the last spin is coupled to the first one if `closing_coupling` is given
+fn new_1d_builder<T>(
+    fields: Vec<f64>,
+    coupling: f64,
+    closing_coupling: Option<f64>,
+    message_initializer: &mut impl FnMut() -> IsingMessage,
+) -> FGBuilderResult<FactorGraphBuilder<IsingFactor<T>, IsingVariable<T>>>
+where
+    T: IsingMessagePassingType + Clone + Debug + Send,
+{
+    let spins_number = fields.len();
+    let variables = fields
+        .into_iter()
+        .map(|field| IsingVariable::new().with_field(field));
+    let mut fgb = FactorGraphBuilder::new_with_variables(variables, spins_number);
+    for i in 1..spins_number {
+        fgb.add_factor(
+            IsingFactor::new(coupling, 0f64, 0f64),
+            &[i - 1, i],
+            message_initializer,
+        )?;
+    }
+    if let Some(closing_coupling) = closing_coupling {
+        fgb.add_factor(
+            IsingFactor::new(closing_coupling, 0f64, 0f64),
+            &[spins_number - 1, 0],
+            message_initializer,
+        )?;
+    }
+    Ok(fgb)
+}
+
+/// Creates a builder of an open Ising chain
+/// `exp ( J sum_i s_i s_{i+1} + sum_i h_i s_i )`, where all fields
+/// are equal to the bulk field except fields of the first and the last spins.
+/// Fields are stored in variables, couplings are the only factors
+///
+/// # Arguments
+///
+/// * `spins_number` - A number of spins
+/// * `coupling` - A coupling `J` between neighboring spins
+/// * `field` - A bulk magnetic field
+/// * `boundary_fields` - Magnetic fields of the first and the last spins
+/// * `message_initializer` - An object that initializes messages
+///
+/// # Notes
+///
+/// The field of a single spin chain is the last boundary field. Pass `[field, field]`
+/// as boundary fields to get a homogeneous chain. More factors can be added to
+/// a returned builder
+///
+/// # Example
+///
+/// ```
+/// use gmrs::ising::{new_ising_chain_builder, random_message_initializer, SumProduct};
+/// use rand::thread_rng;
+///
+/// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+/// let fg = new_ising_chain_builder::<SumProduct>(4, 1., 0.3, [0.3, -0.2], &mut initializer)
+///     .unwrap()
+///     .build();
+/// assert_eq!(fg.get_variable_degrees(), vec![1, 2, 2, 1]);
+/// assert_eq!(fg.local_fields().to_vec(), vec![0.3, 0.3, 0.3, -0.2]);
+/// ```
+pub fn new_ising_chain_builder<T>(
+    spins_number: usize,
+    coupling: f64,
+    field: f64,
+    boundary_fields: [f64; 2],
+    message_initializer: &mut impl FnMut() -> IsingMessage,
+) -> FGBuilderResult<FactorGraphBuilder<IsingFactor<T>, IsingVariable<T>>>
+where
+    T: IsingMessagePassingType + Clone + Debug + Send,
+{
+    let mut fields = vec![field; spins_number];
+    if let Some(first) = fields.first_mut() {
+        *first = boundary_fields[0];
+    }
+    if let Some(last) = fields.last_mut() {
+        *last = boundary_fields[1];
+    }
+    new_1d_builder(fields, coupling, None, message_initializer)
+}
+
+/// Creates a builder of a periodic Ising ring
+/// `exp ( J sum_i s_i s_{i+1} + J' s_{N-1} s_0 + h sum_i s_i )`, where the coupling `J'`
+/// closing a ring may differ from the bulk one, e.g. `J' = -J` gives antiperiodic
+/// boundary conditions. Fields are stored in variables, couplings are the only factors
+///
+/// # Arguments
+///
+/// * `spins_number` - A number of spins
+/// * `coupling` - A coupling `J` between neighboring spins
+/// * `field` - A magnetic field `h`
+/// * `closing_coupling` - A coupling `J'` between the last and the first spins
+/// * `message_initializer` - An object that initializes messages
+///
+/// # Notes
+///
+/// A ring of two spins has two couplings between them, a ring of a single spin
+/// has no couplings
+///
+/// # Example
+///
+/// ```
+/// use gmrs::ising::{new_ising_ring_builder, random_message_initializer, SumProduct};
+/// use rand::thread_rng;
+///
+/// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+/// let fg = new_ising_ring_builder::<SumProduct>(4, 1., 0.3, -1., &mut initializer)
+///     .unwrap()
+///     .build();
+/// assert_eq!(fg.get_variable_degrees(), vec![2, 2, 2, 2]);
+/// assert_eq!(fg.coupling_matrix().to_dense()[[3, 0]], -1.);
+/// ```
+pub fn new_ising_ring_builder<T>(
+    spins_number: usize,
+    coupling: f64,
+    field: f64,
+    closing_coupling: f64,
+    message_initializer: &mut impl FnMut() -> IsingMessage,
+) -> FGBuilderResult<FactorGraphBuilder<IsingFactor<T>, IsingVariable<T>>>
+where
+    T: IsingMessagePassingType + Clone + Debug + Send,
+{
+    let closing_coupling = (spins_number > 1).then_some(closing_coupling);
+    new_1d_builder(
+        vec![field; spins_number],
+        coupling,
+        closing_coupling,
+        message_initializer,
+    )
+}
@@ -1,10 +1,13 @@
-use crate::core::{Factor, FactorGraphBuilder, Message, Variable};
+use crate::core::{
+    saturate_value, BoundedMessage, DampableMessage, DecodableVariable, Factor, FactorGraphBuilder,
+    Message, Variable,
+};
 use ndarray::{Array1, ArrayD, IxDyn};
 use rand::Rng;
 use rand_distr::{Distribution, Uniform};
 use std::{fmt::Debug, marker::PhantomData};
 
-use super::IsingFactorHyperParameters;
+use super::{IsingFactorHyperParameters, Lanes, LANES};
 
 // ------------------------------------------------------------------------------------------
 
@@ -19,6 +22,25 @@ impl Message for IsingMessage {
     }
 }
 
+impl DampableMessage for IsingMessage {
+    #[inline(always)]
+    fn damp(&mut self, prev: &Self, gamma: f64) {
+        self.0 = (1f64 - gamma) * self.0 + gamma * prev.0;
+    }
+
+    #[inline(always)]
+    fn oscillates(&self, prev: &Self, prev_prev: &Self) -> bool {
+        (self.0 - prev.0) * (prev.0 - prev_prev.0) < 0f64
+    }
+}
+
+impl BoundedMessage for IsingMessage {
+    #[inline(always)]
+    fn saturate(&mut self, bound: f64, clamp: bool) -> bool {
+        saturate_value(&mut self.0, bound, clamp)
+    }
+}
+
 // ------------------------------------------------------------------------------------------
 
 #[inline(always)]
@@ -62,7 +84,41 @@ pub trait IsingMessagePassingType {
         parameters: &IsingFactorHyperParameters,
     ) -> IsingMessage;
 
+    /// Updates messages of `LANES` coupling factors at once, each lane is updated
+    /// as by `factor_message_update` with its own inverse temperature. `log_p` contains
+    /// `log_p_ou_iu`, `log_p_ou_id`, `log_p_od_iu` and `log_p_od_id` of all lanes.
+    /// Message passing types of the crate override it with a vectorized kernel,
+    /// the default implementation calls `factor_message_update` for each lane
+    #[inline(always)]
+    fn factor_message_update_lanes(
+        messages: &Lanes,
+        prev_messages: &Lanes,
+        log_p: &[Lanes; 4],
+        betas: &Lanes,
+        gamma: f64,
+    ) -> Lanes {
+        let mut new_messages = [0f64; LANES];
+        for (k, new_message) in new_messages.iter_mut().enumerate() {
+            *new_message = Self::factor_message_update(
+                IsingMessage(messages[k]),
+                IsingMessage(prev_messages[k]),
+                log_p[0][k],
+                log_p[1][k],
+                log_p[2][k],
+                log_p[3][k],
+                &IsingFactorHyperParameters::new(betas[k], gamma),
+            )
+            .0;
+        }
+        new_messages
+    }
+
     fn sample(messages: &[IsingMessage], rng: &mut impl Rng) -> i8;
+
+    /// Adds two weights given by their logarithms in the semiring of a message passing
+    /// type, i.e. computes `log ( exp(x) + exp(y) )` for sum-product and `max(x, y)` for
+    /// max-product. Minus infinity is the neutral element
+    fn log_add(x: f64, y: f64) -> f64;
 }
 
 // ------------------------------------------------------------------------------------------
@@ -80,6 +136,7 @@ pub enum IsingFactor<T: IsingMessagePassingType + ?Sized> {
         log_pud: f64,
         log_pdu: f64,
         log_pdd: f64,
+        group: usize,
     },
     UnitFactor(f64),
 }
@@ -115,6 +172,46 @@ where
             log_pud: -coupling + first_spin_b - second_spin_b,
             log_pdu: -coupling - first_spin_b + second_spin_b,
             log_pdd: coupling - first_spin_b - second_spin_b,
+            group: 0,
+        }
+    }
+
+    /// Assigns a coupling factor to an annealing group, its messages are updated
+    /// with the inverse temperature of this group (see `IsingFactorHyperParameters`),
+    /// thus e.g. hard constraints and soft preferences can be annealed on different
+    /// trajectories. All factors belong to the default group 0 initially. Unit factors
+    /// do not depend on inverse temperature and are not changed
+    ///
+    /// # Arguments
+    ///
+    /// * `group` - An annealing group
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{IsingFactor, SumProduct};
+    ///
+    /// let constraint = IsingFactor::<SumProduct>::new(5., 0., 0.).with_annealing_group(1);
+    /// assert_eq!(constraint.annealing_group(), 1);
+    /// assert_eq!(IsingFactor::<SumProduct>::UnitFactor(0.5).with_annealing_group(1).annealing_group(), 0);
+    /// ```
+    #[inline]
+    pub fn with_annealing_group(mut self, group: usize) -> Self {
+        if let IsingFactor::Coupling {
+            group: old_group, ..
+        } = &mut self
+        {
+            *old_group = group;
+        }
+        self
+    }
+
+    /// Returns an annealing group of a factor
+    #[inline]
+    pub fn annealing_group(&self) -> usize {
+        match self {
+            IsingFactor::Coupling { group, .. } => *group,
+            IsingFactor::UnitFactor(_) => 0,
         }
     }
 }
@@ -146,6 +243,28 @@ where
         src: &[Self::Message],
         dst: &mut [Self::Message],
         parameters: &IsingFactorHyperParameters,
+    ) {
+        // a checked fallback, message passing calls `send_messages_fixed` directly
+        match self {
+            IsingFactor::Coupling { .. } => self.send_messages_fixed::<2>(
+                src.try_into().unwrap(),
+                dst.try_into().unwrap(),
+                parameters,
+            ),
+            IsingFactor::UnitFactor(_) => self.send_messages_fixed::<1>(
+                src.try_into().unwrap(),
+                dst.try_into().unwrap(),
+                parameters,
+            ),
+        }
+    }
+
+    #[inline(always)]
+    fn send_messages_fixed<const N: usize>(
+        &self,
+        src: &[Self::Message; N],
+        dst: &mut [Self::Message; N],
+        parameters: &IsingFactorHyperParameters,
     ) {
         match self {
             IsingFactor::Coupling {
@@ -154,10 +273,15 @@ where
                 log_pud,
                 log_pdu,
                 log_pdd,
-            } => unsafe {
-                let prev_message = *dst.get_unchecked(1);
-                *dst.get_unchecked_mut(1) = T::factor_message_update(
-                    *src.get_unchecked(0),
+                group,
+            } => {
+                let parameters = &parameters.of_group(*group);
+                // N is known at compile time, thus these conversions are free
+                let src: &[IsingMessage; 2] = src.as_slice().try_into().unwrap();
+                let dst: &mut [IsingMessage; 2] = dst.as_mut_slice().try_into().unwrap();
+                let prev_message = dst[1];
+                dst[1] = T::factor_message_update(
+                    src[0],
                     prev_message,
                     *log_puu,
                     *log_pdu,
@@ -165,9 +289,9 @@ where
                     *log_pdd,
                     parameters,
                 );
-                let prev_message = *dst.get_unchecked(0);
-                *dst.get_unchecked_mut(0) = T::factor_message_update(
-                    *src.get_unchecked(1),
+                let prev_message = dst[0];
+                dst[0] = T::factor_message_update(
+                    src[1],
                     prev_message,
                     *log_puu,
                     *log_pud,
@@ -175,10 +299,11 @@ where
                     *log_pdd,
                     parameters,
                 );
-            },
-            IsingFactor::UnitFactor(m) => unsafe {
-                *dst.get_unchecked_mut(0) = IsingMessage(*m);
-            },
+            }
+            IsingFactor::UnitFactor(m) => {
+                let dst: &mut [IsingMessage; 1] = dst.as_mut_slice().try_into().unwrap();
+                dst[0] = IsingMessage(*m);
+            }
         }
     }
 
@@ -191,11 +316,13 @@ where
                 log_pud,
                 log_pdu,
                 log_pdd,
+                ..
             } => {
-                let nu_up_1 = log_sigmoid(unsafe { messages.get_unchecked(0).0 });
-                let nu_up_2 = log_sigmoid(unsafe { messages.get_unchecked(1).0 });
-                let nu_down_1 = log_sigmoid(unsafe { -messages.get_unchecked(0).0 });
-                let nu_down_2 = log_sigmoid(unsafe { -messages.get_unchecked(1).0 });
+                let [message_1, message_2]: &[IsingMessage; 2] = messages.try_into().unwrap();
+                let nu_up_1 = log_sigmoid(message_1.0);
+                let nu_up_2 = log_sigmoid(message_2.0);
+                let nu_down_1 = log_sigmoid(-message_1.0);
+                let nu_down_2 = log_sigmoid(-message_2.0);
                 let marginal = vec![
                     (log_puu + nu_up_1 + nu_up_2).exp(),
                     (log_pud + nu_up_1 + nu_down_2).exp(),
@@ -209,8 +336,9 @@ where
             IsingFactor::UnitFactor(m) => {
                 let log_pu = log_sigmoid(*m);
                 let log_pd = log_sigmoid(-*m);
-                let nu_up = log_sigmoid(unsafe { messages.get_unchecked(0).0 });
-                let nu_down = log_sigmoid(unsafe { messages.get_unchecked(1).0 });
+                let [message]: &[IsingMessage; 1] = messages.try_into().unwrap();
+                let nu_up = log_sigmoid(message.0);
+                let nu_down = log_sigmoid(-message.0);
                 let marginal = vec![(log_pu + nu_up).exp(), (log_pd + nu_down).exp()];
                 let mut marginal = ArrayD::from_shape_vec(IxDyn(&[2]), marginal).unwrap();
                 marginal /= marginal.sum();
@@ -228,25 +356,117 @@ where
                 log_pud,
                 log_pdu,
                 log_pdd,
+                ..
             } => {
                 let factor = vec![log_puu.exp(), log_pud.exp(), log_pdu.exp(), log_pdd.exp()];
                 ArrayD::from_shape_vec(IxDyn(&[2, 2]), factor).unwrap()
             }
             IsingFactor::UnitFactor(m) => {
-                let factor = vec![log_sigmoid(*m), log_sigmoid(-*m)];
-                let mut factor = ArrayD::from_shape_vec(IxDyn(&[2]), factor).unwrap();
-                factor /= factor.sum();
-                factor
+                let factor = vec![sigmoid(*m), sigmoid(-*m)];
+                ArrayD::from_shape_vec(IxDyn(&[2]), factor).unwrap()
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn rescaled(&self, target: &Self::Marginal, current: &Self::Marginal) -> Option<Self> {
+        let shape: &[usize] = match self {
+            IsingFactor::Coupling { .. } => &[2, 2],
+            IsingFactor::UnitFactor(_) => &[2],
+        };
+        if target.shape() != shape || current.shape() != shape {
+            return None;
+        }
+        let log_ratios = target
+            .iter()
+            .zip(current)
+            .map(|(t, c)| {
+                let log_ratio = f64::ln(t / c);
+                (*t > 0f64 && *c > 0f64 && log_ratio.is_finite()).then_some(log_ratio)
+            })
+            .collect::<Option<Vec<_>>>()?;
+        match self {
+            IsingFactor::Coupling {
+                marker,
+                log_puu,
+                log_pud,
+                log_pdu,
+                log_pdd,
+                group,
+            } => Some(IsingFactor::Coupling {
+                marker: *marker,
+                group: *group,
+                log_puu: log_puu + log_ratios[0],
+                log_pud: log_pud + log_ratios[1],
+                log_pdu: log_pdu + log_ratios[2],
+                log_pdd: log_pdd + log_ratios[3],
+            }),
+            IsingFactor::UnitFactor(m) => {
+                Some(IsingFactor::UnitFactor(m + log_ratios[0] - log_ratios[1]))
             }
         }
     }
+
+    #[inline(always)]
+    fn is_contradictory(&self, messages: &[Self::Message]) -> bool {
+        // log-weights and logarithms of factor's elements are kept on the stack,
+        // missing elements of unit factors are padded by zero weights
+        let (log_weights, log_factor) = match self {
+            IsingFactor::Coupling {
+                marker: _,
+                log_puu,
+                log_pud,
+                log_pdu,
+                log_pdd,
+                ..
+            } => {
+                let nu_up_1 = log_sigmoid(messages[0].0);
+                let nu_up_2 = log_sigmoid(messages[1].0);
+                let nu_down_1 = log_sigmoid(-messages[0].0);
+                let nu_down_2 = log_sigmoid(-messages[1].0);
+                let log_weights = [
+                    log_puu + nu_up_1 + nu_up_2,
+                    log_pud + nu_up_1 + nu_down_2,
+                    log_pdu + nu_down_1 + nu_up_2,
+                    log_pdd + nu_down_1 + nu_down_2,
+                ];
+                (log_weights, [*log_puu, *log_pud, *log_pdu, *log_pdd])
+            }
+            IsingFactor::UnitFactor(m) => {
+                let log_pu = log_sigmoid(*m);
+                let log_pd = log_sigmoid(-*m);
+                let log_weights = [
+                    log_pu + log_sigmoid(messages[0].0),
+                    log_pd + log_sigmoid(-messages[0].0),
+                    f64::NEG_INFINITY,
+                    f64::NEG_INFINITY,
+                ];
+                (
+                    log_weights,
+                    [log_pu, log_pd, f64::NEG_INFINITY, f64::NEG_INFINITY],
+                )
+            }
+        };
+        // weights are compared with the largest element of a factor, since
+        // infinitely strong couplings are represented by large finite logarithms
+        let scale = log_factor.into_iter().fold(f64::NEG_INFINITY, f64::max);
+        !log_weights
+            .into_iter()
+            .any(|log_weight| (log_weight - scale).exp() > 0f64)
+    }
 }
 
 // ------------------------------------------------------------------------------------------
 
-/// An Ising variable type
+/// An Ising variable type. A variable may carry its own magnetic field
+/// `exp ( field * s )` and its own damping coefficient overriding the
+/// scheduled one
 #[derive(Debug, Clone, Copy)]
-pub struct IsingVariable<T: IsingMessagePassingType>(PhantomData<T>);
+pub struct IsingVariable<T: IsingMessagePassingType> {
+    marker: PhantomData<T>,
+    field: f64,
+    gamma: Option<f64>,
+}
 
 impl<T: IsingMessagePassingType> IsingVariable<T> {
     /// Creates a new variable.
@@ -259,7 +479,68 @@ impl<T: IsingMessagePassingType> IsingVariable<T> {
     /// ```
     #[inline]
     pub fn new() -> Self {
-        IsingVariable(PhantomData)
+        IsingVariable {
+            marker: PhantomData,
+            field: 0f64,
+            gamma: None,
+        }
+    }
+
+    /// Sets a magnetic field acting on a variable.
+    ///
+    /// # Arguments
+    ///
+    /// * `field` - A magnetic field, the variable's prior has form `exp ( field * s )`
+    ///
+    /// # Example
+    /// ```
+    /// use gmrs::ising::{IsingVariable, SumProduct};
+    ///
+    /// let var = IsingVariable::<SumProduct>::new().with_field(0.5);
+    /// assert_eq!(var.field(), 0.5);
+    /// ```
+    #[inline]
+    pub fn with_field(mut self, field: f64) -> Self {
+        self.field = field;
+        self
+    }
+
+    /// Sets a damping coefficient of a variable's messages that is used
+    /// instead of the one given by a variable scheduler.
+    ///
+    /// # Arguments
+    ///
+    /// * `gamma` - Exponential moving average coefficient
+    ///
+    /// # Example
+    /// ```
+    /// use gmrs::ising::{IsingVariable, SumProduct};
+    ///
+    /// let var = IsingVariable::<SumProduct>::new().with_damping(0.9);
+    /// assert_eq!(var.damping(), Some(0.9));
+    /// ```
+    #[inline]
+    pub fn with_damping(mut self, gamma: f64) -> Self {
+        self.gamma = Some(gamma);
+        self
+    }
+
+    /// Returns a magnetic field acting on a variable
+    #[inline]
+    pub fn field(&self) -> f64 {
+        self.field
+    }
+
+    /// Returns a damping coefficient of a variable if it is set
+    #[inline]
+    pub fn damping(&self) -> Option<f64> {
+        self.gamma
+    }
+
+    // Logarithm of the ratio of up and down beliefs
+    #[inline(always)]
+    fn log_ratio(&self, messages: &[IsingMessage]) -> f64 {
+        2f64 * self.field + messages.iter().map(|x| x.0).sum::<f64>()
     }
 }
 
@@ -280,23 +561,39 @@ where
 
     #[inline(always)]
     fn send_messages(&self, src: &[Self::Message], dst: &mut [Self::Message], parameters: &f64) {
-        let sum_all: f64 = src.iter().map(|x| x.0).sum();
+        let gamma = self.gamma.unwrap_or(*parameters);
+        let sum_all = self.log_ratio(src);
         for (d, s) in dst.iter_mut().zip(src) {
             let prev_message = d.0;
-            d.0 = (1f64 - parameters) * (sum_all - s.0) + parameters * prev_message;
+            d.0 = (1f64 - gamma) * (sum_all - s.0) + gamma * prev_message;
         }
     }
 
     #[inline(always)]
     fn marginal(&self, messages: &[Self::Message]) -> Self::Marginal {
-        let all_sum = messages.iter().map(|x| x.0).sum();
-        let p_up = sigmoid(all_sum);
+        let p_up = sigmoid(self.log_ratio(messages));
         Array1::from_vec(vec![p_up, 1f64 - p_up])
     }
 
     #[inline(always)]
     fn sample(&self, messages: &[Self::Message], rng: &mut impl Rng) -> Self::Sample {
-        T::sample(messages, rng)
+        T::sample(&[IsingMessage(self.log_ratio(messages))], rng)
+    }
+
+    #[inline(always)]
+    fn pinned(&self, messages: &[Self::Message], tolerance: f64) -> Option<Self::Sample> {
+        let log_ratio = self.log_ratio(messages);
+        if 1f64 / (1f64 + f64::exp(log_ratio.abs())) < tolerance {
+            Some(if log_ratio > 0f64 { 1 } else { -1 })
+        } else {
+            None
+        }
+    }
+
+    #[inline(always)]
+    fn marginal_gap(&self, messages: &[Self::Message]) -> Option<f64> {
+        // p_up - p_down = tanh(log(p_up / p_down) / 2)
+        Some((self.log_ratio(messages) / 2f64).tanh().abs())
     }
 
     #[inline(always)]
@@ -307,6 +604,84 @@ where
             other => panic!("Unsupported sample value {other}, must be ether 1 or -1. It is a bug, please open an issue"),
         }
     }
+
+    #[inline(always)]
+    fn sample_to_soft_message(
+        &self,
+        sample: &Self::Sample,
+        probability: f64,
+    ) -> Option<Self::Message> {
+        let log_ratio = f64::ln(probability / (1f64 - probability));
+        match sample {
+            1 => Some(IsingMessage(log_ratio)),
+            -1 => Some(IsingMessage(-log_ratio)),
+            _ => None,
+        }
+    }
+
+    #[inline(always)]
+    fn marginal_to_message(
+        &self,
+        marginal: &Self::Marginal,
+        messages: &[Self::Message],
+    ) -> Option<Self::Message> {
+        match marginal.as_slice() {
+            Some(&[p_up, p_down]) if p_up > 0f64 && p_down > 0f64 => {
+                let log_ratio = f64::ln(p_up / p_down);
+                log_ratio
+                    .is_finite()
+                    .then(|| IsingMessage(log_ratio - self.log_ratio(messages)))
+            }
+            _ => None,
+        }
+    }
+
+    #[inline(always)]
+    fn is_valid_message(&self, message: &Self::Message) -> bool {
+        message.0.is_finite()
+    }
+
+    #[inline(always)]
+    fn sample_index(&self, sample: &Self::Sample) -> Option<usize> {
+        match sample {
+            1 => Some(0),
+            -1 => Some(1),
+            _ => None,
+        }
+    }
+
+    #[inline(always)]
+    fn marginal_discrepancy(
+        &self,
+        marginal: &Self::Marginal,
+        other: &Self::Marginal,
+    ) -> Option<f64> {
+        (marginal.len() == other.len()).then(|| {
+            marginal
+                .iter()
+                .zip(other)
+                .fold(0f64, |acc, (p, q)| acc.max((p - q).abs()))
+        })
+    }
+
+    #[inline(always)]
+    fn marginal_values(&self, marginal: &Self::Marginal) -> Option<Vec<f64>> {
+        Some(marginal.to_vec())
+    }
+}
+
+impl<T> DecodableVariable for IsingVariable<T>
+where
+    T: IsingMessagePassingType + Clone + Debug + Send,
+{
+    #[inline(always)]
+    fn argmax(&self, messages: &[Self::Message]) -> Self::Sample {
+        if self.log_ratio(messages) > 0f64 {
+            1
+        } else {
+            -1
+        }
+    }
 }
 
 // ------------------------------------------------------------------------------------------
@@ -333,9 +708,10 @@ pub fn new_ising_builder<T>(
 where
     T: IsingMessagePassingType + Clone + Debug + Send,
 {
-    let mut fgb = FactorGraphBuilder::new_with_capacity(variables_number, factors_capacity);
-    fgb.fill(IsingVariable::new());
-    fgb
+    FactorGraphBuilder::new_with_variables(
+        vec![IsingVariable::new(); variables_number],
+        factors_capacity,
+    )
 }
 
 /// Crates a new random Ising message initializer.
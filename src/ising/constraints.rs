@@ -0,0 +1,422 @@
+use std::{fmt::Debug, marker::PhantomData};
+
+use ndarray::{ArrayD, IxDyn};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    core::Factor,
+    ising::{
+        common::log_sigmoid, IsingFactorHyperParameters, IsingMessage, IsingMessagePassingType,
+    },
+};
+
+// A magnitude of messages forcing values of variables. It is the same as the magnitude
+// of messages of frozen variables, thus opposite forcing messages sum to zero instead of NaN
+const HARD_LOG_RATIO: f64 = 1e30;
+
+// A maximal number of states of an automaton counting literals of a constraint
+const STATES_NUMBER: usize = 3;
+
+// Logarithms of weights of counts of literals
+type CountLogWeights = [f64; STATES_NUMBER];
+
+// ------------------------------------------------------------------------------------------
+
+/// A hard logical constraint over literals, i.e. binary variables or their negations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum LogicalConstraint {
+    /// The parity of the number of true literals is equal to a given one,
+    /// e.g. a parity check of a linear code has the parity `false`
+    Xor { parity: bool },
+
+    /// At least one literal is true, e.g. a clause of a SAT problem
+    Or,
+
+    /// All literals are true
+    And,
+
+    /// At most one literal is true
+    AtMostOne,
+}
+
+impl LogicalConstraint {
+    /// Checks whether values of literals satisfy a constraint
+    ///
+    /// # Arguments
+    ///
+    /// * `literals` - Values of literals
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::LogicalConstraint;
+    ///
+    /// assert!(LogicalConstraint::Xor { parity: true }.is_satisfied_by(&[true, true, true]));
+    /// assert!(LogicalConstraint::Or.is_satisfied_by(&[false, true]));
+    /// assert!(!LogicalConstraint::And.is_satisfied_by(&[false, true]));
+    /// assert!(!LogicalConstraint::AtMostOne.is_satisfied_by(&[true, true]));
+    /// ```
+    pub fn is_satisfied_by(&self, literals: &[bool]) -> bool {
+        let count = literals
+            .iter()
+            .fold(0, |count, literal| self.add(count, self.count(*literal)));
+        self.is_satisfied(count)
+    }
+
+    // Returns the count of a literal, false literals are counted by AND
+    // and true literals are counted by other constraints
+    #[inline(always)]
+    fn count(&self, literal: bool) -> usize {
+        match self {
+            LogicalConstraint::And => !literal as usize,
+            _ => literal as usize,
+        }
+    }
+
+    // Adds counts of literals, a sum is reduced to the states distinguished by a constraint
+    #[inline(always)]
+    fn add(&self, lhs: usize, rhs: usize) -> usize {
+        match self {
+            LogicalConstraint::Xor { .. } => (lhs + rhs) % 2,
+            LogicalConstraint::Or | LogicalConstraint::And => (lhs + rhs).min(1),
+            LogicalConstraint::AtMostOne => (lhs + rhs).min(2),
+        }
+    }
+
+    // Checks whether the count of all literals satisfies a constraint
+    #[inline(always)]
+    fn is_satisfied(&self, count: usize) -> bool {
+        match self {
+            LogicalConstraint::Xor { parity } => count == *parity as usize,
+            LogicalConstraint::Or => count == 1,
+            LogicalConstraint::And => count == 0,
+            LogicalConstraint::AtMostOne => count <= 1,
+        }
+    }
+}
+
+// Returns logarithms of probabilities of a literal to be true and false given a message
+#[inline(always)]
+fn literal_log_weights(message: &IsingMessage, negated: bool) -> [f64; 2] {
+    let log_weights = [log_sigmoid(message.0), log_sigmoid(-message.0)];
+    if negated {
+        [log_weights[1], log_weights[0]]
+    } else {
+        log_weights
+    }
+}
+
+// ------------------------------------------------------------------------------------------
+
+/// A factor of an Ising factor graph equal to 1 if a logical constraint is satisfied and 0
+/// otherwise. A literal is true if a spin is up, i.e. `s = 1`, or if a spin is down for
+/// negated literals. Messages are computed exactly in the logarithmic domain by counting
+/// literals, thus the cost of an update is linear in the degree, and infinite incoming
+/// log-likelihood ratios are supported. Outgoing messages forcing a value of a variable
+/// are saturated to the magnitude of messages of frozen variables
+///
+/// # Notes
+///
+/// A constraint does not depend on the inverse temperature, while the damping
+/// coefficient is applied to its messages. A constraint can be mixed with coupling
+/// factors by [`factor_enum`](crate::factor_enum), e.g. to add fields or soft terms.
+/// `Factor::from_message` creates a degree one constraint forcing the sign of a message,
+/// thus `IsingFactor` should be the first variant of such enum to represent soft messages
+///
+/// # Example
+///
+/// ```
+/// use gmrs::core::{Factor, FactorGraphBuilder};
+/// use gmrs::factor_enum;
+/// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+/// use gmrs::ising::{ConstraintFactor, IsingFactor, IsingMessage, IsingVariable, LogicalConstraint, SumProduct};
+///
+/// factor_enum! {
+///     enum ClauseOrField {
+///         Field(IsingFactor<SumProduct>),
+///         Clause(ConstraintFactor<SumProduct>),
+///     }
+/// }
+///
+/// let mut initializer = || IsingMessage(0.);
+/// let mut fgb = FactorGraphBuilder::<ClauseOrField, IsingVariable<SumProduct>>::new_with_variables(
+///     vec![IsingVariable::new(); 2],
+///     2,
+/// );
+/// // the clause (x_0 or not x_1) with x_1 likely true
+/// let clause = ConstraintFactor::new(LogicalConstraint::Or, 2).with_negations(&[false, true]);
+/// fgb.add_factor(clause.into(), &[0, 1], &mut initializer).unwrap();
+/// fgb.add_factor(IsingFactor::UnitFactor(2.).into(), &[1], &mut initializer).unwrap();
+/// let mut fg = fgb.build();
+/// let factor_scheduler = get_standard_factor_scheduler(0.);
+/// let variable_scheduler = get_standard_variable_scheduler(0.);
+/// let _ = fg.run_message_passing_parallel(100, 0, 1e-10, &factor_scheduler, &variable_scheduler).unwrap();
+/// // x_0 is false only if x_1 is false
+/// let p_false = 1. / (1. + f64::exp(2.));
+/// let p_up = 1. / (1. + p_false);
+/// assert!((fg.variable_marginals()[0][0] - p_up).abs() < 1e-10);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConstraintFactor<T: IsingMessagePassingType> {
+    marker: PhantomData<T>,
+    constraint: LogicalConstraint,
+    negations: Vec<bool>,
+}
+
+impl<T> ConstraintFactor<T>
+where
+    T: IsingMessagePassingType + Debug + Send,
+{
+    /// Creates a constraint over non-negated literals
+    ///
+    /// # Arguments
+    ///
+    /// * `constraint` - A logical constraint
+    /// * `degree` - A number of variables of a constraint
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::Factor;
+    /// use gmrs::ising::{ConstraintFactor, LogicalConstraint, SumProduct};
+    ///
+    /// let parity_check = ConstraintFactor::<SumProduct>::new(LogicalConstraint::Xor { parity: false }, 4);
+    /// assert_eq!(parity_check.degree(), 4);
+    /// ```
+    #[inline]
+    pub fn new(constraint: LogicalConstraint, degree: usize) -> Self {
+        ConstraintFactor {
+            marker: PhantomData,
+            constraint,
+            negations: vec![false; degree],
+        }
+    }
+
+    /// Sets which literals are negations of variables
+    ///
+    /// # Arguments
+    ///
+    /// * `negations` - Flags of negated literals in the order of variables of a constraint
+    ///
+    /// # Notes
+    ///
+    /// If the number of flags is not equal to the degree of a constraint, the method panics
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{ConstraintFactor, LogicalConstraint, SumProduct};
+    ///
+    /// // not x_0 or x_1, i.e. x_0 implies x_1
+    /// let implication = ConstraintFactor::<SumProduct>::new(LogicalConstraint::Or, 2).with_negations(&[true, false]);
+    /// assert!(implication.is_satisfied(&[1, 1]));
+    /// assert!(!implication.is_satisfied(&[1, -1]));
+    /// ```
+    #[inline]
+    pub fn with_negations(mut self, negations: &[bool]) -> Self {
+        assert_eq!(
+            negations.len(),
+            self.negations.len(),
+            "Number of negation flags must be equal to the degree of a constraint"
+        );
+        self.negations.copy_from_slice(negations);
+        self
+    }
+
+    /// Returns a logical constraint
+    #[inline]
+    pub fn constraint(&self) -> LogicalConstraint {
+        self.constraint
+    }
+
+    /// Returns flags of negated literals
+    #[inline]
+    pub fn negations(&self) -> &[bool] {
+        &self.negations
+    }
+
+    /// Checks whether values of spins satisfy a constraint
+    ///
+    /// # Arguments
+    ///
+    /// * `spins` - Values of spins of a constraint, either 1 or -1
+    pub fn is_satisfied(&self, spins: &[i8]) -> bool {
+        assert_eq!(
+            spins.len(),
+            self.negations.len(),
+            "Number of spins values must be equal to the degree of a constraint"
+        );
+        let literals: Vec<_> = spins
+            .iter()
+            .zip(&self.negations)
+            .map(|(spin, negated)| (*spin == 1) != *negated)
+            .collect();
+        self.constraint.is_satisfied_by(&literals)
+    }
+
+    // Returns logarithms of probabilities of literals to be true and false given messages
+    #[inline(always)]
+    fn literals_log_weights(&self, messages: &[IsingMessage]) -> Vec<[f64; 2]> {
+        messages
+            .iter()
+            .zip(&self.negations)
+            .map(|(message, negated)| literal_log_weights(message, *negated))
+            .collect()
+    }
+
+    // Accumulates a literal into logarithms of weights of counts
+    #[inline(always)]
+    fn accumulate(&self, counts: &CountLogWeights, literal: &[f64; 2]) -> CountLogWeights {
+        let mut accumulated = [f64::NEG_INFINITY; STATES_NUMBER];
+        for (count, log_weight) in counts.iter().enumerate() {
+            if *log_weight == f64::NEG_INFINITY {
+                continue;
+            }
+            for (value, literal_log_weight) in [true, false].into_iter().zip(literal) {
+                let count = self.constraint.add(count, self.constraint.count(value));
+                accumulated[count] =
+                    T::log_add(accumulated[count], log_weight + literal_log_weight);
+            }
+        }
+        accumulated
+    }
+
+    // Computes logarithms of weights of satisfying assignments given that a literal is
+    // true and false, literals except the given one are weighted by their probabilities
+    fn extrinsic_log_weights(&self, literals: &[[f64; 2]]) -> Vec<[f64; 2]> {
+        let mut empty = [f64::NEG_INFINITY; STATES_NUMBER];
+        empty[0] = 0f64;
+        // counts of literals preceding and following a given one
+        let mut prefixes = Vec::with_capacity(literals.len() + 1);
+        prefixes.push(empty);
+        for literal in literals {
+            prefixes.push(self.accumulate(prefixes.last().unwrap(), literal));
+        }
+        let mut suffixes = vec![empty; literals.len() + 1];
+        for (position, literal) in literals.iter().enumerate().rev() {
+            suffixes[position] = self.accumulate(&suffixes[position + 1], literal);
+        }
+        let constraint = &self.constraint;
+        (0..literals.len())
+            .map(|position| {
+                [true, false].map(|value| {
+                    let mut log_weight = f64::NEG_INFINITY;
+                    for (prefix, prefix_log_weight) in prefixes[position].iter().enumerate() {
+                        for (suffix, suffix_log_weight) in suffixes[position + 1].iter().enumerate()
+                        {
+                            let count = constraint
+                                .add(constraint.add(prefix, constraint.count(value)), suffix);
+                            if constraint.is_satisfied(count) {
+                                log_weight =
+                                    T::log_add(log_weight, prefix_log_weight + suffix_log_weight);
+                            }
+                        }
+                    }
+                    log_weight
+                })
+            })
+            .collect()
+    }
+
+    // Returns logarithms of an unnormalized joint distribution of variables, spin up
+    // corresponds to the index 0 along an axis
+    fn joint_log_weights(&self, literals: &[[f64; 2]]) -> ArrayD<f64> {
+        let degree = self.negations.len();
+        let shape = vec![2; degree];
+        ArrayD::from_shape_fn(IxDyn(&shape), |index| {
+            let values: Vec<_> = (0..degree)
+                .map(|position| (index[position] == 0) != self.negations[position])
+                .collect();
+            if self.constraint.is_satisfied_by(&values) {
+                values
+                    .iter()
+                    .zip(literals)
+                    .map(|(value, literal)| literal[!*value as usize])
+                    .sum()
+            } else {
+                f64::NEG_INFINITY
+            }
+        })
+    }
+}
+
+impl<T> Factor for ConstraintFactor<T>
+where
+    T: IsingMessagePassingType + Clone + Debug + Send,
+{
+    type Message = IsingMessage;
+    type Marginal = ArrayD<f64>;
+    type Parameters = IsingFactorHyperParameters;
+
+    #[inline(always)]
+    fn from_message(message: &Self::Message) -> Self {
+        ConstraintFactor::new(LogicalConstraint::And, 1).with_negations(&[message.0 < 0f64])
+    }
+
+    #[inline(always)]
+    fn degree(&self) -> usize {
+        self.negations.len()
+    }
+
+    fn send_messages(
+        &self,
+        src: &[Self::Message],
+        dst: &mut [Self::Message],
+        parameters: &IsingFactorHyperParameters,
+    ) {
+        let literals = self.literals_log_weights(src);
+        let gamma = parameters.gamma;
+        for ((message, [log_true, log_false]), negated) in dst
+            .iter_mut()
+            .zip(self.extrinsic_log_weights(&literals))
+            .zip(&self.negations)
+        {
+            // if no assignment of other literals is allowed, a message is uninformative
+            let log_ratio = if log_true == f64::NEG_INFINITY && log_false == f64::NEG_INFINITY {
+                0f64
+            } else {
+                (log_true - log_false).clamp(-HARD_LOG_RATIO, HARD_LOG_RATIO)
+            };
+            let log_ratio = if *negated { -log_ratio } else { log_ratio };
+            message.0 = (1f64 - gamma) * log_ratio + gamma * message.0;
+        }
+    }
+
+    fn marginal(&self, messages: &[Self::Message]) -> Self::Marginal {
+        let mut log_weights = self.joint_log_weights(&self.literals_log_weights(messages));
+        let max = log_weights
+            .iter()
+            .copied()
+            .fold(f64::NEG_INFINITY, f64::max);
+        if max == f64::NEG_INFINITY {
+            // messages contradict a constraint, the factor itself is normalized
+            let mut factor = self.factor();
+            factor /= factor.sum();
+            return factor;
+        }
+        log_weights.mapv_inplace(|log_weight| (log_weight - max).exp());
+        let sum = log_weights.sum();
+        log_weights / sum
+    }
+
+    fn factor(&self) -> Self::Marginal {
+        let uninformative = vec![[0f64; 2]; self.negations.len()];
+        self.joint_log_weights(&uninformative).mapv(f64::exp)
+    }
+
+    fn is_contradictory(&self, messages: &[Self::Message]) -> bool {
+        let mut counts = [f64::NEG_INFINITY; STATES_NUMBER];
+        counts[0] = 0f64;
+        for (message, negated) in messages.iter().zip(&self.negations) {
+            counts = self.accumulate(&counts, &literal_log_weights(message, *negated));
+        }
+        // forced values are represented by large finite messages, thus weights are
+        // compared with zero after exponentiation
+        !counts.iter().enumerate().any(|(count, log_weight)| {
+            self.constraint.is_satisfied(count) && log_weight.exp() > 0f64
+        })
+    }
+}
@@ -0,0 +1,168 @@
+use std::{fmt::Debug, mem::swap};
+
+use crate::{
+    core::{FGError, FGResult, FactorGraph},
+    ising::{IsingFactor, IsingMessage, IsingMessagePassingType, IsingVariable},
+};
+
+// ------------------------------------------------------------------------------------------
+
+impl<T: IsingMessagePassingType> IsingFactor<T> {
+    // Flips a spin at a given position of a factor, i.e. swaps its up and down states
+    fn flip(&mut self, position: usize) {
+        match self {
+            IsingFactor::Coupling {
+                log_puu,
+                log_pud,
+                log_pdu,
+                log_pdd,
+                ..
+            } => {
+                if position == 0 {
+                    swap(log_puu, log_pdu);
+                    swap(log_pud, log_pdd);
+                } else {
+                    swap(log_puu, log_pud);
+                    swap(log_pdu, log_pdd);
+                }
+            }
+            IsingFactor::UnitFactor(message) => *message = -*message,
+        }
+    }
+}
+
+impl<T> FactorGraph<IsingFactor<T>, IsingVariable<T>>
+where
+    T: IsingMessagePassingType + Clone + Debug + Send,
+{
+    /// Contracts a pair of spins into a single one by substituting
+    /// `s_removed = s_kept` (or `s_removed = -s_kept` for antiparallel spins)
+    /// into a model. The field of a removed spin is added to the field of a kept one,
+    /// factors of a removed spin are attached to a kept spin, and coupling factors
+    /// joining both spins are removed with their contribution added to the field
+    /// of a kept spin. Messages of reattached edges are kept, thus message
+    /// passing after contraction is warm started
+    ///
+    /// # Arguments
+    ///
+    /// * `kept` - The index of a kept spin
+    /// * `removed` - The index of a removed spin
+    /// * `antiparallel` - Whether spins are contracted antiparallel
+    ///
+    /// # Notes
+    ///
+    /// Indices of spins following a removed one and indices of factors following
+    /// removed ones are shifted down. A coupling joining both spins turns into a field
+    /// that is not scaled by inverse temperatures of hyper-parameters, thus contraction
+    /// is exact for couplings with the unit inverse temperature. Contracting spins of
+    /// different connected components may create cycles
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    ///
+    /// // A chain of 3 spins with a field on the last spin
+    /// let mut fgb = new_ising_builder::<SumProduct>(3, 3);
+    /// fgb.add_factor(IsingFactor::new(1., 0.2, 0.), &[0, 1], &mut initializer).unwrap();
+    /// fgb.add_factor(IsingFactor::new(-0.5, 0., 0.), &[1, 2], &mut initializer).unwrap();
+    /// fgb.add_factor(IsingFactor::UnitFactor(0.6), &[2], &mut initializer).unwrap();
+    /// let mut fg = fgb.build();
+    ///
+    /// // The middle spin is merged into the last one
+    /// fg.contract_variables(2, 1, false).unwrap();
+    /// assert_eq!(fg.get_variable_degrees(), vec![1, 2]);
+    /// let couplings = fg.coupling_matrix().to_dense();
+    /// assert!((couplings[[0, 1]] - 1.).abs() < 1e-12);
+    /// let fields = fg.local_fields();
+    /// assert!((fields[0] - 0.2).abs() < 1e-12);
+    /// assert!((fields[1] - 0.3).abs() < 1e-12);
+    /// ```
+    pub fn contract_variables(
+        &mut self,
+        kept: usize,
+        removed: usize,
+        antiparallel: bool,
+    ) -> FGResult<()> {
+        let variables_number = self.variables.len();
+        if let Some(var_index) = [kept, removed]
+            .into_iter()
+            .find(|var_index| *var_index >= variables_number)
+        {
+            return Err(FGError::OutOfRangeVariable(variables_number, var_index));
+        }
+        if kept == removed {
+            return Err(FGError::ContractionError(kept));
+        }
+        let sign = if antiparallel { -1f64 } else { 1f64 };
+        let mut field =
+            self.variables[kept].variable.field() + sign * self.variables[removed].variable.field();
+        let mut is_removed = vec![false; self.factors.len()];
+        // edges of a removed spin reattached to a kept one
+        let mut moved_edges = Vec::new();
+        let removed_node = &self.variables[removed];
+        for (edge, (fac_index, position)) in removed_node
+            .fac_node_indices
+            .iter()
+            .zip(&removed_node.fac_node_receiver_indices)
+            .enumerate()
+        {
+            let node = &mut self.factors[*fac_index];
+            if node.var_node_indices.contains(&kept) {
+                if let IsingFactor::Coupling {
+                    log_puu,
+                    log_pud,
+                    log_pdu,
+                    log_pdd,
+                    ..
+                } = node.factor
+                {
+                    let log_factor = [[log_puu, log_pud], [log_pdu, log_pdd]];
+                    // the logarithm of a factor for a kept spin state
+                    let log_value = |state: usize| {
+                        let other = if antiparallel { 1 - state } else { state };
+                        if *position == 0 {
+                            log_factor[other][state]
+                        } else {
+                            log_factor[state][other]
+                        }
+                    };
+                    field += (log_value(0) - log_value(1)) / 2f64;
+                }
+                is_removed[*fac_index] = true;
+                continue;
+            }
+            let (mut message, mut receiver) =
+                (removed_node.messages[edge], removed_node.receivers[edge]);
+            if antiparallel {
+                node.factor.flip(*position);
+                node.receivers[*position] = IsingMessage(-node.receivers[*position].0);
+                node.messages[*position] = IsingMessage(-node.messages[*position].0);
+                node.damping.clear();
+                message = IsingMessage(-message.0);
+                receiver = IsingMessage(-receiver.0);
+            }
+            node.var_node_indices[*position] = kept;
+            moved_edges.push((*fac_index, *position, message, receiver));
+        }
+        let removed_node = &mut self.variables[removed];
+        removed_node.truncate(0);
+        removed_node.damping.clear();
+        let kept_node = &mut self.variables[kept];
+        kept_node.variable = kept_node.variable.clone().with_field(field);
+        kept_node.invalidate_marginal();
+        kept_node.damping.clear();
+        for (fac_index, position, message, receiver) in moved_edges {
+            self.factors[fac_index].var_node_receiver_indices[position] = kept_node.degree();
+            kept_node.fac_node_indices.push(fac_index);
+            kept_node.fac_node_receiver_indices.push(position);
+            kept_node.messages.push(message);
+            kept_node.receivers.push(receiver);
+        }
+        self.remove_nodes(&is_removed, Some(removed));
+        Ok(())
+    }
+}
@@ -0,0 +1,223 @@
+use std::fmt::Debug;
+
+use rayon::prelude::{
+    IndexedParallelIterator, IntoParallelRefIterator, IntoParallelRefMutIterator, ParallelIterator,
+};
+
+use crate::{
+    core::{nan_max, FGError, FGResult, FactorGraph, MessagePassingInfo},
+    ising::{
+        batched::IsingBatch, IsingFactor, IsingFactorHyperParameters, IsingMessage,
+        IsingMessagePassingType, IsingVariable,
+    },
+};
+
+// ------------------------------------------------------------------------------------------
+
+impl IsingBatch {
+    // Updates all factor to variable messages in parallel over edges
+    // and returns the maximal discrepancy
+    fn factors_sweep_edges<T: IsingMessagePassingType>(
+        &mut self,
+        parameters: &IsingFactorHyperParameters,
+    ) -> f64 {
+        let c_number = self.couplings_number;
+        let (fv_couplings, fv_units) = self.fv.split_at_mut(2 * c_number);
+        let (fv_first, fv_second) = fv_couplings.split_at_mut(c_number);
+        let (vf_first, vf_second) = self.vf[..(2 * c_number)].split_at(c_number);
+        let (log_puu, log_pud, log_pdu, log_pdd) =
+            (&self.log_puu, &self.log_pud, &self.log_pdu, &self.log_pdd);
+        let groups = &self.groups;
+        let second_discrepancy = fv_second
+            .par_iter_mut()
+            .enumerate()
+            .map(|(c, fv)| {
+                let parameters = &parameters.of_group(groups[c]);
+                let new_message = T::factor_message_update(
+                    IsingMessage(vf_first[c]),
+                    IsingMessage(*fv),
+                    log_puu[c],
+                    log_pdu[c],
+                    log_pud[c],
+                    log_pdd[c],
+                    parameters,
+                )
+                .0;
+                let discrepancy = (new_message - *fv).abs();
+                *fv = new_message;
+                discrepancy
+            })
+            .reduce(|| 0f64, nan_max);
+        let first_discrepancy = fv_first
+            .par_iter_mut()
+            .enumerate()
+            .map(|(c, fv)| {
+                let parameters = &parameters.of_group(groups[c]);
+                let new_message = T::factor_message_update(
+                    IsingMessage(vf_second[c]),
+                    IsingMessage(*fv),
+                    log_puu[c],
+                    log_pud[c],
+                    log_pdu[c],
+                    log_pdd[c],
+                    parameters,
+                )
+                .0;
+                let discrepancy = (new_message - *fv).abs();
+                *fv = new_message;
+                discrepancy
+            })
+            .reduce(|| 0f64, nan_max);
+        let units_discrepancy = fv_units
+            .par_iter_mut()
+            .zip(self.unit_messages.par_iter())
+            .map(|(fv, message)| {
+                let discrepancy = (message - *fv).abs();
+                *fv = *message;
+                discrepancy
+            })
+            .reduce(|| 0f64, nan_max);
+        nan_max(
+            nan_max(first_discrepancy, second_discrepancy),
+            units_discrepancy,
+        )
+    }
+
+    // Updates all variable to factor messages in parallel over edges
+    // and returns the maximal discrepancy
+    fn variables_sweep_edges(&mut self, parameters: f64) -> f64 {
+        let (fv, var_edges, var_offsets) = (&self.fv, &self.var_edges, &self.var_offsets);
+        let sums: Vec<f64> = self
+            .var_fields
+            .par_iter()
+            .enumerate()
+            .map(|(var_index, field)| {
+                field
+                    + var_edges[var_offsets[var_index]..var_offsets[var_index + 1]]
+                        .iter()
+                        .map(|edge| fv[*edge])
+                        .sum::<f64>()
+            })
+            .collect();
+        let var_gammas = &self.var_gammas;
+        self.vf
+            .par_iter_mut()
+            .zip(self.edge_variables.par_iter())
+            .zip(fv.par_iter())
+            .map(|((vf, var_index), fv)| {
+                let gamma = var_gammas[*var_index].unwrap_or(parameters);
+                let new_message = (1f64 - gamma) * (sums[*var_index] - fv) + gamma * *vf;
+                let discrepancy = (new_message - *vf).abs();
+                *vf = new_message;
+                discrepancy
+            })
+            .reduce(|| 0f64, nan_max)
+    }
+
+    // Runs message passing sweeps over edges, a certificate of exactness
+    // is left empty since it depends on a factor graph
+    fn run_edges<T: IsingMessagePassingType>(
+        &mut self,
+        max_iterations_number: usize,
+        min_iterations_number: usize,
+        threshold: f64,
+        factor_scheduler: &impl Fn(usize) -> IsingFactorHyperParameters,
+        variable_scheduler: &impl Fn(usize) -> f64,
+    ) -> FGResult<MessagePassingInfo> {
+        let mut last_discrepancy = f64::MAX;
+        let mut discrepancy_dynamics = Vec::with_capacity(max_iterations_number);
+        for i in 0..max_iterations_number {
+            let factors_discrepancy = self.factors_sweep_edges::<T>(&factor_scheduler(i));
+            let variables_discrepancy = self.variables_sweep_edges(variable_scheduler(i));
+            let max_discrepancy = nan_max(factors_discrepancy, variables_discrepancy);
+            discrepancy_dynamics.push(max_discrepancy);
+            last_discrepancy = max_discrepancy;
+            if (max_discrepancy < threshold) && (i + 1 >= min_iterations_number) {
+                return Ok(MessagePassingInfo {
+                    iterations_number: i,
+                    discrepancy_dynamics,
+                    last_discrepancy,
+                    certificate: None,
+                    marginal_history: None,
+                    observables: Vec::new(),
+                });
+            }
+        }
+        Err(FGError::MessagePassingError {
+            iterations_number: max_iterations_number,
+            discrepancy_dynamics,
+            last_discrepancy,
+        })
+    }
+}
+
+impl<T> FactorGraph<IsingFactor<T>, IsingVariable<T>>
+where
+    T: IsingMessagePassingType + Clone + Debug + Send,
+{
+    /// Runs Ising message passing with the same update rules and convergence
+    /// criterion as [`FactorGraph::run_message_passing_parallel`], but the unit
+    /// of parallel work is a directed edge rather than a node. Messages are stored
+    /// in flat arrays of edges with the CSR adjacency of variables, factor to variable
+    /// and variable to factor messages are updated in parallel over edges, thus work is
+    /// split evenly even if the degree distribution is heavy-tailed and a few hub
+    /// variables have most of the edges. Messages are written back to a factor graph
+    /// when the method returns, also on failure
+    ///
+    /// # Arguments
+    ///
+    /// * `max_iterations_number` - A maximal number of iterations
+    /// * `min_iterations_number` - A minimal number of iterations
+    /// * `threshold` - A threshold specifying the convergence criterion
+    /// * `factor_scheduler` - A scheduler of a factor's messages update rule hyper-parameters
+    /// * `variable_scheduler` - A scheduler of a variable's messages update rule hyper-parameters
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use rand::thread_rng;
+    ///
+    /// // A star with the hub spin 0
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let mut fgb = new_ising_builder::<SumProduct>(100, 99);
+    /// for i in 1..100 {
+    ///     fgb.add_factor(IsingFactor::new(0.1, 0., 0.1), &[0, i], &mut initializer).unwrap();
+    /// }
+    /// let mut fg = fgb.build();
+    /// let mut reference_fg = fg.clone();
+    /// let factor_scheduler = get_standard_factor_scheduler(0.);
+    /// let variable_scheduler = get_standard_variable_scheduler(0.);
+    /// let info = fg.run_message_passing_edge_parallel(1000, 0, 1e-10, &factor_scheduler, &variable_scheduler).unwrap();
+    /// let reference_info = reference_fg.run_message_passing_parallel(1000, 0, 1e-10, &factor_scheduler, &variable_scheduler).unwrap();
+    /// assert_eq!(info.iterations_number, reference_info.iterations_number);
+    /// ```
+    pub fn run_message_passing_edge_parallel(
+        &mut self,
+        max_iterations_number: usize,
+        min_iterations_number: usize,
+        threshold: f64,
+        factor_scheduler: &(impl Fn(usize) -> IsingFactorHyperParameters + Sync),
+        variable_scheduler: &(impl Fn(usize) -> f64 + Sync),
+    ) -> FGResult<MessagePassingInfo> {
+        let mut batch = IsingBatch::new(self);
+        let mut run = || {
+            batch.run_edges::<T>(
+                max_iterations_number,
+                min_iterations_number,
+                threshold,
+                factor_scheduler,
+                variable_scheduler,
+            )
+        };
+        let result = match self.thread_pool.clone() {
+            Some(thread_pool) => thread_pool.install(run),
+            None => run(),
+        };
+        batch.write_back(self);
+        let mut info = result?;
+        info.certificate = self.exactness_certificate();
+        Ok(info)
+    }
+}
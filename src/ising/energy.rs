@@ -0,0 +1,236 @@
+use std::fmt::Debug;
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
+
+use crate::{
+    core::{FGBuilderResult, FactorGraphBuilder},
+    ising::{IsingFactor, IsingMessage, IsingMessagePassingType, IsingVariable},
+};
+
+// ------------------------------------------------------------------------------------------
+
+/// Crates an Ising factor graph builder for the model
+/// `exp ( sum_{i<j} J_ij s_i s_j + sum_i b_i s_i )` by querying
+/// couplings of all pairs of spins `i < j` and fields of all spins.
+/// Pairs are queried in parallel if the `parallel` feature is enabled,
+/// factors are added in the lexicographic order of pairs regardless of it.
+///
+/// # Arguments
+///
+/// * `variables_number` - A number of spins
+/// * `coupling` - Returns a coupling `J_ij` of spins `i < j`,
+///   None if spins do not interact
+/// * `field` - Returns a magnetic field `b_i` of a spin `i`,
+///   it is set as a field of a variable
+/// * `initializer` - An initializer of messages
+///
+/// # Notes
+///
+/// The number of queries grows quadratically with the number of spins,
+/// use [`from_sparse_energy_fn`] if candidate pairs are known in advance
+///
+/// # Example
+///
+/// ```
+/// use gmrs::ising::{from_energy_fn, random_message_initializer, SumProduct};
+/// use rand::thread_rng;
+///
+/// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+/// // a ring of 10 spins in a staggered field
+/// let fgb = from_energy_fn::<SumProduct>(
+///     10,
+///     |i, j| (j - i == 1 || j - i == 9).then_some(0.5),
+///     |i| if i % 2 == 0 { 0.1 } else { -0.1 },
+///     &mut initializer,
+/// )
+/// .unwrap();
+/// let fg = fgb.build();
+/// assert_eq!(fg.coupling_matrix().nnz(), 20);
+/// assert!((fg.local_fields()[1] + 0.1).abs() < 1e-12);
+/// ```
+pub fn from_energy_fn<T>(
+    variables_number: usize,
+    coupling: impl Fn(usize, usize) -> Option<f64> + Sync,
+    field: impl Fn(usize) -> f64 + Sync,
+    initializer: &mut impl FnMut() -> IsingMessage,
+) -> FGBuilderResult<FactorGraphBuilder<IsingFactor<T>, IsingVariable<T>>>
+where
+    T: IsingMessagePassingType + Clone + Debug + Send,
+{
+    let coupling = &coupling;
+    let query = |lhs: usize| {
+        ((lhs + 1)..variables_number)
+            .filter_map(move |rhs| coupling(lhs, rhs).map(|value| (lhs, rhs, value)))
+    };
+    #[cfg(feature = "parallel")]
+    let couplings: Vec<_> = (0..variables_number)
+        .into_par_iter()
+        .flat_map_iter(query)
+        .collect();
+    #[cfg(not(feature = "parallel"))]
+    let couplings: Vec<_> = (0..variables_number).flat_map(query).collect();
+    build(variables_number, couplings, field, initializer)
+}
+
+/// Crates an Ising factor graph builder for the model
+/// `exp ( sum_{i<j} J_ij s_i s_j + sum_i b_i s_i )` similarly to
+/// [`from_energy_fn`], but queries couplings only of given pairs of spins.
+///
+/// # Arguments
+///
+/// * `variables_number` - A number of spins
+/// * `pairs` - A sparsity pattern, i.e. pairs of distinct spins that may interact,
+///   factors are added in the order of pairs
+/// * `coupling` - Returns a coupling of a pair of spins, None if spins do not interact
+/// * `field` - Returns a magnetic field `b_i` of a spin `i`,
+///   it is set as a field of a variable
+/// * `initializer` - An initializer of messages
+///
+/// # Notes
+///
+/// If a pair contains an index out of range of spins, the function returns an error
+///
+/// # Example
+///
+/// ```
+/// use gmrs::ising::{from_sparse_energy_fn, random_message_initializer, SumProduct};
+/// use rand::thread_rng;
+///
+/// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+/// // a 3x3 lattice with couplings decaying with the distance from the center
+/// let side = 3;
+/// let mut pairs = Vec::new();
+/// for i in 0..side {
+///     for j in 0..(side - 1) {
+///         pairs.push((i * side + j, i * side + j + 1));
+///         pairs.push((j * side + i, (j + 1) * side + i));
+///     }
+/// }
+/// let fgb = from_sparse_energy_fn::<SumProduct>(
+///     side * side,
+///     &pairs,
+///     |i, j| Some(1. / (1. + (i as f64 - 4.).abs() + (j as f64 - 4.).abs())),
+///     |_| 0.,
+///     &mut initializer,
+/// )
+/// .unwrap();
+/// assert_eq!(fgb.build().coupling_matrix().nnz(), 24);
+/// ```
+pub fn from_sparse_energy_fn<T>(
+    variables_number: usize,
+    pairs: &[(usize, usize)],
+    coupling: impl Fn(usize, usize) -> Option<f64> + Sync,
+    field: impl Fn(usize) -> f64 + Sync,
+    initializer: &mut impl FnMut() -> IsingMessage,
+) -> FGBuilderResult<FactorGraphBuilder<IsingFactor<T>, IsingVariable<T>>>
+where
+    T: IsingMessagePassingType + Clone + Debug + Send,
+{
+    let query = |(lhs, rhs): &(usize, usize)| coupling(*lhs, *rhs).map(|value| (*lhs, *rhs, value));
+    #[cfg(feature = "parallel")]
+    let couplings: Vec<_> = pairs.par_iter().filter_map(query).collect();
+    #[cfg(not(feature = "parallel"))]
+    let couplings: Vec<_> = pairs.iter().filter_map(query).collect();
+    build(variables_number, couplings, field, initializer)
+}
+
+/// Crates an Ising factor graph builder for the model
+/// `exp ( sum_e J_e s_{i_e} s_{j_e} + sum_i b_i s_i )` from an edge list,
+/// couplings of edges and fields of spins. Couplings are added as factors
+/// in the order of edges, fields are added after them as unit factors
+///
+/// # Arguments
+///
+/// * `edges` - Pairs of spins `(i_e, j_e)` coupled by edges
+/// * `couplings` - Couplings `J_e` of edges
+/// * `fields` - Magnetic fields `b_i` of spins, their number is the number of spins
+/// * `initializer` - An initializer of messages
+///
+/// # Notes
+///
+/// The number of couplings must be equal to the number of edges, otherwise the function
+/// panics. If an edge contains an index out of range of spins, the function returns
+/// an error. The factor of an edge `e` has index `e` and the unit factor of a spin `i`
+/// has index `edges.len() + i`
+///
+/// # Example
+///
+/// ```
+/// use gmrs::ising::{builder_from_edges, random_message_initializer, SumProduct};
+/// use rand::thread_rng;
+///
+/// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+/// // a triangle in a field
+/// let fgb = builder_from_edges::<SumProduct>(
+///     &[[0, 1], [1, 2], [2, 0]],
+///     &[0.5, 0.5, -1.],
+///     &[0.1, 0.2, 0.3],
+///     &mut initializer,
+/// )
+/// .unwrap();
+/// let fg = fgb.build();
+/// assert_eq!(fg.get_variable_degrees(), vec![3, 3, 3]);
+/// assert_eq!(fg.coupling_matrix().to_dense()[[2, 0]], -1.);
+/// assert!((fg.local_fields()[2] - 0.3).abs() < 1e-12);
+/// ```
+pub fn builder_from_edges<T>(
+    edges: &[[usize; 2]],
+    couplings: &[f64],
+    fields: &[f64],
+    initializer: &mut impl FnMut() -> IsingMessage,
+) -> FGBuilderResult<FactorGraphBuilder<IsingFactor<T>, IsingVariable<T>>>
+where
+    T: IsingMessagePassingType + Clone + Debug + Send,
+{
+    assert_eq!(
+        edges.len(),
+        couplings.len(),
+        "Number of couplings must be equal to the number of edges"
+    );
+    let mut fgb = FactorGraphBuilder::new_with_variables(
+        vec![IsingVariable::new(); fields.len()],
+        edges.len() + fields.len(),
+    );
+    let coupling_factors = edges
+        .iter()
+        .zip(couplings)
+        .map(|(edge, coupling)| (IsingFactor::new(*coupling, 0f64, 0f64), &edge[..]));
+    fgb.add_factors(coupling_factors, initializer)?;
+    let unit_factors = fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| (IsingFactor::UnitFactor(2f64 * field), [index]));
+    fgb.add_factors(unit_factors, initializer)?;
+    Ok(fgb)
+}
+
+// Creates a builder from queried couplings and fields
+fn build<T>(
+    variables_number: usize,
+    couplings: Vec<(usize, usize, f64)>,
+    field: impl Fn(usize) -> f64 + Sync,
+    initializer: &mut impl FnMut() -> IsingMessage,
+) -> FGBuilderResult<FactorGraphBuilder<IsingFactor<T>, IsingVariable<T>>>
+where
+    T: IsingMessagePassingType + Clone + Debug + Send,
+{
+    #[cfg(feature = "parallel")]
+    let fields: Vec<_> = (0..variables_number).into_par_iter().map(&field).collect();
+    #[cfg(not(feature = "parallel"))]
+    let fields: Vec<_> = (0..variables_number).map(&field).collect();
+    let mut fgb = FactorGraphBuilder::new_with_variables(
+        fields
+            .into_iter()
+            .map(|field| IsingVariable::new().with_field(field)),
+        couplings.len(),
+    );
+    for (lhs, rhs, value) in couplings {
+        fgb.add_factor(
+            IsingFactor::new(value, 0f64, 0f64),
+            &[lhs, rhs],
+            initializer,
+        )?;
+    }
+    Ok(fgb)
+}
@@ -0,0 +1,261 @@
+use std::fmt::Debug;
+
+use rand::Rng;
+use rand_distr::{Distribution, Normal};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    core::{FGBuilderResult, FactorGraphBuilder},
+    ising::{
+        builder_from_edges,
+        topologies::{complete, erdos_renyi, lattice},
+        IsingFactor, IsingMessage, IsingMessagePassingType, IsingVariable,
+    },
+};
+
+// A builder of an Ising factor graph
+type IsingBuilder<T> = FactorGraphBuilder<IsingFactor<T>, IsingVariable<T>>;
+
+// ------------------------------------------------------------------------------------------
+
+/// A disorder realization of a spin glass `exp ( sum_e J_e s_{i_e} s_{j_e} + sum_i b_i s_i )`,
+/// i.e. couplings of edges and fields of spins. The inverse temperature is not included,
+/// it is set by a factor scheduler (see `IsingFactorHyperParameters`)
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SpinGlass {
+    /// Pairs of spins `(i_e, j_e)` coupled by edges
+    pub edges: Vec<[usize; 2]>,
+
+    /// Couplings `J_e` of edges
+    pub couplings: Vec<f64>,
+
+    /// Magnetic fields `b_i` of spins, their number is the number of spins
+    pub fields: Vec<f64>,
+}
+
+impl SpinGlass {
+    /// Returns the number of spins
+    #[inline]
+    pub fn spins_number(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// Computes the energy `- sum_e J_e s_{i_e} s_{j_e} - sum_i b_i s_i` of a configuration
+    /// of spins
+    ///
+    /// # Arguments
+    ///
+    /// * `spins` - Values of spins, either 1 or -1, e.g. samples of a factor graph
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::ensembles::SpinGlass;
+    ///
+    /// let spin_glass = SpinGlass {
+    ///     edges: vec![[0, 1], [1, 2]],
+    ///     couplings: vec![1., -1.],
+    ///     fields: vec![0.5, 0., 0.],
+    /// };
+    /// assert_eq!(spin_glass.energy(&[1, 1, -1]), -2.5);
+    /// ```
+    pub fn energy(&self, spins: &[i8]) -> f64 {
+        assert_eq!(
+            spins.len(),
+            self.spins_number(),
+            "Number of spins values must be equal to the number of spins"
+        );
+        let coupling_energy: f64 = self
+            .edges
+            .iter()
+            .zip(&self.couplings)
+            .map(|([i, j], coupling)| coupling * (spins[*i] * spins[*j]) as f64)
+            .sum();
+        let field_energy: f64 = self
+            .fields
+            .iter()
+            .zip(spins)
+            .map(|(field, spin)| field * *spin as f64)
+            .sum();
+        -coupling_energy - field_energy
+    }
+
+    /// Returns an Ising factor graph builder of a spin glass
+    /// (see [`builder_from_edges`](crate::ising::builder_from_edges))
+    ///
+    /// # Arguments
+    ///
+    /// * `initializer` - An initializer of messages
+    pub fn builder<T>(
+        &self,
+        initializer: &mut impl FnMut() -> IsingMessage,
+    ) -> FGBuilderResult<IsingBuilder<T>>
+    where
+        T: IsingMessagePassingType + Clone + Debug + Send,
+    {
+        builder_from_edges(&self.edges, &self.couplings, &self.fields, initializer)
+    }
+
+    // Creates a spin glass without fields with couplings sampled for given edges
+    fn sample(
+        spins_number: usize,
+        edges: Vec<[usize; 2]>,
+        coupling: impl Distribution<f64>,
+        rng: &mut impl Rng,
+    ) -> Self {
+        let couplings = coupling.sample_iter(rng).take(edges.len()).collect();
+        SpinGlass {
+            edges,
+            couplings,
+            fields: vec![0f64; spins_number],
+        }
+    }
+
+    // Returns a spin glass and its builder
+    fn with_builder<T>(
+        self,
+        initializer: &mut impl FnMut() -> IsingMessage,
+    ) -> FGBuilderResult<(IsingBuilder<T>, Self)>
+    where
+        T: IsingMessagePassingType + Clone + Debug + Send,
+    {
+        Ok((self.builder(initializer)?, self))
+    }
+}
+
+// A distribution of couplings equal to 1 or -1 with equal probabilities
+struct PlusMinusOne;
+
+impl Distribution<f64> for PlusMinusOne {
+    #[inline]
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> f64 {
+        if rng.gen_bool(0.5) {
+            1f64
+        } else {
+            -1f64
+        }
+    }
+}
+
+/// Samples the Sherrington–Kirkpatrick model, i.e. a complete graph of spins
+/// with Gaussian couplings of zero mean and variance `1 / N` and without fields.
+/// Returns a builder and the disorder realization
+///
+/// # Arguments
+///
+/// * `spins_number` - A number of spins `N`
+/// * `rng` - A random numbers generator
+/// * `initializer` - An initializer of messages
+///
+/// # Example
+///
+/// ```
+/// use gmrs::ising::ensembles::sherrington_kirkpatrick;
+/// use gmrs::ising::{IsingMessage, SumProduct};
+/// use rand::{rngs::StdRng, SeedableRng};
+///
+/// let (fgb, spin_glass) =
+///     sherrington_kirkpatrick::<SumProduct>(100, &mut StdRng::seed_from_u64(42), &mut || IsingMessage(0.))
+///         .unwrap();
+/// assert_eq!(spin_glass.couplings.len(), 4950);
+/// assert_eq!(fgb.build().get_variable_degrees(), vec![100; 100]);
+/// ```
+pub fn sherrington_kirkpatrick<T>(
+    spins_number: usize,
+    rng: &mut impl Rng,
+    initializer: &mut impl FnMut() -> IsingMessage,
+) -> FGBuilderResult<(IsingBuilder<T>, SpinGlass)>
+where
+    T: IsingMessagePassingType + Clone + Debug + Send,
+{
+    let std = 1f64 / (spins_number.max(1) as f64).sqrt();
+    let coupling = Normal::new(0f64, std).expect("The standard deviation is positive and finite");
+    SpinGlass::sample(spins_number, complete(spins_number), coupling, rng).with_builder(initializer)
+}
+
+/// Samples the Edwards–Anderson model with ±J couplings, i.e. a hypercubic lattice
+/// (see [`lattice`](crate::ising::topologies::lattice)) with couplings equal to 1 or -1
+/// with equal probabilities and without fields. Returns a builder and the disorder
+/// realization
+///
+/// # Arguments
+///
+/// * `shape` - Sizes of a lattice along all dimensions
+/// * `periodic` - Whether boundary conditions are periodic
+/// * `rng` - A random numbers generator
+/// * `initializer` - An initializer of messages
+///
+/// # Example
+///
+/// ```
+/// use gmrs::ising::ensembles::edwards_anderson;
+/// use gmrs::ising::{IsingMessage, SumProduct};
+/// use rand::{rngs::StdRng, SeedableRng};
+///
+/// let (_, spin_glass) =
+///     edwards_anderson::<SumProduct>(&[10, 10], true, &mut StdRng::seed_from_u64(42), &mut || IsingMessage(0.))
+///         .unwrap();
+/// assert_eq!(spin_glass.edges.len(), 200);
+/// assert!(spin_glass.couplings.iter().all(|coupling| coupling.abs() == 1.));
+/// // the energy of any configuration is bounded by the number of edges
+/// assert!(spin_glass.energy(&[1; 100]).abs() <= 200.);
+/// ```
+pub fn edwards_anderson<T>(
+    shape: &[usize],
+    periodic: bool,
+    rng: &mut impl Rng,
+    initializer: &mut impl FnMut() -> IsingMessage,
+) -> FGBuilderResult<(IsingBuilder<T>, SpinGlass)>
+where
+    T: IsingMessagePassingType + Clone + Debug + Send,
+{
+    let spins_number = shape.iter().product();
+    let edges = lattice(shape, periodic);
+    SpinGlass::sample(spins_number, edges, PlusMinusOne, rng).with_builder(initializer)
+}
+
+/// Samples a diluted spin glass (the Viana–Bray model), i.e. an Erdős–Rényi random graph
+/// of a given mean degree with couplings equal to 1 or -1 with equal probabilities
+/// and without fields. Returns a builder and the disorder realization
+///
+/// # Arguments
+///
+/// * `spins_number` - A number of spins `N`
+/// * `mean_degree` - A mean degree `c` of spins, each pair of spins is coupled with
+///   the probability `c / (N - 1)`, it must be in [0, N - 1]
+/// * `rng` - A random numbers generator
+/// * `initializer` - An initializer of messages
+///
+/// # Example
+///
+/// ```
+/// use gmrs::ising::ensembles::diluted_spin_glass;
+/// use gmrs::ising::{IsingMessage, SumProduct};
+/// use rand::{rngs::StdRng, SeedableRng};
+///
+/// let (fgb, spin_glass) =
+///     diluted_spin_glass::<SumProduct>(1000, 3., &mut StdRng::seed_from_u64(42), &mut || IsingMessage(0.))
+///         .unwrap();
+/// let mean_degree = 2. * spin_glass.edges.len() as f64 / 1000.;
+/// assert!((mean_degree - 3.).abs() < 0.3);
+/// ```
+pub fn diluted_spin_glass<T>(
+    spins_number: usize,
+    mean_degree: f64,
+    rng: &mut impl Rng,
+    initializer: &mut impl FnMut() -> IsingMessage,
+) -> FGBuilderResult<(IsingBuilder<T>, SpinGlass)>
+where
+    T: IsingMessagePassingType + Clone + Debug + Send,
+{
+    let probability = if spins_number > 1 {
+        mean_degree / (spins_number - 1) as f64
+    } else {
+        0f64
+    };
+    let edges = erdos_renyi(spins_number, probability, rng);
+    SpinGlass::sample(spins_number, edges, PlusMinusOne, rng).with_builder(initializer)
+}
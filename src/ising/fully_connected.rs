@@ -0,0 +1,251 @@
+use std::fmt::Debug;
+
+use ndarray::{array, Array1};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    core::{FGError, FGResult, FactorGraph},
+    ising::{IsingFactor, IsingMessagePassingType, IsingVariable},
+};
+
+// ------------------------------------------------------------------------------------------
+
+/// A fully-connected Ising model `exp ( J sum_{i<j} s_i s_j + sum_i h_i s_i )`
+/// where all pairs of spins share the same coupling `J`, e.g. `J = J_0 / N` in
+/// the Curie–Weiss model. It is solved without explicit coupling factors
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FullyConnectedIsing {
+    /// The coupling `J` shared by all pairs of spins
+    pub coupling: f64,
+
+    /// Magnetic fields `h_i` of spins
+    pub fields: Vec<f64>,
+}
+
+/// A solution of the TAP equations of a fully-connected Ising model
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TapSolution {
+    /// Magnetizations of spins
+    pub magnetizations: Vec<f64>,
+
+    /// Number of iterations past before convergence
+    pub iterations_number: usize,
+
+    /// Final discrepancy between magnetizations of the last and the previous iterations
+    pub last_discrepancy: f64,
+
+    /// The TAP approximation of the logarithm of the partition function
+    pub free_entropy: f64,
+}
+
+impl TapSolution {
+    /// Returns marginals of spins in the format of [`FactorGraph::variable_marginals`],
+    /// i.e. probabilities of up and down states
+    #[inline]
+    pub fn marginals(&self) -> Vec<Array1<f64>> {
+        self.magnetizations
+            .iter()
+            .map(|m| array![(1f64 + m) / 2f64, (1f64 - m) / 2f64])
+            .collect()
+    }
+}
+
+// The entropy of a spin with magnetization `m`
+#[inline(always)]
+fn spin_entropy(m: f64) -> f64 {
+    [(1f64 + m) / 2f64, (1f64 - m) / 2f64]
+        .into_iter()
+        .filter(|p| *p > 0f64)
+        .map(|p| -p * p.ln())
+        .sum()
+}
+
+// Sums `x_i x_j` over pairs `i < j`
+#[inline(always)]
+fn pairs_sum(x: impl Iterator<Item = f64>) -> f64 {
+    let (sum, squares_sum) = x.fold((0f64, 0f64), |(s, q), x| (s + x, q + x * x));
+    (sum * sum - squares_sum) / 2f64
+}
+
+impl FullyConnectedIsing {
+    /// Creates a fully-connected model with arbitrary fields
+    ///
+    /// # Arguments
+    ///
+    /// * `coupling` - A coupling shared by all pairs of spins
+    /// * `fields` - Magnetic fields of spins
+    #[inline]
+    pub fn new(coupling: f64, fields: Vec<f64>) -> Self {
+        FullyConnectedIsing { coupling, fields }
+    }
+
+    /// Creates the Curie–Weiss model `exp ( J / N sum_{i<j} s_i s_j + h sum_i s_i )`
+    ///
+    /// # Arguments
+    ///
+    /// * `spins_number` - A number of spins `N`
+    /// * `coupling` - A coupling `J` before rescaling by the number of spins
+    /// * `field` - A magnetic field `h`
+    #[inline]
+    pub fn curie_weiss(spins_number: usize, coupling: f64, field: f64) -> Self {
+        FullyConnectedIsing {
+            coupling: coupling / spins_number as f64,
+            fields: vec![field; spins_number],
+        }
+    }
+
+    /// Solves the TAP equations
+    /// `m_i = tanh ( h_i + J sum_{k != i} m_k - J^2 m_i sum_{k != i} (1 - m_k^2) )`
+    /// by damped parallel iterations. An iteration takes `O(N)` operations,
+    /// while message passing on an explicit factor graph takes `O(N^2)`
+    ///
+    /// # Arguments
+    ///
+    /// * `max_iterations_number` - A maximal number of iterations
+    /// * `threshold` - A threshold on the maximal change of magnetizations specifying
+    ///   the convergence criterion
+    /// * `damping` - A damping coefficient in [0, 1), a new magnetization is
+    ///   `(1 - damping) * updated + damping * previous`
+    ///
+    /// # Notes
+    ///
+    /// Iterations start from magnetizations `tanh(h_i)` of decoupled spins, thus
+    /// in the ferromagnetic phase a field selects the state, while without fields
+    /// the paramagnetic solution is returned. Up to corrections vanishing with
+    /// the number of spins the solution coincides with the fixed point of sum-product
+    /// message passing on an explicit factor graph
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::FullyConnectedIsing;
+    ///
+    /// // A million spins
+    /// let model = FullyConnectedIsing::curie_weiss(1_000_000, 1.5, 0.1);
+    /// let solution = model.run_tap(1000, 1e-12, 0.).unwrap();
+    ///
+    /// // The mean field equation m = tanh(J m + h)
+    /// let m = solution.magnetizations[0];
+    /// assert!((m - f64::tanh(1.5 * m + 0.1)).abs() < 1e-5);
+    /// ```
+    pub fn run_tap(
+        &self,
+        max_iterations_number: usize,
+        threshold: f64,
+        damping: f64,
+    ) -> FGResult<TapSolution> {
+        let coupling = self.coupling;
+        let mut magnetizations: Vec<f64> = self.fields.iter().map(|h| h.tanh()).collect();
+        let mut discrepancy_dynamics = Vec::new();
+        for iteration in 0..max_iterations_number {
+            let sum: f64 = magnetizations.iter().sum();
+            let variances_sum: f64 = magnetizations.iter().map(|m| 1f64 - m * m).sum();
+            let mut discrepancy = 0f64;
+            for (m, h) in magnetizations.iter_mut().zip(&self.fields) {
+                let reaction = coupling * coupling * *m * (variances_sum - (1f64 - *m * *m));
+                let updated = f64::tanh(h + coupling * (sum - *m) - reaction);
+                let new = (1f64 - damping) * updated + damping * *m;
+                discrepancy = f64::max(discrepancy, (new - *m).abs());
+                *m = new;
+            }
+            discrepancy_dynamics.push(discrepancy);
+            if discrepancy < threshold {
+                return Ok(TapSolution {
+                    free_entropy: self.tap_free_entropy(&magnetizations),
+                    magnetizations,
+                    iterations_number: iteration + 1,
+                    last_discrepancy: discrepancy,
+                });
+            }
+        }
+        Err(FGError::MessagePassingError {
+            iterations_number: max_iterations_number,
+            last_discrepancy: discrepancy_dynamics.last().copied().unwrap_or(f64::NAN),
+            discrepancy_dynamics,
+        })
+    }
+
+    // The TAP free entropy, i.e. the Plefka expansion up to the second order in a coupling
+    fn tap_free_entropy(&self, magnetizations: &[f64]) -> f64 {
+        let coupling = self.coupling;
+        let local: f64 = magnetizations
+            .iter()
+            .zip(&self.fields)
+            .map(|(m, h)| spin_entropy(*m) + h * m)
+            .sum();
+        local
+            + coupling * pairs_sum(magnetizations.iter().copied())
+            + coupling * coupling / 2f64 * pairs_sum(magnetizations.iter().map(|m| 1f64 - m * m))
+    }
+}
+
+impl<T> FactorGraph<IsingFactor<T>, IsingVariable<T>>
+where
+    T: IsingMessagePassingType + Clone + Debug + Send,
+{
+    /// Detects whether a factor graph is a fully-connected Ising model, i.e.
+    /// each pair of distinct spins is joined by exactly one coupling factor and
+    /// all couplings are equal. Fields of unit factors, coupling factors and variables
+    /// are summed up per spin
+    ///
+    /// # Arguments
+    ///
+    /// * `tolerance` - A maximal absolute difference between couplings considered equal
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, IsingFactor, IsingMessage, SumProduct};
+    ///
+    /// let mut fgb = new_ising_builder::<SumProduct>(3, 3);
+    /// for (i, j) in [(0, 1), (1, 2), (0, 2)] {
+    ///     fgb.add_factor(IsingFactor::new(0.5, 0.1, 0.), &[i, j], &mut || IsingMessage(0.)).unwrap();
+    /// }
+    /// let model = fgb.build().as_fully_connected(1e-12).unwrap();
+    /// assert_eq!(model.coupling, 0.5);
+    /// assert!((model.fields[0] - 0.2).abs() < 1e-12);
+    /// let solution = model.run_tap(1000, 1e-12, 0.).unwrap();
+    /// assert_eq!(solution.marginals().len(), 3);
+    /// ```
+    pub fn as_fully_connected(&self, tolerance: f64) -> Option<FullyConnectedIsing> {
+        let spins_number = self.variables.len();
+        let mut is_joined = vec![false; spins_number * spins_number];
+        let mut pairs_number = 0;
+        let mut coupling: Option<f64> = None;
+        for node in &self.factors {
+            let IsingFactor::Coupling {
+                log_puu,
+                log_pud,
+                log_pdu,
+                log_pdd,
+                ..
+            } = node.factor
+            else {
+                continue;
+            };
+            let (i, j) = (node.var_node_indices[0], node.var_node_indices[1]);
+            let (i, j) = (usize::min(i, j), usize::max(i, j));
+            if i == j || is_joined[i * spins_number + j] {
+                return None;
+            }
+            is_joined[i * spins_number + j] = true;
+            pairs_number += 1;
+            let value = (log_puu - log_pud - log_pdu + log_pdd) / 4f64;
+            match coupling {
+                Some(coupling) if (coupling - value).abs() > tolerance => return None,
+                Some(_) => {}
+                None => coupling = Some(value),
+            }
+        }
+        if pairs_number != spins_number * spins_number.saturating_sub(1) / 2 {
+            return None;
+        }
+        Some(FullyConnectedIsing {
+            coupling: coupling.unwrap_or(0f64),
+            fields: self.local_fields().to_vec(),
+        })
+    }
+}
@@ -0,0 +1,136 @@
+// Lane-wise arithmetic for batches of coupling factors. Functions below are
+// branch-free element-wise loops over fixed size arrays, thus LLVM compiles them
+// into packed SIMD instructions (SSE2 by default, AVX/AVX-512 with the corresponding
+// target features), while `f64::exp` and `f64::ln` are scalar calls of libm.
+
+/// A number of coupling factors updated at once by a batched kernel
+pub const LANES: usize = 8;
+
+/// Values of a batch of coupling factors, one per lane
+pub type Lanes = [f64; LANES];
+
+// 1.5 * 2^52, adding it rounds a number of magnitude below 2^51 to an integer
+const ROUND_SHIFT: f64 = 6755399441055744f64;
+
+// ln(2) split into a part exactly representable with a few bits and a remainder
+const LN_2_HI: f64 = 6.931_471_803_691_238e-1;
+const LN_2_LO: f64 = 1.908_214_929_270_587_7e-10;
+
+// exp underflows to subnormal numbers below this value
+const EXP_MIN_ARGUMENT: f64 = -708f64;
+
+// Taylor coefficients 1 / n! of exp for n = 13, ..., 0
+const EXP_COEFFICIENTS: [f64; 14] = [
+    1f64 / 6227020800f64,
+    1f64 / 479001600f64,
+    1f64 / 39916800f64,
+    1f64 / 3628800f64,
+    1f64 / 362880f64,
+    1f64 / 40320f64,
+    1f64 / 5040f64,
+    1f64 / 720f64,
+    1f64 / 120f64,
+    1f64 / 24f64,
+    1f64 / 6f64,
+    1f64 / 2f64,
+    1f64,
+    1f64,
+];
+
+// exp(x) for x <= 0 and NaN, x is reduced to r = x - k ln(2) with |r| <= ln(2) / 2,
+// exp(r) is computed by its Taylor series and multiplied by 2^k built from exponent bits
+#[inline(always)]
+fn exp_non_positive(x: &Lanes) -> Lanes {
+    let mut shifted = [0f64; LANES];
+    let mut r = [0f64; LANES];
+    for k in 0..LANES {
+        let clamped = if x[k] < EXP_MIN_ARGUMENT {
+            EXP_MIN_ARGUMENT
+        } else {
+            x[k]
+        };
+        shifted[k] = clamped * std::f64::consts::LOG2_E + ROUND_SHIFT;
+        let exponent = shifted[k] - ROUND_SHIFT;
+        r[k] = (clamped - exponent * LN_2_HI) - exponent * LN_2_LO;
+    }
+    let mut exp_r = [EXP_COEFFICIENTS[0]; LANES];
+    for coefficient in &EXP_COEFFICIENTS[1..] {
+        for k in 0..LANES {
+            exp_r[k] = exp_r[k] * r[k] + coefficient;
+        }
+    }
+    let mut result = [0f64; LANES];
+    for k in 0..LANES {
+        // the lowest bits of the shifted number are the exponent in the two's complement form
+        let exponent_bits = shifted[k].to_bits().wrapping_sub(ROUND_SHIFT.to_bits());
+        let scale = f64::from_bits(exponent_bits.wrapping_add(1023) << 52);
+        result[k] = if x[k] < EXP_MIN_ARGUMENT {
+            0f64
+        } else {
+            exp_r[k] * scale
+        };
+    }
+    result
+}
+
+// ln(1 + x) for 0 <= x <= 1 and NaN, computed as 2 atanh(s) with s = x / (2 + x) <= 1 / 3
+// by the series 2 s sum_n s^{2n} / (2n + 1) truncated after 17 terms
+#[inline(always)]
+fn ln_1p_unit(x: &Lanes) -> Lanes {
+    let mut s = [0f64; LANES];
+    let mut z = [0f64; LANES];
+    for k in 0..LANES {
+        s[k] = x[k] / (2f64 + x[k]);
+        z[k] = s[k] * s[k];
+    }
+    let mut sum = [1f64 / 33f64; LANES];
+    for n in (0..16).rev() {
+        for k in 0..LANES {
+            sum[k] = sum[k] * z[k] + 1f64 / (2 * n + 1) as f64;
+        }
+    }
+    let mut result = [0f64; LANES];
+    for k in 0..LANES {
+        result[k] = 2f64 * s[k] * sum[k];
+    }
+    result
+}
+
+// log ( 1 + exp(-|x|) ), a correction term of log-sum-exp and log-sigmoid
+#[inline(always)]
+fn soft_plus_negative(x: &Lanes) -> Lanes {
+    let mut negative = [0f64; LANES];
+    for k in 0..LANES {
+        negative[k] = -x[k].abs();
+    }
+    ln_1p_unit(&exp_non_positive(&negative))
+}
+
+/// Lane-wise `log_sigmoid(x)` and `log_sigmoid(-x)`, equal to the scalar
+/// ones up to rounding, also for infinite and NaN arguments
+#[inline(always)]
+pub(super) fn log_sigmoids(x: &Lanes) -> (Lanes, Lanes) {
+    let mut up = [0f64; LANES];
+    let mut down = [0f64; LANES];
+    let correction = soft_plus_negative(x);
+    for k in 0..LANES {
+        up[k] = if x[k] > 0f64 { 0f64 } else { x[k] } - correction[k];
+        down[k] = if x[k] > 0f64 { -x[k] } else { 0f64 } - correction[k];
+    }
+    (up, down)
+}
+
+/// Lane-wise `log_sum_exponents(x, y)`, equal to the scalar one up to rounding,
+/// also for infinite and NaN arguments
+#[inline(always)]
+pub(super) fn log_sum_exponents(x: &Lanes, y: &Lanes) -> Lanes {
+    let mut difference = [0f64; LANES];
+    for k in 0..LANES {
+        difference[k] = x[k] - y[k];
+    }
+    let mut result = soft_plus_negative(&difference);
+    for k in 0..LANES {
+        result[k] += if x[k] > y[k] { x[k] } else { y[k] };
+    }
+    result
+}
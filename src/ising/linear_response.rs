@@ -0,0 +1,91 @@
+use std::fmt::Debug;
+
+use ndarray::Array2;
+
+use crate::{
+    core::{FGError, FGResult, FactorGraph},
+    ising::{IsingFactor, IsingFactorHyperParameters, IsingMessagePassingType, IsingVariable},
+};
+
+// ------------------------------------------------------------------------------------------
+
+impl<T> FactorGraph<IsingFactor<T>, IsingVariable<T>>
+where
+    T: IsingMessagePassingType + Clone + Debug + Send,
+{
+    /// Estimates connected correlations `<s_i s_j> - m_i m_j` between all pairs of given
+    /// spins by linear response, i.e. as susceptibilities `dm_j / db_i` of the fixed point
+    /// of belief propagation with respect to magnetic fields. Unlike factor marginals,
+    /// it gives correlations of spins that do not share a factor. It should be called
+    /// after message passing has converged, a factor graph is restored afterwards.
+    ///
+    /// # Arguments
+    ///
+    /// * `var_indices` - Indices of spins, the i-th row and column of the returned
+    ///   symmetric matrix correspond to the i-th spin
+    /// * `threshold` - A discrepancy below which messages are not propagated further, it
+    ///   must be much smaller than the perturbing field `1e-4` to get accurate responses
+    /// * `max_updates_number` - A maximal number of node updates per perturbation
+    /// * `factor_parameters` - Parameters of factors
+    /// * `variable_parameters` - Parameters of variables
+    ///
+    /// # Notes
+    ///
+    /// Each spin is perturbed once, thus the cost grows linearly with the number of
+    /// given spins. Estimates `dm_j / db_i` and `dm_i / db_j` coincide at an exact fixed
+    /// point, the returned matrix is their average. On trees estimates are exact
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, IsingFactor, IsingMessage, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    ///
+    /// // a chain of 3 spins without fields
+    /// let mut fgb = new_ising_builder::<SumProduct>(3, 2);
+    /// fgb.add_factor(IsingFactor::new(1., 0., 0.), &[0, 1], &mut || IsingMessage(0.)).unwrap();
+    /// fgb.add_factor(IsingFactor::new(0.5, 0., 0.), &[1, 2], &mut || IsingMessage(0.)).unwrap();
+    /// let mut fg = fgb.build();
+    /// let factor_scheduler = get_standard_factor_scheduler(0.);
+    /// let variable_scheduler = get_standard_variable_scheduler(0.);
+    /// fg.run_message_passing_parallel(100, 0, 1e-12, &factor_scheduler, &variable_scheduler)
+    ///     .unwrap();
+    /// let correlations = fg
+    ///     .linear_response_correlations(&[0, 2], 1e-12, 1000, &factor_scheduler(0), &variable_scheduler(0))
+    ///     .unwrap();
+    /// assert!((correlations[[0, 0]] - 1.).abs() < 1e-6);
+    /// assert!((correlations[[0, 1]] - f64::tanh(1.) * f64::tanh(0.5)).abs() < 1e-6);
+    /// ```
+    pub fn linear_response_correlations(
+        &mut self,
+        var_indices: &[usize],
+        threshold: f64,
+        max_updates_number: usize,
+        factor_parameters: &IsingFactorHyperParameters,
+        variable_parameters: &f64,
+    ) -> FGResult<Array2<f64>> {
+        let variables_number = self.variables.len();
+        if let Some(index) = var_indices
+            .iter()
+            .find(|var_index| **var_index >= variables_number)
+        {
+            return Err(FGError::OutOfRangeVariable(variables_number, *index));
+        }
+        let size = var_indices.len();
+        let mut correlations = Array2::zeros((size, size));
+        for (row, var_index) in var_indices.iter().enumerate() {
+            let response = self.magnetization_response(
+                *var_index,
+                threshold,
+                max_updates_number,
+                factor_parameters,
+                variable_parameters,
+            )?;
+            for (col, other) in var_indices.iter().enumerate() {
+                correlations[[row, col]] += response[*other] / 2f64;
+                correlations[[col, row]] += response[*other] / 2f64;
+            }
+        }
+        Ok(correlations)
+    }
+}
@@ -0,0 +1,271 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    core::FactorGraph,
+    ising::{
+        bethe::{log_factor_and_belief, normalized},
+        IsingFactor, IsingFactorHyperParameters, IsingVariable, SumProduct,
+    },
+};
+
+// Spins whose magnetization is closer to one by absolute value are treated as frozen
+const FROZEN_SPIN_TOLERANCE: f64 = 1e-12;
+
+// ------------------------------------------------------------------------------------------
+
+/// A loop series correction to the Bethe approximation of the logarithm of
+/// the partition function, `Z = Z_BP (1 + sum_C r_C)`, where `C` runs over generalized
+/// loops, i.e. subgraphs of couplings where each spin has at least two couplings
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LoopSeries {
+    /// The Bethe approximation of the logarithm of the partition function
+    pub bethe_log_partition: f64,
+
+    /// The truncated sum of contributions `r_C` of generalized loops
+    pub correction: f64,
+
+    /// The corrected logarithm of the partition function, it is not finite
+    /// if the truncated series `1 + correction` is not positive
+    pub log_partition: f64,
+
+    /// A number of generalized loops (connected or not) contributing to the correction
+    pub loops_number: usize,
+}
+
+// A connected generalized loop
+struct Loop {
+    spins: Vec<usize>,
+    length: usize,
+    contribution: f64,
+}
+
+// A coupling between spins with the normalized connected correlation of its belief
+struct Edge {
+    spins: [usize; 2],
+    correlation: f64,
+}
+
+// The central moment of order `q` of a normalized spin `(s - m) / sqrt(1 - m^2)`
+// distributed according to a belief with magnetization `m`
+#[inline(always)]
+fn normalized_moment(magnetization: f64, order: usize) -> f64 {
+    let (up, down) = (1f64 + magnetization, 1f64 - magnetization);
+    let sign = if order.is_multiple_of(2) { 1f64 } else { -1f64 };
+    (up * down.powi(order as i32) + sign * down * up.powi(order as i32))
+        / (2f64 * (up * down).powf(order as f64 / 2f64))
+}
+
+// Enumerates connected sets of edges of at most `max_length` edges whose minimal edge is
+// `root` by the ESU algorithm on the line graph, sets forming generalized loops are kept
+struct LoopsEnumerator<'a> {
+    edges: &'a [Edge],
+    incident_edges: Vec<Vec<usize>>,
+    magnetizations: &'a [f64],
+    max_length: usize,
+    loops: Vec<Loop>,
+}
+
+impl LoopsEnumerator<'_> {
+    fn shares_spin(&self, first: usize, second: usize) -> bool {
+        let [a, b] = self.edges[first].spins;
+        self.edges[second]
+            .spins
+            .iter()
+            .any(|spin| *spin == a || *spin == b)
+    }
+
+    fn extend(&mut self, subgraph: &mut Vec<usize>, mut extension: Vec<usize>, root: usize) {
+        self.keep_if_loop(subgraph);
+        if subgraph.len() == self.max_length {
+            return;
+        }
+        while let Some(edge) = extension.pop() {
+            let mut new_extension = extension.clone();
+            for spin in self.edges[edge].spins {
+                for neighbor in &self.incident_edges[spin] {
+                    if *neighbor > root
+                        && *neighbor != edge
+                        && !new_extension.contains(neighbor)
+                        && !subgraph
+                            .iter()
+                            .any(|other| *other == *neighbor || self.shares_spin(*other, *neighbor))
+                    {
+                        new_extension.push(*neighbor);
+                    }
+                }
+            }
+            subgraph.push(edge);
+            self.extend(subgraph, new_extension, root);
+            subgraph.pop();
+        }
+    }
+
+    fn keep_if_loop(&mut self, subgraph: &[usize]) {
+        let mut degrees: Vec<(usize, usize)> = Vec::new();
+        for edge in subgraph {
+            for spin in self.edges[*edge].spins {
+                match degrees.iter_mut().find(|(other, _)| *other == spin) {
+                    Some((_, degree)) => *degree += 1,
+                    None => degrees.push((spin, 1)),
+                }
+            }
+        }
+        if degrees.iter().any(|(_, degree)| *degree < 2) {
+            return;
+        }
+        let mut contribution: f64 = subgraph
+            .iter()
+            .map(|edge| self.edges[*edge].correlation)
+            .product();
+        for (spin, degree) in &degrees {
+            contribution *= normalized_moment(self.magnetizations[*spin], *degree);
+        }
+        self.loops.push(Loop {
+            spins: degrees.into_iter().map(|(spin, _)| spin).collect(),
+            length: subgraph.len(),
+            contribution,
+        });
+    }
+}
+
+// Sums contributions of sets of spin disjoint loops starting from the `start`-th loop
+// with the total length not exceeding `budget`, returns the sum and the number of sets
+fn disjoint_sum(loops: &[Loop], start: usize, budget: usize, used: &mut [bool]) -> (f64, usize) {
+    let mut sum = 0f64;
+    let mut sets_number = 0;
+    for (index, l) in loops.iter().enumerate().skip(start) {
+        if l.length > budget || l.spins.iter().any(|spin| used[*spin]) {
+            continue;
+        }
+        for spin in &l.spins {
+            used[*spin] = true;
+        }
+        let (rest_sum, rest_number) = disjoint_sum(loops, index + 1, budget - l.length, used);
+        for spin in &l.spins {
+            used[*spin] = false;
+        }
+        sum += l.contribution * (1f64 + rest_sum);
+        sets_number += 1 + rest_number;
+    }
+    (sum, sets_number)
+}
+
+impl FactorGraph<IsingFactor<SumProduct>, IsingVariable<SumProduct>> {
+    /// Computes the loop series correction of Chertkov and Chernyak to the Bethe
+    /// approximation of the logarithm of the partition function from beliefs at a fixed
+    /// point of sum-product message passing. A contribution of a generalized loop `C` is
+    /// `r_C = prod_{(ij) in C} mu_ij prod_{i in C} E[x_i^{q_i}]`, where
+    /// `mu_ij = (<s_i s_j> - m_i m_j) / sqrt((1 - m_i^2) (1 - m_j^2))` is computed from
+    /// a coupling belief, `x_i = (s_i - m_i) / sqrt(1 - m_i^2)` is distributed according to
+    /// a spin belief and `q_i` is the number of couplings of a spin within a loop.
+    /// It should be called after message passing has converged
+    ///
+    /// # Arguments
+    ///
+    /// * `factor_parameters` - Parameters of factors used by message passing
+    /// * `max_length` - A maximal total number of couplings of taken generalized loops
+    ///
+    /// # Notes
+    ///
+    /// Taking all generalized loops gives the exact partition function, however
+    /// their number grows exponentially with the length. Generalized loops consisting
+    /// of several spin disjoint connected loops are accounted. Frozen spins, i.e. spins
+    /// whose beliefs are deterministic, do not belong to loops with non-zero contributions.
+    /// Coupling factors joining a spin with itself are not supported. On trees
+    /// the correction vanishes
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, IsingFactor, IsingMessage, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    ///
+    /// // A ring of 4 spins, the only generalized loop is the ring itself
+    /// let mut fgb = new_ising_builder::<SumProduct>(4, 4);
+    /// for i in 0..4 {
+    ///     fgb.add_factor(IsingFactor::new(0.5, 0., 0.), &[i, (i + 1) % 4], &mut || IsingMessage(0.1))
+    ///         .unwrap();
+    /// }
+    /// let mut fg = fgb.build();
+    /// let factor_scheduler = get_standard_factor_scheduler(0.);
+    /// fg.run_message_passing_parallel(100, 0, 1e-12, &factor_scheduler, &get_standard_variable_scheduler(0.))
+    ///     .unwrap();
+    /// let loop_series = fg.loop_series(&factor_scheduler(0), 4);
+    /// assert_eq!(loop_series.loops_number, 1);
+    ///
+    /// // Z = 2^4 (cosh^4(J) + sinh^4(J))
+    /// let (c, s) = (f64::cosh(0.5), f64::sinh(0.5));
+    /// let log_partition = f64::ln(16. * (c.powi(4) + s.powi(4)));
+    /// assert!((loop_series.log_partition - log_partition).abs() < 1e-10);
+    /// ```
+    pub fn loop_series(
+        &self,
+        factor_parameters: &IsingFactorHyperParameters,
+        max_length: usize,
+    ) -> LoopSeries {
+        let (average_log_weight, bethe_entropy) = self.bethe_terms(factor_parameters);
+        let bethe_log_partition = average_log_weight + bethe_entropy;
+        let magnetizations: Vec<f64> = self
+            .variable_marginals()
+            .iter()
+            .map(|marginal| marginal[0] - marginal[1])
+            .collect();
+        let is_frozen = |spin: usize| 1f64 - magnetizations[spin].abs() < FROZEN_SPIN_TOLERANCE;
+        // couplings with vanishing correlations do not contribute and are skipped
+        let mut edges = Vec::new();
+        for node in &self.factors {
+            let IsingFactor::Coupling { .. } = node.factor else {
+                continue;
+            };
+            let spins = [node.var_node_indices[0], node.var_node_indices[1]];
+            if spins.into_iter().any(is_frozen) {
+                continue;
+            }
+            let (_, log_belief) =
+                log_factor_and_belief(&node.factor, &node.receivers, factor_parameters);
+            let belief = normalized(&log_belief);
+            let [mi, mj] = spins.map(|spin| magnetizations[spin]);
+            let correlation = (belief[0] - belief[1] - belief[2] + belief[3] - mi * mj)
+                / ((1f64 - mi * mi) * (1f64 - mj * mj)).sqrt();
+            if correlation != 0f64 {
+                edges.push(Edge { spins, correlation });
+            }
+        }
+        let mut incident_edges = vec![Vec::new(); self.variables.len()];
+        for (index, edge) in edges.iter().enumerate() {
+            for spin in edge.spins {
+                incident_edges[spin].push(index);
+            }
+        }
+        let mut enumerator = LoopsEnumerator {
+            edges: &edges,
+            incident_edges,
+            magnetizations: &magnetizations,
+            max_length,
+            loops: Vec::new(),
+        };
+        if max_length > 0 {
+            for (root, edge) in edges.iter().enumerate() {
+                let mut extension = Vec::new();
+                for spin in edge.spins {
+                    for neighbor in &enumerator.incident_edges[spin] {
+                        if *neighbor > root && !extension.contains(neighbor) {
+                            extension.push(*neighbor);
+                        }
+                    }
+                }
+                enumerator.extend(&mut vec![root], extension, root);
+            }
+        }
+        let mut used = vec![false; self.variables.len()];
+        let (correction, loops_number) = disjoint_sum(&enumerator.loops, 0, max_length, &mut used);
+        LoopSeries {
+            bethe_log_partition,
+            correction,
+            log_partition: bethe_log_partition + (1f64 + correction).ln(),
+            loops_number,
+        }
+    }
+}
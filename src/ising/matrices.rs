@@ -0,0 +1,158 @@
+use std::fmt::Debug;
+
+use ndarray::Array1;
+
+use crate::{
+    core::{CooMatrix, Factor, FactorGraph},
+    ising::{IsingFactor, IsingMessagePassingType, IsingVariable},
+};
+
+// ------------------------------------------------------------------------------------------
+
+impl<T> FactorGraph<IsingFactor<T>, IsingVariable<T>>
+where
+    T: IsingMessagePassingType + Clone + Debug + Send,
+{
+    /// Returns the symmetric coupling matrix `J` of an Ising model
+    /// `exp ( sum_{i<j} J_ij s_i s_j + sum_i b_i s_i )`. Each coupling factor
+    /// contributes two symmetric entries, couplings of parallel factors are summed up
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let mut fgb = new_ising_builder::<SumProduct>(3, 2);
+    /// fgb.add_factor(IsingFactor::new(0.5, 0.1, 0.), &[0, 1], &mut initializer).unwrap();
+    /// fgb.add_factor(IsingFactor::new(-0.3, 0., 0.), &[1, 2], &mut initializer).unwrap();
+    /// let fg = fgb.build();
+    /// let couplings = fg.coupling_matrix().to_dense();
+    /// assert!((couplings[[0, 1]] - 0.5).abs() < 1e-12);
+    /// assert!((couplings[[2, 1]] + 0.3).abs() < 1e-12);
+    /// assert_eq!(couplings[[0, 2]], 0.);
+    /// ```
+    pub fn coupling_matrix(&self) -> CooMatrix {
+        let variables_number = self.variables.len();
+        let mut couplings = CooMatrix::new((variables_number, variables_number));
+        for factor in &self.factors {
+            if let IsingFactor::Coupling {
+                log_puu,
+                log_pud,
+                log_pdu,
+                log_pdd,
+                ..
+            } = factor.factor
+            {
+                let coupling = (log_puu - log_pud - log_pdu + log_pdd) / 4f64;
+                let lhs = factor.var_node_indices[0];
+                let rhs = factor.var_node_indices[1];
+                couplings.push(lhs, rhs, coupling);
+                couplings.push(rhs, lhs, coupling);
+            }
+        }
+        couplings
+    }
+
+    /// Returns total magnetic fields `b` of an Ising model
+    /// `exp ( sum_{i<j} J_ij s_i s_j + sum_i b_i s_i )` collected from variables,
+    /// coupling factors and unit factors (including those fixing sampled spins)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::FactorGraphBuilder;
+    /// use gmrs::ising::{IsingFactor, IsingVariable, SumProduct, random_message_initializer};
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let variables = vec![IsingVariable::<SumProduct>::new().with_field(0.2), IsingVariable::new()];
+    /// let mut fgb = FactorGraphBuilder::new_with_variables(variables, 1);
+    /// fgb.add_factor(IsingFactor::new(0.5, 0.1, -0.4), &[0, 1], &mut initializer).unwrap();
+    /// let fg = fgb.build();
+    /// let fields = fg.local_fields();
+    /// assert!((fields[0] - 0.3).abs() < 1e-12);
+    /// assert!((fields[1] + 0.4).abs() < 1e-12);
+    /// ```
+    pub fn local_fields(&self) -> Array1<f64> {
+        let mut fields: Array1<f64> = self
+            .variables
+            .iter()
+            .map(|var| var.variable.field())
+            .collect();
+        for factor in &self.factors {
+            match factor.factor {
+                IsingFactor::Coupling {
+                    log_puu,
+                    log_pud,
+                    log_pdu,
+                    log_pdd,
+                    ..
+                } => {
+                    fields[factor.var_node_indices[0]] +=
+                        (log_puu + log_pud - log_pdu - log_pdd) / 4f64;
+                    fields[factor.var_node_indices[1]] +=
+                        (log_puu - log_pud + log_pdu - log_pdd) / 4f64;
+                }
+                IsingFactor::UnitFactor(message) => {
+                    fields[factor.var_node_indices[0]] += message / 2f64;
+                }
+            }
+        }
+        fields
+    }
+
+    /// Returns connected correlations `<s_i s_j> - <s_i><s_j>` of spins joined by
+    /// coupling factors as triples `(i, j, correlation)` in the order of factors.
+    /// Pair expectations are taken from factor marginals and magnetizations from
+    /// variable marginals, thus it should be called after message passing has converged
+    ///
+    /// # Notes
+    ///
+    /// Parallel coupling factors produce separate entries, they coincide at a fixed point
+    /// of message passing. On trees correlations of sum-product message passing are exact
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, IsingFactor, IsingMessage, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    ///
+    /// let mut fgb = new_ising_builder::<SumProduct>(2, 1);
+    /// fgb.add_factor(IsingFactor::new(1., 0., 0.), &[0, 1], &mut || IsingMessage(0.)).unwrap();
+    /// let mut fg = fgb.build();
+    /// fg.run_message_passing_parallel(
+    ///     100,
+    ///     0,
+    ///     1e-12,
+    ///     &get_standard_factor_scheduler(0.),
+    ///     &get_standard_variable_scheduler(0.),
+    /// ).unwrap();
+    /// let correlations = fg.connected_correlations();
+    /// assert_eq!((correlations[0].0, correlations[0].1), (0, 1));
+    /// assert!((correlations[0].2 - f64::tanh(1.)).abs() < 1e-10);
+    /// ```
+    pub fn connected_correlations(&self) -> Vec<(usize, usize, f64)> {
+        let magnetizations: Vec<f64> = self
+            .variable_marginals()
+            .iter()
+            .map(|marginal| marginal[0] - marginal[1])
+            .collect();
+        self.factors
+            .iter()
+            .filter(|node| node.factor.degree() == 2)
+            .map(|node| {
+                let marginal = node.factor.marginal(&node.receivers);
+                let pair_expectation =
+                    marginal[[0, 0]] - marginal[[0, 1]] - marginal[[1, 0]] + marginal[[1, 1]];
+                let (lhs, rhs) = (node.var_node_indices[0], node.var_node_indices[1]);
+                (
+                    lhs,
+                    rhs,
+                    pair_expectation - magnetizations[lhs] * magnetizations[rhs],
+                )
+            })
+            .collect()
+    }
+}
@@ -1,5 +1,6 @@
 use super::{
     common::{log_sigmoid, IsingMessage, IsingMessagePassingType},
+    lanes::{self, Lanes, LANES},
     IsingFactorHyperParameters,
 };
 
@@ -29,6 +30,24 @@ impl IsingMessagePassingType for MaxProduct {
         )
     }
 
+    #[inline(always)]
+    fn factor_message_update_lanes(
+        messages: &Lanes,
+        prev_messages: &Lanes,
+        log_p: &[Lanes; 4],
+        _: &Lanes,
+        gamma: f64,
+    ) -> Lanes {
+        let (nu_up, nu_down) = lanes::log_sigmoids(messages);
+        let mut new_messages = [0f64; LANES];
+        for k in 0..LANES {
+            let log_up = (log_p[0][k] + nu_up[k]).max(log_p[1][k] + nu_down[k]);
+            let log_down = (log_p[2][k] + nu_up[k]).max(log_p[3][k] + nu_down[k]);
+            new_messages[k] = (1f64 - gamma) * (log_up - log_down) + gamma * prev_messages[k];
+        }
+        new_messages
+    }
+
     #[inline(always)]
     fn sample(messages: &[IsingMessage], _: &mut impl rand::Rng) -> i8 {
         let sum_all: f64 = messages.iter().map(|x| x.0).sum();
@@ -38,4 +57,9 @@ impl IsingMessagePassingType for MaxProduct {
             -1
         }
     }
+
+    #[inline(always)]
+    fn log_add(x: f64, y: f64) -> f64 {
+        x.max(y)
+    }
 }
@@ -1,13 +1,47 @@
+mod batched;
+mod bethe;
+mod certificate;
+mod chains;
 mod common;
+mod constraints;
+mod contraction;
+#[cfg(feature = "parallel")]
+mod edges;
+mod energy;
+/// A module providing generators of disordered spin glass ensembles
+pub mod ensembles;
+mod fully_connected;
+mod lanes;
+mod linear_response;
+mod loop_series;
+mod matrices;
 mod max_product;
+mod mutual_information;
+mod observables;
+mod rbm;
+mod replica_coupling;
 /// A module providing schedulers for Ising's message passing algorithms
 pub mod schedulers;
+mod spectral;
 mod sum_product;
+/// A module providing edge lists of standard topologies for Ising models
+pub mod topologies;
+mod trw;
 
+pub use certificate::ConvergenceCertificate;
+pub use chains::{new_ising_chain_builder, new_ising_ring_builder};
 pub use common::{
     new_ising_builder, random_message_initializer, IsingFactor, IsingMessage,
     IsingMessagePassingType, IsingVariable,
 };
+pub use constraints::{ConstraintFactor, LogicalConstraint};
+pub use energy::{builder_from_edges, from_energy_fn, from_sparse_energy_fn};
+pub use fully_connected::{FullyConnectedIsing, TapSolution};
+pub use lanes::{Lanes, LANES};
+pub use loop_series::LoopSeries;
 pub use max_product::MaxProduct;
+pub use mutual_information::PairMutualInformation;
+pub use observables::{BetheEnergy, BetheFreeEntropy, Magnetization};
+pub use rbm::RestrictedBoltzmannMachine;
 pub use schedulers::IsingFactorHyperParameters;
 pub use sum_product::SumProduct;
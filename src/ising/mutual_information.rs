@@ -0,0 +1,209 @@
+use std::{
+    cmp::Ordering,
+    collections::{hash_map::Entry, HashMap},
+    fmt::Debug,
+};
+
+use crate::{
+    core::{FGError, FGResult, Factor, FactorGraph},
+    ising::{
+        IsingFactor, IsingFactorHyperParameters, IsingMessage, IsingMessagePassingType,
+        IsingVariable,
+    },
+};
+
+// A magnetic field perturbing a spin to estimate susceptibilities by finite differences
+const FIELD_PERTURBATION: f64 = 1e-4;
+
+/// An estimate of mutual information between a pair of spins
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PairMutualInformation {
+    /// A pair of spin indices
+    pub pair: (usize, usize),
+    /// A mutual information in nats
+    pub mutual_information: f64,
+    /// Whether spins are adjacent to a common coupling factor, i.e. the estimate
+    /// is computed from a factor marginal rather than from linear response
+    pub adjacent: bool,
+}
+
+// Mutual information of a joint distribution of two spins, negative entries
+// that linear response may produce are clipped
+fn pair_mutual_information(joint: [[f64; 2]; 2]) -> f64 {
+    let joint = joint.map(|row| row.map(|p| p.max(0f64)));
+    let total: f64 = joint.iter().flatten().sum();
+    let first = [joint[0][0] + joint[0][1], joint[1][0] + joint[1][1]];
+    let second = [joint[0][0] + joint[1][0], joint[0][1] + joint[1][1]];
+    let mut mutual_information = 0f64;
+    for (a, row) in joint.iter().enumerate() {
+        for (b, p) in row.iter().enumerate() {
+            if *p > 0f64 {
+                mutual_information += p / total * f64::ln(p * total / (first[a] * second[b]));
+            }
+        }
+    }
+    mutual_information.max(0f64)
+}
+
+impl<T> FactorGraph<IsingFactor<T>, IsingVariable<T>>
+where
+    T: IsingMessagePassingType + Clone + Debug + Send,
+{
+    /// Estimates mutual information between pairs of spins and returns estimates
+    /// ranked in descending order of mutual information. It should be called after
+    /// message passing has converged.
+    ///
+    /// For spins sharing a coupling factor the estimate is computed from the factor
+    /// marginal. For other pairs the connected correlation `<s_i s_j> - m_i m_j` is
+    /// estimated by susceptibility propagation, i.e. the response of `m_j` to a small
+    /// magnetic field acting on `s_i` is found by local message passing from `s_i`.
+    /// A factor graph is restored afterwards.
+    ///
+    /// # Arguments
+    ///
+    /// * `pairs` - Pairs of spin indices
+    /// * `threshold` - A discrepancy below which messages are not propagated further, it
+    ///   must be much smaller than the perturbing field `1e-4` to get accurate responses
+    /// * `max_updates_number` - A maximal number of node updates per perturbation
+    /// * `factor_parameters` - Parameters of factors
+    /// * `variable_parameters` - Parameters of variables
+    ///
+    /// # Notes
+    ///
+    /// On trees both kinds of estimates are exact
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, IsingFactor, IsingMessage, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    ///
+    /// let mut fgb = new_ising_builder::<SumProduct>(3, 2);
+    /// fgb.add_factor(IsingFactor::new(1., 0., 0.), &[0, 1], &mut || IsingMessage(0.)).unwrap();
+    /// fgb.add_factor(IsingFactor::new(0.5, 0., 0.), &[1, 2], &mut || IsingMessage(0.)).unwrap();
+    /// let mut fg = fgb.build();
+    /// let factor_scheduler = get_standard_factor_scheduler(0.);
+    /// let variable_scheduler = get_standard_variable_scheduler(0.);
+    /// fg.run_message_passing_parallel(100, 0, 1e-12, &factor_scheduler, &variable_scheduler)
+    ///     .unwrap();
+    /// let ranking = fg
+    ///     .mutual_information(&[(0, 2), (1, 2), (0, 1)], 1e-12, 1000, &factor_scheduler(0), &variable_scheduler(0))
+    ///     .unwrap();
+    /// assert_eq!(ranking[0].pair, (0, 1));
+    /// assert_eq!(ranking[2].pair, (0, 2));
+    /// assert!(!ranking[2].adjacent);
+    /// ```
+    pub fn mutual_information(
+        &mut self,
+        pairs: &[(usize, usize)],
+        threshold: f64,
+        max_updates_number: usize,
+        factor_parameters: &IsingFactorHyperParameters,
+        variable_parameters: &f64,
+    ) -> FGResult<Vec<PairMutualInformation>> {
+        let variables_number = self.variables.len();
+        if let Some(index) = pairs
+            .iter()
+            .flat_map(|(i, j)| [*i, *j])
+            .find(|index| *index >= variables_number)
+        {
+            return Err(FGError::OutOfRangeVariable(variables_number, index));
+        }
+        let magnetizations: Vec<f64> = self
+            .variable_marginals()
+            .iter()
+            .map(|marginal| marginal[0] - marginal[1])
+            .collect();
+        // susceptibilities are computed once per perturbed spin
+        let mut responses = HashMap::<usize, Vec<f64>>::new();
+        let mut ranking = Vec::with_capacity(pairs.len());
+        for &(i, j) in pairs {
+            let coupling = self.variables[i].fac_node_indices.iter().find(|fac_index| {
+                let var_indices = &self.factors[**fac_index].var_node_indices;
+                var_indices.len() == 2 && var_indices.contains(&j) && i != j
+            });
+            let (joint, adjacent) = if let Some(fac_index) = coupling {
+                let node = &self.factors[*fac_index];
+                let marginal = node.factor.marginal(&node.receivers);
+                let joint = [
+                    [marginal[[0, 0]], marginal[[0, 1]]],
+                    [marginal[[1, 0]], marginal[[1, 1]]],
+                ];
+                (joint, true)
+            } else {
+                if let Entry::Vacant(entry) = responses.entry(i) {
+                    let response = self.magnetization_response(
+                        i,
+                        threshold,
+                        max_updates_number,
+                        factor_parameters,
+                        variable_parameters,
+                    )?;
+                    entry.insert(response);
+                }
+                let (mi, mj) = (magnetizations[i], magnetizations[j]);
+                let correlation = responses[&i][j] + mi * mj;
+                let probability =
+                    |si: f64, sj: f64| (1f64 + si * mi + sj * mj + si * sj * correlation) / 4f64;
+                let joint = [
+                    [probability(1f64, 1f64), probability(1f64, -1f64)],
+                    [probability(-1f64, 1f64), probability(-1f64, -1f64)],
+                ];
+                (joint, false)
+            };
+            ranking.push(PairMutualInformation {
+                pair: (i, j),
+                mutual_information: pair_mutual_information(joint),
+                adjacent,
+            });
+        }
+        ranking.sort_by(|lhs, rhs| {
+            rhs.mutual_information
+                .partial_cmp(&lhs.mutual_information)
+                .unwrap_or(Ordering::Equal)
+        });
+        Ok(ranking)
+    }
+
+    // Computes derivatives of all magnetizations with respect to a magnetic field
+    // acting on a spin by central finite differences
+    pub(super) fn magnetization_response(
+        &mut self,
+        var_index: usize,
+        threshold: f64,
+        max_updates_number: usize,
+        factor_parameters: &IsingFactorHyperParameters,
+        variable_parameters: &f64,
+    ) -> FGResult<Vec<f64>> {
+        let mut perturbed_magnetizations = |field: f64| {
+            let info = self.perturbed_variable_marginals(
+                |fg| {
+                    // a unit factor exp ( s * b ) sends the message 2 * b
+                    fg.add_factor(
+                        IsingFactor::UnitFactor(2f64 * field),
+                        &[var_index],
+                        &mut || IsingMessage(2f64 * field),
+                    )?;
+                    Ok(vec![var_index])
+                },
+                threshold,
+                max_updates_number,
+                factor_parameters,
+                variable_parameters,
+            )?;
+            Ok::<_, FGError>(
+                info.marginals
+                    .iter()
+                    .map(|marginal| marginal[0] - marginal[1])
+                    .collect::<Vec<_>>(),
+            )
+        };
+        let upper = perturbed_magnetizations(FIELD_PERTURBATION)?;
+        let lower = perturbed_magnetizations(-FIELD_PERTURBATION)?;
+        Ok(upper
+            .into_iter()
+            .zip(lower)
+            .map(|(upper, lower)| (upper - lower) / (2f64 * FIELD_PERTURBATION))
+            .collect())
+    }
+}
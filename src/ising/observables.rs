@@ -0,0 +1,76 @@
+use std::fmt::Debug;
+
+use crate::{
+    core::{FactorGraph, Observable},
+    ising::{
+        IsingFactor, IsingFactorHyperParameters, IsingMessagePassingType, IsingVariable, SumProduct,
+    },
+};
+
+// ------------------------------------------------------------------------------------------
+
+/// The magnetization `sum_i (p_i(up) - p_i(down)) / N` averaged over spins,
+/// it is named `magnetization`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Magnetization;
+
+impl<T> Observable<IsingFactor<T>, IsingVariable<T>> for Magnetization
+where
+    T: IsingMessagePassingType + Clone + Debug + Send,
+{
+    #[inline(always)]
+    fn name(&self) -> &str {
+        "magnetization"
+    }
+
+    fn evaluate(&self, factor_graph: &FactorGraph<IsingFactor<T>, IsingVariable<T>>) -> f64 {
+        let marginals = factor_graph.variable_marginals();
+        let sum: f64 = marginals
+            .iter()
+            .map(|marginal| marginal[0] - marginal[1])
+            .sum();
+        sum / marginals.len().max(1) as f64
+    }
+}
+
+/// The Bethe average energy (see [`FactorGraph::bethe_energy`]) at given
+/// hyper-parameters, it is named `bethe_energy`
+#[derive(Debug, Clone)]
+pub struct BetheEnergy(pub IsingFactorHyperParameters);
+
+impl Observable<IsingFactor<SumProduct>, IsingVariable<SumProduct>> for BetheEnergy {
+    #[inline(always)]
+    fn name(&self) -> &str {
+        "bethe_energy"
+    }
+
+    #[inline(always)]
+    fn evaluate(
+        &self,
+        factor_graph: &FactorGraph<IsingFactor<SumProduct>, IsingVariable<SumProduct>>,
+    ) -> f64 {
+        factor_graph.bethe_energy(&self.0)
+    }
+}
+
+/// The Bethe approximation `-beta F` of the logarithm of the partition function
+/// (see [`FactorGraph::bethe_energy`]) at given hyper-parameters, it is named
+/// `bethe_free_entropy`
+#[derive(Debug, Clone)]
+pub struct BetheFreeEntropy(pub IsingFactorHyperParameters);
+
+impl Observable<IsingFactor<SumProduct>, IsingVariable<SumProduct>> for BetheFreeEntropy {
+    #[inline(always)]
+    fn name(&self) -> &str {
+        "bethe_free_entropy"
+    }
+
+    #[inline(always)]
+    fn evaluate(
+        &self,
+        factor_graph: &FactorGraph<IsingFactor<SumProduct>, IsingVariable<SumProduct>>,
+    ) -> f64 {
+        let (average_log_weight, bethe_entropy) = factor_graph.bethe_terms(&self.0);
+        average_log_weight + bethe_entropy
+    }
+}
@@ -0,0 +1,286 @@
+use std::fmt::Debug;
+
+use ndarray::{Array1, Array2, Axis};
+
+use crate::{
+    core::{
+        FGBuilderResult, FGResult, FactorGraphBuilder, MessagePassingOptions, Observables, Observer,
+    },
+    ising::{
+        builder_from_edges, common::sigmoid, IsingFactor, IsingFactorHyperParameters, IsingMessage,
+        IsingMessagePassingType, IsingVariable,
+    },
+};
+
+// ------------------------------------------------------------------------------------------
+
+/// A restricted Boltzmann machine `exp ( v^T W h + a^T v + b^T h )` with binary visible
+/// units `v` and hidden units `h` taking values 0 and 1. Its factor graph is a bipartite Ising
+/// factor graph, where units are mapped to spins by `v = (1 + s) / 2`, visible units have
+/// indices `[0, V)` and hidden units have indices `[V, V + H)`
+#[derive(Debug, Clone)]
+pub struct RestrictedBoltzmannMachine {
+    weights: Array2<f64>,
+    visible_biases: Array1<f64>,
+    hidden_biases: Array1<f64>,
+}
+
+impl RestrictedBoltzmannMachine {
+    /// Creates a restricted Boltzmann machine
+    ///
+    /// # Arguments
+    ///
+    /// * `weights` - A weight matrix `W` of shape (V, H)
+    /// * `visible_biases` - Biases `a` of visible units
+    /// * `hidden_biases` - Biases `b` of hidden units
+    ///
+    /// # Notes
+    ///
+    /// If sizes of biases do not match the shape of the weight matrix, the method panics
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::RestrictedBoltzmannMachine;
+    /// use ndarray::{array, Array1};
+    ///
+    /// let rbm = RestrictedBoltzmannMachine::new(
+    ///     array![[1., -1.], [0.5, 0.], [0., 2.]],
+    ///     Array1::zeros(3),
+    ///     array![0.1, -0.1],
+    /// );
+    /// assert_eq!(rbm.visible_units_number(), 3);
+    /// assert_eq!(rbm.hidden_units_number(), 2);
+    /// ```
+    pub fn new(
+        weights: Array2<f64>,
+        visible_biases: Array1<f64>,
+        hidden_biases: Array1<f64>,
+    ) -> Self {
+        assert_eq!(
+            weights.nrows(),
+            visible_biases.len(),
+            "Number of visible biases must be equal to the number of rows of weights"
+        );
+        assert_eq!(
+            weights.ncols(),
+            hidden_biases.len(),
+            "Number of hidden biases must be equal to the number of columns of weights"
+        );
+        RestrictedBoltzmannMachine {
+            weights,
+            visible_biases,
+            hidden_biases,
+        }
+    }
+
+    /// Returns the number of visible units
+    #[inline]
+    pub fn visible_units_number(&self) -> usize {
+        self.visible_biases.len()
+    }
+
+    /// Returns the number of hidden units
+    #[inline]
+    pub fn hidden_units_number(&self) -> usize {
+        self.hidden_biases.len()
+    }
+
+    /// Returns the weight matrix
+    #[inline]
+    pub fn weights(&self) -> &Array2<f64> {
+        &self.weights
+    }
+
+    /// Returns biases of visible units
+    #[inline]
+    pub fn visible_biases(&self) -> &Array1<f64> {
+        &self.visible_biases
+    }
+
+    /// Returns biases of hidden units
+    #[inline]
+    pub fn hidden_biases(&self) -> &Array1<f64> {
+        &self.hidden_biases
+    }
+
+    /// Returns a builder of the Ising factor graph of a machine. All pairs of
+    /// visible and hidden units are coupled by `W_ij / 4`, biases are transformed
+    /// into fields of spins (see [`builder_from_edges`](crate::ising::builder_from_edges))
+    ///
+    /// # Arguments
+    ///
+    /// * `initializer` - An initializer of messages
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{random_message_initializer, RestrictedBoltzmannMachine, SumProduct};
+    /// use ndarray::{Array1, Array2};
+    /// use rand::thread_rng;
+    ///
+    /// let rbm = RestrictedBoltzmannMachine::new(Array2::ones((4, 3)), Array1::zeros(4), Array1::zeros(3));
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let fg = rbm.builder::<SumProduct>(&mut initializer).unwrap().build();
+    /// // each unit is attached to units of the other layer and to a unit factor
+    /// assert_eq!(fg.get_variable_degrees(), vec![4, 4, 4, 4, 5, 5, 5]);
+    /// ```
+    pub fn builder<T>(
+        &self,
+        initializer: &mut impl FnMut() -> IsingMessage,
+    ) -> FGBuilderResult<FactorGraphBuilder<IsingFactor<T>, IsingVariable<T>>>
+    where
+        T: IsingMessagePassingType + Clone + Debug + Send,
+    {
+        let visible_units_number = self.visible_units_number();
+        let mut edges = Vec::with_capacity(self.weights.len());
+        let mut couplings = Vec::with_capacity(self.weights.len());
+        for ((i, j), weight) in self.weights.indexed_iter() {
+            edges.push([i, visible_units_number + j]);
+            couplings.push(weight / 4f64);
+        }
+        // v^T W h = (1 + s)^T W (1 + t) / 4 contributes to fields of both layers
+        let visible_fields = &self.visible_biases / 2f64 + self.weights.sum_axis(Axis(1)) / 4f64;
+        let hidden_fields = &self.hidden_biases / 2f64 + self.weights.sum_axis(Axis(0)) / 4f64;
+        let fields: Vec<_> = visible_fields
+            .iter()
+            .chain(&hidden_fields)
+            .copied()
+            .collect();
+        builder_from_edges(&edges, &couplings, &fields, initializer)
+    }
+
+    /// Computes probabilities of hidden units to be 1 given values of all visible units,
+    /// they are exact since hidden units are conditionally independent
+    ///
+    /// # Arguments
+    ///
+    /// * `visible` - Values of visible units
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::RestrictedBoltzmannMachine;
+    /// use ndarray::{array, Array1};
+    ///
+    /// let rbm = RestrictedBoltzmannMachine::new(array![[1.], [2.]], Array1::zeros(2), array![-1.]);
+    /// let marginals = rbm.hidden_marginals(&[true, false]);
+    /// assert!((marginals[0] - 0.5).abs() < 1e-12);
+    /// ```
+    pub fn hidden_marginals(&self, visible: &[bool]) -> Array1<f64> {
+        assert_eq!(
+            visible.len(),
+            self.visible_units_number(),
+            "Number of values must be equal to the number of visible units"
+        );
+        let visible = Array1::from_iter(visible.iter().map(|value| *value as u8 as f64));
+        (visible.dot(&self.weights) + &self.hidden_biases).mapv(sigmoid)
+    }
+
+    /// Computes probabilities of visible units to be 1 given values of all hidden units,
+    /// they are exact since visible units are conditionally independent
+    ///
+    /// # Arguments
+    ///
+    /// * `hidden` - Values of hidden units
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::RestrictedBoltzmannMachine;
+    /// use ndarray::{array, Array1};
+    ///
+    /// let rbm = RestrictedBoltzmannMachine::new(array![[1.], [2.]], array![0., -2.], Array1::zeros(1));
+    /// let marginals = rbm.visible_marginals(&[true]);
+    /// assert!((marginals[0] - 1. / (1. + f64::exp(-1.))).abs() < 1e-12);
+    /// assert!((marginals[1] - 0.5).abs() < 1e-12);
+    /// ```
+    pub fn visible_marginals(&self, hidden: &[bool]) -> Array1<f64> {
+        assert_eq!(
+            hidden.len(),
+            self.hidden_units_number(),
+            "Number of values must be equal to the number of hidden units"
+        );
+        let hidden = Array1::from_iter(hidden.iter().map(|value| *value as u8 as f64));
+        (self.weights.dot(&hidden) + &self.visible_biases).mapv(sigmoid)
+    }
+
+    /// Computes probabilities of visible and hidden units to be 1 given values of
+    /// a part of visible units, e.g. for inpainting of missing data. Observed visible
+    /// units are clamped and the rest of a machine is marginalized by message passing
+    ///
+    /// # Arguments
+    ///
+    /// * `visible` - Values of visible units, None if a unit is not observed
+    /// * `initializer` - An initializer of messages
+    /// * `options` - Options of message passing
+    ///
+    /// # Notes
+    ///
+    /// Probabilities of observed units are equal to their values. If all visible
+    /// units are observed, a factor graph is a tree and probabilities of hidden units
+    /// coincide with [`RestrictedBoltzmannMachine::hidden_marginals`]
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::MessagePassingOptions;
+    /// use gmrs::ising::{random_message_initializer, RestrictedBoltzmannMachine, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use ndarray::{array, Array1};
+    /// use rand::thread_rng;
+    ///
+    /// let rbm = RestrictedBoltzmannMachine::new(
+    ///     array![[2., 0.], [2., 0.], [0., 1.]],
+    ///     Array1::zeros(3),
+    ///     Array1::zeros(2),
+    /// );
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let mut options = MessagePassingOptions::new(
+    ///     get_standard_factor_scheduler(0.),
+    ///     get_standard_variable_scheduler(0.),
+    /// );
+    /// let (visible, hidden) = rbm
+    ///     .clamped_marginals::<SumProduct, _, _, _, _, _>(&[Some(true), None, Some(false)], &mut initializer, &mut options)
+    ///     .unwrap();
+    /// assert_eq!(visible[0], 1.);
+    /// // the second visible unit is likely on since it shares a hidden unit with the first one
+    /// assert!(visible[1] > 0.5);
+    /// ```
+    pub fn clamped_marginals<T, FS, VS, O, P, B>(
+        &self,
+        visible: &[Option<bool>],
+        initializer: &mut impl FnMut() -> IsingMessage,
+        options: &mut MessagePassingOptions<FS, VS, O, P, B>,
+    ) -> FGResult<(Array1<f64>, Array1<f64>)>
+    where
+        T: IsingMessagePassingType + Clone + Debug + Send,
+        FS: Fn(usize) -> IsingFactorHyperParameters,
+        VS: Fn(usize) -> f64,
+        O: Observer<IsingFactor<T>, IsingVariable<T>>,
+        B: Observables<IsingFactor<T>, IsingVariable<T>>,
+    {
+        let visible_units_number = self.visible_units_number();
+        assert_eq!(
+            visible.len(),
+            visible_units_number,
+            "Number of values must be equal to the number of visible units"
+        );
+        let mut fg = self
+            .builder::<T>(initializer)
+            .expect("Edges of a restricted Boltzmann machine are valid")
+            .build();
+        for (index, value) in visible.iter().enumerate() {
+            if let Some(value) = value {
+                fg.freeze_variable(&if *value { 1 } else { -1 }, index)?;
+            }
+        }
+        fg.run_message_passing_with_options(options)?;
+        let mut probabilities = fg
+            .variable_marginals()
+            .into_iter()
+            .map(|marginal| marginal[0]);
+        let visible = probabilities.by_ref().take(visible_units_number).collect();
+        Ok((visible, probabilities.collect()))
+    }
+}
@@ -0,0 +1,58 @@
+use std::fmt::Debug;
+
+use crate::{
+    core::{FGResult, FactorGraph},
+    ising::{IsingFactor, IsingMessage, IsingMessagePassingType, IsingVariable},
+};
+
+// ------------------------------------------------------------------------------------------
+
+impl<T> FactorGraph<IsingFactor<T>, IsingVariable<T>>
+where
+    T: IsingMessagePassingType + Clone + Debug + Send,
+{
+    /// Creates a factor graph of two replicas of an Ising model whose copies of each spin
+    /// are coupled by a factor `exp ( beta_g epsilon s_i s_{i + N} )` (see
+    /// [`FactorGraph::coupled_replicas`] for the layout of the resulting factor graph).
+    /// Coupling factors belong to a separate annealing group `g`, thus the coupling strength
+    /// is scheduled by the inverse temperature of this group, e.g. by
+    /// `get_grouped_exponential_factor_scheduler`, independently of couplings of replicas
+    ///
+    /// # Arguments
+    ///
+    /// * `coupling` - A coupling `epsilon` between copies of a spin at the unit inverse
+    ///   temperature of the group
+    /// * `annealing_group` - An annealing group of coupling factors, it should differ
+    ///   from groups of factors of a factor graph
+    /// * `message_initializer` - A function generating initial messages of the second
+    ///   replica and coupling factors
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, IsingFactor, IsingFactorHyperParameters, IsingMessage, SumProduct};
+    ///
+    /// let mut fgb = new_ising_builder::<SumProduct>(2, 1);
+    /// fgb.add_factor(IsingFactor::new(0.5, 0.2, 0.), &[0, 1], &mut || IsingMessage(0.)).unwrap();
+    /// let fg = fgb.build();
+    /// let mut doubled = fg.coupled_ising_replicas(1., 1, &mut || IsingMessage(0.)).unwrap();
+    ///
+    /// // The coupling is switched off by the zero inverse temperature of the first group
+    /// let scheduler = |_| IsingFactorHyperParameters::new(1., 0.).with_group_betas(vec![0.]);
+    /// doubled.run_message_passing_parallel(100, 0, 1e-10, &scheduler, &|_| 0.).unwrap();
+    /// let marginals = doubled.variable_marginals();
+    /// assert!((&marginals[0] - &marginals[2]).iter().all(|x| x.abs() < 1e-10));
+    /// assert!((&marginals[1] - &marginals[3]).iter().all(|x| x.abs() < 1e-10));
+    /// ```
+    pub fn coupled_ising_replicas(
+        &self,
+        coupling: f64,
+        annealing_group: usize,
+        message_initializer: &mut impl FnMut() -> IsingMessage,
+    ) -> FGResult<Self> {
+        self.coupled_replicas(
+            |_| IsingFactor::new(coupling, 0f64, 0f64).with_annealing_group(annealing_group),
+            message_initializer,
+        )
+    }
+}
@@ -1,11 +1,89 @@
+use std::fmt::Debug;
+
+use crate::{
+    core::FactorGraph,
+    ising::{IsingFactor, IsingMessagePassingType, IsingVariable},
+};
+
+// A maximal damping coefficient chosen automatically
+const MAX_AUTO_GAMMA: f64 = 0.9;
+
 /// Hyper-parameters of Ising's message passing algorithms
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct IsingFactorHyperParameters {
     /// Inverse temperature
     pub beta: f64,
 
     /// Exponential moving average coefficient
     pub gamma: f64,
+
+    /// Inverse temperatures of annealing groups of coupling factors (see
+    /// `IsingFactor::with_annealing_group`), a factor of a group `g > 0` uses
+    /// `group_betas[g - 1]` if it is present and `beta` otherwise
+    pub group_betas: Vec<f64>,
+}
+
+impl IsingFactorHyperParameters {
+    /// Creates hyper-parameters with the same inverse temperature for all factors
+    ///
+    /// # Arguments
+    ///
+    /// * `beta` - Inverse temperature
+    /// * `gamma` - Exponential moving average coefficient
+    #[inline]
+    pub fn new(beta: f64, gamma: f64) -> Self {
+        IsingFactorHyperParameters {
+            beta,
+            gamma,
+            group_betas: Vec::new(),
+        }
+    }
+
+    /// Sets inverse temperatures of annealing groups `1, 2, ...`
+    ///
+    /// # Arguments
+    ///
+    /// * `group_betas` - Inverse temperatures of groups starting from the first one
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::IsingFactorHyperParameters;
+    ///
+    /// let parameters = IsingFactorHyperParameters::new(0.5, 0.).with_group_betas(vec![2.]);
+    /// assert_eq!(parameters.group_beta(0), 0.5);
+    /// assert_eq!(parameters.group_beta(1), 2.);
+    /// assert_eq!(parameters.group_beta(2), 0.5);
+    /// ```
+    #[inline]
+    pub fn with_group_betas(mut self, group_betas: Vec<f64>) -> Self {
+        self.group_betas = group_betas;
+        self
+    }
+
+    /// Returns an inverse temperature of an annealing group
+    ///
+    /// # Arguments
+    ///
+    /// * `group` - An annealing group, the default group is 0
+    #[inline(always)]
+    pub fn group_beta(&self, group: usize) -> f64 {
+        match group {
+            0 => self.beta,
+            group => self
+                .group_betas
+                .get(group - 1)
+                .copied()
+                .unwrap_or(self.beta),
+        }
+    }
+
+    // Returns hyper-parameters of factors of an annealing group,
+    // it does not allocate since group inverse temperatures are dropped
+    #[inline(always)]
+    pub(super) fn of_group(&self, group: usize) -> Self {
+        IsingFactorHyperParameters::new(self.group_beta(group), self.gamma)
+    }
 }
 
 /// Returns a scheduler for messages update rule of an Ising factor
@@ -26,7 +104,47 @@ pub fn get_exponential_factor_scheduler(
     let coeff = (beta_end / beta_start).powf(1f64 / iterations_number as f64);
     move |iter| {
         let beta = coeff.powi(iter as i32) * beta_start;
-        IsingFactorHyperParameters { beta, gamma }
+        IsingFactorHyperParameters::new(beta, gamma)
+    }
+}
+
+/// Returns a scheduler for messages update rule of Ising factors whose
+/// annealing groups have exponentially changing inverse temperatures
+/// on different trajectories, e.g. hard constraints can be annealed faster
+/// than soft preferences
+///
+/// # Arguments
+///
+/// * `beta_ranges` - Initial and final inverse temperatures of each annealing group
+///   starting from the default group 0
+/// * `iterations_number` - Number of iterations passed from initial to final inverse temperatures
+/// * `gamma` - Exponential moving average coefficient
+///
+/// # Example
+///
+/// ```
+/// use gmrs::ising::schedulers::get_grouped_exponential_factor_scheduler;
+///
+/// let scheduler = get_grouped_exponential_factor_scheduler(&[(0.1, 1.), (0.1, 10.)], 10, 0.);
+/// assert!((scheduler(10).beta - 1.).abs() < 1e-12);
+/// assert!((scheduler(10).group_beta(1) - 10.).abs() < 1e-12);
+/// assert!((scheduler(5).group_beta(1) - 1.).abs() < 1e-12);
+/// ```
+pub fn get_grouped_exponential_factor_scheduler(
+    beta_ranges: &[(f64, f64)],
+    iterations_number: usize,
+    gamma: f64,
+) -> impl Fn(usize) -> IsingFactorHyperParameters {
+    let schedulers: Vec<_> = beta_ranges
+        .iter()
+        .map(|(beta_start, beta_end)| {
+            get_exponential_factor_scheduler(*beta_start, *beta_end, iterations_number, gamma)
+        })
+        .collect();
+    move |iter| {
+        let mut betas = schedulers.iter().map(|scheduler| scheduler(iter).beta);
+        let beta = betas.next().unwrap_or(1f64);
+        IsingFactorHyperParameters::new(beta, gamma).with_group_betas(betas.collect())
     }
 }
 
@@ -37,7 +155,7 @@ pub fn get_exponential_factor_scheduler(
 ///
 /// * `gamma` - Exponential moving average coefficient
 pub fn get_standard_factor_scheduler(gamma: f64) -> impl Fn(usize) -> IsingFactorHyperParameters {
-    move |_| IsingFactorHyperParameters { beta: 1f64, gamma }
+    move |_| IsingFactorHyperParameters::new(1f64, gamma)
 }
 
 /// Returns a scheduler for messages update rule of and Ising variable
@@ -48,3 +166,82 @@ pub fn get_standard_factor_scheduler(gamma: f64) -> impl Fn(usize) -> IsingFacto
 pub fn get_standard_variable_scheduler(gamma: f64) -> impl Fn(usize) -> f64 {
     move |_| gamma
 }
+
+/// Chooses a damping coefficient of factor messages from statistics of an Ising
+/// factor graph. Linearized sum-product updates of messages sent by a spin have
+/// the gain `kappa_i = sum_{b != a} tanh(beta |J_b|)`, the sum is taken over coupling
+/// factors of the spin except the weakest one. The largest gain `kappa` over spins
+/// bounds the growth of perturbations, it is at most 1 on weakly coupled graphs
+/// and grows with degrees and couplings. The coefficient `kappa / (1 + kappa)`
+/// cancels perturbations oscillating with the gain `kappa`, it is capped by 0.9
+///
+/// # Arguments
+///
+/// * `factor_graph` - An Ising factor graph
+/// * `beta` - Inverse temperature message passing is run at
+///
+/// # Notes
+///
+/// It is a heuristic rather than a guarantee, and ferromagnetic graphs that do not
+/// oscillate converge slower than necessary. Annealing groups are ignored, i.e. all
+/// couplings are taken at the same inverse temperature
+///
+/// # Example
+///
+/// ```
+/// use gmrs::ising::{new_ising_builder, IsingFactor, IsingMessage, SumProduct};
+/// use gmrs::ising::schedulers::auto_damping;
+///
+/// // A star of three spins is weakly coupled
+/// let mut fgb = new_ising_builder::<SumProduct>(4, 3);
+/// for i in 1..4 {
+///     fgb.add_factor(IsingFactor::new(0.1, 0., 0.), &[0, i], &mut || IsingMessage(0.)).unwrap();
+/// }
+/// let fg = fgb.build();
+/// let kappa = 2. * f64::tanh(0.1);
+/// assert!((auto_damping(&fg, 1.) - kappa / (1. + kappa)).abs() < 1e-12);
+/// assert_eq!(auto_damping(&fg, 0.), 0.);
+/// assert_eq!(auto_damping(&fg, 1e3), 2. / 3.);
+/// ```
+pub fn auto_damping<T>(
+    factor_graph: &FactorGraph<IsingFactor<T>, IsingVariable<T>>,
+    beta: f64,
+) -> f64
+where
+    T: IsingMessagePassingType + Clone + Debug + Send,
+{
+    let couplings = factor_graph.coupling_matrix();
+    let mut sums = vec![0f64; couplings.shape.0];
+    let mut weakest = vec![f64::INFINITY; couplings.shape.0];
+    for (row, value) in couplings.row_indices.iter().zip(&couplings.values) {
+        let gain = f64::tanh(beta * value.abs());
+        sums[*row] += gain;
+        weakest[*row] = weakest[*row].min(gain);
+    }
+    let kappa = sums
+        .iter()
+        .zip(&weakest)
+        .filter(|(_, weakest)| weakest.is_finite())
+        .map(|(sum, weakest)| sum - weakest)
+        .fold(0f64, f64::max);
+    f64::min(kappa / (1f64 + kappa), MAX_AUTO_GAMMA)
+}
+
+/// Returns a scheduler for messages update rule of an Ising factor
+/// with a constant inverse temperature and a damping coefficient chosen
+/// by [`auto_damping`]
+///
+/// # Arguments
+///
+/// * `factor_graph` - An Ising factor graph
+/// * `beta` - Inverse temperature
+pub fn get_auto_damped_factor_scheduler<T>(
+    factor_graph: &FactorGraph<IsingFactor<T>, IsingVariable<T>>,
+    beta: f64,
+) -> impl Fn(usize) -> IsingFactorHyperParameters
+where
+    T: IsingMessagePassingType + Clone + Debug + Send,
+{
+    let gamma = auto_damping(factor_graph, beta);
+    move |_| IsingFactorHyperParameters::new(beta, gamma)
+}
@@ -0,0 +1,175 @@
+use std::fmt::Debug;
+
+use ndarray::Array1;
+
+use crate::{
+    core::{CooMatrix, FactorGraph},
+    ising::{IsingFactor, IsingMessagePassingType, IsingVariable},
+};
+
+// ------------------------------------------------------------------------------------------
+
+impl<T> FactorGraph<IsingFactor<T>, IsingVariable<T>>
+where
+    T: IsingMessagePassingType + Clone + Debug + Send,
+{
+    /// Returns the Bethe-Hessian matrix of an Ising model at inverse temperature `beta`
+    /// `H_ij = (1 + sum_k sinh^2(beta J_ik)) delta_ij - sinh(2 beta J_ij) / 2`.
+    /// It is the Hessian of the Bethe free energy at the paramagnetic point, thus
+    /// a negative eigenvalue signals that the paramagnetic fixed point of belief
+    /// propagation is unstable, i.e. a ferromagnetic or spin glass transition
+    ///
+    /// # Arguments
+    ///
+    /// * `beta` - An inverse temperature multiplying all couplings
+    pub fn bethe_hessian(&self, beta: f64) -> CooMatrix {
+        let couplings = self.coupling_matrix();
+        let variables_number = self.variables.len();
+        let mut hessian = CooMatrix::new((variables_number, variables_number));
+        let mut diagonal = vec![1f64; variables_number];
+        let entries = couplings
+            .row_indices
+            .iter()
+            .zip(&couplings.col_indices)
+            .zip(&couplings.values);
+        for ((row, col), coupling) in entries {
+            diagonal[*row] += f64::sinh(beta * coupling).powi(2);
+            hessian.push(*row, *col, -f64::sinh(2f64 * beta * coupling) / 2f64);
+        }
+        for (i, value) in diagonal.into_iter().enumerate() {
+            hessian.push(i, i, value);
+        }
+        hessian
+    }
+
+    /// Returns the weighted non-backtracking matrix of an Ising model at inverse
+    /// temperature `beta`, i.e. the Jacobian of belief propagation at the paramagnetic
+    /// point, together with the list of directed edges `(i, j)` indexing its rows
+    /// and columns. An entry `((j, l), (i, j))` with `i != l` is equal to `tanh(beta J_jl)`.
+    /// The paramagnetic fixed point is stable iff the spectral radius is less than one
+    ///
+    /// # Arguments
+    ///
+    /// * `beta` - An inverse temperature multiplying all couplings
+    pub fn non_backtracking_matrix(&self, beta: f64) -> (CooMatrix, Vec<(usize, usize)>) {
+        let mut edges = Vec::new();
+        let mut weights = Vec::new();
+        let mut out_edges = vec![Vec::new(); self.variables.len()];
+        for factor in &self.factors {
+            if let IsingFactor::Coupling {
+                log_puu,
+                log_pud,
+                log_pdu,
+                log_pdd,
+                ..
+            } = factor.factor
+            {
+                let weight = f64::tanh(beta * (log_puu - log_pud - log_pdu + log_pdd) / 4f64);
+                let lhs = factor.var_node_indices[0];
+                let rhs = factor.var_node_indices[1];
+                for (src, dst) in [(lhs, rhs), (rhs, lhs)] {
+                    out_edges[src].push(edges.len());
+                    edges.push((src, dst));
+                    weights.push(weight);
+                }
+            }
+        }
+        let mut matrix = CooMatrix::new((edges.len(), edges.len()));
+        for (in_edge, (i, j)) in edges.iter().enumerate() {
+            // an edge created together with the incoming one is its reversal
+            let reversed = in_edge ^ 1;
+            for out_edge in &out_edges[*j] {
+                if *out_edge != reversed {
+                    debug_assert_ne!(edges[*out_edge].1, *i);
+                    matrix.push(*out_edge, in_edge, weights[*out_edge]);
+                }
+            }
+        }
+        (matrix, edges)
+    }
+
+    /// Returns the smallest eigenvalue of the Bethe-Hessian matrix and the corresponding
+    /// eigenvector or None if the power method has not converged. Signs of the
+    /// eigenvector components give a spectral estimate of a symmetry broken state,
+    /// which can be used to initialize belief propagation, e.g. in community detection
+    ///
+    /// # Arguments
+    ///
+    /// * `beta` - An inverse temperature multiplying all couplings
+    /// * `max_iterations_number` - A maximal number of power iterations
+    /// * `threshold` - A threshold for the residual norm of a normalized eigenvector
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use rand::thread_rng;
+    ///
+    /// // A ferromagnetic complete graph of 4 spins
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let mut fgb = new_ising_builder::<SumProduct>(4, 6);
+    /// for i in 0..4 {
+    ///     for j in (i + 1)..4 {
+    ///         fgb.add_factor(IsingFactor::new(1., 0., 0.), &[i, j], &mut initializer).unwrap();
+    ///     }
+    /// }
+    /// let fg = fgb.build();
+    ///
+    /// // The paramagnetic point is stable at high temperature
+    /// let (eigenvalue, _) = fg.bethe_hessian_smallest_eigenpair(0.3, 10000, 1e-10).unwrap();
+    /// assert!(eigenvalue > 0.);
+    /// let spectral_radius = fg.non_backtracking_spectral_radius(0.3, 10000, 1e-10).unwrap();
+    /// assert!((spectral_radius - 2. * f64::tanh(0.3)).abs() < 1e-8);
+    ///
+    /// // and unstable at low temperature
+    /// let (eigenvalue, eigenvector) = fg.bethe_hessian_smallest_eigenpair(1., 10000, 1e-10).unwrap();
+    /// assert!(eigenvalue < 0.);
+    /// assert!(eigenvector.iter().all(|x| x * eigenvector[0] > 0.));
+    /// let spectral_radius = fg.non_backtracking_spectral_radius(1., 10000, 1e-10).unwrap();
+    /// assert!(spectral_radius > 1.);
+    /// ```
+    pub fn bethe_hessian_smallest_eigenpair(
+        &self,
+        beta: f64,
+        max_iterations_number: usize,
+        threshold: f64,
+    ) -> Option<(f64, Array1<f64>)> {
+        let mut shifted = self.bethe_hessian(beta);
+        // the Gershgorin bound makes all eigenvalues of `shift - H` non-negative
+        let mut row_sums = vec![0f64; shifted.shape.0];
+        for (row, value) in shifted.row_indices.iter().zip(&shifted.values) {
+            row_sums[*row] += value.abs();
+        }
+        let shift = row_sums.into_iter().fold(0f64, f64::max);
+        shifted.values.iter_mut().for_each(|value| *value = -*value);
+        for i in 0..shifted.shape.0 {
+            shifted.push(i, i, shift);
+        }
+        shifted
+            .leading_eigenpair(max_iterations_number, threshold)
+            .map(|(eigenvalue, eigenvector)| (shift - eigenvalue, eigenvector))
+    }
+
+    /// Returns the spectral radius of the weighted non-backtracking matrix
+    /// (see [`FactorGraph::non_backtracking_matrix`]) or None if the power method
+    /// has not converged. The power method requires the eigenvalue with the largest
+    /// magnitude to be real and unique, which is always the case for ferromagnetic
+    /// couplings on a connected graph with a non-bipartite non-backtracking walk structure
+    ///
+    /// # Arguments
+    ///
+    /// * `beta` - An inverse temperature multiplying all couplings
+    /// * `max_iterations_number` - A maximal number of power iterations
+    /// * `threshold` - A threshold for the residual norm of a normalized eigenvector
+    pub fn non_backtracking_spectral_radius(
+        &self,
+        beta: f64,
+        max_iterations_number: usize,
+        threshold: f64,
+    ) -> Option<f64> {
+        let (matrix, _) = self.non_backtracking_matrix(beta);
+        matrix
+            .leading_eigenpair(max_iterations_number, threshold)
+            .map(|(eigenvalue, _)| eigenvalue.abs())
+    }
+}
@@ -1,6 +1,7 @@
 use super::common::{
     log_sigmoid, log_sum_exponents, sigmoid, IsingMessage, IsingMessagePassingType,
 };
+use super::lanes::{self, Lanes, LANES};
 use crate::ising::IsingFactorHyperParameters;
 use rand_distr::Uniform;
 
@@ -31,6 +32,31 @@ impl IsingMessagePassingType for SumProduct {
         )
     }
 
+    #[inline(always)]
+    fn factor_message_update_lanes(
+        messages: &Lanes,
+        prev_messages: &Lanes,
+        log_p: &[Lanes; 4],
+        betas: &Lanes,
+        gamma: f64,
+    ) -> Lanes {
+        let (nu_up, nu_down) = lanes::log_sigmoids(messages);
+        let mut weights = [[0f64; LANES]; 4];
+        for k in 0..LANES {
+            weights[0][k] = betas[k] * log_p[0][k] + nu_up[k];
+            weights[1][k] = betas[k] * log_p[1][k] + nu_down[k];
+            weights[2][k] = betas[k] * log_p[2][k] + nu_up[k];
+            weights[3][k] = betas[k] * log_p[3][k] + nu_down[k];
+        }
+        let log_up = lanes::log_sum_exponents(&weights[0], &weights[1]);
+        let log_down = lanes::log_sum_exponents(&weights[2], &weights[3]);
+        let mut new_messages = [0f64; LANES];
+        for k in 0..LANES {
+            new_messages[k] = (1f64 - gamma) * (log_up[k] - log_down[k]) + gamma * prev_messages[k];
+        }
+        new_messages
+    }
+
     #[inline(always)]
     fn sample(messages: &[IsingMessage], rng: &mut impl rand::Rng) -> i8 {
         let sum_all = messages.iter().map(|x| x.0).sum();
@@ -40,4 +66,13 @@ impl IsingMessagePassingType for SumProduct {
             -1
         }
     }
+
+    #[inline(always)]
+    fn log_add(x: f64, y: f64) -> f64 {
+        if x == f64::NEG_INFINITY {
+            y
+        } else {
+            log_sum_exponents(x, y)
+        }
+    }
 }
@@ -0,0 +1,246 @@
+use std::{collections::HashSet, fmt::Debug};
+
+use rand::{seq::SliceRandom, Rng};
+use rand_distr::Distribution;
+
+use crate::{
+    core::{FGBuilderResult, FactorGraphBuilder},
+    ising::{
+        builder_from_edges, IsingFactor, IsingMessage, IsingMessagePassingType, IsingVariable,
+    },
+};
+
+// A maximal number of attempts to pair stubs of a random regular graph
+const MAX_REGULAR_GRAPH_ATTEMPTS: usize = 100;
+
+// ------------------------------------------------------------------------------------------
+
+/// Returns edges of a chain of spins, edges are pairs `[i, i + 1]`
+///
+/// # Arguments
+///
+/// * `spins_number` - A number of spins
+/// * `periodic` - Whether the last spin is coupled to the first one
+///
+/// # Notes
+///
+/// A periodic chain of at most two spins is open, thus all returned graphs are simple
+///
+/// # Example
+///
+/// ```
+/// use gmrs::ising::topologies::chain;
+///
+/// assert_eq!(chain(3, false), vec![[0, 1], [1, 2]]);
+/// assert_eq!(chain(3, true), vec![[0, 1], [1, 2], [0, 2]]);
+/// ```
+pub fn chain(spins_number: usize, periodic: bool) -> Vec<[usize; 2]> {
+    lattice(&[spins_number], periodic)
+}
+
+/// Returns edges of a hypercubic lattice of spins, e.g. a 2D or a 3D one. A spin with
+/// coordinates `(x_0, ..., x_{d-1})` has the index `(x_0 * L_1 + x_1) * L_2 + ...`
+/// (row-major order), edges couple nearest neighbors and are pairs `[i, j]` with `i < j`
+///
+/// # Arguments
+///
+/// * `shape` - Sizes `(L_0, ..., L_{d-1})` of a lattice along all dimensions
+/// * `periodic` - Whether boundary conditions are periodic
+///
+/// # Notes
+///
+/// Periodic boundary conditions are not applied along dimensions of at most two spins,
+/// thus all returned graphs are simple
+///
+/// # Example
+///
+/// ```
+/// use gmrs::ising::topologies::lattice;
+///
+/// // a 3x4 square lattice
+/// assert_eq!(lattice(&[3, 4], false).len(), 17);
+/// // a 3x3x3 periodic cubic lattice
+/// assert_eq!(lattice(&[3, 3, 3], true).len(), 81);
+/// ```
+pub fn lattice(shape: &[usize], periodic: bool) -> Vec<[usize; 2]> {
+    let spins_number: usize = shape.iter().product();
+    let mut edges = Vec::with_capacity(shape.len() * spins_number);
+    for index in 0..spins_number {
+        // a distance between indices of neighbors along a dimension
+        let mut stride = spins_number;
+        for side in shape {
+            stride /= side;
+            let coordinate = (index / stride) % side;
+            if coordinate + 1 < *side {
+                edges.push([index, index + stride]);
+            } else if periodic && *side > 2 {
+                edges.push([index - coordinate * stride, index]);
+            }
+        }
+    }
+    edges
+}
+
+/// Returns edges of a complete graph, edges are pairs `[i, j]` with `i < j`
+/// in the lexicographic order
+///
+/// # Arguments
+///
+/// * `spins_number` - A number of spins
+///
+/// # Example
+///
+/// ```
+/// use gmrs::ising::topologies::complete;
+///
+/// assert_eq!(complete(3), vec![[0, 1], [0, 2], [1, 2]]);
+/// ```
+pub fn complete(spins_number: usize) -> Vec<[usize; 2]> {
+    (0..spins_number)
+        .flat_map(|i| ((i + 1)..spins_number).map(move |j| [i, j]))
+        .collect()
+}
+
+/// Returns edges of an Erdős–Rényi random graph, where each pair of spins is coupled
+/// independently with a given probability. Edges are pairs `[i, j]` with `i < j`
+/// in the lexicographic order
+///
+/// # Arguments
+///
+/// * `spins_number` - A number of spins
+/// * `probability` - A probability of an edge, it must be in [0, 1]
+/// * `rng` - A random numbers generator
+///
+/// # Example
+///
+/// ```
+/// use gmrs::ising::topologies::erdos_renyi;
+/// use rand::thread_rng;
+///
+/// let edges = erdos_renyi(100, 0.05, &mut thread_rng());
+/// assert!(edges.iter().all(|[i, j]| i < j && *j < 100));
+/// ```
+pub fn erdos_renyi(spins_number: usize, probability: f64, rng: &mut impl Rng) -> Vec<[usize; 2]> {
+    let mut edges = Vec::new();
+    for i in 0..spins_number {
+        for j in (i + 1)..spins_number {
+            if rng.gen_bool(probability) {
+                edges.push([i, j]);
+            }
+        }
+    }
+    edges
+}
+
+/// Returns edges of a random regular graph, where all spins have the same degree,
+/// sampled by the configuration model, i.e. by random pairing of spins' stubs,
+/// pairings producing self-loops or multi-edges are rejected. Edges are pairs `[i, j]`
+/// with `i < j`
+///
+/// # Arguments
+///
+/// * `spins_number` - A number of spins
+/// * `degree` - A degree of spins
+/// * `rng` - A random numbers generator
+///
+/// # Notes
+///
+/// Returns None if a graph does not exist, i.e. if `degree >= spins_number` or
+/// `spins_number * degree` is odd, or if a simple graph has not been sampled after
+/// a number of attempts. The rejection of a pairing happens rarely for small degrees
+///
+/// # Example
+///
+/// ```
+/// use gmrs::ising::topologies::random_regular;
+/// use rand::thread_rng;
+///
+/// let edges = random_regular(100, 3, &mut thread_rng()).unwrap();
+/// assert_eq!(edges.len(), 150);
+/// assert!(random_regular(5, 3, &mut thread_rng()).is_none());
+/// ```
+pub fn random_regular(
+    spins_number: usize,
+    degree: usize,
+    rng: &mut impl Rng,
+) -> Option<Vec<[usize; 2]>> {
+    if (degree >= spins_number && spins_number > 0) || !(spins_number * degree).is_multiple_of(2) {
+        return None;
+    }
+    let mut stubs: Vec<_> = (0..spins_number)
+        .flat_map(|spin| std::iter::repeat_n(spin, degree))
+        .collect();
+    'attempts: for _ in 0..MAX_REGULAR_GRAPH_ATTEMPTS {
+        stubs.shuffle(rng);
+        let mut edges = HashSet::with_capacity(stubs.len() / 2);
+        for pair in stubs.chunks_exact(2) {
+            let edge = [pair[0].min(pair[1]), pair[0].max(pair[1])];
+            if edge[0] == edge[1] || !edges.insert(edge) {
+                continue 'attempts;
+            }
+        }
+        let mut edges: Vec<_> = edges.into_iter().collect();
+        edges.sort_unstable();
+        return Some(edges);
+    }
+    None
+}
+
+/// Crates an Ising factor graph builder for a given topology with couplings and fields
+/// sampled independently from given distributions. The model is
+/// `exp ( sum_e J_e s_{i_e} s_{j_e} + sum_i b_i s_i )`, it is built by
+/// [`builder_from_edges`](crate::ising::builder_from_edges)
+///
+/// # Arguments
+///
+/// * `spins_number` - A number of spins
+/// * `edges` - Pairs of spins `(i_e, j_e)` coupled by edges
+/// * `coupling` - A distribution of couplings `J_e`
+/// * `field` - A distribution of fields `b_i`
+/// * `rng` - A random numbers generator
+/// * `initializer` - An initializer of messages
+///
+/// # Notes
+///
+/// Couplings are sampled in the order of edges before fields. If an edge contains
+/// an index out of range of spins, the function returns an error
+///
+/// # Example
+///
+/// ```
+/// use gmrs::ising::topologies::{lattice, random_builder};
+/// use gmrs::ising::{random_message_initializer, SumProduct};
+/// use rand::thread_rng;
+/// use rand_distr::{Normal, Uniform};
+///
+/// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+/// // a 10x10 periodic lattice with Gaussian couplings in a random field
+/// let fgb = random_builder::<SumProduct>(
+///     100,
+///     &lattice(&[10, 10], true),
+///     Normal::new(0., 1.).unwrap(),
+///     Uniform::new(-0.1, 0.1),
+///     &mut thread_rng(),
+///     &mut initializer,
+/// )
+/// .unwrap();
+/// assert_eq!(fgb.build().coupling_matrix().nnz(), 400);
+/// ```
+pub fn random_builder<T>(
+    spins_number: usize,
+    edges: &[[usize; 2]],
+    coupling: impl Distribution<f64>,
+    field: impl Distribution<f64>,
+    rng: &mut impl Rng,
+    initializer: &mut impl FnMut() -> IsingMessage,
+) -> FGBuilderResult<FactorGraphBuilder<IsingFactor<T>, IsingVariable<T>>>
+where
+    T: IsingMessagePassingType + Clone + Debug + Send,
+{
+    let couplings: Vec<_> = (&coupling)
+        .sample_iter(&mut *rng)
+        .take(edges.len())
+        .collect();
+    let fields: Vec<_> = (&field).sample_iter(&mut *rng).take(spins_number).collect();
+    builder_from_edges(edges, &couplings, &fields, initializer)
+}
@@ -0,0 +1,416 @@
+use std::fmt::Debug;
+
+use ndarray::{ArrayD, Axis, IxDyn};
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{
+    core::{nan_max, FGError, FGResult, Factor, FactorGraph, MessagePassingInfo, UnionFind},
+    ising::{
+        batched::IsingBatch, common::log_sum_exponents, IsingFactor, IsingFactorHyperParameters,
+        IsingMessage, IsingMessagePassingType, IsingVariable, SumProduct,
+    },
+};
+
+// ------------------------------------------------------------------------------------------
+
+impl IsingBatch {
+    // Updates all factor to variable messages of tree-reweighted message passing
+    // and returns the maximal discrepancy. Messages of a coupling factor are stored
+    // multiplied by its appearance probability, thus variables sum them as usual
+    fn trw_factors_sweep<T: IsingMessagePassingType>(
+        &mut self,
+        parameters: &IsingFactorHyperParameters,
+        edge_probabilities: &[f64],
+    ) -> f64 {
+        let c_number = self.couplings_number;
+        let mut max_discrepancy = 0f64;
+        for (c, rho) in edge_probabilities[..c_number].iter().enumerate() {
+            let parameters = parameters.of_group(self.groups[c]);
+            let parameters =
+                IsingFactorHyperParameters::new(parameters.beta / rho, parameters.gamma);
+            let (first, second) = (c, c + c_number);
+            let new_second = rho
+                * T::factor_message_update(
+                    IsingMessage(self.vf[first]),
+                    IsingMessage(self.fv[second] / rho),
+                    self.log_puu[c],
+                    self.log_pdu[c],
+                    self.log_pud[c],
+                    self.log_pdd[c],
+                    &parameters,
+                )
+                .0;
+            let new_first = rho
+                * T::factor_message_update(
+                    IsingMessage(self.vf[second]),
+                    IsingMessage(self.fv[first] / rho),
+                    self.log_puu[c],
+                    self.log_pud[c],
+                    self.log_pdu[c],
+                    self.log_pdd[c],
+                    &parameters,
+                )
+                .0;
+            max_discrepancy = nan_max(
+                nan_max(max_discrepancy, (new_first - self.fv[first]).abs()),
+                (new_second - self.fv[second]).abs(),
+            );
+            self.fv[first] = new_first;
+            self.fv[second] = new_second;
+        }
+        for (fv, message) in self.fv[(2 * c_number)..]
+            .iter_mut()
+            .zip(&self.unit_messages)
+        {
+            max_discrepancy = nan_max(max_discrepancy, (message - *fv).abs());
+            *fv = *message;
+        }
+        max_discrepancy
+    }
+
+    // Updates all variable to factor messages of tree-reweighted message passing
+    // and returns the maximal discrepancy, a factor's own message is removed
+    // with the weight one instead of its appearance probability
+    fn trw_variables_sweep(&mut self, parameters: f64, edge_probabilities: &[f64]) -> f64 {
+        let mut max_discrepancy = 0f64;
+        for var_index in 0..self.var_fields.len() {
+            let edges =
+                &self.var_edges[self.var_offsets[var_index]..self.var_offsets[var_index + 1]];
+            let gamma = self.var_gammas[var_index].unwrap_or(parameters);
+            let sum_all =
+                self.var_fields[var_index] + edges.iter().map(|edge| self.fv[*edge]).sum::<f64>();
+            for edge in edges {
+                let prev_message = self.vf[*edge];
+                let new_message = (1f64 - gamma)
+                    * (sum_all - self.fv[*edge] / edge_probabilities[*edge])
+                    + gamma * prev_message;
+                max_discrepancy = nan_max(max_discrepancy, (new_message - prev_message).abs());
+                self.vf[*edge] = new_message;
+            }
+        }
+        max_discrepancy
+    }
+}
+
+impl<T> FactorGraph<IsingFactor<T>, IsingVariable<T>>
+where
+    T: IsingMessagePassingType + Clone + Debug + Send,
+{
+    /// Runs tree-reweighted message passing (TRW-BP). Each coupling factor is assigned
+    /// an edge appearance probability, i.e. the probability that it belongs to a spanning
+    /// tree drawn from some distribution over spanning trees. Fixed points of the sum-product
+    /// variant give an upper bound on the logarithm of the partition function (see
+    /// [`FactorGraph::trw_log_partition_bound`]) and are often better behaved than those of
+    /// belief propagation on frustrated graphs. With all probabilities equal to one it
+    /// reduces to ordinary message passing.
+    ///
+    /// Messages are written back to a factor graph when the method returns, also on failure.
+    /// Messages sent by coupling factors are stored multiplied by their appearance
+    /// probabilities, thus [`FactorGraph::variable_marginals`] returns TRW beliefs of variables,
+    /// while marginals of coupling factors must be computed by [`FactorGraph::trw_factor_marginals`]
+    ///
+    /// # Arguments
+    ///
+    /// * `max_iterations_number` - A maximal number of iterations
+    /// * `min_iterations_number` - A minimal number of iterations
+    /// * `threshold` - A threshold specifying the convergence criterion
+    /// * `appearance_probabilities` - An edge appearance probability of each factor, it must lie in
+    ///   (0, 1], probabilities of unit factors are ignored
+    /// * `factor_scheduler` - A scheduler of a factor's messages update rule hyper-parameters
+    /// * `variable_scheduler` - A scheduler of a variable's messages update rule hyper-parameters
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use gmrs::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// // A frustrated triangle, each edge belongs to two of three spanning trees
+    /// let mut fgb = new_ising_builder::<SumProduct>(3, 3);
+    /// for (i, j) in [(0, 1), (1, 2), (2, 0)] {
+    ///     fgb.add_factor(IsingFactor::new(-1., 0.1, 0.), &[i, j], &mut initializer).unwrap();
+    /// }
+    /// let mut fg = fgb.build();
+    /// let factor_scheduler = get_standard_factor_scheduler(0.);
+    /// let variable_scheduler = get_standard_variable_scheduler(0.);
+    /// let appearance_probabilities = vec![2. / 3.; 3];
+    /// fg.run_trw_message_passing(1000, 0, 1e-10, &appearance_probabilities, &factor_scheduler, &variable_scheduler)
+    ///     .unwrap();
+    /// let bound = fg.trw_log_partition_bound(&appearance_probabilities, &factor_scheduler(0)).unwrap();
+    /// // the exact log-partition function by enumeration
+    /// let mut partition_function = 0.;
+    /// for configuration in 0..8 {
+    ///     let s = |i: usize| if (configuration >> i) & 1 == 0 { 1. } else { -1. };
+    ///     let energy: f64 = [(0, 1), (1, 2), (2, 0)].iter().map(|(i, j)| -s(*i) * s(*j) + 0.1 * s(*i)).sum();
+    ///     partition_function += f64::exp(energy);
+    /// }
+    /// assert!(bound >= f64::ln(partition_function));
+    /// ```
+    pub fn run_trw_message_passing(
+        &mut self,
+        max_iterations_number: usize,
+        min_iterations_number: usize,
+        threshold: f64,
+        appearance_probabilities: &[f64],
+        factor_scheduler: &impl Fn(usize) -> IsingFactorHyperParameters,
+        variable_scheduler: &impl Fn(usize) -> f64,
+    ) -> FGResult<MessagePassingInfo> {
+        self.check_appearance_probabilities(appearance_probabilities)?;
+        let mut batch = IsingBatch::new(self);
+        let edge_probabilities: Vec<_> = batch
+            .edge_factors
+            .iter()
+            .enumerate()
+            .map(|(edge, (fac_index, _))| {
+                if edge < 2 * batch.couplings_number {
+                    appearance_probabilities[*fac_index]
+                } else {
+                    1f64
+                }
+            })
+            .collect();
+        let mut last_discrepancy = f64::MAX;
+        let mut discrepancy_dynamics = Vec::with_capacity(max_iterations_number);
+        for i in 0..max_iterations_number {
+            let factors_discrepancy =
+                batch.trw_factors_sweep::<T>(&factor_scheduler(i), &edge_probabilities);
+            let variables_discrepancy =
+                batch.trw_variables_sweep(variable_scheduler(i), &edge_probabilities);
+            let max_discrepancy = nan_max(factors_discrepancy, variables_discrepancy);
+            discrepancy_dynamics.push(max_discrepancy);
+            last_discrepancy = max_discrepancy;
+            if (max_discrepancy < threshold) && (i + 1 >= min_iterations_number) {
+                batch.write_back(self);
+                return Ok(MessagePassingInfo {
+                    iterations_number: i,
+                    discrepancy_dynamics,
+                    last_discrepancy,
+                    certificate: self.exactness_certificate(),
+                    marginal_history: None,
+                    observables: Vec::new(),
+                });
+            }
+        }
+        batch.write_back(self);
+        self.check_contradictions()?;
+        Err(FGError::MessagePassingError {
+            iterations_number: max_iterations_number,
+            discrepancy_dynamics,
+            last_discrepancy,
+        })
+    }
+
+    /// Computes marginals of all factors after tree-reweighted message passing,
+    /// marginals of unit factors coincide with ordinary ones
+    ///
+    /// # Arguments
+    ///
+    /// * `appearance_probabilities` - An edge appearance probability of each factor
+    ///   used by [`FactorGraph::run_trw_message_passing`]
+    /// * `factor_parameters` - Parameters of factors used by message passing
+    pub fn trw_factor_marginals(
+        &self,
+        appearance_probabilities: &[f64],
+        factor_parameters: &IsingFactorHyperParameters,
+    ) -> FGResult<Vec<ArrayD<f64>>> {
+        self.check_appearance_probabilities(appearance_probabilities)?;
+        Ok(self
+            .factors
+            .iter()
+            .zip(appearance_probabilities)
+            .map(|(node, rho)| match node.factor {
+                IsingFactor::Coupling {
+                    log_puu,
+                    log_pud,
+                    log_pdu,
+                    log_pdd,
+                    group,
+                    ..
+                } => {
+                    let beta = factor_parameters.group_beta(group) / rho;
+                    let (first, second) = (node.receivers[0].0 / 2f64, node.receivers[1].0 / 2f64);
+                    let log_weights = [
+                        [
+                            beta * log_puu + first + second,
+                            beta * log_pud + first - second,
+                        ],
+                        [
+                            beta * log_pdu - first + second,
+                            beta * log_pdd - first - second,
+                        ],
+                    ];
+                    let log_total = log_sum_exponents(
+                        log_sum_exponents(log_weights[0][0], log_weights[0][1]),
+                        log_sum_exponents(log_weights[1][0], log_weights[1][1]),
+                    );
+                    let marginal = log_weights
+                        .iter()
+                        .flatten()
+                        .map(|log_weight| (log_weight - log_total).exp())
+                        .collect();
+                    ArrayD::from_shape_vec(IxDyn(&[2, 2]), marginal).unwrap()
+                }
+                IsingFactor::UnitFactor(_) => node.factor.marginal(&node.receivers),
+            })
+            .collect())
+    }
+
+    /// Returns a set of edge appearance probabilities of coupling factors induced by
+    /// a uniform distribution over several random spanning trees (forests if a factor graph
+    /// is disconnected). Trees are drawn until each coupling factor belongs to at least one tree,
+    /// probabilities of unit factors and coupling factors acting on a single spin are one
+    ///
+    /// # Arguments
+    ///
+    /// * `rng` - A random numbers generator
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, IsingFactor, IsingMessage, SumProduct};
+    /// use rand::thread_rng;
+    ///
+    /// let mut fgb = new_ising_builder::<SumProduct>(4, 4);
+    /// for i in 0..4 {
+    ///     fgb.add_factor(IsingFactor::new(1., 0., 0.), &[i, (i + 1) % 4], &mut || IsingMessage(0.)).unwrap();
+    /// }
+    /// let fg = fgb.build();
+    /// let appearance_probabilities = fg.spanning_tree_appearance_probabilities(&mut thread_rng());
+    /// // each spanning tree of a ring of 4 spins contains 3 edges
+    /// assert!((appearance_probabilities.iter().sum::<f64>() - 3.).abs() < 1e-12);
+    /// assert!(appearance_probabilities.iter().all(|rho| *rho > 0. && *rho <= 1.));
+    /// ```
+    pub fn spanning_tree_appearance_probabilities(&self, rng: &mut impl Rng) -> Vec<f64> {
+        let couplings: Vec<_> = self
+            .factors
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| {
+                matches!(node.factor, IsingFactor::Coupling { .. })
+                    && node.var_node_indices[0] != node.var_node_indices[1]
+            })
+            .map(|(fac_index, node)| {
+                (
+                    fac_index,
+                    node.var_node_indices[0],
+                    node.var_node_indices[1],
+                )
+            })
+            .collect();
+        let mut counts = vec![0usize; self.factors.len()];
+        let mut trees_number = 0usize;
+        let mut order = couplings.clone();
+        while couplings
+            .iter()
+            .any(|(fac_index, _, _)| counts[*fac_index] == 0)
+        {
+            // not yet covered couplings are tried first
+            order.shuffle(rng);
+            order.sort_by_key(|(fac_index, _, _)| counts[*fac_index] != 0);
+            let mut forest = UnionFind::new(self.variables.len());
+            for (fac_index, first, second) in &order {
+                if forest.union(*first, *second) {
+                    counts[*fac_index] += 1;
+                }
+            }
+            trees_number += 1;
+        }
+        let mut appearance_probabilities = vec![1f64; self.factors.len()];
+        for (fac_index, _, _) in &couplings {
+            appearance_probabilities[*fac_index] = counts[*fac_index] as f64 / trees_number as f64;
+        }
+        appearance_probabilities
+    }
+
+    // Checks that there is a valid probability for each coupling factor
+    fn check_appearance_probabilities(&self, appearance_probabilities: &[f64]) -> FGResult<()> {
+        if appearance_probabilities.len() != self.factors.len() {
+            return Err(FGError::AppearanceProbabilitiesSizeError(
+                self.factors.len(),
+                appearance_probabilities.len(),
+            ));
+        }
+        for (fac_index, (node, rho)) in self
+            .factors
+            .iter()
+            .zip(appearance_probabilities)
+            .enumerate()
+        {
+            let is_coupling = matches!(node.factor, IsingFactor::Coupling { .. });
+            if is_coupling && !(*rho > 0f64 && *rho <= 1f64) {
+                return Err(FGError::AppearanceProbabilityError(fac_index, *rho));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FactorGraph<IsingFactor<SumProduct>, IsingVariable<SumProduct>> {
+    /// Computes the tree-reweighted upper bound on the logarithm of the partition function,
+    /// i.e. `sum_i H(b_i) - sum_a rho_a I(b_a) + <log factors>`, where `b_i` and `b_a`
+    /// are beliefs of variables and coupling factors, `H` is an entropy and `I` is a mutual
+    /// information. It should be called after [`FactorGraph::run_trw_message_passing`] has
+    /// converged, then it is an upper bound for valid appearance probabilities, i.e.
+    /// those induced by a distribution over spanning trees. With all probabilities equal to
+    /// one it is the Bethe approximation, which is exact on trees
+    ///
+    /// # Arguments
+    ///
+    /// * `appearance_probabilities` - An edge appearance probability of each factor
+    ///   used by [`FactorGraph::run_trw_message_passing`]
+    /// * `factor_parameters` - Parameters of factors used by message passing
+    pub fn trw_log_partition_bound(
+        &self,
+        appearance_probabilities: &[f64],
+        factor_parameters: &IsingFactorHyperParameters,
+    ) -> FGResult<f64> {
+        let factor_marginals =
+            self.trw_factor_marginals(appearance_probabilities, factor_parameters)?;
+        let entropy = |p: f64| if p > 0f64 { -p * p.ln() } else { 0f64 };
+        let mut bound = 0f64;
+        for (variable, marginal) in self.variables.iter().zip(self.variable_marginals()) {
+            let magnetization = marginal[0] - marginal[1];
+            bound += variable.variable.field() * magnetization
+                + entropy(marginal[0])
+                + entropy(marginal[1]);
+        }
+        for ((node, marginal), rho) in self
+            .factors
+            .iter()
+            .zip(&factor_marginals)
+            .zip(appearance_probabilities)
+        {
+            match node.factor {
+                IsingFactor::Coupling {
+                    log_puu,
+                    log_pud,
+                    log_pdu,
+                    log_pdd,
+                    group,
+                    ..
+                } => {
+                    let beta = factor_parameters.group_beta(group);
+                    let log_factor = [[log_puu, log_pud], [log_pdu, log_pdd]];
+                    let first = marginal.sum_axis(Axis(1));
+                    let second = marginal.sum_axis(Axis(0));
+                    for a in 0..2 {
+                        for b in 0..2 {
+                            let p = marginal[[a, b]];
+                            if p > 0f64 {
+                                bound += p * beta * log_factor[a][b]
+                                    - rho * p * f64::ln(p / (first[a] * second[b]));
+                            }
+                        }
+                    }
+                }
+                // a unit factor exp ( s * b ) has the message 2 * b
+                IsingFactor::UnitFactor(message) => {
+                    bound += message / 2f64 * (marginal[[0]] - marginal[[1]]);
+                }
+            }
+        }
+        Ok(bound)
+    }
+}
@@ -1,7 +1,22 @@
 /// A module containing general logic of factor graphs
 pub mod core;
+/// A module containing expectation propagation, where messages of intractable factors
+/// are projected onto an exponential family chosen by the type of variables
+#[cfg(feature = "ep")]
+pub mod ep;
+/// A module containing readers and writers of factor graph file formats
+#[cfg(feature = "io")]
+pub mod io;
 /// A module containing message passing algorithms implementation specific for Ising like models on an arbitrary graph
 pub mod ising;
+/// A module containing Markov chain Monte Carlo samplers operating on factor graphs
+#[cfg(feature = "mcmc")]
+pub mod mcmc;
+/// A module containing Python bindings of Ising models
+#[cfg(feature = "python")]
+mod python;
+/// A module containing sum-product message passing for discrete variables and tabular factors
+pub mod tabular;
 
 #[cfg(test)]
 mod tests;
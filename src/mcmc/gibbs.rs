@@ -0,0 +1,252 @@
+use rand::Rng;
+
+use crate::core::{BatchSamplingInfo, FGError, FGResult, Factor, FactorGraph, Variable};
+
+/// A Gibbs sampler operating directly on a factor graph
+///
+/// # Notes
+///
+/// A conditional distribution of a variable given the current configuration
+/// of other variables is computed by the factors' message update rules: each
+/// adjoint factor receives messages fixing its other variables to their current
+/// values (see `Variable::sample_to_message`) and sends the resulting message
+/// to the updated variable. Thus, factor's hyper-parameters passed to the sampler
+/// must correspond to an undamped sum-product update rule
+/// (e.g. `IsingFactorHyperParameters::new(1., 0.)` for Ising models).
+/// A factor adjacent to the same variable several times does not give
+/// its conditional distribution by this rule, thus such factor graphs are rejected
+#[derive(Debug, Clone)]
+pub struct GibbsSampler<'a, F, V>
+where
+    F: Factor,
+    V: Variable<Message = F::Message>,
+{
+    factor_graph: &'a FactorGraph<F, V>,
+    state: Vec<V::Sample>,
+}
+
+impl<'a, F, V> GibbsSampler<'a, F, V>
+where
+    F: Factor,
+    V: Variable<Message = F::Message>,
+{
+    /// Creates a new Gibbs sampler starting from a given configuration
+    ///
+    /// # Arguments
+    ///
+    /// * `factor_graph` - A factor graph to sample from
+    /// * `initial_state` - An initial configuration of all variables
+    ///
+    /// # Notes
+    ///
+    /// It returns `FGError::RepeatedEdge` if a factor is adjacent to a variable several times
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use gmrs::mcmc::GibbsSampler;
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let mut fgb = new_ising_builder::<SumProduct>(2, 1);
+    /// fgb.add_factor(IsingFactor::new(0.5, 0., 0.), &[0, 1], &mut initializer).unwrap();
+    /// let fg = fgb.build();
+    ///
+    /// let sampler = GibbsSampler::new(&fg, vec![1, -1]).unwrap();
+    /// assert_eq!(sampler.state(), &[1, -1]);
+    /// assert!(GibbsSampler::new(&fg, vec![1]).is_err());
+    /// ```
+    #[inline]
+    pub fn new(
+        factor_graph: &'a FactorGraph<F, V>,
+        initial_state: Vec<V::Sample>,
+    ) -> FGResult<Self> {
+        let variables_number = factor_graph.variables.len();
+        if initial_state.len() != variables_number {
+            return Err(FGError::ConfigurationSizeError(
+                variables_number,
+                initial_state.len(),
+            ));
+        }
+        check_repeated_edges(factor_graph)?;
+        Ok(GibbsSampler {
+            factor_graph,
+            state: initial_state,
+        })
+    }
+
+    /// Creates a new Gibbs sampler starting from a configuration sampled
+    /// independently from the current beliefs of variables
+    ///
+    /// # Arguments
+    ///
+    /// * `factor_graph` - A factor graph to sample from
+    /// * `rng` - A random numbers generator
+    ///
+    /// # Notes
+    ///
+    /// It returns `FGError::RepeatedEdge` if a factor is adjacent to a variable several times
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use gmrs::mcmc::GibbsSampler;
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let mut fgb = new_ising_builder::<SumProduct>(2, 1);
+    /// fgb.add_factor(IsingFactor::new(0.5, 0., 0.), &[0, 1], &mut initializer).unwrap();
+    /// let fg = fgb.build();
+    ///
+    /// let sampler = GibbsSampler::from_beliefs(&fg, &mut thread_rng()).unwrap();
+    /// assert_eq!(sampler.state().len(), 2);
+    /// ```
+    #[inline]
+    pub fn from_beliefs(factor_graph: &'a FactorGraph<F, V>, rng: &mut impl Rng) -> FGResult<Self> {
+        check_repeated_edges(factor_graph)?;
+        let state = factor_graph
+            .variables
+            .iter()
+            .map(|var| var.sample(rng))
+            .collect();
+        Ok(GibbsSampler {
+            factor_graph,
+            state,
+        })
+    }
+
+    /// Returns the current configuration of variables
+    #[inline]
+    pub fn state(&self) -> &[V::Sample] {
+        &self.state
+    }
+
+    /// Resamples a single variable from its conditional distribution
+    /// given the current configuration of all other variables
+    ///
+    /// # Arguments
+    ///
+    /// * `var_index` - An index of a variable
+    /// * `rng` - A random numbers generator
+    /// * `parameters` - Hyper parameters of factors' message update rules
+    #[inline]
+    pub fn update_variable(
+        &mut self,
+        var_index: usize,
+        rng: &mut impl Rng,
+        parameters: &F::Parameters,
+    ) -> FGResult<()> {
+        let variable = self
+            .factor_graph
+            .variables
+            .get(var_index)
+            .ok_or(FGError::OutOfRangeVariable(self.state.len(), var_index))?;
+        let state = &self.state;
+        let substitute = |index: usize| {
+            if index == var_index {
+                None
+            } else {
+                Some(V::sample_to_message(&state[index]))
+            }
+        };
+        let messages: Vec<_> = variable
+            .fac_node_indices
+            .iter()
+            .zip(&variable.fac_node_receiver_indices)
+            .map(|(fac_index, position)| {
+                self.factor_graph.factors[*fac_index]
+                    .substituted_message(*position, substitute, parameters)
+            })
+            .collect();
+        self.state[var_index] = variable.variable.sample(&messages, rng);
+        Ok(())
+    }
+
+    /// Performs a single sweep, i.e. resamples all variables one by one
+    /// in order they were added to a factor graph
+    ///
+    /// # Arguments
+    ///
+    /// * `rng` - A random numbers generator
+    /// * `parameters` - Hyper parameters of factors' message update rules
+    #[inline]
+    pub fn sweep(&mut self, rng: &mut impl Rng, parameters: &F::Parameters) {
+        for var_index in 0..self.state.len() {
+            self.update_variable(var_index, rng, parameters).unwrap();
+        }
+    }
+
+    /// Runs a Markov chain and collects samples. Samples are not weighted,
+    /// energies can be added by [`BatchSamplingInfo::with_energies`]
+    ///
+    /// # Arguments
+    ///
+    /// * `samples_number` - A number of samples to collect
+    /// * `burn_in` - A number of sweeps performed before collecting samples
+    /// * `thinning` - A number of sweeps performed between two subsequent samples (at least one)
+    /// * `rng` - A random numbers generator
+    /// * `parameters` - Hyper parameters of factors' message update rules
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+    /// use gmrs::ising::IsingFactorHyperParameters;
+    /// use gmrs::mcmc::GibbsSampler;
+    /// use rand::thread_rng;
+    ///
+    /// let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    /// let mut fgb = new_ising_builder::<SumProduct>(3, 3);
+    /// for (i, j) in [(0, 1), (1, 2), (2, 0)] {
+    ///     fgb.add_factor(IsingFactor::new(0.5, 0., 0.), &[i, j], &mut initializer).unwrap();
+    /// }
+    /// let fg = fgb.build();
+    ///
+    /// let mut rng = thread_rng();
+    /// let parameters = IsingFactorHyperParameters::new(1., 0.);
+    /// let mut sampler = GibbsSampler::from_beliefs(&fg, &mut rng).unwrap();
+    /// let samples = sampler.run(100, 10, 1, &mut rng, &parameters);
+    /// assert_eq!(samples.samples.len(), 100);
+    /// assert!(samples.samples.iter().all(|sample| sample.len() == 3));
+    /// assert_eq!(samples.effective_sample_size(), 100.);
+    /// ```
+    pub fn run(
+        &mut self,
+        samples_number: usize,
+        burn_in: usize,
+        thinning: usize,
+        rng: &mut impl Rng,
+        parameters: &F::Parameters,
+    ) -> BatchSamplingInfo<V::Sample> {
+        for _ in 0..burn_in {
+            self.sweep(rng, parameters);
+        }
+        let mut samples = Vec::with_capacity(samples_number);
+        for _ in 0..samples_number {
+            for _ in 0..thinning.max(1) {
+                self.sweep(rng, parameters);
+            }
+            samples.push(self.state.clone());
+        }
+        BatchSamplingInfo::from_samples(samples)
+    }
+}
+
+// Checks that every factor is adjacent to each of its variables once
+fn check_repeated_edges<F, V>(factor_graph: &FactorGraph<F, V>) -> FGResult<()>
+where
+    F: Factor,
+    V: Variable<Message = F::Message>,
+{
+    for (fac_index, factor) in factor_graph.factors.iter().enumerate() {
+        let var_indices = &factor.var_node_indices;
+        for (position, var_index) in var_indices.iter().enumerate() {
+            if var_indices[..position].contains(var_index) {
+                return Err(FGError::RepeatedEdge(fac_index, *var_index));
+            }
+        }
+    }
+    Ok(())
+}
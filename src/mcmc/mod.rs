@@ -0,0 +1,3 @@
+mod gibbs;
+
+pub use gibbs::GibbsSampler;
@@ -0,0 +1,211 @@
+//! Python bindings of the Ising part of gmrs.
+//!
+//! The extension module is built by maturin with the `python` feature
+//! (`maturin develop` in the root of the repository, see `pyproject.toml`),
+//! it is tested by `pytest python/tests` and exposes the `IsingModel` class:
+//!
+//! ```python
+//! import gmrs
+//!
+//! model = gmrs.IsingModel(3, seed=42)
+//! model.add_coupling(0, 1, 0.5)
+//! model.add_coupling(1, 2, -0.5)
+//! model.set_field(0, 0.2)
+//! model.run_message_passing(max_iterations_number=1000, threshold=1e-10)
+//! marginals = model.marginals()  # a numpy array of shape (3, 2)
+//! samples = model.sample(100)  # a numpy array of shape (100, 3)
+//! ```
+
+use crate::core::{FactorGraph, FactorGraphBuilder};
+use crate::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+use crate::ising::{random_message_initializer, IsingFactor, IsingVariable, SumProduct};
+use ndarray::{Array1, Array2};
+use numpy::{IntoPyArray, PyArray1, PyArray2};
+use pyo3::exceptions::{PyIndexError, PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use rand::{rngs::StdRng, SeedableRng};
+
+type Factor = IsingFactor<SumProduct>;
+type Variable = IsingVariable<SumProduct>;
+
+/// An Ising model solved by sum-product loopy belief propagation.
+///
+/// Couplings and fields are accumulated until the first message passing run,
+/// which builds the factor graph; later structural changes are not allowed.
+#[pyclass(unsendable)]
+struct IsingModel {
+    fields: Vec<f64>,
+    couplings: Vec<(usize, usize, f64)>,
+    rng: StdRng,
+    graph: Option<FactorGraph<Factor, Variable>>,
+}
+
+impl IsingModel {
+    fn check_spin(&self, spin: usize) -> PyResult<()> {
+        if spin >= self.fields.len() {
+            return Err(PyIndexError::new_err(format!(
+                "Spin index {spin} is out of range for {} spins",
+                self.fields.len()
+            )));
+        }
+        Ok(())
+    }
+
+    fn check_not_built(&self) -> PyResult<()> {
+        if self.graph.is_some() {
+            return Err(PyRuntimeError::new_err(
+                "Model can not be modified after the first message passing run",
+            ));
+        }
+        Ok(())
+    }
+
+    fn graph(&mut self) -> PyResult<&mut FactorGraph<Factor, Variable>> {
+        if self.graph.is_none() {
+            let variables = self
+                .fields
+                .iter()
+                .map(|b| IsingVariable::new().with_field(*b));
+            let mut fgb = FactorGraphBuilder::new_with_variables(variables, self.couplings.len());
+            let mut initializer =
+                random_message_initializer(StdRng::from_rng(&mut self.rng).unwrap(), -0.5, 0.5);
+            for (lhs, rhs, coupling) in &self.couplings {
+                fgb.add_factor(
+                    IsingFactor::new(*coupling, 0., 0.),
+                    &[*lhs, *rhs],
+                    &mut initializer,
+                )
+                .map_err(|err| PyValueError::new_err(err.to_string()))?;
+            }
+            self.graph = Some(fgb.build());
+        }
+        Ok(self.graph.as_mut().unwrap())
+    }
+}
+
+#[pymethods]
+impl IsingModel {
+    /// Creates a model with a given number of spins and no interactions
+    #[new]
+    #[pyo3(signature = (spins_number, seed = None))]
+    fn new(spins_number: usize, seed: Option<u64>) -> Self {
+        let rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        IsingModel {
+            fields: vec![0f64; spins_number],
+            couplings: Vec::new(),
+            rng,
+            graph: None,
+        }
+    }
+
+    /// Number of spins
+    #[getter]
+    fn spins_number(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// Adds a term `coupling * s_lhs * s_rhs` to the negative energy
+    fn add_coupling(&mut self, lhs: usize, rhs: usize, coupling: f64) -> PyResult<()> {
+        self.check_not_built()?;
+        self.check_spin(lhs)?;
+        self.check_spin(rhs)?;
+        self.couplings.push((lhs, rhs, coupling));
+        Ok(())
+    }
+
+    /// Sets a term `field * s_spin` of the negative energy
+    fn set_field(&mut self, spin: usize, field: f64) -> PyResult<()> {
+        self.check_not_built()?;
+        self.check_spin(spin)?;
+        self.fields[spin] = field;
+        Ok(())
+    }
+
+    /// Runs message passing and returns the number of iterations,
+    /// raises RuntimeError if message passing has not converged
+    #[pyo3(signature = (max_iterations_number = 1000, min_iterations_number = 0, threshold = 1e-6, gamma = 0.))]
+    fn run_message_passing(
+        &mut self,
+        max_iterations_number: usize,
+        min_iterations_number: usize,
+        threshold: f64,
+        gamma: f64,
+    ) -> PyResult<usize> {
+        let factor_scheduler = get_standard_factor_scheduler(gamma);
+        let variable_scheduler = get_standard_variable_scheduler(gamma);
+        self.graph()?
+            .run_message_passing_parallel(
+                max_iterations_number,
+                min_iterations_number,
+                threshold,
+                &factor_scheduler,
+                &variable_scheduler,
+            )
+            .map(|info| info.iterations_number)
+            .map_err(|err| PyRuntimeError::new_err(err.to_string()))
+    }
+
+    /// Returns marginals as an array of shape (spins_number, 2), where the first
+    /// element of a row is the probability of a spin up
+    fn marginals<'py>(&mut self, py: Python<'py>) -> PyResult<Bound<'py, PyArray2<f64>>> {
+        let marginals = self.graph()?.variable_marginals();
+        let marginals = Array2::from_shape_fn((marginals.len(), 2), |(i, j)| marginals[i][j]);
+        Ok(marginals.into_pyarray(py))
+    }
+
+    /// Returns magnetizations `<s_i>` as an array of shape (spins_number,)
+    fn magnetizations<'py>(&mut self, py: Python<'py>) -> PyResult<Bound<'py, PyArray1<f64>>> {
+        let magnetizations: Array1<f64> = self
+            .graph()?
+            .variable_marginals()
+            .into_iter()
+            .map(|marginal| marginal[0] - marginal[1])
+            .collect();
+        Ok(magnetizations.into_pyarray(py))
+    }
+
+    /// Draws samples by sequential decimation without modifying the model,
+    /// returns an array of shape (samples_number, spins_number) of spins equal to ±1
+    #[pyo3(signature = (samples_number, max_iterations_number = 1000, min_iterations_number = 0, threshold = 1e-6, gamma = 0.))]
+    fn sample<'py>(
+        &mut self,
+        py: Python<'py>,
+        samples_number: usize,
+        max_iterations_number: usize,
+        min_iterations_number: usize,
+        threshold: f64,
+        gamma: f64,
+    ) -> PyResult<Bound<'py, PyArray2<i8>>> {
+        let factor_scheduler = get_standard_factor_scheduler(gamma);
+        let variable_scheduler = get_standard_variable_scheduler(gamma);
+        let mut rng = StdRng::from_rng(&mut self.rng).unwrap();
+        let info = self
+            .graph()?
+            .sample_n(
+                samples_number,
+                max_iterations_number,
+                min_iterations_number,
+                threshold,
+                &mut rng,
+                &factor_scheduler,
+                &variable_scheduler,
+            )
+            .map_err(|err| PyRuntimeError::new_err(err.to_string()))?;
+        let spins_number = self.fields.len();
+        let samples = Array2::from_shape_fn((info.samples.len(), spins_number), |(i, j)| {
+            info.samples[i][j]
+        });
+        Ok(samples.into_pyarray(py))
+    }
+}
+
+/// Python bindings of gmrs
+#[pymodule]
+#[pyo3(name = "gmrs")]
+fn gmrs_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<IsingModel>()?;
+    Ok(())
+}
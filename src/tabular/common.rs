@@ -0,0 +1,467 @@
+use crate::core::{
+    saturate_value, BoundedMessage, DampableMessage, DecodableVariable, Factor, Message, Variable,
+};
+use ndarray::{Array1, ArrayD, Dimension};
+use rand::Rng;
+use rand_distr::{Distribution, WeightedIndex};
+
+// A maximal deviation of the total probability of a valid initial message from one
+const NORMALIZATION_TOLERANCE: f64 = 1e-8;
+
+// ------------------------------------------------------------------------------------------
+
+/// A message of a tabular factor graph, i.e. an unnormalized
+/// distribution over states of a discrete variable.
+///
+/// # Notes
+///
+/// States out of the range of a non-empty message have zero weight,
+/// which allows one to create a message fixing a variable without knowing its
+/// cardinality. An empty message is uninformative, i.e. all states have unit weight
+#[derive(Debug, Clone)]
+pub struct TabularMessage(pub Array1<f64>);
+
+impl TabularMessage {
+    /// Creates an uninformative message
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::tabular::TabularMessage;
+    ///
+    /// let message = TabularMessage::uninformative();
+    /// assert_eq!(message.value(3), 1.);
+    /// ```
+    #[inline]
+    pub fn uninformative() -> Self {
+        TabularMessage(Array1::zeros(0))
+    }
+
+    /// Returns a weight of a state
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - A state of a variable
+    #[inline(always)]
+    pub fn value(&self, state: usize) -> f64 {
+        if self.0.is_empty() {
+            1f64
+        } else {
+            self.0.get(state).copied().unwrap_or(0f64)
+        }
+    }
+}
+
+impl Message for TabularMessage {
+    #[inline(always)]
+    fn discrepancy(&self, other: &Self) -> f64 {
+        (0..self.0.len().max(other.0.len()))
+            .map(|state| (self.value(state) - other.value(state)).abs())
+            .fold(0f64, f64::max)
+    }
+
+    #[inline(always)]
+    fn memcpy(&self, dst: &mut Self) {
+        if dst.0.len() == self.0.len() {
+            dst.0.assign(&self.0);
+        } else {
+            *dst = self.clone();
+        }
+    }
+}
+
+impl DampableMessage for TabularMessage {
+    #[inline(always)]
+    fn damp(&mut self, prev: &Self, gamma: f64) {
+        if self.0.len() == prev.0.len() {
+            self.0
+                .zip_mut_with(&prev.0, |x, y| *x = (1f64 - gamma) * *x + gamma * y);
+        }
+    }
+
+    #[inline(always)]
+    fn oscillates(&self, prev: &Self, prev_prev: &Self) -> bool {
+        let correlation: f64 = (0..self.0.len())
+            .map(|state| {
+                (self.value(state) - prev.value(state))
+                    * (prev.value(state) - prev_prev.value(state))
+            })
+            .sum();
+        correlation < 0f64
+    }
+}
+
+impl BoundedMessage for TabularMessage {
+    #[inline(always)]
+    fn saturate(&mut self, bound: f64, clamp: bool) -> bool {
+        let mut is_saturated = false;
+        for x in self.0.iter_mut() {
+            is_saturated |= saturate_value(x, bound, clamp);
+        }
+        is_saturated
+    }
+}
+
+// Normalizes a distribution, a zero distribution is replaced by the uniform one
+#[inline(always)]
+fn normalize(mut distribution: Array1<f64>) -> Array1<f64> {
+    let sum = distribution.sum();
+    if sum > 0f64 {
+        distribution /= sum;
+    } else {
+        distribution.fill(1f64 / distribution.len() as f64);
+    }
+    distribution
+}
+
+// Damps a new message by a previous one if they are of the same size
+#[inline(always)]
+fn damped(message: Array1<f64>, prev: &TabularMessage, gamma: f64) -> TabularMessage {
+    let mut message = TabularMessage(message);
+    if gamma != 0f64 {
+        message.damp(prev, gamma);
+    }
+    message
+}
+
+/// Crates a new tabular message initializer producing uninformative messages.
+///
+/// # Example
+///
+/// ```
+/// use gmrs::tabular::uninformative_message_initializer;
+///
+/// let mut initializer = uninformative_message_initializer();
+/// let message = initializer();
+/// ```
+pub fn uninformative_message_initializer() -> impl FnMut() -> TabularMessage {
+    TabularMessage::uninformative
+}
+
+// ------------------------------------------------------------------------------------------
+
+/// A tabular factor given by a non-negative tensor psi(x_1, ..., x_n),
+/// its message passing rule is sum-product with the damping coefficient
+/// given by parameters
+#[derive(Debug, Clone)]
+pub struct TabularFactor {
+    table: ArrayD<f64>,
+}
+
+impl TabularFactor {
+    /// Creates a new tabular factor.
+    ///
+    /// # Arguments
+    ///
+    /// * `table` - A non-negative tensor, i-th axis corresponds to i-th adjacent variable
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::tabular::TabularFactor;
+    /// use ndarray::array;
+    ///
+    /// let factor = TabularFactor::new(array![[1., 2., 0.], [0.5, 1., 3.]].into_dyn());
+    /// ```
+    #[inline]
+    pub fn new(table: ArrayD<f64>) -> Self {
+        TabularFactor { table }
+    }
+
+    /// Returns the table of a factor
+    #[inline]
+    pub fn table(&self) -> &ArrayD<f64> {
+        &self.table
+    }
+
+    // Product of the table and received messages
+    #[inline(always)]
+    fn weights(&self, messages: &[TabularMessage]) -> ArrayD<f64> {
+        let mut weights = self.table.clone();
+        for (index, value) in weights.indexed_iter_mut() {
+            let product: f64 = index
+                .as_array_view()
+                .iter()
+                .zip(messages)
+                .map(|(state, message)| message.value(*state))
+                .product();
+            *value *= product;
+        }
+        weights
+    }
+}
+
+impl Factor for TabularFactor {
+    type Message = TabularMessage;
+    type Marginal = ArrayD<f64>;
+    type Parameters = f64;
+
+    #[inline(always)]
+    fn from_message(message: &Self::Message) -> Self {
+        TabularFactor {
+            table: message.0.clone().into_dyn(),
+        }
+    }
+
+    #[inline(always)]
+    fn degree(&self) -> usize {
+        self.table.ndim()
+    }
+
+    #[inline(always)]
+    fn send_messages(&self, src: &[Self::Message], dst: &mut [Self::Message], parameters: &f64) {
+        let shape = self.table.shape();
+        let mut new_messages: Vec<_> = shape.iter().map(|dim| Array1::zeros(*dim)).collect();
+        for (index, value) in self.table.indexed_iter() {
+            if *value == 0f64 {
+                continue;
+            }
+            let index = index.as_array_view();
+            for (position, new_message) in new_messages.iter_mut().enumerate() {
+                let product: f64 = index
+                    .iter()
+                    .zip(src)
+                    .enumerate()
+                    .filter(|(other, _)| *other != position)
+                    .map(|(_, (state, message))| message.value(*state))
+                    .product();
+                new_message[index[position]] += value * product;
+            }
+        }
+        for (d, new_message) in dst.iter_mut().zip(new_messages) {
+            *d = damped(normalize(new_message), d, *parameters);
+        }
+    }
+
+    #[inline(always)]
+    fn marginal(&self, messages: &[Self::Message]) -> Self::Marginal {
+        let mut marginal = self.weights(messages);
+        let sum = marginal.sum();
+        marginal /= sum;
+        marginal
+    }
+
+    #[inline(always)]
+    fn factor(&self) -> Self::Marginal {
+        self.table.clone()
+    }
+
+    #[inline(always)]
+    fn rescaled(&self, target: &Self::Marginal, current: &Self::Marginal) -> Option<Self> {
+        if target.shape() != self.table.shape() || current.shape() != self.table.shape() {
+            return None;
+        }
+        let mut table = self.table.clone();
+        for ((value, t), c) in table.iter_mut().zip(target).zip(current) {
+            if !(t.is_finite() && *t >= 0f64) {
+                return None;
+            }
+            if *c > 0f64 {
+                *value *= t / c;
+            } else if *t > 0f64 {
+                return None;
+            }
+        }
+        Some(TabularFactor { table })
+    }
+
+    #[inline(always)]
+    fn is_contradictory(&self, messages: &[Self::Message]) -> bool {
+        let sum = self.weights(messages).sum();
+        sum == 0f64 || sum.is_nan()
+    }
+}
+
+// ------------------------------------------------------------------------------------------
+
+/// A discrete variable with a given number of states,
+/// its message passing rule is sum-product with the damping coefficient
+/// given by parameters
+#[derive(Debug, Clone, Copy)]
+pub struct TabularVariable {
+    cardinality: usize,
+}
+
+impl TabularVariable {
+    /// Creates a new variable.
+    ///
+    /// # Arguments
+    ///
+    /// * `cardinality` - A number of states of a variable
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::tabular::TabularVariable;
+    ///
+    /// let var = TabularVariable::new(3);
+    /// assert_eq!(var.cardinality(), 3);
+    /// ```
+    #[inline]
+    pub fn new(cardinality: usize) -> Self {
+        TabularVariable { cardinality }
+    }
+
+    /// Returns a number of states of a variable
+    #[inline]
+    pub fn cardinality(&self) -> usize {
+        self.cardinality
+    }
+
+    // Product of messages excluding the given one
+    #[inline(always)]
+    fn product(&self, messages: &[TabularMessage], excluded: Option<usize>) -> Array1<f64> {
+        Array1::from_shape_fn(self.cardinality, |state| {
+            messages
+                .iter()
+                .enumerate()
+                .filter(|(position, _)| Some(*position) != excluded)
+                .map(|(_, message)| message.value(state))
+                .product()
+        })
+    }
+}
+
+impl Variable for TabularVariable {
+    type Message = TabularMessage;
+    type Marginal = Array1<f64>;
+    type Parameters = f64;
+    type Sample = usize;
+
+    #[inline(always)]
+    fn send_messages(&self, src: &[Self::Message], dst: &mut [Self::Message], parameters: &f64) {
+        for (position, d) in dst.iter_mut().enumerate() {
+            let new_message = normalize(self.product(src, Some(position)));
+            *d = damped(new_message, d, *parameters);
+        }
+    }
+
+    #[inline(always)]
+    fn marginal(&self, messages: &[Self::Message]) -> Self::Marginal {
+        normalize(self.product(messages, None))
+    }
+
+    #[inline(always)]
+    fn sample(&self, messages: &[Self::Message], rng: &mut impl Rng) -> Self::Sample {
+        let marginal = self.marginal(messages);
+        WeightedIndex::new(marginal.iter()).unwrap().sample(rng)
+    }
+
+    #[inline(always)]
+    fn pinned(&self, messages: &[Self::Message], tolerance: f64) -> Option<Self::Sample> {
+        let state = self.argmax(messages);
+        let marginal = self.marginal(messages);
+        if 1f64 - marginal[state] < tolerance {
+            Some(state)
+        } else {
+            None
+        }
+    }
+
+    #[inline(always)]
+    fn marginal_gap(&self, messages: &[Self::Message]) -> Option<f64> {
+        let marginal = self.marginal(messages);
+        let (mut first, mut second) = (0f64, 0f64);
+        for p in &marginal {
+            if *p > first {
+                second = first;
+                first = *p;
+            } else if *p > second {
+                second = *p;
+            }
+        }
+        Some(first - second)
+    }
+
+    #[inline(always)]
+    fn sample_to_message(sample: &Self::Sample) -> Self::Message {
+        let mut message = Array1::zeros(sample + 1);
+        message[*sample] = 1f64;
+        TabularMessage(message)
+    }
+
+    #[inline(always)]
+    fn sample_to_soft_message(
+        &self,
+        sample: &Self::Sample,
+        probability: f64,
+    ) -> Option<Self::Message> {
+        if *sample >= self.cardinality {
+            return None;
+        }
+        let rest = (1f64 - probability) / (self.cardinality - 1).max(1) as f64;
+        let mut message = Array1::from_elem(self.cardinality, rest);
+        message[*sample] = probability;
+        Some(TabularMessage(message))
+    }
+
+    #[inline(always)]
+    fn marginal_to_message(
+        &self,
+        marginal: &Self::Marginal,
+        messages: &[Self::Message],
+    ) -> Option<Self::Message> {
+        if marginal.len() != self.cardinality
+            || marginal.iter().any(|p| !(p.is_finite() && *p >= 0f64))
+            || marginal.sum() <= 0f64
+        {
+            return None;
+        }
+        let product = self.product(messages, None);
+        let mut message = Array1::zeros(self.cardinality);
+        for ((m, target), current) in message.iter_mut().zip(marginal).zip(&product) {
+            if *current > 0f64 {
+                *m = target / current;
+            } else if *target > 0f64 {
+                return None;
+            }
+        }
+        Some(TabularMessage(normalize(message)))
+    }
+
+    #[inline(always)]
+    fn is_valid_message(&self, message: &Self::Message) -> bool {
+        message.0.is_empty()
+            || (message.0.len() == self.cardinality
+                && message.0.iter().all(|p| p.is_finite() && *p >= 0f64)
+                && (message.0.sum() - 1f64).abs() < NORMALIZATION_TOLERANCE)
+    }
+
+    #[inline(always)]
+    fn sample_index(&self, sample: &Self::Sample) -> Option<usize> {
+        (*sample < self.cardinality).then_some(*sample)
+    }
+
+    #[inline(always)]
+    fn marginal_discrepancy(
+        &self,
+        marginal: &Self::Marginal,
+        other: &Self::Marginal,
+    ) -> Option<f64> {
+        (marginal.len() == other.len()).then(|| {
+            marginal
+                .iter()
+                .zip(other)
+                .fold(0f64, |acc, (p, q)| acc.max((p - q).abs()))
+        })
+    }
+
+    #[inline(always)]
+    fn marginal_values(&self, marginal: &Self::Marginal) -> Option<Vec<f64>> {
+        Some(marginal.to_vec())
+    }
+}
+
+impl DecodableVariable for TabularVariable {
+    #[inline(always)]
+    fn argmax(&self, messages: &[Self::Message]) -> Self::Sample {
+        let marginal = self.marginal(messages);
+        let mut argmax = 0;
+        for (state, p) in marginal.iter().enumerate() {
+            if *p > marginal[argmax] {
+                argmax = state;
+            }
+        }
+        argmax
+    }
+}
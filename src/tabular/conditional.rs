@@ -0,0 +1,127 @@
+use crate::core::{ConditionalFactor, Factor};
+use crate::tabular::{TabularFactor, TabularMessage};
+use ndarray::{ArrayD, Axis};
+use rand::Rng;
+use rand_distr::{Distribution, WeightedIndex};
+
+// ------------------------------------------------------------------------------------------
+
+/// A tabular factor representing a conditional probability table
+/// p(x_child | x_parents), in message passing it behaves as a usual tabular factor
+#[derive(Debug, Clone)]
+pub struct ConditionalTabularFactor {
+    factor: TabularFactor,
+    child: usize,
+}
+
+impl ConditionalTabularFactor {
+    /// Creates a new conditional tabular factor.
+    ///
+    /// # Arguments
+    ///
+    /// * `table` - A conditional probability table, i-th axis corresponds to i-th adjacent variable
+    /// * `child` - An axis corresponding to a child
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::tabular::ConditionalTabularFactor;
+    /// use ndarray::array;
+    ///
+    /// // p(x_1 | x_0)
+    /// let factor = ConditionalTabularFactor::new(array![[0.9, 0.1], [0.3, 0.7]].into_dyn(), 1);
+    /// ```
+    #[inline]
+    pub fn new(table: ArrayD<f64>, child: usize) -> Self {
+        assert!(
+            child < table.ndim(),
+            "Child axis {} is out of range of a table with {} axes",
+            child,
+            table.ndim()
+        );
+        ConditionalTabularFactor {
+            factor: TabularFactor::new(table),
+            child,
+        }
+    }
+
+    /// Returns the conditional probability table of a factor
+    #[inline]
+    pub fn table(&self) -> &ArrayD<f64> {
+        self.factor.table()
+    }
+}
+
+impl Factor for ConditionalTabularFactor {
+    type Message = TabularMessage;
+    type Marginal = ArrayD<f64>;
+    type Parameters = f64;
+
+    #[inline(always)]
+    fn from_message(message: &Self::Message) -> Self {
+        let mut table = message.0.clone();
+        let sum = table.sum();
+        if sum > 0f64 {
+            table /= sum;
+        }
+        ConditionalTabularFactor {
+            factor: TabularFactor::new(table.into_dyn()),
+            child: 0,
+        }
+    }
+
+    #[inline(always)]
+    fn degree(&self) -> usize {
+        self.factor.degree()
+    }
+
+    #[inline(always)]
+    fn send_messages(&self, src: &[Self::Message], dst: &mut [Self::Message], parameters: &f64) {
+        self.factor.send_messages(src, dst, parameters)
+    }
+
+    #[inline(always)]
+    fn marginal(&self, messages: &[Self::Message]) -> Self::Marginal {
+        self.factor.marginal(messages)
+    }
+
+    #[inline(always)]
+    fn factor(&self) -> Self::Marginal {
+        self.factor.factor()
+    }
+
+    #[inline(always)]
+    fn is_contradictory(&self, messages: &[Self::Message]) -> bool {
+        self.factor.is_contradictory(messages)
+    }
+}
+
+impl ConditionalFactor<usize> for ConditionalTabularFactor {
+    #[inline(always)]
+    fn child_position(&self) -> usize {
+        self.child
+    }
+
+    fn normalization_error(&self) -> f64 {
+        self.table()
+            .lanes(Axis(self.child))
+            .into_iter()
+            .map(|lane| (lane.sum() - 1f64).abs())
+            .fold(0f64, f64::max)
+    }
+
+    fn sample_child(&self, parents: &[usize], rng: &mut impl Rng) -> usize {
+        let mut distribution = self.table().view();
+        let mut axes: Vec<_> = (0..self.degree())
+            .filter(|axis| *axis != self.child)
+            .collect();
+        // removing axes from the last one keeps indices of the remaining axes valid
+        axes.reverse();
+        for (axis, state) in axes.into_iter().zip(parents.iter().rev()) {
+            distribution = distribution.index_axis_move(Axis(axis), *state);
+        }
+        WeightedIndex::new(distribution.iter())
+            .expect("Conditional distribution must be non-negative and normalized")
+            .sample(rng)
+    }
+}
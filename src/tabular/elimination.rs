@@ -0,0 +1,141 @@
+use crate::core::{FGError, FGResult, FactorGraph};
+use crate::tabular::{uninformative_message_initializer, TabularFactor, TabularVariable};
+use ndarray::{ArrayD, Dimension, IxDyn};
+
+// ------------------------------------------------------------------------------------------
+
+/// A way a variable is eliminated from a tabular model
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Elimination {
+    /// A variable is summed out, marginals of the rest of a model are kept
+    Sum,
+
+    /// A variable is maximized out, max-marginals of the rest of a model are kept
+    Max,
+}
+
+impl FactorGraph<TabularFactor, TabularVariable> {
+    /// Eliminates a variable exactly, i.e. replaces the product of its adjoint factors
+    /// by a single factor over its neighbors, where the variable is summed or maximized out.
+    /// It shrinks a model before running message passing on the remainder
+    ///
+    /// # Arguments
+    ///
+    /// * `var_index` - The index of an eliminated variable
+    /// * `elimination` - Whether a variable is summed or maximized out
+    ///
+    /// # Notes
+    ///
+    /// Indices of variables following an eliminated one are shifted down, adjoint factors
+    /// are removed with indices of following factors shifted down and a new factor
+    /// is appended to the end of factors. Messages of the new factor are uninformative,
+    /// messages of the rest of edges are kept. A variable without neighbors is eliminated
+    /// without adding a factor, i.e. the normalization constant is dropped. The size of
+    /// a new factor grows exponentially with the number of neighbors
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::core::FactorGraphBuilder;
+    /// use gmrs::tabular::{
+    ///     uninformative_message_initializer, Elimination, TabularFactor, TabularVariable,
+    /// };
+    /// use ndarray::array;
+    ///
+    /// let mut initializer = uninformative_message_initializer();
+    ///
+    /// // A chain of 3 binary variables
+    /// let mut fgb = FactorGraphBuilder::new_with_variables(vec![TabularVariable::new(2); 3], 2);
+    /// let factor = TabularFactor::new(array![[2., 1.], [1., 2.]].into_dyn());
+    /// fgb.add_factor(factor.clone(), &[0, 1], &mut initializer).unwrap();
+    /// fgb.add_factor(factor, &[1, 2], &mut initializer).unwrap();
+    /// let mut fg = fgb.build();
+    ///
+    /// // The middle variable is summed out
+    /// fg.eliminate_variable(1, Elimination::Sum).unwrap();
+    /// assert_eq!(fg.get_variable_degrees(), vec![1, 1]);
+    /// assert_eq!(fg.get_factor(0).table(), &array![[5., 4.], [4., 5.]].into_dyn());
+    /// ```
+    pub fn eliminate_variable(
+        &mut self,
+        var_index: usize,
+        elimination: Elimination,
+    ) -> FGResult<()> {
+        let variables_number = self.variables.len();
+        if var_index >= variables_number {
+            return Err(FGError::OutOfRangeVariable(variables_number, var_index));
+        }
+        let mut is_removed = vec![false; self.factors.len()];
+        let mut neighbors = Vec::new();
+        for fac_index in &self.variables[var_index].fac_node_indices {
+            is_removed[*fac_index] = true;
+            for neighbor in &self.factors[*fac_index].var_node_indices {
+                if *neighbor != var_index && !neighbors.contains(neighbor) {
+                    neighbors.push(*neighbor);
+                }
+            }
+        }
+        // adjoint factors with axes given by positions of their variables in
+        // `neighbors`, the eliminated variable corresponds to the last position
+        let factors: Vec<_> = is_removed
+            .iter()
+            .zip(&self.factors)
+            .filter(|(is_removed, _)| **is_removed)
+            .map(|(_, node)| {
+                let positions: Vec<_> = node
+                    .var_node_indices
+                    .iter()
+                    .map(|index| {
+                        neighbors
+                            .iter()
+                            .position(|neighbor| neighbor == index)
+                            .unwrap_or(neighbors.len())
+                    })
+                    .collect();
+                (node.factor.table(), positions)
+            })
+            .collect();
+        let shape: Vec<_> = neighbors
+            .iter()
+            .map(|neighbor| self.variables[*neighbor].variable.cardinality())
+            .collect();
+        let cardinality = self.variables[var_index].variable.cardinality();
+        let mut table = ArrayD::<f64>::zeros(IxDyn(&shape));
+        let mut states = vec![0; neighbors.len() + 1];
+        let mut factor_index = Vec::new();
+        for (index, value) in table.indexed_iter_mut() {
+            states[..neighbors.len()].copy_from_slice(index.slice());
+            for state in 0..cardinality {
+                states[neighbors.len()] = state;
+                let mut product = 1f64;
+                for (factor_table, positions) in &factors {
+                    factor_index.clear();
+                    factor_index.extend(positions.iter().map(|position| states[*position]));
+                    // states out of the range of a table have zero weight
+                    product *= factor_table
+                        .get(IxDyn(&factor_index))
+                        .copied()
+                        .unwrap_or(0f64);
+                }
+                *value = match elimination {
+                    Elimination::Sum => *value + product,
+                    Elimination::Max => value.max(product),
+                };
+            }
+        }
+        self.remove_nodes(&is_removed, Some(var_index));
+        if neighbors.is_empty() {
+            return Ok(());
+        }
+        for neighbor in &mut neighbors {
+            if *neighbor > var_index {
+                *neighbor -= 1;
+            }
+        }
+        self.add_factor(
+            TabularFactor::new(table),
+            &neighbors,
+            &mut uninformative_message_initializer(),
+        )
+    }
+}
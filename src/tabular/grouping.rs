@@ -0,0 +1,359 @@
+use std::collections::HashMap;
+
+use crate::core::{FGBuilderError, FGBuilderResult, Factor, FactorGraph, FactorGraphBuilder};
+use crate::tabular::{TabularFactor, TabularMessage, TabularVariable};
+use ndarray::{Array1, ArrayD, IxDyn};
+
+// ------------------------------------------------------------------------------------------
+
+/// A map between variables of a tabular model and super-variables of a factor graph
+/// built by [`GroupedBuilder`]. A super-variable is either a group of variables with
+/// the joint domain or a single not grouped variable. A joint state of a group
+/// enumerates states of its members in the row-major order, i.e. the state of the last
+/// member changes fastest
+#[derive(Debug, Clone)]
+pub struct Grouping {
+    cardinalities: Vec<usize>,
+    // a super-variable of each variable and the position of a variable in it
+    positions: Vec<(usize, usize)>,
+    members: Vec<Vec<usize>>,
+}
+
+impl Grouping {
+    /// Returns the number of variables of a tabular model
+    #[inline]
+    pub fn variables_number(&self) -> usize {
+        self.cardinalities.len()
+    }
+
+    /// Returns the number of super-variables, i.e. variables of a built factor graph
+    #[inline]
+    pub fn super_variables_number(&self) -> usize {
+        self.members.len()
+    }
+
+    /// Returns the index of a super-variable containing a variable
+    ///
+    /// # Arguments
+    ///
+    /// * `var_index` - An index of a variable of a tabular model
+    #[inline]
+    pub fn super_variable(&self, var_index: usize) -> usize {
+        self.positions[var_index].0
+    }
+
+    /// Returns variables of a super-variable in the order of their states in joint states
+    ///
+    /// # Arguments
+    ///
+    /// * `super_index` - An index of a super-variable
+    #[inline]
+    pub fn members(&self, super_index: usize) -> &[usize] {
+        &self.members[super_index]
+    }
+
+    /// Returns the number of joint states of a super-variable
+    ///
+    /// # Arguments
+    ///
+    /// * `super_index` - An index of a super-variable
+    #[inline]
+    pub fn cardinality(&self, super_index: usize) -> usize {
+        self.members[super_index]
+            .iter()
+            .map(|var_index| self.cardinalities[*var_index])
+            .product()
+    }
+
+    /// Returns states of members of a super-variable given its joint state
+    ///
+    /// # Arguments
+    ///
+    /// * `super_index` - An index of a super-variable
+    /// * `joint_state` - A joint state of a super-variable
+    pub fn decode(&self, super_index: usize, mut joint_state: usize) -> Vec<usize> {
+        let members = &self.members[super_index];
+        let mut states = vec![0; members.len()];
+        for (state, var_index) in states.iter_mut().zip(members).rev() {
+            let cardinality = self.cardinalities[*var_index];
+            *state = joint_state % cardinality;
+            joint_state /= cardinality;
+        }
+        states
+    }
+
+    /// Maps a sample of super-variables to a sample of variables of a tabular model
+    ///
+    /// # Arguments
+    ///
+    /// * `samples` - Joint states of super-variables
+    pub fn samples(&self, samples: &[usize]) -> Vec<usize> {
+        let mut states = vec![0; self.variables_number()];
+        for (super_index, joint_state) in samples.iter().enumerate() {
+            let members = &self.members[super_index];
+            for (var_index, state) in members.iter().zip(self.decode(super_index, *joint_state)) {
+                states[*var_index] = state;
+            }
+        }
+        states
+    }
+
+    /// Maps marginals of super-variables to marginals of variables of a tabular model
+    ///
+    /// # Arguments
+    ///
+    /// * `marginals` - Marginals of super-variables, e.g. computed by
+    ///   [`FactorGraph::variable_marginals`]
+    pub fn marginals(&self, marginals: &[Array1<f64>]) -> Vec<Array1<f64>> {
+        let mut var_marginals: Vec<_> = self
+            .cardinalities
+            .iter()
+            .map(|cardinality| Array1::zeros(*cardinality))
+            .collect();
+        for (super_index, marginal) in marginals.iter().enumerate() {
+            let members = &self.members[super_index];
+            for (joint_state, p) in marginal.iter().enumerate() {
+                let states = self.decode(super_index, joint_state);
+                for (var_index, state) in members.iter().zip(states) {
+                    var_marginals[*var_index][state] += p;
+                }
+            }
+        }
+        var_marginals
+    }
+
+    // Returns a table of a factor over super-variables, given a table of a factor
+    // over variables, and indices of super-variables sorted in ascending order
+    fn lift(&self, table: &ArrayD<f64>, var_indices: &[usize]) -> (ArrayD<f64>, Vec<usize>) {
+        let mut super_indices: Vec<_> = var_indices
+            .iter()
+            .map(|var_index| self.super_variable(*var_index))
+            .collect();
+        super_indices.sort_unstable();
+        super_indices.dedup();
+        let shape: Vec<_> = super_indices
+            .iter()
+            .map(|super_index| self.cardinality(*super_index))
+            .collect();
+        let axes: Vec<_> = var_indices
+            .iter()
+            .map(|var_index| {
+                let (super_index, position) = self.positions[*var_index];
+                (super_indices.binary_search(&super_index).unwrap(), position)
+            })
+            .collect();
+        let lifted = ArrayD::from_shape_fn(IxDyn(&shape), |index| {
+            let joint_states: Vec<_> = super_indices
+                .iter()
+                .enumerate()
+                .map(|(axis, super_index)| self.decode(*super_index, index[axis]))
+                .collect();
+            let var_states: Vec<_> = axes
+                .iter()
+                .map(|(axis, position)| joint_states[*axis][*position])
+                .collect();
+            table[IxDyn(&var_states)]
+        });
+        (lifted, super_indices)
+    }
+}
+
+/// A builder of a tabular factor graph where groups of variables are merged into
+/// super-variables with joint domains, messages of super-variables are distributions
+/// over product spaces. Grouping variables of short loops turns the loops into trees
+/// of super-variables, which is the standard trick to restore the accuracy of belief
+/// propagation on models with many short loops
+///
+/// # Notes
+///
+/// Factors are lifted to super-variables, i.e. a factor is attached to super-variables
+/// of its variables and its table is extended to their joint domains. Factors attached
+/// to the same set of super-variables are multiplied into a single factor, e.g. factors
+/// within a group become a single unary factor. Sizes of joint domains and lifted tables
+/// grow exponentially with sizes of groups
+///
+/// # Example
+///
+/// ```
+/// use gmrs::tabular::{uninformative_message_initializer, GroupedBuilder, TabularFactor, TabularVariable};
+/// use ndarray::array;
+///
+/// // A frustrated triangle of binary variables
+/// let mut fgb = GroupedBuilder::new();
+/// for _ in 0..3 {
+///     fgb.add_variable(TabularVariable::new(2));
+/// }
+/// let factor = TabularFactor::new(array![[1., 3.], [3., 1.]].into_dyn());
+/// for (i, j) in [(0, 1), (1, 2), (2, 0)] {
+///     fgb.add_factor(factor.clone(), &[i, j]).unwrap();
+/// }
+///
+/// // The group turns the triangle into a tree of two super-variables
+/// fgb.add_group(&[0, 1]).unwrap();
+/// let (mut fg, grouping) = fgb.build(&mut uninformative_message_initializer()).unwrap();
+/// assert_eq!(fg.get_factor_degrees(), vec![1, 2]);
+/// assert_eq!(grouping.members(0), &[0, 1]);
+/// fg.run_message_passing_parallel(100, 0, 1e-10, &|_| 0., &|_| 0.).unwrap();
+///
+/// // Exact marginals are uniform by symmetry
+/// let marginals = grouping.marginals(&fg.variable_marginals());
+/// assert!(marginals.iter().flatten().all(|p| (p - 0.5).abs() < 1e-10));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct GroupedBuilder {
+    variables: Vec<TabularVariable>,
+    group_indices: Vec<Option<usize>>,
+    groups: Vec<Vec<usize>>,
+    factors: Vec<(TabularFactor, Vec<usize>)>,
+}
+
+impl GroupedBuilder {
+    /// Creates an empty builder
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a variable and returns its index
+    ///
+    /// # Arguments
+    ///
+    /// * `variable` - A variable to add
+    #[inline]
+    pub fn add_variable(&mut self, variable: TabularVariable) -> usize {
+        self.variables.push(variable);
+        self.group_indices.push(None);
+        self.variables.len() - 1
+    }
+
+    /// Declares a group of variables treated as a single super-variable,
+    /// groups could be declared before or after adding factors
+    ///
+    /// # Arguments
+    ///
+    /// * `var_indices` - Indices of variables of a group, the order of variables
+    ///   defines the order of their states in joint states
+    ///
+    /// # Notes
+    ///
+    /// A variable could belong to a single group only, otherwise
+    /// [`FGBuilderError::DuplicateGroupMember`] is returned and a builder is left unchanged
+    pub fn add_group(&mut self, var_indices: &[usize]) -> FGBuilderResult<()> {
+        let variables_number = self.variables.len();
+        for (position, var_index) in var_indices.iter().enumerate() {
+            if *var_index >= variables_number {
+                return Err(FGBuilderError::OutOfRangeVariable(
+                    variables_number,
+                    *var_index,
+                ));
+            }
+            if self.group_indices[*var_index].is_some()
+                || var_indices[..position].contains(var_index)
+            {
+                return Err(FGBuilderError::DuplicateGroupMember(*var_index));
+            }
+        }
+        for var_index in var_indices {
+            self.group_indices[*var_index] = Some(self.groups.len());
+        }
+        self.groups.push(var_indices.to_vec());
+        Ok(())
+    }
+
+    /// Adds a factor over variables
+    ///
+    /// # Arguments
+    ///
+    /// * `factor` - A new factor
+    /// * `var_indices` - Indices of adjoint variables
+    pub fn add_factor(
+        &mut self,
+        factor: TabularFactor,
+        var_indices: &[usize],
+    ) -> FGBuilderResult<()> {
+        let variables_number = self.variables.len();
+        if factor.degree() != var_indices.len() {
+            return Err(FGBuilderError::DegreeError(
+                factor.degree(),
+                var_indices.to_vec(),
+            ));
+        }
+        if let Some(var_index) = var_indices
+            .iter()
+            .find(|var_index| **var_index >= variables_number)
+        {
+            return Err(FGBuilderError::OutOfRangeVariable(
+                variables_number,
+                *var_index,
+            ));
+        }
+        self.factors.push((factor, var_indices.to_vec()));
+        Ok(())
+    }
+
+    /// Returns a factor graph over super-variables and the map between variables
+    /// and super-variables
+    ///
+    /// # Arguments
+    ///
+    /// * `message_initializer` - An object that initializes messages
+    ///
+    /// # Notes
+    ///
+    /// Super-variables are ordered by their first variables. Lifted factors
+    /// are ordered by their first factors and list super-variables in ascending order
+    pub fn build(
+        self,
+        message_initializer: &mut impl FnMut() -> TabularMessage,
+    ) -> FGBuilderResult<(FactorGraph<TabularFactor, TabularVariable>, Grouping)> {
+        let cardinalities: Vec<_> = self.variables.iter().map(|v| v.cardinality()).collect();
+        let mut positions = vec![(0, 0); cardinalities.len()];
+        let mut members: Vec<Vec<usize>> = Vec::new();
+        let mut is_group_added = vec![false; self.groups.len()];
+        for (var_index, group_index) in self.group_indices.iter().enumerate() {
+            let group = match group_index {
+                None => vec![var_index],
+                Some(group_index) if !is_group_added[*group_index] => {
+                    is_group_added[*group_index] = true;
+                    self.groups[*group_index].clone()
+                }
+                Some(_) => continue,
+            };
+            for (position, member) in group.iter().enumerate() {
+                positions[*member] = (members.len(), position);
+            }
+            members.push(group);
+        }
+        let grouping = Grouping {
+            cardinalities,
+            positions,
+            members,
+        };
+        let mut lifted_factors: Vec<(ArrayD<f64>, Vec<usize>)> = Vec::new();
+        let mut lifted_indices: HashMap<Vec<usize>, usize> = HashMap::new();
+        for (factor, var_indices) in &self.factors {
+            let (table, super_indices) = grouping.lift(factor.table(), var_indices);
+            match lifted_indices.get(&super_indices) {
+                Some(lifted_index) => lifted_factors[*lifted_index].0 *= &table,
+                None => {
+                    lifted_indices.insert(super_indices.clone(), lifted_factors.len());
+                    lifted_factors.push((table, super_indices));
+                }
+            }
+        }
+        let mut fgb =
+            FactorGraphBuilder::new_with_capacity(grouping.members.len(), lifted_factors.len());
+        fgb.add_variables(
+            (0..grouping.super_variables_number())
+                .map(|super_index| TabularVariable::new(grouping.cardinality(super_index))),
+        );
+        for (table, super_indices) in lifted_factors {
+            fgb.add_factor(
+                TabularFactor::new(table),
+                &super_indices,
+                message_initializer,
+            )?;
+        }
+        Ok((fgb.build(), grouping))
+    }
+}
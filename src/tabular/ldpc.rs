@@ -0,0 +1,380 @@
+use rand::{seq::SliceRandom, Rng};
+
+use crate::core::{CooMatrix, FGBuilderResult, FactorGraphBuilder};
+use crate::tabular::{TabularFactor, TabularMessage, TabularVariable};
+use ndarray::{ArrayD, Dimension, IxDyn};
+
+// A maximal number of random swaps tried to resolve a conflict of an edge
+const MAX_SWAP_ATTEMPTS: usize = 1000;
+
+// ------------------------------------------------------------------------------------------
+
+/// Short cycles removed from a Tanner graph sampled by the configuration model
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CycleRemoval {
+    /// Nothing is removed, a variable connected to a check several times is
+    /// connected to it once if the number of connections is odd and is disconnected
+    /// from it otherwise, since a parity check is a sum modulo 2
+    None,
+
+    /// Multi-edges, i.e. cycles of length 2, are removed by swapping edges,
+    /// thus degrees of all nodes are preserved
+    MultiEdges,
+
+    /// Multi-edges and cycles of length 4 are removed by swapping edges,
+    /// i.e. the girth of a Tanner graph is at least 6
+    FourCycles,
+}
+
+/// A binary low-density parity-check (LDPC) code given by parity checks, i.e. sets of
+/// code bits whose sum modulo 2 is zero. Codes are sampled from regular and irregular
+/// ensembles by the configuration model, i.e. by a random matching of sockets
+/// of variable nodes and check nodes of a Tanner graph
+#[derive(Debug, Clone)]
+pub struct LdpcCode {
+    variables_number: usize,
+    checks: Vec<Vec<usize>>,
+}
+
+impl LdpcCode {
+    /// Samples a code from the regular `(dv, dc)` ensemble, where each code bit
+    /// belongs to `dv` checks and each check contains `dc` code bits
+    ///
+    /// # Arguments
+    ///
+    /// * `variables_number` - A number of code bits `n`
+    /// * `variable_degree` - A degree `dv` of variable nodes
+    /// * `check_degree` - A degree `dc` of check nodes
+    /// * `cycle_removal` - Short cycles removed from a Tanner graph
+    /// * `rng` - A random numbers generator
+    ///
+    /// # Notes
+    ///
+    /// The number of checks is `n * dv / dc`, if it is not an integer, the method returns
+    /// None. It also returns None if short cycles could not be removed
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::tabular::{CycleRemoval, LdpcCode};
+    /// use rand::thread_rng;
+    ///
+    /// let code = LdpcCode::regular(120, 3, 6, CycleRemoval::FourCycles, &mut thread_rng()).unwrap();
+    /// assert_eq!(code.checks_number(), 60);
+    /// assert!(code.checks().iter().all(|check| check.len() == 6));
+    /// assert!(code.is_codeword(&[0; 120]));
+    /// ```
+    pub fn regular(
+        variables_number: usize,
+        variable_degree: usize,
+        check_degree: usize,
+        cycle_removal: CycleRemoval,
+        rng: &mut impl Rng,
+    ) -> Option<Self> {
+        let sockets_number = variables_number * variable_degree;
+        if check_degree == 0 || !sockets_number.is_multiple_of(check_degree) {
+            return None;
+        }
+        Self::irregular(
+            &vec![variable_degree; variables_number],
+            &vec![check_degree; sockets_number / check_degree],
+            cycle_removal,
+            rng,
+        )
+    }
+
+    /// Samples a code from an irregular ensemble with given degrees of variable nodes
+    /// and check nodes
+    ///
+    /// # Arguments
+    ///
+    /// * `variable_degrees` - Degrees of variable nodes, i.e. numbers of checks
+    ///   containing code bits
+    /// * `check_degrees` - Degrees of check nodes, i.e. numbers of code bits in checks
+    /// * `cycle_removal` - Short cycles removed from a Tanner graph
+    /// * `rng` - A random numbers generator
+    ///
+    /// # Notes
+    ///
+    /// Degrees are usually sampled from degree distributions of an ensemble.
+    /// If sums of degrees of variable nodes and check nodes differ, the method
+    /// returns None. It also returns None if short cycles could not be removed
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::tabular::{CycleRemoval, LdpcCode};
+    /// use rand::thread_rng;
+    ///
+    /// // half of code bits of degree 2 and half of degree 4, checks of degree 6
+    /// let variable_degrees: Vec<_> = (0..120).map(|i| if i % 2 == 0 { 2 } else { 4 }).collect();
+    /// let code = LdpcCode::irregular(&variable_degrees, &[6; 60], CycleRemoval::MultiEdges, &mut thread_rng())
+    ///     .unwrap();
+    /// assert_eq!(code.parity_check_matrix().nnz(), 360);
+    /// ```
+    pub fn irregular(
+        variable_degrees: &[usize],
+        check_degrees: &[usize],
+        cycle_removal: CycleRemoval,
+        rng: &mut impl Rng,
+    ) -> Option<Self> {
+        if variable_degrees.iter().sum::<usize>() != check_degrees.iter().sum::<usize>() {
+            return None;
+        }
+        let mut variable_sockets: Vec<_> = sockets(variable_degrees);
+        variable_sockets.shuffle(rng);
+        let mut tanner_graph = TannerGraph::new(
+            variable_degrees.len(),
+            check_degrees.len(),
+            variable_sockets.into_iter().zip(sockets(check_degrees)),
+        );
+        if cycle_removal != CycleRemoval::None {
+            let four_cycles = cycle_removal == CycleRemoval::FourCycles;
+            for edge in 0..tanner_graph.edges.len() {
+                if !tanner_graph.resolve(edge, four_cycles, rng) {
+                    return None;
+                }
+            }
+        }
+        let checks = tanner_graph
+            .check_variables
+            .into_iter()
+            .map(|mut variables| {
+                variables.sort_unstable();
+                // variables connected to a check an even number of times cancel
+                let mut check: Vec<usize> = Vec::with_capacity(variables.len());
+                for variable in variables {
+                    if check.last() == Some(&variable) {
+                        check.pop();
+                    } else {
+                        check.push(variable);
+                    }
+                }
+                check
+            })
+            .collect();
+        Some(LdpcCode {
+            variables_number: variable_degrees.len(),
+            checks,
+        })
+    }
+
+    /// Returns the number of code bits
+    #[inline]
+    pub fn variables_number(&self) -> usize {
+        self.variables_number
+    }
+
+    /// Returns the number of checks
+    #[inline]
+    pub fn checks_number(&self) -> usize {
+        self.checks.len()
+    }
+
+    /// Returns checks, i.e. sorted indices of code bits of each check
+    #[inline]
+    pub fn checks(&self) -> &[Vec<usize>] {
+        &self.checks
+    }
+
+    /// Returns the parity-check matrix `H` of shape (checks number, code bits number),
+    /// i.e. `H_ij = 1` if a code bit `j` belongs to a check `i`
+    pub fn parity_check_matrix(&self) -> CooMatrix {
+        let mut matrix = CooMatrix::new((self.checks.len(), self.variables_number));
+        for (check_index, check) in self.checks.iter().enumerate() {
+            for variable in check {
+                matrix.row_indices.push(check_index);
+                matrix.col_indices.push(*variable);
+                matrix.values.push(1f64);
+            }
+        }
+        matrix
+    }
+
+    /// Returns whether a word satisfies all checks
+    ///
+    /// # Arguments
+    ///
+    /// * `word` - Values of code bits, either 0 or 1
+    pub fn is_codeword(&self, word: &[usize]) -> bool {
+        assert_eq!(
+            word.len(),
+            self.variables_number,
+            "Length of a word must be equal to the number of code bits"
+        );
+        self.checks.iter().all(|check| {
+            check
+                .iter()
+                .map(|variable| word[*variable])
+                .sum::<usize>()
+                .is_multiple_of(2)
+        })
+    }
+
+    /// Returns a builder of the factor graph of a code, i.e. of the uniform distribution
+    /// over codewords. Variables are binary tabular variables, the factor of each check is
+    /// a table equal to 1 for assignments of even parity and to 0 otherwise. Factors
+    /// have indices of checks
+    ///
+    /// # Arguments
+    ///
+    /// * `message_initializer` - An object that initializes messages
+    ///
+    /// # Notes
+    ///
+    /// The size of a table grows exponentially with the degree of a check. To decode
+    /// a received word, unit factors of a channel's likelihoods are added to a builder
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gmrs::tabular::{uninformative_message_initializer, CycleRemoval, LdpcCode};
+    /// use rand::thread_rng;
+    ///
+    /// let code = LdpcCode::regular(60, 3, 6, CycleRemoval::FourCycles, &mut thread_rng()).unwrap();
+    /// let fg = code.builder(&mut uninformative_message_initializer()).unwrap().build();
+    /// assert_eq!(fg.get_variable_degrees(), vec![3; 60]);
+    /// ```
+    pub fn builder(
+        &self,
+        message_initializer: &mut impl FnMut() -> TabularMessage,
+    ) -> FGBuilderResult<FactorGraphBuilder<TabularFactor, TabularVariable>> {
+        let mut fgb = FactorGraphBuilder::new_with_variables(
+            vec![TabularVariable::new(2); self.variables_number],
+            self.checks.len(),
+        );
+        // sampled codes may contain identical checks
+        fgb.set_multi_edges_allowed(true);
+        let factors = self.checks.iter().map(|check| {
+            let table = ArrayD::from_shape_fn(IxDyn(&vec![2; check.len()]), |index| {
+                if index.slice().iter().sum::<usize>().is_multiple_of(2) {
+                    1f64
+                } else {
+                    0f64
+                }
+            });
+            (TabularFactor::new(table), check)
+        });
+        fgb.add_factors(factors, message_initializer)?;
+        fgb.set_multi_edges_allowed(false);
+        Ok(fgb)
+    }
+}
+
+// Returns sockets of nodes, i.e. the index of each node repeated its degree times
+fn sockets(degrees: &[usize]) -> Vec<usize> {
+    degrees
+        .iter()
+        .enumerate()
+        .flat_map(|(node, degree)| std::iter::repeat_n(node, *degree))
+        .collect()
+}
+
+// A Tanner graph of the configuration model, nodes are connected by multi-edges
+struct TannerGraph {
+    // pairs (variable, check)
+    edges: Vec<(usize, usize)>,
+    variable_checks: Vec<Vec<usize>>,
+    check_variables: Vec<Vec<usize>>,
+}
+
+impl TannerGraph {
+    fn new(
+        variables_number: usize,
+        checks_number: usize,
+        edges: impl Iterator<Item = (usize, usize)>,
+    ) -> Self {
+        let mut tanner_graph = TannerGraph {
+            edges: Vec::new(),
+            variable_checks: vec![Vec::new(); variables_number],
+            check_variables: vec![Vec::new(); checks_number],
+        };
+        for (variable, check) in edges {
+            tanner_graph.edges.push((variable, check));
+            tanner_graph.variable_checks[variable].push(check);
+            tanner_graph.check_variables[check].push(variable);
+        }
+        tanner_graph
+    }
+
+    // Returns whether an edge belongs to a multi-edge or to a cycle of length 4
+    fn is_conflicting(&self, edge: usize, four_cycles: bool) -> bool {
+        let (variable, check) = self.edges[edge];
+        let check_variables = &self.check_variables[check];
+        if check_variables
+            .iter()
+            .filter(|other| **other == variable)
+            .count()
+            > 1
+        {
+            return true;
+        }
+        four_cycles
+            && self.variable_checks[variable]
+                .iter()
+                .filter(|other_check| **other_check != check)
+                .any(|other_check| {
+                    self.check_variables[*other_check]
+                        .iter()
+                        .any(|other| *other != variable && check_variables.contains(other))
+                })
+    }
+
+    // Swaps variables of two edges
+    fn swap(&mut self, lhs: usize, rhs: usize) {
+        let ((lhs_variable, lhs_check), (rhs_variable, rhs_check)) =
+            (self.edges[lhs], self.edges[rhs]);
+        replace(
+            &mut self.check_variables[lhs_check],
+            lhs_variable,
+            rhs_variable,
+        );
+        replace(
+            &mut self.check_variables[rhs_check],
+            rhs_variable,
+            lhs_variable,
+        );
+        replace(
+            &mut self.variable_checks[lhs_variable],
+            lhs_check,
+            rhs_check,
+        );
+        replace(
+            &mut self.variable_checks[rhs_variable],
+            rhs_check,
+            lhs_check,
+        );
+        self.edges[lhs].0 = rhs_variable;
+        self.edges[rhs].0 = lhs_variable;
+    }
+
+    // Resolves a conflict of an edge by swapping it with random edges, a swap is accepted
+    // only if both new edges are not conflicting, thus new conflicts never appear.
+    // Returns false if a conflict has not been resolved
+    fn resolve(&mut self, edge: usize, four_cycles: bool, rng: &mut impl Rng) -> bool {
+        if !self.is_conflicting(edge, four_cycles) {
+            return true;
+        }
+        for _ in 0..MAX_SWAP_ATTEMPTS {
+            let other = rng.gen_range(0..self.edges.len());
+            if self.edges[other].0 == self.edges[edge].0
+                || self.edges[other].1 == self.edges[edge].1
+            {
+                continue;
+            }
+            self.swap(edge, other);
+            if !self.is_conflicting(edge, four_cycles) && !self.is_conflicting(other, four_cycles) {
+                return true;
+            }
+            self.swap(edge, other);
+        }
+        false
+    }
+}
+
+// Replaces an occurrence of a node in a list of neighbors
+fn replace(neighbors: &mut [usize], old: usize, new: usize) {
+    if let Some(neighbor) = neighbors.iter_mut().find(|neighbor| **neighbor == old) {
+        *neighbor = new;
+    }
+}
@@ -0,0 +1,13 @@
+mod common;
+mod conditional;
+mod elimination;
+mod grouping;
+mod ldpc;
+
+pub use common::{
+    uninformative_message_initializer, TabularFactor, TabularMessage, TabularVariable,
+};
+pub use conditional::ConditionalTabularFactor;
+pub use elimination::Elimination;
+pub use grouping::{GroupedBuilder, Grouping};
+pub use ldpc::{CycleRemoval, LdpcCode};
@@ -0,0 +1,101 @@
+use crate::core::{
+    AllocationCounter, DampingMode, FactorGraphBuilder, MessagePassingOptions, StochasticUpdates,
+};
+use crate::ising::schedulers::{get_standard_variable_scheduler, IsingFactorHyperParameters};
+use crate::ising::{random_message_initializer, IsingFactor, IsingVariable, SumProduct};
+use crate::tabular::{uninformative_message_initializer, TabularFactor, TabularVariable};
+use ndarray::array;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rand_distr::Uniform;
+use std::ops::ControlFlow;
+
+#[global_allocator]
+static GLOBAL: AllocationCounter = AllocationCounter;
+
+#[test]
+fn steady_state_allocations_test() {
+    let spins_number = 100;
+    let mut rng = StdRng::seed_from_u64(42);
+    let distr = Uniform::new(-0.3, 0.3);
+    let variables =
+        (0..spins_number).map(|_| IsingVariable::<SumProduct>::new().with_field(rng.sample(distr)));
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(7), -0.5, 0.5);
+    let mut fgb = FactorGraphBuilder::<IsingFactor<SumProduct>, _>::new_with_variables(
+        variables,
+        2 * spins_number,
+    );
+    for _ in 0..2 * spins_number {
+        let lhs = rng.sample(Uniform::new(0, spins_number));
+        let rhs = (lhs + rng.sample(Uniform::new(1, spins_number))) % spins_number;
+        fgb.add_factor(
+            IsingFactor::new(rng.sample(distr), 0., 0.),
+            &[lhs, rhs],
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    let mut fg = fgb.build();
+    let factor_scheduler = |_| IsingFactorHyperParameters::new(1., 0.5);
+    let variable_scheduler = get_standard_variable_scheduler(0.1);
+    // debug builds panic if a sweep after the first one allocates
+    AllocationCounter::audit(|| {
+        fg.run_message_passing_parallel(1000, 10, 1e-8, &factor_scheduler, &variable_scheduler)
+    })
+    .unwrap();
+    // factors selected by stochastic updates for the first time do not allocate either
+    let mut stochastic_fg = fg.clone();
+    let mut options = MessagePassingOptions::new(
+        |_| IsingFactorHyperParameters::new(1., 0.),
+        &variable_scheduler,
+    )
+    .with_max_iterations_number(10000)
+    .with_min_iterations_number(10)
+    .with_threshold(1e-8)
+    .with_damping(DampingMode::Stochastic(StochasticUpdates {
+        batch_fraction: 0.1,
+        damping: 0.5,
+        seed: 3,
+    }));
+    AllocationCounter::audit(|| stochastic_fg.run_message_passing_with_options(&mut options))
+        .unwrap();
+    let mut fg = fg.clone();
+    let mut allocations_dynamics = Vec::with_capacity(100);
+    let mut options = MessagePassingOptions::new(&factor_scheduler, &variable_scheduler)
+        .with_max_iterations_number(100)
+        .with_min_iterations_number(100)
+        .with_threshold(0.)
+        .with_observer(|_, _, _| {
+            allocations_dynamics.push(AllocationCounter::allocations_number());
+            ControlFlow::Continue(())
+        });
+    let _ = fg.run_message_passing_with_options(&mut options);
+    assert!(allocations_dynamics
+        .windows(2)
+        .skip(1)
+        .all(|w| w[0] == w[1]));
+}
+
+// the audit is a debug assertion, release builds do not panic
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "message passing sweep allocated")]
+fn allocating_factor_test() {
+    let mut fgb = FactorGraphBuilder::new_with_variables(
+        [TabularVariable::new(2), TabularVariable::new(3)],
+        1,
+    );
+    fgb.add_factor(
+        TabularFactor::new(array![[1., 2., 0.], [0.5, 1., 3.]].into_dyn()),
+        &[0, 1],
+        &mut uninformative_message_initializer(),
+    )
+    .unwrap();
+    let mut fg = fgb.build();
+    // without auditing allocating factors are allowed
+    let _ = fg
+        .clone()
+        .run_message_passing_parallel(10, 10, 0., &|_| 0., &|_| 0.);
+    AllocationCounter::audit(|| {
+        let _ = fg.run_message_passing_parallel(10, 10, 0., &|_| 0., &|_| 0.);
+    });
+}
@@ -0,0 +1,760 @@
+use std::collections::HashSet;
+
+use super::torus_fg;
+use crate::core::{
+    DotOptions, ExactnessCertificate, FGBuilderError, FGError, Factor, FactorGraphBuilder,
+};
+use crate::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+use crate::ising::topologies::{
+    chain, complete, erdos_renyi, lattice, random_builder, random_regular,
+};
+use crate::ising::{
+    new_ising_builder, new_ising_ring_builder, random_message_initializer, IsingFactor,
+    IsingFactorHyperParameters, IsingMessage, IsingVariable, MaxProduct, SumProduct,
+};
+use crate::tabular::{
+    uninformative_message_initializer, GroupedBuilder, TabularFactor, TabularVariable,
+};
+use ndarray::{Array1, ArrayD, IxDyn};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rand_distr::Uniform;
+
+#[test]
+fn torus_spectral_transition_test() {
+    // odd side makes the torus non-bipartite
+    let side = 5;
+    let coupling = 0.5f64;
+    let spins_number = side * side;
+    let fg = torus_fg(side, IsingFactor::new(coupling, 0., 0.), 42);
+    let hessian = fg.bethe_hessian(1.).to_dense();
+    assert_eq!(hessian, hessian.t());
+    let (nb_matrix, edges) = fg.non_backtracking_matrix(1.);
+    assert_eq!(edges.len(), 4 * spins_number);
+    assert_eq!(nb_matrix.shape, (4 * spins_number, 4 * spins_number));
+    assert_eq!(nb_matrix.nnz(), 3 * 4 * spins_number);
+    // the transition of a 4-regular graph is at 3 tanh(beta J) = 1
+    let critical_beta = f64::atanh(1. / 3.) / coupling;
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    for beta in [
+        0.5 * critical_beta,
+        0.9 * critical_beta,
+        1.1 * critical_beta,
+        2. * critical_beta,
+    ] {
+        let t = f64::tanh(beta * coupling);
+        let (eigenvalue, _) = fg
+            .bethe_hessian_smallest_eigenpair(beta, 100000, 1e-10)
+            .unwrap();
+        assert!(
+            (eigenvalue - (1. - 3. * t) / (1. + t)).abs() < 1e-8,
+            "beta: {beta}, eigenvalue: {eigenvalue}"
+        );
+        let spectral_radius = fg
+            .non_backtracking_spectral_radius(beta, 100000, 1e-10)
+            .unwrap();
+        assert!(
+            (spectral_radius - 3. * t).abs() < 1e-8,
+            "beta: {beta}, spectral radius: {spectral_radius}"
+        );
+        // belief propagation leaves the paramagnetic point iff it is unstable
+        let mut initializer = random_message_initializer(StdRng::seed_from_u64(7), 0., 0.1);
+        let mut fgb = new_ising_builder::<SumProduct>(spins_number, 2 * spins_number);
+        for i in 0..side {
+            for j in 0..side {
+                let spin = i * side + j;
+                let right = i * side + (j + 1) % side;
+                let down = ((i + 1) % side) * side + j;
+                for neighbor in [right, down] {
+                    fgb.add_factor(
+                        IsingFactor::new(beta * coupling, 0., 0.),
+                        &[spin, neighbor],
+                        &mut initializer,
+                    )
+                    .unwrap();
+                }
+            }
+        }
+        let mut bp_fg = fgb.build();
+        bp_fg
+            .run_message_passing_parallel(10000, 0, 1e-12, &factor_scheduler, &variable_scheduler)
+            .unwrap();
+        let magnetization = bp_fg.variable_marginals()[0][0] - 0.5;
+        assert_eq!(magnetization.abs() > 1e-3, eigenvalue < 0., "beta: {beta}");
+    }
+}
+
+#[test]
+fn convergence_certificate_test() {
+    let mut rng = StdRng::seed_from_u64(42);
+    let spins_number = 30;
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(7), -2., 2.);
+    let mut fgb = new_ising_builder::<SumProduct>(spins_number, 4 * spins_number);
+    // a random graph of degree at most 4 with asymmetric coupling factors
+    let mut degrees = vec![0; spins_number];
+    for _ in 0..(2 * spins_number) {
+        let (lhs, rhs) = (
+            rng.gen_range(0..spins_number),
+            rng.gen_range(0..spins_number),
+        );
+        if lhs == rhs || degrees[lhs] == 4 || degrees[rhs] == 4 {
+            continue;
+        }
+        degrees[lhs] += 1;
+        degrees[rhs] += 1;
+        let factor = IsingFactor::new(
+            rng.gen_range(-0.25f64..0.25f64),
+            rng.gen_range(-0.5f64..0.5f64),
+            rng.gen_range(-0.5f64..0.5f64),
+        )
+        .with_annealing_group(rng.gen_range(0..2));
+        fgb.add_factor(factor, &[lhs, rhs], &mut initializer)
+            .unwrap();
+    }
+    for spin in 0..spins_number {
+        fgb.add_factor(
+            IsingFactor::UnitFactor(rng.gen_range(-1f64..1f64)),
+            &[spin],
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    let fg = fgb.build();
+    let parameters = IsingFactorHyperParameters::new(1., 0.3).with_group_betas(vec![0.5]);
+    let factor_scheduler = |_| parameters.clone();
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    let certificate = fg.convergence_certificate(&parameters).unwrap();
+    assert!(certificate.contraction_rate < 1.);
+    let mut fixed_point_fg = fg.clone();
+    fixed_point_fg
+        .run_message_passing_parallel(10000, 0, 1e-14, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    // distances to the fixed point are within certified bounds
+    for iterations_number in 1..30 {
+        let mut fg = fg.clone();
+        assert!(matches!(
+            fg.run_message_passing_parallel(
+                iterations_number,
+                0,
+                0.,
+                &factor_scheduler,
+                &variable_scheduler,
+            ),
+            Err(FGError::MessagePassingError { .. })
+        ));
+        let distance = fg
+            .factors
+            .iter()
+            .zip(&fixed_point_fg.factors)
+            .flat_map(|(lhs, rhs)| lhs.messages.iter().zip(&rhs.messages))
+            .map(|(lhs, rhs)| (lhs.0 - rhs.0).abs())
+            .fold(0f64, f64::max);
+        assert!(distance <= certificate.distance_bound(iterations_number) + 1e-12);
+    }
+    // the a-priori iterations bound is sufficient
+    for threshold in [1e-3, 1e-6, 1e-10] {
+        let max_iterations_number = certificate.iterations_bound(threshold);
+        let mut fg = fg.clone();
+        let info = fg
+            .run_message_passing_parallel(
+                max_iterations_number,
+                0,
+                threshold,
+                &factor_scheduler,
+                &variable_scheduler,
+            )
+            .unwrap();
+        assert!(info.iterations_number < max_iterations_number);
+    }
+    // strong couplings are not certified
+    let mut fgb = new_ising_builder::<SumProduct>(4, 6);
+    for (lhs, rhs) in [(0, 1), (0, 2), (0, 3), (1, 2), (1, 3), (2, 3)] {
+        fgb.add_factor(IsingFactor::new(1., 0., 0.), &[lhs, rhs], &mut initializer)
+            .unwrap();
+    }
+    let fg = fgb.build();
+    assert!(fg
+        .convergence_certificate(&IsingFactorHyperParameters::new(1., 0.))
+        .is_none());
+    let certificate = fg
+        .convergence_certificate(&IsingFactorHyperParameters::new(0.3, 0.))
+        .unwrap();
+    assert!((certificate.contraction_rate - 2. * f64::tanh(0.3)).abs() < 1e-12);
+    assert!((certificate.initial_distance - 1.2).abs() < 1e-12);
+    // variables with own damping are not certified
+    let variables = vec![IsingVariable::<SumProduct>::new().with_damping(0.5); 2];
+    let mut fgb = FactorGraphBuilder::new_with_variables(variables, 1);
+    fgb.add_factor(IsingFactor::new(0.1, 0., 0.), &[0, 1], &mut initializer)
+        .unwrap();
+    assert!(fgb
+        .build()
+        .convergence_certificate(&IsingFactorHyperParameters::new(1., 0.))
+        .is_none());
+}
+
+#[test]
+fn single_cycle_max_product_test() {
+    let spins_number = 12;
+    let mut rng = StdRng::seed_from_u64(42);
+    let distr = Uniform::new(-1., 1.);
+    let couplings: Vec<f64> = (0..spins_number).map(|_| rng.sample(distr)).collect();
+    let fields: Vec<f64> = (0..spins_number).map(|_| rng.sample(distr)).collect();
+    let mut initializer = random_message_initializer(rng, -0.5, 0.5);
+    let mut fgb = new_ising_builder::<MaxProduct>(spins_number, spins_number);
+    for i in 0..spins_number {
+        fgb.add_factor(
+            IsingFactor::new(couplings[i], fields[i], 0.),
+            &[i, (i + 1) % spins_number],
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    let mut fg = fgb.build();
+    let info = fg
+        .run_message_passing_parallel(
+            10000,
+            0,
+            1e-10,
+            &get_standard_factor_scheduler(0.5),
+            &get_standard_variable_scheduler(0.5),
+        )
+        .unwrap();
+    assert_eq!(info.certificate, Some(ExactnessCertificate::SingleCycle));
+    let energy = |config: &[i8]| -> f64 {
+        (0..spins_number)
+            .map(|i| {
+                let (s1, s2) = (config[i] as f64, config[(i + 1) % spins_number] as f64);
+                couplings[i] * s1 * s2 + fields[i] * s1
+            })
+            .sum()
+    };
+    let exact_energy = (0..(1 << spins_number))
+        .map(|x: usize| {
+            let config: Vec<i8> = (0..spins_number)
+                .map(|i| if (x >> i) & 1 == 1 { 1 } else { -1 })
+                .collect();
+            energy(&config)
+        })
+        .fold(f64::MIN, f64::max);
+    let assignment = fg.map_assignment(&IsingFactorHyperParameters::new(1., 0.));
+    assert!((energy(&assignment) - exact_energy).abs() < 1e-10);
+    // a frozen variable attaches a unit factor that does not create cycles
+    fg.freeze_variable(&assignment[0], 0).unwrap();
+    assert_eq!(
+        fg.exactness_certificate(),
+        Some(ExactnessCertificate::SingleCycle)
+    );
+    // a coupling closing a second cycle invalidates the cached certificate
+    fg.add_factor(
+        IsingFactor::new(0.5, 0., 0.),
+        &[0, spins_number / 2],
+        &mut random_message_initializer(StdRng::seed_from_u64(7), -0.5, 0.5),
+    )
+    .unwrap();
+    assert_eq!(fg.exactness_certificate(), None);
+}
+
+#[test]
+fn repeated_variable_certificate_test() {
+    // a factor adjoint to the same variable twice does not form a cycle
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(42), -0.5, 0.5);
+    let mut fgb = new_ising_builder::<MaxProduct>(3, 2);
+    fgb.set_multi_edges_allowed(true);
+    fgb.add_factor(IsingFactor::new(0.5, 0., 0.), &[0, 0], &mut initializer)
+        .unwrap();
+    fgb.add_factor(IsingFactor::new(0.5, 0., 0.), &[0, 1], &mut initializer)
+        .unwrap();
+    let fg = fgb.build();
+    assert!(!fg.has_cycles());
+    assert_eq!(fg.exactness_certificate(), Some(ExactnessCertificate::Tree));
+}
+
+#[test]
+fn lattice_cycles_test() {
+    let side = 5;
+    let spins_number = side * side;
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(42), -0.5, 0.5);
+    let mut fgb = new_ising_builder::<SumProduct>(spins_number, 3 * spins_number);
+    let mut bonds = Vec::new();
+    for i in 0..side {
+        for j in 0..(side - 1) {
+            bonds.push([i * side + j, i * side + j + 1]);
+            bonds.push([j * side + i, (j + 1) * side + i]);
+        }
+    }
+    for bond in &bonds {
+        fgb.add_factor(IsingFactor::new(0.5, 0., 0.), bond, &mut initializer)
+            .unwrap();
+    }
+    // unit factors do not form cycles
+    for spin in 0..spins_number {
+        fgb.add_factor(IsingFactor::UnitFactor(0.1), &[spin], &mut initializer)
+            .unwrap();
+    }
+    let mut fg = fgb.build();
+    assert!(fg.has_cycles());
+    assert_eq!(fg.girth(), Some(8));
+    assert_eq!(fg.short_cycles(6).variables, vec![0; spins_number]);
+    // shortest cycles are plaquettes
+    let counts = fg.short_cycles(8);
+    for spin in 0..spins_number {
+        let (i, j) = (spin / side, spin % side);
+        let rows = if i == 0 || i == side - 1 { 1 } else { 2 };
+        let cols = if j == 0 || j == side - 1 { 1 } else { 2 };
+        assert_eq!(counts.variables[spin], rows * cols);
+    }
+    for (fac_index, [first, second]) in bonds.iter().enumerate() {
+        let horizontal = second - first == 1;
+        let position = if horizontal {
+            first / side
+        } else {
+            first % side
+        };
+        let expected = if position == 0 || position == side - 1 {
+            1
+        } else {
+            2
+        };
+        assert_eq!(counts.factors[fac_index], expected);
+    }
+    assert!(counts.factors[bonds.len()..]
+        .iter()
+        .all(|count| *count == 0));
+    // a parallel coupling forms a cycle of length 4
+    fg.add_factor(IsingFactor::new(0.5, 0., 0.), &[1, 0], &mut initializer)
+        .unwrap();
+    assert_eq!(fg.girth(), Some(4));
+    let counts = fg.short_cycles(4);
+    assert_eq!(counts.variables[..3], [1, 1, 0]);
+    assert_eq!(counts.factors[0], 1);
+    assert_eq!(counts.factors[bonds.len() + spins_number], 1);
+    assert_eq!(counts.factors.iter().sum::<usize>(), 2);
+}
+
+#[test]
+fn tree_cycles_test() {
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(42), -0.5, 0.5);
+    let spins_number = 20;
+    let mut fgb = new_ising_builder::<SumProduct>(spins_number, spins_number - 1);
+    for spin in 1..spins_number {
+        fgb.add_factor(
+            IsingFactor::new(0.5, 0., 0.),
+            &[(spin - 1) / 2, spin],
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    let fg = fgb.build();
+    assert!(!fg.has_cycles());
+    assert_eq!(fg.girth(), None);
+    let counts = fg.short_cycles(spins_number);
+    assert!(counts.variables.iter().all(|count| *count == 0));
+    assert!(counts.factors.iter().all(|count| *count == 0));
+    // a higher order factor closes cycles with pairwise ones
+    let mut fgb = FactorGraphBuilder::<TabularFactor, TabularVariable>::new_with_variables(
+        vec![TabularVariable::new(2); 3],
+        3,
+    );
+    let mut initializer = uninformative_message_initializer();
+    fgb.add_factor(
+        TabularFactor::new(ArrayD::ones(vec![2; 3])),
+        &[0, 1, 2],
+        &mut initializer,
+    )
+    .unwrap();
+    fgb.add_factor(
+        TabularFactor::new(ArrayD::ones(vec![2; 2])),
+        &[0, 1],
+        &mut initializer,
+    )
+    .unwrap();
+    fgb.add_factor(
+        TabularFactor::new(ArrayD::ones(vec![2; 2])),
+        &[1, 2],
+        &mut initializer,
+    )
+    .unwrap();
+    let fg = fgb.build();
+    assert!(fg.has_cycles());
+    assert_eq!(fg.girth(), Some(4));
+    let counts = fg.short_cycles(4);
+    assert_eq!(counts.variables, vec![1, 2, 1]);
+    assert_eq!(counts.factors, vec![2, 1, 1]);
+    // the cycle through all nodes
+    let counts = fg.short_cycles(6);
+    assert_eq!(counts.variables, vec![2, 3, 2]);
+    assert_eq!(counts.factors, vec![3, 2, 2]);
+}
+
+#[test]
+fn components_test() {
+    let spins_number = 300;
+    let couplings_number = 120;
+    let error = 1e-10;
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    let mut rng = StdRng::seed_from_u64(42);
+    let distr = Uniform::new(-0.5, 0.5);
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(7), -0.5, 0.5);
+    // a diluted graph has many small components and isolated spins
+    let mut fgb = new_ising_builder::<SumProduct>(spins_number, couplings_number + 10);
+    for _ in 0..couplings_number {
+        let lhs = rng.sample(Uniform::new(0, spins_number));
+        let rhs = (lhs + rng.sample(Uniform::new(1, spins_number))) % spins_number;
+        fgb.add_factor(
+            IsingFactor::new(rng.sample(distr), rng.sample(distr), rng.sample(distr)),
+            &[lhs, rhs],
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    for spin in 0..10 {
+        fgb.add_factor(
+            IsingFactor::UnitFactor(rng.sample(distr)),
+            &[spin],
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    let mut fg = fgb.build();
+    let components = fg.connected_components();
+    assert!(components.len() > 1);
+    let mut variables: Vec<_> = components
+        .iter()
+        .flat_map(|c| c.variables.iter().copied())
+        .collect();
+    let mut factors: Vec<_> = components
+        .iter()
+        .flat_map(|c| c.factors.iter().copied())
+        .collect();
+    variables.sort();
+    factors.sort();
+    assert_eq!(variables, (0..spins_number).collect::<Vec<_>>());
+    assert_eq!(factors, (0..couplings_number + 10).collect::<Vec<_>>());
+    for (fac_index, var_index) in fg.edges() {
+        let component = components
+            .iter()
+            .find(|c| c.factors.contains(&fac_index))
+            .unwrap();
+        assert!(component.variables.contains(&var_index));
+    }
+    // components solved separately agree with the whole factor graph
+    let subgraphs = fg.split_components();
+    fg.run_message_passing_parallel(1000, 0, error, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    let marginals = fg.variable_marginals();
+    let factor_marginals = fg.factor_marginals();
+    for (component, mut subgraph) in subgraphs {
+        assert_eq!(
+            subgraph.get_factor_degrees(),
+            component
+                .factors
+                .iter()
+                .map(|fac_index| fg.get_factor_degrees()[*fac_index])
+                .collect::<Vec<_>>()
+        );
+        subgraph
+            .run_message_passing_parallel(1000, 0, error, &factor_scheduler, &variable_scheduler)
+            .unwrap();
+        for (var_index, marginal) in component
+            .variables
+            .iter()
+            .zip(subgraph.variable_marginals())
+        {
+            assert!((marginals[*var_index][0] - marginal[0]).abs() < 1e-8);
+        }
+        for (fac_index, marginal) in component.factors.iter().zip(subgraph.factor_marginals()) {
+            for (lhs, rhs) in factor_marginals[*fac_index].iter().zip(marginal.iter()) {
+                assert!((lhs - rhs).abs() < 1e-8);
+            }
+        }
+    }
+}
+
+// Returns degrees of spins checking that a graph is simple
+fn degrees(spins_number: usize, edges: &[[usize; 2]]) -> Vec<usize> {
+    let mut degrees = vec![0; spins_number];
+    let mut unique_edges = HashSet::new();
+    for [i, j] in edges {
+        assert!(i < j);
+        assert!(unique_edges.insert([*i, *j]));
+        degrees[*i] += 1;
+        degrees[*j] += 1;
+    }
+    degrees
+}
+
+#[test]
+fn topologies_test() {
+    let mut rng = StdRng::seed_from_u64(42);
+    // lattices
+    assert!(chain(1, true).is_empty());
+    assert_eq!(chain(2, true), vec![[0, 1]]);
+    assert_eq!(degrees(10, &chain(10, true)), vec![2; 10]);
+    assert_eq!(degrees(60, &lattice(&[3, 4, 5], true)), vec![6; 60]);
+    let square = lattice(&[4, 5], false);
+    let square_degrees = degrees(20, &square);
+    assert_eq!(
+        square_degrees.iter().filter(|degree| **degree == 2).count(),
+        4
+    );
+    assert_eq!(
+        square_degrees.iter().filter(|degree| **degree == 4).count(),
+        6
+    );
+    assert!(square.contains(&[0, 5]) && square.contains(&[18, 19]));
+    // a periodic chain matches a ring
+    let edges = chain(6, true);
+    let fg = random_builder::<SumProduct>(
+        6,
+        &edges,
+        Uniform::new_inclusive(0.7, 0.7),
+        Uniform::new_inclusive(0.1, 0.1),
+        &mut rng,
+        &mut || IsingMessage(0.),
+    )
+    .unwrap()
+    .build();
+    let ring = new_ising_ring_builder::<SumProduct>(6, 0.7, 0.1, 0.7, &mut || IsingMessage(0.))
+        .unwrap()
+        .build();
+    assert!(
+        (fg.coupling_matrix().to_dense() - ring.coupling_matrix().to_dense())
+            .iter()
+            .all(|x| x.abs() < 1e-12)
+    );
+    assert!((fg.local_fields() - ring.local_fields())
+        .iter()
+        .all(|x| x.abs() < 1e-12));
+    // random graphs
+    assert_eq!(degrees(8, &complete(8)), vec![7; 8]);
+    let edges = erdos_renyi(200, 0.1, &mut rng);
+    degrees(200, &edges);
+    assert!((edges.len() as f64 - 1990.).abs() < 200.);
+    assert!(erdos_renyi(10, 0., &mut rng).is_empty());
+    assert_eq!(erdos_renyi(10, 1., &mut rng), complete(10));
+    for degree in 1..5 {
+        let edges = random_regular(50, degree, &mut rng).unwrap();
+        assert_eq!(degrees(50, &edges), vec![degree; 50]);
+    }
+    assert!(random_regular(7, 3, &mut rng).is_none());
+    assert!(random_regular(4, 4, &mut rng).is_none());
+    // sampling is reproducible
+    let sample = |seed| {
+        random_builder::<SumProduct>(
+            50,
+            &complete(50),
+            Uniform::new(-1., 1.),
+            Uniform::new(-1., 1.),
+            &mut StdRng::seed_from_u64(seed),
+            &mut || IsingMessage(0.),
+        )
+        .unwrap()
+        .build()
+        .coupling_matrix()
+        .to_dense()
+    };
+    assert_eq!(sample(1), sample(1));
+    assert_ne!(sample(1), sample(2));
+}
+
+#[test]
+fn ising_matrices_test() {
+    let spins_number = 8;
+    let mut rng = StdRng::seed_from_u64(42);
+    let mut initializer = random_message_initializer(rng.clone(), -0.5, 0.5);
+    let mut fgb = new_ising_builder::<SumProduct>(spins_number, 16);
+    let mut edges = Vec::new();
+    for _ in 0..12 {
+        let lhs = rng.gen_range(0..spins_number);
+        let rhs = (lhs + rng.gen_range(1..spins_number)) % spins_number;
+        let factor = IsingFactor::new(
+            rng.gen_range(-1f64..1f64),
+            rng.gen_range(-1f64..1f64),
+            rng.gen_range(-1f64..1f64),
+        );
+        fgb.add_factor(factor, &[lhs, rhs], &mut initializer)
+            .unwrap();
+        edges.push((lhs, rhs, factor));
+    }
+    let mut fg = fgb.build();
+    fg.freeze_variable(&-1, 3).unwrap();
+    // incidence matrix
+    let incidence = fg.incidence_matrix().to_dense();
+    assert_eq!(incidence.shape(), &[13, spins_number]);
+    let variable_degrees: Vec<usize> = incidence
+        .sum_axis(ndarray::Axis(0))
+        .iter()
+        .map(|d| *d as usize)
+        .collect();
+    assert_eq!(variable_degrees, fg.get_variable_degrees());
+    let factor_degrees: Vec<usize> = incidence
+        .sum_axis(ndarray::Axis(1))
+        .iter()
+        .map(|d| *d as usize)
+        .collect();
+    assert_eq!(factor_degrees, fg.get_factor_degrees());
+    // coupling matrix is symmetric with zero diagonal
+    let couplings = fg.coupling_matrix();
+    assert_eq!(couplings.nnz(), 24);
+    let couplings = couplings.to_dense();
+    assert_eq!(couplings, couplings.t());
+    assert!(couplings.diag().iter().all(|d| *d == 0.));
+    // log-weights computed from factors and from matrices coincide up to a constant
+    let fields = fg.local_fields();
+    let log_weight_from_factors = |spins: &[i8]| {
+        let mut log_weight = 0f64;
+        for (lhs, rhs, factor) in &edges {
+            let table = factor.factor();
+            let lhs_state = ((1 - spins[*lhs]) / 2) as usize;
+            let rhs_state = ((1 - spins[*rhs]) / 2) as usize;
+            log_weight += table[[lhs_state, rhs_state]].ln();
+        }
+        log_weight
+    };
+    let log_weight_from_matrices = |spins: &[i8]| {
+        let s: Array1<f64> = spins.iter().map(|s| *s as f64).collect();
+        let mut fields = fields.clone();
+        // the huge field of the frozen spin only adds a constant
+        fields[3] = 0.;
+        s.dot(&couplings.dot(&s)) / 2. + fields.dot(&s)
+    };
+    let reference: Vec<i8> = vec![-1; spins_number];
+    for _ in 0..20 {
+        let mut spins: Vec<i8> = (0..spins_number)
+            .map(|_| if rng.gen::<bool>() { 1 } else { -1 })
+            .collect();
+        spins[3] = -1;
+        let lhs = log_weight_from_factors(&spins) - log_weight_from_factors(&reference);
+        let rhs = log_weight_from_matrices(&spins) - log_weight_from_matrices(&reference);
+        assert!((lhs - rhs).abs() < 1e-10, "{lhs} vs {rhs}");
+    }
+}
+
+#[test]
+fn grouping_test() {
+    let mut rng = StdRng::seed_from_u64(42);
+    // a 2 x 3 grid, rows of variables are 0, 1, 2 and 3, 4, 5
+    let cardinalities = [2, 3, 2, 3, 2, 2];
+    let edges = [(0, 1), (1, 2), (3, 4), (4, 5), (0, 3), (1, 4), (2, 5)];
+    let mut fgb = GroupedBuilder::new();
+    for cardinality in cardinalities {
+        fgb.add_variable(TabularVariable::new(cardinality));
+    }
+    let mut factors = Vec::new();
+    for (i, j) in edges {
+        let table = ArrayD::from_shape_fn(IxDyn(&[cardinalities[i], cardinalities[j]]), |_| {
+            rng.gen_range(0.1..1.)
+        });
+        fgb.add_factor(TabularFactor::new(table.clone()), &[i, j])
+            .unwrap();
+        factors.push((table, [i, j]));
+    }
+    assert_eq!(
+        fgb.add_factor(TabularFactor::new(ArrayD::ones(IxDyn(&[2]))), &[6]),
+        Err(FGBuilderError::OutOfRangeVariable(6, 6))
+    );
+
+    // columns of the grid form a chain of super-variables
+    fgb.add_group(&[0, 3]).unwrap();
+    fgb.add_group(&[4, 1]).unwrap();
+    assert_eq!(
+        fgb.add_group(&[2, 1]),
+        Err(FGBuilderError::DuplicateGroupMember(1))
+    );
+    assert_eq!(
+        fgb.add_group(&[2, 2]),
+        Err(FGBuilderError::DuplicateGroupMember(2))
+    );
+    fgb.add_group(&[2, 5]).unwrap();
+    let (mut fg, grouping) = fgb.build(&mut uninformative_message_initializer()).unwrap();
+    assert_eq!(grouping.super_variables_number(), 3);
+    assert_eq!(grouping.members(1), &[4, 1]);
+    assert_eq!(grouping.super_variable(3), 0);
+    assert_eq!(grouping.cardinality(1), 6);
+    assert_eq!(grouping.decode(1, 5), vec![1, 2]);
+    assert_eq!(fg.get_factor_degrees(), vec![2, 2, 1, 1, 1]);
+    fg.run_message_passing_parallel(100, 0, 1e-12, &|_| 0., &|_| 0.)
+        .unwrap();
+
+    // exact marginals
+    let mut joint = ArrayD::<f64>::ones(IxDyn(&cardinalities));
+    for (index, value) in joint.indexed_iter_mut() {
+        for (table, [i, j]) in &factors {
+            *value *= table[[index[*i], index[*j]]];
+        }
+    }
+    let sum = joint.sum();
+    let mut exact: Vec<_> = cardinalities
+        .iter()
+        .map(|cardinality| Array1::<f64>::zeros(*cardinality))
+        .collect();
+    for (index, value) in joint.indexed_iter() {
+        for (var_index, marginal) in exact.iter_mut().enumerate() {
+            marginal[index[var_index]] += value / sum;
+        }
+    }
+    let marginals = grouping.marginals(&fg.variable_marginals());
+    for (marginal, exact) in marginals.iter().zip(&exact) {
+        assert!((marginal - exact).iter().all(|x| x.abs() < 1e-10));
+    }
+
+    // samples of super-variables are mapped to states of variables
+    let info = fg
+        .sample(100, 0, 1e-12, &mut rng, &|_| 0., &|_| 0.)
+        .unwrap();
+    let samples = grouping.samples(&info.samples);
+    assert_eq!(samples.len(), 6);
+    for (super_index, joint_state) in info.samples.iter().enumerate() {
+        let states = grouping.decode(super_index, *joint_state);
+        for (var_index, state) in grouping.members(super_index).iter().zip(states) {
+            assert_eq!(samples[*var_index], state);
+            assert!(state < cardinalities[*var_index]);
+        }
+    }
+}
+
+#[test]
+fn dot_export_test() {
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(42), -0.5, 0.5);
+    let mut fgb = new_ising_builder::<SumProduct>(3, 3);
+    fgb.add_factor(IsingFactor::new(0.5, 0.1, 0.), &[0, 1], &mut initializer)
+        .unwrap();
+    fgb.add_factor(IsingFactor::new(-0.5, 0., 0.2), &[1, 2], &mut initializer)
+        .unwrap();
+    fgb.add_factor(IsingFactor::new(1., 0., 0.), &[2, 0], &mut initializer)
+        .unwrap();
+    let fg = fgb.build();
+    let dot = fg.to_dot();
+    assert!(dot.starts_with("graph factor_graph {\n"));
+    assert!(dot.ends_with("}\n"));
+    assert_eq!(dot.matches("shape=circle").count(), 3);
+    assert_eq!(dot.matches("shape=box").count(), 3);
+    assert_eq!(dot.matches(" -- ").count(), 6);
+    for edge in [
+        "f0 -- v0;",
+        "f0 -- v1;",
+        "f1 -- v1;",
+        "f1 -- v2;",
+        "f2 -- v2;",
+        "f2 -- v0;",
+    ] {
+        assert!(dot.contains(edge), "{edge} is missing in {dot}");
+    }
+    assert!(!dot.contains("IsingMessage"));
+    let options = DotOptions {
+        variable_labels: Some(vec!["\"up\"".into(), "b".into()]),
+        factor_labels: Some(vec!["J01".into()]),
+        factor_parameters: true,
+        messages: true,
+    };
+    let dot = fg.to_dot_with(&options);
+    // quotes are escaped and missing labels fall back to indices
+    assert!(dot.contains("v0 [shape=circle, label=\"\\\"up\\\"\"];"));
+    assert!(dot.contains("v2 [shape=circle, label=\"2\"];"));
+    assert!(dot.contains("f0 [shape=box, label=\"J01\\nCoupling"));
+    assert!(dot.contains("f1 [shape=box, label=\"f1\\nCoupling"));
+    assert_eq!(dot.matches("f->v: IsingMessage").count(), 6);
+    assert_eq!(dot.matches("v->f: IsingMessage").count(), 6);
+    assert_eq!(dot.lines().count(), 3 + 3 + 6 + 2);
+}
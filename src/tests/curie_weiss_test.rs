@@ -1,6 +1,8 @@
 use super::ising_utils::{exact_curie_weiss_free_entropy, exact_curie_weiss_up_probability};
 use crate::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
-use crate::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+use crate::ising::{
+    new_ising_builder, random_message_initializer, FullyConnectedIsing, IsingFactor, SumProduct,
+};
 use rand::thread_rng;
 
 #[test]
@@ -29,6 +31,9 @@ fn curie_weiss_test() {
         }
     }
     let mut fg = fgb.build();
+    let model = fg.as_fully_connected(1e-12).unwrap();
+    assert!((model.coupling - coupling / spins_number as f64).abs() < 1e-12);
+    assert!((model.fields[0] - magnetic_field).abs() < 1e-12);
     let _ = fg
         .run_message_passing_parallel(10000, 0, error, &factor_scheduler, &variable_scheduler)
         .unwrap();
@@ -50,4 +55,35 @@ fn curie_weiss_test() {
         .abs()
             < 1e-2
     );
+    // the TAP fast path agrees with message passing on explicit factors
+    let solution = model.run_tap(10000, error, 0.5).unwrap();
+    for (tap, bp) in solution.marginals().iter().zip(&variable_marginals) {
+        assert!((tap - bp).mapv(f64::abs).sum() < 1e-4);
+    }
+    assert!((solution.free_entropy - bethe_free_entropy).abs() < 1e-2);
+    // a missing pair breaks the fully-connected structure
+    let mut fgb = new_ising_builder::<SumProduct>(3, 2);
+    fgb.add_factor(IsingFactor::new(0.5, 0., 0.), &[0, 1], &mut initializer)
+        .unwrap();
+    fgb.add_factor(IsingFactor::new(0.5, 0., 0.), &[1, 2], &mut initializer)
+        .unwrap();
+    assert!(fgb.build().as_fully_connected(1e-12).is_none());
+}
+
+#[test]
+fn large_curie_weiss_test() {
+    let spins_number = 1_000_000;
+    let coupling = 1.1234;
+    let magnetic_field = 0.7654;
+    let error = 1e-12f64;
+    let model = FullyConnectedIsing::curie_weiss(spins_number, coupling, magnetic_field);
+    let solution = model.run_tap(1000, error, 0.).unwrap();
+    let exact_up_prob = exact_curie_weiss_up_probability(coupling, magnetic_field, error);
+    assert!((solution.marginals()[spins_number / 2][0] - exact_up_prob).abs() < 1e-5);
+    assert!(
+        (solution.free_entropy / spins_number as f64
+            - exact_curie_weiss_free_entropy(coupling, magnetic_field, error))
+        .abs()
+            < 1e-5
+    );
 }
@@ -0,0 +1,171 @@
+use crate::core::{DecodableVariable, Factor, FactorGraphBuilder, Variable};
+use crate::ep::{
+    uninformative_message_initializer, Bernoulli, EPFactor, EPMessage, EPVariable, Gaussian,
+};
+use crate::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+use crate::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+#[test]
+fn gaussian_ep_test() {
+    // a single logistic likelihood, EP is exact in moments
+    let mut fgb = FactorGraphBuilder::new_with_variables(
+        vec![EPVariable::<Gaussian>::new(EPMessage::new(0.5, 0.5))],
+        1,
+    );
+    let mut initializer = uninformative_message_initializer();
+    let log_sigmoid = |x: &[f64]| -(-x[0]).exp().ln_1p();
+    fgb.add_factor(
+        EPFactor::<Gaussian>::new(1, 40, log_sigmoid),
+        &[0],
+        &mut initializer,
+    )
+    .unwrap();
+    let mut fg = fgb.build();
+    fg.run_message_passing_parallel(100, 0, 1e-12, &|_| 0., &|_| 0.)
+        .unwrap();
+    // reference moments by the Riemann sum
+    let (mut normalization, mut first, mut second) = (0f64, 0f64, 0f64);
+    let step = 1e-4;
+    for i in 0..400_000 {
+        let x = -20. + step * i as f64;
+        let weight = (0.5 * x - 0.25 * x * x + log_sigmoid(&[x])).exp();
+        normalization += weight;
+        first += weight * x;
+        second += weight * x * x;
+    }
+    let mean = first / normalization;
+    let variance = second / normalization - mean * mean;
+    let (ep_mean, ep_variance) = fg.variable_marginals()[0];
+    assert!((ep_mean - mean).abs() < 1e-6, "{ep_mean} vs {mean}");
+    assert!((ep_variance - variance).abs() < 1e-6);
+    assert!((fg.factor_marginals()[0][0].0 - mean).abs() < 1e-6);
+    // a Gaussian chain, EP is exact in means and variances on trees
+    let variables_number = 4;
+    let mut fgb = FactorGraphBuilder::new_with_variables(
+        vec![EPVariable::<Gaussian>::new(EPMessage::new(0., 1.)); variables_number],
+        variables_number,
+    );
+    for i in 0..(variables_number - 1) {
+        fgb.add_factor(
+            EPFactor::<Gaussian>::new(2, 30, |x| -(x[0] - x[1]).powi(2) / 2.),
+            &[i, i + 1],
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    // an observation of the first variable
+    fgb.add_factor(
+        EPFactor::<Gaussian>::from_message(&EPMessage::new(2., 1.)),
+        &[0],
+        &mut initializer,
+    )
+    .unwrap();
+    let mut fg = fgb.build();
+    fg.run_message_passing_parallel(1000, 0, 1e-12, &|_| 0., &|_| 0.)
+        .unwrap();
+    // the precision matrix is tridiagonal with [3, 3, 3, 2] on the diagonal
+    // and -1 off the diagonal, the linear term is [2, 0, 0, 0]
+    let means = [26., 10., 4., 2.].map(|x| x / 34.);
+    let variances = [13., 15., 16., 21.].map(|x| x / 34.);
+    for ((mean, variance), (expected_mean, expected_variance)) in fg
+        .variable_marginals()
+        .into_iter()
+        .zip(means.into_iter().zip(variances))
+    {
+        assert!((mean - expected_mean).abs() < 1e-6);
+        assert!((variance - expected_variance).abs() < 1e-6);
+    }
+    assert!(fg
+        .site_approximations()
+        .iter()
+        .all(|sites| sites.iter().all(|site| site.quadratic > 0.)));
+}
+
+#[test]
+fn bernoulli_ep_test() {
+    // on pairwise binary models EP with factorized projections coincides with BP
+    let mut rng = StdRng::seed_from_u64(42);
+    let spins_number = 10;
+    let mut couplings: Vec<_> = (1..spins_number)
+        .map(|i| (rng.gen_range(0..i), i, rng.gen_range(-1f64..1f64)))
+        .collect();
+    // closing loops
+    couplings.push((0, spins_number - 1, 0.3));
+    couplings.push((2, 7, -0.4));
+    let fields: Vec<f64> = (0..spins_number)
+        .map(|_| rng.gen_range(-0.5f64..0.5f64))
+        .collect();
+    let mut ising_initializer = random_message_initializer(StdRng::seed_from_u64(7), -0.5, 0.5);
+    let mut ising_fgb = new_ising_builder::<SumProduct>(spins_number, 2 * spins_number);
+    let mut ep_fgb = FactorGraphBuilder::new_with_variables(
+        fields
+            .iter()
+            .map(|field| EPVariable::<Bernoulli>::new(EPMessage::new(*field, 0.))),
+        couplings.len(),
+    );
+    let mut ep_initializer = uninformative_message_initializer();
+    for (i, j, coupling) in &couplings {
+        ising_fgb
+            .add_factor(
+                IsingFactor::new(*coupling, 0., 0.),
+                &[*i, *j],
+                &mut ising_initializer,
+            )
+            .unwrap();
+        let coupling = *coupling;
+        ep_fgb
+            .add_factor(
+                EPFactor::<Bernoulli>::new(2, 0, move |s| coupling * s[0] * s[1]),
+                &[*i, *j],
+                &mut ep_initializer,
+            )
+            .unwrap();
+    }
+    for (i, field) in fields.iter().enumerate() {
+        ising_fgb
+            .add_factor(
+                IsingFactor::UnitFactor(2. * field),
+                &[i],
+                &mut ising_initializer,
+            )
+            .unwrap();
+    }
+    let mut ising_fg = ising_fgb.build();
+    let mut ep_fg = ep_fgb.build();
+    ising_fg
+        .run_message_passing_parallel(
+            1000,
+            0,
+            1e-12,
+            &get_standard_factor_scheduler(0.),
+            &get_standard_variable_scheduler(0.),
+        )
+        .unwrap();
+    ep_fg
+        .run_message_passing_parallel(1000, 0, 1e-12, &|_| 0., &|_| 0.)
+        .unwrap();
+    for (ising_marginal, (mean, variance)) in ising_fg
+        .variable_marginals()
+        .iter()
+        .zip(ep_fg.variable_marginals())
+    {
+        let magnetization = ising_marginal[0] - ising_marginal[1];
+        assert!((magnetization - mean).abs() < 1e-8);
+        assert!((1. - mean * mean - variance).abs() < 1e-8);
+    }
+    // pinning a spin by a sampled value
+    let var = EPVariable::<Bernoulli>::new(EPMessage::new(0.3, 0.));
+    let pin =
+        EPFactor::<Bernoulli>::from_message(&EPVariable::<Bernoulli>::sample_to_message(&-1.));
+    let mut cavity = [EPMessage::default()];
+    var.send_messages(&[EPMessage::default()], &mut cavity, &0.);
+    let mut site = [EPMessage::default()];
+    pin.send_messages(&cavity, &mut site, &0.);
+    assert_eq!(var.argmax(&site), -1.);
+    // an improper cavity leaves a site unchanged
+    let factor = EPFactor::<Gaussian>::new(1, 10, |x| -x[0].abs());
+    let mut site = [EPMessage::new(1., 2.)];
+    factor.send_messages(&[EPMessage::new(0., -1.)], &mut site, &0.);
+    assert_eq!(site[0], EPMessage::new(1., 2.));
+}
@@ -0,0 +1,930 @@
+use super::{config_spins, ising_fg, ising_log_weight, random_tree};
+use crate::core::{FGBuilderError, FGError, FactorGraphBuilder};
+use crate::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+use crate::ising::{
+    builder_from_edges, from_energy_fn, from_sparse_energy_fn, new_ising_builder,
+    random_message_initializer, IsingFactor, IsingFactorHyperParameters, IsingVariable, SumProduct,
+};
+use ndarray::Array2;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rand_distr::Uniform;
+
+#[test]
+fn bethe_energy_entropy_test() {
+    let mut rng = StdRng::seed_from_u64(42);
+    let spins_number = 9;
+    let beta = 0.7;
+    let (couplings, fields) = random_tree(&mut rng, spins_number);
+    let unit_messages: Vec<f64> = (0..spins_number)
+        .map(|_| rng.gen_range(-1f64..1f64))
+        .collect();
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(7), -0.5, 0.5);
+    let mut fgb = FactorGraphBuilder::new_with_variables(
+        fields
+            .iter()
+            .map(|field| IsingVariable::<SumProduct>::new().with_field(*field)),
+        2 * spins_number,
+    );
+    for (i, j, coupling) in &couplings {
+        fgb.add_factor(
+            IsingFactor::new(*coupling, 0., 0.),
+            &[*i, *j],
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    // unit factors only on even spins, so that degrees differ
+    for i in (0..spins_number).step_by(2) {
+        fgb.add_factor(
+            IsingFactor::UnitFactor(unit_messages[i]),
+            &[i],
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    let mut fg = fgb.build();
+    let parameters = IsingFactorHyperParameters::new(beta, 0.);
+    fg.run_message_passing_parallel(
+        1000,
+        0,
+        1e-12,
+        &|_| parameters.clone(),
+        &get_standard_variable_scheduler(0.),
+    )
+    .unwrap();
+    // exact values by enumeration
+    let log_weight = |spins: &[f64]| -> f64 {
+        let coupling_part: f64 = couplings
+            .iter()
+            .map(|(i, j, coupling)| beta * coupling * spins[*i] * spins[*j])
+            .sum();
+        let field_part: f64 = fields.iter().zip(spins).map(|(b, s)| b * s).sum();
+        // a unit factor is sigmoid(m s)
+        let unit_part: f64 = (0..spins_number)
+            .step_by(2)
+            .map(|i| -(-unit_messages[i] * spins[i]).exp().ln_1p())
+            .sum();
+        coupling_part + field_part + unit_part
+    };
+    let log_weights: Vec<f64> = (0..(1usize << spins_number))
+        .map(|config| log_weight(&config_spins(config, spins_number)))
+        .collect();
+    let log_partition = log_weights.iter().map(|x| x.exp()).sum::<f64>().ln();
+    let probabilities: Vec<f64> = log_weights
+        .iter()
+        .map(|x| (x - log_partition).exp())
+        .collect();
+    let exact_energy = -probabilities
+        .iter()
+        .zip(&log_weights)
+        .map(|(p, x)| p * x)
+        .sum::<f64>()
+        / beta;
+    let exact_entropy = -probabilities.iter().map(|p| p * p.ln()).sum::<f64>();
+    let energy = fg.bethe_energy(&parameters);
+    let entropy = fg.bethe_entropy(&parameters);
+    assert!(
+        (energy - exact_energy).abs() < 1e-8,
+        "{energy} vs {exact_energy}"
+    );
+    assert!(
+        (entropy - exact_entropy).abs() < 1e-8,
+        "{entropy} vs {exact_entropy}"
+    );
+    let free_energy = energy - entropy / beta;
+    assert!((-beta * free_energy - log_partition).abs() < 1e-8);
+    // a frozen spin has a zero entropy contribution
+    let mut frozen = fg.clone();
+    frozen.freeze_variable(&1, 1).unwrap();
+    frozen
+        .run_message_passing_parallel(
+            1000,
+            0,
+            1e-12,
+            &|_| parameters.clone(),
+            &get_standard_variable_scheduler(0.),
+        )
+        .unwrap();
+    assert!(frozen.bethe_entropy(&parameters) < entropy);
+}
+
+// Exact logarithm of the partition function and magnetizations by enumeration
+fn exact_solution(couplings: &[(usize, usize, f64)], fields: &[f64]) -> (f64, Vec<f64>) {
+    let spins_number = fields.len();
+    let mut partition_function = 0f64;
+    let mut magnetizations = vec![0f64; spins_number];
+    for configuration in 0..(1 << spins_number) {
+        let spins = config_spins(configuration, spins_number);
+        let weight = ising_log_weight(couplings, fields, &spins).exp();
+        partition_function += weight;
+        for (magnetization, spin) in magnetizations.iter_mut().zip(&spins) {
+            *magnetization += spin * weight;
+        }
+    }
+    for magnetization in &mut magnetizations {
+        *magnetization /= partition_function;
+    }
+    (partition_function.ln(), magnetizations)
+}
+
+#[test]
+fn trw_test() {
+    let mut rng = StdRng::seed_from_u64(42);
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    // on a random tree with unit probabilities the bound is exact
+    let spins_number = 12;
+    let (couplings, fields) = random_tree(&mut rng, spins_number);
+    let mut fg = ising_fg(&couplings, &fields, 7);
+    let mut reference_fg = fg.clone();
+    let appearance_probabilities = fg.spanning_tree_appearance_probabilities(&mut rng);
+    assert!(appearance_probabilities.iter().all(|rho| *rho == 1.));
+    fg.run_trw_message_passing(
+        1000,
+        0,
+        1e-12,
+        &appearance_probabilities,
+        &factor_scheduler,
+        &variable_scheduler,
+    )
+    .unwrap();
+    reference_fg
+        .run_message_passing_parallel(1000, 0, 1e-12, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    let (log_partition_function, magnetizations) = exact_solution(&couplings, &fields);
+    let bound = fg
+        .trw_log_partition_bound(&appearance_probabilities, &factor_scheduler(0))
+        .unwrap();
+    assert!((bound - log_partition_function).abs() < 1e-8);
+    let trw_factor_marginals = fg
+        .trw_factor_marginals(&appearance_probabilities, &factor_scheduler(0))
+        .unwrap();
+    for (lhs, rhs) in trw_factor_marginals
+        .iter()
+        .zip(reference_fg.factor_marginals())
+    {
+        assert!((lhs - rhs).iter().all(|x| x.abs() < 1e-8));
+    }
+    for (marginal, magnetization) in fg.variable_marginals().iter().zip(&magnetizations) {
+        assert!((marginal[0] - marginal[1] - magnetization).abs() < 1e-8);
+    }
+    // on a frustrated lattice the bound holds for probabilities of spanning trees
+    let side = 4;
+    let spins_number = side * side;
+    let mut couplings = Vec::new();
+    for i in 0..side {
+        for j in 0..(side - 1) {
+            couplings.push((i * side + j, i * side + j + 1, rng.gen_range(-1f64..1f64)));
+            couplings.push((j * side + i, (j + 1) * side + i, rng.gen_range(-1f64..1f64)));
+        }
+    }
+    let fields: Vec<f64> = (0..spins_number)
+        .map(|_| rng.gen_range(-0.2f64..0.2f64))
+        .collect();
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(7), -0.5, 0.5);
+    let mut fgb = new_ising_builder::<SumProduct>(spins_number, couplings.len());
+    for (i, j, coupling) in &couplings {
+        fgb.add_factor(
+            IsingFactor::new(*coupling, fields[*i] / 4., fields[*j] / 4.),
+            &[*i, *j],
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    let fg = fgb.build();
+    // each spin has from 2 to 4 couplings
+    let degrees_fields: Vec<f64> = (0..spins_number)
+        .map(|spin| {
+            let degree = couplings
+                .iter()
+                .filter(|(i, j, _)| *i == spin || *j == spin)
+                .count();
+            fields[spin] * degree as f64 / 4.
+        })
+        .collect();
+    let (log_partition_function, _) = exact_solution(&couplings, &degrees_fields);
+    for _ in 0..3 {
+        let appearance_probabilities = fg.spanning_tree_appearance_probabilities(&mut rng);
+        let edges_number: f64 = appearance_probabilities.iter().sum();
+        assert!((edges_number - (spins_number - 1) as f64).abs() < 1e-10);
+        let mut trw_fg = fg.clone();
+        trw_fg
+            .run_trw_message_passing(
+                10000,
+                0,
+                1e-10,
+                &appearance_probabilities,
+                &get_standard_factor_scheduler(0.5),
+                &get_standard_variable_scheduler(0.5),
+            )
+            .unwrap();
+        let bound = trw_fg
+            .trw_log_partition_bound(&appearance_probabilities, &factor_scheduler(0))
+            .unwrap();
+        assert!(
+            bound >= log_partition_function,
+            "bound: {bound}, exact: {log_partition_function}"
+        );
+    }
+    // invalid probabilities
+    let mut fg = fg;
+    assert!(matches!(
+        fg.run_trw_message_passing(10, 0, 1e-10, &[1.], &factor_scheduler, &variable_scheduler),
+        Err(FGError::AppearanceProbabilitiesSizeError(24, 1))
+    ));
+    let mut appearance_probabilities = vec![0.5; couplings.len()];
+    appearance_probabilities[3] = 0.;
+    assert!(matches!(
+        fg.trw_log_partition_bound(&appearance_probabilities, &factor_scheduler(0)),
+        Err(FGError::AppearanceProbabilityError(3, _))
+    ));
+}
+
+#[test]
+fn loop_series_test() {
+    let mut rng = StdRng::seed_from_u64(42);
+    let spins_number = 8;
+    let beta = 0.8;
+    // a ring with chords, a double coupling and a dangling spin
+    let mut couplings: Vec<(usize, usize, f64)> = (0..(spins_number - 1))
+        .map(|i| {
+            (
+                i,
+                (i + 1) % (spins_number - 1),
+                rng.gen_range(-0.6f64..0.6f64),
+            )
+        })
+        .collect();
+    couplings.extend([(0, 3, 0.4), (2, 5, -0.3), (1, 2, 0.2), (6, 7, 0.5)]);
+    let fields: Vec<f64> = (0..spins_number)
+        .map(|_| rng.gen_range(-0.3f64..0.3f64))
+        .collect();
+    let unit_messages: Vec<f64> = (0..spins_number)
+        .map(|_| rng.gen_range(-0.5f64..0.5f64))
+        .collect();
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(7), -0.5, 0.5);
+    let mut fgb = FactorGraphBuilder::new_with_variables(
+        fields
+            .iter()
+            .map(|field| IsingVariable::<SumProduct>::new().with_field(*field)),
+        2 * spins_number,
+    );
+    for (i, j, coupling) in &couplings {
+        fgb.add_factor(
+            IsingFactor::new(*coupling, 0., 0.),
+            &[*i, *j],
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    for i in (1..spins_number).step_by(3) {
+        fgb.add_factor(
+            IsingFactor::UnitFactor(unit_messages[i]),
+            &[i],
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    let mut fg = fgb.build();
+    let parameters = IsingFactorHyperParameters::new(beta, 0.);
+    fg.run_message_passing_parallel(
+        1000,
+        0,
+        1e-14,
+        &|_| parameters.clone(),
+        &get_standard_variable_scheduler(0.),
+    )
+    .unwrap();
+    // the exact logarithm of the partition function by enumeration
+    let log_weight = |spins: &[f64]| -> f64 {
+        let coupling_part: f64 = couplings
+            .iter()
+            .map(|(i, j, coupling)| beta * coupling * spins[*i] * spins[*j])
+            .sum();
+        let field_part: f64 = fields.iter().zip(spins).map(|(b, s)| b * s).sum();
+        let unit_part: f64 = (1..spins_number)
+            .step_by(3)
+            .map(|i| -(-unit_messages[i] * spins[i]).exp().ln_1p())
+            .sum();
+        coupling_part + field_part + unit_part
+    };
+    let log_partition = (0..(1usize << spins_number))
+        .map(|config| log_weight(&config_spins(config, spins_number)).exp())
+        .sum::<f64>()
+        .ln();
+    let bethe_log_partition = fg.bethe_entropy(&parameters) - beta * fg.bethe_energy(&parameters);
+    // without loops the estimate is the Bethe one
+    let truncated = fg.loop_series(&parameters, 0);
+    assert_eq!(truncated.loops_number, 0);
+    assert_eq!(truncated.correction, 0.);
+    assert!((truncated.bethe_log_partition - bethe_log_partition).abs() < 1e-10);
+    assert!((truncated.log_partition - bethe_log_partition).abs() < 1e-10);
+    // the series over all generalized loops is exact
+    let full = fg.loop_series(&parameters, couplings.len());
+    assert!((full.log_partition - log_partition).abs() < 1e-10);
+    // short loops improve the Bethe estimate and their number grows with the length
+    let short = fg.loop_series(&parameters, 4);
+    assert!(short.loops_number > 0 && short.loops_number < full.loops_number);
+    assert!(
+        (short.log_partition - log_partition).abs() < (bethe_log_partition - log_partition).abs()
+    );
+    // the double coupling is the only loop of length 2
+    assert_eq!(fg.loop_series(&parameters, 2).loops_number, 1);
+    // the correction vanishes on trees
+    let mut fgb = FactorGraphBuilder::new_with_variables(
+        vec![IsingVariable::<SumProduct>::new().with_field(0.2); 4],
+        3,
+    );
+    for (i, j) in [(0, 1), (1, 2), (1, 3)] {
+        fgb.add_factor(IsingFactor::new(0.7, 0.1, 0.), &[i, j], &mut initializer)
+            .unwrap();
+    }
+    let mut fg = fgb.build();
+    fg.run_message_passing_parallel(
+        1000,
+        0,
+        1e-14,
+        &|_| parameters.clone(),
+        &get_standard_variable_scheduler(0.),
+    )
+    .unwrap();
+    let tree = fg.loop_series(&parameters, 3);
+    assert_eq!(tree.loops_number, 0);
+    assert_eq!(tree.log_partition, tree.bethe_log_partition);
+}
+
+// Exact connected correlations of an Ising model by enumeration
+fn exact_correlations(couplings: &[(usize, usize, f64)], fields: &[f64]) -> Array2<f64> {
+    let spins_number = fields.len();
+    let mut partition_function = 0f64;
+    let mut magnetizations = vec![0f64; spins_number];
+    let mut correlations = Array2::<f64>::zeros((spins_number, spins_number));
+    for configuration in 0..(1 << spins_number) {
+        let spins = config_spins(configuration, spins_number);
+        let weight = ising_log_weight(couplings, fields, &spins).exp();
+        partition_function += weight;
+        for (magnetization, spin) in magnetizations.iter_mut().zip(&spins) {
+            *magnetization += weight * spin;
+        }
+        for ((i, j), correlation) in correlations.indexed_iter_mut() {
+            *correlation += weight * spins[i] * spins[j];
+        }
+    }
+    for ((i, j), correlation) in correlations.indexed_iter_mut() {
+        *correlation = *correlation / partition_function
+            - magnetizations[i] * magnetizations[j] / partition_function.powi(2);
+    }
+    correlations
+}
+
+#[test]
+fn linear_response_test() {
+    let mut rng = StdRng::seed_from_u64(42);
+    let spins_number = 10;
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    // a random tree where linear response is exact, and the tree with weak loops
+    let (mut couplings, fields) = random_tree(&mut rng, spins_number);
+    for (loops, tolerance) in [(false, 1e-6), (true, 1e-2)] {
+        if loops {
+            couplings.push((0, spins_number - 1, 0.1));
+            couplings.push((3, 8, -0.1));
+        }
+        let mut fg = ising_fg(&couplings, &fields, 7);
+        fg.run_message_passing_parallel(1000, 0, 1e-12, &factor_scheduler, &variable_scheduler)
+            .unwrap();
+        let marginals = fg.variable_marginals();
+        let var_indices: Vec<_> = (0..spins_number).collect();
+        let correlations = fg
+            .linear_response_correlations(
+                &var_indices,
+                1e-12,
+                100_000,
+                &factor_scheduler(0),
+                &variable_scheduler(0),
+            )
+            .unwrap();
+        let exact = exact_correlations(&couplings, &fields);
+        assert!((&correlations - &exact).iter().all(|x| x.abs() < tolerance));
+        assert_eq!(correlations, correlations.t());
+        // a factor graph is restored
+        for (lhs, rhs) in marginals.iter().zip(fg.variable_marginals()) {
+            assert!((lhs - rhs).iter().all(|x| x.abs() < 1e-12));
+        }
+        // a subset of spins in an arbitrary order
+        let subset = fg
+            .linear_response_correlations(
+                &[7, 2],
+                1e-12,
+                100_000,
+                &factor_scheduler(0),
+                &variable_scheduler(0),
+            )
+            .unwrap();
+        assert!((subset[[0, 1]] - correlations[[7, 2]]).abs() < 1e-10);
+        assert!((subset[[1, 1]] - correlations[[2, 2]]).abs() < 1e-10);
+        assert!(matches!(
+            fg.linear_response_correlations(
+                &[0, spins_number],
+                1e-12,
+                100_000,
+                &factor_scheduler(0),
+                &variable_scheduler(0),
+            ),
+            Err(FGError::OutOfRangeVariable(10, 10))
+        ));
+    }
+}
+
+#[test]
+fn connected_correlations_test() {
+    let mut rng = StdRng::seed_from_u64(42);
+    let spins_number = 8;
+    let (couplings, fields) = random_tree(&mut rng, spins_number);
+    let mut fg = ising_fg(&couplings, &fields, 7);
+    fg.run_message_passing_parallel(
+        1000,
+        0,
+        1e-12,
+        &get_standard_factor_scheduler(0.),
+        &get_standard_variable_scheduler(0.),
+    )
+    .unwrap();
+    // exact moments by enumeration
+    let mut normalization = 0f64;
+    let mut magnetizations = vec![0f64; spins_number];
+    let mut pair_expectations = vec![0f64; couplings.len()];
+    for config in 0..(1usize << spins_number) {
+        let spins = config_spins(config, spins_number);
+        let weight = ising_log_weight(&couplings, &fields, &spins).exp();
+        normalization += weight;
+        for (m, s) in magnetizations.iter_mut().zip(&spins) {
+            *m += weight * s;
+        }
+        for (c, (i, j, _)) in pair_expectations.iter_mut().zip(&couplings) {
+            *c += weight * spins[*i] * spins[*j];
+        }
+    }
+    let correlations = fg.connected_correlations();
+    // unit factors are skipped
+    assert_eq!(correlations.len(), couplings.len());
+    for ((lhs, rhs, correlation), ((i, j, _), pair_expectation)) in correlations
+        .iter()
+        .zip(couplings.iter().zip(&pair_expectations))
+    {
+        assert_eq!((lhs, rhs), (i, j));
+        let exact = pair_expectation / normalization
+            - magnetizations[*i] * magnetizations[*j] / normalization.powi(2);
+        assert!(
+            (correlation - exact).abs() < 1e-8,
+            "{correlation} vs {exact}"
+        );
+    }
+    // the sign follows the sign of a coupling on a tree
+    for ((_, _, correlation), (_, _, coupling)) in correlations.iter().zip(&couplings) {
+        assert_eq!(correlation.signum(), coupling.signum());
+    }
+}
+
+// Exact mutual information between two spins of an Ising model by enumeration
+fn exact_mutual_information(
+    couplings: &[(usize, usize, f64)],
+    fields: &[f64],
+    pair: (usize, usize),
+) -> f64 {
+    let spins_number = fields.len();
+    let mut joint = [[0f64; 2]; 2];
+    for configuration in 0..(1 << spins_number) {
+        let spins = config_spins(configuration, spins_number);
+        joint[(configuration >> pair.0) & 1][(configuration >> pair.1) & 1] +=
+            ising_log_weight(couplings, fields, &spins).exp();
+    }
+    let total: f64 = joint.iter().flatten().sum();
+    let first = [joint[0][0] + joint[0][1], joint[1][0] + joint[1][1]];
+    let second = [joint[0][0] + joint[1][0], joint[0][1] + joint[1][1]];
+    let mut mutual_information = 0f64;
+    for a in 0..2 {
+        for b in 0..2 {
+            mutual_information +=
+                joint[a][b] / total * f64::ln(joint[a][b] * total / (first[a] * second[b]));
+        }
+    }
+    mutual_information
+}
+
+#[test]
+fn tree_mutual_information_test() {
+    let spins_number = 10;
+    let mut rng = StdRng::seed_from_u64(42);
+    // a random tree with random couplings and fields, where belief propagation is exact
+    let (couplings, fields) = random_tree(&mut rng, spins_number);
+    let mut fg = ising_fg(&couplings, &fields, 7);
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    fg.run_message_passing_parallel(1000, 0, 1e-14, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    let initial_marginals = fg.variable_marginals();
+    let pairs: Vec<_> = (0..spins_number)
+        .flat_map(|i| ((i + 1)..spins_number).map(move |j| (i, j)))
+        .collect();
+    let ranking = fg
+        .mutual_information(
+            &pairs,
+            1e-14,
+            1000000,
+            &factor_scheduler(0),
+            &variable_scheduler(0),
+        )
+        .unwrap();
+    assert_eq!(ranking.len(), pairs.len());
+    assert_eq!(
+        ranking.iter().filter(|x| x.adjacent).count(),
+        spins_number - 1
+    );
+    assert!(ranking
+        .windows(2)
+        .all(|x| x[0].mutual_information >= x[1].mutual_information));
+    for estimate in &ranking {
+        let exact = exact_mutual_information(&couplings, &fields, estimate.pair);
+        assert!(
+            (estimate.mutual_information - exact).abs() < 1e-6,
+            "pair: {:?}, estimate: {}, exact: {exact}",
+            estimate.pair,
+            estimate.mutual_information,
+        );
+        let adjacent = couplings.iter().any(|(i, j, _)| (*i, *j) == estimate.pair);
+        assert_eq!(adjacent, estimate.adjacent);
+    }
+    // a factor graph is restored
+    assert_eq!(fg.variable_marginals(), initial_marginals);
+    assert_eq!(fg.get_factor_degrees().len(), 2 * spins_number - 1);
+    assert!(matches!(
+        fg.mutual_information(
+            &[(0, spins_number)],
+            1e-14,
+            1000000,
+            &factor_scheduler(0),
+            &variable_scheduler(0),
+        ),
+        Err(FGError::OutOfRangeVariable(..))
+    ));
+}
+
+#[test]
+fn energy_fn_test() {
+    let mut rng = StdRng::seed_from_u64(42);
+    let spins_number = 12;
+    let mut pairs = Vec::new();
+    for lhs in 0..spins_number {
+        for rhs in (lhs + 1)..spins_number {
+            if rng.gen_bool(0.3) {
+                pairs.push((lhs, rhs));
+            }
+        }
+    }
+    let mut dense_couplings = vec![vec![None; spins_number]; spins_number];
+    for (lhs, rhs) in &pairs {
+        dense_couplings[*lhs][*rhs] = Some(rng.gen_range(-0.5f64..0.5f64));
+    }
+    let fields: Vec<f64> = (0..spins_number)
+        .map(|_| rng.gen_range(-0.5f64..0.5f64))
+        .collect();
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(7), -0.5, 0.5);
+    let fg = from_energy_fn::<SumProduct>(
+        spins_number,
+        |i, j| dense_couplings[i][j],
+        |i| fields[i],
+        &mut initializer,
+    )
+    .unwrap()
+    .build();
+    let edges: Vec<_> = pairs.iter().map(|(lhs, rhs)| [*lhs, *rhs]).collect();
+    let couplings: Vec<_> = pairs
+        .iter()
+        .map(|(lhs, rhs)| dense_couplings[*lhs][*rhs].unwrap())
+        .collect();
+    let edges_fg = builder_from_edges::<SumProduct>(&edges, &couplings, &fields, &mut initializer)
+        .unwrap()
+        .build();
+    assert_eq!(edges_fg.factors.len(), pairs.len() + spins_number);
+    // the sparse version queries pairs in a different order
+    pairs.reverse();
+    let sparse_fg = from_sparse_energy_fn::<SumProduct>(
+        spins_number,
+        &pairs,
+        |i, j| dense_couplings[i][j],
+        |i| fields[i],
+        &mut initializer,
+    )
+    .unwrap()
+    .build();
+    // a reference graph built by loops
+    let mut fgb = new_ising_builder::<SumProduct>(spins_number, 2 * spins_number);
+    for (lhs, row) in dense_couplings.iter().enumerate() {
+        for (rhs, coupling) in row.iter().enumerate() {
+            if let Some(coupling) = coupling {
+                fgb.add_factor(
+                    IsingFactor::new(*coupling, 0., 0.),
+                    &[lhs, rhs],
+                    &mut initializer,
+                )
+                .unwrap();
+            }
+        }
+    }
+    for (i, field) in fields.iter().enumerate() {
+        fgb.add_factor(IsingFactor::UnitFactor(2. * field), &[i], &mut initializer)
+            .unwrap();
+    }
+    let mut reference_fg = fgb.build();
+    let reference_couplings = reference_fg.coupling_matrix().to_dense();
+    let reference_fields = reference_fg.local_fields();
+    for fg in [&fg, &sparse_fg, &edges_fg] {
+        assert_eq!(fg.coupling_matrix().nnz(), 2 * pairs.len());
+        assert!((fg.coupling_matrix().to_dense() - &reference_couplings)
+            .iter()
+            .all(|x| x.abs() < 1e-12));
+        assert!((fg.local_fields() - &reference_fields)
+            .iter()
+            .all(|x| x.abs() < 1e-12));
+    }
+    let factor_scheduler = get_standard_factor_scheduler(0.5);
+    let variable_scheduler = get_standard_variable_scheduler(0.5);
+    let mut fg = fg;
+    fg.run_message_passing_parallel(1000, 0, 1e-10, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    reference_fg
+        .run_message_passing_parallel(1000, 0, 1e-10, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    for (lhs, rhs) in fg
+        .variable_marginals()
+        .iter()
+        .zip(reference_fg.variable_marginals())
+    {
+        assert!((lhs - rhs).iter().all(|x| x.abs() < 1e-8));
+    }
+    // a pair out of range
+    let error = from_sparse_energy_fn::<SumProduct>(
+        3,
+        &[(0, 1), (1, 3)],
+        |_, _| Some(1.),
+        |_| 0.,
+        &mut initializer,
+    )
+    .unwrap_err();
+    assert_eq!(error.reason(), &FGBuilderError::OutOfRangeVariable(3, 3));
+    let error =
+        builder_from_edges::<SumProduct>(&[[0, 1], [1, 3]], &[1., 1.], &[0.; 3], &mut initializer)
+            .unwrap_err();
+    assert_eq!(error.reason(), &FGBuilderError::OutOfRangeVariable(3, 3));
+    // pairs without interaction are skipped
+    let fg = from_energy_fn::<SumProduct>(4, |_, _| None, |_| 0.1, &mut initializer)
+        .unwrap()
+        .build();
+    assert_eq!(fg.coupling_matrix().nnz(), 0);
+    assert_eq!(fg.variable_marginals().len(), 4);
+}
+
+#[test]
+fn fitting_test() {
+    let side = 3;
+    let tolerance = 1e-8;
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    let mut rng = StdRng::seed_from_u64(42);
+    let distr = Uniform::new(-0.4, 0.4);
+    let mut edges = Vec::new();
+    for i in 0..side {
+        for j in 0..side {
+            let spin = i * side + j;
+            if j + 1 < side {
+                edges.push([spin, spin + 1]);
+            }
+            if i + 1 < side {
+                edges.push([spin, spin + side]);
+            }
+        }
+    }
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(7), -0.5, 0.5);
+    let mut true_fgb = new_ising_builder::<SumProduct>(side * side, edges.len());
+    let mut fgb = new_ising_builder::<SumProduct>(side * side, edges.len());
+    for edge in &edges {
+        true_fgb
+            .add_factor(
+                IsingFactor::new(rng.sample(distr), rng.sample(distr), rng.sample(distr)),
+                edge,
+                &mut initializer,
+            )
+            .unwrap();
+        fgb.add_factor(IsingFactor::new(0., 0., 0.), edge, &mut initializer)
+            .unwrap();
+    }
+    // targets are message passing marginals of a model with the same topology
+    let mut true_fg = true_fgb.build();
+    true_fg
+        .run_message_passing_parallel(1000, 0, 1e-12, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    let targets: Vec<_> = true_fg.factor_marginals().into_iter().enumerate().collect();
+    let mut fg = fgb.build();
+    let info = fg
+        .fit_marginals(
+            &targets,
+            1000,
+            tolerance,
+            1000,
+            0,
+            1e-12,
+            &factor_scheduler,
+            &variable_scheduler,
+        )
+        .unwrap();
+    assert!(info.last_discrepancy < tolerance);
+    assert!(info.scaling_steps_number > 0);
+    for ((_, target), marginal) in targets.iter().zip(fg.factor_marginals()) {
+        for (lhs, rhs) in target.iter().zip(marginal.iter()) {
+            assert!((lhs - rhs).abs() < 1e-6);
+        }
+    }
+    for (lhs, rhs) in true_fg
+        .variable_marginals()
+        .iter()
+        .zip(fg.variable_marginals())
+    {
+        assert!((lhs[0] - rhs[0]).abs() < 1e-6);
+    }
+    // invalid targets
+    assert!(matches!(
+        fg.fit_marginals(
+            &[(edges.len(), targets[0].1.clone())],
+            10,
+            tolerance,
+            1000,
+            0,
+            1e-12,
+            &factor_scheduler,
+            &variable_scheduler,
+        ),
+        Err(FGError::OutOfRangeFactor(_, _))
+    ));
+    let mut target = targets[0].1.clone();
+    target.fill(0.);
+    target[[0, 0]] = 1.;
+    assert!(matches!(
+        fg.fit_marginals(
+            &[(0, target)],
+            10,
+            tolerance,
+            1000,
+            0,
+            1e-12,
+            &factor_scheduler,
+            &variable_scheduler,
+        ),
+        Err(FGError::ScalingError(0))
+    ));
+}
+
+#[test]
+fn localized_queries_test() {
+    let side = 8;
+    let spins_number = side * side;
+    let error = 1e-12;
+    let mut rng = StdRng::seed_from_u64(42);
+    let mut initializer = random_message_initializer(rng.clone(), -0.5, 0.5);
+    let mut fgb = new_ising_builder::<SumProduct>(spins_number, 2 * spins_number);
+    for i in 0..side {
+        for j in 0..side {
+            let spin = i * side + j;
+            if j + 1 < side {
+                let coupling = rng.gen_range(-0.4..0.4);
+                fgb.add_factor(
+                    IsingFactor::new(coupling, 0.05, 0.),
+                    &[spin, spin + 1],
+                    &mut initializer,
+                )
+                .unwrap();
+            }
+            if i + 1 < side {
+                let coupling = rng.gen_range(-0.4..0.4);
+                fgb.add_factor(
+                    IsingFactor::new(coupling, 0., -0.05),
+                    &[spin, spin + side],
+                    &mut initializer,
+                )
+                .unwrap();
+            }
+        }
+    }
+    let mut fg = fgb.build();
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    fg.run_message_passing_parallel(10000, 0, error, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    let initial_marginals = fg.variable_marginals();
+    let queries: Vec<Vec<(usize, i8)>> = (0..10)
+        .map(|_| {
+            (0..3)
+                .map(|_| {
+                    let spin = rng.gen_range(0..spins_number);
+                    (spin, if rng.gen::<bool>() { 1 } else { -1 })
+                })
+                .collect()
+        })
+        .collect();
+    let infos = fg
+        .query_marginals(
+            queries.clone(),
+            error,
+            1000000,
+            &factor_scheduler(0),
+            &variable_scheduler(0),
+        )
+        .unwrap();
+    assert_eq!(infos.len(), queries.len());
+    for (query, info) in queries.into_iter().zip(infos) {
+        // the reference is a full message passing run with fixed variables
+        let mut reference_fg = fg.clone();
+        for (spin, value) in query {
+            reference_fg.freeze_variable(&value, spin).unwrap();
+        }
+        let reference_info = reference_fg
+            .run_message_passing_parallel(10000, 0, error, &factor_scheduler, &variable_scheduler)
+            .unwrap();
+        let full_updates_number =
+            reference_info.iterations_number * (reference_fg.factors.len() + spins_number);
+        assert!(info.updates_number < full_updates_number);
+        for (marginal, reference) in info.marginals.iter().zip(reference_fg.variable_marginals()) {
+            assert!((marginal - &reference).iter().all(|x| x.abs() < 1e-8));
+        }
+    }
+    // a factor graph is not modified by queries
+    for (marginal, initial) in fg.variable_marginals().iter().zip(&initial_marginals) {
+        assert!((marginal - initial).iter().all(|x| *x == 0.));
+    }
+    assert!(fg.get_factor_degrees().iter().all(|degree| *degree == 2));
+    // conditioning in place restores a factor graph
+    let evidence = vec![(3, 1), (17, -1)];
+    let info = fg
+        .conditional_variable_marginals(
+            &evidence,
+            error,
+            1000000,
+            &factor_scheduler(0),
+            &variable_scheduler(0),
+        )
+        .unwrap();
+    let reference_info = fg
+        .query_marginals(
+            vec![evidence],
+            error,
+            1000000,
+            &factor_scheduler(0),
+            &variable_scheduler(0),
+        )
+        .unwrap()
+        .remove(0);
+    assert_eq!(info.updates_number, reference_info.updates_number);
+    assert_eq!(info.marginals, reference_info.marginals);
+    assert!(matches!(
+        fg.conditional_variable_marginals(
+            &[(0, 1), (spins_number, 1)],
+            error,
+            1000000,
+            &factor_scheduler(0),
+            &variable_scheduler(0),
+        ),
+        Err(FGError::OutOfRangeVariable(..))
+    ));
+    assert!(fg.get_factor_degrees().iter().all(|degree| *degree == 2));
+    assert_eq!(
+        fg.get_variable_degrees().iter().sum::<usize>(),
+        2 * 2 * side * (side - 1)
+    );
+    for (marginal, initial) in fg.variable_marginals().iter().zip(&initial_marginals) {
+        assert_eq!(marginal, initial);
+    }
+    // errors
+    assert!(matches!(
+        fg.query_marginals(
+            vec![vec![(spins_number, 1)]],
+            error,
+            1000000,
+            &factor_scheduler(0),
+            &variable_scheduler(0),
+        ),
+        Err(FGError::OutOfRangeVariable(..))
+    ));
+    assert!(matches!(
+        fg.query_marginals(
+            vec![vec![(0, 1)]],
+            error,
+            5,
+            &factor_scheduler(0),
+            &variable_scheduler(0),
+        ),
+        Err(FGError::MessagePassingError {
+            iterations_number: 5,
+            ..
+        })
+    ));
+}
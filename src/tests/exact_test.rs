@@ -0,0 +1,392 @@
+use crate::core::{
+    EliminationHeuristic, FGError, FactorGraph, FactorGraphBuilder, TreeDecomposition,
+};
+use crate::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+use crate::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+use crate::tabular::{
+    uninformative_message_initializer, Elimination, TabularFactor, TabularVariable,
+};
+use ndarray::{ArrayD, Axis, IxDyn};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+type TabularGraph = FactorGraph<TabularFactor, TabularVariable>;
+
+// Unnormalized weights of all configurations of a model
+fn joint(fg: &TabularGraph, cardinalities: &[usize]) -> ArrayD<f64> {
+    let tables = fg.factors();
+    let mut joint = ArrayD::ones(IxDyn(cardinalities));
+    for (index, value) in joint.indexed_iter_mut() {
+        for (fac_index, table) in tables.iter().enumerate() {
+            let factor_index: Vec<_> = fg
+                .factor_neighbors(fac_index)
+                .iter()
+                .map(|var_index| index[*var_index])
+                .collect();
+            *value *= table[IxDyn(&factor_index)];
+        }
+    }
+    joint
+}
+
+// Sums or maximizes out an axis of weights and normalizes the result
+fn reduce(weights: &ArrayD<f64>, axis: usize, elimination: Elimination) -> ArrayD<f64> {
+    let reduced = weights.map_axis(Axis(axis), |lane| match elimination {
+        Elimination::Sum => lane.sum(),
+        Elimination::Max => lane.fold(0f64, |acc, x| acc.max(*x)),
+    });
+    let sum = reduced.sum();
+    reduced / sum
+}
+
+// A ring of variables with different cardinalities and random unit factors
+fn random_ring(rng: &mut impl Rng, cardinalities: &[usize]) -> TabularGraph {
+    let mut initializer = uninformative_message_initializer();
+    let variables = cardinalities.iter().map(|c| TabularVariable::new(*c));
+    let mut fgb = FactorGraphBuilder::new_with_variables(variables, 2 * cardinalities.len());
+    for i in 0..cardinalities.len() {
+        let j = (i + 1) % cardinalities.len();
+        let table = ArrayD::from_shape_fn(IxDyn(&[cardinalities[i], cardinalities[j]]), |_| {
+            rng.gen_range(0.1..1.)
+        });
+        fgb.add_factor(TabularFactor::new(table), &[i, j], &mut initializer)
+            .unwrap();
+        let table = ArrayD::from_shape_fn(IxDyn(&[cardinalities[i]]), |_| rng.gen_range(0.1..1.));
+        fgb.add_factor(TabularFactor::new(table), &[i], &mut initializer)
+            .unwrap();
+    }
+    fgb.build()
+}
+
+#[test]
+fn elimination_test() {
+    let mut rng = StdRng::seed_from_u64(42);
+    let cardinalities = [2, 3, 2, 3, 2];
+    for elimination in [Elimination::Sum, Elimination::Max] {
+        let mut fg = random_ring(&mut rng, &cardinalities);
+        let exact = reduce(&joint(&fg, &cardinalities), 1, elimination);
+        fg.eliminate_variable(1, elimination).unwrap();
+        assert_eq!(fg.get_variable_degrees(), vec![3, 3, 3, 3]);
+        assert_eq!(fg.get_factor_degrees(), vec![1, 2, 1, 2, 1, 2, 1, 2]);
+        assert_eq!(fg.factor_neighbors(7), &[0, 1]);
+        let weights = joint(&fg, &[2, 2, 3, 2]);
+        let found = &weights / weights.sum();
+        for (e, f) in exact.iter().zip(&found) {
+            assert!((e - f).abs() < 1e-12);
+        }
+    }
+    // variables of a ring are eliminated one by one down to a single one
+    let mut fg = random_ring(&mut rng, &cardinalities);
+    let joint_weights = joint(&fg, &cardinalities);
+    for _ in 1..cardinalities.len() {
+        fg.eliminate_variable(1, Elimination::Sum).unwrap();
+    }
+    assert_eq!(fg.get_factor_degrees(), vec![1, 1]);
+    fg.run_message_passing_parallel(100, 0, 1e-12, &|_| 0., &|_| 0.)
+        .unwrap();
+    let mut exact = joint_weights;
+    for axis in (1..cardinalities.len()).rev() {
+        exact = exact.sum_axis(Axis(axis));
+    }
+    let exact = &exact / exact.sum();
+    for (e, f) in exact.iter().zip(&fg.variable_marginals()[0]) {
+        assert!((e - f).abs() < 1e-12);
+    }
+    // an isolated variable is removed without adding a factor
+    fg.add_variable(TabularVariable::new(4));
+    fg.eliminate_variable(1, Elimination::Max).unwrap();
+    assert_eq!(fg.get_variable_degrees(), vec![2]);
+    fg.eliminate_variable(0, Elimination::Sum).unwrap();
+    assert!(fg.get_variable_degrees().is_empty());
+    assert!(fg.get_factor_degrees().is_empty());
+    assert!(matches!(
+        fg.eliminate_variable(0, Elimination::Sum),
+        Err(FGError::OutOfRangeVariable(0, 0))
+    ));
+}
+
+// Checks that bags cover all factors and that bags containing
+// a variable form a connected subtree
+fn check_decomposition(
+    decomposition: &TreeDecomposition,
+    scopes: &[Vec<usize>],
+    variables_number: usize,
+) {
+    assert_eq!(decomposition.bags.len(), variables_number);
+    assert_eq!(decomposition.parents.len(), variables_number);
+    let mut order = decomposition.elimination_order.clone();
+    order.sort_unstable();
+    assert_eq!(order, (0..variables_number).collect::<Vec<_>>());
+    for scope in scopes {
+        assert!(decomposition
+            .bags
+            .iter()
+            .any(|bag| scope.iter().all(|var_index| bag.contains(var_index))));
+    }
+    for var_index in 0..variables_number {
+        let containing: Vec<_> = (0..variables_number)
+            .filter(|bag_index| decomposition.bags[*bag_index].contains(&var_index))
+            .collect();
+        // exactly one bag of a subtree has a parent outside of it
+        let subtree_roots = containing
+            .iter()
+            .filter(|bag_index| match decomposition.parents[**bag_index] {
+                Some(parent) => !decomposition.bags[parent].contains(&var_index),
+                None => true,
+            })
+            .count();
+        assert_eq!(subtree_roots, 1);
+    }
+    let width = decomposition.bags.iter().map(Vec::len).max().unwrap() - 1;
+    assert_eq!(width, decomposition.treewidth);
+}
+
+#[test]
+fn ising_tree_decomposition_test() {
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(42), -0.5, 0.5);
+    let side = 6;
+    let spins_number = side * side;
+    let heuristics = [
+        EliminationHeuristic::MinDegree,
+        EliminationHeuristic::MinFill,
+    ];
+    // a binary tree with unit factors
+    let mut fgb = new_ising_builder::<SumProduct>(spins_number, 2 * spins_number);
+    let mut scopes = Vec::new();
+    for spin in 1..spins_number {
+        scopes.push(vec![(spin - 1) / 2, spin]);
+    }
+    for spin in 0..spins_number {
+        scopes.push(vec![spin]);
+    }
+    for scope in &scopes {
+        let factor = if scope.len() == 1 {
+            IsingFactor::UnitFactor(0.1)
+        } else {
+            IsingFactor::new(0.5, 0., 0.)
+        };
+        fgb.add_factor(factor, scope, &mut initializer).unwrap();
+    }
+    let fg = fgb.build();
+    for heuristic in heuristics {
+        let decomposition = fg.tree_decomposition(heuristic);
+        check_decomposition(&decomposition, &scopes, spins_number);
+        assert_eq!(decomposition.treewidth, 1);
+    }
+    // a square lattice whose treewidth equals its side
+    let mut fgb = new_ising_builder::<SumProduct>(spins_number, 2 * spins_number);
+    let mut scopes = Vec::new();
+    for i in 0..side {
+        for j in 0..(side - 1) {
+            scopes.push(vec![i * side + j, i * side + j + 1]);
+            scopes.push(vec![j * side + i, (j + 1) * side + i]);
+        }
+    }
+    for scope in &scopes {
+        fgb.add_factor(IsingFactor::new(0.5, 0., 0.), scope, &mut initializer)
+            .unwrap();
+    }
+    let fg = fgb.build();
+    for heuristic in heuristics {
+        let decomposition = fg.tree_decomposition(heuristic);
+        check_decomposition(&decomposition, &scopes, spins_number);
+        assert!(decomposition.treewidth >= side);
+        assert!(decomposition.treewidth <= side + 1);
+    }
+    // disconnected spins form a forest of single spin bags
+    let fg = new_ising_builder::<SumProduct>(3, 0).build();
+    let decomposition = fg.tree_decomposition(EliminationHeuristic::MinFill);
+    check_decomposition(&decomposition, &[], 3);
+    assert_eq!(decomposition.treewidth, 0);
+    assert_eq!(decomposition.parents, vec![None; 3]);
+}
+
+#[test]
+fn tabular_tree_decomposition_test() {
+    // a higher order factor forms a clique
+    let mut fgb = FactorGraphBuilder::<TabularFactor, TabularVariable>::new_with_variables(
+        vec![TabularVariable::new(2); 6],
+        3,
+    );
+    let mut initializer = uninformative_message_initializer();
+    let scopes = vec![vec![0, 1, 2, 3], vec![3, 4], vec![4, 5]];
+    for scope in &scopes {
+        fgb.add_factor(
+            TabularFactor::new(ArrayD::ones(vec![2; scope.len()])),
+            scope,
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    let fg = fgb.build();
+    for heuristic in [
+        EliminationHeuristic::MinDegree,
+        EliminationHeuristic::MinFill,
+    ] {
+        let decomposition = fg.tree_decomposition(heuristic);
+        check_decomposition(&decomposition, &scopes, 6);
+        assert_eq!(decomposition.treewidth, 3);
+    }
+}
+
+// The logarithm of an unnormalized weight of a configuration up to a constant
+fn log_weight(
+    couplings: &ndarray::Array2<f64>,
+    fields: &ndarray::Array1<f64>,
+    spins: &[f64],
+) -> f64 {
+    let mut log_weight = 0f64;
+    for (i, si) in spins.iter().enumerate() {
+        log_weight += fields[i] * si;
+        for (j, sj) in spins.iter().enumerate().skip(i + 1) {
+            log_weight += couplings[[i, j]] * si * sj;
+        }
+    }
+    log_weight
+}
+
+fn spins(config: usize, spins_number: usize) -> Vec<f64> {
+    (0..spins_number)
+        .map(|i| if (config >> i) & 1 == 0 { 1. } else { -1. })
+        .collect()
+}
+
+#[test]
+fn contraction_test() {
+    let mut rng = StdRng::seed_from_u64(42);
+    let spins_number = 7;
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(7), -0.5, 0.5);
+    // a random tree with extra couplings, fields and unit factors
+    let mut fgb = new_ising_builder::<SumProduct>(spins_number, 3 * spins_number);
+    for i in 1..spins_number {
+        let j = rng.gen_range(0..i);
+        fgb.add_factor(
+            IsingFactor::new(
+                rng.gen_range(-1f64..1f64),
+                rng.gen_range(-0.3f64..0.3f64),
+                rng.gen_range(-0.3f64..0.3f64),
+            ),
+            &[i, j],
+            &mut initializer,
+        )
+        .unwrap();
+        fgb.add_factor(
+            IsingFactor::UnitFactor(rng.gen_range(-1f64..1f64)),
+            &[i],
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    fgb.add_factor(IsingFactor::new(0.4, 0.1, -0.2), &[2, 5], &mut initializer)
+        .unwrap();
+    fgb.add_factor(IsingFactor::new(-0.7, 0., 0.), &[5, 2], &mut initializer)
+        .unwrap();
+    let fg = fgb.build();
+    let couplings = fg.coupling_matrix().to_dense();
+    let fields = fg.local_fields();
+    for (kept, removed, antiparallel) in [(2, 5, false), (5, 2, true), (0, 6, true), (6, 1, false)]
+    {
+        let mut contracted = fg.clone();
+        contracted
+            .contract_variables(kept, removed, antiparallel)
+            .unwrap();
+        assert_eq!(contracted.get_variable_degrees().len(), spins_number - 1);
+        let contracted_couplings = contracted.coupling_matrix().to_dense();
+        let contracted_fields = contracted.local_fields();
+        // the contracted model is the original one with a substituted spin
+        let mut differences = Vec::new();
+        for config in 0..(1usize << (spins_number - 1)) {
+            let contracted_spins = spins(config, spins_number - 1);
+            let mut original_spins = contracted_spins.clone();
+            let kept_index = if kept > removed { kept - 1 } else { kept };
+            let sign = if antiparallel { -1. } else { 1. };
+            original_spins.insert(removed, sign * contracted_spins[kept_index]);
+            differences.push(
+                log_weight(&couplings, &fields, &original_spins)
+                    - log_weight(&contracted_couplings, &contracted_fields, &contracted_spins),
+            );
+        }
+        assert!(differences
+            .iter()
+            .all(|difference| (difference - differences[0]).abs() < 1e-10));
+        // edges are consistent, thus message passing runs on a contracted graph
+        for (fac_index, var_indices) in (0..contracted.get_factor_degrees().len())
+            .map(|fac_index| (fac_index, contracted.factor_neighbors(fac_index).to_vec()))
+        {
+            for var_index in var_indices {
+                assert!(contracted
+                    .variable_neighbors(var_index)
+                    .contains(&fac_index));
+            }
+        }
+        contracted
+            .run_message_passing_parallel(
+                10000,
+                0,
+                1e-10,
+                &get_standard_factor_scheduler(0.5),
+                &get_standard_variable_scheduler(0.),
+            )
+            .unwrap();
+    }
+    // contraction of adjacent spins of a tree keeps a tree, thus marginals are exact
+    let kept_parent = fg
+        .variable_neighbors(3)
+        .iter()
+        .find(|fac_index| fg.factor_neighbors(**fac_index).len() == 2)
+        .map(|fac_index| fg.factor_neighbors(*fac_index).to_vec())
+        .unwrap();
+    let (kept, removed) = (kept_parent[0], kept_parent[1]);
+    let mut contracted_tree = fg.clone();
+    // removing the extra couplings turns the graph into a tree
+    let tree_factors = contracted_tree.get_factor_degrees().len() - 2;
+    for fac_index in tree_factors..(tree_factors + 2) {
+        contracted_tree
+            .replace_factor(fac_index, IsingFactor::new(0., 0., 0.))
+            .unwrap();
+    }
+    let tree_couplings = contracted_tree.coupling_matrix().to_dense();
+    let tree_fields = contracted_tree.local_fields();
+    contracted_tree
+        .contract_variables(kept, removed, false)
+        .unwrap();
+    contracted_tree
+        .run_message_passing_parallel(
+            1000,
+            0,
+            1e-12,
+            &get_standard_factor_scheduler(0.),
+            &get_standard_variable_scheduler(0.),
+        )
+        .unwrap();
+    let mut normalization = 0f64;
+    let mut magnetizations = vec![0f64; spins_number];
+    for config in 0..(1usize << spins_number) {
+        let spins = spins(config, spins_number);
+        if spins[kept] != spins[removed] {
+            continue;
+        }
+        let weight = log_weight(&tree_couplings, &tree_fields, &spins).exp();
+        normalization += weight;
+        for (m, s) in magnetizations.iter_mut().zip(&spins) {
+            *m += weight * s;
+        }
+    }
+    magnetizations.remove(removed);
+    for (marginal, m) in contracted_tree
+        .variable_marginals()
+        .iter()
+        .zip(magnetizations)
+    {
+        assert!((marginal[0] - marginal[1] - m / normalization).abs() < 1e-8);
+    }
+    // invalid contractions
+    let mut fg = fg;
+    assert!(matches!(
+        fg.contract_variables(1, 1, false),
+        Err(FGError::ContractionError(1))
+    ));
+    assert!(matches!(
+        fg.contract_variables(0, spins_number, false),
+        Err(FGError::OutOfRangeVariable(_, _))
+    ));
+}
@@ -1,6 +1,13 @@
-use rand::{distributions::Uniform, thread_rng, Rng};
+use rand::{distributions::Uniform, rngs::StdRng, thread_rng, Rng, SeedableRng};
 
-use crate::core::{Factor, FactorGraphBuilder, Message, Variable};
+use crate::core::{FGBuilderError, FGError, Factor, FactorGraphBuilder, Message, Variable};
+use crate::ising::{
+    new_ising_builder, random_message_initializer, IsingFactor, IsingMessage, SumProduct,
+};
+use crate::tabular::{
+    uninformative_message_initializer, TabularFactor, TabularMessage, TabularVariable,
+};
+use ndarray::{array, ArrayD, IxDyn};
 
 // The simples fake implementation of the message passing traits.
 // Note, that it is nonsense for all the applications apart
@@ -86,8 +93,10 @@ impl Variable for FakeVariable {
 fn small_factor_graph_builder_logic() {
     let mut rng = thread_rng();
     let mut mesage_initializer = || FakeMessage(rng.sample(Uniform::new(usize::MIN, usize::MAX)));
-    let mut fgb = FactorGraphBuilder::<FakeFactor, FakeVariable>::new_with_capacity(4, 3);
-    fgb.fill(FakeVariable);
+    let mut fgb = FactorGraphBuilder::<FakeFactor, FakeVariable>::new_with_variables(
+        vec![FakeVariable; 4],
+        3,
+    );
     fgb.add_factor(FakeFactor(3), &[0, 1, 3], &mut mesage_initializer)
         .unwrap();
     fgb.add_factor(FakeFactor(2), &[1, 2], &mut mesage_initializer)
@@ -181,46 +190,246 @@ fn small_factor_graph_builder_logic() {
         assert_eq!(var2.fac_node_receiver_indices, [1, 0]);
         assert_eq!(var3.fac_node_receiver_indices, [2, 0, 0]);
         // --------------------------------------------------------------------------------------
-        assert_eq!(unsafe { (*fac0.senders[0]).0 }, var0.receivers[0].0);
-        assert_eq!(unsafe { (*fac0.senders[1]).0 }, var1.receivers[0].0);
-        assert_eq!(unsafe { (*fac0.senders[2]).0 }, var3.receivers[0].0);
-        assert_eq!(unsafe { (*fac1.senders[0]).0 }, var1.receivers[1].0);
-        assert_eq!(unsafe { (*fac1.senders[1]).0 }, var2.receivers[0].0);
-        assert_eq!(unsafe { (*fac2.senders[0]).0 }, var3.receivers[1].0);
-        assert_eq!(unsafe { (*fac2.senders[1]).0 }, var1.receivers[2].0);
-        assert_eq!(
-            unsafe { (*freeze1.senders[0]).0 },
-            var0.receivers.last().unwrap().0
-        );
-        assert_eq!(
-            unsafe { (*freeze2.senders[0]).0 },
-            var1.receivers.last().unwrap().0
-        );
-        assert_eq!(
-            unsafe { (*freeze3.senders[0]).0 },
-            var2.receivers.last().unwrap().0
-        );
-        assert_eq!(
-            unsafe { (*freeze4.senders[0]).0 },
-            var3.receivers.last().unwrap().0
-        );
+        assert_eq!(fac0.messages[0].0, var0.receivers[0].0);
+        assert_eq!(fac0.messages[1].0, var1.receivers[0].0);
+        assert_eq!(fac0.messages[2].0, var3.receivers[0].0);
+        assert_eq!(fac1.messages[0].0, var1.receivers[1].0);
+        assert_eq!(fac1.messages[1].0, var2.receivers[0].0);
+        assert_eq!(fac2.messages[0].0, var3.receivers[1].0);
+        assert_eq!(fac2.messages[1].0, var1.receivers[2].0);
+        assert_eq!(freeze1.messages[0].0, var0.receivers.last().unwrap().0);
+        assert_eq!(freeze2.messages[0].0, var1.receivers.last().unwrap().0);
+        assert_eq!(freeze3.messages[0].0, var2.receivers.last().unwrap().0);
+        assert_eq!(freeze4.messages[0].0, var3.receivers.last().unwrap().0);
         // --------------------------------------------------------------------------------------
         assert_eq!(var0.receivers.last().unwrap().0, 0);
         assert_eq!(var1.receivers.last().unwrap().0, 1);
         assert_eq!(var2.receivers.last().unwrap().0, 2);
         assert_eq!(var3.receivers.last().unwrap().0, 3);
         // --------------------------------------------------------------------------------------
-        assert_eq!(unsafe { (*var0.senders[0]).0 }, fac0.receivers[0].0);
-        assert_eq!(unsafe { (*var0.senders[1]).0 }, freeze1.receivers[0].0);
-        assert_eq!(unsafe { (*var1.senders[0]).0 }, fac0.receivers[1].0);
-        assert_eq!(unsafe { (*var1.senders[1]).0 }, fac1.receivers[0].0);
-        assert_eq!(unsafe { (*var1.senders[2]).0 }, fac2.receivers[1].0);
-        assert_eq!(unsafe { (*var1.senders[3]).0 }, freeze2.receivers[0].0);
-        assert_eq!(unsafe { (*var2.senders[0]).0 }, fac1.receivers[1].0);
-        assert_eq!(unsafe { (*var2.senders[1]).0 }, freeze3.receivers[0].0);
-        assert_eq!(unsafe { (*var3.senders[0]).0 }, fac0.receivers[2].0);
-        assert_eq!(unsafe { (*var3.senders[1]).0 }, fac2.receivers[0].0);
-        assert_eq!(unsafe { (*var3.senders[2]).0 }, freeze4.receivers[0].0);
+        assert_eq!(var0.messages[0].0, fac0.receivers[0].0);
+        assert_eq!(var0.messages[1].0, freeze1.receivers[0].0);
+        assert_eq!(var1.messages[0].0, fac0.receivers[1].0);
+        assert_eq!(var1.messages[1].0, fac1.receivers[0].0);
+        assert_eq!(var1.messages[2].0, fac2.receivers[1].0);
+        assert_eq!(var1.messages[3].0, freeze2.receivers[0].0);
+        assert_eq!(var2.messages[0].0, fac1.receivers[1].0);
+        assert_eq!(var2.messages[1].0, freeze3.receivers[0].0);
+        assert_eq!(var3.messages[0].0, fac0.receivers[2].0);
+        assert_eq!(var3.messages[1].0, fac2.receivers[0].0);
+        assert_eq!(var3.messages[2].0, freeze4.receivers[0].0);
         drop(fg);
     }
 }
+
+#[test]
+fn failed_add_factor_leaves_builder_unchanged() {
+    let mut mesage_initializer = || FakeMessage(0);
+    let mut fgb = FactorGraphBuilder::<FakeFactor, FakeVariable>::new_with_variables(
+        vec![FakeVariable; 3],
+        2,
+    );
+    fgb.add_factor(FakeFactor(2), &[0, 1], &mut mesage_initializer)
+        .unwrap();
+    // the first variable is valid, the second one is out of range
+    let error = fgb
+        .add_factor(FakeFactor(2), &[2, 3], &mut mesage_initializer)
+        .unwrap_err();
+    assert_eq!(
+        error,
+        FGBuilderError::FactorError {
+            factor_index: 1,
+            factor: "FakeFactor(2)".to_string(),
+            cause: Box::new(FGBuilderError::OutOfRangeVariable(3, 3)),
+        }
+    );
+    assert!(error.to_string().contains("FakeFactor(2)"));
+    let error = fgb
+        .add_factor(FakeFactor(3), &[0, 2], &mut mesage_initializer)
+        .unwrap_err();
+    assert_eq!(error.reason(), &FGBuilderError::DegreeError(3, vec![0, 2]));
+    // the builder is usable after failures
+    fgb.add_factor(FakeFactor(2), &[2, 1], &mut mesage_initializer)
+        .unwrap();
+    let fg = fgb.build();
+    assert_eq!(fg.get_factor_degrees(), vec![2, 2]);
+    assert_eq!(fg.get_variable_degrees(), vec![1, 2, 1]);
+    assert_eq!(fg.variables[2].fac_node_indices, [1]);
+    assert_eq!(fg.factors[1].var_node_receiver_indices, [0, 1]);
+}
+
+#[test]
+fn message_initializer_contracts() {
+    // Ising messages must be finite
+    let mut fgb = new_ising_builder::<SumProduct>(2, 1);
+    let error = fgb
+        .add_factor(IsingFactor::new(0.5, 0., 0.), &[0, 1], &mut || {
+            IsingMessage(f64::NAN)
+        })
+        .unwrap_err();
+    assert_eq!(error.reason(), &FGBuilderError::InvalidMessage(0));
+    fgb.add_factor(IsingFactor::new(0.5, 0., 0.), &[0, 1], &mut || {
+        IsingMessage(0.1)
+    })
+    .unwrap();
+    let mut fg = fgb.build();
+    assert!(matches!(
+        fg.add_factor(IsingFactor::UnitFactor(0.3), &[1], &mut || {
+            IsingMessage(f64::INFINITY)
+        }),
+        Err(FGError::InvalidMessage(1))
+    ));
+    assert_eq!(fg.get_factor_degrees(), vec![2]);
+    // tabular messages are either uninformative or normalized over all states
+    let mut fgb = FactorGraphBuilder::<TabularFactor, TabularVariable>::new_with_variables(
+        [TabularVariable::new(2), TabularVariable::new(3)],
+        1,
+    );
+    let unit_factor = TabularFactor::new(array![0.2, 0.3, 0.5].into_dyn());
+    for message in [
+        array![0.5, 0.5],
+        array![0.2, 0.3, 0.4],
+        array![-0.5, 1.5, 0.],
+    ] {
+        let error = fgb
+            .add_factor(unit_factor.clone(), &[1], &mut || {
+                TabularMessage(message.clone())
+            })
+            .unwrap_err();
+        assert_eq!(error.reason(), &FGBuilderError::InvalidMessage(1));
+    }
+    fgb.add_factor(unit_factor, &[1], &mut || {
+        TabularMessage(array![0.2, 0.3, 0.5])
+    })
+    .unwrap();
+    let factor = TabularFactor::new(ArrayD::ones(IxDyn(&[2, 3])));
+    fgb.add_factor(factor, &[0, 1], &mut uninformative_message_initializer())
+        .unwrap();
+    // messages fixing variables are not verified
+    let mut fg = fgb.build();
+    fg.freeze_variable(&1, 1).unwrap();
+    assert_eq!(fg.get_factor_degrees(), vec![1, 2, 1]);
+}
+
+#[test]
+fn multi_edges_validation() {
+    let mut mesage_initializer = || FakeMessage(0);
+    let mut fgb = FactorGraphBuilder::<FakeFactor, FakeVariable>::new_with_variables(
+        vec![FakeVariable; 3],
+        4,
+    );
+    assert!(!fgb.multi_edges_allowed());
+    fgb.add_factor(FakeFactor(2), &[0, 1], &mut mesage_initializer)
+        .unwrap();
+    let error = fgb
+        .add_factor(FakeFactor(3), &[2, 0, 2], &mut mesage_initializer)
+        .unwrap_err();
+    assert_eq!(
+        error,
+        FGBuilderError::FactorError {
+            factor_index: 1,
+            factor: "FakeFactor(3)".to_string(),
+            cause: Box::new(FGBuilderError::DuplicateVariable(2)),
+        }
+    );
+    assert_eq!(
+        fgb.add_factor(FakeFactor(2), &[0, 1], &mut mesage_initializer)
+            .unwrap_err()
+            .reason(),
+        &FGBuilderError::DuplicateFactor(0)
+    );
+    // the same factor over variables in a different order is not a duplicate
+    fgb.add_factor(FakeFactor(2), &[1, 0], &mut mesage_initializer)
+        .unwrap();
+    fgb.set_multi_edges_allowed(true);
+    fgb.add_factor(FakeFactor(2), &[0, 1], &mut mesage_initializer)
+        .unwrap();
+    fgb.add_factor(FakeFactor(2), &[2, 2], &mut mesage_initializer)
+        .unwrap();
+    let fg = fgb.build();
+    assert_eq!(fg.get_variable_degrees(), vec![3, 3, 2]);
+    assert_eq!(fg.factors[3].var_node_receiver_indices, [0, 1]);
+}
+
+#[test]
+fn bulk_factors_insertion() {
+    let mut rng = thread_rng();
+    let factors: Vec<_> = (0..200)
+        .map(|_| {
+            let i = rng.gen_range(0..50);
+            let j = (i + rng.gen_range(1..50)) % 50;
+            (
+                IsingFactor::<SumProduct>::new(rng.gen(), rng.gen(), rng.gen()),
+                [i, j],
+            )
+        })
+        .collect();
+    let initializer = || random_message_initializer(StdRng::seed_from_u64(42), -0.5, 0.5);
+    let mut fgb = new_ising_builder::<SumProduct>(50, 0);
+    let mut sequential_initializer = initializer();
+    for (factor, var_indices) in factors.clone() {
+        fgb.add_factor(factor, &var_indices, &mut sequential_initializer)
+            .unwrap();
+    }
+    let mut bulk_fgb = new_ising_builder::<SumProduct>(50, 0);
+    assert_eq!(
+        bulk_fgb
+            .add_factors(factors.clone(), &mut initializer())
+            .unwrap(),
+        0..200
+    );
+    assert_eq!(
+        format!("{:?}", fgb.build().variables),
+        format!("{:?}", bulk_fgb.build().variables)
+    );
+
+    // insertion stops at the first rejected factor
+    let mut fgb = new_ising_builder::<SumProduct>(50, 0);
+    let mut rejected = factors.clone();
+    rejected[100].1 = [3, 50];
+    let error = fgb
+        .add_factors(rejected, &mut || IsingMessage(0.))
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        FGBuilderError::FactorError {
+            factor_index: 100,
+            ..
+        }
+    ));
+    assert_eq!(error.reason(), &FGBuilderError::OutOfRangeVariable(50, 50));
+    assert_eq!(fgb.build().factors.len(), 100);
+
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+
+        let mut fgb = new_ising_builder::<SumProduct>(50, 0);
+        fgb.add_factors(factors.clone(), &mut || IsingMessage(0.))
+            .unwrap();
+        let mut parallel_fgb = new_ising_builder::<SumProduct>(50, 0);
+        let parallel_factors = factors
+            .clone()
+            .into_par_iter()
+            .map(|(factor, var_indices)| (factor, var_indices.to_vec()));
+        assert_eq!(
+            parallel_fgb
+                .add_factors_parallel(parallel_factors, &|| IsingMessage(0.))
+                .unwrap(),
+            0..200
+        );
+        assert_eq!(
+            format!("{:?}", fgb.build().variables),
+            format!("{:?}", parallel_fgb.build().variables)
+        );
+
+        // duplicates among new factors are found
+        let duplicated = vec![factors[0], factors[0]]
+            .into_par_iter()
+            .map(|(factor, var_indices)| (factor, var_indices.to_vec()));
+        let error = new_ising_builder::<SumProduct>(50, 0)
+            .add_factors_parallel(duplicated, &|| IsingMessage(0.))
+            .unwrap_err();
+        assert_eq!(error.reason(), &FGBuilderError::DuplicateFactor(0));
+    }
+}
@@ -0,0 +1,240 @@
+use crate::core::FactorGraphBuilder;
+use crate::io::{
+    read_dimacs, read_ising, read_libdai, write_ising, write_libdai, Clause, DimacsError,
+    IsingInstance, IsingTextError, LibdaiError, LibdaiModel,
+};
+use crate::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+use crate::ising::{random_message_initializer, IsingFactor, IsingVariable, SumProduct};
+use crate::tabular::{uninformative_message_initializer, TabularFactor, TabularVariable};
+use ndarray::{ArrayD, IxDyn};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rand_distr::Uniform;
+
+// A clause of two literals as an Ising factor exp ( -weight * [clause is violated] )
+#[inline]
+fn two_clause_factor(clause: &Clause) -> IsingFactor<SumProduct> {
+    let sign = |i: usize| if clause.literals[i].negated { -1. } else { 1. };
+    let w = clause.weight / 4.;
+    IsingFactor::new(-w * sign(0) * sign(1), w * sign(0), w * sign(1))
+}
+
+#[test]
+fn dimacs_two_sat_test() {
+    let dimacs = "c a tree-like weighted 2-SAT instance
+p wcnf 5 4 100
+1.5 1 -2 0
+0.7 2 3
+0
+2.1 -3 4 0
+0.3 -4 -5 0
+%
+0
+";
+    let formula = read_dimacs(dimacs.as_bytes()).unwrap();
+    assert_eq!(formula.variables_number, 5);
+    assert_eq!(formula.clauses.len(), 4);
+    assert_eq!(formula.clauses[1].weight, 0.7);
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(42), -0.5, 0.5);
+    let mut fg = formula
+        .to_builder(
+            IsingVariable::<SumProduct>::new(),
+            two_clause_factor,
+            &mut initializer,
+        )
+        .unwrap()
+        .build();
+    let _ = fg
+        .run_message_passing_parallel(
+            1000,
+            0,
+            1e-10,
+            &get_standard_factor_scheduler(0.),
+            &get_standard_variable_scheduler(0.),
+        )
+        .unwrap();
+    // exact marginals by enumeration
+    let mut up_probabilities = [0f64; 5];
+    let mut partition_function = 0f64;
+    for config in 0..(1 << 5) {
+        let value = |variable: usize| (config >> variable) & 1 == 1;
+        let penalty: f64 = formula
+            .clauses
+            .iter()
+            .filter(|clause| {
+                clause
+                    .literals
+                    .iter()
+                    .all(|l| value(l.variable) == l.negated)
+            })
+            .map(|clause| clause.weight)
+            .sum();
+        let weight = f64::exp(-penalty);
+        partition_function += weight;
+        for (variable, p) in up_probabilities.iter_mut().enumerate() {
+            if value(variable) {
+                *p += weight;
+            }
+        }
+    }
+    for (marginal, p) in fg.variable_marginals().iter().zip(up_probabilities) {
+        assert!((marginal[0] - p / partition_function).abs() < 1e-8);
+    }
+}
+
+#[test]
+fn dimacs_errors_test() {
+    assert!(matches!(
+        read_dimacs("1 2 0\n".as_bytes()),
+        Err(DimacsError::HeaderError(_))
+    ));
+    assert!(matches!(
+        read_dimacs("p cnf 2 1\n1 3 0\n".as_bytes()),
+        Err(DimacsError::OutOfRangeLiteral(2, 3))
+    ));
+    assert!(matches!(
+        read_dimacs("p cnf 2 1\n1 x 0\n".as_bytes()),
+        Err(DimacsError::ParseError(2, _))
+    ));
+    assert!(matches!(
+        read_dimacs("p cnf 2 2\n1 2 0\n".as_bytes()),
+        Err(DimacsError::ClausesNumberError(2, 1))
+    ));
+}
+
+#[test]
+fn ising_text_round_trip_test() {
+    let mut rng = StdRng::seed_from_u64(42);
+    let spins_number = 6;
+    let instance = IsingInstance {
+        spins_number,
+        couplings: (1..spins_number)
+            .map(|i| (rng.gen_range(0..i), i, rng.gen_range(-1f64..1f64)))
+            .collect(),
+        fields: (0..spins_number)
+            .map(|i| (i, rng.gen_range(-0.5f64..0.5f64)))
+            .collect(),
+    };
+    let mut buffer = Vec::new();
+    write_ising(&mut buffer, &instance).unwrap();
+    assert_eq!(read_ising(buffer.as_slice()).unwrap(), instance);
+    // an instance survives the conversion to a factor graph and back
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(7), -0.5, 0.5);
+    let fg = instance
+        .to_builder::<SumProduct>(&mut initializer)
+        .unwrap()
+        .build();
+    let restored = IsingInstance::from_factor_graph(&fg);
+    assert_eq!(restored.spins_number, spins_number);
+    for (lhs, rhs) in restored.couplings.iter().zip(&instance.couplings) {
+        assert_eq!((lhs.0, lhs.1), (rhs.0, rhs.1));
+        assert!((lhs.2 - rhs.2).abs() < 1e-12);
+    }
+    for (lhs, rhs) in restored.fields.iter().zip(&instance.fields) {
+        assert_eq!(lhs.0, rhs.0);
+        assert!((lhs.1 - rhs.1).abs() < 1e-12);
+    }
+    // fields of a factor graph match fields of an instance
+    let fields = fg.local_fields();
+    for (i, field) in &instance.fields {
+        assert!((fields[*i] - field).abs() < 1e-12);
+    }
+}
+
+#[test]
+fn ising_text_errors_test() {
+    assert!(matches!(
+        read_ising("# no header\n".as_bytes()),
+        Err(IsingTextError::HeaderError(_))
+    ));
+    assert!(matches!(
+        read_ising("two\n0 1 0.5\n".as_bytes()),
+        Err(IsingTextError::HeaderError(_))
+    ));
+    assert!(matches!(
+        read_ising("2\n\n0 2 0.5\n".as_bytes()),
+        Err(IsingTextError::OutOfRangeSpin(2, 2))
+    ));
+    assert_eq!(
+        read_ising("2\n0 1 0.5 0.1\n".as_bytes()),
+        Err(IsingTextError::ParseError(2, "0 1 0.5 0.1".to_string()))
+    );
+    assert_eq!(
+        read_ising("2\n1 1 0.5\n".as_bytes()),
+        Err(IsingTextError::ParseError(2, "1 1 0.5".to_string()))
+    );
+    assert_eq!(
+        read_ising("2\n# a field\n1 x\n".as_bytes()),
+        Err(IsingTextError::ParseError(3, "1 x".to_string()))
+    );
+}
+
+#[test]
+fn libdai_round_trip_test() {
+    let cardinalities = [2, 3, 2, 4, 3];
+    // a tree with a unit, pairwise and triple factors
+    let adjacency: [&[usize]; 4] = [&[0], &[0, 1], &[1, 2, 3], &[4, 3]];
+    let mut rng = StdRng::seed_from_u64(42);
+    let distr = Uniform::new(0.1, 1.);
+    let mut initializer = uninformative_message_initializer();
+    let mut fgb = FactorGraphBuilder::new_with_variables(
+        cardinalities.iter().map(|x| TabularVariable::new(*x)),
+        adjacency.len(),
+    );
+    let mut tables = Vec::new();
+    for var_indices in adjacency {
+        let shape: Vec<_> = var_indices.iter().map(|x| cardinalities[*x]).collect();
+        let table = ArrayD::from_shape_simple_fn(IxDyn(&shape), || rng.sample(distr));
+        tables.push(table.clone());
+        fgb.add_factor(TabularFactor::new(table), var_indices, &mut initializer)
+            .unwrap();
+    }
+    let mut fg = fgb.build();
+    let mut buffer = Vec::new();
+    write_libdai(&mut buffer, &LibdaiModel::from_factor_graph(&fg)).unwrap();
+    let model = read_libdai(buffer.as_slice()).unwrap();
+    assert_eq!(model.cardinalities, cardinalities.to_vec());
+    let mut restored_fg = model.to_builder(&mut initializer).unwrap().build();
+    for fg in [&mut fg, &mut restored_fg] {
+        let _ = fg
+            .run_message_passing_parallel(100, 0, 1e-12, &|_| 0., &|_| 0.)
+            .unwrap();
+    }
+    // exact marginals by enumeration
+    let mut exact_marginals: Vec<Vec<f64>> = cardinalities.iter().map(|x| vec![0.; *x]).collect();
+    let mut config = vec![0usize; cardinalities.len()];
+    let configs_number: usize = cardinalities.iter().product();
+    for mut x in 0..configs_number {
+        for (state, cardinality) in config.iter_mut().zip(cardinalities) {
+            *state = x % cardinality;
+            x /= cardinality;
+        }
+        let weight: f64 = adjacency
+            .iter()
+            .zip(&tables)
+            .map(|(var_indices, table)| {
+                let index: Vec<_> = var_indices.iter().map(|x| config[*x]).collect();
+                table[IxDyn(&index)]
+            })
+            .product();
+        for (marginal, state) in exact_marginals.iter_mut().zip(&config) {
+            marginal[*state] += weight;
+        }
+    }
+    for ((marginal, restored_marginal), exact_marginal) in fg
+        .variable_marginals()
+        .iter()
+        .zip(restored_fg.variable_marginals())
+        .zip(exact_marginals)
+    {
+        let sum: f64 = exact_marginal.iter().sum();
+        for (state, p) in exact_marginal.iter().enumerate() {
+            assert!((marginal[state] - p / sum).abs() < 1e-10);
+            assert!((restored_marginal[state] - p / sum).abs() < 1e-10);
+        }
+    }
+    // an index of an entry is out of range of a table
+    assert_eq!(
+        read_libdai("1\n\n1\n0\n2\n1\n5 0.5\n".as_bytes()),
+        Err(LibdaiError::OutOfRangeEntry(2, 5))
+    );
+}
@@ -1,8 +1,12 @@
 use super::ising_utils::{
     exact_infinite_1d_ising_free_entropy, exact_infinite_1d_ising_up_probability,
 };
+use crate::core::FactorGraphBuilder;
 use crate::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
-use crate::ising::{new_ising_builder, random_message_initializer, IsingFactor, SumProduct};
+use crate::ising::{
+    new_ising_builder, new_ising_chain_builder, new_ising_ring_builder, random_message_initializer,
+    IsingFactor, IsingVariable, SumProduct,
+};
 use rand::thread_rng;
 
 #[test]
@@ -61,3 +65,114 @@ fn ising_1d_test() {
             < error * 10f64
     );
 }
+
+#[test]
+fn ising_1d_variable_fields_test() {
+    let spins_number = 101;
+    let coupling = 1.1f64;
+    let magnetic_field = 0.3f64;
+    let error = 1e-10f64;
+    let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    let factor_scheduler = get_standard_factor_scheduler(0.5);
+    let variable_scheduler = get_standard_variable_scheduler(0.5);
+    // fields are stored in variables, the middle spin has its own damping
+    let variables = (0..spins_number).map(|i| {
+        let variable = IsingVariable::<SumProduct>::new().with_field(magnetic_field);
+        if i == spins_number / 2 {
+            variable.with_damping(0.9)
+        } else {
+            variable
+        }
+    });
+    let mut fgb = FactorGraphBuilder::new_with_variables(variables, spins_number - 1);
+    for i in 0..(spins_number - 1) {
+        fgb.add_factor(
+            IsingFactor::<SumProduct>::new(coupling, 0f64, 0f64),
+            &[i, i + 1],
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    let mut fg = fgb.build();
+    let _ = fg
+        .run_message_passing_parallel(1000, 0, error, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    let variable_marginals = fg.variable_marginals();
+    let (exact_mid_spin_prob_up, exact_bound_spin_prob_up) =
+        exact_infinite_1d_ising_up_probability(coupling, magnetic_field, error);
+    assert!(
+        (exact_mid_spin_prob_up - variable_marginals[spins_number / 2][0]).abs() < error * 10f64
+    );
+    assert!((exact_bound_spin_prob_up - variable_marginals[0][0]).abs() < error * 10f64);
+}
+
+#[test]
+fn ising_1d_builders_test() {
+    let spins_number = 101;
+    let coupling = 1.1f64;
+    let magnetic_field = 0.3f64;
+    let error = 1e-10f64;
+    let mut initializer = random_message_initializer(thread_rng(), -0.5, 0.5);
+    let factor_scheduler = get_standard_factor_scheduler(0.5);
+    let variable_scheduler = get_standard_variable_scheduler(0.5);
+    let (exact_mid_spin_prob_up, exact_bound_spin_prob_up) =
+        exact_infinite_1d_ising_up_probability(coupling, magnetic_field, error);
+    // a homogeneous chain
+    let mut fg = new_ising_chain_builder::<SumProduct>(
+        spins_number,
+        coupling,
+        magnetic_field,
+        [magnetic_field; 2],
+        &mut initializer,
+    )
+    .unwrap()
+    .build();
+    assert_eq!(fg.factors.len(), spins_number - 1);
+    let _ = fg
+        .run_message_passing_parallel(1000, 0, error, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    let variable_marginals = fg.variable_marginals();
+    assert!(
+        (exact_mid_spin_prob_up - variable_marginals[spins_number / 2][0]).abs() < error * 10f64
+    );
+    assert!((exact_bound_spin_prob_up - variable_marginals[0][0]).abs() < error * 10f64);
+    assert!(
+        (exact_bound_spin_prob_up - variable_marginals[spins_number - 1][0]).abs() < error * 10f64
+    );
+    // a boundary field only changes the boundary spin's own marginal by the field
+    let mut fg = new_ising_chain_builder::<SumProduct>(
+        spins_number,
+        coupling,
+        magnetic_field,
+        [magnetic_field, -magnetic_field],
+        &mut initializer,
+    )
+    .unwrap()
+    .build();
+    let _ = fg
+        .run_message_passing_parallel(1000, 0, error, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    let variable_marginals = fg.variable_marginals();
+    assert!((exact_bound_spin_prob_up - variable_marginals[0][0]).abs() < error * 10f64);
+    let last = &variable_marginals[spins_number - 1];
+    let exact_log_ratio =
+        (exact_bound_spin_prob_up / (1f64 - exact_bound_spin_prob_up)).ln() - 4f64 * magnetic_field;
+    assert!(((last[0] / last[1]).ln() - exact_log_ratio).abs() < error * 100f64);
+    // a periodic ring is translation invariant
+    let mut fg = new_ising_ring_builder::<SumProduct>(
+        spins_number,
+        coupling,
+        magnetic_field,
+        coupling,
+        &mut initializer,
+    )
+    .unwrap()
+    .build();
+    assert_eq!(fg.get_variable_degrees(), vec![2; spins_number]);
+    let _ = fg
+        .run_message_passing_parallel(1000, 0, error, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    for marginal in fg.variable_marginals() {
+        assert!((exact_mid_spin_prob_up - marginal[0]).abs() < error * 10f64);
+    }
+}
@@ -31,9 +31,10 @@ fn ising_2d_test() {
         }
     }
     let mut fg = fgb.build();
-    let _ = fg
+    let info = fg
         .run_message_passing_parallel(10000, 0, error, &factor_scheduler, &variable_scheduler)
         .unwrap();
+    assert_eq!(info.certificate, None);
     let variable_marginals = fg.variable_marginals();
     let calculated_up_prob = if variable_marginals[0][0] > variable_marginals[0][1] {
         variable_marginals[0][0]
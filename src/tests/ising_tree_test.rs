@@ -1,3 +1,4 @@
+use crate::core::ExactnessCertificate;
 use crate::ising::{
     new_ising_builder, random_message_initializer,
     schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler},
@@ -100,7 +101,7 @@ fn maxcut_random_tree_test() {
         .unwrap();
     }
     let mut fg = fgb.build();
-    let _ = fg
+    let info = fg
         .run_message_passing_parallel(
             max_iterations_number,
             min_iterations_number,
@@ -109,6 +110,7 @@ fn maxcut_random_tree_test() {
             &variable_scheduler,
         )
         .unwrap();
+    assert_eq!(info.certificate, Some(ExactnessCertificate::Tree));
     let mut energy = 0.;
     for (fm, f) in fg.factor_marginals().into_iter().zip(fg.factors()) {
         let (e, _) = f
@@ -120,6 +122,9 @@ fn maxcut_random_tree_test() {
     }
     let exact_energy = eval_energy(&argmax, &edges, &weights);
     assert!((energy - exact_energy).abs() < 1e-10);
+    let map_assignment = fg.map_assignment(&get_standard_factor_scheduler(0.)(0));
+    let map_energy = eval_energy(&map_assignment, &edges, &weights);
+    assert!((map_energy - exact_energy).abs() < 1e-10);
     let mut rng = thread_rng();
     let sampling_info = fg
         .sample(
@@ -0,0 +1,86 @@
+use crate::core::FGError;
+use crate::ising::{
+    new_ising_builder, random_message_initializer, IsingFactor, IsingFactorHyperParameters,
+    SumProduct,
+};
+use crate::mcmc::GibbsSampler;
+use rand::{rngs::StdRng, SeedableRng};
+
+#[test]
+fn frustrated_triangle_gibbs_test() {
+    let edges = [[0, 1], [1, 2], [2, 0]];
+    let couplings = [0.8f64, -0.6f64, 0.4f64];
+    let fields = [0.3f64, -0.2f64, 0.1f64];
+    let samples_number = 40000;
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(42), -0.5, 0.5);
+    let mut fgb = new_ising_builder::<SumProduct>(3, 3);
+    for (i, (edge, coupling)) in edges.iter().zip(couplings).enumerate() {
+        // each magnetic field is attached to the factor where the spin goes first
+        fgb.add_factor(
+            IsingFactor::new(coupling, fields[i], 0.),
+            edge,
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    let fg = fgb.build();
+    let mut rng = StdRng::seed_from_u64(7);
+    let parameters = IsingFactorHyperParameters::new(1., 0.);
+    let mut sampler = GibbsSampler::new(&fg, vec![1, 1, 1]).unwrap();
+    let samples = sampler
+        .run(samples_number, 100, 1, &mut rng, &parameters)
+        .samples;
+    let log_weight = |config: &[i8]| {
+        let s: Vec<f64> = config.iter().map(|x| *x as f64).collect();
+        edges
+            .iter()
+            .zip(couplings)
+            .map(|([i, j], c)| c * s[*i] * s[*j])
+            .sum::<f64>()
+            + fields.iter().zip(&s).map(|(h, x)| h * x).sum::<f64>()
+    };
+    let configs: Vec<[i8; 3]> = (0..8)
+        .map(|n| {
+            [
+                1 - 2 * ((n & 1) as i8),
+                1 - 2 * (((n >> 1) & 1) as i8),
+                1 - 2 * (((n >> 2) & 1) as i8),
+            ]
+        })
+        .collect();
+    let partition_function: f64 = configs.iter().map(|c| log_weight(c).exp()).sum();
+    for config in &configs {
+        let exact = log_weight(config).exp() / partition_function;
+        let empirical = samples
+            .iter()
+            .filter(|sample| sample.as_slice() == config)
+            .count() as f64
+            / samples_number as f64;
+        assert!(
+            (exact - empirical).abs() < 1.5e-2,
+            "config: {config:?}, exact: {exact}, empirical: {empirical}"
+        );
+    }
+}
+
+#[test]
+fn repeated_variable_gibbs_test() {
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(42), -0.5, 0.5);
+    let mut fgb = new_ising_builder::<SumProduct>(2, 2);
+    fgb.set_multi_edges_allowed(true);
+    fgb.add_factor(IsingFactor::new(0.5, 0.1, 0.), &[0, 1], &mut initializer)
+        .unwrap();
+    // a factor whose both spins are the same variable
+    fgb.add_factor(IsingFactor::new(0.8, 0.3, -0.2), &[1, 1], &mut initializer)
+        .unwrap();
+    let fg = fgb.build();
+    let mut rng = StdRng::seed_from_u64(7);
+    assert!(matches!(
+        GibbsSampler::new(&fg, vec![1, 1]),
+        Err(FGError::RepeatedEdge(1, 1))
+    ));
+    assert!(matches!(
+        GibbsSampler::from_beliefs(&fg, &mut rng),
+        Err(FGError::RepeatedEdge(1, 1))
+    ));
+}
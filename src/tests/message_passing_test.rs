@@ -0,0 +1,1223 @@
+use std::fmt::Debug;
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use super::{chain_fg, ring_fg, torus_fg};
+use crate::core::{
+    AdaptiveDamping, DampingMode, FGError, Factor, FactorGraph, FactorGraphBuilder, FnObservable,
+    MessagePassingOptions, Observable, StochasticUpdates, StreamedMarginal, Variable,
+};
+use crate::ising::schedulers::{
+    auto_damping, get_auto_damped_factor_scheduler, get_grouped_exponential_factor_scheduler,
+    get_standard_factor_scheduler, get_standard_variable_scheduler,
+};
+use crate::ising::{
+    new_ising_builder, new_ising_chain_builder, random_message_initializer, BetheEnergy,
+    BetheFreeEntropy, IsingFactor, IsingFactorHyperParameters, IsingMessage,
+    IsingMessagePassingType, IsingVariable, Lanes, Magnetization, MaxProduct, SumProduct, LANES,
+};
+use ndarray::{Array1, ArrayD};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rand_distr::Uniform;
+
+#[test]
+fn antiferromagnetic_curie_weiss_adaptive_damping_test() {
+    let spins_number = 100;
+    let coupling = -5.;
+    let magnetic_field = 0.7654;
+    let error = 1e-10f64;
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(42), -0.5, 0.5);
+    let mut fgb =
+        new_ising_builder::<SumProduct>(spins_number, (spins_number - 1) * spins_number / 2);
+    for i in 0..spins_number {
+        for j in (i + 1)..spins_number {
+            fgb.add_factor(
+                IsingFactor::new(
+                    coupling / (spins_number as f64),
+                    magnetic_field / ((spins_number - 1) as f64),
+                    magnetic_field / ((spins_number - 1) as f64),
+                ),
+                &[i, j],
+                &mut initializer,
+            )
+            .unwrap();
+        }
+    }
+    let fg = fgb.build();
+    // undamped message passing oscillates
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    assert!(fg
+        .clone()
+        .run_message_passing_parallel(1000, 0, error, &factor_scheduler, &variable_scheduler)
+        .is_err());
+    // strongly damped message passing is the reference
+    let mut reference_fg = fg.clone();
+    let _ = reference_fg
+        .run_message_passing_parallel(
+            10000,
+            0,
+            error,
+            &get_standard_factor_scheduler(0.5),
+            &get_standard_variable_scheduler(0.5),
+        )
+        .unwrap();
+    // damping chosen from statistics of the graph
+    let gamma = auto_damping(&fg, 1.);
+    assert!(gamma > 0.5 && gamma < 0.9);
+    let mut auto_damped_fg = fg.clone();
+    auto_damped_fg
+        .run_message_passing_parallel(
+            1000,
+            0,
+            error,
+            &get_auto_damped_factor_scheduler(&fg, 1.),
+            &variable_scheduler,
+        )
+        .unwrap();
+    for (marginal, reference_marginal) in auto_damped_fg
+        .variable_marginals()
+        .iter()
+        .zip(reference_fg.variable_marginals())
+    {
+        assert!((marginal[0] - reference_marginal[0]).abs() < 1e-8);
+    }
+    let mut fg = fg;
+    let _ = fg
+        .run_message_passing_with_options(
+            &mut MessagePassingOptions::new(&factor_scheduler, &variable_scheduler)
+                .with_threshold(error)
+                .with_damping(DampingMode::Adaptive(AdaptiveDamping::default())),
+        )
+        .unwrap();
+    for (marginal, reference_marginal) in fg
+        .variable_marginals()
+        .iter()
+        .zip(reference_fg.variable_marginals())
+    {
+        assert!((marginal[0] - reference_marginal[0]).abs() < 1e-8);
+    }
+}
+
+#[test]
+fn stochastic_updates_test() {
+    let spins_number = 30;
+    let mut rng = StdRng::seed_from_u64(42);
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(7), -0.5, 0.5);
+    // a dense model with weak random couplings
+    let mut fgb = new_ising_builder::<SumProduct>(spins_number, spins_number * spins_number);
+    let scale = 0.5 / (spins_number as f64).sqrt();
+    for i in 0..spins_number {
+        for j in (i + 1)..spins_number {
+            let coupling = scale * rng.gen_range(-1f64..1f64);
+            let field = rng.gen_range(-0.02f64..0.02f64);
+            fgb.add_factor(
+                IsingFactor::new(coupling, field, 0.),
+                &[i, j],
+                &mut initializer,
+            )
+            .unwrap();
+        }
+    }
+    let fg = fgb.build();
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    let mut reference_fg = fg.clone();
+    let reference_info = reference_fg
+        .run_message_passing_parallel(1000, 0, 1e-12, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    // all factors are updated without damping, which is the ordinary message passing
+    let mut full_fg = fg.clone();
+    let options = |max_iterations_number, batch_fraction, damping, seed| {
+        MessagePassingOptions::new(&factor_scheduler, &variable_scheduler)
+            .with_max_iterations_number(max_iterations_number)
+            .with_threshold(1e-12)
+            .with_damping(DampingMode::Stochastic(StochasticUpdates {
+                batch_fraction,
+                damping,
+                seed,
+            }))
+    };
+    let info = full_fg
+        .run_message_passing_with_options(&mut options(1000, 1., 0., 1))
+        .unwrap();
+    assert_eq!(
+        info.discrepancy_dynamics,
+        reference_info.discrepancy_dynamics
+    );
+    assert_eq!(
+        full_fg.variable_marginals(),
+        reference_fg.variable_marginals()
+    );
+    // a stochastic iteration reaches the same fixed point and is reproducible
+    let run = |seed: u64| {
+        let mut stochastic_fg = fg.clone();
+        let info = stochastic_fg
+            .run_message_passing_with_options(&mut options(100000, 0.2, 0.5, seed))
+            .unwrap();
+        (info, stochastic_fg.variable_marginals())
+    };
+    let (info, marginals) = run(3);
+    assert!(info.iterations_number > reference_info.iterations_number);
+    assert!(info
+        .discrepancy_dynamics
+        .iter()
+        .rev()
+        .take(5)
+        .all(|discrepancy| *discrepancy < 1e-12));
+    for (lhs, rhs) in marginals.iter().zip(reference_fg.variable_marginals()) {
+        assert!((lhs - rhs).iter().all(|x| x.abs() < 1e-9));
+    }
+    let (repeated_info, repeated_marginals) = run(3);
+    assert_eq!(
+        info.discrepancy_dynamics,
+        repeated_info.discrepancy_dynamics
+    );
+    assert_eq!(marginals, repeated_marginals);
+}
+
+#[test]
+fn convergence_window_test() {
+    let fg = torus_fg(4, IsingFactor::new(0.3, 0.1, -0.1), 42);
+    // messages are frozen at the 4-th iteration, thus the discrepancy dips to zero
+    let factor_scheduler =
+        |i: usize| IsingFactorHyperParameters::new(1., if i == 3 { 1. } else { 0. });
+    let variable_scheduler = |i: usize| if i == 3 { 1. } else { 0. };
+    let mut premature_fg = fg.clone();
+    let info = premature_fg
+        .run_message_passing_parallel(1000, 0, 1e-10, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    assert_eq!(info.iterations_number, 3);
+    let mut windowed_fg = fg.clone();
+    let info = windowed_fg
+        .run_message_passing_with_options(
+            &mut MessagePassingOptions::new(&factor_scheduler, &variable_scheduler)
+                .with_convergence_window(3),
+        )
+        .unwrap();
+    assert!(info.iterations_number > 5);
+    assert!(info.discrepancy_dynamics[4] > 1e-10);
+    assert!(info
+        .discrepancy_dynamics
+        .iter()
+        .rev()
+        .take(3)
+        .all(|discrepancy| *discrepancy < 1e-10));
+    assert!(info.discrepancy_dynamics[info.iterations_number - 3] >= 1e-10);
+    // a window of zero or one iteration is the standard stopping rule
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    let factor_scheduler = |_| IsingFactorHyperParameters::new(1., 0.);
+    let mut standard_fg = fg.clone();
+    let standard_info = standard_fg
+        .run_message_passing_parallel(1000, 0, 1e-10, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    for window in [0, 1] {
+        let mut windowed_fg = fg.clone();
+        let info = windowed_fg
+            .run_message_passing_with_options(
+                &mut MessagePassingOptions::new(&factor_scheduler, &variable_scheduler)
+                    .with_convergence_window(window),
+            )
+            .unwrap();
+        assert_eq!(
+            info.discrepancy_dynamics,
+            standard_info.discrepancy_dynamics
+        );
+        assert_eq!(
+            windowed_fg.variable_marginals(),
+            standard_fg.variable_marginals()
+        );
+    }
+}
+
+#[test]
+fn marginal_stability_test() {
+    let fg = torus_fg(4, IsingFactor::new(0.4, 0.05, -0.02), 42);
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    let mut converged_fg = fg.clone();
+    let converged_info = converged_fg
+        .run_message_passing_parallel(10000, 0, 1e-14, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    let options = |min_iterations_number| {
+        MessagePassingOptions::new(&factor_scheduler, &variable_scheduler)
+            .with_max_iterations_number(10000)
+            .with_min_iterations_number(min_iterations_number)
+            .with_threshold(1e-14)
+            .with_marginal_tolerance(1e-6)
+    };
+    let mut stable_fg = fg.clone();
+    let info = stable_fg
+        .run_message_passing_with_options(&mut options(0))
+        .unwrap();
+    assert!(info.iterations_number < converged_info.iterations_number);
+    assert!(info.last_discrepancy >= 1e-14);
+    assert_eq!(info.discrepancy_dynamics.len(), info.iterations_number + 1);
+    for (stable, converged) in stable_fg
+        .variable_marginals()
+        .iter()
+        .zip(converged_fg.variable_marginals())
+    {
+        assert!((stable - &converged).mapv(f64::abs).sum() < 1e-4);
+    }
+    // the minimal number of iterations is respected
+    let info = fg
+        .clone()
+        .run_message_passing_with_options(&mut options(converged_info.iterations_number + 10))
+        .unwrap();
+    assert_eq!(info.iterations_number, converged_info.iterations_number + 9);
+    // a run failing both criteria is an error
+    assert!(fg
+        .clone()
+        .run_message_passing_with_options(
+            &mut options(0)
+                .with_max_iterations_number(3)
+                .with_marginal_tolerance(1e-14)
+        )
+        .is_err());
+}
+
+#[test]
+fn time_budget_test() {
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    // a frustrated triangle without damping, the minimal number of iterations
+    // is not reachable within the budget
+    let mut fgb = new_ising_builder::<SumProduct>(3, 3);
+    for (i, j) in [(0, 1), (1, 2), (2, 0)] {
+        fgb.add_factor(IsingFactor::new(-3., 0., 0.), &[i, j], &mut || {
+            IsingMessage(0.5)
+        })
+        .unwrap();
+    }
+    let mut fg = fgb.build();
+    let budget = Duration::from_millis(10);
+    let mut options = MessagePassingOptions::new(&factor_scheduler, &variable_scheduler)
+        .with_max_iterations_number(1_000_000)
+        .with_min_iterations_number(1_000_000)
+        .with_budget(budget);
+    match fg.run_message_passing_with_options(&mut options) {
+        Err(FGError::TimedOut {
+            elapsed,
+            sampled_variables_number,
+            iterations_number,
+            last_discrepancy,
+            discrepancy_dynamics,
+        }) => {
+            assert!(elapsed > budget);
+            assert_eq!(sampled_variables_number, 0);
+            assert!(iterations_number < 1_000_000);
+            assert_eq!(discrepancy_dynamics.len(), iterations_number);
+            assert_eq!(*discrepancy_dynamics.last().unwrap(), last_discrepancy);
+        }
+        _ => panic!("message passing must run out of time"),
+    }
+
+    // a converging run is not affected by a budget
+    let chain =
+        new_ising_chain_builder::<SumProduct>(20, 0.5, 0.1, [0., 0.], &mut || IsingMessage(0.))
+            .unwrap()
+            .build();
+    let info = chain
+        .clone()
+        .run_message_passing_parallel(1000, 0, 1e-10, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    let mut options = MessagePassingOptions::new(&factor_scheduler, &variable_scheduler)
+        .with_budget(Duration::from_secs(60));
+    let budgeted_info = chain
+        .clone()
+        .run_message_passing_with_options(&mut options)
+        .unwrap();
+    assert_eq!(info.iterations_number, budgeted_info.iterations_number);
+    let samples = chain
+        .clone()
+        .sample(
+            1000,
+            0,
+            1e-10,
+            &mut StdRng::seed_from_u64(42),
+            &factor_scheduler,
+            &variable_scheduler,
+        )
+        .unwrap()
+        .samples;
+    let budgeted_samples = chain
+        .clone()
+        .sample_with_options(&mut StdRng::seed_from_u64(42), &mut options)
+        .unwrap()
+        .samples;
+    assert_eq!(samples, budgeted_samples);
+
+    // sampling stops at the first message passing run after the budget is exceeded
+    let mut options = options.with_budget(Duration::ZERO);
+    let result = chain
+        .clone()
+        .sample_with_options(&mut StdRng::seed_from_u64(42), &mut options);
+    assert!(matches!(
+        result,
+        Err(FGError::TimedOut {
+            sampled_variables_number: 0,
+            iterations_number: 1,
+            ..
+        })
+    ));
+}
+
+#[test]
+#[allow(deprecated)]
+fn options_test() {
+    let side = 4;
+    let spins_number = side * side;
+    let fg = torus_fg(side, IsingFactor::new(0.3, 0.05, 0.), 42);
+    let factor_scheduler = get_standard_factor_scheduler(0.5);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+
+    // options reproduce positional parameters of deprecated variants
+    let info = fg
+        .clone()
+        .run_message_passing_parallel_windowed(
+            500,
+            5,
+            1e-8,
+            3,
+            &factor_scheduler,
+            &variable_scheduler,
+        )
+        .unwrap();
+    let mut options = MessagePassingOptions::new(&factor_scheduler, &variable_scheduler)
+        .with_max_iterations_number(500)
+        .with_min_iterations_number(5)
+        .with_threshold(1e-8)
+        .with_convergence_window(3);
+    let options_info = fg
+        .clone()
+        .run_message_passing_with_options(&mut options)
+        .unwrap();
+    assert_eq!(info.discrepancy_dynamics, options_info.discrepancy_dynamics);
+
+    // the observer interrupts message passing
+    let mut options = options.with_observer(|_, iteration, _| {
+        if iteration == 4 {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    });
+    let result = fg.clone().run_message_passing_with_options(&mut options);
+    assert!(matches!(
+        result,
+        Err(FGError::Interrupted {
+            iterations_number: 5,
+            ..
+        })
+    ));
+
+    // sampling options reproduce positional parameters
+    let info = fg
+        .clone()
+        .sample_with_pinning(
+            500,
+            0,
+            1e-8,
+            1e-6,
+            &mut StdRng::seed_from_u64(7),
+            &factor_scheduler,
+            &variable_scheduler,
+        )
+        .unwrap();
+    let mut reports_number = 0;
+    let mut options = MessagePassingOptions::new(&factor_scheduler, &variable_scheduler)
+        .with_max_iterations_number(500)
+        .with_threshold(1e-8)
+        .with_pinning_tolerance(1e-6)
+        .with_progress(|_| reports_number += 1);
+    let options_info = fg
+        .clone()
+        .sample_with_options(&mut StdRng::seed_from_u64(7), &mut options)
+        .unwrap();
+    assert_eq!(info.samples, options_info.samples);
+    assert_eq!(info.decimation_order, options_info.decimation_order);
+    assert_eq!(
+        info.total_iterations_number,
+        options_info.total_iterations_number
+    );
+    assert_eq!(
+        reports_number,
+        spins_number - options_info.pinned_variables_number
+    );
+}
+
+#[test]
+fn message_passing_observer_test() {
+    let spins_number = 50;
+    let factor_scheduler = get_standard_factor_scheduler(0.5);
+    let variable_scheduler = get_standard_variable_scheduler(0.5);
+    let fg = chain_fg(spins_number, IsingFactor::new(0.8, 0.1, -0.1), 42);
+    // an observer that never stops sees the whole run
+    let mut observed = Vec::new();
+    let mut options = MessagePassingOptions::new(&factor_scheduler, &variable_scheduler)
+        .with_observer(|_, iteration, discrepancy| {
+            observed.push((iteration, discrepancy));
+            ControlFlow::Continue(())
+        });
+    let info = fg
+        .clone()
+        .run_message_passing_with_options(&mut options)
+        .unwrap();
+    assert_eq!(observed.len(), info.iterations_number + 1);
+    for (i, ((iteration, discrepancy), expected)) in
+        observed.iter().zip(&info.discrepancy_dynamics).enumerate()
+    {
+        assert_eq!(i, *iteration);
+        assert_eq!(discrepancy, expected);
+    }
+    // an observer stopping the run once the discrepancy is small
+    let mut fg = fg;
+    let mut options = MessagePassingOptions::new(&factor_scheduler, &variable_scheduler)
+        .with_observer(|_, _, discrepancy| {
+            if discrepancy < 1e-3 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+    let result = fg.run_message_passing_with_options(&mut options);
+    match result {
+        Err(FGError::Interrupted {
+            iterations_number,
+            last_discrepancy,
+            discrepancy_dynamics,
+        }) => {
+            assert!(last_discrepancy < 1e-3);
+            assert_eq!(iterations_number, discrepancy_dynamics.len());
+            assert!(iterations_number < info.iterations_number + 1);
+        }
+        other => panic!("Message passing must be interrupted, got {other:?}"),
+    }
+}
+
+#[test]
+fn observables_test() {
+    type Graph = FactorGraph<IsingFactor<SumProduct>, IsingVariable<SumProduct>>;
+    let spins_number = 10;
+    let stride = 3;
+    let factor_scheduler = get_standard_factor_scheduler(0.5);
+    let variable_scheduler = get_standard_variable_scheduler(0.5);
+    let mut fg = ring_fg(spins_number, IsingFactor::new(0.4, 0.1, 0.), 42);
+    let parameters = factor_scheduler(0);
+    let first_spin = FnObservable::new("first_spin", |fg: &Graph| {
+        let marginal = &fg.variable_marginals()[0];
+        marginal[0] - marginal[1]
+    });
+    let energy = BetheEnergy(parameters.clone());
+    let free_entropy = BetheFreeEntropy(parameters.clone());
+    let observables: [&dyn Observable<_, _>; 4] =
+        [&Magnetization, &energy, &free_entropy, &first_spin];
+    let mut options = MessagePassingOptions::new(&factor_scheduler, &variable_scheduler)
+        .with_threshold(1e-12)
+        .with_observables(&observables, stride);
+    let info = fg.run_message_passing_with_options(&mut options).unwrap();
+    let names: Vec<_> = info.observables.iter().map(|x| x.name.as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "magnetization",
+            "bethe_energy",
+            "bethe_free_entropy",
+            "first_spin"
+        ]
+    );
+    for series in &info.observables {
+        assert_eq!(series.iterations.len(), series.values.len());
+        assert_eq!(*series.iterations.last().unwrap(), info.iterations_number);
+        assert!(series.iterations[..series.iterations.len() - 1]
+            .iter()
+            .all(|iteration| (iteration + 1) % stride == 0));
+    }
+    // final values match the converged state
+    let marginals = fg.variable_marginals();
+    let magnetization: f64 =
+        marginals.iter().map(|m| m[0] - m[1]).sum::<f64>() / spins_number as f64;
+    let last = |index: usize| *info.observables[index].values.last().unwrap();
+    assert!((last(0) - magnetization).abs() < 1e-12);
+    assert!((last(1) - fg.bethe_energy(&parameters)).abs() < 1e-12);
+    assert!((last(2) - fg.bethe_entropy(&parameters) + fg.bethe_energy(&parameters)).abs() < 1e-12);
+    // all spins are equivalent on a ring
+    assert!((last(3) - magnetization).abs() < 1e-10);
+}
+
+#[test]
+fn warm_restart_scan_test() {
+    let spins_number = 100;
+    let realizations_number = 20;
+    let error = 1e-10;
+    let factor_scheduler = get_standard_factor_scheduler(0.5);
+    let variable_scheduler = get_standard_variable_scheduler(0.5);
+    let mut rng = StdRng::seed_from_u64(42);
+    let distr = Uniform::new(-1., 1.);
+    let mut couplings: Vec<f64> = (0..(spins_number - 1)).map(|_| rng.sample(distr)).collect();
+    let build = |couplings: &[f64]| {
+        let mut initializer = random_message_initializer(StdRng::seed_from_u64(7), -0.5, 0.5);
+        let mut fgb = new_ising_builder::<SumProduct>(spins_number, spins_number - 1);
+        for (i, coupling) in couplings.iter().enumerate() {
+            fgb.add_factor(
+                IsingFactor::new(*coupling, 0.2, 0.),
+                &[i, i + 1],
+                &mut initializer,
+            )
+            .unwrap();
+        }
+        fgb.build()
+    };
+    let mut fg = build(&couplings);
+    let _ = fg
+        .run_message_passing_parallel(1000, 0, error, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    // each realization resamples one coupling
+    let mut instances = Vec::with_capacity(realizations_number);
+    let mut realizations = Vec::with_capacity(realizations_number);
+    for _ in 0..realizations_number {
+        let index = rng.sample(Uniform::new(0, spins_number - 1));
+        couplings[index] = rng.sample(distr);
+        instances.push(couplings.clone());
+        realizations.push(vec![(index, IsingFactor::new(couplings[index], 0.2, 0.))]);
+    }
+    let warm_results = fg
+        .scan_realizations(
+            realizations,
+            1000,
+            0,
+            error,
+            &factor_scheduler,
+            &variable_scheduler,
+            |fg, result| (result.unwrap().iterations_number, fg.variable_marginals()),
+        )
+        .unwrap();
+    let mut warm_iterations = 0;
+    let mut cold_iterations = 0;
+    for (instance, (iterations_number, marginals)) in instances.iter().zip(warm_results) {
+        let mut cold_fg = build(instance);
+        let info = cold_fg
+            .run_message_passing_parallel(1000, 0, error, &factor_scheduler, &variable_scheduler)
+            .unwrap();
+        for (warm, cold) in marginals.iter().zip(cold_fg.variable_marginals()) {
+            assert!((warm[0] - cold[0]).abs() < 1e-8);
+        }
+        warm_iterations += iterations_number;
+        cold_iterations += info.iterations_number;
+    }
+    assert!(
+        warm_iterations < cold_iterations,
+        "warm: {warm_iterations}, cold: {cold_iterations}"
+    );
+}
+
+#[test]
+fn annealing_groups_test() {
+    let spins_number = 40;
+    let couplings_number = 80;
+    let betas = [0.7, 1.5, 0.3];
+    let mut rng = StdRng::seed_from_u64(42);
+    let distr = Uniform::new(-0.4, 0.4);
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(7), -0.5, 0.5);
+    let mut fgb = new_ising_builder::<SumProduct>(spins_number, couplings_number + 1);
+    // the reference model has factors explicitly multiplied by inverse temperatures
+    let mut reference_fgb = new_ising_builder::<SumProduct>(spins_number, couplings_number + 1);
+    for c in 0..couplings_number {
+        let lhs = rng.sample(Uniform::new(0, spins_number));
+        let rhs = (lhs + rng.sample(Uniform::new(1, spins_number))) % spins_number;
+        let (coupling, first_b, second_b) =
+            (rng.sample(distr), rng.sample(distr), rng.sample(distr));
+        let group = c % betas.len();
+        let beta = betas[group];
+        fgb.add_factor(
+            IsingFactor::new(coupling, first_b, second_b).with_annealing_group(group),
+            &[lhs, rhs],
+            &mut initializer,
+        )
+        .unwrap();
+        reference_fgb
+            .add_factor(
+                IsingFactor::new(beta * coupling, beta * first_b, beta * second_b),
+                &[lhs, rhs],
+                &mut initializer,
+            )
+            .unwrap();
+    }
+    // unit factors do not depend on inverse temperature
+    fgb.add_factor(IsingFactor::UnitFactor(0.7), &[3], &mut initializer)
+        .unwrap();
+    reference_fgb
+        .add_factor(IsingFactor::UnitFactor(0.7), &[3], &mut initializer)
+        .unwrap();
+    let fg = fgb.build();
+    let mut reference_fg = reference_fgb.build();
+    let factor_scheduler =
+        |_| IsingFactorHyperParameters::new(betas[0], 0.2).with_group_betas(betas[1..].to_vec());
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    reference_fg
+        .run_message_passing_parallel(
+            1000,
+            0,
+            1e-12,
+            &|_| IsingFactorHyperParameters::new(1., 0.2),
+            &variable_scheduler,
+        )
+        .unwrap();
+    let reference_marginals = reference_fg.variable_marginals();
+    let assert_close = |marginals: Vec<ndarray::Array1<f64>>| {
+        for (lhs, rhs) in marginals.iter().zip(&reference_marginals) {
+            assert!((lhs[0] - rhs[0]).abs() < 1e-9, "{} != {}", lhs[0], rhs[0]);
+        }
+    };
+    let mut grouped_fg = fg.clone();
+    grouped_fg
+        .run_message_passing_parallel(1000, 0, 1e-12, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    assert_close(grouped_fg.variable_marginals());
+    let mut batched_fg = fg.clone();
+    batched_fg
+        .run_message_passing_batched(1000, 0, 1e-12, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    assert_close(batched_fg.variable_marginals());
+    #[cfg(feature = "parallel")]
+    {
+        let mut edge_fg = fg.clone();
+        edge_fg
+            .run_message_passing_edge_parallel(
+                1000,
+                0,
+                1e-12,
+                &factor_scheduler,
+                &variable_scheduler,
+            )
+            .unwrap();
+        assert_close(edge_fg.variable_marginals());
+    }
+    // groups annealed on different trajectories end at their final temperatures
+    let beta_ranges: Vec<_> = betas.iter().map(|beta| (0.01, *beta)).collect();
+    let annealing_scheduler = get_grouped_exponential_factor_scheduler(&beta_ranges, 50, 0.2);
+    let final_scheduler = |i: usize| annealing_scheduler(i.min(50));
+    let mut annealed_fg = fg.clone();
+    annealed_fg
+        .run_message_passing_parallel(1000, 51, 1e-12, &final_scheduler, &variable_scheduler)
+        .unwrap();
+    assert_close(annealed_fg.variable_marginals());
+}
+
+type Graph = FactorGraph<IsingFactor<SumProduct>, IsingVariable<SumProduct>>;
+
+// Compares cached marginals with ones computed from messages directly
+fn assert_fresh(fg: &Graph) {
+    let marginals = fg.variable_marginals();
+    assert_eq!(marginals.len(), fg.variables.len());
+    for (marginal, node) in marginals.iter().zip(&fg.variables) {
+        assert!(node.marginal.get().is_some());
+        let fresh = node.variable.marginal(&node.receivers);
+        assert!((marginal - &fresh).mapv(f64::abs).sum() < 1e-12);
+    }
+}
+
+#[test]
+fn marginal_cache_test() {
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(42), -0.5, 0.5);
+    let mut fgb = new_ising_builder::<SumProduct>(5, 6);
+    for i in 0..5 {
+        fgb.add_factor(
+            IsingFactor::new(0.3, 0.1 * i as f64, -0.05),
+            &[i, (i + 1) % 5],
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    let mut fg = fgb.build();
+    let run = |fg: &mut Graph| {
+        fg.run_message_passing_parallel(1000, 0, 1e-12, &factor_scheduler, &variable_scheduler)
+            .unwrap();
+    };
+    run(&mut fg);
+    assert_fresh(&fg);
+    // untouched variables keep their cached marginals
+    let snapshot = fg.snapshot();
+    fg.freeze_variable(&1, 2).unwrap();
+    assert!(fg.variables[2].marginal.get().is_none());
+    assert!(fg.variables[0].marginal.get().is_some());
+    assert_fresh(&fg);
+    run(&mut fg);
+    assert_fresh(&fg);
+    assert!((fg.variable_marginals()[2][0] - 1.).abs() < 1e-12);
+    fg.rollback(&snapshot).unwrap();
+    assert_fresh(&fg);
+    fg.add_factor(IsingFactor::UnitFactor(0.4), &[4], &mut initializer)
+        .unwrap();
+    assert_fresh(&fg);
+    run(&mut fg);
+    assert_fresh(&fg);
+    fg.contract_variables(0, 1, true).unwrap();
+    assert_fresh(&fg);
+    run(&mut fg);
+    assert_fresh(&fg);
+}
+
+#[test]
+fn streaming_test() {
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(42), -0.5, 0.5);
+    // a slowly converging ring of strongly coupled spins and weakly coupled pairs
+    let ring_size = 30;
+    let pairs_number = 10;
+    let spins_number = ring_size + 2 * pairs_number;
+    let mut fgb = new_ising_builder::<SumProduct>(spins_number, ring_size + pairs_number);
+    for i in 0..ring_size {
+        fgb.add_factor(
+            IsingFactor::new(1., 0.01, 0.),
+            &[i, (i + 1) % ring_size],
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    for i in (ring_size..spins_number).step_by(2) {
+        fgb.add_factor(
+            IsingFactor::new(0.2, 0.1, -0.1),
+            &[i, i + 1],
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    let fg = fgb.build();
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+
+    let mut streamed_fg = fg.clone();
+    let mut emitted: Vec<StreamedMarginal<Array1<f64>>> = Vec::new();
+    let info = streamed_fg
+        .run_message_passing_parallel_streaming(
+            10000,
+            0,
+            1e-10,
+            1e-10,
+            3,
+            &factor_scheduler,
+            &variable_scheduler,
+            |streamed| emitted.push(streamed),
+        )
+        .unwrap();
+    // each variable is emitted once
+    let mut variables: Vec<_> = emitted.iter().map(|streamed| streamed.variable).collect();
+    variables.sort_unstable();
+    assert_eq!(variables, (0..spins_number).collect::<Vec<_>>());
+    // pairs are emitted long before the ring converges
+    let marginals = streamed_fg.variable_marginals();
+    for streamed in &emitted {
+        assert!(streamed.iteration <= info.iterations_number);
+        if streamed.variable >= ring_size {
+            assert!(streamed.iteration < info.iterations_number / 2);
+        }
+        let error = &streamed.marginal - &marginals[streamed.variable];
+        assert!(error.iter().all(|x| x.abs() < 1e-8));
+    }
+    assert_eq!(
+        info.discrepancy_dynamics,
+        fg.clone()
+            .run_message_passing_parallel(10000, 0, 1e-10, &factor_scheduler, &variable_scheduler)
+            .unwrap()
+            .discrepancy_dynamics
+    );
+
+    // a failed run emits stable variables only
+    let mut emitted_variables = Vec::new();
+    let result = fg.clone().run_message_passing_parallel_streaming(
+        info.iterations_number / 2,
+        0,
+        1e-10,
+        1e-10,
+        3,
+        &factor_scheduler,
+        &variable_scheduler,
+        |streamed| emitted_variables.push(streamed.variable),
+    );
+    assert!(result.is_err());
+    emitted_variables.sort_unstable();
+    assert_eq!(
+        emitted_variables,
+        (ring_size..spins_number).collect::<Vec<_>>()
+    );
+}
+
+// Numbers of calls of the fixed degree path per degree
+static FIXED_CALLS: [AtomicUsize; 3] = [
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+];
+
+// Number of calls of the slice path of a factor that does not override the fixed degree path
+static SLICE_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+// An Ising factor counting calls of the fixed degree path
+#[derive(Debug, Clone)]
+struct CountingFactor(IsingFactor<SumProduct>);
+
+impl Factor for CountingFactor {
+    type Message = IsingMessage;
+    type Marginal = ArrayD<f64>;
+    type Parameters = IsingFactorHyperParameters;
+
+    fn from_message(message: &Self::Message) -> Self {
+        CountingFactor(IsingFactor::from_message(message))
+    }
+
+    fn degree(&self) -> usize {
+        self.0.degree()
+    }
+
+    fn send_messages(&self, _: &[Self::Message], _: &mut [Self::Message], _: &Self::Parameters) {
+        unreachable!("factors of degree 1 and 2 take the fixed degree path")
+    }
+
+    fn send_messages_fixed<const N: usize>(
+        &self,
+        src: &[Self::Message; N],
+        dst: &mut [Self::Message; N],
+        parameters: &Self::Parameters,
+    ) {
+        FIXED_CALLS[N].fetch_add(1, Ordering::SeqCst);
+        self.0.send_messages_fixed(src, dst, parameters)
+    }
+
+    fn marginal(&self, messages: &[Self::Message]) -> Self::Marginal {
+        self.0.marginal(messages)
+    }
+
+    fn factor(&self) -> Self::Marginal {
+        self.0.factor()
+    }
+}
+
+// An Ising factor that keeps the default fixed degree path, thus it runs
+// the checked slice kernel of an Ising factor
+#[derive(Debug, Clone)]
+struct SliceFactor(IsingFactor<SumProduct>);
+
+impl Factor for SliceFactor {
+    type Message = IsingMessage;
+    type Marginal = ArrayD<f64>;
+    type Parameters = IsingFactorHyperParameters;
+
+    fn from_message(message: &Self::Message) -> Self {
+        SliceFactor(IsingFactor::from_message(message))
+    }
+
+    fn degree(&self) -> usize {
+        self.0.degree()
+    }
+
+    fn send_messages(
+        &self,
+        src: &[Self::Message],
+        dst: &mut [Self::Message],
+        parameters: &Self::Parameters,
+    ) {
+        SLICE_CALLS.fetch_add(1, Ordering::SeqCst);
+        self.0.send_messages(src, dst, parameters)
+    }
+
+    fn marginal(&self, messages: &[Self::Message]) -> Self::Marginal {
+        self.0.marginal(messages)
+    }
+
+    fn factor(&self) -> Self::Marginal {
+        self.0.factor()
+    }
+}
+
+// Builds a frustrated ring with a field on the first spin from wrapped Ising factors
+fn wrapped_ring_fg<F>(
+    spins_number: usize,
+    wrap: impl Fn(IsingFactor<SumProduct>) -> F,
+) -> FactorGraph<F, IsingVariable<SumProduct>>
+where
+    F: Factor<Message = IsingMessage>,
+{
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(42), -0.5, 0.5);
+    let mut fgb = FactorGraphBuilder::<F, IsingVariable<SumProduct>>::new_with_variables(
+        vec![IsingVariable::new(); spins_number],
+        spins_number + 1,
+    );
+    for i in 0..spins_number {
+        let factor = IsingFactor::new(0.4, 0.1, -0.2);
+        fgb.add_factor(wrap(factor), &[i, (i + 1) % spins_number], &mut initializer)
+            .unwrap();
+    }
+    fgb.add_factor(wrap(IsingFactor::UnitFactor(0.5)), &[0], &mut initializer)
+        .unwrap();
+    fgb.build()
+}
+
+#[test]
+fn fixed_degree_path_test() {
+    let spins_number = 20;
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    let mut fg = wrapped_ring_fg(spins_number, CountingFactor);
+    let mut slice_fg = wrapped_ring_fg(spins_number, SliceFactor);
+    let info = fg
+        .run_message_passing_parallel(1000, 0, 1e-10, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    let slice_info = slice_fg
+        .run_message_passing_parallel(1000, 0, 1e-10, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    // the array kernel and the slice kernel of an Ising factor are the same update rule
+    assert_eq!(info.iterations_number, slice_info.iterations_number);
+    for (lhs, rhs) in fg
+        .variable_marginals()
+        .iter()
+        .zip(slice_fg.variable_marginals())
+    {
+        assert_eq!(lhs, rhs);
+    }
+    // every factor update of a graph with degrees 1 and 2 takes the fixed degree path
+    let sweeps_number = info.iterations_number + 1;
+    assert_eq!(FIXED_CALLS[1].load(Ordering::SeqCst), sweeps_number);
+    assert_eq!(
+        FIXED_CALLS[2].load(Ordering::SeqCst),
+        spins_number * sweeps_number
+    );
+    assert_eq!(
+        SLICE_CALLS.load(Ordering::SeqCst),
+        (spins_number + 1) * sweeps_number
+    );
+}
+
+fn check_ising_kernels<T>(rng: &mut impl Rng)
+where
+    T: IsingMessagePassingType + Clone + Debug + Send,
+{
+    let parameters = IsingFactorHyperParameters::new(0.7, 0.3).with_group_betas(vec![2.]);
+    for group in [0, 1] {
+        let factor = IsingFactor::<T>::new(rng.gen_range(-1f64..1f64), 0.2, -0.3)
+            .with_annealing_group(group);
+        let src = [
+            IsingMessage(rng.gen_range(-2f64..2f64)),
+            IsingMessage(rng.gen_range(-2f64..2f64)),
+        ];
+        let prev = [
+            IsingMessage(rng.gen_range(-2f64..2f64)),
+            IsingMessage(rng.gen_range(-2f64..2f64)),
+        ];
+        let mut fixed_dst = prev;
+        let mut slice_dst = prev;
+        factor.send_messages_fixed(&src, &mut fixed_dst, &parameters);
+        factor.send_messages(&src, &mut slice_dst, &parameters);
+        assert_eq!(fixed_dst.map(|m| m.0), slice_dst.map(|m| m.0));
+        assert_ne!(fixed_dst.map(|m| m.0), prev.map(|m| m.0));
+    }
+    let unit = IsingFactor::<T>::UnitFactor(0.25);
+    let mut fixed_dst = [IsingMessage(0.)];
+    let mut slice_dst = [IsingMessage(0.)];
+    unit.send_messages_fixed(&[IsingMessage(1.)], &mut fixed_dst, &parameters);
+    unit.send_messages(&[IsingMessage(1.)], &mut slice_dst, &parameters);
+    assert_eq!(fixed_dst[0].0, 0.25);
+    assert_eq!(slice_dst[0].0, 0.25);
+}
+
+#[test]
+fn ising_fixed_degree_kernels_test() {
+    let mut rng = StdRng::seed_from_u64(42);
+    for _ in 0..10 {
+        check_ising_kernels::<SumProduct>(&mut rng);
+        check_ising_kernels::<MaxProduct>(&mut rng);
+    }
+}
+
+#[test]
+#[should_panic]
+fn ising_slice_kernel_checks_degree_test() {
+    let factor = IsingFactor::<SumProduct>::new(1., 0., 0.);
+    let src = [IsingMessage(0.); 3];
+    let mut dst = [IsingMessage(0.); 3];
+    factor.send_messages(&src, &mut dst, &get_standard_factor_scheduler(0.)(0));
+}
+
+fn check_lane_kernel<T>(rng: &mut impl Rng)
+where
+    T: IsingMessagePassingType,
+{
+    let special = [
+        0.,
+        -0.,
+        1e-300,
+        800.,
+        -800.,
+        f64::INFINITY,
+        f64::NEG_INFINITY,
+        f64::NAN,
+    ];
+    let mut random_lanes = |scale: f64| -> Lanes {
+        let mut lanes = [0f64; LANES];
+        for lane in &mut lanes {
+            *lane = if rng.gen_bool(0.2) {
+                special[rng.gen_range(0..special.len())]
+            } else {
+                rng.gen_range(-scale..scale)
+            };
+        }
+        lanes
+    };
+    let messages = random_lanes(30.);
+    let prev_messages = random_lanes(30.);
+    let log_p = [
+        random_lanes(5.),
+        random_lanes(5.),
+        random_lanes(5.),
+        random_lanes(5.),
+    ];
+    let betas = random_lanes(3.);
+    let gamma = rng.gen_range(0f64..1f64);
+    let new_messages =
+        T::factor_message_update_lanes(&messages, &prev_messages, &log_p, &betas, gamma);
+    for k in 0..LANES {
+        let reference = T::factor_message_update(
+            IsingMessage(messages[k]),
+            IsingMessage(prev_messages[k]),
+            log_p[0][k],
+            log_p[1][k],
+            log_p[2][k],
+            log_p[3][k],
+            &IsingFactorHyperParameters::new(betas[k], gamma),
+        )
+        .0;
+        let message = new_messages[k];
+        assert!(
+            message == reference
+                || (message.is_nan() && reference.is_nan())
+                || (message - reference).abs() <= 1e-13 * reference.abs().max(1.),
+            "{message} != {reference}"
+        );
+    }
+}
+
+#[test]
+fn lane_kernel_test() {
+    let mut rng = StdRng::seed_from_u64(42);
+    for _ in 0..1000 {
+        check_lane_kernel::<SumProduct>(&mut rng);
+        check_lane_kernel::<MaxProduct>(&mut rng);
+    }
+}
+
+#[test]
+fn batched_kernel_test() {
+    let spins_number = 50;
+    let couplings_number = 123;
+    let mut rng = StdRng::seed_from_u64(42);
+    let distr = Uniform::new(-0.3, 0.3);
+    let variables = (0..spins_number).map(|i| {
+        let variable = IsingVariable::<SumProduct>::new().with_field(rng.sample(distr));
+        if i % 3 == 0 {
+            variable.with_damping(0.2)
+        } else {
+            variable
+        }
+    });
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(7), -0.5, 0.5);
+    let mut fgb = FactorGraphBuilder::new_with_variables(variables, couplings_number + 2);
+    for _ in 0..couplings_number {
+        let lhs = rng.sample(Uniform::new(0, spins_number));
+        let rhs = (lhs + rng.sample(Uniform::new(1, spins_number))) % spins_number;
+        fgb.add_factor(
+            IsingFactor::new(rng.sample(distr), rng.sample(distr), rng.sample(distr)),
+            &[lhs, rhs],
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    fgb.add_factor(IsingFactor::UnitFactor(0.7), &[3], &mut initializer)
+        .unwrap();
+    fgb.add_factor(IsingFactor::UnitFactor(-1.2), &[10], &mut initializer)
+        .unwrap();
+    let mut fg = fgb.build();
+    let mut reference_fg = fg.clone();
+    let factor_scheduler = |_| IsingFactorHyperParameters::new(0.8, 0.3);
+    let variable_scheduler = get_standard_variable_scheduler(0.1);
+    let info = fg
+        .run_message_passing_batched(1000, 5, 1e-10, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    let reference_info = reference_fg
+        .run_message_passing_parallel(1000, 5, 1e-10, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    assert_eq!(info.iterations_number, reference_info.iterations_number);
+    for (lhs, rhs) in fg
+        .variable_marginals()
+        .iter()
+        .zip(reference_fg.variable_marginals())
+    {
+        assert!((lhs[0] - rhs[0]).abs() < 1e-10);
+    }
+    for (lhs, rhs) in fg
+        .factor_marginals()
+        .iter()
+        .zip(reference_fg.factor_marginals())
+    {
+        for (l, r) in lhs.iter().zip(rhs.iter()) {
+            assert!((l - r).abs() < 1e-10);
+        }
+    }
+    // a non-converged run writes messages back as well
+    let mut fg = reference_fg.clone();
+    let err = fg.run_message_passing_batched(2, 0, 0., &factor_scheduler, &variable_scheduler);
+    assert!(err.is_err());
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn edge_parallel_kernel_test() {
+    let spins_number = 200;
+    let mut rng = StdRng::seed_from_u64(42);
+    let distr = Uniform::new(-0.2, 0.2);
+    let variables =
+        (0..spins_number).map(|_| IsingVariable::<SumProduct>::new().with_field(rng.sample(distr)));
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(7), -0.5, 0.5);
+    let mut fgb = FactorGraphBuilder::new_with_variables(variables, 2 * spins_number);
+    // preferential attachment produces a few hubs with most of the edges,
+    // it may also attach a spin to itself
+    fgb.set_multi_edges_allowed(true);
+    let mut ends = vec![0, 1];
+    fgb.add_factor(IsingFactor::new(0.1, 0., 0.), &[0, 1], &mut initializer)
+        .unwrap();
+    for spin in 2..spins_number {
+        for _ in 0..2 {
+            let target = ends[rng.sample(Uniform::new(0, ends.len()))];
+            fgb.add_factor(
+                IsingFactor::new(rng.sample(distr), 0., 0.),
+                &[spin, target],
+                &mut initializer,
+            )
+            .unwrap();
+            ends.extend([spin, target]);
+        }
+    }
+    fgb.add_factor(IsingFactor::UnitFactor(-0.4), &[0], &mut initializer)
+        .unwrap();
+    let mut fg = fgb.build();
+    let mut reference_fg = fg.clone();
+    let factor_scheduler = |_| IsingFactorHyperParameters::new(0.9, 0.2);
+    let variable_scheduler = get_standard_variable_scheduler(0.1);
+    let info = fg
+        .run_message_passing_edge_parallel(1000, 0, 1e-10, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    let reference_info = reference_fg
+        .run_message_passing_parallel(1000, 0, 1e-10, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    assert_eq!(info.iterations_number, reference_info.iterations_number);
+    assert_eq!(info.certificate, reference_info.certificate);
+    for (lhs, rhs) in fg
+        .variable_marginals()
+        .iter()
+        .zip(reference_fg.variable_marginals())
+    {
+        assert!((lhs[0] - rhs[0]).abs() < 1e-10);
+    }
+}
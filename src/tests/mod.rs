@@ -1,6 +1,130 @@
+#[cfg(not(feature = "parallel"))]
+mod allocations_test;
+mod analysis_test;
 mod curie_weiss_test;
+#[cfg(feature = "ep")]
+mod ep_test;
+mod estimates_test;
+mod exact_test;
 mod factor_graph_builder_tests;
+#[cfg(feature = "io")]
+mod io_test;
 mod ising_1d_sum_product;
 mod ising_2d_sum_product;
 mod ising_tree_test;
 mod ising_utils;
+#[cfg(feature = "mcmc")]
+mod mcmc_test;
+mod message_passing_test;
+mod models_test;
+mod monitoring_test;
+#[cfg(feature = "parallel")]
+mod parallel_test;
+mod sampling_test;
+#[cfg(feature = "tracing")]
+mod tracing_test;
+
+use crate::core::FactorGraph;
+use crate::ising::{
+    new_ising_builder, random_message_initializer, IsingFactor, IsingVariable, SumProduct,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+// A sum-product Ising factor graph used by most of the tests
+type IsingGraph = FactorGraph<IsingFactor<SumProduct>, IsingVariable<SumProduct>>;
+
+// Builds an Ising graph from couplings given by a list of edges, messages are initialized
+// uniformly at random in [-0.5, 0.5] by a generator with a given seed
+fn edges_fg(
+    spins_number: usize,
+    edges: impl IntoIterator<Item = [usize; 2]>,
+    factor: IsingFactor<SumProduct>,
+    seed: u64,
+) -> IsingGraph {
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(seed), -0.5, 0.5);
+    let mut fgb = new_ising_builder::<SumProduct>(spins_number, 2 * spins_number);
+    for edge in edges {
+        fgb.add_factor(factor, &edge, &mut initializer).unwrap();
+    }
+    fgb.build()
+}
+
+// An open chain of spins with the same coupling factor between neighbours
+fn chain_fg(spins_number: usize, factor: IsingFactor<SumProduct>, seed: u64) -> IsingGraph {
+    let edges = (0..(spins_number - 1)).map(|i| [i, i + 1]);
+    edges_fg(spins_number, edges, factor, seed)
+}
+
+// A ring of spins with the same coupling factor between neighbours
+fn ring_fg(spins_number: usize, factor: IsingFactor<SumProduct>, seed: u64) -> IsingGraph {
+    let edges = (0..spins_number).map(|i| [i, (i + 1) % spins_number]);
+    edges_fg(spins_number, edges, factor, seed)
+}
+
+// A square lattice with periodic boundary conditions, each spin is coupled
+// to its right neighbour first and to its bottom neighbour next
+fn torus_fg(side: usize, factor: IsingFactor<SumProduct>, seed: u64) -> IsingGraph {
+    let edges = (0..side * side).flat_map(|spin| {
+        let (i, j) = (spin / side, spin % side);
+        [
+            [spin, i * side + (j + 1) % side],
+            [spin, ((i + 1) % side) * side + j],
+        ]
+    });
+    edges_fg(side * side, edges, factor, seed)
+}
+
+// Random couplings in [-1, 1] of a random tree of spins and random fields in [-0.5, 0.5]
+fn random_tree(rng: &mut impl Rng, spins_number: usize) -> (Vec<(usize, usize, f64)>, Vec<f64>) {
+    let couplings = (1..spins_number)
+        .map(|i| (rng.gen_range(0..i), i, rng.gen_range(-1f64..1f64)))
+        .collect();
+    let fields = (0..spins_number)
+        .map(|_| rng.gen_range(-0.5f64..0.5f64))
+        .collect();
+    (couplings, fields)
+}
+
+// Builds an Ising graph whose probability is proportional to exp(sum J_ij s_i s_j + sum b_i s_i),
+// couplings are followed by unit factors of fields. Messages are initialized as in `edges_fg`
+fn ising_fg(couplings: &[(usize, usize, f64)], fields: &[f64], seed: u64) -> IsingGraph {
+    let spins_number = fields.len();
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(seed), -0.5, 0.5);
+    let mut fgb = new_ising_builder::<SumProduct>(spins_number, 2 * spins_number);
+    for (i, j, coupling) in couplings {
+        fgb.add_factor(
+            IsingFactor::new(*coupling, 0., 0.),
+            &[*i, *j],
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    for (i, field) in fields.iter().enumerate() {
+        fgb.add_factor(IsingFactor::UnitFactor(2. * field), &[i], &mut initializer)
+            .unwrap();
+    }
+    fgb.build()
+}
+
+// Spins of a configuration given by bits of an integer, a zero bit is a spin up
+fn config_spins(configuration: usize, spins_number: usize) -> Vec<f64> {
+    (0..spins_number)
+        .map(|i| {
+            if (configuration >> i) & 1 == 0 {
+                1.
+            } else {
+                -1.
+            }
+        })
+        .collect()
+}
+
+// Logarithm of an unnormalized probability of a spins configuration
+fn ising_log_weight(couplings: &[(usize, usize, f64)], fields: &[f64], spins: &[f64]) -> f64 {
+    let coupling_part: f64 = couplings
+        .iter()
+        .map(|(i, j, coupling)| coupling * spins[*i] * spins[*j])
+        .sum();
+    let field_part: f64 = fields.iter().zip(spins).map(|(b, s)| b * s).sum();
+    coupling_part + field_part
+}
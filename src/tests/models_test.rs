@@ -0,0 +1,1272 @@
+use std::collections::HashSet;
+use std::ops::ControlFlow;
+
+use crate::core::{
+    Annotated, FGBuilderError, FGError, Factor, FactorGraph, FactorGraphBuilder, LabeledBuilder,
+    MessagePassingOptions,
+};
+use crate::factor_enum;
+use crate::ising::ensembles::{
+    diluted_spin_glass, edwards_anderson, sherrington_kirkpatrick, SpinGlass,
+};
+use crate::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+use crate::ising::{
+    new_ising_builder, random_message_initializer, ConstraintFactor, IsingFactor,
+    IsingFactorHyperParameters, IsingMessage, IsingVariable, LogicalConstraint, MaxProduct,
+    RestrictedBoltzmannMachine, SumProduct,
+};
+use crate::tabular::{
+    uninformative_message_initializer, ConditionalTabularFactor, CycleRemoval, LdpcCode,
+    TabularFactor, TabularVariable,
+};
+use ndarray::{array, Array1, Array2, ArrayD};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rand_distr::Uniform;
+
+factor_enum! {
+    enum SumProductFactor {
+        Field(IsingFactor<SumProduct>),
+        Constraint(ConstraintFactor<SumProduct>),
+    }
+}
+
+factor_enum! {
+    enum MaxProductFactor {
+        Field(IsingFactor<MaxProduct>),
+        Constraint(ConstraintFactor<MaxProduct>),
+    }
+}
+
+// Constraints of a tree factor graph over 8 variables and their variables
+fn tree_constraints() -> Vec<(LogicalConstraint, Vec<bool>, Vec<usize>)> {
+    vec![
+        (
+            LogicalConstraint::Xor { parity: true },
+            vec![false, false, true],
+            vec![0, 1, 2],
+        ),
+        (
+            LogicalConstraint::Or,
+            vec![false, true, false],
+            vec![2, 3, 4],
+        ),
+        (LogicalConstraint::AtMostOne, vec![false; 3], vec![4, 5, 6]),
+        (LogicalConstraint::And, vec![false, true], vec![1, 7]),
+    ]
+}
+
+// Spins of an assignment of variables given by bits of an integer
+fn spins(assignment: usize, spins_number: usize) -> Vec<i8> {
+    (0..spins_number)
+        .map(|i| if (assignment >> i) & 1 == 1 { 1 } else { -1 })
+        .collect()
+}
+
+#[test]
+fn constraints_on_tree_test() {
+    let spins_number = 8;
+    let mut rng = StdRng::seed_from_u64(42);
+    let fields: Vec<f64> = (0..spins_number)
+        .map(|_| rng.gen_range(-1f64..1f64))
+        .collect();
+    let constraints: Vec<_> = tree_constraints()
+        .into_iter()
+        .map(|(constraint, negations, variables)| {
+            let factor = ConstraintFactor::<SumProduct>::new(constraint, variables.len())
+                .with_negations(&negations);
+            (factor, variables)
+        })
+        .collect();
+    // exact marginals and the most probable assignment by enumeration
+    let mut weights = vec![0f64; spins_number];
+    let mut partition_function = 0f64;
+    let mut best = (f64::NEG_INFINITY, 0);
+    for assignment in 0..(1 << spins_number) {
+        let spins = spins(assignment, spins_number);
+        let is_satisfied = constraints.iter().all(|(factor, variables)| {
+            let spins: Vec<_> = variables.iter().map(|i| spins[*i]).collect();
+            factor.is_satisfied(&spins)
+        });
+        if !is_satisfied {
+            continue;
+        }
+        let log_weight: f64 = fields.iter().zip(&spins).map(|(b, s)| b * *s as f64).sum();
+        partition_function += log_weight.exp();
+        for (weight, spin) in weights.iter_mut().zip(&spins) {
+            if *spin == 1 {
+                *weight += log_weight.exp();
+            }
+        }
+        if log_weight > best.0 {
+            best = (log_weight, assignment);
+        }
+    }
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(7), -0.5, 0.5);
+    // sum-product marginals are exact on a tree
+    let mut fgb =
+        FactorGraphBuilder::<SumProductFactor, IsingVariable<SumProduct>>::new_with_variables(
+            vec![IsingVariable::new(); spins_number],
+            2 * spins_number,
+        );
+    for (factor, variables) in &constraints {
+        fgb.add_factor(factor.clone().into(), variables, &mut initializer)
+            .unwrap();
+    }
+    for (i, field) in fields.iter().enumerate() {
+        fgb.add_factor(
+            IsingFactor::UnitFactor(2. * field).into(),
+            &[i],
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    let mut fg = fgb.build();
+    fg.run_message_passing_parallel(100, 0, 1e-10, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    for (marginal, weight) in fg.variable_marginals().iter().zip(&weights) {
+        assert!((marginal[0] - weight / partition_function).abs() < 1e-10);
+    }
+    // factor marginals are consistent with variable marginals
+    let marginals = fg.factor_marginals();
+    assert!((marginals[3].sum() - 1.).abs() < 1e-10);
+    assert!((marginals[3][[0, 1]] - 1.).abs() < 1e-10);
+    // max-product decoding finds the most probable assignment
+    let mut fgb =
+        FactorGraphBuilder::<MaxProductFactor, IsingVariable<MaxProduct>>::new_with_variables(
+            vec![IsingVariable::new(); spins_number],
+            2 * spins_number,
+        );
+    for (factor, variables) in &constraints {
+        let factor = ConstraintFactor::<MaxProduct>::new(factor.constraint(), variables.len())
+            .with_negations(factor.negations());
+        fgb.add_factor(factor.into(), variables, &mut initializer)
+            .unwrap();
+    }
+    for (i, field) in fields.iter().enumerate() {
+        fgb.add_factor(
+            IsingFactor::UnitFactor(2. * field).into(),
+            &[i],
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    let mut fg = fgb.build();
+    fg.run_message_passing_parallel(100, 0, 1e-10, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    assert_eq!(
+        fg.map_assignment(&factor_scheduler(0)),
+        spins(best.1, spins_number)
+    );
+}
+
+#[test]
+fn infinite_messages_test() {
+    let parameters = get_standard_factor_scheduler(0.)(0);
+    let inf = f64::INFINITY;
+    // x_0 xor x_1 xor x_2 = 0 with known x_0 and x_1
+    let xor = ConstraintFactor::<SumProduct>::new(LogicalConstraint::Xor { parity: false }, 3);
+    let src = [IsingMessage(inf), IsingMessage(-inf), IsingMessage(0.)];
+    let mut dst = [IsingMessage(0.); 3];
+    xor.send_messages(&src, &mut dst, &parameters);
+    assert_eq!(dst[2].0, 1e30);
+    assert_eq!(dst[0].0, 0.);
+    assert!(!xor.is_contradictory(&src));
+    // at most one of x_0, x_1 and x_2 with x_0 true forces others to be false
+    let at_most_one = ConstraintFactor::<SumProduct>::new(LogicalConstraint::AtMostOne, 3);
+    let src = [IsingMessage(inf), IsingMessage(0.5), IsingMessage(-0.5)];
+    at_most_one.send_messages(&src, &mut dst, &parameters);
+    assert_eq!(dst[1].0, -1e30);
+    assert_eq!(dst[2].0, -1e30);
+    // x_0 is true only if others are false, p(x_1) p(x_2) = p(not x_1) p(not x_2)
+    let both = 1. / (2. + f64::exp(0.5) + f64::exp(-0.5));
+    assert!((dst[0].0 - f64::ln(both / (1. - both))).abs() < 1e-12);
+    // a clause with all literals false is contradictory, messages stay finite
+    let clause = ConstraintFactor::<SumProduct>::new(LogicalConstraint::Or, 2)
+        .with_negations(&[false, true]);
+    let src = [IsingMessage(-inf), IsingMessage(1e30)];
+    clause.send_messages(&src, &mut dst[..2], &parameters);
+    assert_eq!(dst[0].0, 1e30);
+    assert_eq!(dst[1].0, -1e30);
+    assert!(clause.is_contradictory(&src));
+    assert!(clause.marginal(&src).iter().all(|p| p.is_finite()));
+    let src = [IsingMessage(-inf), IsingMessage(-inf)];
+    assert!(!clause.is_contradictory(&src));
+    // a unit constraint forces the sign of a message
+    let unit = ConstraintFactor::<MaxProduct>::from_message(&IsingMessage(-0.1));
+    unit.send_messages(&[IsingMessage(inf)], &mut dst[..1], &parameters);
+    assert_eq!(dst[0].0, -1e30);
+}
+
+// Returns degrees of code bits
+fn variable_degrees(code: &LdpcCode) -> Vec<usize> {
+    let mut degrees = vec![0; code.variables_number()];
+    for check in code.checks() {
+        for variable in check {
+            degrees[*variable] += 1;
+        }
+    }
+    degrees
+}
+
+// Returns whether checks of a code share at most one code bit
+fn has_girth_six(code: &LdpcCode) -> bool {
+    let checks: Vec<HashSet<_>> = code
+        .checks()
+        .iter()
+        .map(|check| check.iter().collect())
+        .collect();
+    checks.iter().enumerate().all(|(index, lhs)| {
+        checks[(index + 1)..]
+            .iter()
+            .all(|rhs| lhs.intersection(rhs).count() <= 1)
+    })
+}
+
+#[test]
+fn ldpc_test() {
+    let mut rng = StdRng::seed_from_u64(42);
+    // regular ensembles
+    assert!(LdpcCode::regular(10, 3, 4, CycleRemoval::None, &mut rng).is_none());
+    assert!(LdpcCode::regular(10, 3, 0, CycleRemoval::None, &mut rng).is_none());
+    let code = LdpcCode::regular(200, 3, 6, CycleRemoval::FourCycles, &mut rng).unwrap();
+    assert_eq!(code.checks_number(), 100);
+    assert!(code.checks().iter().all(|check| check.len() == 6));
+    assert_eq!(variable_degrees(&code), vec![3; 200]);
+    assert!(has_girth_six(&code));
+    let code = LdpcCode::regular(200, 3, 6, CycleRemoval::MultiEdges, &mut rng).unwrap();
+    assert!(code.checks().iter().all(|check| check.len() == 6));
+    assert_eq!(variable_degrees(&code), vec![3; 200]);
+    // without removal degrees could only decrease by an even number
+    let code = LdpcCode::regular(20, 4, 8, CycleRemoval::None, &mut rng).unwrap();
+    assert_eq!(code.checks_number(), 10);
+    assert!(variable_degrees(&code)
+        .iter()
+        .all(|degree| *degree <= 4 && degree % 2 == 0));
+    assert!(code
+        .checks()
+        .iter()
+        .all(|check| check.windows(2).all(|pair| pair[0] < pair[1])));
+    // an irregular ensemble
+    let degrees: Vec<_> = (0..300).map(|i| 2 + i % 3).collect();
+    assert!(LdpcCode::irregular(&degrees, &[6; 100], CycleRemoval::None, &mut rng).is_none());
+    let code =
+        LdpcCode::irregular(&degrees, &[6; 150], CycleRemoval::FourCycles, &mut rng).unwrap();
+    assert_eq!(variable_degrees(&code), degrees);
+    assert!(has_girth_six(&code));
+    // the parity-check matrix
+    let matrix = code.parity_check_matrix();
+    assert_eq!(matrix.shape, (150, 300));
+    assert_eq!(matrix.nnz(), 900);
+    let mut word = vec![0; 300];
+    assert!(code.is_codeword(&word));
+    word[7] = 1;
+    assert!(!code.is_codeword(&word));
+    // decoding of the zero codeword sent through a binary symmetric channel
+    let code = LdpcCode::regular(300, 3, 6, CycleRemoval::FourCycles, &mut rng).unwrap();
+    let mut initializer = uninformative_message_initializer();
+    let mut fgb = code.builder(&mut initializer).unwrap();
+    let flip_probability = 0.02;
+    let mut received = vec![0; 300];
+    for (variable, bit) in received.iter_mut().enumerate() {
+        if rng.gen_bool(flip_probability) {
+            *bit = 1;
+        }
+        let likelihood = if *bit == 0 {
+            array![1. - flip_probability, flip_probability]
+        } else {
+            array![flip_probability, 1. - flip_probability]
+        };
+        fgb.add_factor(
+            TabularFactor::new(likelihood.into_dyn()),
+            &[variable],
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    assert!(received.contains(&1));
+    let mut fg = fgb.build();
+    fg.run_message_passing_parallel(100, 0, 1e-8, &|_| 0., &|_| 0.)
+        .unwrap();
+    let decoded: Vec<_> = fg
+        .variable_marginals()
+        .iter()
+        .map(|marginal| (marginal[1] > marginal[0]) as usize)
+        .collect();
+    assert_eq!(decoded, vec![0; 300]);
+}
+
+// The sprinkler network: cloudy (0) -> sprinkler (1), cloudy (0) -> rain (2),
+// sprinkler (1), rain (2) -> wet grass (3)
+fn sprinkler_tables() -> Vec<(ArrayD<f64>, usize, Vec<usize>)> {
+    vec![
+        (array![0.5, 0.5].into_dyn(), 0, vec![0]),
+        (array![[0.5, 0.5], [0.9, 0.1]].into_dyn(), 1, vec![0, 1]),
+        // the child axis goes first
+        (array![[0.8, 0.2], [0.2, 0.8]].into_dyn(), 0, vec![2, 0]),
+        (
+            array![[[1., 0.], [0.1, 0.9]], [[0.1, 0.9], [0.01, 0.99]]].into_dyn(),
+            2,
+            vec![1, 2, 3],
+        ),
+    ]
+}
+
+fn exact_joint(tables: &[(ArrayD<f64>, usize, Vec<usize>)], config: &[usize]) -> f64 {
+    tables
+        .iter()
+        .map(|(table, _, vars)| {
+            let index: Vec<usize> = vars.iter().map(|var| config[*var]).collect();
+            table[index.as_slice()]
+        })
+        .product()
+}
+
+#[test]
+fn sprinkler_ancestral_sampling_test() {
+    let tables = sprinkler_tables();
+    let mut initializer = uninformative_message_initializer();
+    let mut fgb = FactorGraphBuilder::new_with_variables(vec![TabularVariable::new(2); 4], 4);
+    // factors are added in a non-topological order
+    for (table, child, vars) in tables.iter().rev() {
+        fgb.add_factor(
+            ConditionalTabularFactor::new(table.clone(), *child),
+            vars,
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    let fg = fgb.build();
+    fg.verify_normalization(1e-12).unwrap();
+    let order = fg.ancestral_order().unwrap();
+    assert_eq!(order[0], 3);
+    assert_eq!(order[3], 0);
+    let samples_number = 50000;
+    let mut rng = StdRng::seed_from_u64(42);
+    let mut counts = vec![0usize; 16];
+    for _ in 0..samples_number {
+        let sample = fg.ancestral_sample(&mut rng).unwrap();
+        counts[sample.iter().fold(0, |acc, state| 2 * acc + state)] += 1;
+    }
+    for (code, count) in counts.into_iter().enumerate() {
+        let config: Vec<usize> = (0..4).map(|var| (code >> (3 - var)) & 1).collect();
+        let exact = exact_joint(&tables, &config);
+        let empirical = count as f64 / samples_number as f64;
+        assert!(
+            (exact - empirical).abs() < 1e-2,
+            "config: {config:?}, exact: {exact}, empirical: {empirical}"
+        );
+    }
+}
+
+#[test]
+fn not_directed_acyclic_test() {
+    let mut initializer = uninformative_message_initializer();
+    // a directed cycle 0 -> 1 -> 0
+    let mut fgb = FactorGraphBuilder::new_with_variables(vec![TabularVariable::new(2); 2], 2);
+    let transition = array![[0.9, 0.1], [0.2, 0.8]].into_dyn();
+    fgb.add_factor(
+        ConditionalTabularFactor::new(transition.clone(), 1),
+        &[0, 1],
+        &mut initializer,
+    )
+    .unwrap();
+    fgb.add_factor(
+        ConditionalTabularFactor::new(transition.clone(), 1),
+        &[1, 0],
+        &mut initializer,
+    )
+    .unwrap();
+    let fg = fgb.build();
+    assert!(fg.ancestral_order().is_none());
+    assert!(matches!(
+        fg.ancestral_sample(&mut StdRng::seed_from_u64(42)),
+        Err(FGError::NotDirectedAcyclic)
+    ));
+    // a variable without a parent factor
+    let mut fgb = FactorGraphBuilder::new_with_variables(vec![TabularVariable::new(2); 2], 1);
+    fgb.add_factor(
+        ConditionalTabularFactor::new(transition.clone(), 1),
+        &[0, 1],
+        &mut initializer,
+    )
+    .unwrap();
+    let mut fg = fgb.build();
+    assert!(fg.ancestral_order().is_none());
+    // fixing a variable makes it a child of two factors
+    fg.freeze_variable(&1, 1).unwrap();
+    assert!(fg.ancestral_order().is_none());
+    // unnormalized tables
+    let mut fgb = FactorGraphBuilder::new_with_variables(vec![TabularVariable::new(2); 2], 2);
+    fgb.add_factor(
+        ConditionalTabularFactor::new(array![0.5, 0.5].into_dyn(), 0),
+        &[0],
+        &mut initializer,
+    )
+    .unwrap();
+    fgb.add_factor(
+        ConditionalTabularFactor::new(array![[0.9, 0.3], [0.1, 0.7]].into_dyn(), 1),
+        &[0, 1],
+        &mut initializer,
+    )
+    .unwrap();
+    let fg = fgb.build();
+    assert!(fg.ancestral_order().is_some());
+    match fg.verify_normalization(1e-6) {
+        Err(FGError::NormalizationError(1, error)) => assert!((error - 0.2).abs() < 1e-12),
+        other => panic!("unexpected result: {other:?}"),
+    }
+}
+
+#[test]
+fn ancestral_fast_path_test() {
+    let chain_length = 10;
+    let samples_number = 600;
+    let transition = array![[0.9, 0.1], [0.3, 0.7]].into_dyn();
+    let mut initializer = uninformative_message_initializer();
+    let mut fgb = FactorGraphBuilder::new_with_variables(
+        vec![TabularVariable::new(2); chain_length],
+        chain_length,
+    );
+    fgb.add_factor(
+        ConditionalTabularFactor::new(array![0.2, 0.8].into_dyn(), 0),
+        &[0],
+        &mut initializer,
+    )
+    .unwrap();
+    for i in 0..(chain_length - 1) {
+        fgb.add_factor(
+            ConditionalTabularFactor::new(transition.clone(), 1),
+            &[i, i + 1],
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    let mut fg = fgb.build();
+    let factor_scheduler = |_| 0.;
+    let variable_scheduler = |_| 0.;
+    fg.run_message_passing_parallel(1000, 0, 1e-12, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    // exact marginal of the last variable
+    let mut exact = array![0.2, 0.8];
+    for _ in 0..(chain_length - 1) {
+        exact = array![
+            exact[0] * 0.9 + exact[1] * 0.3,
+            exact[0] * 0.1 + exact[1] * 0.7
+        ];
+    }
+    let mut rng = StdRng::seed_from_u64(42);
+    let ancestral = fg
+        .sample_n_auto(
+            samples_number,
+            1000,
+            0,
+            1e-12,
+            &mut rng,
+            &factor_scheduler,
+            &variable_scheduler,
+        )
+        .unwrap();
+    assert_eq!(ancestral.total_iterations_number, 0);
+    assert_eq!(ancestral.iterations_per_sample, vec![0; samples_number]);
+    // a fixed variable makes the graph undirected, so decimation is used
+    fg.freeze_variable(&0, 0).unwrap();
+    fg.run_message_passing_parallel(1000, 0, 1e-12, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    assert!(matches!(
+        fg.sample_n_ancestral(1, &mut rng),
+        Err(FGError::NotDirectedAcyclic)
+    ));
+    let decimation = fg
+        .sample_n_auto(
+            samples_number,
+            1000,
+            0,
+            1e-12,
+            &mut rng,
+            &factor_scheduler,
+            &variable_scheduler,
+        )
+        .unwrap();
+    assert!(decimation.total_iterations_number > 0);
+    assert!(decimation.samples.iter().all(|sample| sample[0] == 0));
+    let mut exact_given_first = array![1., 0.];
+    for _ in 0..(chain_length - 1) {
+        exact_given_first = array![
+            exact_given_first[0] * 0.9 + exact_given_first[1] * 0.3,
+            exact_given_first[0] * 0.1 + exact_given_first[1] * 0.7
+        ];
+    }
+    for (info, exact) in [(ancestral, exact), (decimation, exact_given_first)] {
+        let empirical = info
+            .samples
+            .iter()
+            .filter(|sample| sample[chain_length - 1] == 0)
+            .count() as f64
+            / samples_number as f64;
+        assert!(
+            (exact[0] - empirical).abs() < 6e-2,
+            "exact: {}, empirical: {empirical}",
+            exact[0]
+        );
+    }
+}
+
+// Returns values of units encoded by bits of an integer
+fn units(code: usize, units_number: usize) -> Vec<bool> {
+    (0..units_number).map(|i| (code >> i) & 1 == 1).collect()
+}
+
+// Computes exact probabilities of units to be 1 given values of a part of visible units
+fn exact_marginals(
+    rbm: &RestrictedBoltzmannMachine,
+    visible: &[Option<bool>],
+) -> (Array1<f64>, Array1<f64>) {
+    let (visible_units_number, hidden_units_number) =
+        (rbm.visible_units_number(), rbm.hidden_units_number());
+    let mut visible_marginals = Array1::zeros(visible_units_number);
+    let mut hidden_marginals = Array1::zeros(hidden_units_number);
+    let mut partition_function = 0.;
+    for visible_code in 0..(1 << visible_units_number) {
+        let v = units(visible_code, visible_units_number);
+        if v.iter()
+            .zip(visible)
+            .any(|(value, observed)| observed.is_some_and(|observed| observed != *value))
+        {
+            continue;
+        }
+        let v = Array1::from_iter(v.iter().map(|value| *value as u8 as f64));
+        for hidden_code in 0..(1 << hidden_units_number) {
+            let h = Array1::from_iter(
+                units(hidden_code, hidden_units_number)
+                    .iter()
+                    .map(|value| *value as u8 as f64),
+            );
+            let weight = f64::exp(
+                v.dot(&rbm.weights().dot(&h))
+                    + rbm.visible_biases().dot(&v)
+                    + rbm.hidden_biases().dot(&h),
+            );
+            partition_function += weight;
+            visible_marginals = visible_marginals + weight * &v;
+            hidden_marginals = hidden_marginals + weight * &h;
+        }
+    }
+    (
+        visible_marginals / partition_function,
+        hidden_marginals / partition_function,
+    )
+}
+
+fn random_rbm(
+    visible_units_number: usize,
+    hidden_units_number: usize,
+    rng: &mut impl Rng,
+) -> RestrictedBoltzmannMachine {
+    RestrictedBoltzmannMachine::new(
+        Array2::from_shape_simple_fn((visible_units_number, hidden_units_number), || {
+            rng.gen_range(-1f64..1f64)
+        }),
+        Array1::from_shape_simple_fn(visible_units_number, || rng.gen_range(-1f64..1f64)),
+        Array1::from_shape_simple_fn(hidden_units_number, || rng.gen_range(-1f64..1f64)),
+    )
+}
+
+fn is_close(lhs: &Array1<f64>, rhs: &Array1<f64>) -> bool {
+    (lhs - rhs).iter().all(|x| x.abs() < 1e-8)
+}
+
+#[test]
+fn rbm_test() {
+    let mut rng = StdRng::seed_from_u64(42);
+    let mut options = MessagePassingOptions::new(
+        get_standard_factor_scheduler(0.),
+        get_standard_variable_scheduler(0.),
+    );
+    let mut initializer = || IsingMessage(0.);
+    let rbm = random_rbm(5, 3, &mut rng);
+    // exact conditionals
+    let visible = [true, false, false, true, true];
+    let (_, hidden_marginals) = exact_marginals(&rbm, &visible.map(Some));
+    assert!(is_close(&rbm.hidden_marginals(&visible), &hidden_marginals));
+    let hidden = [false, true, true];
+    let clamped_rbm = RestrictedBoltzmannMachine::new(
+        rbm.weights().t().to_owned(),
+        rbm.hidden_biases().clone(),
+        rbm.visible_biases().clone(),
+    );
+    let (_, visible_marginals) = exact_marginals(&clamped_rbm, &hidden.map(Some));
+    assert!(is_close(
+        &rbm.visible_marginals(&hidden),
+        &visible_marginals
+    ));
+    // all visible units are observed, thus a factor graph is a tree
+    let (visible_probabilities, hidden_probabilities) = rbm
+        .clamped_marginals::<SumProduct, _, _, _, _, _>(
+            &visible.map(Some),
+            &mut initializer,
+            &mut options,
+        )
+        .unwrap();
+    assert_eq!(visible_probabilities.to_vec(), vec![1., 0., 0., 1., 1.]);
+    assert!(is_close(&hidden_probabilities, &hidden_marginals));
+    // a single hidden unit also gives a tree
+    let rbm = random_rbm(6, 1, &mut rng);
+    let visible = [Some(true), None, None, Some(false), None, None];
+    let (visible_probabilities, hidden_probabilities) = rbm
+        .clamped_marginals::<SumProduct, _, _, _, _, _>(&visible, &mut initializer, &mut options)
+        .unwrap();
+    let (visible_marginals, hidden_marginals) = exact_marginals(&rbm, &visible);
+    assert!(is_close(&visible_probabilities, &visible_marginals));
+    assert!(is_close(&hidden_probabilities, &hidden_marginals));
+}
+
+// A magnetic field acting on a spin, exp ( field * s )
+#[derive(Debug, Clone)]
+struct Field(f64);
+
+impl Factor for Field {
+    type Message = IsingMessage;
+    type Marginal = ArrayD<f64>;
+    type Parameters = IsingFactorHyperParameters;
+
+    fn from_message(message: &Self::Message) -> Self {
+        Field(message.0 / 2.)
+    }
+
+    fn degree(&self) -> usize {
+        1
+    }
+
+    fn send_messages(&self, _: &[Self::Message], dst: &mut [Self::Message], _: &Self::Parameters) {
+        dst[0] = IsingMessage(2. * self.0);
+    }
+
+    fn marginal(&self, _: &[Self::Message]) -> Self::Marginal {
+        unimplemented!()
+    }
+
+    fn factor(&self) -> Self::Marginal {
+        array![self.0.exp(), (-self.0).exp()].into_dyn()
+    }
+}
+
+factor_enum! {
+    enum CouplingOrField {
+        Coupling(IsingFactor<SumProduct>),
+        Field(Field),
+    }
+}
+
+#[test]
+fn mixed_factors_test() {
+    let spins_number = 20;
+    let coupling = 0.8;
+    let magnetic_field = 0.3;
+    let error = 1e-10;
+    let factor_scheduler = get_standard_factor_scheduler(0.5);
+    let variable_scheduler = get_standard_variable_scheduler(0.5);
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(42), -0.5, 0.5);
+    // fields as separate factors
+    let mut fgb =
+        FactorGraphBuilder::<CouplingOrField, IsingVariable<SumProduct>>::new_with_variables(
+            vec![IsingVariable::new(); spins_number],
+            2 * spins_number - 1,
+        );
+    for i in 0..spins_number {
+        fgb.add_factor(Field(magnetic_field).into(), &[i], &mut initializer)
+            .unwrap();
+    }
+    for i in 0..(spins_number - 1) {
+        fgb.add_factor(
+            IsingFactor::new(coupling, 0., 0.).into(),
+            &[i, i + 1],
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    let mut mixed_fg = fgb.build();
+    assert_eq!(mixed_fg.get_factor_degrees()[0], 1);
+    // fields absorbed into couplings
+    let mut fgb = new_ising_builder::<SumProduct>(spins_number, spins_number - 1);
+    for i in 0..(spins_number - 1) {
+        let first_spin_b = if i == 0 {
+            magnetic_field
+        } else {
+            magnetic_field / 2.
+        };
+        let second_spin_b = if i == spins_number - 2 {
+            magnetic_field
+        } else {
+            magnetic_field / 2.
+        };
+        fgb.add_factor(
+            IsingFactor::new(coupling, first_spin_b, second_spin_b),
+            &[i, i + 1],
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    let mut fg = fgb.build();
+    let _ = mixed_fg
+        .run_message_passing_parallel(1000, 0, error, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    let _ = fg
+        .run_message_passing_parallel(1000, 0, error, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    for (mixed, plain) in mixed_fg
+        .variable_marginals()
+        .iter()
+        .zip(fg.variable_marginals())
+    {
+        assert!((mixed[0] - plain[0]).abs() < 1e-8);
+    }
+    // frozen variables are represented by the first variant
+    mixed_fg.freeze_variable(&1, 0).unwrap();
+    assert!(matches!(
+        mixed_fg.factors.last().unwrap().factor,
+        CouplingOrField::Coupling(_)
+    ));
+}
+
+type AnnotatedGraph = FactorGraph<
+    Annotated<IsingFactor<SumProduct>, String>,
+    Annotated<IsingVariable<SumProduct>, (usize, usize)>,
+>;
+
+#[test]
+fn annotated_nodes_test() {
+    let side = 4;
+    let spins_number = side * side;
+    let coupling = |i: usize, j: usize| 0.1 * ((i + 2 * j) % 5) as f64 - 0.2;
+    let mut plain_fgb = new_ising_builder::<SumProduct>(spins_number, 2 * spins_number);
+    let mut fgb = FactorGraphBuilder::new_with_variables(
+        (0..spins_number)
+            .map(|i| Annotated::new(IsingVariable::<SumProduct>::new(), (i / side, i % side))),
+        2 * spins_number,
+    );
+    // two disconnected ladders of width two
+    for i in 0..side {
+        for j in 0..(side - 1) {
+            for (first, second) in [
+                (i * side + j, i * side + j + 1),
+                (j * side + i, (j + 1) * side + i),
+            ] {
+                if (first % side < 2) != (second % side < 2) {
+                    continue;
+                }
+                let factor = IsingFactor::new(coupling(first, second), 0.1, -0.1);
+                plain_fgb
+                    .add_factor(factor, &[first, second], &mut || IsingMessage(0.))
+                    .unwrap();
+                fgb.add_factor(
+                    Annotated::new(factor, format!("{first}-{second}")),
+                    &[first, second],
+                    &mut || IsingMessage(0.),
+                )
+                .unwrap();
+            }
+        }
+    }
+    let mut plain_fg = plain_fgb.build();
+    let mut fg = fgb.build();
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    plain_fg
+        .run_message_passing_parallel(1000, 0, 1e-12, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    // data is accessible in callbacks
+    let mut observed_coordinates = Vec::new();
+    let mut options = MessagePassingOptions::new(&factor_scheduler, &variable_scheduler)
+        .with_threshold(1e-12)
+        .with_observer(|fg: &AnnotatedGraph, _, _| {
+            observed_coordinates.push(fg.get_variable(spins_number - 1).data);
+            ControlFlow::Continue(())
+        });
+    fg.run_message_passing_with_options(&mut options).unwrap();
+    assert!(observed_coordinates
+        .iter()
+        .all(|coordinates| *coordinates == (side - 1, side - 1)));
+    // annotations do not affect inference
+    assert_eq!(fg.variable_marginals(), plain_fg.variable_marginals());
+    assert_eq!(fg.factor_marginals(), plain_fg.factor_marginals());
+    // data survives clone and splitting
+    let mut cloned_fg = fg.clone();
+    *cloned_fg.variable_data_mut(0).unwrap() = (side, side);
+    cloned_fg.factor_data_mut(0).unwrap().push_str(" edited");
+    assert_eq!(fg.get_variable(0).data, (0, 0));
+    assert_eq!(cloned_fg.get_factor(0).data, "0-1 edited");
+    assert_eq!(fg.get_factor(0).data, "0-1");
+    for (component, subgraph) in fg.split_components() {
+        for (local_index, var_index) in component.variables.iter().enumerate() {
+            assert_eq!(
+                subgraph.get_variable(local_index).data,
+                fg.get_variable(*var_index).data
+            );
+        }
+        for (local_index, fac_index) in component.factors.iter().enumerate() {
+            assert_eq!(
+                subgraph.get_factor(local_index).data,
+                fg.get_factor(*fac_index).data
+            );
+        }
+    }
+    // factors created from messages carry default data
+    let factors_number = fg.get_factor_degrees().len();
+    fg.freeze_variable(&1, 0).unwrap();
+    plain_fg.freeze_variable(&1, 0).unwrap();
+    assert_eq!(fg.get_factor(factors_number).data, "");
+    fg.run_message_passing_parallel(1000, 0, 1e-12, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    plain_fg
+        .run_message_passing_parallel(1000, 0, 1e-12, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    assert_eq!(fg.variable_marginals(), plain_fg.variable_marginals());
+    assert!(matches!(
+        fg.variable_data_mut(spins_number),
+        Err(FGError::OutOfRangeVariable(..))
+    ));
+    assert!(matches!(
+        fg.factor_data_mut(factors_number + 1),
+        Err(FGError::OutOfRangeFactor(..))
+    ));
+}
+
+#[test]
+fn labeled_builder_test() {
+    let names: Vec<String> = (0..6).map(|i| format!("gene_{i}")).collect();
+    let couplings = [
+        (0, 1, 0.3),
+        (1, 2, -0.4),
+        (2, 3, 0.5),
+        (3, 4, 0.2),
+        (1, 5, 0.7),
+    ];
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    // variables are added in a shuffled order
+    let order = [3, 0, 5, 1, 4, 2];
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(42), -0.5, 0.5);
+    let mut fgb =
+        LabeledBuilder::<String, IsingFactor<SumProduct>, IsingVariable<SumProduct>>::new();
+    for i in order {
+        let variable = IsingVariable::new().with_field(0.1 * i as f64);
+        assert_eq!(
+            fgb.add_variable(names[i].clone(), variable),
+            Ok(order.iter().position(|j| *j == i).unwrap())
+        );
+    }
+    for (lhs, rhs, coupling) in couplings {
+        fgb.add_factor(
+            IsingFactor::new(coupling, 0., 0.),
+            &[names[lhs].clone(), names[rhs].clone()],
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    assert_eq!(
+        fgb.add_variable(names[5].clone(), IsingVariable::new()),
+        Err(FGBuilderError::DuplicateLabel(2))
+    );
+    assert_eq!(
+        fgb.add_factor(
+            IsingFactor::new(0.1, 0., 0.),
+            &[names[0].clone(), "gene_6".to_string()],
+            &mut initializer
+        ),
+        Err(FGBuilderError::UnknownLabel(1))
+    );
+    let (mut fg, labels) = fgb.build();
+    assert_eq!(labels.len(), 6);
+    assert_eq!(fg.get_factor_degrees(), vec![2; 5]);
+    fg.run_message_passing_parallel(1000, 0, 1e-10, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    // the same model with variables indexed by their names
+    let mut fgb = new_ising_builder::<SumProduct>(0, couplings.len());
+    fgb.add_variables((0..6).map(|i| IsingVariable::new().with_field(0.1 * i as f64)));
+    for (lhs, rhs, coupling) in couplings {
+        fgb.add_factor(
+            IsingFactor::new(coupling, 0., 0.),
+            &[lhs, rhs],
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    let mut reference_fg = fgb.build();
+    reference_fg
+        .run_message_passing_parallel(1000, 0, 1e-10, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    let marginals = labels.labeled(fg.variable_marginals());
+    for (i, reference) in reference_fg.variable_marginals().iter().enumerate() {
+        let index = labels.index(&names[i]).unwrap();
+        assert_eq!(labels.label(index), Some(&names[i]));
+        assert!((marginals[&names[i]][0] - reference[0]).abs() < 1e-10);
+    }
+    let degrees = labels.labeled(fg.get_variable_degrees());
+    for (i, degree) in reference_fg.get_variable_degrees().iter().enumerate() {
+        assert_eq!(degrees[&names[i]], *degree);
+    }
+}
+
+// Checks the energy of random configurations against the coupling matrix and fields
+// of a factor graph
+fn check_energy(spin_glass: &SpinGlass, rng: &mut impl Rng) {
+    let fg = spin_glass
+        .builder::<SumProduct>(&mut || IsingMessage(0.))
+        .unwrap()
+        .build();
+    let couplings = fg.coupling_matrix();
+    let fields = fg.local_fields();
+    for _ in 0..10 {
+        let spins: Vec<i8> = (0..spin_glass.spins_number())
+            .map(|_| if rng.gen_bool(0.5) { 1 } else { -1 })
+            .collect();
+        let dense_spins = Array1::from_iter(spins.iter().map(|spin| *spin as f64));
+        let energy =
+            -0.5 * dense_spins.dot(&couplings.dot(&dense_spins)) - fields.dot(&dense_spins);
+        assert!((spin_glass.energy(&spins) - energy).abs() < 1e-10);
+    }
+}
+
+#[test]
+fn ensembles_test() {
+    let mut rng = StdRng::seed_from_u64(42);
+    let mut initializer = || IsingMessage(0.);
+    // Sherrington–Kirkpatrick
+    let (fgb, spin_glass) =
+        sherrington_kirkpatrick::<SumProduct>(300, &mut rng, &mut initializer).unwrap();
+    assert_eq!(fgb.build().get_variable_degrees(), vec![300; 300]);
+    let couplings_number = spin_glass.couplings.len() as f64;
+    assert_eq!(couplings_number, 44850.);
+    let mean = spin_glass.couplings.iter().sum::<f64>() / couplings_number;
+    let variance = spin_glass.couplings.iter().map(|x| x * x).sum::<f64>() / couplings_number;
+    assert!(mean.abs() < 1e-3);
+    assert!((300. * variance - 1.).abs() < 0.05);
+    check_energy(&spin_glass, &mut rng);
+    // Edwards–Anderson
+    let (fgb, spin_glass) =
+        edwards_anderson::<SumProduct>(&[6, 6, 6], true, &mut rng, &mut initializer).unwrap();
+    assert_eq!(fgb.build().get_variable_degrees(), vec![7; 216]);
+    assert!(spin_glass.couplings.iter().all(|x| x.abs() == 1.));
+    let frustrated = spin_glass.couplings.iter().filter(|x| **x < 0.).count();
+    assert!((frustrated as f64 - 324.).abs() < 60.);
+    check_energy(&spin_glass, &mut rng);
+    // diluted spin glass
+    let (_, spin_glass) =
+        diluted_spin_glass::<SumProduct>(2000, 4., &mut rng, &mut initializer).unwrap();
+    assert!((spin_glass.edges.len() as f64 - 4000.).abs() < 300.);
+    assert!(spin_glass.couplings.iter().all(|x| x.abs() == 1.));
+    check_energy(&spin_glass, &mut rng);
+    let (_, spin_glass) =
+        diluted_spin_glass::<SumProduct>(1, 4., &mut rng, &mut initializer).unwrap();
+    assert!(spin_glass.edges.is_empty());
+    // disorder realizations are reproducible
+    let sample = |seed| {
+        edwards_anderson::<SumProduct>(
+            &[4, 4],
+            false,
+            &mut StdRng::seed_from_u64(seed),
+            &mut || IsingMessage(0.),
+        )
+        .unwrap()
+        .1
+        .couplings
+    };
+    assert_eq!(sample(1), sample(1));
+    assert_ne!(sample(1), sample(2));
+}
+
+#[test]
+fn incremental_factors_test() {
+    let side = 6;
+    let spins_number = side * side;
+    let error = 1e-10;
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    let mut rng = StdRng::seed_from_u64(42);
+    let distr = Uniform::new(-0.3, 0.3);
+    let mut edges = Vec::new();
+    for i in 0..side {
+        for j in 0..side {
+            let spin = i * side + j;
+            if j + 1 < side {
+                edges.push(([spin, spin + 1], rng.sample(distr), rng.sample(distr)));
+            }
+            if i + 1 < side {
+                edges.push(([spin, spin + side], rng.sample(distr), rng.sample(distr)));
+            }
+        }
+    }
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(7), -0.5, 0.5);
+    let mut fg = new_ising_builder::<SumProduct>(spins_number, edges.len()).build();
+    // adding couplings one by one and warm starting message passing
+    for (added_number, (edge, coupling, field)) in edges.iter().enumerate() {
+        fg.add_factor(
+            IsingFactor::new(*coupling, *field, 0.),
+            edge,
+            &mut initializer,
+        )
+        .unwrap();
+        assert_eq!(fg.get_factor_degrees().len(), added_number + 1);
+        fg.run_message_passing_parallel(1000, 0, error, &factor_scheduler, &variable_scheduler)
+            .unwrap();
+    }
+    let mut fgb = new_ising_builder::<SumProduct>(spins_number, edges.len());
+    for (edge, coupling, field) in &edges {
+        fgb.add_factor(
+            IsingFactor::new(*coupling, *field, 0.),
+            edge,
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    let mut rebuilt_fg = fgb.build();
+    rebuilt_fg
+        .run_message_passing_parallel(1000, 0, error, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    assert_eq!(fg.get_variable_degrees(), rebuilt_fg.get_variable_degrees());
+    for (lhs, rhs) in fg
+        .variable_marginals()
+        .iter()
+        .zip(rebuilt_fg.variable_marginals())
+    {
+        assert!((lhs[0] - rhs[0]).abs() < 1e-8);
+    }
+    // invalid factors do not modify a factor graph
+    assert!(matches!(
+        fg.add_factor(IsingFactor::new(0.1, 0., 0.), &[0], &mut initializer),
+        Err(FGError::DegreeError(2, _))
+    ));
+    assert!(matches!(
+        fg.add_factor(
+            IsingFactor::new(0.1, 0., 0.),
+            &[0, spins_number],
+            &mut initializer
+        ),
+        Err(FGError::OutOfRangeVariable(_, _))
+    ));
+    assert_eq!(fg.get_variable_degrees(), rebuilt_fg.get_variable_degrees());
+    assert_eq!(fg.get_factor_degrees(), rebuilt_fg.get_factor_degrees());
+}
+
+#[test]
+fn incremental_variables_test() {
+    let spins_number = 30;
+    let error = 1e-10;
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    let mut rng = StdRng::seed_from_u64(42);
+    let distr = Uniform::new(-0.5, 0.5);
+    let couplings: Vec<f64> = (0..spins_number).map(|_| rng.sample(distr)).collect();
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(7), -0.5, 0.5);
+    // a chain growing spin by spin from an empty factor graph
+    let mut fg = new_ising_builder::<SumProduct>(0, 0).build();
+    assert_eq!(fg.add_variable(IsingVariable::new().with_field(0.2)), 0);
+    for (i, coupling) in couplings.iter().enumerate().skip(1) {
+        assert_eq!(fg.add_variable(IsingVariable::new().with_field(0.2)), i);
+        fg.add_factor(
+            IsingFactor::new(*coupling, 0., 0.),
+            &[i - 1, i],
+            &mut initializer,
+        )
+        .unwrap();
+        fg.run_message_passing_parallel(1000, 0, error, &factor_scheduler, &variable_scheduler)
+            .unwrap();
+    }
+    let mut fgb = FactorGraphBuilder::<IsingFactor<SumProduct>, _>::new_with_capacity(
+        spins_number,
+        spins_number - 1,
+    );
+    fgb.add_variables(
+        (0..spins_number).map(|_| IsingVariable::<SumProduct>::new().with_field(0.2)),
+    );
+    for (i, coupling) in couplings.iter().enumerate().skip(1) {
+        fgb.add_factor(
+            IsingFactor::new(*coupling, 0., 0.),
+            &[i - 1, i],
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    let mut rebuilt_fg = fgb.build();
+    rebuilt_fg
+        .run_message_passing_parallel(1000, 0, error, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    for (lhs, rhs) in fg
+        .variable_marginals()
+        .iter()
+        .zip(rebuilt_fg.variable_marginals())
+    {
+        assert!((lhs[0] - rhs[0]).abs() < 1e-8);
+    }
+    // a disconnected variable only feels its own field
+    let index = fg
+        .add_variables([IsingVariable::new().with_field(0.3)])
+        .start;
+    let marginal = &fg.variable_marginals()[index];
+    assert!((marginal[0] - 1. / (1. + f64::exp(-0.6))).abs() < 1e-12);
+}
+
+#[test]
+fn update_factors_test() {
+    let spins_number = 30;
+    let error = 1e-10;
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    let mut rng = StdRng::seed_from_u64(42);
+    let distr = Uniform::new(-0.3, 0.3);
+    let mut edges = Vec::new();
+    for spin in 0..spins_number {
+        edges.push(([spin, (spin + 1) % spins_number], rng.sample(distr)));
+        edges.push(([spin, (spin + 7) % spins_number], rng.sample(distr)));
+    }
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(7), -0.5, 0.5);
+    let mut fgb = new_ising_builder::<SumProduct>(spins_number, edges.len());
+    for (edge, coupling) in &edges {
+        fgb.add_factor(IsingFactor::new(*coupling, 0.1, 0.), edge, &mut initializer)
+            .unwrap();
+    }
+    let mut fg = fgb.build();
+    // ramping couplings with warm started message passing
+    for beta in [1.2, 1.5, 2.] {
+        for (fac_index, (_, coupling)) in edges.iter().enumerate() {
+            fg.update_factor(fac_index, |factor| {
+                *factor = IsingFactor::new(beta * coupling, 0.1, 0.)
+            })
+            .unwrap();
+        }
+        fg.run_message_passing_parallel(1000, 0, error, &factor_scheduler, &variable_scheduler)
+            .unwrap();
+    }
+    let mut fgb = new_ising_builder::<SumProduct>(spins_number, edges.len());
+    for (edge, coupling) in &edges {
+        fgb.add_factor(
+            IsingFactor::new(2. * coupling, 0.1, 0.),
+            edge,
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    let mut rebuilt_fg = fgb.build();
+    rebuilt_fg
+        .run_message_passing_parallel(1000, 0, error, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    for (lhs, rhs) in fg
+        .variable_marginals()
+        .iter()
+        .zip(rebuilt_fg.variable_marginals())
+    {
+        assert!((lhs[0] - rhs[0]).abs() < 1e-8);
+    }
+    // failed updates do not modify a factor graph
+    let marginals = fg.factor_marginals();
+    assert!(matches!(
+        fg.update_factor(0, |factor| *factor = IsingFactor::UnitFactor(1.)),
+        Err(FGError::FactorDegreeError(2, 1))
+    ));
+    assert!(matches!(
+        fg.update_factor(edges.len(), |_| {}),
+        Err(FGError::OutOfRangeFactor(_, _))
+    ));
+    for (lhs, rhs) in marginals.iter().zip(fg.factor_marginals()) {
+        assert_eq!(lhs, rhs);
+    }
+}
+
+#[test]
+fn localized_repair_test() {
+    let side = 12;
+    let spins_number = side * side;
+    let error = 1e-12;
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    let mut rng = StdRng::seed_from_u64(42);
+    let distr = Uniform::new(-0.3, 0.3);
+    let mut edges = Vec::new();
+    for i in 0..side {
+        for j in 0..side {
+            let spin = i * side + j;
+            if j + 1 < side {
+                edges.push([spin, spin + 1]);
+            }
+            if i + 1 < side {
+                edges.push([spin, spin + side]);
+            }
+        }
+    }
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(7), -0.5, 0.5);
+    let mut fgb = new_ising_builder::<SumProduct>(spins_number, edges.len());
+    for edge in &edges {
+        fgb.add_factor(
+            IsingFactor::new(rng.sample(distr), 0.1, 0.),
+            edge,
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    let mut fg = fgb.build();
+    fg.run_message_passing_parallel(1000, 0, error, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    // a few couplings change at each tick
+    for _ in 0..5 {
+        let updates: Vec<_> = (0..3)
+            .map(|_| {
+                let fac_index = rng.gen_range(0..edges.len());
+                (fac_index, IsingFactor::new(rng.sample(distr), 0.1, 0.))
+            })
+            .collect();
+        let mut resolved_fg = fg.clone();
+        for (fac_index, factor) in &updates {
+            resolved_fg.replace_factor(*fac_index, *factor).unwrap();
+        }
+        resolved_fg
+            .run_message_passing_parallel(1000, 0, error, &factor_scheduler, &variable_scheduler)
+            .unwrap();
+        let info = fg
+            .update_factors_locally(updates, 100000, 1e-14, &factor_scheduler(0), &0.)
+            .unwrap();
+        assert!(info.updates_number > 0);
+        assert!(info.touched_variables_number <= spins_number);
+        for (lhs, rhs) in fg
+            .variable_marginals()
+            .iter()
+            .zip(resolved_fg.variable_marginals())
+        {
+            assert!((lhs[0] - rhs[0]).abs() < 1e-8);
+        }
+    }
+    // an unchanged factor does not propagate anything
+    let factor = *fg.get_factor(0);
+    let info = fg
+        .update_factors_locally([(0, factor)], 100000, 1e-10, &factor_scheduler(0), &0.)
+        .unwrap();
+    assert_eq!(info.updates_number, 1);
+    assert_eq!(info.touched_variables_number, 0);
+    // failed updates do not modify a factor graph
+    let marginals = fg.variable_marginals();
+    assert!(matches!(
+        fg.update_factors_locally(
+            [
+                (0, IsingFactor::new(1., 0., 0.)),
+                (1, IsingFactor::UnitFactor(1.))
+            ],
+            100000,
+            1e-10,
+            &factor_scheduler(0),
+            &0.,
+        ),
+        Err(FGError::FactorDegreeError(2, 1))
+    ));
+    assert!(matches!(
+        fg.update_factors_locally(
+            [(edges.len(), IsingFactor::new(1., 0., 0.))],
+            100000,
+            1e-10,
+            &factor_scheduler(0),
+            &0.,
+        ),
+        Err(FGError::OutOfRangeFactor(_, _))
+    ));
+    assert_eq!(marginals, fg.variable_marginals());
+}
@@ -0,0 +1,575 @@
+use std::io::BufWriter;
+use std::ops::ControlFlow;
+use std::sync::mpsc::sync_channel;
+
+use super::{chain_fg, ring_fg, torus_fg};
+use crate::core::{
+    BoundedMessage, FGError, Factor, FactorGraph, MarginalsPublisher, MessageAudit,
+    MessagePassingOptions, MessageTrace, RunReport, SampleWriter,
+};
+use crate::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+use crate::ising::{
+    new_ising_builder, random_message_initializer, IsingFactor, IsingMessage, IsingVariable,
+    SumProduct,
+};
+use crate::tabular::TabularMessage;
+use ndarray::{array, Array1};
+use rand::{rngs::StdRng, SeedableRng};
+
+#[test]
+#[allow(deprecated)]
+fn concurrent_marginals_reading_test() {
+    let spins_number = 100;
+    let publishing_period = 3;
+    let factor_scheduler = get_standard_factor_scheduler(0.5);
+    let variable_scheduler = get_standard_variable_scheduler(0.5);
+    let mut fg = chain_fg(spins_number, IsingFactor::new(0.8, 0.1, -0.1), 42);
+    let publisher = MarginalsPublisher::<Array1<f64>>::new();
+    assert!(publisher.latest().is_none());
+    let reader = publisher.clone();
+    let info = std::thread::scope(|s| {
+        let reader_handle = s.spawn(move || {
+            let mut observed_iterations = Vec::new();
+            while observed_iterations.len() < 1000 {
+                if let Some(published) = reader.latest() {
+                    assert_eq!(published.marginals.len(), spins_number);
+                    for marginal in &published.marginals {
+                        assert!((marginal.sum() - 1.).abs() < 1e-10);
+                    }
+                    observed_iterations.push(published.iteration);
+                }
+                std::thread::yield_now();
+            }
+            observed_iterations
+        });
+        let info = fg
+            .run_message_passing_parallel_publishing(
+                1000,
+                0,
+                1e-10,
+                &factor_scheduler,
+                &variable_scheduler,
+                &publisher,
+                publishing_period,
+            )
+            .unwrap();
+        let observed_iterations = reader_handle.join().unwrap();
+        // published iterations never go back in time
+        assert!(observed_iterations.windows(2).all(|w| w[0] <= w[1]));
+        info
+    });
+    let published = publisher.latest().unwrap();
+    assert_eq!(published.iteration, info.iterations_number);
+    assert_eq!(published.discrepancy, info.last_discrepancy);
+    assert_eq!(published.marginals, fg.variable_marginals());
+}
+
+#[test]
+fn marginal_history_test() {
+    let spins_number = 20;
+    let recording_period = 4;
+    let fg = ring_fg(spins_number, IsingFactor::new(0.6, 0.05, 0.), 42);
+    // damping depends on the iteration number
+    let factor_scheduler = |i: usize| get_standard_factor_scheduler(0.5)(i % 3);
+    let variable_scheduler = |i: usize| get_standard_variable_scheduler(0.5)(i % 2);
+    let mut observed_fg = fg.clone();
+    let mut observed = Vec::new();
+    let observed_info = observed_fg
+        .run_message_passing_with_options(
+            &mut MessagePassingOptions::new(&factor_scheduler, &variable_scheduler)
+                .with_threshold(1e-12)
+                .with_observer(
+                    |fg: &FactorGraph<IsingFactor<SumProduct>, IsingVariable<SumProduct>>, _, _| {
+                        observed.push(fg.variable_marginals());
+                        std::ops::ControlFlow::Continue(())
+                    },
+                ),
+        )
+        .unwrap();
+    let mut recorded_fg = fg.clone();
+    let info = recorded_fg
+        .run_message_passing_with_options(
+            &mut MessagePassingOptions::new(&factor_scheduler, &variable_scheduler)
+                .with_threshold(1e-12)
+                .with_marginals_recording(recording_period),
+        )
+        .unwrap();
+    assert_eq!(info.iterations_number, observed_info.iterations_number);
+    let marginal_history = info.marginal_history.unwrap();
+    assert_eq!(
+        marginal_history.len(),
+        (info.iterations_number + recording_period) / recording_period
+    );
+    for recorded in &marginal_history {
+        let observed: Vec<_> = observed[recorded.iteration]
+            .iter()
+            .map(|marginal| marginal.to_vec())
+            .collect();
+        assert_eq!(recorded.marginals, observed);
+        assert_eq!(
+            recorded.discrepancy,
+            info.discrepancy_dynamics[recorded.iteration]
+        );
+    }
+    assert_eq!(
+        marginal_history.last().unwrap().iteration,
+        info.iterations_number
+    );
+    // recording is disabled by default
+    let info = fg
+        .clone()
+        .run_message_passing_parallel(1000, 0, 1e-12, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    assert!(info.marginal_history.is_none());
+}
+
+#[test]
+fn two_spins_streaming_sampling_test() {
+    let samples_number = 20000;
+    let coupling = 0.7f64;
+    let first_spin_b = 0.3f64;
+    let second_spin_b = -0.2f64;
+    let error = 1e-10f64;
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(42), -0.5, 0.5);
+    let mut fgb = new_ising_builder::<SumProduct>(2, 1);
+    fgb.add_factor(
+        IsingFactor::new(coupling, first_spin_b, second_spin_b),
+        &[0, 1],
+        &mut initializer,
+    )
+    .unwrap();
+    let mut fg = fgb.build();
+    let _ = fg
+        .run_message_passing_parallel(100, 0, error, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    let mut rng = StdRng::seed_from_u64(7);
+    // a bounded channel blocks the sampler until the consumer catches up
+    let (mut sender, receiver) = sync_channel::<Vec<i8>>(16);
+    let (info, counts) = std::thread::scope(|s| {
+        let consumer = s.spawn(move || {
+            let mut counts = [0usize; 4];
+            for sample in receiver.iter() {
+                counts[(sample[0] + 1 + (sample[1] + 1) / 2) as usize] += 1;
+            }
+            counts
+        });
+        let info = fg
+            .sample_to_sink(
+                samples_number,
+                100,
+                0,
+                error,
+                &mut rng,
+                &factor_scheduler,
+                &variable_scheduler,
+                &mut sender,
+            )
+            .unwrap();
+        drop(sender);
+        (info, consumer.join().unwrap())
+    });
+    assert_eq!(info.samples_number, samples_number);
+    assert!(!info.is_stopped_by_sink);
+    assert_eq!(counts.iter().sum::<usize>(), samples_number);
+    let weight =
+        |s1: f64, s2: f64| f64::exp(coupling * s1 * s2 + first_spin_b * s1 + second_spin_b * s2);
+    let partition_function = weight(1., 1.) + weight(1., -1.) + weight(-1., 1.) + weight(-1., -1.);
+    for (s1, s2, count) in [
+        (-1., -1., counts[0]),
+        (-1., 1., counts[1]),
+        (1., -1., counts[2]),
+        (1., 1., counts[3]),
+    ] {
+        let exact = weight(s1, s2) / partition_function;
+        let empirical = count as f64 / samples_number as f64;
+        assert!(
+            (exact - empirical).abs() < 2e-2,
+            "exact: {exact}, empirical: {empirical}"
+        );
+    }
+}
+
+#[test]
+fn sink_stops_sampling_test() {
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(42), -0.5, 0.5);
+    let mut fgb = new_ising_builder::<SumProduct>(3, 2);
+    fgb.add_factor(IsingFactor::new(0.5, 0.1, 0.), &[0, 1], &mut initializer)
+        .unwrap();
+    fgb.add_factor(IsingFactor::new(-0.5, 0., 0.2), &[1, 2], &mut initializer)
+        .unwrap();
+    let fg = fgb.build();
+    let mut rng = StdRng::seed_from_u64(7);
+    // a closure sink stopping after 5 samples
+    let mut consumed = 0;
+    let mut sink = |sample: Vec<i8>| {
+        assert_eq!(sample.len(), 3);
+        consumed += 1;
+        if consumed == 5 {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    };
+    let info = fg
+        .sample_to_sink(
+            100,
+            100,
+            0,
+            1e-10,
+            &mut rng,
+            &factor_scheduler,
+            &variable_scheduler,
+            &mut sink,
+        )
+        .unwrap();
+    assert_eq!(info.samples_number, 5);
+    assert!(info.is_stopped_by_sink);
+    // a dropped receiver stops sampling
+    let (mut sender, receiver) = sync_channel::<Vec<i8>>(1);
+    drop(receiver);
+    let info = fg
+        .sample_to_sink(
+            100,
+            100,
+            0,
+            1e-10,
+            &mut rng,
+            &factor_scheduler,
+            &variable_scheduler,
+            &mut sender,
+        )
+        .unwrap();
+    assert!(info.is_stopped_by_sink);
+    // a buffered writer
+    let mut writer = SampleWriter::new(BufWriter::new(Vec::new()));
+    let info = fg
+        .sample_to_sink(
+            50,
+            100,
+            0,
+            1e-10,
+            &mut rng,
+            &factor_scheduler,
+            &variable_scheduler,
+            &mut writer,
+        )
+        .unwrap();
+    assert_eq!(info.samples_number, 50);
+    let text = String::from_utf8(writer.into_inner().unwrap().into_inner().unwrap()).unwrap();
+    assert_eq!(text.lines().count(), 50);
+    for line in text.lines() {
+        let spins: Vec<i8> = line
+            .split_whitespace()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        assert_eq!(spins.len(), 3);
+        assert!(spins.iter().all(|s| *s == 1 || *s == -1));
+    }
+}
+
+#[test]
+fn message_trace_test() {
+    let factor_scheduler = get_standard_factor_scheduler(0.5);
+    let variable_scheduler = get_standard_variable_scheduler(0.5);
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(42), -0.5, 0.5);
+    let mut fgb = new_ising_builder::<SumProduct>(4, 4);
+    for (i, j) in [(0, 1), (1, 2), (2, 0), (2, 3)] {
+        fgb.add_factor(IsingFactor::new(0.3, 0.1, 0.), &[i, j], &mut initializer)
+            .unwrap();
+    }
+    let mut fg = fgb.build();
+    // an edge that does not exist is rejected before message passing
+    let mut trace = MessageTrace::new(vec![(3, 2), (0, 2)]);
+    let snapshot = fg.snapshot();
+    assert!(matches!(
+        fg.run_message_passing_parallel_traced(
+            1000,
+            0,
+            1e-10,
+            &factor_scheduler,
+            &variable_scheduler,
+            &mut trace,
+        ),
+        Err(FGError::UnknownEdge(0, 2))
+    ));
+    assert!(trace.iterations.is_empty());
+    let mut trace = MessageTrace::new(vec![(3, 2), (1, 1)]);
+    let info = fg
+        .run_message_passing_parallel_traced(
+            1000,
+            0,
+            1e-10,
+            &factor_scheduler,
+            &variable_scheduler,
+            &mut trace,
+        )
+        .unwrap();
+    assert_eq!(trace.iterations.len(), info.iterations_number + 1);
+    assert_eq!(trace.iterations[..3], [0, 1, 2]);
+    assert!(trace.factor_messages.iter().all(|x| x.len() == 2));
+    // the last record matches the final messages
+    let last = trace.factor_messages.last().unwrap();
+    assert_eq!(last[0].0, fg.factors[3].messages[0].0);
+    assert_eq!(last[1].0, fg.factors[1].messages[0].0);
+    let last = trace.variable_messages.last().unwrap();
+    assert_eq!(last[0].0, fg.factors[3].receivers[0].0);
+    assert_eq!(last[1].0, fg.factors[1].receivers[0].0);
+    // traced messages do not depend on tracing
+    fg.rollback(&snapshot).unwrap();
+    let mut all_trace = MessageTrace::all(&fg);
+    fg.run_message_passing_parallel_traced(
+        1000,
+        0,
+        1e-10,
+        &factor_scheduler,
+        &variable_scheduler,
+        &mut all_trace,
+    )
+    .unwrap();
+    assert_eq!(all_trace.edges.len(), 8);
+    let edge = all_trace.edges.iter().position(|x| *x == (3, 2)).unwrap();
+    for (all, traced) in all_trace.factor_messages.iter().zip(&trace.factor_messages) {
+        assert_eq!(all[edge].0, traced[0].0);
+    }
+    let mut buffer = Vec::new();
+    trace.write(&mut buffer).unwrap();
+    let text = String::from_utf8(buffer).unwrap();
+    assert_eq!(text.lines().count(), 1 + 2 * trace.iterations.len());
+    assert!(text
+        .lines()
+        .nth(1)
+        .unwrap()
+        .starts_with("0\t3\t2\tIsingMessage("));
+}
+
+#[test]
+fn run_report_test() {
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    let fg = ring_fg(3, IsingFactor::new(0.4, 0.1, 0.), 42);
+    // a run that is too short fails
+    let report =
+        fg.clone()
+            .run_message_passing_report(2, 0, 1e-12, &factor_scheduler, &variable_scheduler);
+    assert!(!report.is_converged);
+    assert_eq!(report.iterations_number, 2);
+    assert!(report.discrepancy > 1e-12);
+    assert!(report.error.is_some());
+    let mut fg = fg;
+    let report = fg
+        .run_message_passing_report(1000, 0, 1e-12, &factor_scheduler, &variable_scheduler)
+        .with_observable("m0", 1.)
+        .with_observable("m0", 2.)
+        .with_timing("postprocessing", 0.5);
+    assert!(report.is_converged);
+    assert!(report.discrepancy < 1e-12);
+    assert!(report.error.is_none());
+    assert_eq!(report.observables.len(), 1);
+    assert_eq!(report.observables["m0"], 2.);
+    assert_eq!(report.timings.len(), 2);
+    // errors unrelated to convergence do not carry iterations
+    let report = RunReport::new(&Err(FGError::SnapshotMismatch));
+    assert!(!report.is_converged);
+    assert_eq!(report.iterations_number, 0);
+    assert!(report.discrepancy.is_nan());
+    assert_eq!(report.error, Some(FGError::SnapshotMismatch.to_string()));
+}
+
+#[test]
+fn factor_inconsistency_ranking_test() {
+    let coupling = 0.9;
+    let first_spin_b = 0.4;
+    let second_spin_b = -0.3;
+    let error = 1e-10;
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(42), -0.5, 0.5);
+    let mut fgb = new_ising_builder::<SumProduct>(3, 2);
+    fgb.add_factor(
+        IsingFactor::new(coupling, first_spin_b, second_spin_b),
+        &[0, 1],
+        &mut initializer,
+    )
+    .unwrap();
+    fgb.add_factor(IsingFactor::new(0.1, 0., 0.), &[1, 2], &mut initializer)
+        .unwrap();
+    let mut fg = fgb.build();
+    // a frozen variable is represented by a unit factor
+    fg.freeze_variable(&1, 2).unwrap();
+    let _ = fg
+        .run_message_passing_parallel(100, 0, error, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    let ranking = fg.factor_inconsistency_ranking();
+    assert_eq!(ranking[0].factor_index, 0);
+    // the divergence of a pair factor is the mutual information of spins
+    let spin_2_b = 0.1;
+    let weight = |s1: f64, s2: f64| {
+        f64::exp(coupling * s1 * s2 + first_spin_b * s1 + (second_spin_b + spin_2_b) * s2)
+    };
+    let partition_function = weight(1., 1.) + weight(1., -1.) + weight(-1., 1.) + weight(-1., -1.);
+    let p = |s1: f64, s2: f64| weight(s1, s2) / partition_function;
+    let p1 = |s1: f64| p(s1, 1.) + p(s1, -1.);
+    let p2 = |s2: f64| p(1., s2) + p(-1., s2);
+    let mutual_information: f64 = [(1., 1.), (1., -1.), (-1., 1.), (-1., -1.)]
+        .into_iter()
+        .map(|(s1, s2)| p(s1, s2) * (p(s1, s2) / (p1(s1) * p2(s2))).ln())
+        .sum();
+    assert!((ranking[0].divergence - mutual_information).abs() < 1e-8);
+    // a factor adjacent to a frozen variable and a unit factor
+    // freezing it are consistent with variables
+    for inconsistency in &ranking[1..] {
+        assert!(inconsistency.divergence.abs() < 1e-10);
+    }
+    assert!(ranking.iter().any(|x| x.var_indices == vec![2]));
+}
+
+#[test]
+fn unit_factor_test() {
+    let (field, message) = (0.7, -0.2);
+    let sigmoid = |x: f64| 1. / (1. + f64::exp(-x));
+    let factor = IsingFactor::<SumProduct>::UnitFactor(field);
+    // the factor tensor is a distribution of a spin, not its logarithm
+    let tensor = factor.factor();
+    assert_eq!(tensor.shape(), [2]);
+    assert!((tensor[[0]] - sigmoid(field)).abs() < 1e-12);
+    assert!((tensor[[1]] - sigmoid(-field)).abs() < 1e-12);
+    // a marginal uses the only incoming message for both spin states
+    let marginal = factor.marginal(&[IsingMessage(message)]);
+    assert_eq!(marginal.shape(), [2]);
+    assert!((marginal[[0]] - sigmoid(field + message)).abs() < 1e-12);
+    assert!((marginal[[1]] - sigmoid(-field - message)).abs() < 1e-12);
+}
+
+#[test]
+fn audit_test() {
+    // A triangle with a nearly hard field on the last spin
+    let mut fgb = new_ising_builder::<SumProduct>(3, 4);
+    for (i, j) in [(0, 1), (1, 2), (2, 0)] {
+        fgb.add_factor(IsingFactor::new(0.3, 0., 0.), &[i, j], &mut || {
+            IsingMessage(0.)
+        })
+        .unwrap();
+    }
+    fgb.add_factor(IsingFactor::UnitFactor(200.), &[2], &mut || {
+        IsingMessage(0.)
+    })
+    .unwrap();
+    let fg = fgb.build();
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+
+    let mut audited_fg = fg.clone();
+    let mut audit = MessageAudit::new(50., false);
+    let info = audited_fg
+        .run_message_passing_parallel_audited(
+            100,
+            0,
+            1e-10,
+            &factor_scheduler,
+            &variable_scheduler,
+            &mut audit,
+        )
+        .unwrap();
+    let sweeps_number = info.iterations_number + 1;
+    // the unit factor and the last spin send out of bounds messages at each sweep
+    assert_eq!(audit.saturating_factors(), vec![(3, sweeps_number)]);
+    assert_eq!(audit.variable_saturations, vec![0, 0, 2 * sweeps_number]);
+    assert_eq!(audit.saturations_number(), 3 * sweeps_number);
+    // checks do not change message passing
+    let mut plain_fg = fg.clone();
+    plain_fg
+        .run_message_passing_parallel(100, 0, 1e-10, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    assert_eq!(
+        plain_fg.variable_marginals(),
+        audited_fg.variable_marginals()
+    );
+    // counts are accumulated across runs
+    audited_fg
+        .run_message_passing_parallel_audited(
+            100,
+            0,
+            1e-10,
+            &factor_scheduler,
+            &variable_scheduler,
+            &mut audit,
+        )
+        .unwrap();
+    assert!(audit.factor_saturations[3] > sweeps_number);
+
+    // clamping keeps messages within bounds
+    let mut clamped_fg = fg.clone();
+    let mut audit = MessageAudit::new(50., true);
+    clamped_fg
+        .run_message_passing_parallel_audited(
+            100,
+            0,
+            1e-10,
+            &factor_scheduler,
+            &variable_scheduler,
+            &mut audit,
+        )
+        .unwrap();
+    assert!(audit.saturations_number() > 0);
+    for factor in &clamped_fg.factors {
+        assert!(factor.messages.iter().all(|m| m.0.abs() <= 50.));
+        assert!(factor.receivers.iter().all(|m| m.0.abs() <= 50.));
+    }
+}
+
+#[test]
+fn bounded_messages_test() {
+    let mut message = IsingMessage(-1e30);
+    assert!(message.saturate(1e3, true));
+    assert_eq!(message.0, -1e3);
+    assert!(!message.saturate(1e3, true));
+    let mut message = TabularMessage(array![0.5, f64::INFINITY, f64::NAN]);
+    assert!(message.saturate(10., true));
+    assert_eq!(message.0[1], 10.);
+    assert!(message.0[2].is_nan());
+    let mut message = TabularMessage(array![0.5, 0.25]);
+    assert!(!message.saturate(10., false));
+}
+
+#[test]
+fn sampling_progress_test() {
+    let side = 5;
+    let spins_number = side * side;
+    let fg = torus_fg(side, IsingFactor::new(0.3, 0.05, 0.), 42);
+    let factor_scheduler = get_standard_factor_scheduler(0.5);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    let info = fg
+        .clone()
+        .sample(
+            1000,
+            0,
+            1e-8,
+            &mut StdRng::seed_from_u64(7),
+            &factor_scheduler,
+            &variable_scheduler,
+        )
+        .unwrap();
+    let mut reports = Vec::new();
+    let mut options = MessagePassingOptions::new(&factor_scheduler, &variable_scheduler)
+        .with_threshold(1e-8)
+        .with_progress(|progress| reports.push(*progress));
+    let reported_info = fg
+        .clone()
+        .sample_with_options(&mut StdRng::seed_from_u64(7), &mut options)
+        .unwrap();
+    // reporting does not change sampling
+    assert_eq!(info.samples, reported_info.samples);
+    assert_eq!(reports.len(), spins_number);
+    let mut total_iterations_number = 0;
+    for (step, progress) in reports.iter().enumerate() {
+        assert_eq!(progress.sampled_variables_number, step + 1);
+        assert_eq!(progress.variables_number, spins_number);
+        total_iterations_number += info.iterations_per_variable[step];
+        assert_eq!(progress.total_iterations_number, total_iterations_number);
+        assert!(progress.last_discrepancy < 1e-8);
+    }
+}
@@ -0,0 +1,272 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::core::balancing::balanced_chunks;
+use crate::core::{Factor, FactorGraphBuilder};
+use crate::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+use crate::ising::{
+    new_ising_builder, random_message_initializer, IsingFactor, IsingFactorHyperParameters,
+    IsingMessage, IsingVariable, SumProduct,
+};
+use ndarray::ArrayD;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rand_distr::Uniform;
+use rayon::ThreadPoolBuilder;
+
+#[test]
+fn load_balancing_test() {
+    // chunks cover all nodes in order and only a single node may exceed the target cost
+    let mut costs: Vec<usize> = (0..1000)
+        .map(|i| if i % 97 == 0 { 500 } else { 3 })
+        .collect();
+    let total_cost: usize = costs.iter().sum();
+    let chunks = balanced_chunks(&mut costs, |cost| *cost);
+    let target_cost = total_cost / (4 * rayon::current_num_threads());
+    let mut covered = 0;
+    for chunk in &chunks {
+        let chunk_cost: usize = chunk.iter().sum();
+        assert!(chunk.len() == 1 || chunk_cost <= target_cost.max(1));
+        covered += chunk.len();
+    }
+    assert_eq!(covered, 1000);
+    // a hub connected to every spin has its edges split across workers
+    let spins_number = 3000;
+    let mut rng = StdRng::seed_from_u64(42);
+    let distr = Uniform::new(-0.2, 0.2);
+    let variables =
+        (0..spins_number).map(|_| IsingVariable::<SumProduct>::new().with_field(rng.sample(distr)));
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(7), -0.5, 0.5);
+    let mut fgb = FactorGraphBuilder::new_with_variables(variables, 2 * spins_number);
+    let mut ends = vec![0, 1];
+    fgb.add_factor(IsingFactor::new(0.05, 0., 0.), &[0, 1], &mut initializer)
+        .unwrap();
+    for spin in 2..spins_number {
+        let target = ends[rng.sample(Uniform::new(0, ends.len()))];
+        fgb.add_factor(
+            IsingFactor::new(rng.sample(distr), 0., 0.),
+            &[spin, target],
+            &mut initializer,
+        )
+        .unwrap();
+        fgb.add_factor(
+            IsingFactor::new(0.01 * rng.sample(distr), 0., 0.),
+            &[spin, 0],
+            &mut initializer,
+        )
+        .unwrap();
+        ends.extend([spin, target]);
+    }
+    let mut fg = fgb.build();
+    assert!(fg.variable_neighbors(0).len() >= spins_number - 1);
+    let mut reference_fg = fg.clone();
+    let factor_scheduler = |_| IsingFactorHyperParameters::new(1., 0.2);
+    let variable_scheduler = get_standard_variable_scheduler(0.1);
+    let info = fg
+        .run_message_passing_parallel(1000, 0, 1e-10, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    let reference_info = reference_fg
+        .run_message_passing_batched(1000, 0, 1e-10, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    assert_eq!(info.iterations_number, reference_info.iterations_number);
+    for (lhs, rhs) in fg
+        .variable_marginals()
+        .iter()
+        .zip(reference_fg.variable_marginals())
+    {
+        assert!((lhs[0] - rhs[0]).abs() < 1e-10);
+    }
+}
+
+// Number of threads of a pool that executed the last factor update
+static LAST_POOL_SIZE: AtomicUsize = AtomicUsize::new(0);
+
+// An Ising coupling recording a pool it is evaluated in
+#[derive(Debug, Clone)]
+struct RecordingCoupling(IsingFactor<SumProduct>);
+
+impl Factor for RecordingCoupling {
+    type Message = IsingMessage;
+    type Marginal = ArrayD<f64>;
+    type Parameters = IsingFactorHyperParameters;
+
+    fn from_message(message: &Self::Message) -> Self {
+        RecordingCoupling(IsingFactor::from_message(message))
+    }
+
+    fn degree(&self) -> usize {
+        self.0.degree()
+    }
+
+    fn send_messages(
+        &self,
+        src: &[Self::Message],
+        dst: &mut [Self::Message],
+        parameters: &Self::Parameters,
+    ) {
+        LAST_POOL_SIZE.store(rayon::current_num_threads(), Ordering::SeqCst);
+        self.0.send_messages(src, dst, parameters)
+    }
+
+    fn marginal(&self, messages: &[Self::Message]) -> Self::Marginal {
+        self.0.marginal(messages)
+    }
+
+    fn factor(&self) -> Self::Marginal {
+        self.0.factor()
+    }
+}
+
+#[test]
+fn custom_thread_pool_test() {
+    let spins_number = 10;
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(42), -0.5, 0.5);
+    let mut fgb =
+        FactorGraphBuilder::<RecordingCoupling, IsingVariable<SumProduct>>::new_with_variables(
+            vec![IsingVariable::new(); spins_number],
+            spins_number - 1,
+        );
+    for i in 0..(spins_number - 1) {
+        fgb.add_factor(
+            RecordingCoupling(IsingFactor::new(0.5, 0.1, 0.)),
+            &[i, i + 1],
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    let mut fg = fgb.build();
+    assert!(fg.thread_pool().is_none());
+    let pool_size = rayon::current_num_threads() + 3;
+    let pool = Arc::new(
+        ThreadPoolBuilder::new()
+            .num_threads(pool_size)
+            .build()
+            .unwrap(),
+    );
+    fg.set_thread_pool(Some(pool.clone()));
+    fg.run_message_passing_parallel(100, 0, 1e-10, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    assert_eq!(LAST_POOL_SIZE.load(Ordering::SeqCst), pool_size);
+    // clones share the pool
+    let cloned_fg = fg.clone();
+    assert!(Arc::ptr_eq(cloned_fg.thread_pool().unwrap(), &pool));
+    // the global pool is used again after resetting
+    fg.set_thread_pool(None);
+    fg.run_message_passing_parallel(100, 0, 1e-10, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    assert_eq!(
+        LAST_POOL_SIZE.load(Ordering::SeqCst),
+        rayon::current_num_threads()
+    );
+}
+
+#[test]
+fn parallel_threshold_test() {
+    let spins_number = 20;
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(42), -0.5, 0.5);
+    let mut fgb = new_ising_builder::<SumProduct>(spins_number, spins_number);
+    for i in 0..spins_number {
+        fgb.add_factor(
+            IsingFactor::new(0.3, 0.1, -0.2),
+            &[i, (i + 1) % spins_number],
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    // a small factor graph is swept serially by default
+    let mut fg = fgb.build();
+    assert!(fg.parallel_threshold() > 2 * spins_number);
+    let mut parallel_fg = fg.clone();
+    parallel_fg.set_parallel_threshold(0);
+    let info = fg
+        .run_message_passing_parallel(1000, 0, 1e-12, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    let parallel_info = parallel_fg
+        .run_message_passing_parallel(1000, 0, 1e-12, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    assert_eq!(info.iterations_number, parallel_info.iterations_number);
+    for (lhs, rhs) in fg
+        .variable_marginals()
+        .iter()
+        .zip(parallel_fg.variable_marginals())
+    {
+        assert_eq!(lhs, rhs);
+    }
+    // subgraphs of connected components inherit the threshold
+    for (_, subgraph) in parallel_fg.split_components() {
+        assert_eq!(subgraph.parallel_threshold(), 0);
+    }
+}
+
+#[test]
+fn replica_sampling_test() {
+    let replicas_number = 20000;
+    let (coupling, first_spin_b, second_spin_b) = (0.7f64, 0.3f64, -0.2f64);
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(42), -0.5, 0.5);
+    let mut fgb = new_ising_builder::<SumProduct>(2, 1);
+    fgb.add_factor(
+        IsingFactor::new(coupling, first_spin_b, second_spin_b),
+        &[0, 1],
+        &mut initializer,
+    )
+    .unwrap();
+    let mut fg = fgb.build();
+    fg.run_message_passing_parallel(100, 0, 1e-10, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    let info = fg
+        .sample_parallel(
+            replicas_number,
+            100,
+            0,
+            1e-10,
+            &mut StdRng::seed_from_u64(7),
+            &factor_scheduler,
+            &variable_scheduler,
+        )
+        .unwrap();
+    assert_eq!(fg.get_factor_degrees(), vec![2]);
+    assert_eq!(
+        info.total_iterations_number,
+        info.iterations_per_sample.iter().sum::<usize>()
+    );
+    let decimation_orders = info.decimation_orders.as_ref().unwrap();
+    assert!(decimation_orders.iter().all(|order| order == &[0, 1]));
+    let weight =
+        |s1: f64, s2: f64| f64::exp(coupling * s1 * s2 + first_spin_b * s1 + second_spin_b * s2);
+    let configs = [(1i8, 1i8), (1, -1), (-1, 1), (-1, -1)];
+    let partition_function: f64 = configs
+        .iter()
+        .map(|(s1, s2)| weight(*s1 as f64, *s2 as f64))
+        .sum();
+    for (s1, s2) in configs {
+        let exact = weight(s1 as f64, s2 as f64) / partition_function;
+        let empirical = info
+            .samples
+            .iter()
+            .filter(|sample| sample[..] == [s1, s2])
+            .count() as f64
+            / replicas_number as f64;
+        assert!((exact - empirical).abs() < 1.5e-2);
+    }
+    // samples do not depend on the number of threads
+    fg.set_thread_pool(Some(Arc::new(
+        ThreadPoolBuilder::new().num_threads(3).build().unwrap(),
+    )));
+    let pool_info = fg
+        .sample_parallel(
+            replicas_number,
+            100,
+            0,
+            1e-10,
+            &mut StdRng::seed_from_u64(7),
+            &factor_scheduler,
+            &variable_scheduler,
+        )
+        .unwrap();
+    assert_eq!(info.samples, pool_info.samples);
+}
@@ -0,0 +1,1021 @@
+use super::{config_spins, ising_fg, ising_log_weight, random_tree};
+use crate::core::{
+    ExactnessCertificate, FGError, Factor, FactorGraphBuilder, MessagePassingOptions, Variable,
+};
+use crate::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+use crate::ising::{
+    new_ising_builder, new_ising_chain_builder, random_message_initializer, IsingFactor,
+    IsingFactorHyperParameters, IsingMessage, IsingVariable, MaxProduct, SumProduct,
+};
+use crate::tabular::{uninformative_message_initializer, TabularFactor, TabularVariable};
+use ndarray::{array, ArrayD, Axis, IxDyn};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+#[test]
+fn two_spins_batch_sampling_test() {
+    let samples_number = 20000;
+    let coupling = 0.7f64;
+    let first_spin_b = 0.3f64;
+    let second_spin_b = -0.2f64;
+    let error = 1e-10f64;
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(42), -0.5, 0.5);
+    let mut fgb = new_ising_builder::<SumProduct>(2, 1);
+    fgb.add_factor(
+        IsingFactor::new(coupling, first_spin_b, second_spin_b),
+        &[0, 1],
+        &mut initializer,
+    )
+    .unwrap();
+    let mut fg = fgb.build();
+    let _ = fg
+        .run_message_passing_parallel(100, 0, error, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    let mut rng = StdRng::seed_from_u64(7);
+    let sampling_info = fg
+        .sample_n(
+            samples_number,
+            100,
+            0,
+            error,
+            &mut rng,
+            &factor_scheduler,
+            &variable_scheduler,
+        )
+        .unwrap();
+    assert_eq!(sampling_info.samples.len(), samples_number);
+    assert_eq!(sampling_info.iterations_per_sample.len(), samples_number);
+    assert_eq!(
+        sampling_info.total_iterations_number,
+        sampling_info.iterations_per_sample.iter().sum::<usize>()
+    );
+    let decimation_orders = sampling_info.decimation_orders.as_ref().unwrap();
+    assert!(decimation_orders.iter().all(|order| order == &[0, 1]));
+    assert!(sampling_info.log_weights.is_none());
+    assert_eq!(sampling_info.effective_sample_size(), samples_number as f64);
+    // the factor graph is not modified by batch sampling
+    assert_eq!(fg.get_factor_degrees(), vec![2]);
+    let weight =
+        |s1: f64, s2: f64| f64::exp(coupling * s1 * s2 + first_spin_b * s1 + second_spin_b * s2);
+    let partition_function = weight(1., 1.) + weight(1., -1.) + weight(-1., 1.) + weight(-1., -1.);
+    let sampling_info =
+        sampling_info.with_energies(|sample| -f64::ln(weight(sample[0] as f64, sample[1] as f64)));
+    for (sample, energy) in sampling_info
+        .samples
+        .iter()
+        .zip(sampling_info.energies.as_ref().unwrap())
+    {
+        let expected = -coupling * (sample[0] * sample[1]) as f64
+            - first_spin_b * sample[0] as f64
+            - second_spin_b * sample[1] as f64;
+        assert!((energy - expected).abs() < 1e-12);
+    }
+    for (s1, s2) in [(1i8, 1i8), (1, -1), (-1, 1), (-1, -1)] {
+        let exact = weight(s1 as f64, s2 as f64) / partition_function;
+        let empirical = sampling_info
+            .samples
+            .iter()
+            .filter(|sample| sample[0] == s1 && sample[1] == s2)
+            .count() as f64
+            / samples_number as f64;
+        assert!(
+            (exact - empirical).abs() < 2e-2,
+            "exact: {exact}, empirical: {empirical}"
+        );
+    }
+}
+
+// Factors whose message passing oscillates iff the first spin is fixed up.
+// `Gate` joins spins 0, 1 and 2 and sends `1 - m` to spin 2, where `m` is the message of
+// spin 1, when the message of spin 0 is positive. Together with `Copy` joining spins 1 and 2
+// it forms a loop where messages flip every iteration
+#[derive(Debug, Clone)]
+enum GatedLoop {
+    Gate,
+    Copy,
+    Unit(f64),
+}
+
+impl Factor for GatedLoop {
+    type Message = IsingMessage;
+    type Marginal = ();
+    type Parameters = ();
+
+    #[inline(always)]
+    fn from_message(message: &Self::Message) -> Self {
+        GatedLoop::Unit(message.0)
+    }
+
+    #[inline(always)]
+    fn degree(&self) -> usize {
+        match self {
+            GatedLoop::Gate => 3,
+            GatedLoop::Copy => 2,
+            GatedLoop::Unit(_) => 1,
+        }
+    }
+
+    #[inline(always)]
+    fn marginal(&self, _: &[Self::Message]) -> Self::Marginal {}
+
+    #[inline(always)]
+    fn send_messages(&self, src: &[Self::Message], dst: &mut [Self::Message], _: &()) {
+        match self {
+            GatedLoop::Gate => {
+                dst[0] = IsingMessage(0.);
+                dst[1] = IsingMessage(0.);
+                dst[2] = IsingMessage(if src[0].0 > 0. { 1. - src[1].0 } else { 0. });
+            }
+            GatedLoop::Copy => {
+                dst[0] = src[1];
+                dst[1] = src[0];
+            }
+            GatedLoop::Unit(message) => dst[0] = IsingMessage(*message),
+        }
+    }
+
+    #[inline(always)]
+    fn factor(&self) -> Self::Marginal {}
+}
+
+#[test]
+fn backtracking_test() {
+    let mut fgb = FactorGraphBuilder::<GatedLoop, IsingVariable<SumProduct>>::new_with_variables(
+        vec![IsingVariable::new(); 3],
+        2,
+    );
+    let mut initializer = || IsingMessage(0.);
+    fgb.add_factor(GatedLoop::Gate, &[0, 1, 2], &mut initializer)
+        .unwrap();
+    fgb.add_factor(GatedLoop::Copy, &[1, 2], &mut initializer)
+        .unwrap();
+    let fg = fgb.build();
+    let factor_scheduler = |_| ();
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    let mut rng = StdRng::seed_from_u64(42);
+    let mut total_backtracks_number = 0;
+    for depth in [0, 1, 2] {
+        for _ in 0..20 {
+            let mut fg = fg.clone();
+            let info = fg
+                .sample_with_backtracking(
+                    20,
+                    0,
+                    1e-10,
+                    depth,
+                    100,
+                    &mut rng,
+                    &factor_scheduler,
+                    &variable_scheduler,
+                )
+                .unwrap();
+            // only samples with the first spin down survive
+            assert_eq!(info.samples[0], -1);
+            assert_eq!(info.decimation_order, vec![0, 1, 2]);
+            assert!(info.total_iterations_number >= 20 * info.backtracks_number);
+            assert_eq!(fg.get_factor_degrees(), vec![3, 2, 1, 1, 1]);
+            total_backtracks_number += info.backtracks_number;
+        }
+    }
+    assert!(total_backtracks_number > 0);
+    // without backtracks a failure restores the factor graph
+    let mut failures_number = 0;
+    for _ in 0..20 {
+        let mut fg = fg.clone();
+        match fg.sample_with_backtracking(
+            20,
+            0,
+            1e-10,
+            1,
+            0,
+            &mut rng,
+            &factor_scheduler,
+            &variable_scheduler,
+        ) {
+            Ok(info) => assert_eq!(info.samples[0], -1),
+            Err(FGError::SamplingError {
+                variables_number, ..
+            }) => {
+                assert_eq!(variables_number, 0);
+                assert_eq!(fg.get_factor_degrees(), vec![3, 2]);
+                assert_eq!(fg.get_variable_degrees(), vec![1, 2, 2]);
+                failures_number += 1;
+            }
+            Err(err) => panic!("unexpected error {err}"),
+        }
+    }
+    assert!(failures_number > 0);
+}
+
+// Weights of a chain of 3 spins with couplings and fields
+fn weight(couplings: &[f64; 2], fields: &[f64; 3], spins: &[i8; 3]) -> f64 {
+    let log_weight = couplings[0] * (spins[0] * spins[1]) as f64
+        + couplings[1] * (spins[1] * spins[2]) as f64
+        + fields
+            .iter()
+            .zip(spins)
+            .map(|(field, spin)| field * *spin as f64)
+            .sum::<f64>();
+    log_weight.exp()
+}
+
+#[test]
+fn conditional_sampling_test() {
+    let couplings = [0.7, -0.4];
+    let fields = [0.3, -0.2, 0.5];
+    let mut rng = StdRng::seed_from_u64(42);
+    let mut fgb = new_ising_builder::<SumProduct>(3, 5);
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(7), -0.5, 0.5);
+    for (i, coupling) in couplings.iter().enumerate() {
+        fgb.add_factor(
+            IsingFactor::new(*coupling, 0., 0.),
+            &[i, i + 1],
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    for (i, field) in fields.iter().enumerate() {
+        fgb.add_factor(IsingFactor::UnitFactor(2. * field), &[i], &mut initializer)
+            .unwrap();
+    }
+    let fg = fgb.build();
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    // exact conditional probabilities of configurations of spins 0 and 2 given spin 1 is down
+    let mut exact = [0f64; 4];
+    for (index, probability) in exact.iter_mut().enumerate() {
+        let spins = [1 - 2 * (index / 2) as i8, -1, 1 - 2 * (index % 2) as i8];
+        *probability = weight(&couplings, &fields, &spins);
+    }
+    let normalization: f64 = exact.iter().sum();
+    let samples_number = 20000;
+    let mut frequencies = [0f64; 4];
+    for _ in 0..samples_number {
+        let mut fg = fg.clone();
+        let info = fg
+            .sample_conditional(
+                &[(1, -1)],
+                100,
+                0,
+                1e-10,
+                &mut rng,
+                &factor_scheduler,
+                &variable_scheduler,
+            )
+            .unwrap();
+        assert_eq!(info.samples[1], -1);
+        assert_eq!(info.clamped_variables, vec![1]);
+        assert_eq!(info.decimation_order, vec![0, 2]);
+        assert_eq!(info.iterations_per_variable[1], 0);
+        let index = 2 * ((1 - info.samples[0]) / 2) as usize + ((1 - info.samples[2]) / 2) as usize;
+        frequencies[index] += 1f64 / samples_number as f64;
+    }
+    for (e, f) in exact.iter().zip(frequencies) {
+        assert!((e / normalization - f).abs() < 2e-2);
+    }
+    // invalid assignments do not modify a factor graph
+    let mut fg = fg;
+    assert!(matches!(
+        fg.sample_conditional(
+            &[(0, 1), (5, 1)],
+            100,
+            0,
+            1e-10,
+            &mut rng,
+            &factor_scheduler,
+            &variable_scheduler,
+        ),
+        Err(FGError::OutOfRangeVariable(3, 5))
+    ));
+    assert!(matches!(
+        fg.sample_conditional(
+            &[(2, 1), (2, -1)],
+            100,
+            0,
+            1e-10,
+            &mut rng,
+            &factor_scheduler,
+            &variable_scheduler,
+        ),
+        Err(FGError::DuplicateAssignment(2))
+    ));
+    assert_eq!(fg.get_factor_degrees(), vec![2, 2, 1, 1, 1]);
+    // an empty assignment reduces to ordinary sampling
+    let info = fg
+        .sample_conditional(
+            &[],
+            100,
+            0,
+            1e-10,
+            &mut rng,
+            &factor_scheduler,
+            &variable_scheduler,
+        )
+        .unwrap();
+    assert!(info.clamped_variables.is_empty());
+    assert_eq!(info.decimation_order, vec![0, 1, 2]);
+}
+
+#[test]
+fn consensus_map_test() {
+    let mut rng = StdRng::seed_from_u64(11);
+    let factor_scheduler = get_standard_factor_scheduler(0.5);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(5), -1., 1.);
+    // a random tree, all runs find the exact MAP assignment
+    let spins_number = 8;
+    let mut fgb = new_ising_builder::<MaxProduct>(spins_number, 2 * spins_number);
+    for i in 1..spins_number {
+        let j = rng.gen_range(0..i);
+        fgb.add_factor(
+            IsingFactor::new(rng.gen_range(-1f64..1f64), 0., 0.),
+            &[j, i],
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    for i in 0..spins_number {
+        fgb.add_factor(
+            IsingFactor::UnitFactor(rng.gen_range(-1f64..1f64)),
+            &[i],
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    let fg = fgb.build();
+    let degrees = fg.get_factor_degrees();
+    let info = fg
+        .consensus_map(
+            5,
+            &mut initializer,
+            None,
+            1000,
+            0,
+            1e-10,
+            &factor_scheduler,
+            &variable_scheduler,
+        )
+        .unwrap();
+    assert_eq!(info.runs.len(), 5);
+    assert_eq!(info.failed_runs_number, 0);
+    assert!(info.frozen_variables.is_empty());
+    assert!(info.agreement.iter().all(|agreement| *agreement == 1.));
+    assert!(info.runs.iter().all(|run| *run == info.assignment));
+    // the factor graph is untouched
+    assert_eq!(fg.get_factor_degrees(), degrees);
+    // a frustrated ring, frozen variables keep their consensus values
+    let spins_number = 12;
+    let mut fgb = new_ising_builder::<MaxProduct>(spins_number, 2 * spins_number);
+    for i in 0..spins_number {
+        fgb.add_factor(
+            IsingFactor::new(rng.gen_range(-1f64..1f64), 0., 0.),
+            &[i, (i + 1) % spins_number],
+            &mut initializer,
+        )
+        .unwrap();
+        fgb.add_factor(
+            IsingFactor::UnitFactor(rng.gen_range(-0.2f64..0.2f64)),
+            &[i],
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    let fg = fgb.build();
+    let runs_number = 10;
+    let info = fg
+        .consensus_map(
+            runs_number,
+            &mut initializer,
+            Some(0.8),
+            10000,
+            0,
+            1e-10,
+            &factor_scheduler,
+            &get_standard_variable_scheduler(0.5),
+        )
+        .unwrap();
+    assert_eq!(info.runs.len() + info.failed_runs_number, runs_number);
+    assert_eq!(info.assignment.len(), spins_number);
+    for (var_index, agreement) in info.agreement.iter().enumerate() {
+        let votes = info.runs.iter().filter(|run| run[var_index] == 1).count() as f64
+            / info.runs.len() as f64;
+        assert!((agreement - votes.max(1. - votes)).abs() < 1e-12);
+        assert_eq!(
+            info.frozen_variables.contains(&var_index),
+            *agreement >= 0.8
+        );
+    }
+    for var_index in &info.frozen_variables {
+        let majority = info
+            .runs
+            .iter()
+            .filter(|run| run[*var_index] == info.assignment[*var_index])
+            .count();
+        assert!(majority as f64 >= 0.8 * info.runs.len() as f64);
+    }
+}
+
+#[test]
+fn marginal_gap_ranking_test() {
+    let mut rng = StdRng::seed_from_u64(42);
+    let spins_number = 12;
+    let (mut couplings, fields) = random_tree(&mut rng, spins_number);
+    couplings.push((0, spins_number - 1, 0.4));
+    let mut fg = ising_fg(&couplings, &fields, 7);
+    fg.run_message_passing_parallel(
+        1000,
+        0,
+        1e-12,
+        &get_standard_factor_scheduler(0.),
+        &get_standard_variable_scheduler(0.),
+    )
+    .unwrap();
+    let marginals = fg.variable_marginals();
+    let ranking = fg.marginal_gap_ranking();
+    assert_eq!(ranking.len(), spins_number);
+    for gap in &ranking {
+        let marginal = &marginals[gap.var_index];
+        assert!((gap.gap - (marginal[0] - marginal[1]).abs()).abs() < 1e-12);
+    }
+    assert!(ranking.windows(2).all(|pair| pair[0].gap >= pair[1].gap));
+    // a tabular variable reports the gap between the two largest probabilities
+    let mut fgb = FactorGraphBuilder::new_with_variables(vec![TabularVariable::new(3)], 1);
+    fgb.add_factor(
+        TabularFactor::new(array![0.2, 0.5, 0.3].into_dyn()),
+        &[0],
+        &mut uninformative_message_initializer(),
+    )
+    .unwrap();
+    let mut fg = fgb.build();
+    fg.run_message_passing_parallel(10, 0, 1e-12, &|_| 0., &|_| 0.)
+        .unwrap();
+    let ranking = fg.marginal_gap_ranking();
+    assert_eq!(ranking[0].var_index, 0);
+    assert!((ranking[0].gap - 0.2).abs() < 1e-12);
+    assert_eq!(
+        TabularVariable::new(2)
+            .marginal_gap(&[])
+            .map(|gap| gap.abs() < 1e-12),
+        Some(true)
+    );
+}
+
+#[test]
+fn decimate_most_confident_test() {
+    let mut rng = StdRng::seed_from_u64(3);
+    let spins_number = 10;
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    for _ in 0..5 {
+        let (couplings, fields) = random_tree(&mut rng, spins_number);
+        let mut initializer = random_message_initializer(StdRng::seed_from_u64(7), -0.5, 0.5);
+        let mut fgb = new_ising_builder::<MaxProduct>(spins_number, 2 * spins_number);
+        for (i, j, coupling) in &couplings {
+            fgb.add_factor(
+                IsingFactor::new(*coupling, 0., 0.),
+                &[*i, *j],
+                &mut initializer,
+            )
+            .unwrap();
+        }
+        for (i, field) in fields.iter().enumerate() {
+            fgb.add_factor(IsingFactor::UnitFactor(2. * field), &[i], &mut initializer)
+                .unwrap();
+        }
+        let mut fg = fgb.build();
+        fg.run_message_passing_parallel(1000, 0, 1e-12, &factor_scheduler, &variable_scheduler)
+            .unwrap();
+        let first = fg.marginal_gap_ranking()[0].var_index;
+        let info = fg
+            .decimate_most_confident(1000, 0, 1e-12, &factor_scheduler, &variable_scheduler)
+            .unwrap();
+        assert_eq!(info.decimation_order[0], first);
+        let mut order = info.decimation_order.clone();
+        order.sort();
+        assert_eq!(order, (0..spins_number).collect::<Vec<_>>());
+        // max-product decimation on a tree finds the exact MAP assignment
+        let max_log_weight = (0..(1usize << spins_number))
+            .map(|config| {
+                ising_log_weight(&couplings, &fields, &config_spins(config, spins_number))
+            })
+            .fold(f64::NEG_INFINITY, f64::max);
+        let spins: Vec<f64> = info.samples.iter().map(|spin| *spin as f64).collect();
+        assert!((ising_log_weight(&couplings, &fields, &spins) - max_log_weight).abs() < 1e-10);
+    }
+}
+
+#[test]
+fn two_rings_rao_blackwellized_sampling_test() {
+    // two rings of 4 spins sharing the spin 0
+    let edges = [
+        [0, 1],
+        [1, 2],
+        [2, 3],
+        [3, 0],
+        [0, 4],
+        [4, 5],
+        [5, 6],
+        [6, 0],
+    ];
+    let couplings = [0.9f64, -0.7, 0.8, 0.6, -0.5, 0.9, 0.7, 0.8];
+    let fields = [0.3f64, -0.2, 0.1, 0.4, -0.3, 0.2, 0.1];
+    let spins_number = fields.len();
+    let samples_number = 10;
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(42), -0.5, 0.5);
+    let mut fgb = new_ising_builder::<SumProduct>(spins_number, edges.len() + spins_number);
+    for (edge, coupling) in edges.iter().zip(couplings) {
+        fgb.add_factor(IsingFactor::new(coupling, 0., 0.), edge, &mut initializer)
+            .unwrap();
+    }
+    for (spin, field) in fields.iter().enumerate() {
+        fgb.add_factor(
+            IsingFactor::UnitFactor(2. * field),
+            &[spin],
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    let mut fg = fgb.build();
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    fg.run_message_passing_parallel(1000, 0, 1e-12, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    let cutset = fg.loop_cutset();
+    assert_eq!(cutset, vec![0]);
+    // exact magnetizations conditioned on the spin 0 by enumeration
+    let exact_magnetizations = |s0: f64| {
+        let mut partition_function = 0f64;
+        let mut magnetizations = vec![0f64; spins_number];
+        for n in 0..(1 << (spins_number - 1)) {
+            let s: Vec<f64> = std::iter::once(s0)
+                .chain((1..spins_number).map(|i| 1. - 2. * ((n >> (i - 1)) & 1) as f64))
+                .collect();
+            let log_weight = edges
+                .iter()
+                .zip(couplings)
+                .map(|([i, j], c)| c * s[*i] * s[*j])
+                .sum::<f64>()
+                + fields.iter().zip(&s).map(|(h, x)| h * x).sum::<f64>();
+            let weight = log_weight.exp();
+            partition_function += weight;
+            for (m, x) in magnetizations.iter_mut().zip(&s) {
+                *m += weight * x;
+            }
+        }
+        magnetizations
+            .into_iter()
+            .map(|m| m / partition_function)
+            .collect::<Vec<_>>()
+    };
+    let mut rng = StdRng::seed_from_u64(7);
+    for _ in 0..samples_number {
+        let info = fg
+            .clone()
+            .sample_rao_blackwellized(
+                &cutset,
+                1000,
+                0,
+                1e-12,
+                &mut rng,
+                &factor_scheduler,
+                &variable_scheduler,
+            )
+            .unwrap();
+        assert_eq!(info.certificate, Some(ExactnessCertificate::Tree));
+        assert!(info.samples[1..].iter().all(|s| s.is_none()));
+        let exact = exact_magnetizations(info.samples[0].unwrap() as f64);
+        for (exact, marginal) in exact.iter().zip(&info.marginals) {
+            let magnetization = marginal[0] - marginal[1];
+            assert!(
+                (exact - magnetization).abs() < 1e-8,
+                "exact: {exact}, conditional: {magnetization}"
+            );
+        }
+    }
+}
+
+#[test]
+fn replica_coupling_test() {
+    let spins_number = 5;
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(42), -0.5, 0.5);
+    let fg = new_ising_chain_builder::<SumProduct>(
+        spins_number,
+        0.7,
+        0.1,
+        [0.3, -0.2],
+        &mut initializer,
+    )
+    .unwrap()
+    .build();
+    let mut doubled = fg.coupled_ising_replicas(2., 1, &mut initializer).unwrap();
+    let factors_number = fg.get_factor_degrees().len();
+    assert_eq!(doubled.get_variable_degrees().len(), 2 * spins_number);
+    assert_eq!(
+        doubled.get_factor_degrees().len(),
+        2 * factors_number + spins_number
+    );
+    for (i, degree) in doubled.get_variable_degrees().into_iter().enumerate() {
+        let original_degree = fg.get_variable_degrees()[i % spins_number];
+        assert_eq!(degree, original_degree + 1);
+    }
+    for i in 0..spins_number {
+        let coupling_factor = 2 * factors_number + i;
+        assert_eq!(
+            doubled.factor_neighbors(coupling_factor),
+            &[i, i + spins_number]
+        );
+    }
+
+    // Replicas are independent at the zero inverse temperature of coupling factors
+    let decoupled_scheduler =
+        |_: usize| IsingFactorHyperParameters::new(1., 0.).with_group_betas(vec![0.]);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    let mut original = fg.clone();
+    original
+        .run_message_passing_parallel(1000, 0, 1e-12, &decoupled_scheduler, &variable_scheduler)
+        .unwrap();
+    doubled
+        .run_message_passing_parallel(1000, 0, 1e-12, &decoupled_scheduler, &variable_scheduler)
+        .unwrap();
+    let original_marginals = original.variable_marginals();
+    let doubled_marginals = doubled.variable_marginals();
+    for (i, marginal) in doubled_marginals.iter().enumerate() {
+        let diff = (marginal - &original_marginals[i % spins_number])
+            .iter()
+            .map(|x| x.abs())
+            .fold(0f64, f64::max);
+        assert!(diff < 1e-8);
+    }
+}
+
+#[test]
+fn replica_coupling_strength_test() {
+    // Decoupled spins in a field, thus each pair of copies is solved exactly
+    let field = 0.3;
+    let variables = (0..3).map(|_| IsingVariable::<SumProduct>::new().with_field(field));
+    let fg =
+        FactorGraphBuilder::<IsingFactor<SumProduct>, _>::new_with_variables(variables, 0).build();
+    let mut doubled = fg
+        .coupled_ising_replicas(0.5, 2, &mut || IsingMessage(0.))
+        .unwrap();
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    for epsilon in [0., 0.25, 1.] {
+        let scheduler = |_: usize| {
+            IsingFactorHyperParameters::new(1., 0.).with_group_betas(vec![1., 2. * epsilon])
+        };
+        doubled
+            .run_message_passing_parallel(1000, 0, 1e-12, &scheduler, &variable_scheduler)
+            .unwrap();
+        let weights = [
+            f64::exp(2. * field + epsilon),
+            f64::exp(-2. * field + epsilon),
+            2. * f64::exp(-epsilon),
+        ];
+        let exact_magnetization = (weights[0] - weights[1]) / weights.iter().sum::<f64>();
+        for marginal in doubled.variable_marginals() {
+            assert!((marginal[0] - marginal[1] - exact_magnetization).abs() < 1e-8);
+        }
+    }
+
+    // A coupling factor must be attached to two variables
+    assert!(matches!(
+        fg.coupled_replicas(|_| IsingFactor::UnitFactor(0.), &mut || IsingMessage(0.)),
+        Err(FGError::DegreeError(..))
+    ));
+}
+
+#[test]
+fn empirical_marginals_test() {
+    let mut rng = StdRng::seed_from_u64(42);
+    let cardinalities = [2, 3, 2];
+    let mut initializer = uninformative_message_initializer();
+    let variables = cardinalities.iter().map(|c| TabularVariable::new(*c));
+    let mut fgb = FactorGraphBuilder::new_with_variables(variables, 3);
+    for (i, j) in [(0, 1), (1, 2)] {
+        let table = ArrayD::from_shape_fn(IxDyn(&[cardinalities[i], cardinalities[j]]), |_| {
+            rng.gen_range(0.1..1.)
+        });
+        fgb.add_factor(TabularFactor::new(table), &[i, j], &mut initializer)
+            .unwrap();
+    }
+    let table = ArrayD::from_shape_fn(IxDyn(&[3]), |_| rng.gen_range(0.1..1.));
+    fgb.add_factor(TabularFactor::new(table), &[1], &mut initializer)
+        .unwrap();
+    let mut fg = fgb.build();
+    fg.run_message_passing_parallel(100, 0, 1e-12, &|_| 0., &|_| 0.)
+        .unwrap();
+    // message passing is exact on a tree, thus samples agree with its marginals
+    let samples = fg
+        .sample_n(20000, 100, 0, 1e-12, &mut rng, &|_| 0., &|_| 0.)
+        .unwrap()
+        .samples;
+    let errors = fg.marginal_errors(&samples).unwrap();
+    assert_eq!(errors.len(), 3);
+    for (var_index, error) in errors.iter().enumerate() {
+        assert_eq!(error.var_index, var_index);
+        assert!(error.max_error <= 2. * error.total_variation + 1e-12);
+        assert!(error.total_variation < 2e-2);
+    }
+    let empirical = fg.empirical_marginals(&samples).unwrap();
+    let pair_marginals = fg
+        .empirical_pair_marginals(&samples, &[(0, 1), (2, 1)])
+        .unwrap();
+    let factor_marginals = fg.factor_marginals();
+    for (empirical, exact) in pair_marginals[0].iter().zip(&factor_marginals[0]) {
+        assert!((empirical - exact).abs() < 2e-2);
+    }
+    assert_eq!(pair_marginals[1].shape(), &[2, 3]);
+    for (empirical, exact) in pair_marginals[1].iter().zip(&factor_marginals[1].t()) {
+        assert!((empirical - exact).abs() < 2e-2);
+    }
+    // pairwise marginals are consistent with single-variable ones
+    for (lhs, rhs) in pair_marginals[0]
+        .sum_axis(Axis(0))
+        .iter()
+        .zip(&empirical[1])
+    {
+        assert!((lhs - rhs).abs() < 1e-12);
+    }
+    // broken samples are rejected
+    assert!(matches!(
+        fg.empirical_marginals(&[vec![0, 3, 1]]),
+        Err(FGError::InvalidSample(1))
+    ));
+    assert!(matches!(
+        fg.marginal_errors(&[vec![0, 1]]),
+        Err(FGError::ConfigurationSizeError(2, 3))
+    ));
+    assert!(matches!(
+        fg.empirical_pair_marginals(&samples, &[(0, 5)]),
+        Err(FGError::OutOfRangeVariable(3, 5))
+    ));
+}
+
+#[test]
+fn pinned_components_sampling_test() {
+    let samples_number = 2000;
+    let chain_length = 6;
+    let field = 0.3f64;
+    let error = 1e-10f64;
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(42), -0.5, 0.5);
+    // a rigid chain with a field on the first spin and a weakly coupled pair
+    let mut fgb = new_ising_builder::<SumProduct>(chain_length + 2, chain_length);
+    fgb.add_factor(IsingFactor::new(30., field, 0.), &[0, 1], &mut initializer)
+        .unwrap();
+    for i in 1..(chain_length - 1) {
+        fgb.add_factor(IsingFactor::new(30., 0., 0.), &[i, i + 1], &mut initializer)
+            .unwrap();
+    }
+    fgb.add_factor(
+        IsingFactor::new(0.2, 0.1, -0.1),
+        &[chain_length, chain_length + 1],
+        &mut initializer,
+    )
+    .unwrap();
+    let mut fg = fgb.build();
+    let _ = fg
+        .run_message_passing_parallel(100, 0, error, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    let mut rng = StdRng::seed_from_u64(7);
+    let mut options = MessagePassingOptions::new(&factor_scheduler, &variable_scheduler)
+        .with_max_iterations_number(100)
+        .with_threshold(error)
+        .with_pinning_tolerance(1e-10);
+    let mut up_count = 0;
+    for _ in 0..samples_number {
+        let mut sample_fg = fg.clone();
+        let info = sample_fg
+            .sample_with_options(&mut rng, &mut options)
+            .unwrap();
+        // the whole chain is fixed after sampling its first spin,
+        // while the weakly coupled pair is never pinned
+        assert_eq!(info.pinned_variables_number, chain_length - 1);
+        assert!(info.iterations_per_variable[1..chain_length]
+            .iter()
+            .all(|n| *n == 0));
+        assert!(info.samples[..chain_length]
+            .iter()
+            .all(|s| *s == info.samples[0]));
+        assert_eq!(sample_fg.get_variable_degrees()[chain_length - 1], 2);
+        if info.samples[0] == 1 {
+            up_count += 1;
+        }
+    }
+    let exact = f64::exp(field) / (f64::exp(field) + f64::exp(-field));
+    let empirical = up_count as f64 / samples_number as f64;
+    assert!(
+        (exact - empirical).abs() < 5e-2,
+        "exact: {exact}, empirical: {empirical}"
+    );
+    // zero tolerance coincides with plain sampling
+    let info = fg
+        .clone()
+        .sample(
+            100,
+            0,
+            error,
+            &mut rng,
+            &factor_scheduler,
+            &variable_scheduler,
+        )
+        .unwrap();
+    assert_eq!(info.pinned_variables_number, 0);
+    assert_eq!(info.iterations_per_variable.len(), chain_length + 2);
+}
+
+#[test]
+fn soft_pinning_test() {
+    let error = 1e-10f64;
+    let probability = 0.7f64;
+    let (coupling, field) = (0.4f64, -0.2f64);
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(42), -0.5, 0.5);
+    let mut fgb = new_ising_builder::<SumProduct>(2, 2);
+    fgb.add_factor(
+        IsingFactor::new(coupling, 0., field),
+        &[0, 1],
+        &mut initializer,
+    )
+    .unwrap();
+    let mut fg = fgb.build();
+    fg.soft_freeze_variable(&1, 0, probability).unwrap();
+    fg.run_message_passing_parallel(100, 0, error, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    // exact marginal of the second spin by enumeration
+    let weight = |s0: f64, s1: f64| {
+        let prior = if s0 > 0. {
+            probability
+        } else {
+            1. - probability
+        };
+        prior * f64::exp(coupling * s0 * s1 + field * s1)
+    };
+    let p_up = (weight(1., 1.) + weight(-1., 1.))
+        / [(1., 1.), (1., -1.), (-1., 1.), (-1., -1.)]
+            .iter()
+            .map(|(s0, s1)| weight(*s0, *s1))
+            .sum::<f64>();
+    assert!((fg.variable_marginals()[1][0] - p_up).abs() < 1e-8);
+    // finite pinning keeps messages finite
+    assert!(fg.variable_marginals()[0][1] > 0.);
+    assert!(matches!(
+        fg.soft_freeze_variable(&1, 0, 0.),
+        Err(FGError::SoftPinningError(0, _))
+    ));
+    assert!(matches!(
+        fg.soft_freeze_variable(&1, 2, 0.5),
+        Err(FGError::OutOfRangeVariable(2, 2))
+    ));
+    assert_eq!(fg.get_factor_degrees(), vec![2, 1]);
+    // tabular variables distribute the rest of the probability uniformly
+    let fgb =
+        FactorGraphBuilder::<TabularFactor, _>::new_with_variables([TabularVariable::new(3)], 1);
+    let mut fg = fgb.build();
+    fg.soft_freeze_variable(&2, 0, 0.6).unwrap();
+    assert!(fg.soft_freeze_variable(&3, 0, 0.6).is_err());
+    fg.run_message_passing_parallel(10, 0, error, &|_| 0., &|_| 0.)
+        .unwrap();
+    let marginal = &fg.variable_marginals()[0];
+    for (found, exact) in marginal.iter().zip([0.2, 0.2, 0.6]) {
+        assert!((found - exact).abs() < 1e-10);
+    }
+}
+
+#[test]
+fn marginal_evidence_test() {
+    let error = 1e-10f64;
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(42), -0.5, 0.5);
+    // a single target per tree is matched exactly after message passing
+    let mut fgb = new_ising_builder::<SumProduct>(5, 4);
+    for i in 0..4 {
+        fgb.add_factor(
+            IsingFactor::new(0.6, 0.1, -0.2),
+            &[i, i + 1],
+            &mut initializer,
+        )
+        .unwrap();
+    }
+    let mut fg = fgb.build();
+    fg.run_message_passing_parallel(100, 0, error, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    fg.add_marginal_evidence(&[(2, array![0.85, 0.15])])
+        .unwrap();
+    assert!((fg.variable_marginals()[2][0] - 0.85).abs() < 1e-10);
+    fg.run_message_passing_parallel(100, 0, error, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    assert!((fg.variable_marginals()[2][0] - 0.85).abs() < 1e-10);
+    // invalid targets do not modify a factor graph
+    assert!(matches!(
+        fg.add_marginal_evidence(&[(0, array![0.5, 0.5]), (1, array![0.5, 0.2, 0.3])]),
+        Err(FGError::MarginalEvidenceError(1))
+    ));
+    assert!(matches!(
+        fg.add_marginal_evidence(&[(5, array![0.5, 0.5])]),
+        Err(FGError::OutOfRangeVariable(5, 5))
+    ));
+    assert_eq!(fg.get_factor_degrees(), vec![2, 2, 2, 2, 1]);
+    // a state forbidden by a factor can not be reached by tabular variables
+    let mut fgb = FactorGraphBuilder::<TabularFactor, _>::new_with_variables(
+        [TabularVariable::new(3), TabularVariable::new(2)],
+        2,
+    );
+    fgb.add_factor(
+        TabularFactor::new(array![[1., 2.], [0.5, 1.], [0., 0.]].into_dyn()),
+        &[0, 1],
+        &mut uninformative_message_initializer(),
+    )
+    .unwrap();
+    let mut fg = fgb.build();
+    fg.run_message_passing_parallel(10, 0, error, &|_| 0., &|_| 0.)
+        .unwrap();
+    assert!(fg
+        .add_marginal_evidence(&[(0, array![0.3, 0.3, 0.4])])
+        .is_err());
+    fg.add_marginal_evidence(&[(0, array![0.3, 0.7, 0.])])
+        .unwrap();
+    fg.run_message_passing_parallel(10, 0, error, &|_| 0., &|_| 0.)
+        .unwrap();
+    for (found, exact) in fg.variable_marginals()[0].iter().zip([0.3, 0.7, 0.]) {
+        assert!((found - exact).abs() < 1e-10);
+    }
+}
+
+#[test]
+fn contradictory_evidence_test() {
+    let error = 1e-10f64;
+    // a chain of equalities with ends frozen to different states
+    let mut fgb = FactorGraphBuilder::<TabularFactor, TabularVariable>::new_with_variables(
+        vec![TabularVariable::new(2); 3],
+        2,
+    );
+    let equality = TabularFactor::new(array![[1., 0.], [0., 1.]].into_dyn());
+    let mut initializer = uninformative_message_initializer();
+    fgb.add_factor(equality.clone(), &[0, 1], &mut initializer)
+        .unwrap();
+    fgb.add_factor(equality, &[1, 2], &mut initializer).unwrap();
+    let mut fg = fgb.build();
+    fg.freeze_variable(&0, 0).unwrap();
+    let mut consistent_fg = fg.clone();
+    consistent_fg.freeze_variable(&0, 2).unwrap();
+    consistent_fg
+        .run_message_passing_parallel(100, 0, error, &|_| 0., &|_| 0.)
+        .unwrap();
+    assert!(consistent_fg.contradictory_factors().is_empty());
+    fg.freeze_variable(&1, 2).unwrap();
+    // messages stay finite, thus contradictions are found only by an explicit check
+    fg.run_message_passing_parallel(100, 0, error, &|_| 0., &|_| 0.)
+        .unwrap();
+    assert_eq!(fg.contradictory_factors(), vec![0, 1, 2, 3]);
+    // a spin frozen to both values
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    let mut fgb = new_ising_builder::<SumProduct>(3, 2);
+    let mut initializer = random_message_initializer(StdRng::seed_from_u64(42), -0.5, 0.5);
+    fgb.add_factor(IsingFactor::new(0.5, 0.1, 0.), &[0, 1], &mut initializer)
+        .unwrap();
+    fgb.add_factor(IsingFactor::new(-0.5, 0., 0.), &[1, 2], &mut initializer)
+        .unwrap();
+    let mut fg = fgb.build();
+    fg.freeze_variable(&1, 1).unwrap();
+    fg.run_message_passing_parallel(100, 0, error, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    assert!(fg.contradictory_factors().is_empty());
+    fg.freeze_variable(&-1, 1).unwrap();
+    let mut batched_fg = fg.clone();
+    fg.run_message_passing_parallel(100, 0, error, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    batched_fg
+        .run_message_passing_batched(100, 0, error, &factor_scheduler, &variable_scheduler)
+        .unwrap();
+    assert_eq!(fg.contradictory_factors(), vec![2, 3]);
+    assert_eq!(batched_fg.contradictory_factors(), vec![2, 3]);
+    // diverged messages are checked for contradictions during message passing,
+    // the coupling receives undefined messages and is reported as well
+    let mut fgb = new_ising_builder::<SumProduct>(2, 3);
+    fgb.add_factor(IsingFactor::new(0.5, 0., 0.), &[0, 1], &mut initializer)
+        .unwrap();
+    fgb.add_factor(
+        IsingFactor::UnitFactor(f64::INFINITY),
+        &[1],
+        &mut initializer,
+    )
+    .unwrap();
+    fgb.add_factor(
+        IsingFactor::UnitFactor(f64::NEG_INFINITY),
+        &[1],
+        &mut initializer,
+    )
+    .unwrap();
+    let mut fg = fgb.build();
+    let result =
+        fg.run_message_passing_parallel(100, 0, error, &factor_scheduler, &variable_scheduler);
+    assert!(
+        matches!(&result, Err(FGError::ContradictionError(fac_indices)) if *fac_indices == vec![0, 1, 2])
+    );
+}
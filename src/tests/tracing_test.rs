@@ -0,0 +1,136 @@
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::ising::schedulers::{get_standard_factor_scheduler, get_standard_variable_scheduler};
+use crate::ising::{new_ising_chain_builder, IsingFactor, IsingMessage, SumProduct};
+use rand::{rngs::StdRng, SeedableRng};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+// A message of an event and its discrepancy if present
+type RecordedEvent = (String, Option<f64>);
+
+// Records names of spans and messages of events
+#[derive(Default)]
+struct Recorder {
+    spans: Arc<Mutex<Vec<String>>>,
+    events: Arc<Mutex<Vec<RecordedEvent>>>,
+    next_id: AtomicU64,
+}
+
+#[derive(Default)]
+struct EventVisitor {
+    message: String,
+    discrepancy: Option<f64>,
+}
+
+impl Visit for EventVisitor {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        if field.name() == "discrepancy" {
+            self.discrepancy = Some(value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        }
+    }
+}
+
+impl Subscriber for Recorder {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        self.spans
+            .lock()
+            .unwrap()
+            .push(span.metadata().name().to_string());
+        Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed) + 1)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut visitor = EventVisitor::default();
+        event.record(&mut visitor);
+        self.events
+            .lock()
+            .unwrap()
+            .push((visitor.message, visitor.discrepancy));
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+#[test]
+fn tracing_test() {
+    let recorder = Recorder::default();
+    let spans = recorder.spans.clone();
+    let events = recorder.events.clone();
+    let mut fg =
+        new_ising_chain_builder::<SumProduct>(5, 0.5, 0.1, [0., 0.], &mut || IsingMessage(0.))
+            .unwrap()
+            .build();
+    let factor_scheduler = get_standard_factor_scheduler(0.);
+    let variable_scheduler = get_standard_variable_scheduler(0.);
+    let info = tracing::subscriber::with_default(recorder, || {
+        fg.run_message_passing_parallel(100, 0, 1e-10, &factor_scheduler, &variable_scheduler)
+            .unwrap()
+    });
+    assert_eq!(*spans.lock().unwrap(), vec!["message_passing".to_string()]);
+    let events = events.lock().unwrap().clone();
+    let iterations: Vec<_> = events
+        .iter()
+        .filter(|(message, _)| message == "iteration")
+        .collect();
+    assert_eq!(iterations.len(), info.iterations_number + 1);
+    for ((_, discrepancy), expected) in iterations.iter().zip(&info.discrepancy_dynamics) {
+        assert_eq!(*discrepancy, Some(*expected));
+    }
+    assert_eq!(events.last().unwrap().0, "converged");
+
+    // failed runs are reported
+    let recorder = Recorder::default();
+    let events = recorder.events.clone();
+    fg.replace_factor(0, IsingFactor::new(2., 0., 0.)).unwrap();
+    tracing::subscriber::with_default(recorder, || {
+        assert!(fg
+            .run_message_passing_parallel(1, 0, 1e-10, &factor_scheduler, &variable_scheduler)
+            .is_err());
+    });
+    assert_eq!(events.lock().unwrap().last().unwrap().0, "not converged");
+
+    // sampling progress
+    let recorder = Recorder::default();
+    let spans = recorder.spans.clone();
+    let events = recorder.events.clone();
+    tracing::subscriber::with_default(recorder, || {
+        fg.sample_n(
+            3,
+            100,
+            0,
+            1e-10,
+            &mut StdRng::seed_from_u64(42),
+            &factor_scheduler,
+            &variable_scheduler,
+        )
+        .unwrap();
+    });
+    let spans = spans.lock().unwrap();
+    assert_eq!(spans[0], "sampling_batch");
+    assert_eq!(spans.iter().filter(|name| *name == "sampling").count(), 3);
+    let events = events.lock().unwrap();
+    let count = |name: &str| events.iter().filter(|(message, _)| message == name).count();
+    assert_eq!(count("sample drawn"), 3);
+    assert_eq!(count("sampled"), 3);
+    assert_eq!(count("variable sampled"), 3 * 5);
+}